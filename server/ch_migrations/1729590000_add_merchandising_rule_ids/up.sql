@@ -0,0 +1 @@
+ALTER TABLE search_queries ADD COLUMN IF NOT EXISTS merchandising_rule_ids Array(String) DEFAULT [];
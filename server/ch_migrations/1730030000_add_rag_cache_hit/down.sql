@@ -0,0 +1 @@
+ALTER TABLE rag_queries DROP COLUMN IF EXISTS cache_hit;
@@ -0,0 +1 @@
+ALTER TABLE rag_queries ADD COLUMN IF NOT EXISTS cache_hit Bool DEFAULT false;
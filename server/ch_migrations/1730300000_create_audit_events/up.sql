@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS audit_events (
+    id UUID,
+    organization_id UUID,
+    actor_user_id UUID,
+    auth_method String,
+    api_key_id UUID,
+    ip_address String,
+    action String,
+    target_type String,
+    target_id String,
+    diff String,
+    created_at DateTime
+) ENGINE = MergeTree()
+ORDER BY (organization_id, created_at, action, id)
+PARTITION BY
+    (toYYYYMM(created_at),
+    organization_id);
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS dataset_synonyms (
+    id UUID PRIMARY KEY,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    synonyms TEXT[] NOT NULL,
+    input TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS dataset_synonyms_dataset_id_idx ON dataset_synonyms(dataset_id);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS merchandising_rules (
+    id UUID PRIMARY KEY,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    query_match TEXT NOT NULL,
+    query_pattern TEXT,
+    action JSONB NOT NULL,
+    starts_at TIMESTAMP,
+    ends_at TIMESTAMP,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS merchandising_rules_dataset_id_idx ON merchandising_rules(dataset_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS chunk_metadata_versions_no_update ON chunk_metadata_versions;
+DROP FUNCTION IF EXISTS chunk_metadata_versions_append_only();
+DROP TABLE IF EXISTS chunk_metadata_versions;
@@ -0,0 +1,33 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS chunk_metadata_versions (
+    id UUID PRIMARY KEY,
+    chunk_id UUID NOT NULL,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    action TEXT NOT NULL,
+    link TEXT,
+    chunk_html TEXT,
+    metadata JSONB,
+    tracking_id TEXT,
+    time_stamp TIMESTAMP,
+    location JSONB,
+    image_urls TEXT[],
+    tag_set TEXT[],
+    num_value FLOAT8,
+    weight FLOAT8 NOT NULL,
+    actor_user_id UUID,
+    actor_api_key_id UUID,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS chunk_metadata_versions_chunk_id_idx ON chunk_metadata_versions(dataset_id, chunk_id, created_at);
+
+CREATE OR REPLACE FUNCTION chunk_metadata_versions_append_only()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'chunk_metadata_versions is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER chunk_metadata_versions_no_update
+BEFORE UPDATE ON chunk_metadata_versions
+FOR EACH ROW EXECUTE FUNCTION chunk_metadata_versions_append_only();
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS citations JSONB;
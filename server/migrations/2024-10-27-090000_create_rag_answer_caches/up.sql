@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS rag_answer_caches (
+    id UUID PRIMARY KEY,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    query TEXT NOT NULL,
+    query_embedding REAL[] NOT NULL,
+    filters_hash TEXT NOT NULL,
+    completion TEXT NOT NULL,
+    chunk_ids UUID[] NOT NULL,
+    hit_count INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_hit_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_rag_answer_caches_dataset_id_filters_hash ON rag_answer_caches (dataset_id, filters_hash, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_rag_answer_caches_chunk_ids ON rag_answer_caches USING GIN (chunk_ids);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS crawl_pages;
+DROP TABLE IF EXISTS crawl_runs;
@@ -0,0 +1,33 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS crawl_runs (
+    id UUID PRIMARY KEY,
+    crawl_request_id UUID NOT NULL,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    scrape_id UUID NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    pages_ingested INT4 NOT NULL DEFAULT 0,
+    pages_unchanged INT4 NOT NULL DEFAULT 0,
+    pages_skipped INT4 NOT NULL DEFAULT 0,
+    pages_failed INT4 NOT NULL DEFAULT 0,
+    chunks_created INT4 NOT NULL DEFAULT 0,
+    chunks_deleted INT4 NOT NULL DEFAULT 0,
+    started_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS crawl_runs_dataset_id_idx ON crawl_runs(dataset_id, started_at);
+
+CREATE TABLE IF NOT EXISTS crawl_pages (
+    id UUID PRIMARY KEY,
+    run_id UUID NOT NULL REFERENCES crawl_runs(id) ON DELETE CASCADE,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    chunks_created INT4 NOT NULL DEFAULT 0,
+    content_hash TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS crawl_pages_run_id_idx ON crawl_pages(run_id, status);
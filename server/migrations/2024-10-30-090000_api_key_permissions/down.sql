@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS user_api_key_previous_blake3_hash_idx;
+
+ALTER TABLE user_api_key
+    DROP COLUMN IF EXISTS permissions,
+    DROP COLUMN IF EXISTS expires_at,
+    DROP COLUMN IF EXISTS allowed_cidrs,
+    DROP COLUMN IF EXISTS key_prefix,
+    DROP COLUMN IF EXISTS last_used_at,
+    DROP COLUMN IF EXISTS previous_blake3_hash,
+    DROP COLUMN IF EXISTS previous_hash_expires_at;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE user_api_key
+    ADD COLUMN IF NOT EXISTS permissions TEXT[],
+    ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS allowed_cidrs TEXT[],
+    ADD COLUMN IF NOT EXISTS key_prefix TEXT,
+    ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS previous_blake3_hash TEXT,
+    ADD COLUMN IF NOT EXISTS previous_hash_expires_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS user_api_key_previous_blake3_hash_idx ON user_api_key(previous_blake3_hash);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE user_api_key ADD COLUMN IF NOT EXISTS rate_limits JSONB;
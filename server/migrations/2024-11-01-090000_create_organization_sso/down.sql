@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS organization_domains;
+DROP TABLE IF EXISTS organization_sso_configs;
+ALTER TABLE organizations DROP COLUMN IF EXISTS sso_only;
@@ -0,0 +1,28 @@
+-- Your SQL goes here
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS sso_only BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS organization_sso_configs (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL UNIQUE REFERENCES organizations(id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    oidc_issuer_url TEXT,
+    oidc_client_id TEXT,
+    oidc_client_secret TEXT,
+    saml_idp_metadata TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS organization_domains (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    domain TEXT NOT NULL,
+    verification_token TEXT NOT NULL,
+    default_role INT4 NOT NULL DEFAULT 0,
+    verified_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS organization_domains_org_domain_idx ON organization_domains(organization_id, domain);
+-- Any number of organizations can claim a domain but only one of them can verify it
+CREATE UNIQUE INDEX IF NOT EXISTS organization_domains_verified_domain_idx ON organization_domains(domain) WHERE verified_at IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS organization_scim_tokens (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL UNIQUE REFERENCES organizations(id) ON DELETE CASCADE,
+    blake3_hash TEXT NOT NULL UNIQUE,
+    token_prefix TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS organization_sso_identities (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    subject TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- An identity provider subject signs in to exactly one user and a user has at most one identity per organization
+CREATE UNIQUE INDEX IF NOT EXISTS organization_sso_identities_org_subject_idx ON organization_sso_identities(organization_id, subject);
+CREATE UNIQUE INDEX IF NOT EXISTS organization_sso_identities_org_user_idx ON organization_sso_identities(organization_id, user_id);
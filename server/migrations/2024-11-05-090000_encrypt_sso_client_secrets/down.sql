@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+UPDATE organization_sso_configs SET encrypted_oidc_client_secret = NULL;
+ALTER TABLE organization_sso_configs RENAME COLUMN encrypted_oidc_client_secret TO oidc_client_secret;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Client secrets are encrypted with SECRET_KEY by the server from now on, plaintext ones have to be set again
+ALTER TABLE organization_sso_configs RENAME COLUMN oidc_client_secret TO encrypted_oidc_client_secret;
+UPDATE organization_sso_configs SET encrypted_oidc_client_secret = NULL;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_rag_answer_caches_dataset_id_created_at;
+
+DELETE FROM rag_answer_caches;
+ALTER TABLE rag_answer_caches DROP COLUMN IF EXISTS embedding_size;
+ALTER TABLE rag_answer_caches ADD COLUMN IF NOT EXISTS query_embedding REAL[] NOT NULL;
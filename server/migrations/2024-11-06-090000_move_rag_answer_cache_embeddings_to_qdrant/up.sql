@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Question embeddings are searched in the rag_answer_caches_{size} Qdrant collections, answers cached before them have no point there and are dropped
+DELETE FROM rag_answer_caches;
+ALTER TABLE rag_answer_caches DROP COLUMN IF EXISTS query_embedding;
+ALTER TABLE rag_answer_caches ADD COLUMN IF NOT EXISTS embedding_size INT NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_rag_answer_caches_dataset_id_created_at ON rag_answer_caches (dataset_id, created_at DESC);
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- What the last sync of a connector ingested for each document of its source, so that unchanged documents are skipped and removed ones deleted
+CREATE TABLE IF NOT EXISTS connector_documents (
+    id UUID PRIMARY KEY,
+    crawl_request_id UUID NOT NULL REFERENCES crawl_requests(id) ON DELETE CASCADE,
+    source TEXT NOT NULL,
+    version TEXT NOT NULL,
+    tracking_ids TEXT[] NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (crawl_request_id, source)
+);
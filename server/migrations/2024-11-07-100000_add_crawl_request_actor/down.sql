@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE crawl_requests DROP COLUMN IF EXISTS actor_api_key_id;
+ALTER TABLE crawl_requests DROP COLUMN IF EXISTS actor_user_id;
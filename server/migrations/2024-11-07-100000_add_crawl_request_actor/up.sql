@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- The user or API key which configured the crawl, chunk versions written by its syncs are attributed to them
+ALTER TABLE crawl_requests ADD COLUMN IF NOT EXISTS actor_user_id UUID;
+ALTER TABLE crawl_requests ADD COLUMN IF NOT EXISTS actor_api_key_id UUID;
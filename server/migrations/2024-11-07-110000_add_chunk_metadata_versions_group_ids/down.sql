@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE chunk_metadata_versions DROP COLUMN IF EXISTS group_ids;
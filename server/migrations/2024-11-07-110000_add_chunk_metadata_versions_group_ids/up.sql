@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE chunk_metadata_versions ADD COLUMN IF NOT EXISTS group_ids UUID[];
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+UPDATE messages SET sort_order = summarized_through WHERE role = 'summary' AND summarized_through IS NOT NULL;
+
+ALTER TABLE messages DROP COLUMN IF EXISTS summarized_through;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS summarized_through INT;
+
+-- Summaries took the sort order of the last message they cover
+UPDATE messages SET summarized_through = sort_order, sort_order = -1 WHERE role = 'summary';
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE organization_scim_tokens DROP COLUMN IF EXISTS can_grant_owner;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE organization_scim_tokens ADD COLUMN IF NOT EXISTS can_grant_owner BOOLEAN NOT NULL DEFAULT false;
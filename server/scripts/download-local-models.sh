@@ -0,0 +1,23 @@
+#!/bin/sh
+# Downloads the ONNX models the local embedding provider allows by default (see LOCAL_MODELS) into the given directory, ./models if none is given
+set -e
+
+models_dir=${1:-./models}
+
+download_model() {
+    name=$1
+    repo=$2
+    model_file=$3
+
+    mkdir -p "$models_dir/$name"
+    curl -fsSL "https://huggingface.co/$repo/resolve/main/$model_file" -o "$models_dir/$name/model.onnx"
+    curl -fsSL "https://huggingface.co/$repo/resolve/main/tokenizer.json" -o "$models_dir/$name/tokenizer.json"
+}
+
+download_model bge-small-en-v1.5 Xenova/bge-small-en-v1.5 onnx/model.onnx
+download_model ms-marco-MiniLM-L-6-v2 Xenova/ms-marco-MiniLM-L-6-v2 onnx/model.onnx
+download_model splade-doc Qdrant/Splade_PP_en_v1 model.onnx
+
+# SPLADE++ uses the same model for documents and queries
+mkdir -p "$models_dir/splade-query"
+cp "$models_dir/splade-doc/"* "$models_dir/splade-query/"
@@ -84,13 +84,18 @@ async fn main() -> Result<(), ServiceError> {
 
     for request in new_requests {
         log::info!("Re-crawling site: {}", request.url);
-        let new_scrape_id = crawl_site(request.crawl_options.clone())
-            .await
-            .expect("Failed to crawl site");
-
-        let updated_request = update_scrape_id(request.scrape_id, new_scrape_id, pool.clone())
-            .await
-            .expect("Failed to update scrape id");
+        // Sources other than firecrawl are read by the worker itself so the request is re-queued as is
+        let updated_request = if request.crawl_options.uses_firecrawl() {
+            let new_scrape_id = crawl_site(request.crawl_options.clone())
+                .await
+                .expect("Failed to crawl site");
+
+            update_scrape_id(request.scrape_id, new_scrape_id, pool.clone())
+                .await
+                .expect("Failed to update scrape id")
+        } else {
+            request
+        };
 
         let serialized_message = serde_json::to_string(&updated_request).unwrap();
         let mut redis_conn = redis_pool
@@ -12,13 +12,22 @@ use trieve_server::{
     data::models::{self, WorkerEvent},
     operators::{
         clickhouse_operator::{ClickHouseEvent, EventQueue},
+        connector_operator::{
+            delete_chunks_by_tracking_ids, diff_connector_documents, get_connector_documents,
+            get_ingested_documents, matches_crawl_paths, set_ingested_documents,
+        },
+        crawl_run_operator::{
+            create_crawl_run_query, fail_interrupted_crawl_runs_query, finish_crawl_run_query,
+            get_previous_page_hashes_query, prune_crawl_pages_query,
+        },
         dataset_operator::get_dataset_by_id_query,
         user_operator::hash_function,
     },
 };
 use trieve_server::{
     data::models::{
-        CrawlRequest, CrawlShopifyOptions, DatasetConfiguration, RedisPool, ScrapeOptions,
+        CrawlPageResult, CrawlPageStatus, CrawlRequest, CrawlRunPG, CrawlRunStatus,
+        CrawlShopifyOptions, DatasetConfiguration, RedisPool, ScrapeOptions,
     },
     operators::crawl_operator::{get_crawl_from_firecrawl, Status},
 };
@@ -26,7 +35,10 @@ use trieve_server::{
     data::models::{CrawlStatus, Pool},
     errors::ServiceError,
     establish_connection, get_env,
-    operators::crawl_operator::{get_tags, update_crawl_status},
+    operators::crawl_operator::{
+        get_crawl_request_actor_query, get_crawl_request_credentials_query, get_tags,
+        update_crawl_status,
+    },
 };
 use trieve_server::{
     handlers::chunk_handler::ChunkReqPayload, operators::crawl_operator::chunk_html,
@@ -41,7 +53,9 @@ use ureq::json;
 struct ScrapeReport {
     request_id: uuid::Uuid,
     pages_scraped: usize,
+    pages_unchanged: usize,
     chunks_created: usize,
+    chunks_deleted: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -169,8 +183,9 @@ fn create_chunk_req_payload(
 
 async fn get_chunks_from_shopify(
     scrape_request: CrawlRequest,
-) -> Result<(Vec<ChunkReqPayload>, usize), ServiceError> {
+) -> Result<(Vec<ChunkReqPayload>, usize, Vec<CrawlPageResult>), ServiceError> {
     let mut chunks: Vec<ChunkReqPayload> = Vec::new();
+    let mut pages: Vec<CrawlPageResult> = Vec::new();
     let mut cur_page = 1;
 
     loop {
@@ -187,6 +202,8 @@ async fn get_chunks_from_shopify(
         }
 
         for product in response.products {
+            let product_chunks_start = chunks.len();
+
             if product.variants.len() == 1 {
                 chunks.push(create_chunk_req_payload(
                     &product,
@@ -204,20 +221,48 @@ async fn get_chunks_from_shopify(
                     )?);
                 }
             }
+
+            pages.push(get_page_result(
+                format!("{}/products/{}", scrape_request.url, product.handle),
+                &chunks[product_chunks_start..],
+            ));
         }
 
         cur_page += 1;
     }
 
-    Ok((chunks, cur_page))
+    Ok((chunks, cur_page, pages))
+}
+
+/// Outcome of a page from the chunks which were made from it
+fn get_page_result(url: String, page_chunks: &[ChunkReqPayload]) -> CrawlPageResult {
+    let content_hash = hash_function(
+        &page_chunks
+            .iter()
+            .map(|chunk| chunk.chunk_html.clone().unwrap_or_default())
+            .collect::<Vec<String>>()
+            .join("\n"),
+    );
+
+    CrawlPageResult::from_details(
+        url,
+        if page_chunks.is_empty() {
+            CrawlPageStatus::Empty
+        } else {
+            CrawlPageStatus::Ingested
+        },
+        page_chunks.len(),
+        Some(content_hash),
+    )
 }
 
 #[allow(clippy::print_stdout)]
 async fn get_chunks_with_firecrawl(
     scrape_request: CrawlRequest,
     pool: web::Data<Pool>,
-) -> Result<(Vec<ChunkReqPayload>, usize), ServiceError> {
+) -> Result<(Vec<ChunkReqPayload>, usize, Vec<CrawlPageResult>), ServiceError> {
     let mut chunks = vec![];
+    let mut pages = vec![];
     let mut spec = None;
 
     if let Some(ScrapeOptions::OpenApi(openapi_options)) =
@@ -304,37 +349,73 @@ async fn get_chunks_with_firecrawl(
 
     let page_count = data.len();
 
-    for page in data {
+    for (page_index, page) in data.into_iter().enumerate() {
         let page = match page {
             Some(page) => page,
-            None => continue,
+            None => {
+                pages.push(CrawlPageResult::failed(
+                    String::new(),
+                    format!("Firecrawl returned no document for page {}", page_index),
+                ));
+                continue;
+            }
         };
 
-        if page.metadata.status_code != Some(200) {
-            log::error!("Error getting page metadata for chunk: {:?}", page.metadata);
-            update_crawl_status(scrape_request.id, CrawlStatus::Failed, pool.clone())
-                .await
-                .map_err(|e| {
-                    log::error!("Error updating crawl status: {:?}", e);
-                    ServiceError::InternalServerError("Error updating crawl status".to_string())
-                })?;
-        }
-
         let page_link = page
             .metadata
             .source_url
             .clone()
+            .or(page.metadata.og_url.clone())
             .unwrap_or_default()
             .trim_end_matches("/")
             .to_string();
+
         if page_link.is_empty() {
-            println!(
-                "Error page source_url is not present for page_metadata: {:?}",
+            log::error!(
+                "Page source_url is not present for page_metadata: {:?}",
                 page.metadata
             );
+            pages.push(CrawlPageResult::failed(
+                String::new(),
+                format!("Firecrawl returned no url for page {}", page_index),
+            ));
             continue;
         }
 
+        // Firecrawl can return pages outside of the crawl's paths, i.e. after following a redirect
+        if !matches_crawl_paths(&page_link, &scrape_request.crawl_options) {
+            pages.push(CrawlPageResult::from_details(
+                page_link,
+                CrawlPageStatus::SkippedByPath,
+                0,
+                None,
+            ));
+            continue;
+        }
+
+        if page.metadata.status_code != Some(200) {
+            log::error!("Error getting page metadata for chunk: {:?}", page.metadata);
+            update_crawl_status(scrape_request.id, CrawlStatus::Failed, pool.clone())
+                .await
+                .map_err(|e| {
+                    log::error!("Error updating crawl status: {:?}", e);
+                    ServiceError::InternalServerError("Error updating crawl status".to_string())
+                })?;
+
+            pages.push(CrawlPageResult::failed(
+                page_link.clone(),
+                match page.metadata.status_code {
+                    Some(status_code) => {
+                        format!("Page returned status code {}", status_code)
+                    }
+                    None => "Page returned no status code".to_string(),
+                },
+            ));
+        }
+
+        let page_chunks_start = chunks.len();
+        let page_failed = page.metadata.status_code != Some(200);
+
         let page_title = page.metadata.og_title.clone().unwrap_or_default();
         let page_description = page.metadata.og_description.clone().unwrap_or_default();
         let page_html = page.html.clone().unwrap_or_default();
@@ -496,6 +577,13 @@ async fn get_chunks_with_firecrawl(
                             chunks.push(chunk);
                         }
 
+                        if !page_failed {
+                            pages.push(get_page_result(
+                                page_link.clone(),
+                                &chunks[page_chunks_start..],
+                            ));
+                        }
+
                         continue;
                     }
                 }
@@ -581,17 +669,71 @@ async fn get_chunks_with_firecrawl(
             };
             chunks.push(chunk);
         }
+
+        if !page_failed {
+            pages.push(get_page_result(page_link, &chunks[page_chunks_start..]));
+        }
     }
 
-    Ok((chunks, page_count))
+    Ok((chunks, page_count, pages))
 }
 
-#[allow(clippy::print_stdout)]
+/// Records a crawl run around the crawl such that the outcome of every page can be looked up later
 async fn crawl(
     scrape_request: CrawlRequest,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
 ) -> Result<ScrapeReport, ServiceError> {
+    // A run left in running belongs to a worker which stopped before finishing it, the request is being retried now
+    fail_interrupted_crawl_runs_query(scrape_request.id, pool.clone()).await?;
+
+    let crawl_run =
+        create_crawl_run_query(CrawlRunPG::from_details(&scrape_request), pool.clone()).await?;
+    let dataset_id = crawl_run.dataset_id;
+
+    match ingest_crawl(scrape_request, pool.clone(), redis_pool).await {
+        Ok((scrape_report, pages)) => {
+            finish_crawl_run_query(
+                crawl_run,
+                CrawlRunStatus::Completed,
+                None,
+                pages,
+                scrape_report.chunks_deleted,
+                pool.clone(),
+            )
+            .await?;
+
+            if let Err(err) = prune_crawl_pages_query(dataset_id, pool).await {
+                log::error!("Failed to prune crawl pages: {:?}", err);
+            }
+
+            Ok(scrape_report)
+        }
+        Err(err) => {
+            if let Err(finish_err) = finish_crawl_run_query(
+                crawl_run,
+                CrawlRunStatus::Failed,
+                Some(err.to_string()),
+                vec![],
+                0,
+                pool,
+            )
+            .await
+            {
+                log::error!("Failed to mark crawl run as failed: {:?}", finish_err);
+            }
+
+            Err(err)
+        }
+    }
+}
+
+#[allow(clippy::print_stdout)]
+async fn ingest_crawl(
+    scrape_request: CrawlRequest,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(ScrapeReport, Vec<CrawlPageResult>), ServiceError> {
     let dataset = get_dataset_by_id_query(
         trieve_server::data::models::UnifiedId::TrieveUuid(scrape_request.dataset_id),
         pool.clone(),
@@ -604,12 +746,44 @@ async fn crawl(
 
     let dataset_config = DatasetConfiguration::from_json(dataset.server_configuration.clone());
 
-    // Use shopify specific logic to get chunks or firecrawl
-    let (chunks, page_count) = match scrape_request.crawl_options.scrape_options {
+    let mut connector_diff = None;
+
+    // Use shopify specific logic, a sync connector or firecrawl to get chunks
+    let (chunks, page_count, mut pages) = match scrape_request.crawl_options.scrape_options {
         Some(ScrapeOptions::Shopify(_)) => get_chunks_from_shopify(scrape_request.clone()).await?,
+        _ if scrape_request.crawl_options.is_connector() => {
+            let previous_documents =
+                get_ingested_documents(scrape_request.id, pool.clone(), redis_pool.clone()).await?;
+            let credentials =
+                get_crawl_request_credentials_query(scrape_request.id, pool.clone()).await?;
+            let sync_result = get_connector_documents(
+                &scrape_request.crawl_options,
+                credentials,
+                &previous_documents,
+            )
+            .await?;
+            let mut diff = diff_connector_documents(sync_result, previous_documents);
+            let chunks = std::mem::take(&mut diff.chunks_to_upsert);
+            let page_count = diff.documents_changed + diff.documents_unchanged;
+            let pages = std::mem::take(&mut diff.pages);
+            connector_diff = Some(diff);
+
+            (chunks, page_count, pages)
+        }
         _ => get_chunks_with_firecrawl(scrape_request.clone(), pool.clone()).await?,
     };
 
+    // Pages with the same content as in the last completed run are reported as unchanged
+    let previous_page_hashes = get_previous_page_hashes_query(dataset.id, pool.clone()).await?;
+    for page in pages.iter_mut() {
+        if page.status == CrawlPageStatus::Ingested
+            && page.content_hash.is_some()
+            && previous_page_hashes.get(&page.url) == page.content_hash.as_ref()
+        {
+            page.status = CrawlPageStatus::Unchanged;
+        }
+    }
+
     let chunks_to_upload = chunks.chunks(120);
 
     for chunk in chunks_to_upload {
@@ -641,6 +815,32 @@ async fn crawl(
         }
     }
 
+    // Remove chunks of documents or sections which disappeared from the source since the last sync
+    let chunks_deleted = match connector_diff {
+        Some(diff) => {
+            let actor = get_crawl_request_actor_query(scrape_request.id, pool.clone()).await?;
+            let chunks_deleted = delete_chunks_by_tracking_ids(
+                diff.tracking_ids_to_delete,
+                dataset.clone(),
+                dataset_config.clone(),
+                actor,
+                pool.clone(),
+            )
+            .await?;
+
+            set_ingested_documents(
+                scrape_request.id,
+                &diff.ingested_documents,
+                pool.clone(),
+                redis_pool.clone(),
+            )
+            .await?;
+
+            chunks_deleted
+        }
+        None => 0,
+    };
+
     update_crawl_status(
         scrape_request.scrape_id,
         CrawlStatus::Completed,
@@ -655,11 +855,21 @@ async fn crawl(
     )
     .await?;
 
-    Ok(ScrapeReport {
-        request_id: scrape_request.id,
-        pages_scraped: page_count,
-        chunks_created: chunks.len(),
-    })
+    let pages_unchanged = pages
+        .iter()
+        .filter(|page| page.status == CrawlPageStatus::Unchanged)
+        .count();
+
+    Ok((
+        ScrapeReport {
+            request_id: scrape_request.id,
+            pages_scraped: page_count,
+            pages_unchanged,
+            chunks_created: chunks.len(),
+            chunks_deleted,
+        },
+        pages,
+    ))
 }
 
 #[allow(clippy::print_stdout)]
@@ -763,7 +973,9 @@ async fn scrape_worker(
                             models::EventType::CrawlCompleted {
                                 scrape_id: scrape_report.request_id,
                                 pages_crawled: scrape_report.pages_scraped,
+                                pages_unchanged: scrape_report.pages_unchanged,
                                 chunks_created: scrape_report.chunks_created,
+                                chunks_deleted: scrape_report.chunks_deleted,
                                 crawl_options: crawl_request.crawl_options,
                             },
                         )
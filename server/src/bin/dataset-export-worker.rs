@@ -0,0 +1,342 @@
+use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
+use sentry::{Hub, SentryFutureExt};
+use signal_hook::consts::SIGTERM;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use trieve_server::{
+    data::models::{self, DatasetExportMessage},
+    errors::ServiceError,
+    establish_connection, get_env,
+    operators::{
+        clickhouse_operator::{ClickHouseEvent, EventQueue},
+        dataset_archive_operator::{create_dataset_archive, get_dataset_export_key},
+        dataset_operator::get_dataset_by_id_query,
+        file_operator::get_aws_bucket,
+    },
+};
+
+fn main() {
+    dotenvy::dotenv().ok();
+    let sentry_url = std::env::var("SENTRY_URL");
+    let _guard = if let Ok(sentry_url) = sentry_url {
+        let guard = sentry::init((
+            sentry_url,
+            sentry::ClientOptions {
+                release: sentry::release_name!(),
+                traces_sample_rate: 1.0,
+                ..Default::default()
+            },
+        ));
+
+        tracing_subscriber::Registry::default()
+            .with(sentry::integrations::tracing::layer())
+            .with(
+                tracing_subscriber::fmt::layer().with_filter(
+                    EnvFilter::from_default_env()
+                        .add_directive(tracing_subscriber::filter::LevelFilter::INFO.into()),
+                ),
+            )
+            .init();
+
+        log::info!("Sentry monitoring enabled");
+        Some(guard)
+    } else {
+        tracing_subscriber::Registry::default()
+            .with(
+                tracing_subscriber::fmt::layer().with_filter(
+                    EnvFilter::from_default_env()
+                        .add_directive(tracing_subscriber::filter::LevelFilter::INFO.into()),
+                ),
+            )
+            .init();
+
+        None
+    };
+
+    let database_url = get_env!("DATABASE_URL", "DATABASE_URL is not set");
+
+    let mut config = ManagerConfig::default();
+    config.custom_setup = Box::new(establish_connection);
+
+    let mgr = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new_with_config(
+        database_url,
+        config,
+    );
+
+    let pool = diesel_async::pooled_connection::deadpool::Pool::builder(mgr)
+        .max_size(3)
+        .build()
+        .expect("Failed to create diesel_async pool");
+
+    let web_pool = actix_web::web::Data::new(pool.clone());
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime")
+        .block_on(
+            async move {
+                let redis_url = get_env!("REDIS_URL", "REDIS_URL is not set");
+                let redis_connections: u32 = std::env::var("REDIS_CONNECTIONS")
+                    .unwrap_or("2".to_string())
+                    .parse()
+                    .unwrap_or(2);
+
+                let redis_manager = bb8_redis::RedisConnectionManager::new(redis_url)
+                    .expect("Failed to connect to redis");
+
+                let redis_pool = bb8_redis::bb8::Pool::builder()
+                    .max_size(redis_connections)
+                    .connection_timeout(std::time::Duration::from_secs(2))
+                    .build(redis_manager)
+                    .await
+                    .expect("Failed to create redis pool");
+
+                let web_redis_pool = actix_web::web::Data::new(redis_pool);
+
+                let event_queue = if std::env::var("USE_ANALYTICS")
+                    .unwrap_or("false".to_string())
+                    .parse()
+                    .unwrap_or(false)
+                {
+                    log::info!("Analytics enabled");
+
+                    let clickhouse_client = clickhouse::Client::default()
+                        .with_url(
+                            std::env::var("CLICKHOUSE_URL")
+                                .unwrap_or("http://localhost:8123".to_string()),
+                        )
+                        .with_user(
+                            std::env::var("CLICKHOUSE_USER").unwrap_or("default".to_string()),
+                        )
+                        .with_password(
+                            std::env::var("CLICKHOUSE_PASSWORD").unwrap_or("".to_string()),
+                        )
+                        .with_database(
+                            std::env::var("CLICKHOUSE_DATABASE").unwrap_or("default".to_string()),
+                        )
+                        .with_option("async_insert", "1")
+                        .with_option("wait_for_async_insert", "0");
+
+                    let mut event_queue = EventQueue::new(clickhouse_client.clone());
+                    event_queue.start_service();
+                    event_queue
+                } else {
+                    log::info!("Analytics disabled");
+                    EventQueue::default()
+                };
+
+                let web_event_queue = actix_web::web::Data::new(event_queue);
+
+                let should_terminate = Arc::new(AtomicBool::new(false));
+                signal_hook::flag::register(SIGTERM, Arc::clone(&should_terminate))
+                    .expect("Failed to register shutdown hook");
+
+                dataset_export_worker(should_terminate, web_redis_pool, web_pool, web_event_queue)
+                    .await
+            }
+            .bind_hub(Hub::new_from_top(Hub::current())),
+        );
+}
+
+async fn dataset_export_worker(
+    should_terminate: Arc<AtomicBool>,
+    redis_pool: actix_web::web::Data<models::RedisPool>,
+    web_pool: actix_web::web::Data<models::Pool>,
+    event_queue: actix_web::web::Data<EventQueue>,
+) {
+    log::info!("Starting dataset export worker service thread");
+
+    let mut redis_conn_sleep = std::time::Duration::from_secs(1);
+
+    #[allow(unused_assignments)]
+    let mut opt_redis_connection = None;
+
+    loop {
+        let borrowed_redis_connection = match redis_pool.get().await {
+            Ok(redis_connection) => Some(redis_connection),
+            Err(err) => {
+                log::error!("Failed to get redis connection outside of loop: {:?}", err);
+                None
+            }
+        };
+
+        if borrowed_redis_connection.is_some() {
+            opt_redis_connection = borrowed_redis_connection;
+            break;
+        }
+
+        tokio::time::sleep(redis_conn_sleep).await;
+        redis_conn_sleep = std::cmp::min(redis_conn_sleep * 2, std::time::Duration::from_secs(300));
+    }
+
+    let mut redis_connection =
+        opt_redis_connection.expect("Failed to get redis connection outside of loop");
+
+    let mut broken_pipe_sleep = std::time::Duration::from_secs(10);
+
+    loop {
+        if should_terminate.load(Ordering::Relaxed) {
+            log::info!("Shutting down");
+            break;
+        }
+
+        let payload_result: Result<Vec<String>, redis::RedisError> = redis::cmd("brpoplpush")
+            .arg("dataset_export")
+            .arg("dataset_export_processing")
+            .arg(1.0)
+            .query_async(&mut redis_connection.clone())
+            .await;
+
+        let serialized_message = if let Ok(payload) = payload_result {
+            broken_pipe_sleep = std::time::Duration::from_secs(10);
+
+            if payload.is_empty() {
+                continue;
+            }
+
+            payload
+                .first()
+                .expect("Payload must have a first element")
+                .clone()
+        } else {
+            log::error!("Unable to process {:?}", payload_result);
+
+            if payload_result.is_err_and(|err| err.is_io_error()) {
+                tokio::time::sleep(broken_pipe_sleep).await;
+                broken_pipe_sleep =
+                    std::cmp::min(broken_pipe_sleep * 2, std::time::Duration::from_secs(300));
+            }
+
+            continue;
+        };
+
+        let processing_ctx = sentry::TransactionContext::new(
+            "dataset export worker building archive",
+            "dataset export worker building archive",
+        );
+        let transaction = sentry::start_transaction(processing_ctx);
+        let export_message: DatasetExportMessage =
+            serde_json::from_str(&serialized_message).expect("Failed to parse export message");
+
+        let event_type = match export_dataset(export_message.clone(), web_pool.clone()).await {
+            Ok(chunk_count) => {
+                log::info!(
+                    "Exported dataset {:?} with {} chunks as {:?}",
+                    export_message.dataset_id,
+                    chunk_count,
+                    export_message.export_id
+                );
+
+                models::EventType::DatasetExported {
+                    export_id: export_message.export_id,
+                    chunk_count,
+                }
+            }
+            Err(err) => {
+                log::error!("Failed to export dataset: {:?}", err);
+
+                let _ = redis::cmd("lpush")
+                    .arg("dead_letters_dataset_export")
+                    .arg(&serialized_message)
+                    .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *redis_connection)
+                    .await;
+
+                models::EventType::DatasetExportFailed {
+                    export_id: export_message.export_id,
+                    error: err.to_string(),
+                }
+            }
+        };
+
+        event_queue
+            .send(ClickHouseEvent::WorkerEvent(
+                models::WorkerEvent::from_details(export_message.dataset_id, event_type).into(),
+            ))
+            .await;
+
+        let _ = redis::cmd("LREM")
+            .arg("dataset_export_processing")
+            .arg(1)
+            .arg(serialized_message)
+            .query_async::<redis::aio::MultiplexedConnection, usize>(&mut *redis_connection)
+            .await;
+
+        transaction.finish();
+    }
+}
+
+async fn export_dataset(
+    export_message: DatasetExportMessage,
+    web_pool: actix_web::web::Data<models::Pool>,
+) -> Result<usize, ServiceError> {
+    let tx_ctx = sentry::TransactionContext::new(
+        "dataset export worker export_dataset",
+        "dataset export worker export_dataset",
+    );
+    let transaction = sentry::start_transaction(tx_ctx);
+    sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone().into())));
+
+    let dataset = get_dataset_by_id_query(
+        models::UnifiedId::TrieveUuid(export_message.dataset_id),
+        web_pool.clone(),
+    )
+    .await?;
+
+    // The archive is written to a temporary file rather than memory since it can be far larger
+    // than the worker's memory once vectors or file contents are included.
+    let archive_path =
+        std::env::temp_dir().join(format!("dataset-export-{}.zip", export_message.export_id));
+
+    let result = async {
+        let archive_span = transaction.start_child("create_archive", "Create dataset archive");
+
+        let archive_file = std::fs::File::create(&archive_path).map_err(|e| {
+            log::error!("Could not create archive file {:?}", e);
+            ServiceError::InternalServerError("Could not create archive file".to_string())
+        })?;
+
+        let (archive_file, chunk_count) = create_dataset_archive(
+            archive_file,
+            dataset,
+            export_message.include_vectors,
+            export_message.include_files,
+            web_pool,
+        )
+        .await?;
+
+        archive_span.finish();
+
+        let upload_span = transaction.start_child("upload_archive", "Upload archive to S3");
+
+        drop(archive_file);
+        let mut archive_file = tokio::fs::File::open(&archive_path).await.map_err(|e| {
+            log::error!("Could not open archive file {:?}", e);
+            ServiceError::InternalServerError("Could not read archive file".to_string())
+        })?;
+
+        get_aws_bucket()?
+            .put_object_stream(
+                &mut archive_file,
+                get_dataset_export_key(export_message.dataset_id, export_message.export_id),
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Could not upload dataset archive to S3 {:?}", e);
+                ServiceError::BadRequest("Could not upload dataset archive to S3".to_string())
+            })?;
+
+        upload_span.finish();
+
+        Ok(chunk_count)
+    }
+    .await;
+
+    let _ = tokio::fs::remove_file(&archive_path).await;
+    transaction.finish();
+
+    result
+}
@@ -415,6 +415,7 @@ pub async fn bulk_delete_chunks(
         chunk_delete_message.filter,
         chunk_delete_message.dataset_id,
         dataset_config,
+        chunk_delete_message.actor,
         web_pool.clone(),
     )
     .await
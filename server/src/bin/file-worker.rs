@@ -15,6 +15,7 @@ use trieve_server::{
         clickhouse_operator::{ClickHouseEvent, EventQueue},
         dataset_operator::get_dataset_and_organization_from_dataset_id_query,
         file_operator::{create_file_chunks, create_file_query, get_aws_bucket},
+        file_parser_operator::parse_file,
     },
 };
 
@@ -303,40 +304,19 @@ async fn upload_file(
 
     get_file_span.finish();
 
-    let tika_url = std::env::var("TIKA_URL")
-        .expect("TIKA_URL must be set")
-        .to_string();
+    let parse_file_span = transaction.start_child("parse_file", "Parse file into sections");
 
-    let tika_client = reqwest::Client::new();
-    let tika_html_parse_span = transaction.start_child("tika_html_parse", "Parse tika html");
-
-    let tika_response = tika_client
-        .put(format!("{}/tika", tika_url))
-        .header("Accept", "text/html")
-        .body(file_data.clone())
-        .send()
-        .await
-        .map_err(|err| {
-            log::error!("Could not send file to tika {:?}", err);
-            ServiceError::BadRequest("Could not send file to tika".to_string())
-        })?;
-
-    let tike_html_converted_file_bytes = tika_response
-        .bytes()
-        .await
-        .map_err(|err| {
-            log::error!("Could not get tika response bytes {:?}", err);
-            ServiceError::BadRequest("Could not get tika response bytes".to_string())
-        })?
-        .to_vec();
-    tika_html_parse_span.finish();
+    let parsed_file = parse_file(
+        &file_worker_message.upload_file_data.file_name,
+        file_worker_message
+            .upload_file_data
+            .file_mime_type
+            .as_deref(),
+        &file_data,
+    )
+    .await?;
 
-    let html_content = String::from_utf8_lossy(&tike_html_converted_file_bytes).to_string();
-    if html_content.is_empty() {
-        return Err(ServiceError::BadRequest(
-            "Could not parse file with tika".to_string(),
-        ));
-    }
+    parse_file_span.finish();
 
     let file_size_mb = (file_data.len() as f64 / 1024.0 / 1024.0).round() as i64;
 
@@ -372,7 +352,7 @@ async fn upload_file(
     create_file_chunks(
         created_file.id,
         file_worker_message.upload_file_data,
-        html_content,
+        parsed_file,
         dataset_org_plan_sub,
         web_pool.clone(),
         event_queue.clone(),
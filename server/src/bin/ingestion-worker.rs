@@ -10,7 +10,8 @@ use std::collections::HashMap;
 use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc};
 use tracing_subscriber::{prelude::*, EnvFilter, Layer};
 use trieve_server::data::models::{
-    self, ChunkMetadata, DatasetConfiguration, QdrantPayload, UnifiedId, WorkerEvent,
+    self, ChunkMetadata, ChunkMetadataVersionPG, ChunkVersionAction, ChunkVersionActor,
+    DatasetConfiguration, QdrantPayload, UnifiedId, WorkerEvent,
 };
 use trieve_server::errors::ServiceError;
 use trieve_server::handlers::chunk_handler::{
@@ -22,11 +23,15 @@ use trieve_server::operators::chunk_operator::{
     bulk_insert_chunk_metadata_query, bulk_revert_insert_chunk_metadata_query,
     insert_chunk_metadata_query, update_chunk_metadata_query,
 };
+use trieve_server::operators::chunk_version_operator::insert_chunk_metadata_versions_query;
 use trieve_server::operators::clickhouse_operator::{ClickHouseEvent, EventQueue};
 use trieve_server::operators::dataset_operator::get_dataset_by_id_query;
+use trieve_server::operators::embedding_cache_operator::{
+    get_dense_vectors_with_cache, get_sparse_vectors_with_cache, EmbeddingCacheStats,
+};
 use trieve_server::operators::group_operator::get_groups_from_group_ids_query;
 use trieve_server::operators::model_operator::{
-    get_bm25_embeddings, get_dense_vector, get_dense_vectors, get_sparse_vectors,
+    get_bm25_embeddings, get_dense_vectors, get_sparse_vectors, get_vector_field_embeddings,
 };
 use trieve_server::operators::parse_operator::{
     average_embeddings, coarse_doc_chunker, convert_html_to_text,
@@ -34,6 +39,7 @@ use trieve_server::operators::parse_operator::{
 use trieve_server::operators::qdrant_operator::{
     bulk_upsert_qdrant_points_query, update_qdrant_point_query,
 };
+use trieve_server::operators::rag_cache_operator::invalidate_cached_answers_query;
 use trieve_server::{establish_connection, get_env};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -293,18 +299,27 @@ async fn ingestion_worker(
                     payload.clone(),
                     dataset_config.clone(),
                     web_pool.clone(),
+                    redis_pool.clone(),
                     reqwest_client.clone(),
                 )
                 .await
                 {
-                    Ok(chunk_ids) => {
-                        log::info!("Uploaded {:} chunks", chunk_ids.len());
+                    Ok((chunk_ids, embedding_cache_stats)) => {
+                        log::info!(
+                            "Uploaded {:} chunks, {:} reused cached embeddings",
+                            chunk_ids.len(),
+                            embedding_cache_stats.skipped
+                        );
 
                         event_queue
                             .send(ClickHouseEvent::WorkerEvent(
                                 WorkerEvent::from_details(
                                     payload.dataset_id,
-                                    models::EventType::ChunksUploaded { chunk_ids },
+                                    models::EventType::ChunksUploaded {
+                                        chunk_ids,
+                                        chunks_skipped_embedding: embedding_cache_stats.skipped,
+                                        chunks_embedded: embedding_cache_stats.embedded,
+                                    },
                                 )
                                 .into(),
                             ))
@@ -334,8 +349,15 @@ async fn ingestion_worker(
             }
 
             IngestionMessage::Update(payload) => {
-                match update_chunk(payload.clone(), web_pool.clone(), dataset_config).await {
-                    Ok(_) => {
+                match update_chunk(
+                    payload.clone(),
+                    web_pool.clone(),
+                    redis_pool.clone(),
+                    dataset_config,
+                )
+                .await
+                {
+                    Ok(embedding_skipped) => {
                         log::info!("Updated chunk: {:?}", payload.chunk_metadata.id);
                         event_queue
                             .send(ClickHouseEvent::WorkerEvent(
@@ -343,6 +365,7 @@ async fn ingestion_worker(
                                     payload.dataset_id,
                                     models::EventType::ChunkUpdated {
                                         chunk_id: payload.chunk_metadata.id,
+                                        embedding_skipped,
                                     },
                                 )
                                 .into(),
@@ -383,6 +406,7 @@ pub struct ChunkDataWithEmbeddingText {
     pub upsert_by_tracking_id: bool,
     pub fulltext_boost: Option<FullTextBoost>,
     pub semantic_boost: Option<SemanticBoost>,
+    pub vector_fields: Option<HashMap<String, String>>,
 }
 
 impl From<ChunkDataWithEmbeddingText> for models::ChunkData {
@@ -398,13 +422,14 @@ impl From<ChunkDataWithEmbeddingText> for models::ChunkData {
     }
 }
 
-#[tracing::instrument(skip(payload, web_pool))]
+#[tracing::instrument(skip(payload, web_pool, redis_pool))]
 pub async fn bulk_upload_chunks(
     payload: BulkUploadIngestionMessage,
     dataset_config: DatasetConfiguration,
     web_pool: actix_web::web::Data<models::Pool>,
+    redis_pool: actix_web::web::Data<models::RedisPool>,
     reqwest_client: reqwest::Client,
-) -> Result<Vec<uuid::Uuid>, ServiceError> {
+) -> Result<(Vec<uuid::Uuid>, EmbeddingCacheStats), ServiceError> {
     let tx_ctx = sentry::TransactionContext::new(
         "ingestion worker bulk_upload_chunk",
         "ingestion worker bulk_upload_chunk",
@@ -502,6 +527,7 @@ pub async fn bulk_upload_chunks(
                     .semantic_boost
                     .clone()
                     .filter(|boost| !boost.phrase.is_empty()),
+                vector_fields: message.chunk.vector_fields.clone(),
             }
         })
         .filter(|data| !data.content.is_empty())
@@ -515,6 +541,7 @@ pub async fn bulk_upload_chunks(
                 message,
                 dataset_config.clone(),
                 ingestion_data,
+                payload.actor,
                 web_pool.clone(),
                 reqwest_client.clone(),
             )
@@ -525,8 +552,13 @@ pub async fn bulk_upload_chunks(
             }
         }
 
+        let embedding_cache_stats = EmbeddingCacheStats {
+            skipped: 0,
+            embedded: chunk_ids.len(),
+        };
+
         transaction.finish();
-        return Ok(chunk_ids);
+        return Ok((chunk_ids, embedding_cache_stats));
     }
 
     precompute_transaction.finish();
@@ -552,7 +584,7 @@ pub async fn bulk_upload_chunks(
 
     if inserted_chunk_metadatas.is_empty() {
         // All collisions
-        return Ok(vec![]);
+        return Ok((vec![], EmbeddingCacheStats::default()));
     }
 
     // Only embed the things we get returned from here, this reduces the number of times we embed data that are just duplicates
@@ -578,16 +610,17 @@ pub async fn bulk_upload_chunks(
         "calling_create_all_embeddings",
     );
 
-    let embedding_vectors = match dataset_config.SEMANTIC_ENABLED {
+    // Unchanged chunks, i.e. re-crawled pages or upserts by tracking_id, reuse the vectors cached for their text
+    let (embedding_vectors, dense_cache_hits) = match dataset_config.SEMANTIC_ENABLED {
         true => {
-            let vectors = match get_dense_vectors(
+            let (vectors, cache_hits) = match get_dense_vectors_with_cache(
                 embedding_content_and_boosts
                     .iter()
                     .map(|(content, _, semantic_boost)| (content.clone(), semantic_boost.clone()))
                     .collect(),
-                "doc",
                 dataset_config.clone(),
                 reqwest_client.clone(),
+                redis_pool.clone(),
             )
             .await
             {
@@ -606,11 +639,39 @@ pub async fn bulk_upload_chunks(
                     )))
                 }
             }?;
-            vectors.into_iter().map(Some).collect()
+            (vectors.into_iter().map(Some).collect(), cache_hits)
         }
-        false => vec![None; embedding_content_and_boosts.len()],
+        false => (
+            vec![None; embedding_content_and_boosts.len()],
+            vec![true; embedding_content_and_boosts.len()],
+        ),
     };
 
+    let chunk_vector_fields = ingestion_data
+        .iter()
+        .map(|data| data.vector_fields.clone())
+        .collect_vec();
+
+    let field_embeddings = match get_vector_field_embeddings(
+        chunk_vector_fields.clone(),
+        &dataset_config,
+        reqwest_client.clone(),
+    )
+    .await
+    {
+        Ok(field_embeddings) => Ok(field_embeddings),
+        Err(err) => {
+            if !upsert_by_tracking_id_being_used {
+                bulk_revert_insert_chunk_metadata_query(
+                    inserted_chunk_metadata_ids.clone(),
+                    web_pool.clone(),
+                )
+                .await?;
+            }
+            Err(err)
+        }
+    }?;
+
     // Assuming split average is false, Assume Explicit Vectors don't exist
     embedding_transaction.finish();
 
@@ -631,14 +692,15 @@ pub async fn bulk_upload_chunks(
             })
             .collect();
 
-    let splade_vectors = if dataset_config.FULLTEXT_ENABLED {
-        match get_sparse_vectors(
+    let (splade_vectors, sparse_cache_hits) = if dataset_config.FULLTEXT_ENABLED {
+        match get_sparse_vectors_with_cache(
             content_and_boosts
                 .iter()
                 .map(|(content, boost, _)| (content.clone(), boost.clone()))
                 .collect(),
-            "doc",
+            dataset_config.clone(),
             reqwest_client,
+            redis_pool,
         )
         .await
         {
@@ -657,11 +719,17 @@ pub async fn bulk_upload_chunks(
     } else {
         let content_size = content_and_boosts.len();
 
-        Ok(std::iter::repeat(vec![(0, 0.0)])
-            .take(content_size)
-            .collect())
+        Ok((
+            std::iter::repeat(vec![(0, 0.0)])
+                .take(content_size)
+                .collect(),
+            vec![true; content_size],
+        ))
     }?;
 
+    let embedding_cache_stats =
+        EmbeddingCacheStats::from_cache_hits(&dense_cache_hits, &sparse_cache_hits);
+
     let bm25_vectors = if dataset_config.BM25_ENABLED
         && std::env::var("BM25_ACTIVE").unwrap_or("false".to_string()) == "true"
     {
@@ -673,6 +741,7 @@ pub async fn bulk_upload_chunks(
             dataset_config.BM25_AVG_LEN,
             dataset_config.BM25_B,
             dataset_config.BM25_K,
+            dataset_config.LANGUAGE,
         )
         .into_iter()
         .map(Some)
@@ -687,10 +756,19 @@ pub async fn bulk_upload_chunks(
         inserted_chunk_metadatas.clone(),
         embedding_vectors.iter(),
         splade_vectors.iter(),
-        bm25_vectors.iter()
+        bm25_vectors.iter(),
+        field_embeddings.iter(),
+        chunk_vector_fields.iter()
     ))
     .then(
-        |(chunk_data, embedding_vector, splade_vector, bm25_vector)| async {
+        |(
+            chunk_data,
+            embedding_vector,
+            splade_vector,
+            bm25_vector,
+            field_embedding,
+            vector_fields,
+        )| async {
             let qdrant_point_id = chunk_data.chunk_metadata.qdrant_point_id;
 
             let chunk_tags: Option<Vec<Option<String>>> =
@@ -708,12 +786,15 @@ pub async fn bulk_upload_chunks(
                     None
                 };
 
-            let payload = QdrantPayload::new(
-                chunk_data.chunk_metadata,
-                chunk_data.group_ids,
-                None,
-                chunk_tags,
-            );
+            let payload = QdrantPayload {
+                vector_fields: vector_fields.clone(),
+                ..QdrantPayload::new(
+                    chunk_data.chunk_metadata,
+                    chunk_data.group_ids,
+                    None,
+                    chunk_tags,
+                )
+            };
 
             let mut vector_payload = HashMap::from([(
                 "sparse_vectors".to_string(),
@@ -747,6 +828,10 @@ pub async fn bulk_upload_chunks(
                 );
             }
 
+            for (vector_name, vector) in field_embedding {
+                vector_payload.insert(vector_name.clone(), Vector::from(vector.clone()));
+            }
+
             // If qdrant_point_id does not exist, does not get written to qdrant
             Ok(PointStruct::new(
                 qdrant_point_id.to_string(),
@@ -788,7 +873,35 @@ pub async fn bulk_upload_chunks(
         return Err(err);
     }
 
-    Ok(inserted_chunk_metadata_ids)
+    // Chunks upserted by tracking_id keep the id of the existing chunk
+    let requested_chunk_ids: Vec<uuid::Uuid> = payload
+        .ingestion_messages
+        .iter()
+        .map(|message| message.ingest_specific_chunk_metadata.id)
+        .collect();
+
+    record_chunk_versions(
+        inserted_chunk_metadatas
+            .into_iter()
+            .map(|chunk_data| {
+                let action = if requested_chunk_ids.contains(&chunk_data.chunk_metadata.id) {
+                    ChunkVersionAction::Create
+                } else {
+                    ChunkVersionAction::Update
+                };
+
+                ChunkMetadataVersionPG::from_details(
+                    chunk_data.chunk_metadata,
+                    action,
+                    payload.actor,
+                )
+            })
+            .collect(),
+        web_pool,
+    )
+    .await;
+
+    Ok((inserted_chunk_metadata_ids, embedding_cache_stats))
 }
 
 #[tracing::instrument(skip(payload, web_pool))]
@@ -796,6 +909,7 @@ async fn upload_chunk(
     mut payload: UploadIngestionMessage,
     dataset_config: DatasetConfiguration,
     ingestion_data: ChunkDataWithEmbeddingText,
+    actor: Option<ChunkVersionActor>,
     web_pool: actix_web::web::Data<models::Pool>,
     reqwest_client: reqwest::Client,
 ) -> Result<uuid::Uuid, ServiceError> {
@@ -945,7 +1059,14 @@ async fn upload_chunk(
             })
             .collect();
 
-        match get_sparse_vectors(content_and_boosts.clone(), "doc", reqwest_client).await {
+        match get_sparse_vectors(
+            content_and_boosts.clone(),
+            "doc",
+            dataset_config.clone(),
+            reqwest_client.clone(),
+        )
+        .await
+        {
             Ok(vectors) => Ok(vectors.first().expect("First vector must exist").clone()),
             Err(err) => Err(err),
         }
@@ -953,6 +1074,16 @@ async fn upload_chunk(
         Ok(vec![(0, 0.0)])
     }?;
 
+    let field_embedding = get_vector_field_embeddings(
+        vec![ingestion_data.vector_fields.clone()],
+        &dataset_config,
+        reqwest_client.clone(),
+    )
+    .await?
+    .into_iter()
+    .next()
+    .unwrap_or_default();
+
     let bm25_vector = if dataset_config.BM25_ENABLED
         && std::env::var("BM25_ACTIVE").unwrap_or("false".to_string()) == "true"
     {
@@ -962,6 +1093,7 @@ async fn upload_chunk(
                 dataset_config.BM25_AVG_LEN,
                 dataset_config.BM25_B,
                 dataset_config.BM25_K,
+                dataset_config.LANGUAGE,
             )
             .first()
             .expect("Vector Must exist")
@@ -1008,8 +1140,10 @@ async fn upload_chunk(
                 None
             };
 
-        let qdrant_payload =
-            QdrantPayload::new(chunk_metadata, payload.chunk.group_ids, None, chunk_tags);
+        let qdrant_payload = QdrantPayload {
+            vector_fields: ingestion_data.vector_fields.clone(),
+            ..QdrantPayload::new(chunk_metadata, payload.chunk.group_ids, None, chunk_tags)
+        };
 
         let vector_name = match &embedding_vector {
             Some(embedding_vector) => match embedding_vector.len() {
@@ -1045,6 +1179,10 @@ async fn upload_chunk(
             );
         }
 
+        for (vector_name, vector) in field_embedding {
+            vector_payload.insert(vector_name, Vector::from(vector));
+        }
+
         let point = PointStruct::new(
             qdrant_point_id.clone().to_string(),
             vector_payload,
@@ -1068,7 +1206,24 @@ async fn upload_chunk(
 
         insert_tx.finish();
 
-        inserted_chunk.id
+        let action = if inserted_chunk.id == payload.ingest_specific_chunk_metadata.id {
+            ChunkVersionAction::Create
+        } else {
+            ChunkVersionAction::Update
+        };
+        let inserted_chunk_id = inserted_chunk.id;
+
+        record_chunk_versions(
+            vec![ChunkMetadataVersionPG::from_details(
+                inserted_chunk,
+                action,
+                actor,
+            )],
+            web_pool.clone(),
+        )
+        .await;
+
+        inserted_chunk_id
     };
 
     transaction.finish();
@@ -1076,11 +1231,13 @@ async fn upload_chunk(
 }
 
 #[tracing::instrument(skip(web_pool))]
+/// Returns whether the vectors of the chunk were reused from the embedding cache
 async fn update_chunk(
     payload: UpdateIngestionMessage,
     web_pool: actix_web::web::Data<models::Pool>,
+    redis_pool: actix_web::web::Data<models::RedisPool>,
     dataset_config: DatasetConfiguration,
-) -> Result<(), ServiceError> {
+) -> Result<bool, ServiceError> {
     let content = match payload.convert_html_to_text.unwrap_or(true) {
         true => convert_html_to_text(
             &(payload
@@ -1104,36 +1261,53 @@ async fn update_chunk(
 
     let chunk_metadata = payload.chunk_metadata.clone();
 
-    let embedding_vector = match dataset_config.SEMANTIC_ENABLED {
+    let reqwest_client = reqwest::Client::new();
+
+    let (embedding_vector, dense_cache_hit) = match dataset_config.SEMANTIC_ENABLED {
         true => {
-            let embedding = get_dense_vector(
-                content.to_string(),
-                payload.semantic_boost,
-                "doc",
+            let (embeddings, cache_hits) = get_dense_vectors_with_cache(
+                vec![(content.to_string(), payload.semantic_boost)],
                 dataset_config.clone(),
+                reqwest_client.clone(),
+                redis_pool.clone(),
             )
             .await
             .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
-            Some(embedding)
+            (
+                embeddings.into_iter().next(),
+                cache_hits.first().copied().unwrap_or(false),
+            )
         }
-        false => None,
+        false => (None, true),
     };
 
-    let splade_vector = if dataset_config.FULLTEXT_ENABLED {
-        let reqwest_client = reqwest::Client::new();
+    let field_embedding = get_vector_field_embeddings(
+        vec![payload.vector_fields.clone()],
+        &dataset_config,
+        reqwest_client.clone(),
+    )
+    .await?
+    .into_iter()
+    .next()
+    .unwrap_or_default();
 
-        match get_sparse_vectors(
+    let (splade_vector, sparse_cache_hit) = if dataset_config.FULLTEXT_ENABLED {
+        match get_sparse_vectors_with_cache(
             vec![(content.clone(), payload.fulltext_boost.clone())],
-            "doc",
+            dataset_config.clone(),
             reqwest_client,
+            redis_pool,
         )
         .await
         {
-            Ok(v) => v.first().unwrap_or(&vec![(0, 0.0)]).clone(),
-            Err(_) => vec![(0, 0.0)],
+            Ok((v, cache_hits)) => (
+                v.first().unwrap_or(&vec![(0, 0.0)]).clone(),
+                cache_hits.first().copied().unwrap_or(false),
+            ),
+            Err(_) => (vec![(0, 0.0)], false),
         }
     } else {
-        vec![(0, 0.0)]
+        (vec![(0, 0.0)], true)
     };
 
     let bm25_vector = if dataset_config.BM25_ENABLED
@@ -1144,6 +1318,7 @@ async fn update_chunk(
             dataset_config.BM25_AVG_LEN,
             dataset_config.BM25_B,
             dataset_config.BM25_K,
+            dataset_config.LANGUAGE,
         );
 
         vecs.first().cloned()
@@ -1151,7 +1326,7 @@ async fn update_chunk(
         None
     };
 
-    if let Some(group_ids) = payload.group_ids {
+    let updated_chunk = if let Some(group_ids) = payload.group_ids {
         let mut chunk_group_ids: Vec<uuid::Uuid> = vec![];
         for group_id in group_ids {
             let group = dataset_owns_group(group_id, payload.dataset_id, web_pool.clone())
@@ -1160,7 +1335,7 @@ async fn update_chunk(
             chunk_group_ids.push(group.id);
         }
 
-        update_chunk_metadata_query(
+        let updated_chunk = update_chunk_metadata_query(
             chunk_metadata.clone().into(),
             Some(chunk_group_ids.clone()),
             payload.dataset_id,
@@ -1172,6 +1347,8 @@ async fn update_chunk(
             // If the chunk is a collision, we don't want to update the qdrant point
             chunk_metadata.into(),
             embedding_vector,
+            field_embedding,
+            payload.vector_fields.clone(),
             Some(chunk_group_ids),
             payload.dataset_id,
             splade_vector,
@@ -1181,8 +1358,10 @@ async fn update_chunk(
         )
         .await
         .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+        updated_chunk
     } else {
-        update_chunk_metadata_query(
+        let updated_chunk = update_chunk_metadata_query(
             chunk_metadata.clone().into(),
             None,
             payload.dataset_id,
@@ -1194,6 +1373,8 @@ async fn update_chunk(
             // If the chunk is a collision, we don't want to update the qdrant point
             chunk_metadata.into(),
             embedding_vector,
+            field_embedding,
+            payload.vector_fields.clone(),
             None,
             payload.dataset_id,
             splade_vector,
@@ -1203,9 +1384,43 @@ async fn update_chunk(
         )
         .await
         .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+        updated_chunk
+    };
+
+    record_chunk_versions(
+        vec![ChunkMetadataVersionPG::from_details(
+            updated_chunk,
+            ChunkVersionAction::Update,
+            payload.actor,
+        )],
+        web_pool,
+    )
+    .await;
+
+    Ok(dense_cache_hit && sparse_cache_hit)
+}
+
+/// Versions are recorded once the chunks are ingested and the cached answers citing updated chunks are invalidated, failing either does not fail the ingestion
+async fn record_chunk_versions(
+    versions: Vec<ChunkMetadataVersionPG>,
+    web_pool: actix_web::web::Data<models::Pool>,
+) {
+    let updated_chunk_ids = versions
+        .iter()
+        .filter(|version| {
+            ChunkVersionAction::from(version.action.clone()) == ChunkVersionAction::Update
+        })
+        .map(|version| version.chunk_id)
+        .collect::<Vec<uuid::Uuid>>();
+
+    if let Err(err) = invalidate_cached_answers_query(updated_chunk_ids, web_pool.clone()).await {
+        log::error!("Failed to invalidate cached answers: {:?}", err);
     }
 
-    Ok(())
+    if let Err(err) = insert_chunk_metadata_versions_query(versions, web_pool).await {
+        log::error!("Failed to record chunk versions: {:?}", err);
+    }
 }
 
 #[tracing::instrument(skip(redis_pool, event_queue))]
@@ -1,6 +1,6 @@
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 use trieve_server::{
-    data::models::{MigratePointMessage, MigrationMode},
+    data::models::{DatasetLanguage, MigratePointMessage, MigrationMode},
     errors::ServiceError,
     get_env,
     operators::qdrant_operator::scroll_qdrant_collection_ids,
@@ -74,6 +74,7 @@ async fn main() -> Result<(), ServiceError> {
                     average_len: 256.0,
                     b: 0.75,
                     k: 1.2,
+                    language: DatasetLanguage::English,
                 },
             })
             .expect("Failed to serialze MigratePoint message");
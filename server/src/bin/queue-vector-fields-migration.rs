@@ -0,0 +1,170 @@
+use qdrant_client::qdrant::Distance;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use trieve_server::{
+    data::models::{MigratePointMessage, MigrationMode},
+    errors::ServiceError,
+    get_env,
+    operators::qdrant_operator::{
+        create_qdrant_collection_query, create_qdrant_payload_indexes_query, get_qdrant_connection,
+        get_vector_fields_collection_name, get_vector_fields_migration_stage,
+        scroll_qdrant_collection_ids, VectorFieldsMigrationStage,
+    },
+};
+
+/// Copies every collection into a `{collection}_fields` collection with the vector field slots, the reindex-worker backfills the field vectors of each point.
+/// Run it once the server and workers have VECTOR_FIELDS_MIGRATION=dual_write, so chunks written during the copy land in both collections.
+/// Once the reindex-worker has drained the queue, set VECTOR_FIELDS_MIGRATION=migrated to read and write only the new collections.
+#[allow(clippy::print_stdout)]
+#[tokio::main]
+async fn main() -> Result<(), ServiceError> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::Registry::default()
+        .with(
+            tracing_subscriber::fmt::layer().with_filter(
+                EnvFilter::from_default_env()
+                    .add_directive(tracing_subscriber::filter::LevelFilter::INFO.into()),
+            ),
+        )
+        .init();
+
+    if get_vector_fields_migration_stage() != VectorFieldsMigrationStage::DualWrite {
+        return Err(ServiceError::BadRequest(
+            "Set VECTOR_FIELDS_MIGRATION=dual_write on the server and workers before copying collections"
+                .to_string(),
+        ));
+    }
+
+    let redis_url = get_env!("REDIS_URL", "REDIS_URL is not set");
+    let redis_connections: u32 = std::env::var("REDIS_CONNECTIONS")
+        .unwrap_or("2".to_string())
+        .parse()
+        .unwrap_or(2);
+
+    let redis_manager =
+        bb8_redis::RedisConnectionManager::new(redis_url).expect("Failed to connect to redis");
+
+    let redis_pool = bb8_redis::bb8::Pool::builder()
+        .max_size(redis_connections)
+        .connection_timeout(std::time::Duration::from_secs(2))
+        .build(redis_manager)
+        .await
+        .expect("Failed to create redis pool");
+
+    let web_redis_pool = actix_web::web::Data::new(redis_pool);
+    let vector_sizes: Vec<u64> = std::env::var("VECTOR_SIZES")
+        .unwrap_or("384,512,768,1024,1536,3072".to_string())
+        .split(',')
+        .map(|x| x.parse().ok())
+        .collect::<Option<Vec<u64>>>()
+        .unwrap_or(vec![384, 512, 768, 1024, 1536, 3072]);
+
+    let quantize = std::env::var("QUANTIZE_VECTORS")
+        .unwrap_or("false".to_string())
+        .parse()
+        .unwrap_or(false);
+
+    let replication_factor: u32 = std::env::var("REPLICATION_FACTOR")
+        .unwrap_or("2".to_string())
+        .parse()
+        .unwrap_or(2);
+
+    let collections: Vec<(String, u64, Distance)> = vector_sizes
+        .iter()
+        .flat_map(|size| {
+            vec![
+                (format!("{}_vectors", size), *size, Distance::Cosine),
+                (
+                    format!("{}_vectors_manhattan", size),
+                    *size,
+                    Distance::Manhattan,
+                ),
+                (format!("{}_vectors_dot", size), *size, Distance::Dot),
+                (
+                    format!("{}_vectors_euclidian", size),
+                    *size,
+                    Distance::Euclid,
+                ),
+            ]
+        })
+        .collect();
+
+    let qdrant_client = get_qdrant_connection(None, None).await?;
+
+    for (collection, size, distance) in collections {
+        let collection_exists = qdrant_client
+            .collection_exists(collection.clone())
+            .await
+            .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+
+        if !collection_exists {
+            log::info!("skipping collection {:?} as it does not exist", collection);
+            continue;
+        }
+
+        let to_collection = get_vector_fields_collection_name(&collection);
+
+        let to_collection_exists = qdrant_client
+            .collection_exists(to_collection.clone())
+            .await
+            .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+
+        if !to_collection_exists {
+            create_qdrant_collection_query(
+                &qdrant_client,
+                to_collection.clone(),
+                size,
+                distance,
+                quantize,
+                replication_factor,
+            )
+            .await?;
+
+            create_qdrant_payload_indexes_query(&qdrant_client, to_collection.clone()).await?;
+        }
+
+        log::info!("queue'ing collection: {:?}", collection);
+
+        let mut offset = Some(uuid::Uuid::nil().to_string());
+
+        while let Some(cur_offset) = offset.clone() {
+            let (qdrant_point_ids, new_offset) = scroll_qdrant_collection_ids(
+                collection.clone(),
+                Some(cur_offset.to_string()),
+                Some(1000),
+            )
+            .await?;
+
+            let mut conn = web_redis_pool
+                .get()
+                .await
+                .expect("Failed to connect to redis");
+
+            let message = serde_json::to_string(&MigratePointMessage {
+                qdrant_point_ids: qdrant_point_ids.clone(),
+                from_collection: collection.clone(),
+                to_collection: to_collection.clone(),
+                mode: MigrationMode::VectorFields,
+            })
+            .expect("Failed to serialze MigratePoint message");
+
+            redis::cmd("lpush")
+                .arg("collection_migration")
+                .arg(&message)
+                .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *conn)
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest("Failed to send message to redis".to_string())
+                })?;
+
+            log::info!(
+                "Migrated {:?} points between {:?} and {:?}",
+                qdrant_point_ids.len(),
+                offset.clone(),
+                new_offset.clone()
+            );
+            offset = new_offset;
+        }
+    }
+
+    Ok(())
+}
@@ -1,15 +1,27 @@
+use diesel_async::pooled_connection::{AsyncDieselConnectionManager, ManagerConfig};
 use itertools::Itertools;
 #[allow(deprecated)]
 use qdrant_client::{
-    qdrant::{self, GetPointsBuilder, PointId, RetrievedPoint, UpsertPointsBuilder},
+    qdrant::{
+        self, vectors::VectorsOptions, GetPointsBuilder, PointId, RetrievedPoint,
+        UpsertPointsBuilder, Vector,
+    },
     Qdrant,
 };
+use std::collections::HashMap;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 use trieve_server::{
-    data::models::{MigratePointMessage, MigrationMode},
+    data::models::{
+        Bm25ReindexMessage, DatasetConfiguration, DatasetLanguage, MigratePointMessage,
+        MigrationMode, Pool, RedisPool, UnifiedId,
+    },
     errors::ServiceError,
-    get_env,
-    operators::{model_operator::get_bm25_embeddings, qdrant_operator::get_qdrant_connection},
+    establish_connection, get_env,
+    operators::{
+        dataset_operator::{get_dataset_by_id_query, queue_bm25_reindex_points_query},
+        model_operator::{get_bm25_embeddings, get_vector_field_embeddings},
+        qdrant_operator::get_qdrant_connection,
+    },
 };
 
 #[allow(clippy::print_stdout)]
@@ -38,6 +50,24 @@ async fn main() -> Result<(), ServiceError> {
         .expect("Failed to create redis pool");
 
     let web_redis_pool = actix_web::web::Data::new(redis_pool);
+
+    let database_url = get_env!("DATABASE_URL", "DATABASE_URL is not set");
+
+    let mut config = ManagerConfig::default();
+    config.custom_setup = Box::new(establish_connection);
+
+    let mgr = AsyncDieselConnectionManager::<diesel_async::AsyncPgConnection>::new_with_config(
+        database_url,
+        config,
+    );
+
+    let pool = diesel_async::pooled_connection::deadpool::Pool::builder(mgr)
+        .max_size(3)
+        .build()
+        .expect("Failed to create diesel_async pool");
+
+    let web_pool = actix_web::web::Data::new(pool);
+
     let mut connection = web_redis_pool
         .get()
         .await
@@ -47,12 +77,13 @@ async fn main() -> Result<(), ServiceError> {
 
     loop {
         let payload_result: Result<Vec<String>, redis::RedisError> = redis::cmd("brpop")
+            .arg("bm25_reindex")
             .arg("collection_migration")
             .arg(1)
             .query_async(&mut *connection)
             .await;
 
-        let serialized_message = match payload_result {
+        let (queue, serialized_message) = match payload_result {
             Ok(payload) => {
                 broken_pipe_sleep = std::time::Duration::from_secs(10);
 
@@ -61,10 +92,16 @@ async fn main() -> Result<(), ServiceError> {
                     continue;
                 }
 
-                payload
-                    .get(1)
-                    .expect("Payload must have a first element")
-                    .clone()
+                (
+                    payload
+                        .first()
+                        .expect("Payload must have a first element")
+                        .clone(),
+                    payload
+                        .get(1)
+                        .expect("Payload must have a second element")
+                        .clone(),
+                )
             }
             Err(err) => {
                 log::error!("Unable to process {:?}", err);
@@ -79,6 +116,23 @@ async fn main() -> Result<(), ServiceError> {
             }
         };
 
+        if queue == "bm25_reindex" {
+            if let Err(e) = queue_bm25_reindex(
+                &serialized_message,
+                web_pool.clone(),
+                web_redis_pool.clone(),
+            )
+            .await
+            {
+                log::error!(
+                    "Error queueing bm25 reindex {:?} {:?}",
+                    e,
+                    serialized_message
+                );
+            }
+            continue;
+        }
+
         let migration_message: MigratePointMessage = match serde_json::from_str(&serialized_message)
         {
             Ok(message) => message,
@@ -127,7 +181,12 @@ async fn main() -> Result<(), ServiceError> {
             .result;
 
         let result = match migration_message.mode {
-            MigrationMode::BM25 { average_len, k, b } => {
+            MigrationMode::BM25 {
+                average_len,
+                k,
+                b,
+                language,
+            } => {
                 migrate_bm25(
                     qdrant_client,
                     points,
@@ -135,6 +194,16 @@ async fn main() -> Result<(), ServiceError> {
                     average_len,
                     b,
                     k,
+                    language,
+                )
+                .await
+            }
+            MigrationMode::VectorFields => {
+                migrate_vector_fields(
+                    qdrant_client,
+                    points,
+                    migration_message.to_collection,
+                    web_pool.clone(),
                 )
                 .await
             }
@@ -161,6 +230,134 @@ async fn main() -> Result<(), ServiceError> {
     }
 }
 
+/// Queues every chunk of a dataset as collection_migration messages which recompute its BM25 vectors with the dataset's current configuration
+async fn queue_bm25_reindex(
+    serialized_message: &str,
+    pool: actix_web::web::Data<Pool>,
+    redis_pool: actix_web::web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    let reindex_message: Bm25ReindexMessage = serde_json::from_str(serialized_message)
+        .map_err(|_| ServiceError::BadRequest("Failed to deserialize message".to_string()))?;
+
+    let dataset = get_dataset_by_id_query(
+        UnifiedId::TrieveUuid(reindex_message.dataset_id),
+        pool.clone(),
+    )
+    .await?;
+    let dataset_config = DatasetConfiguration::from_json(dataset.server_configuration);
+
+    log::info!("Queueing bm25 reindex of dataset {}", dataset.id);
+
+    queue_bm25_reindex_points_query(dataset.id, dataset_config, pool, redis_pool).await
+}
+
+/// Texts of the dataset's vector fields for a point, from the vector_fields kept in its payload or else from the string values of its metadata with the same keys
+fn get_point_vector_fields(
+    point: &qdrant::PointStruct,
+    dataset_vector_fields: &[String],
+) -> Option<HashMap<String, String>> {
+    let metadata: Option<serde_json::Value> = point
+        .payload
+        .get("metadata")
+        .cloned()
+        .map(|value| value.into());
+
+    let mut vector_fields: HashMap<String, String> = dataset_vector_fields
+        .iter()
+        .filter_map(|field| {
+            let content = metadata.as_ref()?.get(field)?.as_str()?;
+            Some((field.clone(), content.to_string()))
+        })
+        .collect();
+
+    if let Some(stored_vector_fields) = point
+        .payload
+        .get("vector_fields")
+        .cloned()
+        .and_then(|value| serde_json::from_value::<HashMap<String, String>>(value.into()).ok())
+    {
+        vector_fields.extend(stored_vector_fields);
+    }
+
+    Some(vector_fields).filter(|vector_fields| !vector_fields.is_empty())
+}
+
+/// Copies points into a collection with the vector field slots and backfills the field vectors of chunks whose dataset has VECTOR_FIELDS
+#[tracing::instrument(skip(qdrant_client, points, pool))]
+pub async fn migrate_vector_fields(
+    qdrant_client: Qdrant,
+    points: Vec<RetrievedPoint>,
+    to_collection: String,
+    pool: actix_web::web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    let reqwest_client = reqwest::Client::new();
+
+    let mut new_points = points
+        .iter()
+        .map(|point| qdrant::PointStruct {
+            id: point.id.clone(),
+            payload: point.payload.clone(),
+            vectors: point.vectors.clone(),
+        })
+        .collect_vec();
+
+    let points_by_dataset = new_points
+        .iter_mut()
+        .filter_map(|point| {
+            let dataset_id = point
+                .payload
+                .get("dataset_id")?
+                .as_str()?
+                .parse::<uuid::Uuid>()
+                .ok()?;
+            Some((dataset_id, point))
+        })
+        .into_group_map();
+
+    for (dataset_id, dataset_points) in points_by_dataset {
+        // Points of deleted datasets are copied as they are and removed by sync-qdrant
+        let dataset_config =
+            match get_dataset_by_id_query(UnifiedId::TrieveUuid(dataset_id), pool.clone()).await {
+                Ok(dataset) => DatasetConfiguration::from_json(dataset.server_configuration),
+                Err(_) => continue,
+            };
+
+        if dataset_config.VECTOR_FIELDS.is_empty() {
+            continue;
+        }
+
+        let vector_fields = dataset_points
+            .iter()
+            .map(|point| get_point_vector_fields(point, &dataset_config.VECTOR_FIELDS))
+            .collect_vec();
+
+        let field_embeddings =
+            get_vector_field_embeddings(vector_fields, &dataset_config, reqwest_client.clone())
+                .await?;
+
+        for (point, field_embedding) in dataset_points.into_iter().zip(field_embeddings) {
+            if let Some(VectorsOptions::Vectors(named_vectors)) = point
+                .vectors
+                .as_mut()
+                .and_then(|vectors| vectors.vectors_options.as_mut())
+            {
+                named_vectors.vectors.extend(
+                    field_embedding
+                        .into_iter()
+                        .map(|(vector_name, vector)| (vector_name, Vector::from(vector))),
+                );
+            }
+        }
+    }
+
+    qdrant_client
+        .upsert_points(UpsertPointsBuilder::new(to_collection, new_points))
+        .await
+        .map_err(|e| ServiceError::BadRequest(format!("Failed to upsert points {:?}", e)))?;
+
+    Ok(())
+}
+
 #[tracing::instrument(skip(qdrant_client, points))]
 pub async fn migrate_bm25(
     qdrant_client: Qdrant,
@@ -169,6 +366,7 @@ pub async fn migrate_bm25(
     average_len: f32,
     b: f32,
     k: f32,
+    language: DatasetLanguage,
 ) -> Result<(), ServiceError> {
     // Insert points into new collection
     let new_points = points
@@ -184,7 +382,8 @@ pub async fn migrate_bm25(
             };
 
             // calculate bm25
-            let bm25_embeddings = get_bm25_embeddings(vec![(content, None)], average_len, b, k);
+            let bm25_embeddings =
+                get_bm25_embeddings(vec![(content, None)], average_len, b, k, language);
 
             let bm25_embedding = bm25_embeddings.first().expect("BM25 Vectors");
 
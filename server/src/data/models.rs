@@ -168,6 +168,8 @@ impl Topic {
     "created_at": "2021-01-01 00:00:00.000",
     "updated_at": "2021-01-01 00:00:00.000",
     "dataset_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "citations": [{"doc": 1, "chunk_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3", "start": 42, "end": 45}],
+    "summarized_through": null,
 }))]
 #[diesel(table_name = messages)]
 pub struct Message {
@@ -182,6 +184,10 @@ pub struct Message {
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
     pub dataset_id: uuid::Uuid,
+    /// The `[n]` doc citations of an assistant message resolved to the chunks they refer to. This is a list of MessageCitation.
+    pub citations: Option<serde_json::Value>,
+    /// Sort order of the last message a chat history summary covers. Only set on summaries.
+    pub summarized_through: Option<i32>,
 }
 
 impl From<Message> for ChatMessage {
@@ -268,8 +274,34 @@ impl Message {
             created_at: chrono::Utc::now().naive_local(),
             updated_at: chrono::Utc::now().naive_local(),
             dataset_id: dataset_id.into(),
+            citations: None,
+            summarized_through: None,
         }
     }
+
+    pub fn with_citations(mut self, citations: &[MessageCitation]) -> Self {
+        self.citations = Some(serde_json::json!(citations));
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+#[schema(example = json!({
+    "doc": 1,
+    "chunk_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "start": 42,
+    "end": 45,
+}))]
+/// A `[n]` doc citation in a completion resolved to the chunk which was given to the LLM as doc n.
+pub struct MessageCitation {
+    /// The doc number used by the LLM, 1-indexed in the order the chunks were placed into the context window.
+    pub doc: usize,
+    /// Id of the chunk which was placed into the context window as the doc.
+    pub chunk_id: uuid::Uuid,
+    /// Character offset of the opening bracket of the citation within the completion.
+    pub start: usize,
+    /// Character offset just past the closing bracket of the citation within the completion.
+    pub end: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, ToSchema)]
@@ -768,6 +800,8 @@ impl From<ChunkMetadataWithScore> for ScoreChunk {
             chunk: NewChunkMetadataTypes::Metadata(val.clone().into()),
             highlights: None,
             score: val.score,
+            hybrid_legs: None,
+            merchandising_rule_ids: None,
         }
     }
 }
@@ -865,6 +899,12 @@ pub struct ScoreChunkDTO {
     pub metadata: Vec<ChunkMetadataTypes>,
     pub highlights: Option<Vec<String>>,
     pub score: f64,
+    /// The hybrid search legs which retrieved this chunk. Only present for hybrid searches.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub hybrid_legs: Option<Vec<HybridLeg>>,
+    /// Ids of the merchandising rules which pinned, buried or boosted this chunk. Only present if a rule affected the chunk.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
@@ -886,6 +926,12 @@ pub struct ScoreChunk {
     pub chunk: NewChunkMetadataTypes,
     pub highlights: Option<Vec<String>>,
     pub score: f32,
+    /// The hybrid search legs which retrieved this chunk. Only present for hybrid searches.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub hybrid_legs: Option<Vec<HybridLeg>>,
+    /// Ids of the merchandising rules which pinned, buried or boosted this chunk. Only present if a rule affected the chunk.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
@@ -983,6 +1029,8 @@ impl From<ScoreChunkDTO> for ScoreChunk {
             chunk: score_chunk_dto.metadata[0].clone().into(),
             highlights: score_chunk_dto.highlights,
             score: score_chunk_dto.score as f32,
+            hybrid_legs: score_chunk_dto.hybrid_legs,
+            merchandising_rule_ids: score_chunk_dto.merchandising_rule_ids,
         }
     }
 }
@@ -1753,6 +1801,294 @@ impl From<WorkerEvent> for WorkerEventClickhouse {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAuthMethod {
+    #[display(fmt = "session")]
+    Session,
+    #[display(fmt = "api_key")]
+    ApiKey,
+    #[display(fmt = "admin_api_key")]
+    AdminApiKey,
+    #[display(fmt = "stripe_webhook")]
+    StripeWebhook,
+    #[display(fmt = "scim_token")]
+    ScimToken,
+}
+
+impl From<String> for AuditAuthMethod {
+    fn from(auth_method: String) -> Self {
+        match auth_method.as_str() {
+            "api_key" => AuditAuthMethod::ApiKey,
+            "admin_api_key" => AuditAuthMethod::AdminApiKey,
+            "stripe_webhook" => AuditAuthMethod::StripeWebhook,
+            "scim_token" => AuditAuthMethod::ScimToken,
+            _ => AuditAuthMethod::Session,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    #[display(fmt = "dataset_updated")]
+    DatasetUpdated,
+    #[display(fmt = "dataset_deleted")]
+    DatasetDeleted,
+    #[display(fmt = "api_key_created")]
+    ApiKeyCreated,
+    #[display(fmt = "api_key_rotated")]
+    ApiKeyRotated,
+    #[display(fmt = "api_key_updated")]
+    ApiKeyUpdated,
+    #[display(fmt = "api_key_deleted")]
+    ApiKeyDeleted,
+    #[display(fmt = "user_role_updated")]
+    UserRoleUpdated,
+    #[display(fmt = "user_removed_from_organization")]
+    UserRemovedFromOrganization,
+    #[display(fmt = "subscription_created")]
+    SubscriptionCreated,
+    #[display(fmt = "subscription_plan_updated")]
+    SubscriptionPlanUpdated,
+    #[display(fmt = "subscription_canceled")]
+    SubscriptionCanceled,
+    #[display(fmt = "sso_config_updated")]
+    SsoConfigUpdated,
+    #[display(fmt = "sso_config_deleted")]
+    SsoConfigDeleted,
+    #[display(fmt = "domain_verified")]
+    DomainVerified,
+    #[display(fmt = "user_added_to_organization")]
+    UserAddedToOrganization,
+    #[display(fmt = "scim_token_created")]
+    ScimTokenCreated,
+    #[display(fmt = "scim_token_deleted")]
+    ScimTokenDeleted,
+}
+
+impl AuditAction {
+    /// Kind of entity whose id is stored as the target of the event
+    pub fn target_type(&self) -> &'static str {
+        match self {
+            AuditAction::DatasetUpdated | AuditAction::DatasetDeleted => "dataset",
+            AuditAction::ApiKeyCreated
+            | AuditAction::ApiKeyRotated
+            | AuditAction::ApiKeyUpdated
+            | AuditAction::ApiKeyDeleted => "api_key",
+            AuditAction::UserRoleUpdated
+            | AuditAction::UserRemovedFromOrganization
+            | AuditAction::UserAddedToOrganization => "user",
+            AuditAction::SubscriptionCreated
+            | AuditAction::SubscriptionPlanUpdated
+            | AuditAction::SubscriptionCanceled => "subscription",
+            AuditAction::SsoConfigUpdated
+            | AuditAction::SsoConfigDeleted
+            | AuditAction::ScimTokenCreated
+            | AuditAction::ScimTokenDeleted => "organization",
+            AuditAction::DomainVerified => "domain",
+        }
+    }
+}
+
+/// Who made a request which is recorded in the audit log and how they authenticated
+#[derive(Debug, Clone)]
+pub struct AuditActor {
+    pub user_id: Option<uuid::Uuid>,
+    pub auth_method: AuditAuthMethod,
+    pub api_key_id: Option<uuid::Uuid>,
+    pub ip_address: Option<String>,
+}
+
+impl AuditActor {
+    pub fn stripe_webhook() -> Self {
+        AuditActor {
+            user_id: None,
+            auth_method: AuditAuthMethod::StripeWebhook,
+            api_key_id: None,
+            ip_address: None,
+        }
+    }
+
+    pub fn scim_token(ip_address: Option<String>) -> Self {
+        AuditActor {
+            user_id: None,
+            auth_method: AuditAuthMethod::ScimToken,
+            api_key_id: None,
+            ip_address,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Row)]
+pub struct AuditEventClickhouse {
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub id: uuid::Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub organization_id: uuid::Uuid,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub actor_user_id: uuid::Uuid,
+    pub auth_method: String,
+    #[serde(with = "clickhouse::serde::uuid")]
+    pub api_key_id: uuid::Uuid,
+    pub ip_address: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub diff: String,
+    #[serde(with = "clickhouse::serde::time::datetime")]
+    pub created_at: OffsetDateTime,
+}
+
+impl AuditEventClickhouse {
+    pub fn from_details(
+        organization_id: uuid::Uuid,
+        actor: &AuditActor,
+        action: AuditAction,
+        target_id: String,
+        diff: serde_json::Value,
+    ) -> Self {
+        AuditEventClickhouse {
+            id: uuid::Uuid::new_v4(),
+            organization_id,
+            actor_user_id: actor.user_id.unwrap_or_default(),
+            auth_method: actor.auth_method.to_string(),
+            api_key_id: actor.api_key_id.unwrap_or_default(),
+            ip_address: actor.ip_address.clone().unwrap_or_default(),
+            action: action.to_string(),
+            target_type: action.target_type().to_string(),
+            target_id,
+            diff: diff.to_string(),
+            created_at: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example=json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "organization_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "actor_user_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "auth_method": "api_key",
+    "api_key_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "ip_address": "203.0.113.7",
+    "action": "dataset_updated",
+    "target_type": "dataset",
+    "target_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "diff": {"server_configuration.RAG_PROMPT": {"before": "Use the following retrieved documents to respond briefly and accurately:", "after": "Answer in one sentence:"}},
+    "created_at": "2021-01-01 00:00:00.000",
+}))]
+pub struct AuditEvent {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    /// Id of the user who made the change. Not present for changes made by Stripe or over SCIM.
+    pub actor_user_id: Option<uuid::Uuid>,
+    pub auth_method: AuditAuthMethod,
+    /// Id of the api key the change was made with. Only present when auth_method is api_key.
+    pub api_key_id: Option<uuid::Uuid>,
+    pub ip_address: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    /// Fields which were changed, mapping the path of each field to its value before and after the change.
+    pub diff: serde_json::Value,
+    pub created_at: String,
+}
+
+impl From<AuditEventClickhouse> for AuditEvent {
+    fn from(clickhouse_event: AuditEventClickhouse) -> Self {
+        let non_nil = |id: uuid::Uuid| Some(id).filter(|id| !id.is_nil());
+
+        AuditEvent {
+            id: uuid::Uuid::from_bytes(*clickhouse_event.id.as_bytes()),
+            organization_id: uuid::Uuid::from_bytes(*clickhouse_event.organization_id.as_bytes()),
+            actor_user_id: non_nil(clickhouse_event.actor_user_id),
+            auth_method: AuditAuthMethod::from(clickhouse_event.auth_method),
+            api_key_id: non_nil(clickhouse_event.api_key_id),
+            ip_address: Some(clickhouse_event.ip_address).filter(|ip| !ip.is_empty()),
+            action: clickhouse_event.action,
+            target_type: clickhouse_event.target_type,
+            target_id: clickhouse_event.target_id,
+            diff: serde_json::from_str(&clickhouse_event.diff).unwrap_or_default(),
+            created_at: clickhouse_event.created_at.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct AuditLogFilter {
+    pub date_range: Option<DateRange>,
+    /// Only return events with one of these actions
+    pub actions: Option<Vec<AuditAction>>,
+    /// Only return events made by this user
+    pub actor_user_id: Option<uuid::Uuid>,
+    /// Only return events made with this api key
+    pub api_key_id: Option<uuid::Uuid>,
+    /// Only return events targeting this dataset, api key, user or subscription
+    pub target_id: Option<String>,
+}
+
+/// Bound of an audit log date range as a UTC timestamp. Timestamps without an offset are taken to be in UTC like the stored events.
+fn parse_audit_date(date: &str) -> Result<NaiveDateTime, ServiceError> {
+    let date = date.trim();
+
+    chrono::DateTime::parse_from_rfc3339(date)
+        .map(|date| date.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S%.f"))
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        })
+        .map_err(|_| ServiceError::BadRequest(format!("Invalid timestamp format: {}", date)))
+}
+
+impl AuditLogFilter {
+    pub fn add_to_query(&self, mut query_string: String) -> Result<String, ServiceError> {
+        if let Some(date_range) = &self.date_range {
+            for (operator, date) in [
+                (">", &date_range.gt),
+                ("<", &date_range.lt),
+                (">=", &date_range.gte),
+                ("<=", &date_range.lte),
+            ] {
+                if let Some(date) = date {
+                    query_string.push_str(&format!(
+                        " AND created_at {} '{}'",
+                        operator,
+                        parse_audit_date(date)?.format("%Y-%m-%d %H:%M:%S%.3f")
+                    ));
+                }
+            }
+        }
+
+        if let Some(actions) = self.actions.as_ref().filter(|actions| !actions.is_empty()) {
+            query_string.push_str(&format!(
+                " AND action IN ({})",
+                actions
+                    .iter()
+                    .map(|action| format!("'{}'", action))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ));
+        }
+        if let Some(actor_user_id) = &self.actor_user_id {
+            query_string.push_str(&format!(" AND actor_user_id = '{}'", actor_user_id));
+        }
+        if let Some(api_key_id) = &self.api_key_id {
+            query_string.push_str(&format!(" AND api_key_id = '{}'", api_key_id));
+        }
+        if let Some(target_id) = &self.target_id {
+            query_string.push_str(&format!(
+                " AND target_id = '{}'",
+                target_id.replace('\\', "\\\\").replace('\'', "\\'")
+            ));
+        }
+
+        Ok(query_string)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Display, ToSchema)]
 #[serde(untagged)]
 pub enum EventType {
@@ -1764,9 +2100,22 @@ pub enum EventType {
     #[display(fmt = "file_upload_failed")]
     FileUploadFailed { file_id: uuid::Uuid, error: String },
     #[display(fmt = "chunks_uploaded")]
-    ChunksUploaded { chunk_ids: Vec<uuid::Uuid> },
+    ChunksUploaded {
+        chunk_ids: Vec<uuid::Uuid>,
+        /// Chunks whose dense and sparse vectors were reused from the embedding cache
+        #[serde(default)]
+        chunks_skipped_embedding: usize,
+        /// Chunks which had to be embedded
+        #[serde(default)]
+        chunks_embedded: usize,
+    },
     #[display(fmt = "chunk_updated")]
-    ChunkUpdated { chunk_id: uuid::Uuid },
+    ChunkUpdated {
+        chunk_id: uuid::Uuid,
+        /// Whether the vectors of the updated chunk were reused from the embedding cache
+        #[serde(default)]
+        embedding_skipped: bool,
+    },
     #[display(fmt = "bulk_chunks_deleted")]
     BulkChunksDeleted { message: String },
     #[display(fmt = "dataset_delete_failed")]
@@ -1786,6 +2135,10 @@ pub enum EventType {
         pages_crawled: usize,
         chunks_created: usize,
         crawl_options: CrawlOptions,
+        #[serde(default)]
+        pages_unchanged: usize,
+        #[serde(default)]
+        chunks_deleted: usize,
     },
     #[display(fmt = "crawl_failed")]
     CrawlFailed {
@@ -1793,6 +2146,27 @@ pub enum EventType {
         crawl_options: CrawlOptions,
         error: String,
     },
+    #[display(fmt = "dataset_imported")]
+    DatasetImported {
+        import_id: uuid::Uuid,
+        chunks_imported: usize,
+        chunks_queued_for_embedding: usize,
+    },
+    #[display(fmt = "dataset_import_failed")]
+    DatasetImportFailed {
+        import_id: uuid::Uuid,
+        error: String,
+    },
+    #[display(fmt = "dataset_exported")]
+    DatasetExported {
+        export_id: uuid::Uuid,
+        chunk_count: usize,
+    },
+    #[display(fmt = "dataset_export_failed")]
+    DatasetExportFailed {
+        export_id: uuid::Uuid,
+        error: String,
+    },
 }
 
 impl EventType {
@@ -1810,6 +2184,10 @@ impl EventType {
             EventTypeRequest::GroupChunksActionFailed,
             EventTypeRequest::CrawlCompleted,
             EventTypeRequest::CrawlFailed,
+            EventTypeRequest::DatasetImported,
+            EventTypeRequest::DatasetImportFailed,
+            EventTypeRequest::DatasetExported,
+            EventTypeRequest::DatasetExportFailed,
         ]
     }
 }
@@ -1886,7 +2264,8 @@ pub struct DatasetEventCount {
         "INDEXED_ONLY": false,
         "LOCKED": false,
         "SYSTEM_PROMPT": "You are a helpful assistant",
-        "MAX_LIMIT": 10000
+        "MAX_LIMIT": 10000,
+        "VECTOR_FIELDS": ["title", "summary"]
     },
 }))]
 #[diesel(table_name = datasets)]
@@ -2009,6 +2388,106 @@ pub enum DistanceMetric {
     Dot,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Display, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+/// The backend which creates a dataset's embeddings or reranks its results. local runs ONNX models in process and needs the server to be built with the local-embeddings feature.
+pub enum EmbeddingProviderType {
+    #[display(fmt = "openai_compatible")]
+    OpenaiCompatible,
+    #[display(fmt = "cohere")]
+    Cohere,
+    #[display(fmt = "tei")]
+    Tei,
+    #[display(fmt = "local")]
+    Local,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Display, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+/// The language a dataset's text is analyzed in for BM25 and highlighting. It selects the tokenizer, stemmer and stop words, chinese, japanese and korean text is split into character n-grams instead of words.
+pub enum DatasetLanguage {
+    #[default]
+    #[display(fmt = "english")]
+    English,
+    #[display(fmt = "arabic")]
+    Arabic,
+    #[display(fmt = "danish")]
+    Danish,
+    #[display(fmt = "dutch")]
+    Dutch,
+    #[display(fmt = "finnish")]
+    Finnish,
+    #[display(fmt = "french")]
+    French,
+    #[display(fmt = "german")]
+    German,
+    #[display(fmt = "greek")]
+    Greek,
+    #[display(fmt = "hungarian")]
+    Hungarian,
+    #[display(fmt = "italian")]
+    Italian,
+    #[display(fmt = "norwegian")]
+    Norwegian,
+    #[display(fmt = "portuguese")]
+    Portuguese,
+    #[display(fmt = "romanian")]
+    Romanian,
+    #[display(fmt = "russian")]
+    Russian,
+    #[display(fmt = "spanish")]
+    Spanish,
+    #[display(fmt = "swedish")]
+    Swedish,
+    #[display(fmt = "tamil")]
+    Tamil,
+    #[display(fmt = "turkish")]
+    Turkish,
+    #[display(fmt = "chinese")]
+    Chinese,
+    #[display(fmt = "japanese")]
+    Japanese,
+    #[display(fmt = "korean")]
+    Korean,
+}
+
+impl DatasetLanguage {
+    /// Languages which are not written with spaces between words
+    pub fn is_cjk(&self) -> bool {
+        matches!(
+            self,
+            DatasetLanguage::Chinese | DatasetLanguage::Japanese | DatasetLanguage::Korean
+        )
+    }
+
+    /// The tantivy stemmer and stop word language, None for the CJK languages which are segmented into n-grams
+    pub fn tantivy_language(&self) -> Option<tantivy::tokenizer::Language> {
+        use tantivy::tokenizer::Language;
+
+        match self {
+            DatasetLanguage::English => Some(Language::English),
+            DatasetLanguage::Arabic => Some(Language::Arabic),
+            DatasetLanguage::Danish => Some(Language::Danish),
+            DatasetLanguage::Dutch => Some(Language::Dutch),
+            DatasetLanguage::Finnish => Some(Language::Finnish),
+            DatasetLanguage::French => Some(Language::French),
+            DatasetLanguage::German => Some(Language::German),
+            DatasetLanguage::Greek => Some(Language::Greek),
+            DatasetLanguage::Hungarian => Some(Language::Hungarian),
+            DatasetLanguage::Italian => Some(Language::Italian),
+            DatasetLanguage::Norwegian => Some(Language::Norwegian),
+            DatasetLanguage::Portuguese => Some(Language::Portuguese),
+            DatasetLanguage::Romanian => Some(Language::Romanian),
+            DatasetLanguage::Russian => Some(Language::Russian),
+            DatasetLanguage::Spanish => Some(Language::Spanish),
+            DatasetLanguage::Swedish => Some(Language::Swedish),
+            DatasetLanguage::Tamil => Some(Language::Tamil),
+            DatasetLanguage::Turkish => Some(Language::Turkish),
+            DatasetLanguage::Chinese | DatasetLanguage::Japanese | DatasetLanguage::Korean => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[schema(example=json!({
     "LLM_BASE_URL": "https://api.openai.com/v1",
@@ -2035,7 +2514,8 @@ pub enum DistanceMetric {
     "INDEXED_ONLY": false,
     "LOCKED": false,
     "SYSTEM_PROMPT": "You are a helpful assistant",
-    "MAX_LIMIT": 10000
+    "MAX_LIMIT": 10000,
+    "VECTOR_FIELDS": ["title", "summary"]
 }))]
 #[allow(non_snake_case)]
 pub struct DatasetConfiguration {
@@ -2045,6 +2525,10 @@ pub struct DatasetConfiguration {
     pub EMBEDDING_BASE_URL: String,
     pub EMBEDDING_MODEL_NAME: String,
     pub RERANKER_BASE_URL: String,
+    pub EMBEDDING_PROVIDER: EmbeddingProviderType,
+    pub SPARSE_EMBEDDING_PROVIDER: EmbeddingProviderType,
+    pub RERANKER_PROVIDER: EmbeddingProviderType,
+    pub RERANKER_MODEL_NAME: Option<String>,
     pub MESSAGE_TO_QUERY_PROMPT: String,
     pub RAG_PROMPT: String,
     pub N_RETRIEVALS_TO_INCLUDE: usize,
@@ -2055,6 +2539,7 @@ pub struct DatasetConfiguration {
     pub BM25_B: f32,
     pub BM25_K: f32,
     pub BM25_AVG_LEN: f32,
+    pub LANGUAGE: DatasetLanguage,
     pub FULLTEXT_ENABLED: bool,
     pub SEMANTIC_ENABLED: bool,
     pub EMBEDDING_QUERY_PREFIX: String,
@@ -2069,6 +2554,10 @@ pub struct DatasetConfiguration {
     pub SYSTEM_PROMPT: String,
     pub MAX_LIMIT: u64,
     pub PUBLIC_DATASET: PublicDatasetOptions,
+    pub VECTOR_FIELDS: Vec<String>,
+    pub CHAT_HISTORY_TOKEN_BUDGET: Option<u64>,
+    pub ANSWER_CACHE_DISTANCE_THRESHOLD: Option<f64>,
+    pub RATE_LIMITS: RateLimits,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
@@ -2104,7 +2593,8 @@ pub struct PublicDatasetOptions {
     "INDEXED_ONLY": false,
     "LOCKED": false,
     "SYSTEM_PROMPT": "You are a helpful assistant",
-    "MAX_LIMIT": 10000
+    "MAX_LIMIT": 10000,
+    "VECTOR_FIELDS": ["title", "summary"]
 }))]
 #[allow(non_snake_case)]
 /// Lets you specify the configuration for a dataset
@@ -2120,6 +2610,14 @@ pub struct DatasetConfigurationDTO {
     pub EMBEDDING_MODEL_NAME: Option<String>,
     /// The base URL for the reranker API
     pub RERANKER_BASE_URL: Option<String>,
+    /// The provider which creates the dense embeddings. Defaults to openai_compatible.
+    pub EMBEDDING_PROVIDER: Option<EmbeddingProviderType>,
+    /// The provider which creates the sparse embeddings for fulltext search. Defaults to tei, which calls the SPLADE servers at SPARSE_SERVER_DOC_ORIGIN and SPARSE_SERVER_QUERY_ORIGIN.
+    pub SPARSE_EMBEDDING_PROVIDER: Option<EmbeddingProviderType>,
+    /// The provider which reranks results with a cross encoder. Defaults to tei.
+    pub RERANKER_PROVIDER: Option<EmbeddingProviderType>,
+    /// The name of the reranker model, used by the cohere and local reranker providers
+    pub RERANKER_MODEL_NAME: Option<String>,
     /// The prompt to use for converting a message to a query
     pub MESSAGE_TO_QUERY_PROMPT: Option<String>,
     /// The prompt to use for the RAG model
@@ -2140,6 +2638,8 @@ pub struct DatasetConfigurationDTO {
     pub BM25_K: Option<f32>,
     /// The average length of the chunks in the index for BM25
     pub BM25_AVG_LEN: Option<f32>,
+    /// The language of the dataset's text, it picks the BM25 tokenizer, stemmer and stop words as well as how highlights are matched. Defaults to english. Changing it requeues the dataset's chunks for BM25 reindexing.
+    pub LANGUAGE: Option<DatasetLanguage>,
     /// Whether to use fulltext search
     pub FULLTEXT_ENABLED: Option<bool>,
     /// Whether to use semantic search
@@ -2168,6 +2668,14 @@ pub struct DatasetConfigurationDTO {
     pub MAX_LIMIT: Option<u64>,
     /// Config for making the dataset public
     pub PUBLIC_DATASET: Option<PublicDatasetOptions>,
+    /// Names of the extra fields of a chunk, such as "title" or "summary", which are each embedded into their own vector. Fields can only be appended, at most 4 are allowed and "chunk_html" is reserved for the vector of the chunk itself.
+    pub VECTOR_FIELDS: Option<Vec<String>>,
+    /// The maximum number of tokens of chat history to send to the LLM. Once a topic exceeds it, its older messages are summarized by the LLM into a stored summary message which is sent in their place. If not specified, the whole chat history is sent.
+    pub CHAT_HISTORY_TOKEN_BUDGET: Option<u64>,
+    /// Turns on the answer cache for messages. A new question whose embedding is within this cosine distance of a cached question with the same filters is answered with the cached completion and chunks instead of a new retrieval and LLM call. Cached answers are invalidated when any of their chunks are updated or deleted. If not specified, the cache is off.
+    pub ANSWER_CACHE_DISTANCE_THRESHOLD: Option<f64>,
+    /// Requests per minute the dataset allows to its search, ingest and RAG routes. Classes which are not specified use the defaults of the organization's plan and limits above them are capped at the plan's. Api keys can have lower limits of their own.
+    pub RATE_LIMITS: Option<RateLimits>,
 }
 
 impl From<DatasetConfigurationDTO> for DatasetConfiguration {
@@ -2178,6 +2686,10 @@ impl From<DatasetConfigurationDTO> for DatasetConfiguration {
             EMBEDDING_BASE_URL: dto.EMBEDDING_BASE_URL.unwrap_or("https://api.openai.com/v1".to_string()),
             EMBEDDING_MODEL_NAME: dto.EMBEDDING_MODEL_NAME.unwrap_or("text-embedding-3-small".to_string()),
             RERANKER_BASE_URL: dto.RERANKER_BASE_URL.unwrap_or("".to_string()),
+            EMBEDDING_PROVIDER: dto.EMBEDDING_PROVIDER.unwrap_or(EmbeddingProviderType::OpenaiCompatible),
+            SPARSE_EMBEDDING_PROVIDER: dto.SPARSE_EMBEDDING_PROVIDER.unwrap_or(EmbeddingProviderType::Tei),
+            RERANKER_PROVIDER: dto.RERANKER_PROVIDER.unwrap_or(EmbeddingProviderType::Tei),
+            RERANKER_MODEL_NAME: dto.RERANKER_MODEL_NAME,
             MESSAGE_TO_QUERY_PROMPT: dto.MESSAGE_TO_QUERY_PROMPT.unwrap_or("Write a 1-2 sentence semantic search query along the lines of a hypothetical response to: \n\n".to_string()),
             RAG_PROMPT: dto.RAG_PROMPT.unwrap_or("Use the following retrieved documents to respond briefly and accurately:".to_string()),
             N_RETRIEVALS_TO_INCLUDE: dto.N_RETRIEVALS_TO_INCLUDE.unwrap_or(8),
@@ -2188,6 +2700,7 @@ impl From<DatasetConfigurationDTO> for DatasetConfiguration {
             BM25_B: dto.BM25_B.unwrap_or(0.75),
             BM25_K: dto.BM25_K.unwrap_or(0.75),
             BM25_AVG_LEN: dto.BM25_AVG_LEN.unwrap_or(256.0),
+            LANGUAGE: dto.LANGUAGE.unwrap_or_default(),
             FULLTEXT_ENABLED: dto.FULLTEXT_ENABLED.unwrap_or(true),
             SEMANTIC_ENABLED: dto.SEMANTIC_ENABLED.unwrap_or(true),
             EMBEDDING_QUERY_PREFIX: dto.EMBEDDING_QUERY_PREFIX.unwrap_or("".to_string()),
@@ -2205,6 +2718,10 @@ impl From<DatasetConfigurationDTO> for DatasetConfiguration {
                 enabled: dto.PUBLIC_DATASET.map(|public_dataset| public_dataset.enabled).unwrap_or(false),
                 api_key: "".to_string()
             },
+            VECTOR_FIELDS: dto.VECTOR_FIELDS.unwrap_or_default(),
+            CHAT_HISTORY_TOKEN_BUDGET: dto.CHAT_HISTORY_TOKEN_BUDGET,
+            ANSWER_CACHE_DISTANCE_THRESHOLD: dto.ANSWER_CACHE_DISTANCE_THRESHOLD,
+            RATE_LIMITS: dto.RATE_LIMITS.unwrap_or_default(),
         }
     }
 }
@@ -2217,6 +2734,10 @@ impl From<DatasetConfiguration> for DatasetConfigurationDTO {
             EMBEDDING_BASE_URL: Some(config.EMBEDDING_BASE_URL),
             EMBEDDING_MODEL_NAME: Some(config.EMBEDDING_MODEL_NAME),
             RERANKER_BASE_URL: Some(config.RERANKER_BASE_URL),
+            EMBEDDING_PROVIDER: Some(config.EMBEDDING_PROVIDER),
+            SPARSE_EMBEDDING_PROVIDER: Some(config.SPARSE_EMBEDDING_PROVIDER),
+            RERANKER_PROVIDER: Some(config.RERANKER_PROVIDER),
+            RERANKER_MODEL_NAME: config.RERANKER_MODEL_NAME,
             MESSAGE_TO_QUERY_PROMPT: Some(config.MESSAGE_TO_QUERY_PROMPT),
             RAG_PROMPT: Some(config.RAG_PROMPT),
             N_RETRIEVALS_TO_INCLUDE: Some(config.N_RETRIEVALS_TO_INCLUDE),
@@ -2227,6 +2748,7 @@ impl From<DatasetConfiguration> for DatasetConfigurationDTO {
             BM25_B: Some(config.BM25_B),
             BM25_K: Some(config.BM25_K),
             BM25_AVG_LEN: Some(config.BM25_AVG_LEN),
+            LANGUAGE: Some(config.LANGUAGE),
             FULLTEXT_ENABLED: Some(config.FULLTEXT_ENABLED),
             SEMANTIC_ENABLED: Some(config.SEMANTIC_ENABLED),
             EMBEDDING_QUERY_PREFIX: Some(config.EMBEDDING_QUERY_PREFIX),
@@ -2244,6 +2766,10 @@ impl From<DatasetConfiguration> for DatasetConfigurationDTO {
                 enabled: config.PUBLIC_DATASET.enabled,
                 api_key: "".to_string(),
             }),
+            VECTOR_FIELDS: Some(config.VECTOR_FIELDS),
+            CHAT_HISTORY_TOKEN_BUDGET: config.CHAT_HISTORY_TOKEN_BUDGET,
+            ANSWER_CACHE_DISTANCE_THRESHOLD: config.ANSWER_CACHE_DISTANCE_THRESHOLD,
+            RATE_LIMITS: Some(config.RATE_LIMITS),
         }
     }
 }
@@ -2256,6 +2782,10 @@ impl Default for DatasetConfiguration {
             EMBEDDING_BASE_URL: "https://api.openai.com/v1".to_string(),
             EMBEDDING_MODEL_NAME: "text-embedding-3-small".to_string(),
             RERANKER_BASE_URL: "".to_string(),
+            EMBEDDING_PROVIDER: EmbeddingProviderType::OpenaiCompatible,
+            SPARSE_EMBEDDING_PROVIDER: EmbeddingProviderType::Tei,
+            RERANKER_PROVIDER: EmbeddingProviderType::Tei,
+            RERANKER_MODEL_NAME: None,
             MESSAGE_TO_QUERY_PROMPT: "Write a 1-2 sentence semantic search query along the lines of a hypothetical response to: \n\n".to_string(),
             RAG_PROMPT: "Use the following retrieved documents to respond briefly and accurately:".to_string(),
             N_RETRIEVALS_TO_INCLUDE: 8,
@@ -2266,6 +2796,7 @@ impl Default for DatasetConfiguration {
             BM25_B: 0.75,
             BM25_K: 0.75,
             BM25_AVG_LEN: 256.0,
+            LANGUAGE: DatasetLanguage::English,
             FULLTEXT_ENABLED: true,
             SEMANTIC_ENABLED: true,
             EMBEDDING_QUERY_PREFIX: "".to_string(),
@@ -2283,6 +2814,10 @@ impl Default for DatasetConfiguration {
                 enabled: false,
                 api_key: "".to_string()
             },
+            VECTOR_FIELDS: vec![],
+            CHAT_HISTORY_TOKEN_BUDGET: None,
+            ANSWER_CACHE_DISTANCE_THRESHOLD: None,
+            RATE_LIMITS: RateLimits::default(),
         }
     }
 }
@@ -2412,6 +2947,22 @@ impl DatasetConfiguration {
                         s.to_string()
                     }
                 }).expect("RERANKER_SERVER_ORIGIN should exist"),
+            EMBEDDING_PROVIDER: configuration
+                .get("EMBEDDING_PROVIDER")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or(EmbeddingProviderType::OpenaiCompatible),
+            SPARSE_EMBEDDING_PROVIDER: configuration
+                .get("SPARSE_EMBEDDING_PROVIDER")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or(EmbeddingProviderType::Tei),
+            RERANKER_PROVIDER: configuration
+                .get("RERANKER_PROVIDER")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or(EmbeddingProviderType::Tei),
+            RERANKER_MODEL_NAME: configuration
+                .get("RERANKER_MODEL_NAME")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
             LLM_DEFAULT_MODEL: configuration
                 .get("LLM_DEFAULT_MODEL")
                 .unwrap_or(&json!("gpt-3.5-turbo-1106"))
@@ -2454,6 +3005,10 @@ impl DatasetConfiguration {
                 .get("BM25_AVG_LEN")
                 .and_then(|v| v.as_f64().map(|f| f as f32))
                 .unwrap_or(256f32),
+            LANGUAGE: configuration
+                .get("LANGUAGE")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default(),
             EMBEDDING_QUERY_PREFIX: configuration
                 .get("EMBEDDING_QUERY_PREFIX")
                 .unwrap_or(&{
@@ -2528,7 +3083,27 @@ impl DatasetConfiguration {
             PUBLIC_DATASET: PublicDatasetOptions {
                 enabled: configuration_json.pointer("/PUBLIC_DATASET/enabled").unwrap_or(&json!(false)).as_bool().unwrap_or(false),
                 api_key: configuration_json.pointer("/PUBLIC_DATASET/api_key").unwrap_or(&json!("")).as_str().unwrap_or("").to_string(),
-            }
+            },
+            VECTOR_FIELDS: configuration
+                .get("VECTOR_FIELDS")
+                .and_then(|v| v.as_array())
+                .map(|fields| {
+                    fields
+                        .iter()
+                        .filter_map(|field| field.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            CHAT_HISTORY_TOKEN_BUDGET: configuration
+                .get("CHAT_HISTORY_TOKEN_BUDGET")
+                .and_then(|v| v.as_u64()),
+            ANSWER_CACHE_DISTANCE_THRESHOLD: configuration
+                .get("ANSWER_CACHE_DISTANCE_THRESHOLD")
+                .and_then(|v| v.as_f64()),
+            RATE_LIMITS: configuration
+                .get("RATE_LIMITS")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default(),
         }
     }
 
@@ -2538,6 +3113,10 @@ impl DatasetConfiguration {
             "LLM_API_KEY": self.LLM_API_KEY,
             "EMBEDDING_BASE_URL": self.EMBEDDING_BASE_URL,
             "EMBEDDING_MODEL_NAME": self.EMBEDDING_MODEL_NAME,
+            "EMBEDDING_PROVIDER": self.EMBEDDING_PROVIDER,
+            "SPARSE_EMBEDDING_PROVIDER": self.SPARSE_EMBEDDING_PROVIDER,
+            "RERANKER_PROVIDER": self.RERANKER_PROVIDER,
+            "RERANKER_MODEL_NAME": self.RERANKER_MODEL_NAME,
             "MESSAGE_TO_QUERY_PROMPT": self.MESSAGE_TO_QUERY_PROMPT,
             "RAG_PROMPT": self.RAG_PROMPT,
             "N_RETRIEVALS_TO_INCLUDE": self.N_RETRIEVALS_TO_INCLUDE,
@@ -2547,6 +3126,7 @@ impl DatasetConfiguration {
             "BM25_B": self.BM25_B,
             "BM25_K": self.BM25_K,
             "BM25_AVG_LEN": self.BM25_AVG_LEN,
+            "LANGUAGE": self.LANGUAGE,
             "FULLTEXT_ENABLED": self.FULLTEXT_ENABLED,
             "SEMANTIC_ENABLED": self.SEMANTIC_ENABLED,
             "EMBEDDING_QUERY_PREFIX": self.EMBEDDING_QUERY_PREFIX,
@@ -2563,7 +3143,11 @@ impl DatasetConfiguration {
             "PUBLIC_DATASET" : {
                 "enabled": self.PUBLIC_DATASET.enabled,
                 "api_key": self.PUBLIC_DATASET.api_key
-            }
+            },
+            "VECTOR_FIELDS": self.VECTOR_FIELDS,
+            "CHAT_HISTORY_TOKEN_BUDGET": self.CHAT_HISTORY_TOKEN_BUDGET,
+            "ANSWER_CACHE_DISTANCE_THRESHOLD": self.ANSWER_CACHE_DISTANCE_THRESHOLD,
+            "RATE_LIMITS": self.RATE_LIMITS,
         })
     }
 }
@@ -2594,6 +3178,19 @@ impl DatasetConfigurationDTO {
                 .RERANKER_BASE_URL
                 .clone()
                 .unwrap_or(curr_dataset_config.RERANKER_BASE_URL),
+            EMBEDDING_PROVIDER: self
+                .EMBEDDING_PROVIDER
+                .unwrap_or(curr_dataset_config.EMBEDDING_PROVIDER),
+            SPARSE_EMBEDDING_PROVIDER: self
+                .SPARSE_EMBEDDING_PROVIDER
+                .unwrap_or(curr_dataset_config.SPARSE_EMBEDDING_PROVIDER),
+            RERANKER_PROVIDER: self
+                .RERANKER_PROVIDER
+                .unwrap_or(curr_dataset_config.RERANKER_PROVIDER),
+            RERANKER_MODEL_NAME: self
+                .RERANKER_MODEL_NAME
+                .clone()
+                .or(curr_dataset_config.RERANKER_MODEL_NAME),
             MESSAGE_TO_QUERY_PROMPT: self
                 .MESSAGE_TO_QUERY_PROMPT
                 .clone()
@@ -2624,6 +3221,7 @@ impl DatasetConfigurationDTO {
             BM25_AVG_LEN: self
                 .BM25_AVG_LEN
                 .unwrap_or(curr_dataset_config.BM25_AVG_LEN),
+            LANGUAGE: self.LANGUAGE.unwrap_or(curr_dataset_config.LANGUAGE),
             FULLTEXT_ENABLED: self
                 .FULLTEXT_ENABLED
                 .unwrap_or(curr_dataset_config.FULLTEXT_ENABLED),
@@ -2667,6 +3265,17 @@ impl DatasetConfigurationDTO {
                     .map(|public_dataset| public_dataset.api_key)
                     .unwrap_or(curr_dataset_config.PUBLIC_DATASET.api_key),
             },
+            VECTOR_FIELDS: self
+                .VECTOR_FIELDS
+                .clone()
+                .unwrap_or(curr_dataset_config.VECTOR_FIELDS),
+            CHAT_HISTORY_TOKEN_BUDGET: self
+                .CHAT_HISTORY_TOKEN_BUDGET
+                .or(curr_dataset_config.CHAT_HISTORY_TOKEN_BUDGET),
+            ANSWER_CACHE_DISTANCE_THRESHOLD: self
+                .ANSWER_CACHE_DISTANCE_THRESHOLD
+                .or(curr_dataset_config.ANSWER_CACHE_DISTANCE_THRESHOLD),
+            RATE_LIMITS: self.RATE_LIMITS.unwrap_or(curr_dataset_config.RATE_LIMITS),
         }
     }
 }
@@ -2707,6 +3316,7 @@ impl DatasetAndOrgWithSubAndPlan {
     "created_at": "2021-01-01 00:00:00.000",
     "updated_at": "2021-01-01 00:00:00.000",
     "registerable": true,
+    "sso_only": false,
 }))]
 #[diesel(table_name = organizations)]
 pub struct Organization {
@@ -2716,6 +3326,8 @@ pub struct Organization {
     pub updated_at: chrono::NaiveDateTime,
     pub registerable: Option<bool>,
     pub deleted: i32,
+    /// Whether members have to sign in through the organization's SSO provider to use it with a session. Api keys are unaffected.
+    pub sso_only: bool,
 }
 
 impl Organization {
@@ -2727,6 +3339,7 @@ impl Organization {
             updated_at: chrono::Utc::now().naive_local(),
             registerable: Some(true),
             deleted: 0,
+            sso_only: false,
         }
     }
 
@@ -2771,6 +3384,234 @@ impl Invitation {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// Protocol the organization's identity provider signs users in with.
+pub enum SsoProvider {
+    #[display(fmt = "oidc")]
+    Oidc,
+    #[display(fmt = "saml")]
+    Saml,
+}
+
+impl From<String> for SsoProvider {
+    fn from(provider: String) -> Self {
+        match provider.as_str() {
+            "saml" => SsoProvider::Saml,
+            _ => SsoProvider::Oidc,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = organization_sso_configs)]
+pub struct OrganizationSsoConfig {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    pub provider: String,
+    pub oidc_issuer_url: Option<String>,
+    pub oidc_client_id: Option<String>,
+    /// Client secret of the OIDC provider encrypted with SECRET_KEY
+    pub encrypted_oidc_client_secret: Option<String>,
+    pub saml_idp_metadata: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl OrganizationSsoConfig {
+    pub fn from_details(
+        organization_id: uuid::Uuid,
+        provider: SsoProvider,
+        oidc_issuer_url: Option<String>,
+        oidc_client_id: Option<String>,
+        encrypted_oidc_client_secret: Option<String>,
+        saml_idp_metadata: Option<String>,
+    ) -> Self {
+        OrganizationSsoConfig {
+            id: uuid::Uuid::new_v4(),
+            organization_id,
+            provider: provider.to_string(),
+            oidc_issuer_url,
+            oidc_client_id,
+            encrypted_oidc_client_secret,
+            saml_idp_metadata,
+            created_at: chrono::Utc::now().naive_local(),
+            updated_at: chrono::Utc::now().naive_local(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = organization_sso_identities)]
+/// Links the subject an organization's identity provider gives a user to the Trieve user it signs in as
+pub struct OrganizationSsoIdentity {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub subject: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl OrganizationSsoIdentity {
+    pub fn from_details(organization_id: uuid::Uuid, user_id: uuid::Uuid, subject: String) -> Self {
+        OrganizationSsoIdentity {
+            id: uuid::Uuid::new_v4(),
+            organization_id,
+            user_id,
+            subject,
+            created_at: chrono::Utc::now().naive_local(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "organization_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "provider": "oidc",
+    "oidc_issuer_url": "https://acme.okta.com",
+    "oidc_client_id": "0oa1b2c3d4e5f6g7h8i9",
+    "saml_idp_metadata": null,
+    "created_at": "2021-01-01 00:00:00.000",
+    "updated_at": "2021-01-01 00:00:00.000",
+}))]
+/// SSO configuration of an organization. The OIDC client secret is never returned.
+pub struct OrganizationSsoConfigDTO {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    pub provider: SsoProvider,
+    pub oidc_issuer_url: Option<String>,
+    pub oidc_client_id: Option<String>,
+    /// XML metadata of the SAML identity provider
+    pub saml_idp_metadata: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl From<OrganizationSsoConfig> for OrganizationSsoConfigDTO {
+    fn from(config: OrganizationSsoConfig) -> Self {
+        OrganizationSsoConfigDTO {
+            id: config.id,
+            organization_id: config.organization_id,
+            provider: config.provider.into(),
+            oidc_issuer_url: config.oidc_issuer_url,
+            oidc_client_id: config.oidc_client_id,
+            saml_idp_metadata: config.saml_idp_metadata,
+            created_at: config.created_at,
+            updated_at: config.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "organization_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "domain": "acme.com",
+    "verification_token": "trieve-domain-verification=Xk2lPq8vR4tY7wZ1",
+    "default_role": 0,
+    "verified_at": "2021-01-01 00:00:00.000",
+    "created_at": "2021-01-01 00:00:00.000",
+}))]
+#[diesel(table_name = organization_domains)]
+/// An email domain claimed by an organization. Once verified, users with an email at the domain are added to the organization with the default role when they sign in.
+pub struct OrganizationDomain {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    pub domain: String,
+    /// Value of the TXT record which has to be set on `_trieve-verification.<domain>` to verify the domain
+    pub verification_token: String,
+    /// Role users joining through the domain are given, 0 is User, 1 is Admin and 2 is Owner
+    pub default_role: i32,
+    pub verified_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl OrganizationDomain {
+    pub fn from_details(organization_id: uuid::Uuid, domain: String, default_role: i32) -> Self {
+        let token: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+
+        OrganizationDomain {
+            id: uuid::Uuid::new_v4(),
+            organization_id,
+            domain,
+            verification_token: format!("trieve-domain-verification={}", token),
+            default_role,
+            verified_at: None,
+            created_at: chrono::Utc::now().naive_local(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = organization_scim_tokens)]
+/// Bearer token identity providers use to provision the members of an organization over SCIM. Only the hash of the token is stored.
+pub struct OrganizationScimToken {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    pub blake3_hash: String,
+    pub token_prefix: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub can_grant_owner: bool,
+}
+
+impl OrganizationScimToken {
+    pub fn from_details(
+        organization_id: uuid::Uuid,
+        blake3_hash: String,
+        token_prefix: String,
+        can_grant_owner: bool,
+    ) -> Self {
+        OrganizationScimToken {
+            id: uuid::Uuid::new_v4(),
+            organization_id,
+            blake3_hash,
+            token_prefix,
+            created_at: chrono::Utc::now().naive_local(),
+            last_used_at: None,
+            can_grant_owner,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "organization_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "token_prefix": "tr-scim-Xk2",
+    "created_at": "2021-01-01 00:00:00.000",
+    "last_used_at": "2021-01-01 00:00:00.000",
+    "can_grant_owner": false,
+}))]
+pub struct OrganizationScimTokenDTO {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    /// First characters of the token to tell it apart in the identity provider's settings
+    pub token_prefix: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    /// Whether the identity provider can make members owners of the organization, otherwise it can grant at most the admin role
+    pub can_grant_owner: bool,
+}
+
+impl From<OrganizationScimToken> for OrganizationScimTokenDTO {
+    fn from(token: OrganizationScimToken) -> Self {
+        OrganizationScimTokenDTO {
+            id: token.id,
+            organization_id: token.organization_id,
+            token_prefix: token.token_prefix,
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+            can_grant_owner: token.can_grant_owner,
+        }
+    }
+}
+
 #[derive(
     Debug, Serialize, Deserialize, Selectable, Clone, Queryable, Insertable, ValidGrouping, ToSchema,
 )]
@@ -2828,6 +3669,68 @@ impl StripePlan {
             name,
         }
     }
+
+    /// Requests per minute a dataset of an organization on this plan can make to a class of routes when its configuration does not set a limit. Limits scale with the chunk and message counts of the plan.
+    pub fn default_rate_limit(&self, route_class: RateLimitRouteClass) -> u32 {
+        match route_class {
+            RateLimitRouteClass::Search => (self.chunk_count / 50).clamp(120, 12000) as u32,
+            RateLimitRouteClass::Ingest => (self.chunk_count / 100).clamp(60, 6000) as u32,
+            RateLimitRouteClass::Rag => (self.message_count / 100).clamp(10, 1000) as u32,
+        }
+    }
+
+    /// Requests per minute a dataset of an organization on this plan can make to a class of routes. Limits the dataset sets are capped at the plan's default so they can only be lowered.
+    pub fn dataset_rate_limit(
+        &self,
+        dataset_rate_limits: &RateLimits,
+        route_class: RateLimitRouteClass,
+    ) -> u32 {
+        let plan_limit = self.default_rate_limit(route_class);
+
+        dataset_rate_limits
+            .get(route_class)
+            .map_or(plan_limit, |limit| limit.min(plan_limit))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitRouteClass {
+    /// Search, autocomplete, count and recommendation routes for chunks and groups
+    #[display(fmt = "search")]
+    Search,
+    /// Creating and updating chunks and uploading files
+    #[display(fmt = "ingest")]
+    Ingest,
+    /// Creating, editing and regenerating messages, suggested queries and generating completions off chunks
+    #[display(fmt = "rag")]
+    Rag,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[schema(example = json!({
+    "search_per_minute": 600,
+    "ingest_per_minute": 300,
+    "rag_per_minute": 30,
+}))]
+/// Requests per minute allowed for each class of routes. Classes which are not specified are not limited any further.
+pub struct RateLimits {
+    /// Requests per minute to search, autocomplete, count and recommend routes
+    pub search_per_minute: Option<u32>,
+    /// Requests per minute to create and update chunks and upload files
+    pub ingest_per_minute: Option<u32>,
+    /// Requests per minute to create, edit and regenerate messages, get suggested queries and generate completions
+    pub rag_per_minute: Option<u32>,
+}
+
+impl RateLimits {
+    pub fn get(&self, route_class: RateLimitRouteClass) -> Option<u32> {
+        match route_class {
+            RateLimitRouteClass::Search => self.search_per_minute,
+            RateLimitRouteClass::Ingest => self.ingest_per_minute,
+            RateLimitRouteClass::Rag => self.rag_per_minute,
+        }
+    }
 }
 
 impl Default for StripePlan {
@@ -3086,11 +3989,54 @@ impl From<ApiKeyRole> for i32 {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone, ToSchema)]
-#[schema(example = json!({
-    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
-    "user_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3",
-    "api_key_hash": "hash",
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Display, ToSchema)]
+/// A permission which can be granted to an api key. Keys without permissions can do everything their role allows.
+pub enum ApiKeyPermission {
+    /// Search, count, scroll and get chunks and groups
+    #[serde(rename = "chunk:read")]
+    #[display(fmt = "chunk:read")]
+    ChunkRead,
+    /// Create, update and delete chunks, including through file uploads
+    #[serde(rename = "chunk:write")]
+    #[display(fmt = "chunk:write")]
+    ChunkWrite,
+    /// Create, update and delete groups and their chunk memberships
+    #[serde(rename = "group:write")]
+    #[display(fmt = "group:write")]
+    GroupWrite,
+    /// Create, edit and regenerate RAG messages and completions
+    #[serde(rename = "rag:create")]
+    #[display(fmt = "rag:create")]
+    RagCreate,
+    /// Read search, RAG, recommendation and event analytics
+    #[serde(rename = "analytics:read")]
+    #[display(fmt = "analytics:read")]
+    AnalyticsRead,
+}
+
+impl std::str::FromStr for ApiKeyPermission {
+    type Err = ServiceError;
+
+    fn from_str(permission: &str) -> Result<Self, Self::Err> {
+        match permission {
+            "chunk:read" => Ok(ApiKeyPermission::ChunkRead),
+            "chunk:write" => Ok(ApiKeyPermission::ChunkWrite),
+            "group:write" => Ok(ApiKeyPermission::GroupWrite),
+            "rag:create" => Ok(ApiKeyPermission::RagCreate),
+            "analytics:read" => Ok(ApiKeyPermission::AnalyticsRead),
+            _ => Err(ServiceError::BadRequest(format!(
+                "Unknown api key permission: {}",
+                permission
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "user_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3",
+    "api_key_hash": "hash",
     "name": "Trieve",
     "created_at": "2021-01-01 00:00:00.000",
     "updated_at": "2021-01-01 00:00:00.000",
@@ -3110,6 +4056,14 @@ pub struct UserApiKey {
     pub dataset_ids: Option<Vec<Option<String>>>,
     pub organization_ids: Option<Vec<Option<String>>>,
     pub scopes: Option<Vec<Option<String>>>,
+    pub permissions: Option<Vec<Option<String>>>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub allowed_cidrs: Option<Vec<Option<String>>>,
+    pub key_prefix: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub previous_blake3_hash: Option<String>,
+    pub previous_hash_expires_at: Option<chrono::NaiveDateTime>,
+    pub rate_limits: Option<serde_json::Value>,
 }
 
 impl UserApiKey {
@@ -3136,6 +4090,39 @@ impl UserApiKey {
             organization_ids: organization_ids
                 .map(|ids| ids.into_iter().map(|id| Some(id.to_string())).collect()),
             scopes: scopes.map(|scopes| scopes.into_iter().map(Some).collect()),
+            permissions: None,
+            expires_at: None,
+            allowed_cidrs: None,
+            key_prefix: None,
+            last_used_at: None,
+            previous_blake3_hash: None,
+            previous_hash_expires_at: None,
+            rate_limits: None,
+        }
+    }
+
+    /// Rate limits set on the key itself, requests made with the key count against these on top of the limits of the dataset
+    pub fn get_rate_limits(&self) -> RateLimits {
+        self.rate_limits
+            .clone()
+            .and_then(|rate_limits| serde_json::from_value(rate_limits).ok())
+            .unwrap_or_default()
+    }
+
+    /// Whether the key was granted specific permissions, which limits it to the routes checking one of them
+    pub fn has_permissions(&self) -> bool {
+        self.permissions
+            .as_ref()
+            .is_some_and(|permissions| !permissions.is_empty())
+    }
+
+    /// Whether the key was granted the permission, keys without any permissions are granted all of them
+    pub fn has_permission(&self, permission: ApiKeyPermission) -> bool {
+        match &self.permissions {
+            Some(permissions) if !permissions.is_empty() => {
+                permissions.contains(&Some(permission.to_string()))
+            }
+            _ => true,
         }
     }
 }
@@ -3148,6 +4135,13 @@ impl UserApiKey {
     "role": 1,
     "dataset_ids": ["d0d0d0d0-d0d0-d0d0-d0d0-d0d0d0d0d0d0"],
     "organization_ids": ["o1o1o1o1-o1o1-o1o1-o1o1-o1o1o1o1o1o1"],
+    "permissions": ["chunk:read", "rag:create"],
+    "expires_at": "2022-01-01 00:00:00.000",
+    "allowed_cidrs": ["10.0.0.0/8"],
+    "key_prefix": "tr-AbCd1234",
+    "last_used_at": "2021-06-01 00:00:00.000",
+    "previous_key_expires_at": null,
+    "rate_limits": {"search_per_minute": 120, "rag_per_minute": 10},
     "created_at": "2021-01-01 00:00:00.000",
     "updated_at": "2021-01-01 00:00:00.000",
 }))]
@@ -3158,6 +4152,15 @@ pub struct ApiKeyRespBody {
     pub role: i32,
     pub dataset_ids: Option<Vec<String>>,
     pub organization_ids: Option<Vec<String>>,
+    pub permissions: Option<Vec<ApiKeyPermission>>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub allowed_cidrs: Option<Vec<String>>,
+    /// Start of the api key which can be used to tell keys apart. Not present for keys created before prefixes were stored.
+    pub key_prefix: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    /// Time until which the secret the key had before it was last rotated is still accepted
+    pub previous_key_expires_at: Option<chrono::NaiveDateTime>,
+    pub rate_limits: Option<RateLimits>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -3175,6 +4178,23 @@ impl From<UserApiKey> for ApiKeyRespBody {
             organization_ids: api_key
                 .organization_ids
                 .map(|ids| ids.into_iter().flatten().collect()),
+            permissions: api_key.permissions.map(|permissions| {
+                permissions
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|permission| permission.parse::<ApiKeyPermission>().ok())
+                    .collect()
+            }),
+            expires_at: api_key.expires_at,
+            allowed_cidrs: api_key
+                .allowed_cidrs
+                .map(|cidrs| cidrs.into_iter().flatten().collect()),
+            key_prefix: api_key.key_prefix,
+            last_used_at: api_key.last_used_at,
+            previous_key_expires_at: api_key.previous_hash_expires_at,
+            rate_limits: api_key
+                .rate_limits
+                .and_then(|rate_limits| serde_json::from_value(rate_limits).ok()),
             created_at: api_key.created_at,
             updated_at: api_key.updated_at,
         }
@@ -3227,6 +4247,9 @@ pub struct QdrantPayload {
     pub location: Option<GeoInfo>,
     pub num_value: Option<f64>,
     pub group_tag_set: Option<Vec<Option<String>>>,
+    /// Texts of the chunk's vector fields, kept so their vectors can be re-created when points are migrated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_fields: Option<HashMap<String, String>>,
 }
 
 impl From<QdrantPayload> for Payload {
@@ -3256,6 +4279,7 @@ impl QdrantPayload {
             location: chunk_metadata.location,
             num_value: chunk_metadata.num_value,
             group_tag_set,
+            vector_fields: None,
         }
     }
 
@@ -3311,6 +4335,11 @@ impl QdrantPayload {
                     .map(|value| Some(value.to_string()))
                     .collect()
             }),
+            vector_fields: point
+                .payload
+                .get("vector_fields")
+                .cloned()
+                .and_then(|value| serde_json::from_value(value.into()).ok()),
         }
     }
 }
@@ -3380,6 +4409,11 @@ impl From<RetrievedPoint> for QdrantPayload {
                     .map(|value| Some(value.to_string().replace(['"', '\\'], "")))
                     .collect()
             }),
+            vector_fields: point
+                .payload
+                .get("vector_fields")
+                .cloned()
+                .and_then(|value| serde_json::from_value(value.into()).ok()),
         }
     }
 }
@@ -3392,6 +4426,120 @@ pub struct FileWorkerMessage {
     pub attempt_number: u8,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatasetImportMessage {
+    pub import_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatasetExportMessage {
+    pub export_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub include_vectors: bool,
+    pub include_files: bool,
+}
+
+/// Version of the dataset archive layout written by the export endpoint.
+pub const DATASET_ARCHIVE_VERSION: u32 = 1;
+
+/// Written to `manifest.json` at the root of a dataset export archive.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct DatasetArchiveManifest {
+    pub version: u32,
+    pub exported_at: chrono::NaiveDateTime,
+    pub dataset_id: uuid::Uuid,
+    pub dataset_name: String,
+    pub tracking_id: Option<String>,
+    pub server_configuration: serde_json::Value,
+    /// Size of the dense vectors in `chunks.jsonl`. Imports into a dataset with a different size re-embed the chunks.
+    pub embedding_size: usize,
+    pub includes_vectors: bool,
+    pub includes_file_blobs: bool,
+    pub chunk_count: usize,
+    pub group_count: usize,
+    pub file_count: usize,
+}
+
+/// A line of `chunks.jsonl` in a dataset export archive.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArchivedChunk {
+    pub id: uuid::Uuid,
+    pub link: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+    pub chunk_html: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub tracking_id: Option<String>,
+    pub time_stamp: Option<NaiveDateTime>,
+    pub weight: f64,
+    pub location: Option<GeoInfo>,
+    pub image_urls: Option<Vec<Option<String>>>,
+    pub tag_set: Option<Vec<Option<String>>>,
+    pub num_value: Option<f64>,
+    pub group_ids: Vec<uuid::Uuid>,
+    pub dense_vector: Option<Vec<f32>>,
+    pub sparse_vector: Option<Vec<(u32, f32)>>,
+}
+
+impl ArchivedChunk {
+    pub fn from_details(
+        chunk_metadata: ChunkMetadata,
+        group_ids: Vec<uuid::Uuid>,
+        dense_vector: Option<Vec<f32>>,
+        sparse_vector: Option<Vec<(u32, f32)>>,
+    ) -> Self {
+        ArchivedChunk {
+            id: chunk_metadata.id,
+            link: chunk_metadata.link,
+            created_at: chunk_metadata.created_at,
+            updated_at: chunk_metadata.updated_at,
+            chunk_html: chunk_metadata.chunk_html,
+            metadata: chunk_metadata.metadata,
+            tracking_id: chunk_metadata.tracking_id,
+            time_stamp: chunk_metadata.time_stamp,
+            weight: chunk_metadata.weight,
+            location: chunk_metadata.location,
+            image_urls: chunk_metadata.image_urls,
+            tag_set: chunk_metadata.tag_set,
+            num_value: chunk_metadata.num_value,
+            group_ids,
+            dense_vector,
+            sparse_vector,
+        }
+    }
+
+    /// Chunk metadata for the archived chunk in a new dataset. Ids are regenerated such that a dataset can be imported next to the dataset it was exported from.
+    pub fn to_chunk_metadata(&self, dataset_id: uuid::Uuid) -> ChunkMetadata {
+        ChunkMetadata {
+            id: uuid::Uuid::new_v4(),
+            link: self.link.clone(),
+            qdrant_point_id: uuid::Uuid::new_v4(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            chunk_html: self.chunk_html.clone(),
+            metadata: self.metadata.clone(),
+            tracking_id: self.tracking_id.clone(),
+            time_stamp: self.time_stamp,
+            dataset_id,
+            weight: self.weight,
+            location: self.location,
+            image_urls: self.image_urls.clone(),
+            tag_set: self.tag_set.clone(),
+            num_value: self.num_value,
+        }
+    }
+}
+
+/// A line of `files.jsonl` in a dataset export archive.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArchivedFile {
+    pub file: File,
+    pub group_ids: Vec<uuid::Uuid>,
+    /// Path of the file contents within the archive when the export included file blobs.
+    pub blob_path: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[serde(untagged)]
 pub enum RangeCondition {
@@ -3790,6 +4938,8 @@ pub struct SearchQueryEvent {
     pub created_at: String,
     pub query_rating: Option<SearchQueryRating>,
     pub user_id: String,
+    /// Ids of the merchandising rules which were applied to the results of the search.
+    pub merchandising_rule_ids: Vec<uuid::Uuid>,
 }
 
 impl Default for SearchQueryEvent {
@@ -3806,6 +4956,7 @@ impl Default for SearchQueryEvent {
             created_at: chrono::Utc::now().to_string(),
             query_rating: None,
             user_id: String::from(""),
+            merchandising_rule_ids: vec![],
         }
     }
 }
@@ -4060,6 +5211,7 @@ pub struct SearchQueryEventClickhouse {
     pub created_at: OffsetDateTime,
     pub query_rating: String,
     pub user_id: String,
+    pub merchandising_rule_ids: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -4117,6 +5269,11 @@ impl From<SearchQueryEventClickhouse> for SearchQueryEvent {
             created_at: clickhouse_response.created_at.to_string(),
             query_rating,
             user_id: clickhouse_response.user_id,
+            merchandising_rule_ids: clickhouse_response
+                .merchandising_rule_ids
+                .iter()
+                .filter_map(|rule_id| uuid::Uuid::parse_str(rule_id).ok())
+                .collect(),
         }
     }
 }
@@ -4162,6 +5319,8 @@ pub struct RagQueryEvent {
     pub query_rating: Option<SearchQueryRating>,
     pub created_at: String,
     pub user_id: String,
+    /// Whether the completion was served from the dataset's answer cache instead of being generated
+    pub cache_hit: bool,
 }
 
 impl From<String> for ClickhouseRagTypes {
@@ -4218,6 +5377,7 @@ impl RagQueryEventClickhouse {
             llm_response: self.llm_response,
             created_at: self.created_at.to_string(),
             user_id: self.user_id,
+            cache_hit: self.cache_hit,
         }
     }
 }
@@ -4239,6 +5399,7 @@ pub struct RagQueryEventClickhouse {
     #[serde(with = "clickhouse::serde::time::datetime")]
     pub created_at: OffsetDateTime,
     pub user_id: String,
+    pub cache_hit: bool,
 }
 
 #[derive(Debug, Row, Serialize, Deserialize, ToSchema)]
@@ -4977,6 +6138,7 @@ impl EventTypes {
                 created_at: OffsetDateTime::now_utc(),
                 query_rating: serde_json::to_string(&query_rating).unwrap_or("".to_string()),
                 user_id: user_id.unwrap_or_default(),
+                merchandising_rule_ids: vec![],
             }),
             EventTypes::RAG {
                 rag_type,
@@ -5004,6 +6166,7 @@ impl EventTypes {
                 dataset_id,
                 created_at: OffsetDateTime::now_utc(),
                 user_id: user_id.unwrap_or_default(),
+                cache_hit: false,
             }),
             EventTypes::Recommendation {
                 recommendation_type,
@@ -5669,11 +6832,27 @@ pub enum EventTypeRequest {
     CrawlCompleted,
     #[display(fmt = "crawl_failed")]
     CrawlFailed,
+    #[display(fmt = "dataset_imported")]
+    DatasetImported,
+    #[display(fmt = "dataset_import_failed")]
+    DatasetImportFailed,
+    #[display(fmt = "dataset_exported")]
+    DatasetExported,
+    #[display(fmt = "dataset_export_failed")]
+    DatasetExportFailed,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum MigrationMode {
-    BM25 { average_len: f32, k: f32, b: f32 },
+    BM25 {
+        average_len: f32,
+        k: f32,
+        b: f32,
+        #[serde(default)]
+        language: DatasetLanguage,
+    },
+    /// Copies points as they are into a collection which has the vector field slots
+    VectorFields,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -5684,6 +6863,12 @@ pub struct MigratePointMessage {
     pub mode: MigrationMode,
 }
 
+/// Asks the reindex-worker to queue every chunk of a dataset for a BM25 reindex with the dataset's current configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Bm25ReindexMessage {
+    pub dataset_id: uuid::Uuid,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct SortByField {
     /// Field to sort by. This has to be a numeric field with a Qdrant `Range` index on it. i.e. num_value and timestamp
@@ -5722,6 +6907,52 @@ pub enum ReRankOptions {
     CrossEncoder,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+/// Strategy used to merge the semantic and fulltext result sets of a hybrid search.
+pub enum HybridFusionStrategy {
+    /// Re-score the union of both result sets with the dataset's cross encoder reranker.
+    #[default]
+    CrossEncoder,
+    /// Reciprocal Rank Fusion. Each leg contributes weight / (rrf_k + rank) for every chunk it returned.
+    #[serde(rename = "rrf", alias = "RRF")]
+    Rrf,
+    /// Weighted sum of the min-max normalized scores of each leg.
+    Linear,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+/// One of the retrieval legs which make up a hybrid search.
+pub enum HybridLeg {
+    Semantic,
+    Fulltext,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Default)]
+#[schema(example = json!({
+    "fusion_strategy": "rrf",
+    "semantic_weight": 1.0,
+    "fulltext_weight": 0.5,
+    "semantic_prefetch": 50,
+    "fulltext_prefetch": 50,
+}))]
+/// Hybrid options let you control how the semantic and fulltext legs of a hybrid search are retrieved and merged. Only used when search_type is "hybrid".
+pub struct HybridSearchOptions {
+    /// How to merge the results of the semantic and fulltext legs. Can be "cross_encoder", "rrf", or "linear". "rrf" and "linear" do not require a reranker model and score results between 0 and 1, which is what score_threshold is compared against. Defaults to "cross_encoder".
+    pub fusion_strategy: Option<HybridFusionStrategy>,
+    /// Weight applied to the semantic leg when fusing with "rrf" or "linear". Must not be negative and cannot be 0 together with fulltext_weight. Defaults to 1.0.
+    pub semantic_weight: Option<f32>,
+    /// Weight applied to the fulltext leg when fusing with "rrf" or "linear". Must not be negative and cannot be 0 together with semantic_weight. Defaults to 1.0.
+    pub fulltext_weight: Option<f32>,
+    /// Number of candidates to pull in from the semantic leg before fusing. Defaults to the page_size of the request.
+    pub semantic_prefetch: Option<u64>,
+    /// Number of candidates to pull in from the fulltext leg before fusing. Defaults to the page_size of the request.
+    pub fulltext_prefetch: Option<u64>,
+    /// Constant added to each rank when fusing with "rrf". Higher values flatten the contribution of top ranks. Defaults to 60.
+    pub rrf_k: Option<f32>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct ChunkWithPosition {
     pub chunk_id: uuid::Uuid,
@@ -5927,6 +7158,145 @@ pub struct SortOptions {
     pub tag_weights: Option<HashMap<String, f32>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// Shape of the decay curve used by recency bias. Exp decays sharply right after the offset and has a long tail, gauss decays slowly at first and then drops off.
+pub enum RecencyDecayFunction {
+    #[default]
+    Exp,
+    Gauss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A duration written as a number followed by a unit of s, m, h, d or w, e.g. "30d" or "12h".
+pub struct DecayDuration(pub chrono::Duration);
+
+impl TryFrom<String> for DecayDuration {
+    type Error = String;
+
+    fn try_from(duration: String) -> Result<Self, Self::Error> {
+        let duration = duration.trim();
+        let unit_start = duration
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(|| {
+                format!(
+                    "Duration {:?} is missing a unit of s, m, h, d or w",
+                    duration
+                )
+            })?;
+        let amount = duration[..unit_start]
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|amount| amount.is_finite() && *amount >= 0.0)
+            .ok_or_else(|| {
+                format!(
+                    "Duration {:?} must start with a non-negative number",
+                    duration
+                )
+            })?;
+        let unit_seconds = match duration[unit_start..].trim() {
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            "w" => 604800.0,
+            unit => {
+                return Err(format!(
+                    "Unknown duration unit {:?}, expected s, m, h, d or w",
+                    unit
+                ))
+            }
+        };
+
+        Ok(DecayDuration(chrono::Duration::milliseconds(
+            (amount * unit_seconds * 1000.0) as i64,
+        )))
+    }
+}
+
+impl From<DecayDuration> for String {
+    fn from(duration: DecayDuration) -> Self {
+        format!("{}s", duration.0.num_milliseconds() as f64 / 1000.0)
+    }
+}
+
+impl Serialize for DecayDuration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&String::from(*self))
+    }
+}
+
+impl<'de> Deserialize<'de> for DecayDuration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        DecayDuration::try_from(String::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ToSchema)]
+#[schema(example = json!({
+    "decay_function": "gauss",
+    "scale": "30d",
+    "offset": "1d",
+    "decay": 0.5
+}))]
+/// Recency bias multiplies the score of each chunk by a decay factor based on how far its time_stamp is from the origin. Chunks within `offset` of the origin keep their score, chunks `offset + scale` away have their score multiplied by `decay`. Chunks without a time_stamp are not affected.
+pub struct RecencyBias {
+    /// Decay function to use, either "exp" or "gauss". Default is "exp".
+    pub decay_function: Option<RecencyDecayFunction>,
+    /// Distance from the origin, past the offset, at which the score is multiplied by `decay`. Written as a number followed by a unit of s, m, h, d or w, e.g. "30d".
+    #[schema(value_type = String, example = "30d")]
+    pub scale: DecayDuration,
+    /// Chunks whose time_stamp is within the offset of the origin are not decayed. Uses the same format as scale. Default is "0s".
+    #[schema(value_type = Option<String>, example = "1d")]
+    pub offset: Option<DecayDuration>,
+    /// Time to measure the distance of time_stamps from. Default is now.
+    pub origin: Option<NaiveDateTime>,
+    /// Factor applied to the score of chunks `offset + scale` away from the origin, between 0 and 1 exclusive. Default is 0.5.
+    pub decay: Option<f64>,
+}
+
+impl RecencyBias {
+    /// Factor to multiply the score of a chunk with the given time_stamp by, between 0 and 1.
+    pub fn decay_factor(&self, time_stamp: NaiveDateTime, origin: NaiveDateTime) -> f64 {
+        let scale = self.scale.0.num_milliseconds() as f64 / 1000.0;
+        if scale <= 0.0 {
+            return 1.0;
+        }
+
+        let offset = self
+            .offset
+            .map(|offset| offset.0.num_milliseconds() as f64 / 1000.0)
+            .unwrap_or(0.0);
+        let distance =
+            ((time_stamp - origin).num_milliseconds().abs() as f64 / 1000.0 - offset).max(0.0);
+        let decay = self
+            .decay
+            .unwrap_or(0.5)
+            .clamp(f64::EPSILON, 1.0 - f64::EPSILON);
+
+        match self.decay_function.unwrap_or_default() {
+            RecencyDecayFunction::Exp => (decay.ln() / scale * distance).exp(),
+            RecencyDecayFunction::Gauss => {
+                let variance = -scale.powi(2) / (2.0 * decay.ln());
+                (-distance.powi(2) / (2.0 * variance)).exp()
+            }
+        }
+    }
+
+    pub fn origin(&self) -> NaiveDateTime {
+        self.origin
+            .unwrap_or_else(|| chrono::Local::now().naive_local())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Default)]
 /// Highlight Options lets you specify different methods to highlight the chunks in the result set. If not specified, this defaults to the score of the chunks.
 pub struct HighlightOptions {
@@ -5974,6 +7344,106 @@ pub struct TypoRange {
     pub max: Option<u32>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Default)]
+#[schema(example = json!({
+    "terms": [
+        { "field": "tag_set", "limit": 10 },
+        { "field": "metadata.brand", "limit": 5 }
+    ],
+    "num_value_ranges": [
+        { "lt": 10.0 },
+        { "gte": 10.0, "lt": 50.0 },
+        { "gte": 50.0 }
+    ],
+    "time_stamp_interval": "month"
+}))]
+/// Facet options let you request aggregate counts computed over every chunk which matches the filters of the search, not only the returned page. If not specified, no facets are computed.
+pub struct FacetOptions {
+    /// Term facets to count. The field can be "tag_set" or "metadata.<key>", where nested metadata keys are separated by periods.
+    pub terms: Option<Vec<TermsFacet>>,
+    /// Ranges to bucket the num_value of the matching chunks into. Each range includes gte and excludes lt, either bound can be omitted.
+    pub num_value_ranges: Option<Vec<NumValueRange>>,
+    /// Interval to bucket the time_stamp of the matching chunks into. If not specified, no date histogram is returned.
+    pub time_stamp_interval: Option<DateHistogramInterval>,
+    /// Maximum number of matching chunks to compute facets over. If not specified, this defaults to the MAX_LIMIT of the dataset.
+    pub max_candidates: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TermsFacet {
+    /// Field to count the values of. Can be "tag_set" or "metadata.<key>".
+    pub field: String,
+    /// Maximum number of values to return for the field, ordered by count. If not specified, this defaults to 10.
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Default)]
+pub struct NumValueRange {
+    /// Inclusive lower bound of the range. If not specified, the range is unbounded below.
+    pub gte: Option<f64>,
+    /// Exclusive upper bound of the range. If not specified, the range is unbounded above.
+    pub lt: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Display, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateHistogramInterval {
+    #[display(fmt = "hour")]
+    Hour,
+    #[display(fmt = "day")]
+    Day,
+    #[display(fmt = "week")]
+    Week,
+    #[display(fmt = "month")]
+    Month,
+    #[display(fmt = "year")]
+    Year,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Default)]
+/// Facet counts computed over the chunks which matched the filters of the search.
+pub struct FacetResults {
+    /// Counts for each of the requested term facets.
+    pub terms: Vec<TermsFacetResult>,
+    /// Counts for each of the requested num_value ranges, in the order they were requested.
+    pub num_value_ranges: Vec<NumValueRangeCount>,
+    /// Counts for each time_stamp bucket, ordered by date. Buckets without chunks are omitted.
+    pub time_stamp_histogram: Vec<DateHistogramBucket>,
+    /// Number of matching chunks the facets were computed over.
+    pub candidate_count: u64,
+    /// True if more chunks matched than max_candidates or the dataset's MAX_LIMIT allow, in which case the counts only cover the first candidate_count of them.
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TermsFacetResult {
+    pub field: String,
+    pub values: Vec<FacetValueCount>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Queryable, QueryableByName)]
+pub struct FacetValueCount {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub value: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NumValueRangeCount {
+    pub gte: Option<f64>,
+    pub lt: Option<f64>,
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, QueryableByName)]
+pub struct DateHistogramBucket {
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub key: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Default)]
 #[schema(example = json!({
     "use_images": true,
@@ -6005,10 +7475,12 @@ impl Default for ContextOptions {
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Default)]
 /// LLM options to use for the completion. If not specified, this defaults to the dataset's LLM options.
 pub struct LLMOptions {
-    /// Completion first decides whether the stream should contain the stream of the completion response or the chunks first. Default is false. Keep in mind that || is used to separate the chunks from the completion response. If || is in the completion then you may want to split on ||{ instead.
+    /// Completion first decides whether the stream should contain the stream of the completion response or the chunks first. Default is false. Keep in mind that || is used to separate the chunks from the completion response. If || is in the completion then you may want to split on ||{ instead, or use the `sse` stream_format which does not need a delimiter. Ignored when stream_format is `sse`.
     pub completion_first: Option<bool>,
     /// Whether or not to stream the response. If this is set to true or not included, the response will be a stream. If this is set to false, the response will be a normal JSON response. Default is true.
     pub stream_response: Option<bool>,
+    /// Format of the streamed response. `delimited` streams the chunks and the completion separated by ||. `sse` streams Server-Sent Events of type retrieval, delta, citation, error, usage and done. Default is `delimited`.
+    pub stream_format: Option<RagStreamFormat>,
     /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic. Default is 0.5.
     pub temperature: Option<f32>,
     /// Frequency penalty is a number between -2.0 and 2.0. Positive values penalize new tokens based on their existing frequency in the text so far, decreasing the model's likelihood to repeat the same line verbatim. Default is 0.7.
@@ -6025,6 +7497,30 @@ pub struct LLMOptions {
     pub image_config: Option<ImageConfig>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+/// Format of a streamed RAG response.
+pub enum RagStreamFormat {
+    /// The chunks and the completion separated by ||.
+    #[default]
+    Delimited,
+    /// Server-Sent Events of type retrieval, delta, citation, error, usage and done.
+    Sse,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Default)]
+#[schema(example = json!({
+    "max_steps": 5,
+    "max_tokens": 20000,
+}))]
+/// Agent options let the LLM search the dataset itself, as many times as it needs, before answering. Every search is limited to the filters of the request. Trieve's search, group search, get chunk by tracking id and count endpoints are exposed to the LLM as tools. Every tool call and its result is stored as a message in the topic with the role `tool_call` or `tool`.
+pub struct AgentOptions {
+    /// Maximum number of times the LLM can call tools before it has to answer. Default is 5, at most the server's AGENT_MAX_STEPS which defaults to 10.
+    pub max_steps: Option<u32>,
+    /// Maximum number of prompt and completion tokens the tool calling steps can use before the LLM has to answer. The final answer is not counted. Default is 20000, at most the server's AGENT_MAX_TOKENS which defaults to 50000.
+    pub max_tokens: Option<u32>,
+}
+
 // Helper function to extract SortOptions and HighlightOptions
 fn extract_sort_highlight_options(
     other: &mut HashMap<String, Value>,
@@ -6175,6 +7671,10 @@ impl<'de> Deserialize<'de> for SearchChunksReqPayload {
             remove_stop_words: Option<bool>,
             user_id: Option<String>,
             typo_options: Option<TypoOptions>,
+            hybrid_options: Option<HybridSearchOptions>,
+            facets: Option<FacetOptions>,
+            vector_fields: Option<HashMap<String, f32>>,
+            recency_bias: Option<RecencyBias>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6207,6 +7707,10 @@ impl<'de> Deserialize<'de> for SearchChunksReqPayload {
             remove_stop_words: helper.remove_stop_words,
             user_id: helper.user_id,
             typo_options: helper.typo_options,
+            hybrid_options: helper.hybrid_options,
+            facets: helper.facets,
+            vector_fields: helper.vector_fields,
+            recency_bias: helper.recency_bias,
         })
     }
 }
@@ -6233,6 +7737,7 @@ impl<'de> Deserialize<'de> for AutocompleteReqPayload {
             remove_stop_words: Option<bool>,
             user_id: Option<String>,
             typo_options: Option<TypoOptions>,
+            recency_bias: Option<RecencyBias>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6264,6 +7769,7 @@ impl<'de> Deserialize<'de> for AutocompleteReqPayload {
             remove_stop_words: helper.remove_stop_words,
             user_id: helper.user_id,
             typo_options: helper.typo_options,
+            recency_bias: helper.recency_bias,
         })
     }
 }
@@ -6292,6 +7798,8 @@ impl<'de> Deserialize<'de> for SearchWithinGroupReqPayload {
             remove_stop_words: Option<bool>,
             user_id: Option<String>,
             typo_options: Option<TypoOptions>,
+            hybrid_options: Option<HybridSearchOptions>,
+            recency_bias: Option<RecencyBias>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6325,6 +7833,8 @@ impl<'de> Deserialize<'de> for SearchWithinGroupReqPayload {
             remove_stop_words: helper.remove_stop_words,
             user_id: helper.user_id,
             typo_options: helper.typo_options,
+            hybrid_options: helper.hybrid_options,
+            recency_bias: helper.recency_bias,
         })
     }
 }
@@ -6350,6 +7860,9 @@ impl<'de> Deserialize<'de> for SearchOverGroupsReqPayload {
             remove_stop_words: Option<bool>,
             user_id: Option<String>,
             typo_options: Option<TypoOptions>,
+            hybrid_options: Option<HybridSearchOptions>,
+            facets: Option<FacetOptions>,
+            recency_bias: Option<RecencyBias>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6378,6 +7891,9 @@ impl<'de> Deserialize<'de> for SearchOverGroupsReqPayload {
             typo_options: helper.typo_options,
             remove_stop_words: helper.remove_stop_words,
             user_id: helper.user_id,
+            hybrid_options: helper.hybrid_options,
+            facets: helper.facets,
+            recency_bias: helper.recency_bias,
         })
     }
 }
@@ -6402,6 +7918,8 @@ impl<'de> Deserialize<'de> for CreateMessageReqPayload {
             pub user_id: Option<String>,
             pub use_group_search: Option<bool>,
             pub context_options: Option<ContextOptions>,
+            pub recency_bias: Option<RecencyBias>,
+            pub agent_options: Option<AgentOptions>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6433,6 +7951,8 @@ impl<'de> Deserialize<'de> for CreateMessageReqPayload {
             llm_options,
             user_id: helper.user_id,
             context_options,
+            recency_bias: helper.recency_bias,
+            agent_options: helper.agent_options,
         })
     }
 }
@@ -6456,6 +7976,8 @@ impl<'de> Deserialize<'de> for RegenerateMessageReqPayload {
             pub user_id: Option<String>,
             pub use_group_search: Option<bool>,
             pub context_options: Option<ContextOptions>,
+            pub recency_bias: Option<RecencyBias>,
+            pub agent_options: Option<AgentOptions>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6486,6 +8008,8 @@ impl<'de> Deserialize<'de> for RegenerateMessageReqPayload {
             llm_options,
             user_id: helper.user_id,
             context_options,
+            recency_bias: helper.recency_bias,
+            agent_options: helper.agent_options,
         })
     }
 }
@@ -6511,6 +8035,8 @@ impl<'de> Deserialize<'de> for EditMessageReqPayload {
             pub llm_options: Option<LLMOptions>,
             pub user_id: Option<String>,
             pub context_options: Option<ContextOptions>,
+            pub recency_bias: Option<RecencyBias>,
+            pub agent_options: Option<AgentOptions>,
             #[serde(flatten)]
             other: std::collections::HashMap<String, serde_json::Value>,
         }
@@ -6543,6 +8069,8 @@ impl<'de> Deserialize<'de> for EditMessageReqPayload {
             user_id: helper.user_id,
             llm_options,
             context_options,
+            recency_bias: helper.recency_bias,
+            agent_options: helper.agent_options,
         })
     }
 }
@@ -6567,10 +8095,33 @@ impl From<(ParsedQuery, f32)> for MultiQuery {
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
 #[serde(untagged)]
-/// Query is the search query. This can be any string. The query will be used to create an embedding vector and/or SPLADE vector which will be used to find the result set.  You can either provide one query, or multiple with weights. Multi-query only works with Semantic Search and is not compatible with cross encoder re-ranking or highlights.
+/// Query is the search query. This can be any string. The query will be used to create an embedding vector and/or SPLADE vector which will be used to find the result set.  You can either provide one query, or multiple with weights. Multi-query only works with Semantic Search and is not compatible with cross encoder re-ranking or highlights. Instead of text you can also search with a raw dense vector, a raw sparse vector or the vectors of an existing chunk by its id or tracking_id. These are not compatible with highlights and raw vectors can not be re-ranked with the cross encoder.
 pub enum QueryTypes {
     Single(String),
     Multi(Vec<MultiQuery>),
+    /// Dense vector used as is for semantic search, it must have the dataset's EMBEDDING_SIZE dimensions and only finite values.
+    DenseVector {
+        dense_vector: Vec<f32>,
+    },
+    /// Sparse vector used as is for fulltext or BM25 search, its values must be finite.
+    SparseVector {
+        sparse_vector: SparseVector,
+    },
+    /// Search with the stored vectors of the chunk with this id. The chunk itself is excluded from the results.
+    ChunkId {
+        chunk_id: uuid::Uuid,
+    },
+    /// Search with the stored vectors of the chunk with this tracking_id. The chunk itself is excluded from the results.
+    TrackingId {
+        tracking_id: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+/// Sparse vector given as its non-zero indices and their values
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
 }
 
 impl Default for QueryTypes {
@@ -6586,6 +8137,21 @@ impl QueryTypes {
             QueryTypes::Multi(_) => Err(ServiceError::BadRequest(
                 "Cannot use Multi Query with cross encoder or highlights".to_string(),
             )),
+            _ => Err(ServiceError::BadRequest(
+                "Cannot use a vector or chunk query with highlights".to_string(),
+            )),
+        }
+    }
+
+    /// Text of the query for analytics, multi queries are serialized as json and vector or chunk queries are described by their kind
+    pub fn to_query_string(&self) -> String {
+        match self {
+            QueryTypes::Single(query) => query.clone(),
+            QueryTypes::Multi(query) => serde_json::to_string(&query).unwrap_or_default(),
+            QueryTypes::DenseVector { .. } => "dense_vector".to_string(),
+            QueryTypes::SparseVector { .. } => "sparse_vector".to_string(),
+            QueryTypes::ChunkId { chunk_id } => format!("chunk_id:{}", chunk_id),
+            QueryTypes::TrackingId { tracking_id } => format!("tracking_id:{}", tracking_id),
         }
     }
 }
@@ -6696,8 +8262,284 @@ impl From<CrawlRequest> for CrawlRequestPG {
     }
 }
 
-/// Options for setting up the crawl which will populate the dataset.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// Where a crawl run is at. Runs which errored before all pages were processed are failed.
+pub enum CrawlRunStatus {
+    #[display(fmt = "running")]
+    Running,
+    #[display(fmt = "completed")]
+    Completed,
+    #[display(fmt = "failed")]
+    Failed,
+}
+
+impl From<String> for CrawlRunStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "completed" => CrawlRunStatus::Completed,
+            "failed" => CrawlRunStatus::Failed,
+            _ => CrawlRunStatus::Running,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// Outcome of a single page of a crawl run.
+pub enum CrawlPageStatus {
+    /// The page was new or changed and its chunks were uploaded
+    #[display(fmt = "ingested")]
+    Ingested,
+    /// The page has the same content as in the previous run
+    #[display(fmt = "unchanged")]
+    Unchanged,
+    /// The page was left out because of the include_paths or exclude_paths of the crawl
+    #[display(fmt = "skipped_by_path")]
+    SkippedByPath,
+    /// No chunks could be made from the page, i.e. it has no headings or no content
+    #[display(fmt = "empty")]
+    Empty,
+    /// The page could not be fetched or parsed
+    #[display(fmt = "failed")]
+    Failed,
+}
+
+impl From<String> for CrawlPageStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "unchanged" => CrawlPageStatus::Unchanged,
+            "skipped_by_path" => CrawlPageStatus::SkippedByPath,
+            "empty" => CrawlPageStatus::Empty,
+            "failed" => CrawlPageStatus::Failed,
+            _ => CrawlPageStatus::Ingested,
+        }
+    }
+}
+
+/// Outcome of a page as reported by the crawl worker before it is stored with its run
+#[derive(Debug, Clone)]
+pub struct CrawlPageResult {
+    pub url: String,
+    pub status: CrawlPageStatus,
+    pub error: Option<String>,
+    pub chunks_created: usize,
+    /// Hash of the page's chunks or the version reported by a sync connector, used to tell changed pages apart
+    pub content_hash: Option<String>,
+}
+
+impl CrawlPageResult {
+    pub fn from_details(
+        url: String,
+        status: CrawlPageStatus,
+        chunks_created: usize,
+        content_hash: Option<String>,
+    ) -> Self {
+        CrawlPageResult {
+            url,
+            status,
+            error: None,
+            chunks_created,
+            content_hash,
+        }
+    }
+
+    pub fn failed(url: String, error: String) -> Self {
+        CrawlPageResult {
+            url,
+            status: CrawlPageStatus::Failed,
+            error: Some(error),
+            chunks_created: 0,
+            content_hash: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = crawl_runs)]
+pub struct CrawlRunPG {
+    pub id: uuid::Uuid,
+    pub crawl_request_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub scrape_id: uuid::Uuid,
+    pub status: String,
+    pub error: Option<String>,
+    pub pages_ingested: i32,
+    pub pages_unchanged: i32,
+    pub pages_skipped: i32,
+    pub pages_failed: i32,
+    pub chunks_created: i32,
+    pub chunks_deleted: i32,
+    pub started_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+}
+
+impl CrawlRunPG {
+    pub fn from_details(crawl_request: &CrawlRequest) -> Self {
+        CrawlRunPG {
+            id: uuid::Uuid::new_v4(),
+            crawl_request_id: crawl_request.id,
+            dataset_id: crawl_request.dataset_id,
+            scrape_id: crawl_request.scrape_id,
+            status: CrawlRunStatus::Running.to_string(),
+            error: None,
+            pages_ingested: 0,
+            pages_unchanged: 0,
+            pages_skipped: 0,
+            pages_failed: 0,
+            chunks_created: 0,
+            chunks_deleted: 0,
+            started_at: chrono::Utc::now().naive_local(),
+            finished_at: None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "crawl_request_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "dataset_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "scrape_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "status": "completed",
+    "error": null,
+    "pages_ingested": 120,
+    "pages_unchanged": 300,
+    "pages_skipped": 12,
+    "pages_failed": 2,
+    "chunks_created": 1400,
+    "chunks_deleted": 25,
+    "started_at": "2021-01-01 00:00:00.000",
+    "finished_at": "2021-01-01 00:05:00.000",
+}))]
+/// A single run of a dataset's crawl or sync connector along with how many pages ended up in each outcome.
+pub struct CrawlRun {
+    pub id: uuid::Uuid,
+    pub crawl_request_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub scrape_id: uuid::Uuid,
+    pub status: CrawlRunStatus,
+    /// Error which stopped the run if it failed
+    pub error: Option<String>,
+    pub pages_ingested: i32,
+    pub pages_unchanged: i32,
+    /// Pages which were skipped by path or had no content
+    pub pages_skipped: i32,
+    pub pages_failed: i32,
+    pub chunks_created: i32,
+    pub chunks_deleted: i32,
+    pub started_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<CrawlRunPG> for CrawlRun {
+    fn from(run: CrawlRunPG) -> Self {
+        CrawlRun {
+            id: run.id,
+            crawl_request_id: run.crawl_request_id,
+            dataset_id: run.dataset_id,
+            scrape_id: run.scrape_id,
+            status: run.status.into(),
+            error: run.error,
+            pages_ingested: run.pages_ingested,
+            pages_unchanged: run.pages_unchanged,
+            pages_skipped: run.pages_skipped,
+            pages_failed: run.pages_failed,
+            chunks_created: run.chunks_created,
+            chunks_deleted: run.chunks_deleted,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+        }
+    }
+}
+
+/// What the last sync of a connector ingested for a document of its source
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = connector_documents)]
+pub struct ConnectorDocumentPG {
+    pub id: uuid::Uuid,
+    pub crawl_request_id: uuid::Uuid,
+    pub source: String,
+    pub version: String,
+    pub tracking_ids: Vec<Option<String>>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = crawl_pages)]
+pub struct CrawlPagePG {
+    pub id: uuid::Uuid,
+    pub run_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub url: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub chunks_created: i32,
+    pub content_hash: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl CrawlPagePG {
+    pub fn from_result(
+        run_id: uuid::Uuid,
+        dataset_id: uuid::Uuid,
+        result: CrawlPageResult,
+    ) -> Self {
+        CrawlPagePG {
+            id: uuid::Uuid::new_v4(),
+            run_id,
+            dataset_id,
+            url: result.url,
+            status: result.status.to_string(),
+            error: result.error,
+            chunks_created: result.chunks_created as i32,
+            content_hash: result.content_hash,
+            created_at: chrono::Utc::now().naive_local(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "run_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "url": "https://example.com/docs/getting-started",
+    "status": "failed",
+    "error": "Page returned status code 404",
+    "chunks_created": 0,
+    "content_hash": null,
+    "created_at": "2021-01-01 00:00:00.000",
+}))]
+/// Outcome of a single page of a crawl run.
+pub struct CrawlPage {
+    pub id: uuid::Uuid,
+    pub run_id: uuid::Uuid,
+    /// Url of the page, or the path, object key or feed item id for sync connectors
+    pub url: String,
+    pub status: CrawlPageStatus,
+    pub error: Option<String>,
+    pub chunks_created: i32,
+    pub content_hash: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<CrawlPagePG> for CrawlPage {
+    fn from(page: CrawlPagePG) -> Self {
+        CrawlPage {
+            id: page.id,
+            run_id: page.run_id,
+            url: page.url,
+            status: page.status.into(),
+            error: page.error,
+            chunks_created: page.chunks_created,
+            content_hash: page.content_hash,
+            created_at: page.created_at,
+        }
+    }
+}
+
+/// Options for setting up the crawl which will populate the dataset.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default)]
 #[schema(example=json!({
     "site_url": "https://example.com",
     "interval": "daily",
@@ -6741,7 +8583,7 @@ pub struct CrawlOptions {
 
 #[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
 #[serde(tag = "type")]
-/// Options for including an openapi spec or shopify settigns
+/// Options for including an openapi spec or shopify settigns, or for syncing the dataset from a source other than a site crawl
 pub enum ScrapeOptions {
     /// OpenAPI Scrape Options
     #[serde(rename = "openapi")]
@@ -6749,6 +8591,35 @@ pub enum ScrapeOptions {
     /// Shopify Scrape Options
     #[serde(rename = "shopify")]
     Shopify(CrawlShopifyOptions),
+    /// Git Repository Sync Options
+    #[serde(rename = "git")]
+    Git(CrawlGitOptions),
+    /// S3 Prefix Sync Options
+    #[serde(rename = "s3")]
+    S3(CrawlS3Options),
+    /// RSS or Atom Feed Sync Options
+    #[serde(rename = "rss")]
+    Rss(CrawlRssOptions),
+    /// Sitemap Sync Options
+    #[serde(rename = "sitemap")]
+    Sitemap(CrawlSitemapOptions),
+}
+
+impl ScrapeOptions {
+    /// Location of the source a connector syncs from, None for the options which are applied on top of a site crawl
+    pub fn source_url(&self) -> Option<String> {
+        match self {
+            ScrapeOptions::Git(options) => Some(options.repo_url.clone()),
+            ScrapeOptions::S3(options) => Some(format!(
+                "s3://{}/{}",
+                options.bucket,
+                options.prefix.clone().unwrap_or_default()
+            )),
+            ScrapeOptions::Rss(options) => Some(options.feed_url.clone()),
+            ScrapeOptions::Sitemap(options) => Some(options.sitemap_url.clone()),
+            ScrapeOptions::OpenApi(_) | ScrapeOptions::Shopify(_) => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
@@ -6759,7 +8630,96 @@ pub struct CrawlShopifyOptions {
     pub group_variants: Option<bool>,
 }
 
+#[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
+#[schema(title = "CrawlGitOptions")]
+/// Options for syncing the Markdown and code files of a Git repository
+pub struct CrawlGitOptions {
+    /// Https or ssh URL of the repository to clone
+    pub repo_url: String,
+    /// Branch to sync, defaults to the repository's default branch
+    pub branch: Option<String>,
+    /// Only files under this directory of the repository are synced
+    pub path_prefix: Option<String>,
+    /// Extensions of the files to sync, defaults to md and mdx. Markdown files are split by heading and other files are split into blocks of lines.
+    pub file_extensions: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
+#[schema(title = "CrawlS3Options")]
+/// Options for syncing the Markdown, HTML and text files under an S3 prefix. The bucket is read with the access key given for the connector, which is stored encrypted and never returned.
+pub struct CrawlS3Options {
+    /// Name of the bucket
+    pub bucket: String,
+    /// Only objects whose key starts with this prefix are synced
+    pub prefix: Option<String>,
+    /// Https endpoint of the bucket, defaults to AWS S3 in the region
+    pub endpoint: Option<String>,
+    /// Region of the bucket, defaults to us-east-1
+    pub region: Option<String>,
+    /// Id of the access key to read the bucket with. Required when the connector is created, afterwards the stored key is kept unless a new one is given.
+    #[serde(default, skip_serializing)]
+    pub access_key_id: Option<String>,
+    /// Secret of the access key to read the bucket with
+    #[serde(default, skip_serializing)]
+    pub secret_access_key: Option<String>,
+}
+
+impl CrawlS3Options {
+    pub fn get_credentials(&self) -> Option<ConnectorCredentials> {
+        match (&self.access_key_id, &self.secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) => Some(ConnectorCredentials {
+                access_key_id: access_key_id.clone(),
+                secret_access_key: secret_access_key.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Credentials a sync connector reads its source with, stored encrypted on the crawl request
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConnectorCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
+#[schema(title = "CrawlRssOptions")]
+/// Options for syncing the items of an RSS or Atom feed
+pub struct CrawlRssOptions {
+    /// URL of the feed
+    pub feed_url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
+#[schema(title = "CrawlSitemapOptions")]
+/// Options for syncing the pages listed in a sitemap, fetched directly instead of through Firecrawl
+pub struct CrawlSitemapOptions {
+    /// URL of the sitemap or sitemap index
+    pub sitemap_url: String,
+}
+
 impl CrawlOptions {
+    /// Whether the pages are crawled by Firecrawl, Shopify stores and the sync connectors are fetched by the crawl worker itself
+    pub fn uses_firecrawl(&self) -> bool {
+        matches!(self.scrape_options, None | Some(ScrapeOptions::OpenApi(_)))
+    }
+
+    /// Whether the crawl is one of the sync connectors which diff against the documents ingested by previous runs
+    pub fn is_connector(&self) -> bool {
+        self.scrape_options
+            .as_ref()
+            .is_some_and(|scrape_options| scrape_options.source_url().is_some())
+    }
+
+    /// Location the crawl reads from, the connector's source or else the site url
+    pub fn source_url(&self) -> Option<String> {
+        self.scrape_options
+            .as_ref()
+            .and_then(|scrape_options| scrape_options.source_url())
+            .or(self.site_url.clone())
+    }
+
     pub fn merge(&self, other: CrawlOptions) -> CrawlOptions {
         CrawlOptions {
             site_url: self.site_url.clone().or(other.site_url.clone()),
@@ -6834,3 +8794,541 @@ impl From<CrawlOptions> for FirecrawlCrawlRequest {
         }
     }
 }
+
+#[derive(
+    Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone, PartialEq, ToSchema,
+)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "dataset_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "synonyms": ["tv", "television", "telly"],
+    "input": null,
+    "created_at": "2021-01-01 00:00:00.000",
+    "updated_at": "2021-01-01 00:00:00.000",
+}))]
+#[diesel(table_name = dataset_synonyms)]
+pub struct DatasetSynonymSet {
+    pub id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    /// Terms of the synonym set. Terms can be multiple words.
+    pub synonyms: Vec<Option<String>>,
+    /// If set the synonym set is one-way, queries containing `input` are expanded with `synonyms` but queries containing one of the `synonyms` are not expanded with `input`. If not set all terms are equivalent.
+    pub input: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl DatasetSynonymSet {
+    pub fn from_details(
+        dataset_id: uuid::Uuid,
+        synonyms: Vec<String>,
+        input: Option<String>,
+    ) -> Self {
+        DatasetSynonymSet {
+            id: uuid::Uuid::new_v4(),
+            dataset_id,
+            synonyms: synonyms.into_iter().map(Some).collect(),
+            input,
+            created_at: chrono::Utc::now().naive_local(),
+            updated_at: chrono::Utc::now().naive_local(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// How the query pattern of a merchandising rule is matched against the search query. Matching is case-insensitive.
+pub enum MerchandisingQueryMatch {
+    /// The rule applies to every query.
+    #[display(fmt = "any")]
+    Any,
+    /// The query must equal the pattern.
+    #[display(fmt = "exact")]
+    Exact,
+    /// The query must contain the pattern.
+    #[display(fmt = "contains")]
+    Contains,
+    /// The query must match the pattern as a regular expression.
+    #[display(fmt = "regex")]
+    Regex,
+}
+
+impl From<String> for MerchandisingQueryMatch {
+    fn from(query_match: String) -> Self {
+        match query_match.as_str() {
+            "exact" => MerchandisingQueryMatch::Exact,
+            "contains" => MerchandisingQueryMatch::Contains,
+            "regex" => MerchandisingQueryMatch::Regex,
+            _ => MerchandisingQueryMatch::Any,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[schema(example = json!({
+    "type": "pin",
+    "tracking_id": "SKU-1234",
+    "position": 1
+}))]
+pub enum MerchandisingAction {
+    /// Place the chunk with the tracking id at the position, 1-indexed across all pages. The chunk is added to the results if the search did not return it.
+    Pin { tracking_id: String, position: u32 },
+    /// Move the chunks with the tracking ids to the end of the page.
+    Bury { tracking_ids: Vec<String> },
+    /// Remove the chunks with the tracking ids from the results.
+    Hide { tracking_ids: Vec<String> },
+    /// Multiply the score of chunks with the tag by the factor.
+    Boost { tag: String, factor: f64 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = merchandising_rules)]
+pub struct MerchandisingRulePG {
+    pub id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub name: String,
+    pub query_match: String,
+    pub query_pattern: Option<String>,
+    pub action: serde_json::Value,
+    pub starts_at: Option<chrono::NaiveDateTime>,
+    pub ends_at: Option<chrono::NaiveDateTime>,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "dataset_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "name": "Pin the new TV for tv queries",
+    "query_match": "contains",
+    "query_pattern": "tv",
+    "action": {"type": "pin", "tracking_id": "SKU-1234", "position": 1},
+    "starts_at": "2024-11-01T00:00:00",
+    "ends_at": "2024-12-01T00:00:00",
+    "enabled": true,
+    "created_at": "2021-01-01 00:00:00.000",
+    "updated_at": "2021-01-01 00:00:00.000",
+}))]
+pub struct MerchandisingRule {
+    pub id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub name: String,
+    pub query_match: MerchandisingQueryMatch,
+    pub query_pattern: Option<String>,
+    pub action: MerchandisingAction,
+    /// The rule is only applied after this time. If not set, the rule applies immediately.
+    pub starts_at: Option<chrono::NaiveDateTime>,
+    /// The rule is only applied before this time. If not set, the rule never expires.
+    pub ends_at: Option<chrono::NaiveDateTime>,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl TryFrom<MerchandisingRulePG> for MerchandisingRule {
+    type Error = ServiceError;
+
+    fn try_from(rule: MerchandisingRulePG) -> Result<Self, Self::Error> {
+        Ok(MerchandisingRule {
+            id: rule.id,
+            dataset_id: rule.dataset_id,
+            name: rule.name,
+            query_match: rule.query_match.into(),
+            query_pattern: rule.query_pattern,
+            action: serde_json::from_value(rule.action).map_err(|err| {
+                ServiceError::InternalServerError(format!(
+                    "Invalid merchandising rule action: {}",
+                    err
+                ))
+            })?,
+            starts_at: rule.starts_at,
+            ends_at: rule.ends_at,
+            enabled: rule.enabled,
+            created_at: rule.created_at,
+            updated_at: rule.updated_at,
+        })
+    }
+}
+
+impl From<MerchandisingRule> for MerchandisingRulePG {
+    fn from(rule: MerchandisingRule) -> Self {
+        MerchandisingRulePG {
+            id: rule.id,
+            dataset_id: rule.dataset_id,
+            name: rule.name,
+            query_match: rule.query_match.to_string(),
+            query_pattern: rule.query_pattern,
+            action: serde_json::json!(rule.action),
+            starts_at: rule.starts_at,
+            ends_at: rule.ends_at,
+            enabled: rule.enabled,
+            created_at: rule.created_at,
+            updated_at: rule.updated_at,
+        }
+    }
+}
+
+/// The user or API key which made a change to a chunk. Both are empty for changes made by Trieve itself, e.g. webhooks.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+pub struct ChunkVersionActor {
+    /// Id of the user who made the change
+    pub user_id: Option<uuid::Uuid>,
+    /// Id of the API key the change was made with, if it was not made from a logged in session
+    pub api_key_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Display, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// The kind of change a chunk version records.
+pub enum ChunkVersionAction {
+    #[display(fmt = "create")]
+    Create,
+    #[display(fmt = "update")]
+    Update,
+    #[display(fmt = "delete")]
+    Delete,
+}
+
+impl From<String> for ChunkVersionAction {
+    fn from(action: String) -> Self {
+        match action.as_str() {
+            "update" => ChunkVersionAction::Update,
+            "delete" => ChunkVersionAction::Delete,
+            _ => ChunkVersionAction::Create,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = chunk_metadata_versions)]
+pub struct ChunkMetadataVersionPG {
+    pub id: uuid::Uuid,
+    pub chunk_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub action: String,
+    pub link: Option<String>,
+    pub chunk_html: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub tracking_id: Option<String>,
+    pub time_stamp: Option<NaiveDateTime>,
+    pub location: Option<GeoInfo>,
+    pub image_urls: Option<Vec<Option<String>>>,
+    pub tag_set: Option<Vec<Option<String>>>,
+    pub num_value: Option<f64>,
+    pub weight: f64,
+    pub actor_user_id: Option<uuid::Uuid>,
+    pub actor_api_key_id: Option<uuid::Uuid>,
+    pub created_at: chrono::NaiveDateTime,
+    pub group_ids: Option<Vec<Option<uuid::Uuid>>>,
+}
+
+impl ChunkMetadataVersionPG {
+    pub fn from_details(
+        chunk: ChunkMetadata,
+        action: ChunkVersionAction,
+        actor: Option<ChunkVersionActor>,
+    ) -> Self {
+        let actor = actor.unwrap_or_default();
+
+        ChunkMetadataVersionPG {
+            id: uuid::Uuid::new_v4(),
+            chunk_id: chunk.id,
+            dataset_id: chunk.dataset_id,
+            action: action.to_string(),
+            link: chunk.link,
+            chunk_html: chunk.chunk_html,
+            metadata: chunk.metadata,
+            tracking_id: chunk.tracking_id,
+            time_stamp: chunk.time_stamp,
+            location: chunk.location,
+            image_urls: chunk.image_urls,
+            tag_set: chunk.tag_set,
+            num_value: chunk.num_value,
+            weight: chunk.weight,
+            actor_user_id: actor.user_id,
+            actor_api_key_id: actor.api_key_id,
+            created_at: chrono::Utc::now().naive_local(),
+            group_ids: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "chunk_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "dataset_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "action": "update",
+    "link": "https://trieve.ai",
+    "chunk_html": "<p>Hello, world!</p>",
+    "metadata": {"key": "value"},
+    "tracking_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+    "time_stamp": "2021-01-01 00:00:00.000",
+    "tag_set": ["tag1", "tag2"],
+    "weight": 0.5,
+    "actor": {"user_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3", "api_key_id": null},
+    "created_at": "2021-01-01 00:00:00.000",
+    "group_ids": ["e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3"],
+}))]
+/// Snapshot of a chunk's content and group membership right after a create or update, or right before a delete.
+pub struct ChunkMetadataVersion {
+    pub id: uuid::Uuid,
+    pub chunk_id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub action: ChunkVersionAction,
+    pub link: Option<String>,
+    pub chunk_html: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    pub tracking_id: Option<String>,
+    pub time_stamp: Option<NaiveDateTime>,
+    pub location: Option<GeoInfo>,
+    pub image_urls: Option<Vec<String>>,
+    pub tag_set: Option<Vec<String>>,
+    pub num_value: Option<f64>,
+    pub weight: f64,
+    pub actor: ChunkVersionActor,
+    pub created_at: chrono::NaiveDateTime,
+    /// Ids of the groups the chunk belonged to. Absent for versions recorded before group membership was versioned.
+    pub group_ids: Option<Vec<uuid::Uuid>>,
+}
+
+impl From<ChunkMetadataVersionPG> for ChunkMetadataVersion {
+    fn from(version: ChunkMetadataVersionPG) -> Self {
+        ChunkMetadataVersion {
+            id: version.id,
+            chunk_id: version.chunk_id,
+            dataset_id: version.dataset_id,
+            action: version.action.into(),
+            link: version.link,
+            chunk_html: version.chunk_html,
+            metadata: version.metadata,
+            tracking_id: version.tracking_id,
+            time_stamp: version.time_stamp,
+            location: version.location,
+            image_urls: version
+                .image_urls
+                .map(|urls| urls.into_iter().flatten().collect()),
+            tag_set: version
+                .tag_set
+                .map(|tags| tags.into_iter().flatten().collect()),
+            num_value: version.num_value,
+            weight: version.weight,
+            actor: ChunkVersionActor {
+                user_id: version.actor_user_id,
+                api_key_id: version.actor_api_key_id,
+            },
+            created_at: version.created_at,
+            group_ids: version
+                .group_ids
+                .map(|ids| ids.into_iter().flatten().collect()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable, Selectable, Clone)]
+#[diesel(table_name = rag_answer_caches)]
+pub struct RagAnswerCache {
+    pub id: uuid::Uuid,
+    pub dataset_id: uuid::Uuid,
+    pub query: String,
+    pub filters_hash: String,
+    pub completion: String,
+    pub chunk_ids: Vec<Option<uuid::Uuid>>,
+    pub hit_count: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_hit_at: Option<chrono::NaiveDateTime>,
+    /// Size of the question's embedding, which picks the Qdrant collection it is searched in
+    pub embedding_size: i32,
+}
+
+impl RagAnswerCache {
+    pub fn from_details(
+        dataset_id: uuid::Uuid,
+        query: String,
+        embedding_size: usize,
+        filters_hash: String,
+        completion: String,
+        chunk_ids: Vec<uuid::Uuid>,
+    ) -> Self {
+        RagAnswerCache {
+            id: uuid::Uuid::new_v4(),
+            dataset_id,
+            query,
+            embedding_size: embedding_size as i32,
+            filters_hash,
+            completion,
+            chunk_ids: chunk_ids.into_iter().map(Some).collect(),
+            hit_count: 0,
+            created_at: chrono::Utc::now().naive_local(),
+            last_hit_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn recency_bias(
+        decay_function: RecencyDecayFunction,
+        offset: Option<chrono::Duration>,
+    ) -> RecencyBias {
+        RecencyBias {
+            decay_function: Some(decay_function),
+            scale: DecayDuration(chrono::Duration::days(30)),
+            offset: offset.map(DecayDuration),
+            origin: None,
+            decay: Some(0.5),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {} to be {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_exp_decay_factor_halves_every_scale() {
+        let origin = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let bias = recency_bias(RecencyDecayFunction::Exp, None);
+
+        assert_close(bias.decay_factor(origin, origin), 1.0);
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(30), origin),
+            0.5,
+        );
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(60), origin),
+            0.25,
+        );
+        // Time stamps after the origin decay the same as ones before it
+        assert_close(
+            bias.decay_factor(origin + chrono::Duration::days(30), origin),
+            0.5,
+        );
+    }
+
+    #[test]
+    fn test_gauss_decay_factor() {
+        let origin = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let bias = recency_bias(RecencyDecayFunction::Gauss, None);
+
+        assert_close(bias.decay_factor(origin, origin), 1.0);
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(30), origin),
+            0.5,
+        );
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(60), origin),
+            0.0625,
+        );
+    }
+
+    #[test]
+    fn test_decay_factor_starts_after_offset() {
+        let origin = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let bias = recency_bias(RecencyDecayFunction::Exp, Some(chrono::Duration::days(7)));
+
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(7), origin),
+            1.0,
+        );
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(37), origin),
+            0.5,
+        );
+    }
+
+    #[test]
+    fn test_decay_factor_ignores_zero_scale() {
+        let origin = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let bias = RecencyBias {
+            scale: DecayDuration(chrono::Duration::zero()),
+            ..recency_bias(RecencyDecayFunction::Exp, None)
+        };
+
+        assert_close(
+            bias.decay_factor(origin - chrono::Duration::days(365), origin),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn test_decay_duration_parsing() {
+        assert_eq!(
+            DecayDuration::try_from("30d".to_string()),
+            Ok(DecayDuration(chrono::Duration::days(30)))
+        );
+        assert_eq!(
+            DecayDuration::try_from("1.5h".to_string()),
+            Ok(DecayDuration(chrono::Duration::minutes(90)))
+        );
+        assert!(DecayDuration::try_from("30".to_string()).is_err());
+        assert!(DecayDuration::try_from("-1d".to_string()).is_err());
+        assert!(DecayDuration::try_from("3y".to_string()).is_err());
+    }
+
+    fn audit_date_filter(date_range: DateRange) -> AuditLogFilter {
+        AuditLogFilter {
+            date_range: Some(date_range),
+            actions: None,
+            actor_user_id: None,
+            api_key_id: None,
+            target_id: None,
+        }
+    }
+
+    #[test]
+    fn test_audit_log_filter_date_range() {
+        let query = audit_date_filter(DateRange {
+            gte: Some("2024-01-01".to_string()),
+            lte: None,
+            gt: None,
+            lt: Some("2024-02-01T12:30:00+02:00".to_string()),
+        })
+        .add_to_query(String::new())
+        .unwrap();
+
+        assert_eq!(
+            query,
+            " AND created_at < '2024-02-01 10:30:00.000' AND created_at >= '2024-01-01 00:00:00.000'"
+        );
+    }
+
+    #[test]
+    fn test_audit_log_filter_rejects_invalid_dates() {
+        for date in [
+            "2024-01-01' OR '1'='1",
+            "2024-01-01 00:00:00' OR 1=1 --",
+            "yesterday",
+        ] {
+            assert!(audit_date_filter(DateRange {
+                gte: None,
+                lte: None,
+                gt: Some(date.to_string()),
+                lt: None,
+            })
+            .add_to_query(String::new())
+            .is_err());
+        }
+    }
+}
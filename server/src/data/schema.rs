@@ -51,6 +51,54 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    chunk_metadata_versions (id) {
+        id -> Uuid,
+        chunk_id -> Uuid,
+        dataset_id -> Uuid,
+        action -> Text,
+        link -> Nullable<Text>,
+        chunk_html -> Nullable<Text>,
+        metadata -> Nullable<Jsonb>,
+        tracking_id -> Nullable<Text>,
+        time_stamp -> Nullable<Timestamp>,
+        location -> Nullable<Jsonb>,
+        image_urls -> Nullable<Array<Nullable<Text>>>,
+        tag_set -> Nullable<Array<Nullable<Text>>>,
+        num_value -> Nullable<Float8>,
+        weight -> Float8,
+        actor_user_id -> Nullable<Uuid>,
+        actor_api_key_id -> Nullable<Uuid>,
+        created_at -> Timestamp,
+        group_ids -> Nullable<Array<Nullable<Uuid>>>,
+    }
+}
+
+diesel::table! {
+    connector_documents (id) {
+        id -> Uuid,
+        crawl_request_id -> Uuid,
+        source -> Text,
+        version -> Text,
+        tracking_ids -> Array<Nullable<Text>>,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    crawl_pages (id) {
+        id -> Uuid,
+        run_id -> Uuid,
+        dataset_id -> Uuid,
+        url -> Text,
+        status -> Text,
+        error -> Nullable<Text>,
+        chunks_created -> Int4,
+        content_hash -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     crawl_requests (id) {
         id -> Uuid,
@@ -62,6 +110,28 @@ diesel::table! {
         dataset_id -> Uuid,
         created_at -> Timestamp,
         crawl_options -> Jsonb,
+        encrypted_credentials -> Nullable<Text>,
+        actor_user_id -> Nullable<Uuid>,
+        actor_api_key_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    crawl_runs (id) {
+        id -> Uuid,
+        crawl_request_id -> Uuid,
+        dataset_id -> Uuid,
+        scrape_id -> Uuid,
+        status -> Text,
+        error -> Nullable<Text>,
+        pages_ingested -> Int4,
+        pages_unchanged -> Int4,
+        pages_skipped -> Int4,
+        pages_failed -> Int4,
+        chunks_created -> Int4,
+        chunks_deleted -> Int4,
+        started_at -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
     }
 }
 
@@ -81,6 +151,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    dataset_synonyms (id) {
+        id -> Uuid,
+        dataset_id -> Uuid,
+        synonyms -> Array<Nullable<Text>>,
+        input -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     dataset_tags (id) {
         id -> Uuid,
@@ -148,6 +229,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    merchandising_rules (id) {
+        id -> Uuid,
+        dataset_id -> Uuid,
+        name -> Text,
+        query_match -> Text,
+        query_pattern -> Nullable<Text>,
+        action -> Jsonb,
+        starts_at -> Nullable<Timestamp>,
+        ends_at -> Nullable<Timestamp>,
+        enabled -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     messages (id) {
         id -> Uuid,
@@ -162,6 +259,8 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         dataset_id -> Uuid,
+        citations -> Nullable<Jsonb>,
+        summarized_through -> Nullable<Int4>,
     }
 }
 
@@ -177,6 +276,54 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    organization_domains (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        domain -> Text,
+        verification_token -> Text,
+        default_role -> Int4,
+        verified_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    organization_scim_tokens (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        blake3_hash -> Text,
+        token_prefix -> Text,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        can_grant_owner -> Bool,
+    }
+}
+
+diesel::table! {
+    organization_sso_identities (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        user_id -> Uuid,
+        subject -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    organization_sso_configs (id) {
+        id -> Uuid,
+        organization_id -> Uuid,
+        provider -> Text,
+        oidc_issuer_url -> Nullable<Text>,
+        oidc_client_id -> Nullable<Text>,
+        encrypted_oidc_client_secret -> Nullable<Text>,
+        saml_idp_metadata -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     organizations (id) {
         id -> Uuid,
@@ -185,6 +332,22 @@ diesel::table! {
         updated_at -> Timestamp,
         registerable -> Nullable<Bool>,
         deleted -> Int4,
+        sso_only -> Bool,
+    }
+}
+
+diesel::table! {
+    rag_answer_caches (id) {
+        id -> Uuid,
+        dataset_id -> Uuid,
+        query -> Text,
+        filters_hash -> Text,
+        completion -> Text,
+        chunk_ids -> Array<Nullable<Uuid>>,
+        hit_count -> Int4,
+        created_at -> Timestamp,
+        last_hit_at -> Nullable<Timestamp>,
+        embedding_size -> Int4,
     }
 }
 
@@ -253,6 +416,14 @@ diesel::table! {
         dataset_ids -> Nullable<Array<Nullable<Text>>>,
         organization_ids -> Nullable<Array<Nullable<Text>>>,
         scopes -> Nullable<Array<Nullable<Text>>>,
+        permissions -> Nullable<Array<Nullable<Text>>>,
+        expires_at -> Nullable<Timestamp>,
+        allowed_cidrs -> Nullable<Array<Nullable<Text>>>,
+        key_prefix -> Nullable<Text>,
+        last_used_at -> Nullable<Timestamp>,
+        previous_blake3_hash -> Nullable<Text>,
+        previous_hash_expires_at -> Nullable<Timestamp>,
+        rate_limits -> Nullable<Jsonb>,
     }
 }
 
@@ -284,18 +455,31 @@ diesel::joinable!(chunk_group_bookmarks -> chunk_group (group_id));
 diesel::joinable!(chunk_group_bookmarks -> chunk_metadata (chunk_metadata_id));
 diesel::joinable!(chunk_metadata -> datasets (dataset_id));
 diesel::joinable!(chunk_metadata_tags -> chunk_metadata (chunk_metadata_id));
+diesel::joinable!(chunk_metadata_versions -> datasets (dataset_id));
 diesel::joinable!(chunk_metadata_tags -> dataset_tags (tag_id));
+diesel::joinable!(connector_documents -> crawl_requests (crawl_request_id));
+diesel::joinable!(crawl_pages -> crawl_runs (run_id));
+diesel::joinable!(crawl_pages -> datasets (dataset_id));
 diesel::joinable!(crawl_requests -> datasets (dataset_id));
+diesel::joinable!(crawl_runs -> datasets (dataset_id));
 diesel::joinable!(dataset_event_counts -> datasets (dataset_uuid));
+diesel::joinable!(dataset_synonyms -> datasets (dataset_id));
 diesel::joinable!(dataset_tags -> datasets (dataset_id));
 diesel::joinable!(dataset_usage_counts -> datasets (dataset_id));
 diesel::joinable!(datasets -> organizations (organization_id));
 diesel::joinable!(files -> datasets (dataset_id));
 diesel::joinable!(groups_from_files -> chunk_group (group_id));
 diesel::joinable!(groups_from_files -> files (file_id));
+diesel::joinable!(merchandising_rules -> datasets (dataset_id));
 diesel::joinable!(messages -> datasets (dataset_id));
 diesel::joinable!(messages -> topics (topic_id));
+diesel::joinable!(organization_domains -> organizations (organization_id));
+diesel::joinable!(organization_scim_tokens -> organizations (organization_id));
+diesel::joinable!(organization_sso_configs -> organizations (organization_id));
+diesel::joinable!(organization_sso_identities -> organizations (organization_id));
+diesel::joinable!(organization_sso_identities -> users (user_id));
 diesel::joinable!(organization_usage_counts -> organizations (org_id));
+diesel::joinable!(rag_answer_caches -> datasets (dataset_id));
 diesel::joinable!(stripe_invoices -> organizations (org_id));
 diesel::joinable!(stripe_subscriptions -> organizations (organization_id));
 diesel::joinable!(stripe_subscriptions -> stripe_plans (plan_id));
@@ -309,17 +493,28 @@ diesel::allow_tables_to_appear_in_same_query!(
     chunk_group_bookmarks,
     chunk_metadata,
     chunk_metadata_tags,
+    chunk_metadata_versions,
+    connector_documents,
+    crawl_pages,
     crawl_requests,
+    crawl_runs,
     dataset_event_counts,
     dataset_group_counts,
+    dataset_synonyms,
     dataset_tags,
     dataset_usage_counts,
     datasets,
     files,
     groups_from_files,
     invitations,
+    merchandising_rules,
     messages,
+    organization_domains,
+    organization_scim_tokens,
+    organization_sso_configs,
+    organization_sso_identities,
     organization_usage_counts,
+    rag_answer_caches,
     organizations,
     stripe_invoices,
     stripe_plans,
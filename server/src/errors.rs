@@ -40,6 +40,9 @@ pub enum ServiceError {
 
     #[display(fmt = "Payload Too Large")]
     PayloadTooLarge(String),
+
+    #[display(fmt = "Too Many Requests")]
+    TooManyRequests(u64),
 }
 
 // impl ResponseError trait allows to convert our errors into http responses with appropriate data
@@ -82,6 +85,14 @@ impl ResponseError for ServiceError {
                     message: message.to_string(),
                 })
             }
+            ServiceError::TooManyRequests(retry_after) => HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(ErrorResponseBody {
+                    message: format!(
+                        "Too Many Requests: rate limit exceeded, retry after {} seconds",
+                        retry_after
+                    ),
+                }),
         }
     }
 }
@@ -1,4 +1,4 @@
-use super::auth_handler::AdminOnly;
+use super::auth_handler::{AdminOnly, AnalyticsRead, Permitted};
 use crate::{
     data::models::{
         CTRAnalytics, CTRAnalyticsResponse, CTRType, ClusterAnalytics, ClusterAnalyticsResponse,
@@ -39,6 +39,7 @@ use utoipa::ToSchema;
     )
 )]
 pub async fn get_cluster_analytics(
+    _permission: Permitted<AnalyticsRead>,
     data: web::Json<ClusterAnalytics>,
     _user: AdminOnly,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -174,6 +175,7 @@ pub async fn set_rag_query_rating(
     )
 )]
 pub async fn get_search_analytics(
+    _permission: Permitted<AnalyticsRead>,
     data: web::Json<SearchAnalytics>,
     _user: AdminOnly,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -331,6 +333,7 @@ pub async fn get_search_analytics(
     )
 )]
 pub async fn get_rag_analytics(
+    _permission: Permitted<AnalyticsRead>,
     data: web::Json<RAGAnalytics>,
     _user: AdminOnly,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -417,6 +420,7 @@ pub async fn get_rag_analytics(
     )
 )]
 pub async fn get_recommendation_analytics(
+    _permission: Permitted<AnalyticsRead>,
     data: web::Json<RecommendationAnalytics>,
     _user: AdminOnly,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -603,6 +607,7 @@ pub async fn send_event_data(
     )
 )]
 pub async fn get_ctr_analytics(
+    _permission: Permitted<AnalyticsRead>,
     _user: AdminOnly,
     data: web::Json<CTRAnalytics>,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -700,6 +705,7 @@ pub async fn get_ctr_analytics(
     )
 )]
 pub async fn get_all_events(
+    _permission: Permitted<AnalyticsRead>,
     _user: AdminOnly,
     data: web::Json<GetEventsRequestBody>,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -739,6 +745,7 @@ pub async fn get_all_events(
     )
 )]
 pub async fn get_event_by_id(
+    _permission: Permitted<AnalyticsRead>,
     _user: AdminOnly,
     data: web::Path<uuid::Uuid>,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -778,6 +785,7 @@ pub struct GetTopDatasetsRequestBody {
     )
 )]
 pub async fn get_top_datasets(
+    _permission: Permitted<AnalyticsRead>,
     _user: AdminOnly,
     data: web::Json<GetTopDatasetsRequestBody>,
     clickhouse_client: web::Data<clickhouse::Client>,
@@ -1,8 +1,16 @@
-use crate::data::models::{Organization, RedisPool, StripePlan, UserRole};
+use crate::data::models::{
+    ApiKeyPermission, AuditActor, AuditAuthMethod, ChunkVersionActor, Organization, RedisPool,
+    StripePlan, UserApiKey, UserRole,
+};
 use crate::get_env;
+use crate::handlers::sso_handler::{start_sso_login, SSO_ORGANIZATION_SESSION_KEY};
+use crate::middleware::auth_middleware::{get_api_key_from_headers, get_client_ip};
 use crate::operators::dittofeed_operator::{get_user_ditto_identity, send_user_ditto_identity};
 use crate::operators::invitation_operator::check_inv_valid;
 use crate::operators::organization_operator::{get_org_from_id_query, get_user_org_count};
+use crate::operators::sso_operator::{
+    get_sso_config_query, get_verified_domain_for_email_query, join_verified_domain_org_query,
+};
 use crate::operators::user_operator::{
     add_user_to_organization, create_user_query, get_user_by_oidc_subject_query,
 };
@@ -23,13 +31,16 @@ use oauth2::{
     AuthUrl, AuthorizationCode, ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
     RedirectUrl, Scope, TokenResponse,
 };
-use openidconnect::core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata};
+use openidconnect::core::{
+    CoreAuthenticationFlow, CoreClient, CoreIdTokenClaims, CoreProviderMetadata,
+};
 use openidconnect::{AccessTokenHash, ClientId, IssuerUrl, Nonce};
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::fs::read_to_string;
 use std::future::{ready, Ready};
+use std::marker::PhantomData;
 use utoipa::{IntoParams, ToSchema};
 
 #[derive(Deserialize, Debug)]
@@ -46,6 +57,10 @@ impl FromRequest for LoggedUser {
 
     #[inline]
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Err(err) = ensure_api_key_permitted(req) {
+            return ready(Err(err.into()));
+        }
+
         ready(
             req.extensions()
                 .get::<LoggedUser>()
@@ -55,6 +70,50 @@ impl FromRequest for LoggedUser {
     }
 }
 
+impl FromRequest for ChunkVersionActor {
+    type Error = Error;
+    type Future = Ready<Result<ChunkVersionActor, Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ext = req.extensions();
+
+        ready(Ok(ChunkVersionActor {
+            user_id: ext.get::<LoggedUser>().map(|user| user.id),
+            api_key_id: ext.get::<UserApiKey>().map(|api_key| api_key.id),
+        }))
+    }
+}
+
+impl FromRequest for AuditActor {
+    type Error = Error;
+    type Future = Ready<Result<AuditActor, Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ext = req.extensions();
+        let api_key_id = ext.get::<UserApiKey>().map(|api_key| api_key.id);
+
+        // The admin api key authenticates as the organization's owner without a UserApiKey
+        let auth_method = if api_key_id.is_some() {
+            AuditAuthMethod::ApiKey
+        } else if get_api_key_from_headers(req.headers()).is_some_and(|api_key| {
+            std::env::var("ADMIN_API_KEY").is_ok_and(|admin_api_key| api_key == admin_api_key)
+        }) {
+            AuditAuthMethod::AdminApiKey
+        } else {
+            AuditAuthMethod::Session
+        };
+
+        ready(Ok(AuditActor {
+            user_id: ext.get::<LoggedUser>().map(|user| user.id),
+            auth_method,
+            api_key_id,
+            ip_address: get_client_ip(req).map(|ip| ip.to_string()),
+        }))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OrganizationRole {
     pub user: SlimUser,
@@ -70,6 +129,10 @@ impl FromRequest for AdminOnly {
 
     #[inline]
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Err(err) = ensure_api_key_permitted(req) {
+            return ready(Err(err));
+        }
+
         let ext = req.extensions();
 
         match ext.get::<OrganizationRole>() {
@@ -96,6 +159,10 @@ impl FromRequest for OwnerOnly {
 
     #[inline]
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Err(err) = ensure_api_key_permitted(req) {
+            return ready(Err(err));
+        }
+
         let ext = req.extensions();
 
         match ext.get::<OrganizationRole>() {
@@ -109,6 +176,103 @@ impl FromRequest for OwnerOnly {
     }
 }
 
+/// A permission an api key can be granted, used as the type parameter of `Permitted`.
+pub trait ApiKeyPermissionMarker {
+    const PERMISSION: ApiKeyPermission;
+}
+
+#[derive(Debug)]
+pub struct ChunkRead;
+
+impl ApiKeyPermissionMarker for ChunkRead {
+    const PERMISSION: ApiKeyPermission = ApiKeyPermission::ChunkRead;
+}
+
+#[derive(Debug)]
+pub struct ChunkWrite;
+
+impl ApiKeyPermissionMarker for ChunkWrite {
+    const PERMISSION: ApiKeyPermission = ApiKeyPermission::ChunkWrite;
+}
+
+#[derive(Debug)]
+pub struct GroupWrite;
+
+impl ApiKeyPermissionMarker for GroupWrite {
+    const PERMISSION: ApiKeyPermission = ApiKeyPermission::GroupWrite;
+}
+
+#[derive(Debug)]
+pub struct RagCreate;
+
+impl ApiKeyPermissionMarker for RagCreate {
+    const PERMISSION: ApiKeyPermission = ApiKeyPermission::RagCreate;
+}
+
+#[derive(Debug)]
+pub struct AnalyticsRead;
+
+impl ApiKeyPermissionMarker for AnalyticsRead {
+    const PERMISSION: ApiKeyPermission = ApiKeyPermission::AnalyticsRead;
+}
+
+/// Marks requests whose api key was checked by `Permitted`
+#[derive(Debug, Clone, Copy)]
+struct PermissionChecked;
+
+/// Api keys which were granted permissions are denied by default. They can only be used on routes which check one of their permissions with `Permitted` before extracting the user, dataset or organization.
+pub fn ensure_api_key_permitted(req: &HttpRequest) -> Result<(), ServiceError> {
+    let ext = req.extensions();
+
+    match ext.get::<UserApiKey>() {
+        Some(api_key) if api_key.has_permissions() && ext.get::<PermissionChecked>().is_none() => {
+            Err(ServiceError::Forbidden)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects requests authenticated with an api key which was not granted the permission `P`. Session users and api keys without any permissions are let through, role extractors still apply to them. Must be the first extractor of the handler, the user, dataset and organization extractors deny keys with permissions on routes which did not check one.
+#[derive(Debug)]
+pub struct Permitted<P: ApiKeyPermissionMarker>(PhantomData<P>);
+
+impl<P: ApiKeyPermissionMarker> FromRequest for Permitted<P> {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if req
+            .extensions()
+            .get::<UserApiKey>()
+            .is_some_and(|api_key| !api_key.has_permission(P::PERMISSION))
+        {
+            return ready(Err(ServiceError::Forbidden));
+        }
+
+        req.extensions_mut().insert(PermissionChecked);
+
+        ready(Ok(Self(PhantomData)))
+    }
+}
+
+/// Rejects requests authenticated with a user's api key, used by the routes which create and rotate api keys such that a key can not be used to get a key with more access than it has.
+#[derive(Debug)]
+pub struct NoApiKey;
+
+impl FromRequest for NoApiKey {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        match req.extensions().get::<UserApiKey>() {
+            Some(_) => ready(Err(ServiceError::Forbidden)),
+            None => ready(Ok(Self)),
+        }
+    }
+}
+
 //test
 
 #[tracing::instrument]
@@ -283,7 +447,7 @@ const OIDC_SESSION_KEY: &str = "oidc_state";
 
 #[derive(Deserialize, Debug, ToSchema, IntoParams)]
 #[schema(
-    example = json!({"organization_id": "00000000-0000-0000-0000-000000000000", "redirect_uri": "https://api.trieve.ai", "inv_code": "00000000-0000-0000-0000-000000000000"}),
+    example = json!({"organization_id": "00000000-0000-0000-0000-000000000000", "redirect_uri": "https://api.trieve.ai", "inv_code": "00000000-0000-0000-0000-000000000000", "email": "jane@acme.com"}),
 )]
 pub struct AuthQuery {
     /// ID of organization to authenticate into
@@ -292,6 +456,8 @@ pub struct AuthQuery {
     pub redirect_uri: Option<String>,
     /// Code sent via email as a result of successful call to send_invitation
     pub inv_code: Option<uuid::Uuid>,
+    /// Email of the user signing in. If an organization verified its domain and configured SSO, the user is sent to the organization's identity provider instead.
+    pub email: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -306,7 +472,7 @@ pub struct LoginState {
 
 /// Login
 ///
-/// This will redirect you to the OAuth provider for authentication with email/pass, SSO, Google, Github, etc.
+/// This will redirect you to the OAuth provider for authentication with email/pass, SSO, Google, Github, etc. Pass an email to be sent to the identity provider of the organization which verified its domain instead.
 #[utoipa::path(
     get,
     path = "/auth",
@@ -318,13 +484,35 @@ pub struct LoginState {
         (status = 400, description = "OAuth error likely with OIDC provider.", body = ErrorResponseBody),
     )
 )]
-#[tracing::instrument(skip(oidc_client, session))]
+#[tracing::instrument(skip(oidc_client, session, pool, redis_pool))]
 pub async fn login(
     req: HttpRequest,
     session: Session,
     data: web::Query<AuthQuery>,
     oidc_client: web::Data<CoreClient>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
 ) -> Result<HttpResponse, Error> {
+    let redirect_uri = match data.redirect_uri.clone() {
+        Some(redirect_uri) => redirect_uri,
+        None => req
+            .headers()
+            .get("Referer")
+            .map(|h| h.to_str().unwrap_or("/"))
+            .unwrap_or("/")
+            .to_string(),
+    };
+
+    if let Some(email) = data.email.as_deref() {
+        if let Some((domain, _)) = get_verified_domain_for_email_query(email, pool.clone()).await? {
+            if let Some(sso_config) =
+                get_sso_config_query(domain.organization_id, pool.clone()).await?
+            {
+                return Ok(start_sso_login(&session, sso_config, redirect_uri, redis_pool).await?);
+            }
+        }
+    }
+
     let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
     let (auth_url, csrf_token, nonce) = oidc_client
@@ -350,16 +538,6 @@ pub async fn login(
         .insert(OIDC_SESSION_KEY, oidc_state)
         .map_err(|_| ServiceError::InternalServerError("Could not set OIDC Session".into()))?;
 
-    let redirect_uri = match data.redirect_uri.clone() {
-        Some(redirect_uri) => redirect_uri,
-        None => req
-            .headers()
-            .get("Referer")
-            .map(|h| h.to_str().unwrap_or("/"))
-            .unwrap_or("/")
-            .to_string(),
-    };
-
     let login_state = LoginState {
         redirect_uri,
         organization_id: data.organization_id,
@@ -376,41 +554,19 @@ pub async fn login(
         .finish())
 }
 
-/// OpenID Connect callback
-///
-/// This is the callback route for the OAuth provider, it should not be called directly. Redirects to browser with set-cookie header.
-#[utoipa::path(
-    get,
-    path = "/auth/callback",
-    context_path = "/api",
-    tag = "Auth",
-    responses(
-        (status = 200, description = "Response that returns with set-cookie header", body = SlimUser),
-        (status = 400, description = "Email or password empty or incorrect", body = ErrorResponseBody),
-    )
-)]
-#[tracing::instrument(skip(session, oidc_client, pool, clickhouse_client, redis_pool))]
-pub async fn callback(
-    req: HttpRequest,
-    session: Session,
-    oidc_client: web::Data<CoreClient>,
-    redis_pool: web::Data<RedisPool>,
-    clickhouse_client: web::Data<clickhouse::Client>,
-    pool: web::Data<Pool>,
-    query: web::Query<OpCallback>,
-) -> Result<HttpResponse, Error> {
-    let state: OpenIdConnectState = session
-        .get(OIDC_SESSION_KEY)
-        .map_err(|_| ServiceError::InternalServerError("Could not get OIDC Session".into()))?
-        .ok_or(ServiceError::Unauthorized)?;
-
-    let code_verifier = state.pkce_verifier;
-    let code = query.code.clone();
-    let nonce = state.nonce;
+/// Exchanges the authorization code the provider redirected back with and verifies the ID token returned along with it
+pub async fn get_verified_oidc_claims(
+    oidc_client: &CoreClient,
+    state: OpenIdConnectState,
+    callback: OpCallback,
+) -> Result<CoreIdTokenClaims, ServiceError> {
+    if callback.state != *state.csrf_token.secret() {
+        return Err(ServiceError::Unauthorized);
+    }
 
     let token_response = oidc_client
-        .exchange_code(AuthorizationCode::new(code))
-        .set_pkce_verifier(code_verifier)
+        .exchange_code(AuthorizationCode::new(callback.code))
+        .set_pkce_verifier(state.pkce_verifier)
         .request_async(async_http_client)
         .await
         .map_err(|e| match e {
@@ -433,7 +589,7 @@ pub async fn callback(
 
     let id_token_verifier = oidc_client.id_token_verifier();
     let claims = id_token
-        .claims(&id_token_verifier, &nonce)
+        .claims(&id_token_verifier, &state.nonce)
         .map_err(|_| ServiceError::InternalServerError("Claims Verification Error".into()))?;
 
     match claims.access_token_hash() {
@@ -459,6 +615,72 @@ pub async fn callback(
         }
     }?;
 
+    Ok(claims.clone())
+}
+
+/// Sets the identity cookie for the user and caches them with their organizations
+pub async fn login_user(
+    req: &HttpRequest,
+    user: User,
+    user_orgs: Vec<UserOrganization>,
+    orgs: Vec<Organization>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    let user_string = serde_json::to_string(&user).map_err(|_| {
+        ServiceError::InternalServerError("Failed to serialize user to JSON".into())
+    })?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    let slim_user = SlimUser::from_details(user, user_orgs, orgs);
+
+    let slim_user_string = serde_json::to_string(&slim_user).map_err(|_| {
+        ServiceError::InternalServerError("Failed to serialize slim user to JSON".into())
+    })?;
+
+    redis_conn
+        .set::<_, _, ()>(slim_user.id.to_string(), slim_user_string)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Identity::login(&req.extensions(), user_string).expect("Failed to set login state for user");
+
+    Ok(())
+}
+
+/// OpenID Connect callback
+///
+/// This is the callback route for the OAuth provider, it should not be called directly. Redirects to browser with set-cookie header.
+#[utoipa::path(
+    get,
+    path = "/auth/callback",
+    context_path = "/api",
+    tag = "Auth",
+    responses(
+        (status = 200, description = "Response that returns with set-cookie header", body = SlimUser),
+        (status = 400, description = "Email or password empty or incorrect", body = ErrorResponseBody),
+    )
+)]
+#[tracing::instrument(skip(session, oidc_client, pool, clickhouse_client, redis_pool))]
+pub async fn callback(
+    req: HttpRequest,
+    session: Session,
+    oidc_client: web::Data<CoreClient>,
+    redis_pool: web::Data<RedisPool>,
+    clickhouse_client: web::Data<clickhouse::Client>,
+    pool: web::Data<Pool>,
+    query: web::Query<OpCallback>,
+) -> Result<HttpResponse, Error> {
+    let state: OpenIdConnectState = session
+        .get(OIDC_SESSION_KEY)
+        .map_err(|_| ServiceError::InternalServerError("Could not get OIDC Session".into()))?
+        .ok_or(ServiceError::Unauthorized)?;
+
+    let claims = get_verified_oidc_claims(&oidc_client, state, query.into_inner()).await?;
+
     let user_oidc_subject = claims.subject().to_string();
 
     let email = claims.email().ok_or_else(|| {
@@ -469,6 +691,18 @@ pub async fn callback(
         ServiceError::InternalServerError("Failed to parse name from claims".into())
     })?;
 
+    if let Some((_, organization)) =
+        get_verified_domain_for_email_query(email.as_str(), pool.clone()).await?
+    {
+        if organization.sso_only {
+            return Err(ServiceError::BadRequest(format!(
+                "{} requires signing in through its SSO provider, sign in with your email to be sent to it",
+                organization.name
+            ))
+            .into());
+        }
+    }
+
     let login_state = session
         .get::<LoginState>("login_state")
         .map_err(|_| ServiceError::InternalServerError("Could not get redirect url".into()))?
@@ -536,33 +770,36 @@ pub async fn callback(
                 invitation.organization_id,
                 invitation.role.into(),
             );
-            add_user_to_organization(None, None, user_org, pool, redis_pool.clone()).await?;
+            add_user_to_organization(None, None, user_org, pool.clone(), redis_pool.clone())
+                .await?;
         }
     }
 
-    let user_string = serde_json::to_string(&user).map_err(|_| {
-        ServiceError::InternalServerError("Failed to serialize user to JSON".into())
-    })?;
-
-    let mut redis_conn = redis_pool
-        .get()
-        .await
-        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
-
-    let slim_user = SlimUser::from_details(user, user_orgs.clone(), orgs);
-
-    let slim_user_string = serde_json::to_string(&slim_user).map_err(|_| {
-        ServiceError::InternalServerError("Failed to serialize slim user to JSON".into())
-    })?;
-
-    redis_conn
-        .set::<_, _, ()>(slim_user.id.to_string(), slim_user_string)
+    // Only emails the provider has verified are trusted to join the organization which verified their domain
+    let joined_domain_org = claims.email_verified().unwrap_or(false)
+        && join_verified_domain_org_query(
+            user.id,
+            email.as_str(),
+            &user_orgs,
+            pool.clone(),
+            redis_pool.clone(),
+        )
         .await
-        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+        .unwrap_or_else(|err| {
+            log::error!("Failed to join verified domain organization {:?}", err);
+            false
+        });
+
+    let (user, user_orgs, orgs) = if joined_domain_org {
+        get_user_by_id_query(&user.id, pool).await?
+    } else {
+        (user, user_orgs, orgs)
+    };
 
-    Identity::login(&req.extensions(), user_string).expect("Failed to set login state for user");
+    login_user(&req, user, user_orgs.clone(), orgs, redis_pool).await?;
     session.remove(OIDC_SESSION_KEY);
     session.remove("login_state");
+    session.remove(SSO_ORGANIZATION_SESSION_KEY);
 
     // Add a query param if the user has just been created and is the owner of
     // one organization
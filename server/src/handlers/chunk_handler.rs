@@ -1,9 +1,10 @@
-use super::auth_handler::{AdminOnly, LoggedUser};
+use super::auth_handler::{AdminOnly, ChunkRead, ChunkWrite, LoggedUser, Permitted, RagCreate};
 use crate::data::models::{
     escape_quotes, ChatMessageProxy, ChunkMetadata, ChunkMetadataStringTagSet,
-    ChunkMetadataWithScore, ConditionType, ContextOptions, CountSearchMethod,
-    DatasetAndOrgWithSubAndPlan, DatasetConfiguration, GeoInfo, HighlightOptions, ImageConfig,
-    IngestSpecificChunkMetadata, Pool, QueryTypes, RagQueryEventClickhouse, RecommendType,
+    ChunkMetadataWithScore, ChunkVersionActor, ConditionType, ContextOptions, CountSearchMethod,
+    DatasetAndOrgWithSubAndPlan, DatasetConfiguration, DatasetLanguage, FacetOptions, FacetResults,
+    GeoInfo, HighlightOptions, HybridSearchOptions, ImageConfig, IngestSpecificChunkMetadata, Pool,
+    QueryTypes, RagQueryEventClickhouse, RagStreamFormat, RecencyBias, RecommendType,
     RecommendationEventClickhouse, RecommendationStrategy, RedisPool, ScoreChunk, ScoreChunkDTO,
     SearchMethod, SearchQueryEventClickhouse, SlimChunkMetadataWithScore, SortByField, SortOptions,
     TypoOptions, UnifiedId, UpdateSpecificChunkMetadata,
@@ -17,13 +18,17 @@ use crate::operators::clickhouse_operator::{get_latency_from_header, ClickHouseE
 use crate::operators::dataset_operator::{
     get_dataset_usage_query, ChunkDeleteMessage, DeleteMessage,
 };
+use crate::operators::facet_operator::get_facets_query;
+use crate::operators::message_operator::{
+    sse_completion_stream, RagRetrievedChunk, RagStreamEvent,
+};
 use crate::operators::parse_operator::convert_html_to_text;
 use crate::operators::qdrant_operator::{
     point_ids_exists_in_qdrant, recommend_qdrant_query, scroll_dataset_points,
 };
 use crate::operators::search_operator::{
-    assemble_qdrant_filter, autocomplete_chunks_query, count_chunks_query, search_chunks_query,
-    search_hybrid_chunks,
+    assemble_qdrant_filter, autocomplete_chunks_query, count_chunks_query, parse_vector_query,
+    search_chunks_query, search_hybrid_chunks,
 };
 use actix::Arbiter;
 use actix_web::web::Bytes;
@@ -34,7 +39,8 @@ use dateparser::DateTimeUtc;
 use itertools::Itertools;
 use openai_dive::v1::api::Client;
 use openai_dive::v1::resources::chat::{
-    ChatCompletionParameters, ChatMessage, ChatMessageContent, DeltaChatMessage,
+    ChatCompletionParameters, ChatCompletionStreamOptions, ChatMessage, ChatMessageContent,
+    DeltaChatMessage,
 };
 use openai_dive::v1::resources::chat::{ImageUrl, ImageUrlType};
 use openai_dive::v1::resources::shared::StopToken;
@@ -42,6 +48,8 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use simple_server_timing_header::Timer;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tokio_stream::StreamExt;
 use utoipa::ToSchema;
 
@@ -90,7 +98,8 @@ pub struct ScoringOptions {
     },
     "image_urls": ["https://example.com/red", "https://example.com/blue"],
     "fulltext_boost": {"phrase": "foo", "boost_factor": 5.0},
-    "semantic_boost": {"phrase": "flagship", "distance_factor": 0.5}
+    "semantic_boost": {"phrase": "flagship", "distance_factor": 0.5},
+    "vector_fields": {"title": "Some title", "summary": "A short summary of the content"}
 }))]
 pub struct ChunkReqPayload {
     /// HTML content of the chunk. This can also be plaintext. The innerText of the HTML will be used to create the embedding vector. The point of using HTML is for convienience, as some users have applications where users submit HTML content.
@@ -131,6 +140,8 @@ pub struct ChunkReqPayload {
     /// Semantic boost is useful for moving the embedding vector of the chunk in the direction of the distance phrase. I.e. you can push a chunk with a chunk_html of "iphone" 25% closer to the term "flagship" by using the distance phrase "flagship" and a distance factor of 0.25. Conceptually it's drawing a line (euclidean/L2 distance) between the vector for the innerText of the chunk_html and distance_phrase then moving the vector of the chunk_html distance_factor*L2Distance closer to or away from the distance_phrase point along the line between the two points.
     #[serde(alias = "distance_phrase")]
     pub semantic_boost: Option<SemanticBoost>,
+    /// Vector fields maps the names of the dataset's VECTOR_FIELDS, such as "title" or "summary", to the text which should be embedded into that field's vector. Searches can then target the vector of a single field or a weighted combination of fields. Keys must be listed in the VECTOR_FIELDS of the dataset's configuration.
+    pub vector_fields: Option<HashMap<String, String>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
@@ -211,6 +222,8 @@ pub struct BulkUploadIngestionMessage {
     pub attempt_number: usize,
     pub dataset_id: uuid::Uuid,
     pub ingestion_messages: Vec<UploadIngestionMessage>,
+    /// Recorded on the versions of the chunks once they are ingested
+    pub actor: Option<ChunkVersionActor>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -302,9 +315,11 @@ pub enum CreateChunkReqPayloadEnum {
 )]
 #[tracing::instrument(skip(redis_pool, pool))]
 pub async fn create_chunk(
+    _permission: Permitted<ChunkWrite>,
     create_chunk_data: web::Json<CreateChunkReqPayloadEnum>,
     pool: web::Data<Pool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     redis_pool: web::Data<RedisPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
@@ -361,15 +376,16 @@ pub async fn create_chunk(
     let (upsert_chunks, non_upsert_chunks): (Vec<ChunkReqPayload>, Vec<ChunkReqPayload>) =
         chunks.partition(|chunk| chunk.upsert_by_tracking_id.unwrap_or(false));
 
-    let (non_upsert_chunk_ingestion_message, non_upsert_chunk_metadatas) = create_chunk_metadata(
-        non_upsert_chunks,
-        dataset_org_plan_sub.dataset.id,
-        dataset_config.clone(),
-        pool.clone(),
-    )
-    .await?;
+    let (mut non_upsert_chunk_ingestion_message, non_upsert_chunk_metadatas) =
+        create_chunk_metadata(
+            non_upsert_chunks,
+            dataset_org_plan_sub.dataset.id,
+            dataset_config.clone(),
+            pool.clone(),
+        )
+        .await?;
 
-    let (upsert_chunk_ingestion_message, upsert_chunk_metadatas) = create_chunk_metadata(
+    let (mut upsert_chunk_ingestion_message, upsert_chunk_metadatas) = create_chunk_metadata(
         upsert_chunks,
         dataset_org_plan_sub.dataset.id,
         dataset_config.clone(),
@@ -377,6 +393,9 @@ pub async fn create_chunk(
     )
     .await?;
 
+    non_upsert_chunk_ingestion_message.actor = Some(actor);
+    upsert_chunk_ingestion_message.actor = Some(actor);
+
     let chunk_metadatas = non_upsert_chunk_metadatas
         .clone()
         .into_iter()
@@ -461,9 +480,11 @@ pub async fn create_chunk(
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn delete_chunk(
+    _permission: Permitted<ChunkWrite>,
     chunk_id: web::Path<uuid::Uuid>,
     pool: web::Data<Pool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
 ) -> Result<HttpResponse, actix_web::Error> {
     let dataset_config =
@@ -479,6 +500,7 @@ pub async fn delete_chunk(
         dataset_org_plan_sub.dataset,
         pool,
         dataset_config,
+        Some(actor),
     )
     .await?;
 
@@ -513,9 +535,11 @@ pub struct BulkDeleteChunkPayload {
     )
 )]
 pub async fn bulk_delete_chunk(
+    _permission: Permitted<ChunkWrite>,
     chunk_filter: web::Json<BulkDeleteChunkPayload>,
     redis_pool: web::Data<RedisPool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut redis_conn = redis_pool
@@ -527,6 +551,7 @@ pub async fn bulk_delete_chunk(
         dataset_id: dataset_org_plan_sub.dataset.id,
         attempt_number: 0,
         filter: chunk_filter.into_inner().filter,
+        actor: Some(actor),
     };
 
     let serialized_message = serde_json::to_string(&DeleteMessage::ChunkDelete(message))
@@ -564,9 +589,11 @@ pub async fn bulk_delete_chunk(
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn delete_chunk_by_tracking_id(
+    _permission: Permitted<ChunkWrite>,
     tracking_id: web::Path<String>,
     pool: web::Data<Pool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
 ) -> Result<HttpResponse, actix_web::Error> {
     let tracking_id_inner = tracking_id.into_inner();
@@ -585,6 +612,7 @@ pub async fn delete_chunk_by_tracking_id(
         dataset_org_plan_sub.dataset,
         pool,
         dataset_config,
+        Some(actor),
     )
     .await?;
 
@@ -636,6 +664,8 @@ pub struct UpdateChunkReqPayload {
     /// Semantic boost is useful for moving the embedding vector of the chunk in the direction of the distance phrase. I.e. you can push a chunk with a chunk_html of "iphone" 25% closer to the term "flagship" by using the distance phrase "flagship" and a distance factor of 0.25. Conceptually it's drawing a line (euclidean/L2 distance) between the vector for the innerText of the chunk_html and distance_phrase then moving the vector of the chunk_html distance_factor*L2Distance closer to or away from the distance_phrase point along the line between the two points.
     #[serde(alias = "distance_phrase")]
     pub semantic_boost: Option<SemanticBoost>,
+    /// Vector fields maps the names of the dataset's VECTOR_FIELDS to the text which should be re-embedded into that field's vector. Vector fields which are not provided keep their existing vectors.
+    pub vector_fields: Option<HashMap<String, String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -646,6 +676,9 @@ pub struct UpdateIngestionMessage {
     pub convert_html_to_text: Option<bool>,
     pub fulltext_boost: Option<FullTextBoost>,
     pub semantic_boost: Option<SemanticBoost>,
+    pub vector_fields: Option<HashMap<String, String>>,
+    /// Recorded on the version of the chunk once it is updated
+    pub actor: Option<ChunkVersionActor>,
 }
 
 /// Update Chunk
@@ -670,15 +703,26 @@ pub struct UpdateIngestionMessage {
 )]
 #[tracing::instrument(skip(pool, redis_pool))]
 pub async fn update_chunk(
+    _permission: Permitted<ChunkWrite>,
     update_chunk_data: web::Json<UpdateChunkReqPayload>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
 ) -> Result<HttpResponse, actix_web::Error> {
     let dataset_id = dataset_org_plan_sub.dataset.id;
     let chunk_id = update_chunk_data.chunk_id;
 
+    if let Some(vector_fields) = &update_chunk_data.vector_fields {
+        check_chunk_vector_fields(
+            vector_fields,
+            &DatasetConfiguration::from_json(
+                dataset_org_plan_sub.dataset.server_configuration.clone(),
+            ),
+        )?;
+    }
+
     let chunk_metadata = if let Some(chunk_id) = chunk_id {
         get_metadata_from_id_query(chunk_id, dataset_id, pool).await?
     } else if let Some(tracking_id) = update_chunk_data.tracking_id.clone() {
@@ -776,6 +820,8 @@ pub async fn update_chunk(
         convert_html_to_text: update_chunk_data.convert_html_to_text,
         fulltext_boost: update_chunk_data.fulltext_boost.clone(),
         semantic_boost: update_chunk_data.semantic_boost.clone(),
+        vector_fields: update_chunk_data.vector_fields.clone(),
+        actor: Some(actor),
     };
 
     let mut redis_conn = redis_pool
@@ -838,10 +884,12 @@ pub struct UpdateChunkByTrackingIdData {
 #[deprecated]
 #[tracing::instrument(skip(pool, redis_pool))]
 pub async fn update_chunk_by_tracking_id(
+    _permission: Permitted<ChunkWrite>,
     update_chunk_data: web::Json<UpdateChunkByTrackingIdData>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
 ) -> Result<HttpResponse, actix_web::Error> {
     if update_chunk_data.tracking_id.is_empty() {
@@ -922,6 +970,8 @@ pub async fn update_chunk_by_tracking_id(
         convert_html_to_text: update_chunk_data.convert_html_to_text,
         fulltext_boost: None,
         semantic_boost: None,
+        vector_fields: None,
+        actor: Some(actor),
     };
 
     let mut redis_conn = redis_pool
@@ -1004,7 +1054,7 @@ pub struct ChunkFilter {
     "score_threshold": 0.5
 }))]
 pub struct SearchChunksReqPayload {
-    /// Can be either "semantic", "fulltext", "hybrid, or "bm25". If specified as "hybrid", it will pull in one page of both semantic and full-text results then re-rank them using scores from a cross encoder model. "semantic" will pull in one page of the nearest cosine distant vectors. "fulltext" will pull in one page of full-text results based on SPLADE. "bm25" will get one page of results scored using BM25 with the terms OR'd together.
+    /// Can be either "semantic", "fulltext", "hybrid, or "bm25". If specified as "hybrid", it will pull in one page of both semantic and full-text results then merge them using the fusion strategy from hybrid_options, which defaults to re-ranking with scores from a cross encoder model. "semantic" will pull in one page of the nearest cosine distant vectors. "fulltext" will pull in one page of full-text results based on SPLADE. "bm25" will get one page of results scored using BM25 with the terms OR'd together.
     pub search_type: SearchMethod,
     /// Query is the search query. This can be any string. The query will be used to create an embedding vector and/or SPLADE vector which will be used to find the result set.  You can either provide one query, or multiple with weights. Multi-query only works with Semantic Search.
     pub query: QueryTypes,
@@ -1036,6 +1086,14 @@ pub struct SearchChunksReqPayload {
     pub user_id: Option<String>,
     /// Typo options lets you specify different methods to handle typos in the search query. If not specified, this defaults to no typo handling.
     pub typo_options: Option<TypoOptions>,
+    /// Hybrid options lets you specify how the semantic and fulltext legs of a hybrid search are retrieved and fused. Only used when search_type is "hybrid". If not specified, both legs fetch one page and are re-ranked with a cross encoder.
+    pub hybrid_options: Option<HybridSearchOptions>,
+    /// Facets lets you request term counts, num_value range buckets and time_stamp date histograms computed over every chunk matching the filters of the search. If not specified, no facets are computed.
+    pub facets: Option<FacetOptions>,
+    /// Vector fields lets you choose which vectors of the chunks a semantic search is scored against, mapping the names of the dataset's VECTOR_FIELDS to a weight. Use "chunk_html" for the vector of the chunk itself. A single field searches only that field's vector, while several fields are searched separately and scored by the weighted sum of their scores. Can only be used when search_type is "semantic". If not specified, only the vector of the chunk itself is searched.
+    pub vector_fields: Option<HashMap<String, f32>>,
+    /// Recency bias lets you rank your results by how close their time_stamp is to an origin, which defaults to now. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
 }
 
 impl Default for SearchChunksReqPayload {
@@ -1057,6 +1115,10 @@ impl Default for SearchChunksReqPayload {
             remove_stop_words: None,
             user_id: None,
             typo_options: None,
+            hybrid_options: None,
+            facets: None,
+            vector_fields: None,
+            recency_bias: None,
         }
     }
 }
@@ -1067,6 +1129,11 @@ pub struct SearchChunkQueryResponseBody {
     pub score_chunks: Vec<ScoreChunkDTO>,
     pub corrected_query: Option<String>,
     pub total_chunk_pages: i64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub facets: Option<FacetResults>,
+    /// Ids of the merchandising rules which were applied to the results, including rules which hid chunks. Only present if a rule was applied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -1076,6 +1143,11 @@ pub struct SearchResponseBody {
     pub chunks: Vec<ScoreChunk>,
     pub corrected_query: Option<String>,
     pub total_pages: i64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub facets: Option<FacetResults>,
+    /// Ids of the merchandising rules which were applied to the results, including rules which hid chunks. Only present if a rule was applied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -1098,6 +1170,8 @@ impl SearchChunkQueryResponseBody {
                 .collect(),
             corrected_query: self.corrected_query,
             total_pages: self.total_chunk_pages,
+            facets: self.facets,
+            merchandising_rule_ids: self.merchandising_rule_ids,
         }
     }
 }
@@ -1107,6 +1181,31 @@ pub struct ParsedQuery {
     pub query: String,
     pub quote_words: Option<Vec<String>>,
     pub negated_words: Option<Vec<String>>,
+    pub vectors: Option<QueryVectors>,
+}
+
+/// Vectors of a raw vector or chunk query which are used instead of embedding the query text
+#[derive(Clone, Debug, Default)]
+pub struct QueryVectors {
+    pub dense_vector: Option<Vec<f32>>,
+    pub sparse_vector: Option<Vec<(u32, f32)>>,
+    pub bm25_vector: Option<Vec<(u32, f32)>>,
+    /// Text of the chunk the vectors come from, used for cross encoder re-ranking
+    pub content: Option<String>,
+    /// Point of the chunk the vectors come from so that it can be excluded from the results
+    pub qdrant_point_id: Option<uuid::Uuid>,
+}
+
+impl ParsedQuery {
+    /// Text the cross encoder compares results against, for chunk queries this is the chunk's content
+    pub fn to_rerank_query(&self) -> Result<String, ServiceError> {
+        match &self.vectors {
+            None => Ok(self.query.clone()),
+            Some(vectors) => vectors.content.clone().ok_or(ServiceError::BadRequest(
+                "Cannot use a raw vector query with cross encoder re-ranking".to_string(),
+            )),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -1130,8 +1229,9 @@ pub fn parse_query(
     query: String,
     use_quote_negated_terms: Option<bool>,
     remove_stop_words: Option<bool>,
+    language: DatasetLanguage,
 ) -> ParsedQuery {
-    let stop_words = get_stop_words();
+    let stop_words = get_stop_words(language);
     let query = match remove_stop_words {
         Some(true) => {
             let mut query_parts_split_by_stop_words: Vec<String> = Vec::new();
@@ -1187,12 +1287,14 @@ pub fn parse_query(
                 query,
                 quote_words,
                 negated_words,
+                vectors: None,
             }
         }
         _ => ParsedQuery {
             query,
             quote_words: None,
             negated_words: None,
+            vectors: None,
         },
     }
 }
@@ -1219,7 +1321,9 @@ pub fn parse_query(
     )
 )]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_chunks(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<SearchChunksReqPayload>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -1238,6 +1342,7 @@ pub async fn search_chunks(
             query.clone(),
             data.use_quote_negated_terms,
             data.remove_stop_words,
+            dataset_config.LANGUAGE,
         )),
         QueryTypes::Multi(query) => ParsedQueryTypes::Multi(
             query
@@ -1247,26 +1352,55 @@ pub async fn search_chunks(
                         multi_query.query.clone(),
                         data.use_quote_negated_terms,
                         data.remove_stop_words,
+                        dataset_config.LANGUAGE,
                     );
                     (parsed_query, multi_query.weight)
                 })
                 .collect::<Vec<(ParsedQuery, f32)>>(),
         ),
+        vector_query => {
+            // Vector and chunk queries have no text for typo correction to work on
+            data.typo_options = None;
+            ParsedQueryTypes::Single(
+                parse_vector_query(
+                    vector_query,
+                    dataset_org_plan_sub.dataset.id,
+                    &dataset_config,
+                    pool.clone(),
+                )
+                .await?,
+            )
+        }
     };
 
     data.score_threshold = data.score_threshold.filter(|threshold| *threshold != 0.0);
 
+    if data.vector_fields.is_some() && data.search_type != SearchMethod::Semantic {
+        return Err(ServiceError::BadRequest(
+            "vector_fields can only be used with semantic search".to_string(),
+        )
+        .into());
+    }
+
     let tx_ctx = sentry::TransactionContext::new("search", "search_chunks");
     let transaction = sentry::start_transaction(tx_ctx);
     sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone().into())));
     let mut timer = Timer::new();
 
-    let result_chunks = match data.search_type {
+    let (quote_words, negated_words) = match &parsed_query {
+        ParsedQueryTypes::Single(parsed_query) => (
+            parsed_query.quote_words.clone(),
+            parsed_query.negated_words.clone(),
+        ),
+        ParsedQueryTypes::Multi(_) => (None, None),
+    };
+
+    let mut result_chunks = match data.search_type {
         SearchMethod::Hybrid => {
             search_hybrid_chunks(
                 data.clone(),
                 parsed_query.to_parsed_query()?,
-                pool,
+                pool.clone(),
                 redis_pool,
                 dataset_org_plan_sub.dataset.clone(),
                 &dataset_config,
@@ -1278,7 +1412,7 @@ pub async fn search_chunks(
             search_chunks_query(
                 data.clone(),
                 parsed_query,
-                pool,
+                pool.clone(),
                 redis_pool,
                 dataset_org_plan_sub.dataset.clone(),
                 &dataset_config,
@@ -1289,12 +1423,25 @@ pub async fn search_chunks(
     };
     timer.add("search_chunks");
 
+    if let Some(facet_options) = data.facets.clone() {
+        result_chunks.facets = Some(
+            get_facets_query(
+                facet_options,
+                data.filters.clone(),
+                quote_words,
+                negated_words,
+                dataset_org_plan_sub.dataset.id,
+                &dataset_config,
+                pool,
+            )
+            .await?,
+        );
+        timer.add("computed facets");
+    }
+
     let search_id = uuid::Uuid::new_v4();
 
-    let query = match &data.query {
-        QueryTypes::Single(query) => query.clone(),
-        QueryTypes::Multi(query) => serde_json::to_string(&query).unwrap_or_default(),
-    };
+    let query = data.query.to_query_string();
 
     let clickhouse_event = SearchQueryEventClickhouse {
         id: search_id,
@@ -1321,6 +1468,13 @@ pub async fn search_chunks(
         created_at: time::OffsetDateTime::now_utc(),
         query_rating: String::from(""),
         user_id: data.user_id.clone().unwrap_or_default(),
+        merchandising_rule_ids: result_chunks
+            .merchandising_rule_ids
+            .clone()
+            .unwrap_or_default()
+            .iter()
+            .map(|rule_id| rule_id.to_string())
+            .collect(),
     };
 
     event_queue
@@ -1386,7 +1540,7 @@ pub async fn search_chunks(
             }
         ]
     },
-    "recency_bias": 1.0,
+    "recency_bias": {"scale": "30d", "decay": 0.5},
     "use_weights": true,
     "highlight_results": true,
     "highlight_delimiters": ["?", ",", ".", "!"],
@@ -1422,6 +1576,8 @@ pub struct AutocompleteReqPayload {
     /// User ID is the id of the user who is making the request. This is used to track user interactions with the search results.
     pub user_id: Option<String>,
     pub typo_options: Option<TypoOptions>,
+    /// Recency bias lets you rank your results by how close their time_stamp is to an origin, which defaults to now. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
 }
 
 impl From<AutocompleteReqPayload> for SearchChunksReqPayload {
@@ -1443,6 +1599,10 @@ impl From<AutocompleteReqPayload> for SearchChunksReqPayload {
             remove_stop_words: autocomplete_data.remove_stop_words,
             user_id: autocomplete_data.user_id,
             typo_options: autocomplete_data.typo_options,
+            hybrid_options: None,
+            facets: None,
+            vector_fields: None,
+            recency_bias: autocomplete_data.recency_bias,
         }
     }
 }
@@ -1469,7 +1629,9 @@ impl From<AutocompleteReqPayload> for SearchChunksReqPayload {
     )
 )]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
+#[allow(clippy::too_many_arguments)]
 pub async fn autocomplete(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<AutocompleteReqPayload>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -1485,6 +1647,7 @@ pub async fn autocomplete(
         data.query.clone(),
         data.use_quote_negated_terms,
         data.remove_stop_words,
+        dataset_config.LANGUAGE,
     );
 
     let tx_ctx = sentry::TransactionContext::new("search", "search_chunks");
@@ -1533,6 +1696,7 @@ pub async fn autocomplete(
         created_at: time::OffsetDateTime::now_utc(),
         query_rating: String::from(""),
         user_id: data.user_id.clone().unwrap_or_default(),
+        merchandising_rule_ids: vec![],
     };
 
     event_queue
@@ -1600,6 +1764,7 @@ pub struct ScrollChunksReqPayload {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn scroll_dataset_chunks(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<ScrollChunksReqPayload>,
     _user: LoggedUser,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -1678,6 +1843,7 @@ pub async fn scroll_dataset_chunks(
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunk_by_id(
+    _permission: Permitted<ChunkRead>,
     chunk_id: web::Path<uuid::Uuid>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -1745,6 +1911,10 @@ impl From<CountChunksReqPayload> for SearchChunksReqPayload {
             remove_stop_words: None,
             user_id: None,
             typo_options: None,
+            hybrid_options: None,
+            facets: None,
+            vector_fields: None,
+            recency_bias: None,
         }
     }
 }
@@ -1776,6 +1946,7 @@ pub struct CountChunkQueryResponseBody {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn count_chunks(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<CountChunksReqPayload>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -1789,6 +1960,7 @@ pub async fn count_chunks(
             query.clone(),
             data.use_quote_negated_terms,
             None,
+            dataset_config.LANGUAGE,
         )),
         QueryTypes::Multi(query) => ParsedQueryTypes::Multi(
             query
@@ -1798,11 +1970,21 @@ pub async fn count_chunks(
                         multi_query.query.clone(),
                         data.use_quote_negated_terms,
                         None,
+                        dataset_config.LANGUAGE,
                     );
                     (parsed_query, multi_query.weight)
                 })
                 .collect::<Vec<(ParsedQuery, f32)>>(),
         ),
+        vector_query => ParsedQueryTypes::Single(
+            parse_vector_query(
+                vector_query,
+                dataset_org_plan_sub.dataset.id,
+                &dataset_config,
+                pool.clone(),
+            )
+            .await?,
+        ),
     };
 
     let limit = match data.limit {
@@ -1863,6 +2045,7 @@ pub async fn count_chunks(
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunk_by_tracking_id(
+    _permission: Permitted<ChunkRead>,
     tracking_id: web::Path<String>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -1923,6 +2106,7 @@ pub struct GetChunksData {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunks_by_ids(
+    _permission: Permitted<ChunkRead>,
     chunk_payload: web::Json<GetChunksData>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -1995,6 +2179,7 @@ pub struct GetTrackingChunksData {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunks_by_tracking_ids(
+    _permission: Permitted<ChunkRead>,
     chunk_payload: web::Json<GetTrackingChunksData>,
     _user: LoggedUser,
     pool: web::Data<Pool>,
@@ -2061,6 +2246,8 @@ pub struct RecommendChunksRequest {
     pub slim_chunks: Option<bool>,
     /// User ID is the id of the user who is making the request. This is used to track user interactions with the recommendation results.
     pub user_id: Option<String>,
+    /// Recency bias lets you rank the recommended chunks by how close their time_stamp is to an origin. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -2106,6 +2293,7 @@ pub enum RecommendResponseTypes {
 )]
 #[tracing::instrument(skip(pool, event_queue))]
 pub async fn get_recommended_chunks(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<RecommendChunksRequest>,
     pool: web::Data<Pool>,
     _user: LoggedUser,
@@ -2284,10 +2472,14 @@ pub async fn get_recommended_chunks(
         })?,
     };
 
+    let recency_origin = data
+        .recency_bias
+        .map(|recency_bias| (recency_bias, recency_bias.origin()));
+
     let recommended_chunk_metadatas_with_score = recommended_chunk_metadatas
         .into_iter()
         .map(|chunk_metadata| {
-            let score = recommended_qdrant_results
+            let mut score = recommended_qdrant_results
                 .iter()
                 .find(|recommend_qdrant_result| {
                     recommend_qdrant_result.point_id == chunk_metadata.metadata().qdrant_point_id
@@ -2295,6 +2487,12 @@ pub async fn get_recommended_chunks(
                 .map(|recommend_qdrant_result| recommend_qdrant_result.score)
                 .unwrap_or(0.0);
 
+            if let (Some((recency_bias, origin)), Some(time_stamp)) =
+                (recency_origin, chunk_metadata.metadata().time_stamp)
+            {
+                score *= recency_bias.decay_factor(time_stamp, origin) as f32;
+            }
+
             ChunkMetadataWithScore::from((chunk_metadata.metadata(), score))
         })
         .collect::<Vec<ChunkMetadataWithScore>>();
@@ -2396,6 +2594,8 @@ pub struct GenerateOffChunksReqPayload {
     pub prompt: Option<String>,
     /// Whether or not to stream the response. If this is set to true or not included, the response will be a stream. If this is set to false, the response will be a normal JSON response. Default is true.
     pub stream_response: Option<bool>,
+    /// Format of the streamed response. `delimited` streams only the completion. `sse` streams Server-Sent Events of type retrieval, delta, citation, error, usage and done, where citations resolve the `[n]` doc numbers in the completion to chunk ids. Default is `delimited`.
+    pub stream_format: Option<RagStreamFormat>,
     /// Set highlight_results to false for a slight latency improvement (1-10ms). If not specified, this defaults to true. This will add `<mark><b>` tags to the chunk_html of the chunks to highlight matching splits.
     pub highlight_results: Option<bool>,
     /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic. Default is 0.5.
@@ -2426,7 +2626,7 @@ pub struct GenerateOffChunksReqPayload {
     tag = "Chunk",
     request_body(content = GenerateOffChunksReqPayload, description = "JSON request payload to perform RAG on some chunks (chunks)", content_type = "application/json"),
     responses(
-        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming.",
+        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming. With the sse stream_format this is a text/event-stream of RagStreamEvent.",
             headers(
                 ("TR-QueryID" = uuid::Uuid, description = "Query ID that is used for tracking analytics")
             )
@@ -2447,6 +2647,7 @@ pub struct GenerateOffChunksReqPayload {
 )]
 #[tracing::instrument(skip(pool, event_queue))]
 pub async fn generate_off_chunks(
+    _permission: Permitted<RagCreate>,
     data: web::Json<GenerateOffChunksReqPayload>,
     pool: web::Data<Pool>,
     event_queue: web::Data<EventQueue>,
@@ -2707,6 +2908,7 @@ pub async fn generate_off_chunks(
             rag_type: "chosen_chunks".to_string(),
             llm_response: completion_content.clone(),
             user_id: data.user_id.clone().unwrap_or_default(),
+            cache_hit: false,
         };
 
         event_queue
@@ -2721,9 +2923,26 @@ pub async fn generate_off_chunks(
     let (s, r) = unbounded::<String>();
     let stream = client
         .chat()
-        .create_stream(parameters.clone())
+        .create_stream(ChatCompletionParameters {
+            stream_options: Some(ChatCompletionStreamOptions {
+                include_usage: Some(true),
+            }),
+            ..parameters.clone()
+        })
         .await
-        .unwrap();
+        .map_err(|err| {
+            ServiceError::BadRequest(format!(
+                "Model Response Error. Please try again later. {:?}",
+                err
+            ))
+        })?;
+
+    let stream_format = data.stream_format.unwrap_or_default();
+    let sse_chunks = chunks.clone();
+    let sse_chunk_ids = chunks
+        .iter()
+        .map(|chunk| chunk.id)
+        .collect::<Vec<uuid::Uuid>>();
 
     Arbiter::new().spawn(async move {
         let chunk_v: Vec<String> = r.iter().collect();
@@ -2749,6 +2968,7 @@ pub async fn generate_off_chunks(
             query_rating: String::new(),
             llm_response: completion,
             user_id: data.user_id.clone().unwrap_or_default(),
+            cache_hit: false,
         };
 
         event_queue
@@ -2756,6 +2976,33 @@ pub async fn generate_off_chunks(
             .await;
     });
 
+    if stream_format == RagStreamFormat::Sse {
+        let retrieval_event = RagStreamEvent::Retrieval {
+            search_id: None,
+            chunks: sse_chunks
+                .into_iter()
+                .enumerate()
+                .map(|(idx, chunk)| RagRetrievedChunk {
+                    doc: idx + 1,
+                    score: None,
+                    chunk: chunk.into(),
+                })
+                .collect(),
+        };
+
+        return Ok(HttpResponse::Ok()
+            .insert_header(("TR-QueryID", query_id.to_string()))
+            .content_type("text/event-stream")
+            .streaming(sse_completion_stream(
+                retrieval_event,
+                stream,
+                sse_chunk_ids,
+                s,
+                Arc::new(Mutex::new(None)),
+                query_id,
+            )));
+    }
+
     let completion_stream = stream.map(move |response| -> Result<Bytes, actix_web::Error> {
         match response {
             Ok(response) => {
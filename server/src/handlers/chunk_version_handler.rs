@@ -0,0 +1,217 @@
+use super::auth_handler::{AdminOnly, ChunkRead, ChunkWrite, Permitted};
+use crate::{
+    data::models::{
+        ChunkMetadataVersion, ChunkVersionActor, DatasetAndOrgWithSubAndPlan, Pool, RedisPool,
+    },
+    errors::ServiceError,
+    operators::{
+        chunk_operator::get_metadata_from_ids_query,
+        chunk_version_operator::{
+            get_chunk_version_by_id_query, get_chunk_version_changes, get_chunk_versions_query,
+            get_restore_update_message, get_restore_upload_message, ChunkVersionFieldDiff,
+        },
+        group_operator::check_group_ids_exist_query,
+    },
+};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetChunkVersionsQuery {
+    /// Page number to return, 1-indexed. Default is 1.
+    pub page: Option<u64>,
+    /// Number of versions to return per page. Default is 10.
+    pub page_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetChunkVersionsResponse {
+    pub versions: Vec<ChunkMetadataVersion>,
+    pub total_pages: i64,
+}
+
+/// Get Chunk Versions
+///
+/// Get the history of a chunk, newest first. A version is recorded every time the chunk is created, updated or deleted, along with the user or api key which made the change. Versions of deleted chunks are kept such that they can be restored. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/chunk/{chunk_id}/versions",
+    context_path = "/api",
+    tag = "Chunk",
+    responses(
+        (status = 200, description = "Versions of the chunk", body = GetChunkVersionsResponse),
+        (status = 400, description = "Service error relating to getting the versions of the chunk", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("chunk_id" = uuid::Uuid, Path, description = "Id of the chunk whose versions you want to fetch."),
+        ("page" = Option<u64>, Query, description = "Page number to return, 1-indexed. Default is 1."),
+        ("page_size" = Option<u64>, Query, description = "Number of versions to return per page. Default is 10."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_chunk_versions(
+    _permission: Permitted<ChunkRead>,
+    chunk_id: web::Path<uuid::Uuid>,
+    query: web::Query<GetChunkVersionsQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let page_size = query.page_size.unwrap_or(10).max(1);
+
+    let (versions, total_count) = get_chunk_versions_query(
+        chunk_id.into_inner(),
+        dataset_org_plan_sub.dataset.id,
+        query.page.unwrap_or(1),
+        page_size,
+        pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetChunkVersionsResponse {
+        versions,
+        total_pages: (total_count as f64 / page_size as f64).ceil() as i64,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DiffChunkVersionsQuery {
+    /// Id of the older version to compare.
+    pub from_version_id: uuid::Uuid,
+    /// Id of the newer version to compare.
+    pub to_version_id: uuid::Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DiffChunkVersionsResponse {
+    pub from: ChunkMetadataVersion,
+    pub to: ChunkMetadataVersion,
+    /// Fields of the chunk which differ between the two versions. Empty if the versions have the same content.
+    pub changes: Vec<ChunkVersionFieldDiff>,
+}
+
+/// Diff Chunk Versions
+///
+/// Compare two versions of a chunk field by field. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/chunk/{chunk_id}/versions/diff",
+    context_path = "/api",
+    tag = "Chunk",
+    responses(
+        (status = 200, description = "The two versions and the fields which differ between them", body = DiffChunkVersionsResponse),
+        (status = 400, description = "Service error relating to diffing the versions of the chunk", body = ErrorResponseBody),
+        (status = 404, description = "One of the versions does not exist for the chunk", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("chunk_id" = uuid::Uuid, Path, description = "Id of the chunk whose versions you want to compare."),
+        ("from_version_id" = uuid::Uuid, Query, description = "Id of the older version to compare."),
+        ("to_version_id" = uuid::Uuid, Query, description = "Id of the newer version to compare."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn diff_chunk_versions(
+    _permission: Permitted<ChunkRead>,
+    chunk_id: web::Path<uuid::Uuid>,
+    query: web::Query<DiffChunkVersionsQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let chunk_id = chunk_id.into_inner();
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+
+    let (from, to) = futures::try_join!(
+        get_chunk_version_by_id_query(query.from_version_id, chunk_id, dataset_id, pool.clone()),
+        get_chunk_version_by_id_query(query.to_version_id, chunk_id, dataset_id, pool.clone()),
+    )?;
+
+    let changes = get_chunk_version_changes(&from, &to);
+
+    Ok(HttpResponse::Ok().json(DiffChunkVersionsResponse { from, to, changes }))
+}
+
+/// Restore Chunk Version
+///
+/// Restore the content of a chunk to one of its versions. The chunk is re-queued for ingestion such that its vectors match the restored content, and a new version is recorded once it is processed. Deleted chunks are re-created with their original id. The chunk is put back in the groups it belonged to which still exist, versions recorded before group membership was versioned leave it unchanged. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    post,
+    path = "/chunk/{chunk_id}/versions/{version_id}/restore",
+    context_path = "/api",
+    tag = "Chunk",
+    responses(
+        (status = 204, description = "Confirmation that the chunk was queued to be restored to the version"),
+        (status = 400, description = "Service error relating to restoring the chunk", body = ErrorResponseBody),
+        (status = 404, description = "The version does not exist for the chunk", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("chunk_id" = uuid::Uuid, Path, description = "Id of the chunk you want to restore."),
+        ("version_id" = uuid::Uuid, Path, description = "Id of the version you want to restore the chunk to."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn restore_chunk_version(
+    _permission: Permitted<ChunkWrite>,
+    path: web::Path<(uuid::Uuid, uuid::Uuid)>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    _user: AdminOnly,
+    actor: ChunkVersionActor,
+) -> Result<HttpResponse, ServiceError> {
+    let (chunk_id, version_id) = path.into_inner();
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+
+    let version =
+        get_chunk_version_by_id_query(version_id, chunk_id, dataset_id, pool.clone()).await?;
+
+    let group_ids = match version.group_ids {
+        Some(group_ids) => {
+            Some(check_group_ids_exist_query(group_ids, dataset_id, pool.clone()).await?)
+        }
+        None => None,
+    };
+
+    let existing_chunk = get_metadata_from_ids_query(vec![chunk_id], dataset_id, pool)
+        .await?
+        .into_iter()
+        .next();
+
+    let serialized_message = match existing_chunk {
+        Some(existing_chunk) => serde_json::to_string(&get_restore_update_message(
+            version,
+            &existing_chunk,
+            group_ids,
+            actor,
+        )),
+        None => serde_json::to_string(&get_restore_upload_message(version, group_ids, actor)),
+    }
+    .map_err(|_| ServiceError::BadRequest("Failed to serialize ingestion message".to_string()))?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    redis::cmd("lpush")
+        .arg("ingestion")
+        .arg(&serialized_message)
+        .query_async::<_, ()>(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,207 @@
+use super::auth_handler::AdminOnly;
+use crate::{
+    data::models::{CrawlPage, CrawlPageStatus, CrawlRun, DatasetAndOrgWithSubAndPlan, Pool},
+    errors::ServiceError,
+    operators::crawl_run_operator::{
+        get_crawl_page_states_query, get_crawl_pages_query, get_crawl_run_by_id_query,
+        get_crawl_run_diff, get_crawl_runs_query, get_previous_crawl_run_query, CrawlRunDiff,
+    },
+};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetCrawlRunsQuery {
+    /// Page number to return, 1-indexed. Default is 1.
+    pub page: Option<u64>,
+    /// Number of runs to return per page. Default is 10.
+    pub page_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetCrawlRunsResponse {
+    pub runs: Vec<CrawlRun>,
+    pub total_pages: i64,
+}
+
+/// Get Crawl Runs
+///
+/// Get the runs of the dataset's crawl or sync connector, newest first, with the number of pages which were ingested, unchanged, skipped or failed in each. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/dataset/crawl_runs",
+    context_path = "/api",
+    tag = "Dataset",
+    responses(
+        (status = 200, description = "Runs of the dataset's crawl", body = GetCrawlRunsResponse),
+        (status = 400, description = "Service error relating to getting the crawl runs", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("page" = Option<u64>, Query, description = "Page number to return, 1-indexed. Default is 1."),
+        ("page_size" = Option<u64>, Query, description = "Number of runs to return per page. Default is 10."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_crawl_runs(
+    query: web::Query<GetCrawlRunsQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let page_size = query.page_size.unwrap_or(10).max(1);
+
+    let (runs, total_count) = get_crawl_runs_query(
+        dataset_org_plan_sub.dataset.id,
+        query.page.unwrap_or(1),
+        page_size,
+        pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetCrawlRunsResponse {
+        runs,
+        total_pages: (total_count as f64 / page_size as f64).ceil() as i64,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetCrawlPagesQuery {
+    /// Only return pages with this outcome. If not specified, pages of every outcome are returned.
+    pub status: Option<CrawlPageStatus>,
+    /// Page number to return, 1-indexed. Default is 1.
+    pub page: Option<u64>,
+    /// Number of pages to return per page. Default is 100.
+    pub page_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetCrawlPagesResponse {
+    pub pages: Vec<CrawlPage>,
+    pub total_pages: i64,
+}
+
+/// Get Crawl Run Pages
+///
+/// Get the outcome of every url of a crawl run, ordered by url. Use the status filter to find out which pages were skipped by the include or exclude paths, had no content or failed along with the error. Pages are only kept for the dataset's most recent runs, older runs return no pages. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/dataset/crawl_runs/{run_id}/pages",
+    context_path = "/api",
+    tag = "Dataset",
+    responses(
+        (status = 200, description = "Page results of the crawl run", body = GetCrawlPagesResponse),
+        (status = 400, description = "Service error relating to getting the page results", body = ErrorResponseBody),
+        (status = 404, description = "Crawl run not found", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("run_id" = uuid::Uuid, Path, description = "Id of the crawl run whose pages you want to fetch."),
+        ("status" = Option<CrawlPageStatus>, Query, description = "Only return pages with this outcome."),
+        ("page" = Option<u64>, Query, description = "Page number to return, 1-indexed. Default is 1."),
+        ("page_size" = Option<u64>, Query, description = "Number of pages to return per page. Default is 100."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_crawl_run_pages(
+    run_id: web::Path<uuid::Uuid>,
+    query: web::Query<GetCrawlPagesQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+    let page_size = query.page_size.unwrap_or(100).max(1);
+
+    let run = get_crawl_run_by_id_query(run_id.into_inner(), dataset_id, pool.clone()).await?;
+
+    let (pages, total_count) = get_crawl_pages_query(
+        run.id,
+        dataset_id,
+        query.status,
+        query.page.unwrap_or(1),
+        page_size,
+        pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetCrawlPagesResponse {
+        pages,
+        total_pages: (total_count as f64 / page_size as f64).ceil() as i64,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DiffCrawlRunsQuery {
+    /// Id of the older run to compare against. Defaults to the latest completed run before this one.
+    pub compare_to_run_id: Option<uuid::Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DiffCrawlRunsResponse {
+    pub run: CrawlRun,
+    /// The older run which was compared against. Not present if this is the first run, in which case every page is added.
+    pub previous_run: Option<CrawlRun>,
+    pub diff: CrawlRunDiff,
+}
+
+/// Diff Crawl Runs
+///
+/// Get the urls which were added, removed or changed in a crawl run compared to the run before it. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/dataset/crawl_runs/{run_id}/diff",
+    context_path = "/api",
+    tag = "Dataset",
+    responses(
+        (status = 200, description = "Urls which differ between the two runs", body = DiffCrawlRunsResponse),
+        (status = 400, description = "Service error relating to diffing the crawl runs", body = ErrorResponseBody),
+        (status = 404, description = "One of the crawl runs does not exist for the dataset", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("run_id" = uuid::Uuid, Path, description = "Id of the newer crawl run to compare."),
+        ("compare_to_run_id" = Option<uuid::Uuid>, Query, description = "Id of the older run to compare against. Defaults to the latest completed run before this one."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn diff_crawl_runs(
+    run_id: web::Path<uuid::Uuid>,
+    query: web::Query<DiffCrawlRunsQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+
+    let run = get_crawl_run_by_id_query(run_id.into_inner(), dataset_id, pool.clone()).await?;
+    let previous_run = match query.compare_to_run_id {
+        Some(compare_to_run_id) => {
+            Some(get_crawl_run_by_id_query(compare_to_run_id, dataset_id, pool.clone()).await?)
+        }
+        None => get_previous_crawl_run_query(dataset_id, run.started_at, pool.clone()).await?,
+    };
+
+    let current_pages = get_crawl_page_states_query(run.id, pool.clone()).await?;
+    let previous_pages = match &previous_run {
+        Some(previous_run) => get_crawl_page_states_query(previous_run.id, pool).await?,
+        None => HashMap::new(),
+    };
+
+    Ok(HttpResponse::Ok().json(DiffCrawlRunsResponse {
+        diff: get_crawl_run_diff(&previous_pages, &current_pages),
+        run,
+        previous_run,
+    }))
+}
@@ -1,29 +1,47 @@
-use super::auth_handler::{AdminOnly, LoggedUser, OwnerOnly};
+use super::auth_handler::{ensure_api_key_permitted, AdminOnly, LoggedUser, OwnerOnly};
 use crate::{
     data::models::{
-        CrawlOptions, Dataset, DatasetAndOrgWithSubAndPlan, DatasetConfiguration,
-        DatasetConfigurationDTO, DatasetDTO, OrganizationWithSubAndPlan, Pool, RedisPool,
+        AuditAction, AuditActor, ChunkVersionActor, CrawlOptions, Dataset,
+        DatasetAndOrgWithSubAndPlan, DatasetConfiguration, DatasetConfigurationDTO, DatasetDTO,
+        DatasetExportMessage, DatasetImportMessage, OrganizationWithSubAndPlan, Pool, RedisPool,
         StripePlan, UnifiedId,
     },
     errors::ServiceError,
     middleware::auth_middleware::{verify_admin, verify_owner},
     operators::{
+        audit_operator::{get_audit_diff, get_dataset_audit_state, record_audit_event},
+        chunk_operator::get_row_count_for_organization_id_query,
+        clickhouse_operator::EventQueue,
         crawl_operator::{
             crawl, get_crawl_request_by_dataset_id_query, update_crawl_settings_for_dataset,
             validate_crawl_options,
         },
+        dataset_archive_operator::{
+            get_dataset_export_key, get_dataset_import_key, read_archive_manifest,
+        },
         dataset_operator::{
             clear_dataset_by_dataset_id_query, create_dataset_query, get_dataset_by_id_query,
             get_dataset_usage_query, get_datasets_by_organization_id, get_tags_in_dataset_query,
-            soft_delete_dataset_by_id_query, update_dataset_query,
+            queue_bm25_reindex_query, soft_delete_dataset_by_id_query, update_dataset_query,
         },
         dittofeed_operator::{
             send_ditto_event, DittoDatasetCreated, DittoTrackProperties, DittoTrackRequest,
         },
+        file_operator::get_aws_bucket,
         organization_operator::{get_org_dataset_count, get_org_from_id_query},
+        qdrant_operator::{
+            collection_has_vector_fields_query, get_qdrant_collection_from_dataset_config,
+            get_qdrant_write_collections, MAX_VECTOR_FIELDS,
+        },
     },
 };
 use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
+use base64::{
+    alphabet,
+    engine::{self, general_purpose},
+    Engine as _,
+};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::future::{ready, Ready};
@@ -37,6 +55,10 @@ impl FromRequest for DatasetAndOrgWithSubAndPlan {
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
+        if let Err(err) = ensure_api_key_permitted(req) {
+            return ready(Err(err));
+        }
+
         ready(
             req.extensions()
                 .get::<DatasetAndOrgWithSubAndPlan>()
@@ -56,6 +78,10 @@ impl FromRequest for OrganizationWithSubAndPlan {
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
+        if let Err(err) = ensure_api_key_permitted(req) {
+            return ready(Err(err));
+        }
+
         ready(
             req.extensions()
                 .get::<OrganizationWithSubAndPlan>()
@@ -110,6 +136,67 @@ pub struct CreateDatasetRequest {
     pub crawl_options: Option<CrawlOptions>,
 }
 
+/// Vector fields are mapped to vector slots by their position, so existing fields can never be renamed, removed or reordered.
+fn validate_vector_fields(
+    vector_fields: &[String],
+    curr_vector_fields: &[String],
+) -> Result<(), ServiceError> {
+    if vector_fields.len() > MAX_VECTOR_FIELDS {
+        return Err(ServiceError::BadRequest(format!(
+            "A dataset can have at most {} VECTOR_FIELDS",
+            MAX_VECTOR_FIELDS
+        )));
+    }
+
+    if vector_fields
+        .iter()
+        .any(|field| field.trim().is_empty() || field == "chunk_html")
+    {
+        return Err(ServiceError::BadRequest(
+            "VECTOR_FIELDS must not be empty or named chunk_html".to_string(),
+        ));
+    }
+
+    if vector_fields.iter().unique().count() != vector_fields.len() {
+        return Err(ServiceError::BadRequest(
+            "VECTOR_FIELDS must be unique".to_string(),
+        ));
+    }
+
+    if !vector_fields.starts_with(curr_vector_fields) {
+        return Err(ServiceError::BadRequest(
+            "Existing VECTOR_FIELDS can not be changed, new fields can only be appended"
+                .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Field vectors are written to named vectors which collections created before vector fields existed do not have, upserts to them would fail until the collection has been moved with queue-vector-fields-migration.
+async fn check_collection_has_vector_fields(
+    dataset_config: &DatasetConfiguration,
+) -> Result<(), ServiceError> {
+    if dataset_config.VECTOR_FIELDS.is_empty() {
+        return Ok(());
+    }
+
+    let qdrant_collection =
+        get_qdrant_write_collections(get_qdrant_collection_from_dataset_config(dataset_config))
+            .remove(0);
+
+    if !collection_has_vector_fields_query(qdrant_collection.clone(), dataset_config.EMBEDDING_SIZE)
+        .await?
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "The {} collection of this dataset has no vector field slots yet, VECTOR_FIELDS can be set once it has been migrated with queue-vector-fields-migration",
+            qdrant_collection
+        )));
+    }
+
+    Ok(())
+}
+
 /// Create Dataset
 ///
 /// Auth'ed user must be an owner of the organization to create a dataset.
@@ -136,6 +223,7 @@ pub async fn create_dataset(
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
     org_with_sub_and_plan: OrganizationWithSubAndPlan,
+    actor: ChunkVersionActor,
     user: OwnerOnly,
 ) -> Result<HttpResponse, ServiceError> {
     let org_id = org_with_sub_and_plan.organization.id;
@@ -161,6 +249,22 @@ pub async fn create_dataset(
         validate_crawl_options(&crawl_options)?;
     };
 
+    if let Some(vector_fields) = data
+        .server_configuration
+        .as_ref()
+        .and_then(|config| config.VECTOR_FIELDS.as_ref())
+    {
+        validate_vector_fields(vector_fields, &[])?;
+        check_collection_has_vector_fields(
+            &data
+                .server_configuration
+                .clone()
+                .map(|c| c.into())
+                .unwrap_or_default(),
+        )
+        .await?;
+    }
+
     let dataset = Dataset::from_details(
         data.dataset_name.clone(),
         org_id,
@@ -179,6 +283,7 @@ pub async fn create_dataset(
             pool.clone(),
             redis_pool.clone(),
             dataset.id,
+            actor,
         )
         .await?;
     };
@@ -270,11 +375,13 @@ pub struct UpdateDatasetRequest {
         ("ApiKey" = ["owner"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn update_dataset(
     data: web::Json<UpdateDatasetRequest>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
     user: OwnerOnly,
 ) -> Result<HttpResponse, ServiceError> {
     let curr_dataset = if let Some(dataset_id) = data.dataset_id {
@@ -295,24 +402,64 @@ pub async fn update_dataset(
         return Err(ServiceError::Forbidden);
     }
 
+    let audit_state_before = get_dataset_audit_state(&curr_dataset);
     let curr_dataset_config = DatasetConfiguration::from_json(curr_dataset.server_configuration);
 
+    if let Some(vector_fields) = data
+        .server_configuration
+        .as_ref()
+        .and_then(|config| config.VECTOR_FIELDS.as_ref())
+    {
+        validate_vector_fields(vector_fields, &curr_dataset_config.VECTOR_FIELDS)?;
+    }
+
+    let curr_language = curr_dataset_config.LANGUAGE;
+    let new_dataset_config = data
+        .server_configuration
+        .clone()
+        .map(|c| c.from_curr_dataset(curr_dataset_config.clone()))
+        .unwrap_or(curr_dataset_config);
+
+    if data
+        .server_configuration
+        .as_ref()
+        .is_some_and(|config| config.VECTOR_FIELDS.is_some())
+    {
+        check_collection_has_vector_fields(&new_dataset_config).await?;
+    }
+
     let d = update_dataset_query(
         curr_dataset.id,
         data.dataset_name.clone().unwrap_or(curr_dataset.name),
-        data.server_configuration
-            .clone()
-            .map(|c| c.from_curr_dataset(curr_dataset_config.clone()))
-            .unwrap_or(curr_dataset_config),
+        new_dataset_config.clone(),
         data.new_tracking_id.clone(),
         pool.clone(),
     )
     .await?;
 
+    record_audit_event(
+        d.organization_id,
+        &actor,
+        AuditAction::DatasetUpdated,
+        d.id.to_string(),
+        get_audit_diff(audit_state_before, get_dataset_audit_state(&d)),
+        &event_queue,
+    )
+    .await;
+
+    // BM25 vectors were tokenized in the previous language and have to be recomputed
+    if new_dataset_config.LANGUAGE != curr_language && new_dataset_config.BM25_ENABLED {
+        queue_bm25_reindex_query(curr_dataset.id, redis_pool.clone()).await?;
+    }
+
     if let Some(crawl_options) = data.crawl_options.clone() {
         update_crawl_settings_for_dataset(
             crawl_options.clone(),
             curr_dataset.id,
+            ChunkVersionActor {
+                user_id: actor.user_id,
+                api_key_id: actor.api_key_id,
+            },
             pool.clone(),
             redis_pool.clone(),
         )
@@ -399,11 +546,13 @@ pub async fn get_dataset_crawl_options(
         ("ApiKey" = ["owner"]),
     )
 )]
-#[tracing::instrument(skip(pool, redis_pool))]
+#[tracing::instrument(skip(pool, redis_pool, event_queue))]
 pub async fn delete_dataset(
     data: web::Path<uuid::Uuid>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     user: OwnerOnly,
 ) -> Result<HttpResponse, ServiceError> {
@@ -417,9 +566,21 @@ pub async fn delete_dataset(
         return Err(ServiceError::Forbidden);
     }
 
+    let audit_state_before = get_dataset_audit_state(&dataset_org_plan_sub.dataset);
     let config = DatasetConfiguration::from_json(dataset_org_plan_sub.dataset.server_configuration);
 
     soft_delete_dataset_by_id_query(data.into_inner(), config, pool, redis_pool).await?;
+
+    record_audit_event(
+        dataset_org_plan_sub.organization.organization.id,
+        &actor,
+        AuditAction::DatasetDeleted,
+        dataset_org_plan_sub.dataset.id.to_string(),
+        get_audit_diff(audit_state_before, serde_json::Value::Null),
+        &event_queue,
+    )
+    .await;
+
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -489,11 +650,13 @@ pub async fn clear_dataset(
         ("ApiKey" = ["owner"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn delete_dataset_by_tracking_id(
     tracking_id: web::Path<String>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
     user: OwnerOnly,
 ) -> Result<HttpResponse, ServiceError> {
     let dataset = get_dataset_by_id_query(
@@ -506,10 +669,21 @@ pub async fn delete_dataset_by_tracking_id(
         return Err(ServiceError::Forbidden);
     }
 
+    let audit_state_before = get_dataset_audit_state(&dataset);
     let config = DatasetConfiguration::from_json(dataset.server_configuration);
 
     soft_delete_dataset_by_id_query(dataset.id, config, pool, redis_pool).await?;
 
+    record_audit_event(
+        dataset.organization_id,
+        &actor,
+        AuditAction::DatasetDeleted,
+        dataset.id.to_string(),
+        get_audit_diff(audit_state_before, serde_json::Value::Null),
+        &event_queue,
+    )
+    .await;
+
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -631,6 +805,274 @@ pub async fn get_dataset_by_tracking_id(
     Ok(HttpResponse::Ok().json(dataset))
 }
 
+#[derive(Debug, Deserialize, Serialize, ToSchema, Default)]
+pub struct ExportDatasetQuery {
+    /// Include the dense and sparse vectors of each chunk such that the import can skip re-embedding. Defaults to false.
+    pub include_vectors: Option<bool>,
+    /// Include the contents of uploaded files in the archive. Defaults to false.
+    pub include_files: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ExportDatasetResponse {
+    /// Id of the export. A `dataset_exported` or `dataset_export_failed` event with this id is emitted for the dataset once the export finishes, after which the archive can be downloaded from the get dataset export route.
+    pub export_id: uuid::Uuid,
+}
+
+/// Export Dataset
+///
+/// Queue the creation of a zip archive of the dataset containing its configuration, chunks with their tag sets, groups with their memberships and file records. The archive is built in the background and uploaded to S3, a `dataset_exported` event is emitted once it can be downloaded from the get dataset export route. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    post,
+    path = "/dataset/{dataset_id}/export",
+    context_path = "/api",
+    tag = "Dataset",
+    responses(
+        (status = 200, description = "Dataset export queued", body = ExportDatasetResponse),
+        (status = 400, description = "Service error relating to exporting the dataset", body = ErrorResponseBody),
+        (status = 404, description = "Dataset not found", body = ErrorResponseBody)
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("dataset_id" = uuid, Path, description = "The id of the dataset you want to export."),
+        ("include_vectors" = Option<bool>, Query, description = "Include the vectors of each chunk in the archive. Defaults to false."),
+        ("include_files" = Option<bool>, Query, description = "Include the contents of uploaded files in the archive. Defaults to false."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn export_dataset(
+    dataset_id: web::Path<uuid::Uuid>,
+    query: web::Query<ExportDatasetQuery>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let dataset =
+        get_dataset_by_id_query(UnifiedId::TrieveUuid(dataset_id.into_inner()), pool).await?;
+
+    if !verify_admin(&user, &dataset.organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let export_id = uuid::Uuid::new_v4();
+    let message = DatasetExportMessage {
+        export_id,
+        dataset_id: dataset.id,
+        include_vectors: query.include_vectors.unwrap_or(false),
+        include_files: query.include_files.unwrap_or(false),
+    };
+
+    let serialized_message = serde_json::to_string(&message).map_err(|e| {
+        log::error!("Could not serialize message: {:?}", e);
+        ServiceError::BadRequest("Could not serialize message".to_string())
+    })?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    redis::cmd("lpush")
+        .arg("dataset_export")
+        .arg(&serialized_message)
+        .query_async::<_, ()>(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ExportDatasetResponse { export_id }))
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct GetDatasetExportResponse {
+    /// Presigned S3 url of the export archive. The url is valid for 5 minutes.
+    pub download_url: String,
+}
+
+/// Get Dataset Export
+///
+/// Get a download link for the archive of a finished dataset export. Returns a 404 while the export is still being built. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/dataset/{dataset_id}/export/{export_id}",
+    context_path = "/api",
+    tag = "Dataset",
+    responses(
+        (status = 200, description = "Download link for the export archive", body = GetDatasetExportResponse),
+        (status = 400, description = "Service error relating to getting the export", body = ErrorResponseBody),
+        (status = 404, description = "Dataset not found or export not finished", body = ErrorResponseBody)
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("dataset_id" = uuid, Path, description = "The id of the exported dataset."),
+        ("export_id" = uuid, Path, description = "The id of the export returned by the export dataset route."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_dataset_export(
+    path: web::Path<(uuid::Uuid, uuid::Uuid)>,
+    pool: web::Data<Pool>,
+    user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let (dataset_id, export_id) = path.into_inner();
+    let dataset = get_dataset_by_id_query(UnifiedId::TrieveUuid(dataset_id), pool).await?;
+
+    if !verify_admin(&user, &dataset.organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let bucket = get_aws_bucket()?;
+    let export_key = get_dataset_export_key(dataset.id, export_id);
+
+    match bucket.head_object(&export_key).await {
+        Ok((_, 200)) => {}
+        _ => {
+            return Err(ServiceError::NotFound(
+                "Export not found or not finished yet".to_string(),
+            ))
+        }
+    };
+
+    let download_url = bucket
+        .presign_get(export_key, 300, None)
+        .await
+        .map_err(|e| {
+            log::error!("Error getting signed url: {}", e);
+            ServiceError::BadRequest(format!("Error getting signed url: {}", e))
+        })?;
+
+    Ok(HttpResponse::Ok().json(GetDatasetExportResponse { download_url }))
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema, Clone)]
+#[schema(example = json!({
+    "base64_archive": "base64_encoded_zip",
+    "dataset_name": "My Imported Dataset",
+}))]
+pub struct ImportDatasetReqPayload {
+    /// Base64 encoded zip archive created by the export dataset route. This is the standard base64url encoding.
+    pub base64_archive: String,
+    /// Name of the new dataset. Defaults to the name of the exported dataset.
+    pub dataset_name: Option<String>,
+    /// Tracking ID of the new dataset. The tracking ID of the exported dataset is not reused as it is likely taken within the organization.
+    pub tracking_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ImportDatasetResponse {
+    /// The dataset the archive is being imported into.
+    pub dataset: Dataset,
+    /// Id of the import. A `dataset_imported` or `dataset_import_failed` event with this id is emitted for the dataset once the import finishes.
+    pub import_id: uuid::Uuid,
+}
+
+/// Import Dataset
+///
+/// Create a new dataset from an archive created by the export dataset route. Chunks whose archived vectors match the new dataset's embedding size are inserted directly, all other chunks are re-embedded. The import runs in the background, a `dataset_imported` event is emitted once it completes. Auth'ed user must be an owner of the organization to import a dataset.
+#[utoipa::path(
+    post,
+    path = "/dataset/import",
+    context_path = "/api",
+    tag = "Dataset",
+    request_body(content = ImportDatasetReqPayload, description = "JSON request payload to import a dataset archive", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Dataset created and archive queued for import", body = ImportDatasetResponse),
+        (status = 400, description = "Service error relating to importing the dataset", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool, redis_pool, data))]
+pub async fn import_dataset(
+    data: web::Json<ImportDatasetReqPayload>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    org_with_sub_and_plan: OrganizationWithSubAndPlan,
+    _user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let org_id = org_with_sub_and_plan.organization.id;
+    let plan = org_with_sub_and_plan.plan.unwrap_or_default();
+
+    let mut cleaned_base64 = data.base64_archive.replace('+', "-").replace('/', "_");
+    while cleaned_base64.ends_with('=') {
+        cleaned_base64.pop();
+    }
+    let base64_engine = engine::GeneralPurpose::new(&alphabet::URL_SAFE, general_purpose::NO_PAD);
+    let archive = base64_engine
+        .decode(cleaned_base64)
+        .map_err(|_e| ServiceError::BadRequest("Could not decode base64 archive".to_string()))?;
+
+    let manifest = read_archive_manifest(&archive)?;
+
+    let unlimited = std::env::var("UNLIMITED").unwrap_or("false".to_string());
+    if unlimited == "false" {
+        let dataset_count = get_org_dataset_count(org_id, pool.clone()).await?;
+        if dataset_count >= plan.dataset_count {
+            return Ok(HttpResponse::UpgradeRequired().json(
+                json!({"message": "Your plan must be upgraded to create additional datasets"}),
+            ));
+        }
+
+        let chunk_count = get_row_count_for_organization_id_query(org_id, pool.clone()).await?;
+        if chunk_count + manifest.chunk_count > plan.chunk_count as usize {
+            return Ok(HttpResponse::UpgradeRequired()
+                .json(json!({"message": "Must upgrade your plan to add more chunks"})));
+        }
+    }
+
+    let dataset = Dataset::from_details(
+        data.dataset_name
+            .clone()
+            .unwrap_or(manifest.dataset_name.clone()),
+        org_id,
+        data.tracking_id.clone(),
+        DatasetConfiguration::from_json(manifest.server_configuration.clone()),
+    );
+    let dataset = create_dataset_query(dataset, pool.clone()).await?;
+
+    let import_id = uuid::Uuid::new_v4();
+    get_aws_bucket()?
+        .put_object(get_dataset_import_key(import_id), archive.as_slice())
+        .await
+        .map_err(|e| {
+            log::error!("Could not upload dataset archive to S3 {:?}", e);
+            ServiceError::BadRequest("Could not upload dataset archive to S3".to_string())
+        })?;
+
+    let message = DatasetImportMessage {
+        import_id,
+        dataset_id: dataset.id,
+    };
+
+    let serialized_message = serde_json::to_string(&message).map_err(|e| {
+        log::error!("Could not serialize message: {:?}", e);
+        ServiceError::BadRequest("Could not serialize message".to_string())
+    })?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    redis::cmd("lpush")
+        .arg("dataset_import")
+        .arg(&serialized_message)
+        .query_async::<_, ()>(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ImportDatasetResponse { dataset, import_id }))
+}
+
 #[derive(Debug, Deserialize, Serialize, ToSchema, Default)]
 pub struct GetDatasetsPagination {
     pub limit: Option<i64>,
@@ -1,15 +1,16 @@
 use super::{
-    auth_handler::{AdminOnly, LoggedUser},
+    auth_handler::{AdminOnly, ChunkWrite, LoggedUser, Permitted},
     group_handler::DeleteGroupData,
 };
 use crate::{
     data::models::{
-        DatasetAndOrgWithSubAndPlan, DatasetConfiguration, File, FileAndGroupId, FileWorkerMessage,
-        Pool, RedisPool,
+        ChunkVersionActor, DatasetAndOrgWithSubAndPlan, DatasetConfiguration, File, FileAndGroupId,
+        FileWorkerMessage, Pool, RedisPool,
     },
     errors::ServiceError,
     middleware::auth_middleware::verify_member,
     operators::{
+        chunking_operator::{DEFAULT_MAX_TOKENS_PER_CHUNK, DEFAULT_TOKEN_OVERLAP},
         file_operator::{
             delete_file_query, get_aws_bucket, get_dataset_file_query, get_file_query,
         },
@@ -40,6 +41,23 @@ pub fn validate_file_name(s: String) -> Result<String, actix_web::Error> {
     Err(ServiceError::BadRequest("Invalid file name".to_string()).into())
 }
 
+/// Strategy used to split a file into chunks. Every strategy records the heading breadcrumb (`heading_path`) and character offsets (`char_start`, `char_end`) of each chunk in its metadata such that results can deep-link into the source file.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkingStrategy {
+    /// Splits each parsed section on `split_delimiters` and groups `target_splits_per_chunk` splits into a chunk.
+    #[default]
+    Delimiter,
+    /// One chunk per heading section. HTML files are split the same way crawled pages are, other formats use the headings found while parsing.
+    HeadingHierarchy,
+    /// Windows of at most `max_tokens_per_chunk` tokens which start and end between words, where each window overlaps the previous one by up to `token_overlap` tokens.
+    TokenWindow,
+    /// One chunk per ATX heading section of a Markdown file. Other formats fall back to `heading_hierarchy`.
+    MarkdownSections,
+    /// One chunk per row of a CSV or TSV file with each value prefixed by its column header. Other formats fall back to `delimiter`.
+    TableRows,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 #[schema(example = json!({
     "file_name": "example.pdf",
@@ -56,12 +74,15 @@ pub fn validate_file_name(s: String) -> Result<String, actix_web::Error> {
     "create_chunks": true,
     "split_delimiters": [",",".","\n"],
     "target_splits_per_chunk": 20,
+    "chunking_strategy": "delimiter",
 }))]
 pub struct UploadFileReqPayload {
     /// Base64 encoded file. This is the standard base64url encoding.
     pub base64_file: String,
     /// Name of the file being uploaded, including the extension.
     pub file_name: String,
+    /// MIME type of the file being uploaded. Used to pick the parser for the file. If not specified or `application/octet-stream`, the parser is picked based on the extension of the file name. Markdown, plain text, HTML, CSV, TSV, JSON, JSONL, and DOCX files are parsed natively; other types are sent to Apache Tika if `TIKA_URL` is configured.
+    pub file_mime_type: Option<String>,
     /// Tag set is a comma separated list of tags which will be passed down to the chunks made from the file. Tags are used to filter chunks when searching. HNSW indices are created for each tag such that there is no performance loss when filtering on them.
     pub tag_set: Option<Vec<String>>,
     /// Description is an optional convience field so you do not have to remember what the file contains or is about. It will be included on the group resulting from the file which will hold its chunk.
@@ -80,6 +101,12 @@ pub struct UploadFileReqPayload {
     pub split_delimiters: Option<Vec<String>>,
     /// Target splits per chunk. This is an optional field which allows you to specify the number of splits you want per chunk. If not specified, the default 20 is used. However, you may want to use a different number.
     pub target_splits_per_chunk: Option<usize>,
+    /// Chunking strategy is an optional field which allows you to pick how the file is split into chunks. If not specified, the default `delimiter` strategy is used which splits on `split_delimiters` and groups `target_splits_per_chunk` splits into a chunk.
+    pub chunking_strategy: Option<ChunkingStrategy>,
+    /// Max tokens per chunk is only used by the `token_window` strategy. Tokens are counted with cl100k_base, the tokenizer of OpenAI's embedding models, and words longer than the limit are split. Set it below the context length of the dataset's embedding model. If not specified, the default 300 is used.
+    pub max_tokens_per_chunk: Option<usize>,
+    /// Token overlap is only used by the `token_window` strategy and sets how many tokens each window shares with the previous one. If not specified, the default 50 is used. Must be less than `max_tokens_per_chunk`.
+    pub token_overlap: Option<usize>,
     /// Group tracking id is an optional field which allows you to specify the tracking id of the group that is created from the file. Chunks created will be created with the tracking id of `group_tracking_id|<index of chunk>`
     pub group_tracking_id: Option<String>,
 }
@@ -91,7 +118,7 @@ pub struct UploadFileResult {
 
 /// Upload File
 ///
-/// Upload a file to S3 attached to the server. The file will be converted to HTML with tika and chunked algorithmically, images will be OCR'ed with tesseract. The resulting chunks will be indexed and searchable. Optionally, you can only upload the file and manually create chunks associated to the file after. See docs.trieve.ai and/or contact us for more details and tips. Auth'ed user must be an admin or owner of the dataset's organization to upload a file.
+/// Upload a file to S3 attached to the server. The file will be parsed into sections based on its type and chunked algorithmically. Types without a native parser are converted to HTML with tika, images will be OCR'ed with tesseract. Each chunk carries the page number and heading path of the section it came from in its metadata. The resulting chunks will be indexed and searchable. Optionally, you can only upload the file and manually create chunks associated to the file after. See docs.trieve.ai and/or contact us for more details and tips. Auth'ed user must be an admin or owner of the dataset's organization to upload a file.
 #[utoipa::path(
     post,
     path = "/file",
@@ -111,6 +138,7 @@ pub struct UploadFileResult {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn upload_file_handler(
+    _permission: Permitted<ChunkWrite>,
     data: web::Json<UploadFileReqPayload>,
     pool: web::Data<Pool>,
     user: AdminOnly,
@@ -150,6 +178,27 @@ pub async fn upload_file_handler(
 
     let upload_file_data = data.into_inner();
 
+    if upload_file_data.max_tokens_per_chunk == Some(0) {
+        return Err(ServiceError::BadRequest(
+            "max_tokens_per_chunk must be greater than 0".to_string(),
+        )
+        .into());
+    }
+
+    if upload_file_data
+        .token_overlap
+        .unwrap_or(DEFAULT_TOKEN_OVERLAP)
+        >= upload_file_data
+            .max_tokens_per_chunk
+            .unwrap_or(DEFAULT_MAX_TOKENS_PER_CHUNK)
+        && upload_file_data.chunking_strategy == Some(ChunkingStrategy::TokenWindow)
+    {
+        return Err(ServiceError::BadRequest(
+            "token_overlap must be less than max_tokens_per_chunk".to_string(),
+        )
+        .into());
+    }
+
     let base64_decode_span = transaction.start_child("base64_decode", "base64_decode");
     let mut cleaned_base64 = upload_file_data
         .base64_file
@@ -351,6 +400,7 @@ pub async fn delete_file_handler(
     query: web::Query<DeleteGroupData>,
     pool: web::Data<Pool>,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
 ) -> Result<HttpResponse, actix_web::Error> {
     let dataset_config =
@@ -362,6 +412,7 @@ pub async fn delete_file_handler(
         dataset_org_plan_sub.dataset,
         pool,
         dataset_config,
+        actor,
     )
     .await?;
 
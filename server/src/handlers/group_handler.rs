@@ -1,5 +1,5 @@
 use super::{
-    auth_handler::{AdminOnly, LoggedUser},
+    auth_handler::{AdminOnly, ChunkRead, GroupWrite, LoggedUser, Permitted},
     chunk_handler::{
         parse_query, ChunkFilter, ParsedQuery, ParsedQueryTypes, SearchChunksReqPayload,
     },
@@ -7,8 +7,9 @@ use super::{
 use crate::{
     data::models::{
         escape_quotes, ChunkGroup, ChunkGroupAndFileId, ChunkGroupBookmark, ChunkMetadata,
-        ChunkMetadataStringTagSet, DatasetAndOrgWithSubAndPlan, DatasetConfiguration,
-        HighlightOptions, Pool, QueryTypes, RecommendType, RecommendationEventClickhouse,
+        ChunkMetadataStringTagSet, ChunkVersionActor, DatasetAndOrgWithSubAndPlan,
+        DatasetConfiguration, FacetOptions, HighlightOptions, HybridSearchOptions, Pool,
+        QueryTypes, RecencyBias, RecommendType, RecommendationEventClickhouse,
         RecommendationStrategy, RedisPool, ScoreChunk, ScoreChunkDTO, SearchMethod,
         SearchQueryEventClickhouse, SortOptions, TypoOptions, UnifiedId,
     },
@@ -17,6 +18,7 @@ use crate::{
     operators::{
         chunk_operator::get_metadata_from_tracking_id_query,
         clickhouse_operator::{get_latency_from_header, ClickHouseEvent, EventQueue},
+        facet_operator::get_facets_query,
         group_operator::*,
         qdrant_operator::{
             add_bookmark_to_qdrant_query, recommend_qdrant_groups_query,
@@ -24,8 +26,9 @@ use crate::{
         },
         search_operator::{
             full_text_search_over_groups, get_metadata_from_groups, hybrid_search_over_groups,
-            search_groups_query, search_hybrid_groups, semantic_search_over_groups,
-            GroupScoreChunk, SearchOverGroupsQueryResult, SearchOverGroupsResults,
+            parse_vector_query, rerank_groups_by_recency, search_groups_query,
+            search_hybrid_groups, semantic_search_over_groups, GroupScoreChunk,
+            SearchOverGroupsQueryResult, SearchOverGroupsResults,
         },
     },
 };
@@ -180,6 +183,7 @@ impl From<Vec<ChunkGroup>> for CreateChunkGroupResponseEnum {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn create_chunk_group(
+    _permission: Permitted<GroupWrite>,
     create_group_data: web::Json<CreateChunkGroupReqPayloadEnum>,
     _user: AdminOnly,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -321,6 +325,7 @@ pub struct DatasetGroupQuery {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_groups_for_dataset(
+    _permission: Permitted<ChunkRead>,
     dataset_and_page: web::Path<DatasetGroupQuery>,
     _dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     pool: web::Data<Pool>,
@@ -366,6 +371,7 @@ pub struct GetGroupByTrackingIDData {
 /// get_group_by_tracking_id
 #[tracing::instrument(skip(pool))]
 pub async fn get_group_by_tracking_id(
+    _permission: Permitted<ChunkRead>,
     data: web::Path<GetGroupByTrackingIDData>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     _user: LoggedUser,
@@ -410,6 +416,7 @@ pub struct GetGroupData {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunk_group(
+    _permission: Permitted<ChunkRead>,
     group_id: web::Path<uuid::Uuid>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     _user: LoggedUser,
@@ -463,6 +470,7 @@ pub struct UpdateGroupByTrackingIDReqPayload {
 #[deprecated]
 #[tracing::instrument(skip(pool))]
 pub async fn update_group_by_tracking_id(
+    _permission: Permitted<GroupWrite>,
     data: web::Json<UpdateGroupByTrackingIDReqPayload>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     _user: AdminOnly,
@@ -524,11 +532,13 @@ pub struct DeleteGroupByTrackingIDData {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn delete_group_by_tracking_id(
+    _permission: Permitted<GroupWrite>,
     tracking_id: web::Path<String>,
     data: web::Query<DeleteGroupByTrackingIDData>,
     pool: web::Data<Pool>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
 ) -> Result<HttpResponse, actix_web::Error> {
     let delete_group_pool = pool.clone();
     let dataset_config =
@@ -551,6 +561,7 @@ pub async fn delete_group_by_tracking_id(
         data.delete_chunks,
         delete_group_pool,
         dataset_config,
+        Some(actor),
     )
     .await?;
 
@@ -585,11 +596,13 @@ pub struct DeleteGroupData {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn delete_chunk_group(
+    _permission: Permitted<GroupWrite>,
     group_id: web::Path<uuid::Uuid>,
     data: web::Query<DeleteGroupData>,
     pool: web::Data<Pool>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     _user: AdminOnly,
+    actor: ChunkVersionActor,
 ) -> Result<HttpResponse, actix_web::Error> {
     let delete_group_pool = pool.clone();
     let dataset_config =
@@ -611,6 +624,7 @@ pub async fn delete_chunk_group(
         data.delete_chunks,
         delete_group_pool,
         dataset_config,
+        Some(actor),
     )
     .await?;
 
@@ -658,6 +672,7 @@ pub struct UpdateChunkGroupReqPayload {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn update_chunk_group(
+    _permission: Permitted<GroupWrite>,
     data: web::Json<UpdateChunkGroupReqPayload>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
@@ -750,6 +765,7 @@ pub struct AddChunkToGroupReqPayload {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn add_chunk_to_group(
+    _permission: Permitted<GroupWrite>,
     body: web::Json<AddChunkToGroupReqPayload>,
     group_id: web::Path<uuid::Uuid>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -805,6 +821,7 @@ pub async fn add_chunk_to_group(
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn add_chunk_to_group_by_tracking_id(
+    _permission: Permitted<GroupWrite>,
     data: web::Json<AddChunkToGroupReqPayload>,
     tracking_id: web::Path<String>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -906,6 +923,7 @@ pub enum GetChunksInGroupResponse {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunks_in_group(
+    _permission: Permitted<ChunkRead>,
     group_data: web::Path<GetChunksInGroupPathParams>,
     pool: web::Data<Pool>,
     _user: LoggedUser,
@@ -964,6 +982,7 @@ pub struct GetChunksInGroupByTrackingIdReqPayload {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_chunks_in_group_by_tracking_id(
+    _permission: Permitted<ChunkRead>,
     path_data: web::Path<GetChunksInGroupByTrackingIdReqPayload>,
     pool: web::Data<Pool>,
     _user: LoggedUser,
@@ -1020,6 +1039,7 @@ pub struct GetGroupsForChunksReqPayload {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_groups_for_chunks(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<GetGroupsForChunksReqPayload>,
     pool: web::Data<Pool>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -1064,6 +1084,7 @@ pub struct RemoveChunkFromGroupReqPayload {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn remove_chunk_from_group(
+    _permission: Permitted<GroupWrite>,
     group_id: web::Path<uuid::Uuid>,
     body: Option<web::Json<RemoveChunkFromGroupReqPayload>>,
     query: Option<web::Query<RemoveChunkFromGroupReqPayload>>,
@@ -1119,6 +1140,8 @@ pub struct RecommendGroupsReqPayload {
     pub slim_chunks: Option<bool>,
     /// The user_id is the id of the user who is making the request. This is used to track user interactions with the rrecommendation results.
     pub user_id: Option<String>,
+    /// Recency bias lets you rank the chunks within each recommended group, and the groups by their top chunk, by how close the time_stamp of the chunks is to an origin. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
@@ -1160,6 +1183,7 @@ pub enum RecommendGroupsResponse {
 )]
 #[tracing::instrument(skip(pool, event_queue))]
 pub async fn get_recommended_groups(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<RecommendGroupsReqPayload>,
     pool: web::Data<Pool>,
     event_queue: web::Data<EventQueue>,
@@ -1316,6 +1340,11 @@ pub async fn get_recommended_groups(
         })
         .collect::<Vec<GroupScoreChunk>>();
 
+    let recommended_chunk_metadatas = match &data.recency_bias {
+        Some(recency_bias) => rerank_groups_by_recency(recommended_chunk_metadatas, recency_bias),
+        None => recommended_chunk_metadatas,
+    };
+
     timer.add("fetched metadata from ids");
 
     let recommendation_id = uuid::Uuid::new_v4();
@@ -1410,6 +1439,10 @@ pub struct SearchWithinGroupReqPayload {
     /// The user_id is the id of the user who is making the request. This is used to track user interactions with the search results.
     pub user_id: Option<String>,
     pub typo_options: Option<TypoOptions>,
+    /// Hybrid options lets you specify how the semantic and fulltext legs of a hybrid search are retrieved and fused. Only used when search_type is "hybrid".
+    pub hybrid_options: Option<HybridSearchOptions>,
+    /// Recency bias lets you rank your results by how close their time_stamp is to an origin, which defaults to now. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
 }
 
 impl From<SearchWithinGroupReqPayload> for SearchChunksReqPayload {
@@ -1431,6 +1464,10 @@ impl From<SearchWithinGroupReqPayload> for SearchChunksReqPayload {
             remove_stop_words: search_within_group_data.remove_stop_words,
             user_id: search_within_group_data.user_id,
             typo_options: search_within_group_data.typo_options,
+            hybrid_options: search_within_group_data.hybrid_options,
+            facets: None,
+            vector_fields: None,
+            recency_bias: search_within_group_data.recency_bias,
         }
     }
 }
@@ -1442,6 +1479,9 @@ pub struct SearchWithinGroupResults {
     pub group: ChunkGroupAndFileId,
     pub corrected_query: Option<String>,
     pub total_pages: i64,
+    /// Ids of the merchandising rules which were applied to the results, including rules which hid chunks. Only present if a rule was applied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -1451,6 +1491,9 @@ pub struct SearchWithinGroupResponseBody {
     pub chunks: Vec<ScoreChunk>,
     pub corrected_query: Option<String>,
     pub total_pages: i64,
+    /// Ids of the merchandising rules which were applied to the results, including rules which hid chunks. Only present if a rule was applied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -1473,6 +1516,7 @@ impl SearchWithinGroupResults {
                 .collect(),
             corrected_query: self.corrected_query,
             total_pages: self.total_pages,
+            merchandising_rule_ids: self.merchandising_rule_ids,
         }
     }
 }
@@ -1499,7 +1543,9 @@ impl SearchWithinGroupResults {
     )
 )]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_within_group(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<SearchWithinGroupReqPayload>,
     pool: web::Data<Pool>,
     event_queue: web::Data<EventQueue>,
@@ -1511,7 +1557,7 @@ pub async fn search_within_group(
     let dataset_config =
         DatasetConfiguration::from_json(dataset_org_plan_sub.dataset.server_configuration.clone());
 
-    let data = data.into_inner();
+    let mut data = data.into_inner();
 
     //search over the links as well
     let group_id = data.group_id;
@@ -1537,6 +1583,7 @@ pub async fn search_within_group(
             query.clone(),
             data.use_quote_negated_terms,
             data.remove_stop_words,
+            dataset_config.LANGUAGE,
         )),
         QueryTypes::Multi(query) => ParsedQueryTypes::Multi(
             query
@@ -1546,11 +1593,25 @@ pub async fn search_within_group(
                         multi_query.query.clone(),
                         data.use_quote_negated_terms,
                         data.remove_stop_words,
+                        dataset_config.LANGUAGE,
                     );
                     (parsed_query, multi_query.weight)
                 })
                 .collect::<Vec<(ParsedQuery, f32)>>(),
         ),
+        vector_query => {
+            // Vector and chunk queries have no text for typo correction to work on
+            data.typo_options = None;
+            ParsedQueryTypes::Single(
+                parse_vector_query(
+                    vector_query,
+                    dataset_org_plan_sub.dataset.id,
+                    &dataset_config,
+                    search_pool.clone(),
+                )
+                .await?,
+            )
+        }
     };
 
     let result_chunks = match data.search_type {
@@ -1585,10 +1646,7 @@ pub async fn search_within_group(
 
     let search_id = uuid::Uuid::new_v4();
 
-    let query = match &data.query {
-        QueryTypes::Single(query) => query.clone(),
-        QueryTypes::Multi(query) => serde_json::to_string(&query).unwrap_or_default(),
-    };
+    let query = data.query.to_query_string();
 
     let clickhouse_event = SearchQueryEventClickhouse {
         id: search_id,
@@ -1615,6 +1673,13 @@ pub async fn search_within_group(
         created_at: time::OffsetDateTime::now_utc(),
         query_rating: String::from(""),
         user_id: data.user_id.clone().unwrap_or_default(),
+        merchandising_rule_ids: result_chunks
+            .merchandising_rule_ids
+            .clone()
+            .unwrap_or_default()
+            .iter()
+            .map(|rule_id| rule_id.to_string())
+            .collect(),
     };
 
     event_queue
@@ -1660,6 +1725,12 @@ pub struct SearchOverGroupsReqPayload {
     /// The user_id is the id of the user who is making the request. This is used to track user interactions with the search results.
     pub user_id: Option<String>,
     pub typo_options: Option<TypoOptions>,
+    /// Hybrid options lets you specify how the semantic and fulltext legs of a hybrid search are retrieved and fused. Only used when search_type is "hybrid". Fusion is applied on group rank and the score of each group's top chunk.
+    pub hybrid_options: Option<HybridSearchOptions>,
+    /// Facets lets you request term counts, num_value range buckets and time_stamp date histograms computed over every chunk matching the filters of the search. If not specified, no facets are computed.
+    pub facets: Option<FacetOptions>,
+    /// Recency bias lets you rank the chunks within each group, and the groups by their top chunk, by how close the time_stamp of the chunks is to an origin. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
 }
 
 /// Search Over Groups
@@ -1684,7 +1755,9 @@ pub struct SearchOverGroupsReqPayload {
     )
 )]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_over_groups(
+    _permission: Permitted<ChunkRead>,
     data: web::Json<SearchOverGroupsReqPayload>,
     pool: web::Data<Pool>,
     event_queue: web::Data<EventQueue>,
@@ -1696,11 +1769,14 @@ pub async fn search_over_groups(
     let dataset_config =
         DatasetConfiguration::from_json(dataset_org_plan_sub.dataset.server_configuration.clone());
 
+    let mut data = data.into_inner();
+
     let parsed_query = match data.query.clone() {
         QueryTypes::Single(query) => ParsedQueryTypes::Single(parse_query(
             query.clone(),
             data.use_quote_negated_terms,
             data.remove_stop_words,
+            dataset_config.LANGUAGE,
         )),
         QueryTypes::Multi(query) => ParsedQueryTypes::Multi(
             query
@@ -1710,16 +1786,38 @@ pub async fn search_over_groups(
                         multi_query.query.clone(),
                         data.use_quote_negated_terms,
                         data.remove_stop_words,
+                        dataset_config.LANGUAGE,
                     );
                     (parsed_query, multi_query.weight)
                 })
                 .collect::<Vec<(ParsedQuery, f32)>>(),
         ),
+        vector_query => {
+            // Vector and chunk queries have no text for typo correction to work on
+            data.typo_options = None;
+            ParsedQueryTypes::Single(
+                parse_vector_query(
+                    vector_query,
+                    dataset_org_plan_sub.dataset.id,
+                    &dataset_config,
+                    pool.clone(),
+                )
+                .await?,
+            )
+        }
+    };
+
+    let (quote_words, negated_words) = match &parsed_query {
+        ParsedQueryTypes::Single(parsed_query) => (
+            parsed_query.quote_words.clone(),
+            parsed_query.negated_words.clone(),
+        ),
+        ParsedQueryTypes::Multi(_) => (None, None),
     };
 
     let mut timer = Timer::new();
 
-    let result_chunks = match data.search_type {
+    let mut result_chunks = match data.search_type {
         SearchMethod::FullText => {
             if !dataset_config.FULLTEXT_ENABLED {
                 return Err(ServiceError::BadRequest(
@@ -1731,7 +1829,7 @@ pub async fn search_over_groups(
             full_text_search_over_groups(
                 data.clone(),
                 parsed_query,
-                pool,
+                pool.clone(),
                 redis_pool,
                 dataset_org_plan_sub.dataset.clone(),
                 &dataset_config,
@@ -1743,7 +1841,7 @@ pub async fn search_over_groups(
             hybrid_search_over_groups(
                 data.clone(),
                 parsed_query.to_parsed_query()?,
-                pool,
+                pool.clone(),
                 redis_pool,
                 dataset_org_plan_sub.dataset.clone(),
                 &dataset_config,
@@ -1761,7 +1859,7 @@ pub async fn search_over_groups(
             semantic_search_over_groups(
                 data.clone(),
                 parsed_query,
-                pool,
+                pool.clone(),
                 redis_pool,
                 dataset_org_plan_sub.dataset.clone(),
                 &dataset_config,
@@ -1772,12 +1870,25 @@ pub async fn search_over_groups(
     };
     timer.add("search_chunks");
 
+    if let Some(facet_options) = data.facets.clone() {
+        result_chunks.facets = Some(
+            get_facets_query(
+                facet_options,
+                data.filters.clone(),
+                quote_words,
+                negated_words,
+                dataset_org_plan_sub.dataset.id,
+                &dataset_config,
+                pool,
+            )
+            .await?,
+        );
+        timer.add("computed facets");
+    }
+
     let search_id = uuid::Uuid::new_v4();
 
-    let query = match &data.query {
-        QueryTypes::Single(query) => query.clone(),
-        QueryTypes::Multi(query) => serde_json::to_string(&query).unwrap_or_default(),
-    };
+    let query = data.query.to_query_string();
 
     let clickhouse_event = SearchQueryEventClickhouse {
         id: search_id,
@@ -1804,6 +1915,13 @@ pub async fn search_over_groups(
         created_at: time::OffsetDateTime::now_utc(),
         query_rating: String::from(""),
         user_id: data.user_id.clone().unwrap_or_default(),
+        merchandising_rule_ids: result_chunks
+            .merchandising_rule_ids
+            .clone()
+            .unwrap_or_default()
+            .iter()
+            .map(|rule_id| rule_id.to_string())
+            .collect(),
     };
 
     event_queue
@@ -0,0 +1,303 @@
+use super::auth_handler::AdminOnly;
+use crate::{
+    data::models::{
+        DatasetAndOrgWithSubAndPlan, MerchandisingAction, MerchandisingQueryMatch,
+        MerchandisingRule, Pool,
+    },
+    errors::ServiceError,
+    operators::merchandising_operator::{
+        create_merchandising_rule_query, delete_merchandising_rule_query,
+        get_merchandising_rules_query, update_merchandising_rule_query,
+    },
+};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "name": "Pin the new TV for tv queries",
+    "query_match": "contains",
+    "query_pattern": "tv",
+    "action": {"type": "pin", "tracking_id": "SKU-1234", "position": 1},
+    "starts_at": "2024-11-01T00:00:00",
+    "ends_at": "2024-12-01T00:00:00",
+}))]
+pub struct MerchandisingRuleReqPayload {
+    /// Name of the rule, shown to merchandisers.
+    pub name: String,
+    /// How `query_pattern` is matched against the search query.
+    pub query_match: MerchandisingQueryMatch,
+    /// Pattern to match the search query against. Required unless `query_match` is `any`.
+    pub query_pattern: Option<String>,
+    /// What the rule does to the search results when it matches.
+    pub action: MerchandisingAction,
+    /// The rule is only applied after this time. If not set, the rule applies immediately.
+    pub starts_at: Option<chrono::NaiveDateTime>,
+    /// The rule is only applied before this time. If not set, the rule never expires.
+    pub ends_at: Option<chrono::NaiveDateTime>,
+    /// Disabled rules are never applied. Default is true.
+    pub enabled: Option<bool>,
+}
+
+fn validate_merchandising_rule(
+    payload: MerchandisingRuleReqPayload,
+    rule_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+) -> Result<MerchandisingRule, ServiceError> {
+    if payload.name.trim().is_empty() {
+        return Err(ServiceError::BadRequest(
+            "name must not be empty".to_string(),
+        ));
+    }
+
+    let query_pattern = payload
+        .query_pattern
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty());
+
+    match (payload.query_match, &query_pattern) {
+        (MerchandisingQueryMatch::Any, _) => {}
+        (_, None) => {
+            return Err(ServiceError::BadRequest(
+                "query_pattern is required unless query_match is any".to_string(),
+            ))
+        }
+        (MerchandisingQueryMatch::Regex, Some(pattern)) => {
+            regex::Regex::new(pattern).map_err(|err| {
+                ServiceError::BadRequest(format!("query_pattern is not a valid regex: {}", err))
+            })?;
+        }
+        _ => {}
+    }
+
+    match &payload.action {
+        MerchandisingAction::Pin {
+            tracking_id,
+            position,
+        } => {
+            if tracking_id.trim().is_empty() {
+                return Err(ServiceError::BadRequest(
+                    "tracking_id of a pin must not be empty".to_string(),
+                ));
+            }
+            if *position < 1 {
+                return Err(ServiceError::BadRequest(
+                    "position of a pin is 1-indexed and must be at least 1".to_string(),
+                ));
+            }
+        }
+        MerchandisingAction::Bury { tracking_ids } | MerchandisingAction::Hide { tracking_ids } => {
+            if tracking_ids.is_empty() {
+                return Err(ServiceError::BadRequest(
+                    "tracking_ids must not be empty".to_string(),
+                ));
+            }
+        }
+        MerchandisingAction::Boost { tag, factor } => {
+            if tag.trim().is_empty() {
+                return Err(ServiceError::BadRequest(
+                    "tag of a boost must not be empty".to_string(),
+                ));
+            }
+            if !factor.is_finite() || *factor <= 0.0 {
+                return Err(ServiceError::BadRequest(
+                    "factor of a boost must be greater than 0".to_string(),
+                ));
+            }
+        }
+    }
+
+    if let (Some(starts_at), Some(ends_at)) = (payload.starts_at, payload.ends_at) {
+        if ends_at <= starts_at {
+            return Err(ServiceError::BadRequest(
+                "ends_at must be after starts_at".to_string(),
+            ));
+        }
+    }
+
+    Ok(MerchandisingRule {
+        id: rule_id,
+        dataset_id,
+        name: payload.name.trim().to_string(),
+        query_match: payload.query_match,
+        query_pattern,
+        action: payload.action,
+        starts_at: payload.starts_at,
+        ends_at: payload.ends_at,
+        enabled: payload.enabled.unwrap_or(true),
+        created_at: chrono::Utc::now().naive_local(),
+        updated_at: chrono::Utc::now().naive_local(),
+    })
+}
+
+/// Create Merchandising Rule
+///
+/// Create a rule which pins, buries, hides or boosts chunks in the results of chunk searches whose query matches the rule. Results affected by a rule are flagged with the id of the rule in the search response and in the search analytics. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    post,
+    path = "/merchandising_rule",
+    context_path = "/api",
+    tag = "Merchandising",
+    request_body(content = MerchandisingRuleReqPayload, description = "JSON request payload to create a merchandising rule", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The created merchandising rule", body = MerchandisingRule),
+        (status = 400, description = "Service error relating to creating the merchandising rule", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn create_merchandising_rule(
+    data: web::Json<MerchandisingRuleReqPayload>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let rule = validate_merchandising_rule(
+        data.into_inner(),
+        uuid::Uuid::new_v4(),
+        dataset_org_plan_sub.dataset.id,
+    )?;
+
+    let rule = create_merchandising_rule_query(rule, pool).await?;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetMerchandisingRulesQuery {
+    /// Page number to return, 1-indexed. Default is 1.
+    pub page: Option<u64>,
+    /// Number of rules to return per page. Default is 100.
+    pub page_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetMerchandisingRulesResponse {
+    pub rules: Vec<MerchandisingRule>,
+    pub total_pages: i64,
+}
+
+/// Get Merchandising Rules
+///
+/// Get the merchandising rules of the dataset, newest first. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/merchandising_rule",
+    context_path = "/api",
+    tag = "Merchandising",
+    responses(
+        (status = 200, description = "Merchandising rules of the dataset", body = GetMerchandisingRulesResponse),
+        (status = 400, description = "Service error relating to getting the merchandising rules", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("page" = Option<u64>, Query, description = "Page number to return, 1-indexed. Default is 1."),
+        ("page_size" = Option<u64>, Query, description = "Number of rules to return per page. Default is 100."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_merchandising_rules(
+    query: web::Query<GetMerchandisingRulesQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let page_size = query.page_size.unwrap_or(100).max(1);
+
+    let (rules, total_count) = get_merchandising_rules_query(
+        dataset_org_plan_sub.dataset.id,
+        query.page.unwrap_or(1),
+        page_size,
+        pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetMerchandisingRulesResponse {
+        rules,
+        total_pages: (total_count as f64 / page_size as f64).ceil() as i64,
+    }))
+}
+
+/// Update Merchandising Rule
+///
+/// Replace a merchandising rule. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    put,
+    path = "/merchandising_rule/{rule_id}",
+    context_path = "/api",
+    tag = "Merchandising",
+    request_body(content = MerchandisingRuleReqPayload, description = "JSON request payload to update a merchandising rule", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The updated merchandising rule", body = MerchandisingRule),
+        (status = 400, description = "Service error relating to updating the merchandising rule", body = ErrorResponseBody),
+        (status = 404, description = "Merchandising rule not found", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("rule_id" = uuid::Uuid, Path, description = "Id of the merchandising rule to update."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn update_merchandising_rule(
+    rule_id: web::Path<uuid::Uuid>,
+    data: web::Json<MerchandisingRuleReqPayload>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let rule = validate_merchandising_rule(
+        data.into_inner(),
+        rule_id.into_inner(),
+        dataset_org_plan_sub.dataset.id,
+    )?;
+
+    let rule = update_merchandising_rule_query(rule, pool).await?;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+/// Delete Merchandising Rule
+///
+/// Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    delete,
+    path = "/merchandising_rule/{rule_id}",
+    context_path = "/api",
+    tag = "Merchandising",
+    responses(
+        (status = 204, description = "Confirmation that the merchandising rule was deleted"),
+        (status = 400, description = "Service error relating to deleting the merchandising rule", body = ErrorResponseBody),
+        (status = 404, description = "Merchandising rule not found", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("rule_id" = uuid::Uuid, Path, description = "Id of the merchandising rule to delete."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn delete_merchandising_rule(
+    rule_id: web::Path<uuid::Uuid>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    delete_merchandising_rule_query(rule_id.into_inner(), dataset_org_plan_sub.dataset.id, pool)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
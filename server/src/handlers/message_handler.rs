@@ -1,20 +1,23 @@
 use super::{
-    auth_handler::{AdminOnly, LoggedUser},
+    auth_handler::{AdminOnly, LoggedUser, Permitted, RagCreate},
     chunk_handler::{ChunkFilter, ParsedQuery, ParsedQueryTypes, SearchChunksReqPayload},
 };
 use crate::{
     data::models::{
-        self, ChunkMetadata, ContextOptions, DatasetAndOrgWithSubAndPlan, DatasetConfiguration,
-        HighlightOptions, LLMOptions, Pool, RedisPool, SearchMethod, SuggestType,
+        self, AgentOptions, ChunkMetadata, ContextOptions, DatasetAndOrgWithSubAndPlan,
+        DatasetConfiguration, HighlightOptions, LLMOptions, Pool, RecencyBias, RedisPool,
+        SearchMethod, SuggestType,
     },
     errors::ServiceError,
     get_env,
     operators::{
+        agent_operator::is_agent_tool_message,
         chunk_operator::{get_chunk_metadatas_from_point_ids, get_random_chunk_metadatas_query},
         clickhouse_operator::EventQueue,
         message_operator::{
-            create_topic_message_query, delete_message_query, get_message_by_sort_for_topic_query,
-            get_messages_for_topic_query, get_topic_messages, stream_response,
+            count_tokens, create_topic_message_query, delete_message_query,
+            get_message_by_sort_for_topic_query, get_messages_for_topic_query, get_topic_messages,
+            stream_response,
         },
         organization_operator::get_message_org_count,
         parse_operator::convert_html_to_text,
@@ -104,6 +107,10 @@ pub struct CreateMessageReqPayload {
     pub llm_options: Option<LLMOptions>,
     /// Context options to use for the completion. If not specified, all options will default to false.
     pub context_options: Option<ContextOptions>,
+    /// Recency bias lets you rank the chunks retrieved for RAG by how close their time_stamp is to an origin. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
+    /// Agent options turn on agent mode, where instead of a single search the LLM calls Trieve's search, group search, get chunk by tracking id and count endpoints as tools until it is ready to answer. If not specified, agent mode is off.
+    pub agent_options: Option<AgentOptions>,
 }
 
 /// Create message
@@ -116,7 +123,7 @@ pub struct CreateMessageReqPayload {
     tag = "Message",
     request_body(content = CreateMessageReqPayload, description = "JSON request payload to create a message completion", content_type = "application/json"),
     responses(
-        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming.",
+        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming. With the sse stream_format this is a text/event-stream of RagStreamEvent.",
             headers(
                 ("TR-QueryID" = uuid::Uuid, description = "Query ID that is used for tracking analytics")
             )
@@ -137,6 +144,7 @@ pub struct CreateMessageReqPayload {
 )]
 #[tracing::instrument(skip(pool, event_queue))]
 pub async fn create_message(
+    _permission: Permitted<RagCreate>,
     data: web::Json<CreateMessageReqPayload>,
     user: AdminOnly,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -180,7 +188,7 @@ pub async fn create_message(
         topic_id,
         0,
         "user".to_string(),
-        None,
+        Some(count_tokens(&create_message_data.new_message_content) as i32),
         None,
         dataset_org_plan_sub.dataset.id,
         uuid::Uuid::new_v4(),
@@ -321,6 +329,10 @@ pub struct RegenerateMessageReqPayload {
     pub user_id: Option<String>,
     /// Context options to use for the completion. If not specified, all options will default to false.
     pub context_options: Option<ContextOptions>,
+    /// Recency bias lets you rank the chunks retrieved for RAG by how close their time_stamp is to an origin. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
+    /// Agent options turn on agent mode, where instead of a single search the LLM calls Trieve's search, group search, get chunk by tracking id and count endpoints as tools until it is ready to answer. If not specified, agent mode is off.
+    pub agent_options: Option<AgentOptions>,
 }
 
 #[derive(Serialize, Debug, ToSchema)]
@@ -353,6 +365,10 @@ pub struct EditMessageReqPayload {
     pub user_id: Option<String>,
     /// Context options to use for the completion. If not specified, all options will default to false.
     pub context_options: Option<ContextOptions>,
+    /// Recency bias lets you rank the chunks retrieved for RAG by how close their time_stamp is to an origin. If not specified, this has no effect.
+    pub recency_bias: Option<RecencyBias>,
+    /// Agent options turn on agent mode, where instead of a single search the LLM calls Trieve's search, group search, get chunk by tracking id and count endpoints as tools until it is ready to answer. If not specified, agent mode is off.
+    pub agent_options: Option<AgentOptions>,
 }
 
 impl From<EditMessageReqPayload> for CreateMessageReqPayload {
@@ -371,6 +387,8 @@ impl From<EditMessageReqPayload> for CreateMessageReqPayload {
             llm_options: data.llm_options,
             user_id: data.user_id,
             context_options: data.context_options,
+            recency_bias: data.recency_bias,
+            agent_options: data.agent_options,
         }
     }
 }
@@ -391,13 +409,15 @@ impl From<RegenerateMessageReqPayload> for CreateMessageReqPayload {
             llm_options: data.llm_options,
             user_id: data.user_id,
             context_options: data.context_options,
+            recency_bias: data.recency_bias,
+            agent_options: data.agent_options,
         }
     }
 }
 
 /// Edit message
 ///
-/// Edit message which exists within the topic's chat history. This will delete the message and replace it with a new message. The new message will be generated by the AI based on the new content provided in the request body. The response will include Chunks first on the stream if the topic is using RAG. The structure will look like `[chunks]||mesage`. See docs.trieve.ai for more information. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+/// Edit message which exists within the topic's chat history. This will delete the message and replace it with a new message. The new message will be generated by the AI based on the new content provided in the request body. The response will include Chunks first on the stream if the topic is using RAG. The structure will look like `[chunks]||mesage`. Summaries of the chat history which cover the edited message are deleted along with it and regenerated as needed. See docs.trieve.ai for more information. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
 #[utoipa::path(
     put,
     path = "/message",
@@ -421,6 +441,7 @@ impl From<RegenerateMessageReqPayload> for CreateMessageReqPayload {
 )]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
 pub async fn edit_message(
+    _permission: Permitted<RagCreate>,
     data: web::Json<EditMessageReqPayload>,
     user: AdminOnly,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -474,7 +495,7 @@ pub async fn edit_message(
     tag = "Message",
     request_body(content = RegenerateMessageReqPayload, description = "JSON request payload to delete an agent message then regenerate it in a strem", content_type = "application/json"),
     responses(
-        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming.",
+        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming. With the sse stream_format this is a text/event-stream of RagStreamEvent.",
             headers(
                 ("TR-QueryID" = uuid::Uuid, description = "Query ID that is used for tracking analytics")
             )
@@ -495,6 +516,7 @@ pub async fn edit_message(
 )]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
 pub async fn regenerate_message_patch(
+    _permission: Permitted<RagCreate>,
     data: web::Json<RegenerateMessageReqPayload>,
     user: AdminOnly,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -570,13 +592,16 @@ pub async fn regenerate_message_patch(
         })
         .collect::<Vec<models::Message>>();
 
-    let mut message_to_regenerate = None;
-    for message in previous_messages.iter().rev() {
-        if message.role == "assistant" {
-            message_to_regenerate = Some(message.clone());
-            break;
-        }
-    }
+    // Agent mode stores its tool calls right before the assistant message, those are regenerated as well
+    let message_to_regenerate = previous_messages
+        .iter()
+        .rposition(|message| message.role == "assistant")
+        .map(|mut idx| {
+            while idx > 0 && is_agent_tool_message(&previous_messages[idx - 1]) {
+                idx -= 1;
+            }
+            previous_messages[idx].clone()
+        });
 
     let message_id = match message_to_regenerate {
         Some(message) => message.id,
@@ -618,7 +643,7 @@ pub async fn regenerate_message_patch(
     tag = "Message",
     request_body(content = RegenerateMessageReqPayload, description = "JSON request payload to delete an agent message then regenerate it in a strem", content_type = "application/json"),
     responses(
-        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming.",
+        (status = 200, description = "This will be a HTTP stream of a string, check the chat or search UI for an example how to process this. Response if streaming. With the sse stream_format this is a text/event-stream of RagStreamEvent.",
             headers(
                 ("TR-QueryID" = uuid::Uuid, description = "Query ID that is used for tracking analytics")
             )
@@ -640,6 +665,7 @@ pub async fn regenerate_message_patch(
 #[deprecated]
 #[tracing::instrument(skip(pool, event_queue, redis_pool))]
 pub async fn regenerate_message(
+    _permission: Permitted<RagCreate>,
     data: web::Json<RegenerateMessageReqPayload>,
     user: AdminOnly,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
@@ -700,6 +726,7 @@ pub struct SuggestedQueriesResponse {
 )]
 #[tracing::instrument(skip(pool))]
 pub async fn get_suggested_queries(
+    _permission: Permitted<RagCreate>,
     data: web::Json<SuggestedQueriesReqPayload>,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     pool: web::Data<Pool>,
@@ -746,6 +773,7 @@ pub async fn get_suggested_queries(
                 query,
                 quote_words: None,
                 negated_words: None,
+                vectors: None,
             };
             match search_type {
                 SearchMethod::Hybrid => search_hybrid_chunks(
@@ -1,6 +1,6 @@
 use crate::{data::models::RedisPool, errors::ServiceError};
 use actix_web::{web, HttpResponse};
-use prometheus::{Encoder, Error, Gauge, Registry};
+use prometheus::{Encoder, Error, Gauge, IntCounterVec, Opts, Registry};
 
 #[derive(Clone, Debug)]
 pub struct Metrics {
@@ -15,6 +15,7 @@ pub struct Metrics {
     pub group_update_processing_gauge: Gauge,
     pub pgbulk_queue_gauge: Gauge,
     pub pgbulk_processing_gauge: Gauge,
+    pub rate_limited_counter: IntCounterVec,
 }
 
 impl Metrics {
@@ -67,6 +68,15 @@ impl Metrics {
         )?;
         registry.register(Box::new(group_update_processing_gauge.clone()))?;
 
+        let rate_limited_counter = IntCounterVec::new(
+            Opts::new(
+                "tr_rate_limited_requests",
+                "number of requests rejected by the rate limit",
+            ),
+            &["route_class"],
+        )?;
+        registry.register(Box::new(rate_limited_counter.clone()))?;
+
         Ok(Metrics {
             registry,
             ingest_queue_gauge,
@@ -79,6 +89,7 @@ impl Metrics {
             delete_processing_gauge,
             ingest_processing_gauge,
             group_update_processing_gauge,
+            rate_limited_counter,
         })
     }
 
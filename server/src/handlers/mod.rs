@@ -1,16 +1,22 @@
 pub mod analytics_handler;
 pub mod auth_handler;
 pub mod chunk_handler;
+pub mod chunk_version_handler;
+pub mod crawl_run_handler;
 pub mod dataset_handler;
 pub mod event_handler;
 pub mod file_handler;
 pub mod group_handler;
 pub mod invitation_handler;
+pub mod merchandising_handler;
 pub mod message_handler;
 pub mod metrics_handler;
 pub mod organization_handler;
 pub mod page_handler;
+pub mod scim_handler;
+pub mod sso_handler;
 pub mod stripe_handler;
+pub mod synonym_handler;
 pub mod topic_handler;
 pub mod user_handler;
 pub mod webhook_handler;
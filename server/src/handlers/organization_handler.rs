@@ -1,15 +1,24 @@
 use super::auth_handler::{AdminOnly, LoggedUser, OwnerOnly};
 use crate::{
-    data::models::{OrganizationWithSubAndPlan, Pool, RedisPool, UserOrganization, UserRole},
+    data::models::{
+        AuditAction, AuditActor, AuditEvent, AuditLogFilter, OrganizationWithSubAndPlan, Pool,
+        RedisPool, UserOrganization, UserRole,
+    },
     errors::ServiceError,
     middleware::auth_middleware::{get_role_for_org, verify_admin, verify_owner},
     operators::{
+        audit_operator::{
+            export_audit_events_query, get_audit_diff, get_audit_events_query, record_audit_event,
+        },
+        clickhouse_operator::EventQueue,
         organization_operator::{
             create_organization_query, delete_organization_query, get_org_from_id_query,
             get_org_usage_by_id_query, get_org_users_by_id_query,
             update_all_org_dataset_configs_query, update_organization_query,
         },
-        user_operator::{add_user_to_organization, remove_user_from_org_query},
+        user_operator::{
+            add_user_to_organization, get_user_by_id_query, remove_user_from_org_query,
+        },
     },
 };
 use actix_web::{web, HttpRequest, HttpResponse};
@@ -315,6 +324,8 @@ pub async fn remove_user_from_org(
     data: web::Path<RemoveUserFromOrgPathParams>,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
     org_with_plan_and_sub: OrganizationWithSubAndPlan,
     user: AdminOnly,
 ) -> Result<HttpResponse, actix_web::Error> {
@@ -328,8 +339,28 @@ pub async fn remove_user_from_org(
         None => return Err(ServiceError::Forbidden.into()),
     };
 
+    let removed_user_role = get_user_by_id_query(&data.user_id, pool.clone())
+        .await?
+        .1
+        .into_iter()
+        .find(|user_org| user_org.organization_id == org_id)
+        .map(|user_org| user_org.role);
+
     remove_user_from_org_query(data.user_id, user_role, org_id, pool, redis_pool).await?;
 
+    record_audit_event(
+        org_id,
+        &actor,
+        AuditAction::UserRemovedFromOrganization,
+        data.user_id.to_string(),
+        get_audit_diff(
+            serde_json::json!({ "role": removed_user_role }),
+            serde_json::Value::Null,
+        ),
+        &event_queue,
+    )
+    .await;
+
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -406,3 +437,121 @@ pub async fn update_all_org_dataset_configs(
 
     Ok(HttpResponse::NoContent().finish())
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetAuditLogReqPayload {
+    pub filter: Option<AuditLogFilter>,
+    /// Page number to return, 1-indexed. Default is 1.
+    pub page: Option<u64>,
+    /// Number of events to return per page. Default is 50.
+    pub page_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetAuditLogResponse {
+    pub events: Vec<AuditEvent>,
+    pub total_pages: i64,
+}
+
+/// Get Audit Log
+///
+/// Get the administrative and data-changing actions taken in the organization, newest first. Each event records who made the change, whether they used a session or an api key, their IP, the action, its target and the fields which changed. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    post,
+    path = "/organization/audit_log",
+    context_path = "/api",
+    tag = "Organization",
+    request_body(content = GetAuditLogReqPayload, description = "JSON request payload to filter the audit log", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Events of the organization's audit log", body = GetAuditLogResponse),
+        (status = 400, description = "Service error relating to getting the audit log", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(clickhouse_client))]
+pub async fn get_audit_log(
+    data: web::Json<GetAuditLogReqPayload>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    clickhouse_client: web::Data<clickhouse::Client>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let data = data.into_inner();
+    let page_size = data.page_size.unwrap_or(50).clamp(1, 1000);
+
+    let (events, total_count) = get_audit_events_query(
+        organization_id,
+        data.filter,
+        data.page.unwrap_or(1),
+        page_size,
+        clickhouse_client,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetAuditLogResponse {
+        events,
+        total_pages: (total_count as f64 / page_size as f64).ceil() as i64,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ExportAuditLogReqPayload {
+    pub filter: Option<AuditLogFilter>,
+}
+
+/// Export Audit Log
+///
+/// Download the events of the organization's audit log matching the filter as JSON Lines, one event per line and oldest first. At most 100000 events are exported, narrow the date range to export more. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    post,
+    path = "/organization/audit_log/export",
+    context_path = "/api",
+    tag = "Organization",
+    request_body(content = ExportAuditLogReqPayload, description = "JSON request payload to filter the exported events", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Events of the organization's audit log as JSON Lines", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "Service error relating to exporting the audit log", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(clickhouse_client))]
+pub async fn export_audit_log(
+    data: web::Json<ExportAuditLogReqPayload>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    clickhouse_client: web::Data<clickhouse::Client>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let events =
+        export_audit_events_query(organization_id, data.into_inner().filter, clickhouse_client)
+            .await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header((
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"audit-log-{}.jsonl\"",
+                organization_id
+            ),
+        ))
+        .body(events))
+}
@@ -0,0 +1,1670 @@
+use super::auth_handler::OwnerOnly;
+use crate::{
+    data::models::{
+        ApiKeyRespBody, AuditAction, AuditActor, OrganizationScimTokenDTO,
+        OrganizationWithSubAndPlan, Pool, RedisPool, User, UserRole,
+    },
+    errors::ServiceError,
+    get_env,
+    middleware::auth_middleware::{get_api_key_from_headers, get_client_ip, verify_owner},
+    operators::{
+        audit_operator::{get_audit_diff, record_audit_event},
+        clickhouse_operator::EventQueue,
+        scim_operator::{
+            apply_scim_user_changes_query, create_scim_token_query, delete_scim_token_query,
+            get_scim_former_member_query, get_scim_org_member_query, get_scim_org_members_query,
+            get_scim_org_members_with_role_query, get_scim_token_by_value_query,
+            get_scim_token_query, update_scim_token_last_used_query, ScimUserChanges,
+        },
+        sso_operator::{ensure_org_has_user_capacity, get_verified_domain_for_email_query},
+        user_operator::get_user_by_email_query,
+    },
+};
+use actix_web::{
+    dev::Payload, http::StatusCode, web, FromRequest, HttpRequest, HttpResponse,
+    HttpResponseBuilder, ResponseError,
+};
+use futures_util::future::LocalBoxFuture;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+const SCIM_USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+const SCIM_GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const SCIM_LIST_RESPONSE_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const SCIM_ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+const SCIM_SERVICE_PROVIDER_CONFIG_SCHEMA: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+
+/// Most resources returned by one page of a SCIM list request
+const SCIM_MAX_RESULTS: i64 = 1000;
+const SCIM_DEFAULT_RESULTS: i64 = 100;
+
+/// Groups are fixed, there is one for each role a member of an organization can have
+const SCIM_GROUP_ROLES: [UserRole; 3] = [UserRole::Owner, UserRole::Admin, UserRole::User];
+
+/// Error returned by the SCIM routes in the format identity providers expect
+#[derive(Debug)]
+pub struct ScimError {
+    status: StatusCode,
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+impl ScimError {
+    fn bad_request(scim_type: &'static str, detail: impl Into<String>) -> Self {
+        ScimError {
+            status: StatusCode::BAD_REQUEST,
+            scim_type: Some(scim_type),
+            detail: detail.into(),
+        }
+    }
+
+    fn not_found(resource_type: &str) -> Self {
+        ScimError {
+            status: StatusCode::NOT_FOUND,
+            scim_type: None,
+            detail: format!("{} not found", resource_type),
+        }
+    }
+
+    fn unauthorized() -> Self {
+        ScimError {
+            status: StatusCode::UNAUTHORIZED,
+            scim_type: None,
+            detail: "A valid SCIM token is required".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ScimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.detail)
+    }
+}
+
+impl ResponseError for ScimError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = json!({
+            "schemas": [SCIM_ERROR_SCHEMA],
+            "status": self.status.as_u16().to_string(),
+            "detail": self.detail,
+        });
+        if let Some(scim_type) = self.scim_type {
+            body["scimType"] = json!(scim_type);
+        }
+
+        HttpResponse::build(self.status)
+            .content_type(SCIM_CONTENT_TYPE)
+            .json(body)
+    }
+}
+
+impl From<ServiceError> for ScimError {
+    fn from(err: ServiceError) -> Self {
+        let (status, detail) = match err {
+            ServiceError::BadRequest(message)
+            | ServiceError::DuplicateTrackingId(message)
+            | ServiceError::JsonDeserializeError(message) => (StatusCode::BAD_REQUEST, message),
+            ServiceError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            ServiceError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden".to_string()),
+            ServiceError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ServiceError::PayloadTooLarge(message) => (StatusCode::PAYLOAD_TOO_LARGE, message),
+            ServiceError::TooManyRequests(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too Many Requests".to_string(),
+            ),
+            ServiceError::InternalServerError(message) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, message)
+            }
+        };
+
+        ScimError {
+            status,
+            scim_type: None,
+            detail,
+        }
+    }
+}
+
+/// Organization whose SCIM token the request was made with
+#[derive(Debug, Clone)]
+pub struct ScimOrganization {
+    pub organization_id: uuid::Uuid,
+    pub actor: AuditActor,
+    /// Whether the token can make members owners, see OrganizationScimToken
+    pub can_grant_owner: bool,
+}
+
+impl FromRequest for ScimOrganization {
+    type Error = ScimError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = get_api_key_from_headers(req.headers());
+        let pool = req.app_data::<web::Data<Pool>>().cloned();
+        let ip_address = get_client_ip(req).map(|ip| ip.to_string());
+
+        Box::pin(async move {
+            let (Some(token), Some(pool)) = (token, pool) else {
+                return Err(ScimError::unauthorized());
+            };
+
+            let scim_token = get_scim_token_by_value_query(&token, pool.clone())
+                .await?
+                .ok_or_else(ScimError::unauthorized)?;
+
+            let last_used_stale = scim_token.last_used_at.map_or(true, |last_used_at| {
+                last_used_at < chrono::Utc::now().naive_local() - chrono::Duration::minutes(1)
+            });
+            if last_used_stale {
+                let scim_token_id = scim_token.id;
+                actix_web::rt::spawn(async move {
+                    if let Err(err) = update_scim_token_last_used_query(scim_token_id, pool).await {
+                        log::error!("Failed to update SCIM token last used {:?}", err);
+                    }
+                });
+            }
+
+            Ok(ScimOrganization {
+                organization_id: scim_token.organization_id,
+                actor: AuditActor::scim_token(ip_address),
+                can_grant_owner: scim_token.can_grant_owner,
+            })
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+}
+
+impl ScimName {
+    fn full_name(&self) -> Option<String> {
+        self.formatted.clone().or_else(|| {
+            let full_name = [self.given_name.as_deref(), self.family_name.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<&str>>()
+                .join(" ");
+
+            (!full_name.is_empty()).then_some(full_name)
+        })
+    }
+}
+
+/// Entry of a multi-valued SCIM attribute such as emails, roles or group members
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ScimMultiValue {
+    #[serde(default)]
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    pub location: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<ScimName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub emails: Vec<ScimMultiValue>,
+    #[serde(default = "default_scim_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub roles: Vec<ScimMultiValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<ScimMultiValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+fn default_scim_active() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub display_name: String,
+    #[serde(default)]
+    pub members: Vec<ScimMultiValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse<T> {
+    pub schemas: Vec<String>,
+    pub total_results: i64,
+    pub start_index: i64,
+    pub items_per_page: i64,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<T>,
+}
+
+impl<T> ScimListResponse<T> {
+    fn new(resources: Vec<T>, total_results: i64, start_index: i64) -> Self {
+        ScimListResponse {
+            schemas: vec![SCIM_LIST_RESPONSE_SCHEMA.to_string()],
+            total_results,
+            start_index,
+            items_per_page: resources.len() as i64,
+            resources,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScimPatchRequest {
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScimPatchOperation {
+    /// add, remove or replace. Some identity providers capitalize it.
+    pub op: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub value: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScimListQuery {
+    pub filter: Option<String>,
+    #[serde(rename = "startIndex")]
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+}
+
+impl ScimListQuery {
+    /// One based index of the first resource and the number of resources to return
+    fn get_page(&self) -> (i64, i64) {
+        (
+            self.start_index.unwrap_or(1).max(1),
+            self.count
+                .unwrap_or(SCIM_DEFAULT_RESULTS)
+                .clamp(0, SCIM_MAX_RESULTS),
+        )
+    }
+}
+
+fn scim_response<T: Serialize>(mut response: HttpResponseBuilder, body: &T) -> HttpResponse {
+    response.content_type(SCIM_CONTENT_TYPE).json(body)
+}
+
+/// Identity providers send `application/scim+json` bodies which the JSON extractor rejects, so bodies are parsed from the raw bytes
+fn parse_scim_body<T: DeserializeOwned>(body: &web::Bytes) -> Result<T, ScimError> {
+    serde_json::from_slice(body)
+        .map_err(|err| ScimError::bad_request("invalidSyntax", format!("Invalid body: {}", err)))
+}
+
+/// Value compared by a filter of the form `<attribute> eq "<value>"`, the only kind of filter identity providers send to look up resources
+fn parse_scim_eq_filter(filter: &str, attribute: &str) -> Result<String, ScimError> {
+    let invalid_filter = || {
+        ScimError::bad_request(
+            "invalidFilter",
+            format!(
+                "Only filters of the form {} eq \"value\" are supported",
+                attribute
+            ),
+        )
+    };
+
+    let mut parts = filter.trim().splitn(3, ' ');
+    let (Some(filter_attribute), Some(operator), Some(value)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid_filter());
+    };
+
+    if !filter_attribute.eq_ignore_ascii_case(attribute) || !operator.eq_ignore_ascii_case("eq") {
+        return Err(invalid_filter());
+    }
+
+    value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .map(|value| value.replace("\\\"", "\""))
+        .ok_or_else(invalid_filter)
+}
+
+/// Azure AD sends booleans in patch operations as the strings "True" and "False"
+fn parse_scim_bool(value: &Value) -> Result<bool, ScimError> {
+    match value {
+        Value::Bool(value) => Ok(*value),
+        Value::String(value) if value.eq_ignore_ascii_case("true") => Ok(true),
+        Value::String(value) if value.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(ScimError::bad_request(
+            "invalidValue",
+            "active must be a boolean",
+        )),
+    }
+}
+
+fn get_scim_role_name(role: &UserRole) -> &'static str {
+    match role {
+        UserRole::Owner => "owner",
+        UserRole::Admin => "admin",
+        UserRole::User => "user",
+    }
+}
+
+fn get_scim_group_display_name(role: &UserRole) -> &'static str {
+    match role {
+        UserRole::Owner => "Owners",
+        UserRole::Admin => "Admins",
+        UserRole::User => "Users",
+    }
+}
+
+fn parse_scim_role(role: &str) -> Option<UserRole> {
+    SCIM_GROUP_ROLES.into_iter().find(|group_role| {
+        role.eq_ignore_ascii_case(get_scim_role_name(group_role))
+            || role.eq_ignore_ascii_case(get_scim_group_display_name(group_role))
+    })
+}
+
+/// Role of the primary entry of the roles attribute, or of the first entry when none is primary
+fn get_scim_role(roles: &[ScimMultiValue]) -> Result<Option<UserRole>, ScimError> {
+    let Some(role) = roles
+        .iter()
+        .find(|role| role.primary == Some(true))
+        .or(roles.first())
+    else {
+        return Ok(None);
+    };
+
+    parse_scim_role(&role.value).map(Some).ok_or_else(|| {
+        ScimError::bad_request(
+            "invalidValue",
+            format!(
+                "Unknown role {}, roles must be owner, admin or user",
+                role.value
+            ),
+        )
+    })
+}
+
+/// Role in the value of a patch operation on roles, which can be a role, a role object or a list of them
+fn parse_scim_role_value(value: &Value) -> Result<Option<UserRole>, ScimError> {
+    let roles = match value {
+        Value::String(role) => vec![ScimMultiValue {
+            value: role.clone(),
+            ..Default::default()
+        }],
+        Value::Array(_) => serde_json::from_value(value.clone())
+            .map_err(|_| ScimError::bad_request("invalidValue", "Invalid roles"))?,
+        _ => vec![serde_json::from_value(value.clone())
+            .map_err(|_| ScimError::bad_request("invalidValue", "Invalid roles"))?],
+    };
+
+    get_scim_role(&roles)
+}
+
+/// Ids in the value of a patch operation on group members
+fn parse_scim_member_ids(value: Option<&Value>) -> Result<Vec<String>, ScimError> {
+    let members: Vec<ScimMultiValue> = match value {
+        None | Some(Value::Null) => vec![],
+        Some(members @ Value::Array(_)) => serde_json::from_value(members.clone())
+            .map_err(|_| ScimError::bad_request("invalidValue", "Invalid members"))?,
+        Some(Value::Object(object)) if object.contains_key("members") => {
+            return parse_scim_member_ids(object.get("members"));
+        }
+        Some(value) => vec![serde_json::from_value(value.clone())
+            .map_err(|_| ScimError::bad_request("invalidValue", "Invalid members"))?],
+    };
+
+    Ok(members.into_iter().map(|member| member.value).collect())
+}
+
+fn format_scim_timestamp(timestamp: chrono::NaiveDateTime) -> String {
+    timestamp
+        .and_utc()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn get_scim_location(resource_path: &str) -> String {
+    let base_server_url = get_env!(
+        "BASE_SERVER_URL",
+        "Server hostname for OpenID provider must be set"
+    );
+
+    format!("{}/scim/v2/{}", base_server_url, resource_path)
+}
+
+/// The user as a SCIM resource. Users without a role are not members of the organization and shown as inactive.
+fn get_scim_user_resource(user: &User, role: Option<&UserRole>) -> ScimUser {
+    ScimUser {
+        schemas: vec![SCIM_USER_SCHEMA.to_string()],
+        id: Some(user.id.to_string()),
+        user_name: user.email.clone(),
+        name: user.name.clone().map(|name| ScimName {
+            formatted: Some(name),
+            ..Default::default()
+        }),
+        display_name: user.name.clone(),
+        emails: vec![ScimMultiValue {
+            value: user.email.clone(),
+            display: None,
+            primary: Some(true),
+        }],
+        active: role.is_some(),
+        roles: role
+            .map(|role| ScimMultiValue {
+                value: get_scim_role_name(role).to_string(),
+                display: None,
+                primary: Some(true),
+            })
+            .into_iter()
+            .collect(),
+        groups: role
+            .map(|role| ScimMultiValue {
+                value: get_scim_role_name(role).to_string(),
+                display: Some(get_scim_group_display_name(role).to_string()),
+                primary: None,
+            })
+            .into_iter()
+            .collect(),
+        meta: Some(ScimMeta {
+            resource_type: "User".to_string(),
+            created: Some(format_scim_timestamp(user.created_at)),
+            last_modified: Some(format_scim_timestamp(user.updated_at)),
+            location: get_scim_location(&format!("Users/{}", user.id)),
+        }),
+    }
+}
+
+async fn get_scim_group_resource(
+    organization_id: uuid::Uuid,
+    role: &UserRole,
+    pool: web::Data<Pool>,
+) -> Result<ScimGroup, ScimError> {
+    let members = get_scim_org_members_with_role_query(organization_id, role.clone(), pool)
+        .await?
+        .into_iter()
+        .map(|user| ScimMultiValue {
+            value: user.id.to_string(),
+            display: Some(user.email),
+            primary: None,
+        })
+        .collect();
+
+    Ok(ScimGroup {
+        schemas: vec![SCIM_GROUP_SCHEMA.to_string()],
+        id: Some(get_scim_role_name(role).to_string()),
+        display_name: get_scim_group_display_name(role).to_string(),
+        members,
+        meta: Some(ScimMeta {
+            resource_type: "Group".to_string(),
+            created: None,
+            last_modified: None,
+            location: get_scim_location(&format!("Groups/{}", get_scim_role_name(role))),
+        }),
+    })
+}
+
+/// The user with their role in the organization. Users who are not members are only found, as inactive, when their email is at a domain verified by the organization.
+async fn get_scim_user(
+    organization_id: uuid::Uuid,
+    user_id: &str,
+    pool: web::Data<Pool>,
+) -> Result<(User, Option<UserRole>), ScimError> {
+    let user_id = user_id
+        .parse::<uuid::Uuid>()
+        .map_err(|_| ScimError::not_found("User"))?;
+
+    if let Some((user, user_org)) =
+        get_scim_org_member_query(organization_id, user_id, pool.clone()).await?
+    {
+        return Ok((user, Some(user_org.role.into())));
+    }
+
+    get_scim_former_member_query(organization_id, user_id, pool)
+        .await?
+        .map(|user| (user, None))
+        .ok_or_else(|| ScimError::not_found("User"))
+}
+
+/// SCIM tokens can only make members owners when they were created with can_grant_owner, otherwise they can grant at most the admin role
+fn check_scim_role_grant(
+    changes: &ScimUserChanges,
+    can_grant_owner: bool,
+) -> Result<(), ScimError> {
+    let grants_owner = changes.target_role == Some(UserRole::Owner)
+        && changes.current_role != Some(UserRole::Owner);
+
+    if grants_owner && !can_grant_owner {
+        return Err(ScimError {
+            status: StatusCode::FORBIDDEN,
+            scim_type: None,
+            detail: "The SCIM token can not grant the owner role, create it with can_grant_owner to allow it".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Applies the changes to the user and their membership of the organization in one transaction and records them in the audit log. Users who are removed have their api keys which are limited to the organization revoked.
+/// Changes which grant the owner role without can_grant_owner or which would leave the organization without an owner are rejected.
+async fn apply_scim_user_changes(
+    scim_org: &ScimOrganization,
+    user: &User,
+    changes: ScimUserChanges,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: &EventQueue,
+) -> Result<(), ScimError> {
+    let organization_id = scim_org.organization_id;
+
+    check_scim_role_grant(&changes, scim_org.can_grant_owner)?;
+
+    if changes.current_role.is_none() && changes.target_role.is_some() {
+        ensure_org_has_user_capacity(organization_id, pool.clone()).await?;
+    }
+
+    let revoked_api_keys =
+        apply_scim_user_changes_query(user, organization_id, &changes, pool, redis_pool).await?;
+
+    let (action, before, after) = match (changes.current_role, changes.target_role) {
+        (None, Some(target_role)) => (
+            AuditAction::UserAddedToOrganization,
+            Value::Null,
+            json!({ "role": Into::<i32>::into(target_role) }),
+        ),
+        (Some(current_role), Some(target_role)) if current_role != target_role => (
+            AuditAction::UserRoleUpdated,
+            json!({ "role": Into::<i32>::into(current_role) }),
+            json!({ "role": Into::<i32>::into(target_role) }),
+        ),
+        (Some(current_role), None) => (
+            AuditAction::UserRemovedFromOrganization,
+            json!({ "role": Into::<i32>::into(current_role) }),
+            Value::Null,
+        ),
+        _ => return Ok(()),
+    };
+
+    record_audit_event(
+        organization_id,
+        &scim_org.actor,
+        action,
+        user.id.to_string(),
+        get_audit_diff(before, after),
+        event_queue,
+    )
+    .await;
+
+    for api_key in revoked_api_keys.deleted {
+        record_audit_event(
+            organization_id,
+            &scim_org.actor,
+            AuditAction::ApiKeyDeleted,
+            api_key.id.to_string(),
+            get_audit_diff(json!(ApiKeyRespBody::from(api_key)), Value::Null),
+            event_queue,
+        )
+        .await;
+    }
+
+    for (api_key, limited_api_key) in revoked_api_keys.limited {
+        record_audit_event(
+            organization_id,
+            &scim_org.actor,
+            AuditAction::ApiKeyUpdated,
+            api_key.id.to_string(),
+            get_audit_diff(
+                json!(ApiKeyRespBody::from(api_key)),
+                json!(ApiKeyRespBody::from(limited_api_key)),
+            ),
+            event_queue,
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Email of the user, the primary email when there is one or else the userName
+fn get_scim_user_email(data: &ScimUser) -> Result<String, ScimError> {
+    let email = data
+        .emails
+        .iter()
+        .find(|email| email.primary == Some(true))
+        .or(data.emails.first())
+        .map(|email| email.value.clone())
+        .unwrap_or_else(|| data.user_name.clone());
+
+    if !email.contains('@') {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "The user needs an email as userName or in emails",
+        ));
+    }
+
+    Ok(email.trim().to_string())
+}
+
+fn get_scim_user_name(data: &ScimUser) -> Option<String> {
+    data.display_name
+        .clone()
+        .or_else(|| data.name.as_ref().and_then(|name| name.full_name()))
+}
+
+/// Get SCIM Service Provider Config
+///
+/// Describes which parts of SCIM 2.0 are supported. Authenticated with the organization's SCIM token.
+#[tracing::instrument]
+pub async fn get_scim_service_provider_config(
+    _scim_org: ScimOrganization,
+) -> Result<HttpResponse, ScimError> {
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &json!({
+            "schemas": [SCIM_SERVICE_PROVIDER_CONFIG_SCHEMA],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": SCIM_MAX_RESULTS },
+            "changePassword": { "supported": false },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "OAuth Bearer Token",
+                "description": "Authentication with the organization's SCIM token",
+                "primary": true,
+            }],
+        }),
+    ))
+}
+
+/// List SCIM Users
+///
+/// List the members of the organization. Supports the `userName eq "<email>"` filter.
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_users(
+    query: web::Query<ScimListQuery>,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ScimError> {
+    let email = query
+        .filter
+        .as_deref()
+        .map(|filter| parse_scim_eq_filter(filter, "userName"))
+        .transpose()?;
+    let (start_index, count) = query.get_page();
+
+    let (members, total_results) = get_scim_org_members_query(
+        scim_org.organization_id,
+        email,
+        start_index - 1,
+        count,
+        pool,
+    )
+    .await?;
+
+    let resources = members
+        .iter()
+        .map(|(user, user_org)| get_scim_user_resource(user, Some(&user_org.role.into())))
+        .collect();
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &ScimListResponse::new(resources, total_results, start_index),
+    ))
+}
+
+/// Get SCIM User
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_user_by_id(
+    user_id: web::Path<String>,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ScimError> {
+    let (user, role) = get_scim_user(scim_org.organization_id, &user_id, pool).await?;
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &get_scim_user_resource(&user, role.as_ref()),
+    ))
+}
+
+/// Create SCIM User
+///
+/// Add a user to the organization with the role in roles, user when there is none. The email of the user must be at a domain verified by the organization, users who do not have an account yet are created.
+#[tracing::instrument(skip(pool, redis_pool, event_queue, body))]
+pub async fn create_scim_user(
+    body: web::Bytes,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<HttpResponse, ScimError> {
+    let data: ScimUser = parse_scim_body(&body)?;
+    let organization_id = scim_org.organization_id;
+    let email = get_scim_user_email(&data)?;
+    let role = get_scim_role(&data.roles)?.unwrap_or(UserRole::User);
+
+    if !get_verified_domain_for_email_query(&email, pool.clone())
+        .await?
+        .is_some_and(|(domain, _)| domain.organization_id == organization_id)
+    {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            format!(
+                "The domain of {} is not verified by the organization",
+                email
+            ),
+        ));
+    }
+
+    let (user, changes) = match get_user_by_email_query(&email, pool.clone()).await? {
+        Some(user) => {
+            if get_scim_org_member_query(organization_id, user.id, pool.clone())
+                .await?
+                .is_some()
+            {
+                return Err(ScimError {
+                    status: StatusCode::CONFLICT,
+                    scim_type: Some("uniqueness"),
+                    detail: format!("{} is already a member of the organization", email),
+                });
+            }
+
+            (
+                user,
+                ScimUserChanges {
+                    target_role: data.active.then_some(role.clone()),
+                    ..Default::default()
+                },
+            )
+        }
+        None => {
+            if !data.active {
+                return Err(ScimError::bad_request(
+                    "invalidValue",
+                    "Users without an account can only be created active",
+                ));
+            }
+
+            (
+                User::from_details_with_id(
+                    format!("scim|{}|{}", organization_id, email),
+                    email,
+                    get_scim_user_name(&data),
+                ),
+                ScimUserChanges {
+                    create_user: true,
+                    target_role: Some(role.clone()),
+                    ..Default::default()
+                },
+            )
+        }
+    };
+
+    apply_scim_user_changes(&scim_org, &user, changes, pool, redis_pool, &event_queue).await?;
+
+    Ok(scim_response(
+        HttpResponse::Created(),
+        &get_scim_user_resource(&user, data.active.then_some(&role)),
+    ))
+}
+
+/// Replace SCIM User
+///
+/// Set the name, role and whether the user is active. A user who is made inactive is removed from the organization and has their api keys which can access it revoked. The email of a user can not be changed.
+#[tracing::instrument(skip(pool, redis_pool, event_queue, body))]
+pub async fn replace_scim_user(
+    user_id: web::Path<String>,
+    body: web::Bytes,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<HttpResponse, ScimError> {
+    let data: ScimUser = parse_scim_body(&body)?;
+    let (mut user, current_role) =
+        get_scim_user(scim_org.organization_id, &user_id, pool.clone()).await?;
+
+    let name = get_scim_user_name(&data).filter(|name| Some(name) != user.name.as_ref());
+    if name.is_some() {
+        user.name = name.clone();
+    }
+
+    let role = get_scim_role(&data.roles)?;
+    let target_role = data
+        .active
+        .then(|| role.or(current_role.clone()).unwrap_or(UserRole::User));
+    apply_scim_user_changes(
+        &scim_org,
+        &user,
+        ScimUserChanges {
+            create_user: false,
+            name,
+            current_role,
+            target_role: target_role.clone(),
+        },
+        pool,
+        redis_pool,
+        &event_queue,
+    )
+    .await?;
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &get_scim_user_resource(&user, target_role.as_ref()),
+    ))
+}
+
+/// Changes to a user requested by the operations of a patch request
+#[derive(Debug, Default)]
+struct ScimUserPatch {
+    active: Option<bool>,
+    name: Option<String>,
+    role: Option<UserRole>,
+}
+
+impl ScimUserPatch {
+    fn apply_attribute(&mut self, attribute: &str, value: &Value) -> Result<(), ScimError> {
+        match attribute.to_lowercase().as_str() {
+            "active" => self.active = Some(parse_scim_bool(value)?),
+            "displayname" | "name.formatted" => {
+                self.name = value.as_str().map(|name| name.to_string())
+            }
+            "name" => {
+                self.name = serde_json::from_value::<ScimName>(value.clone())
+                    .ok()
+                    .and_then(|name| name.full_name())
+            }
+            "roles" | "roles[primary eq true].value" => self.role = parse_scim_role_value(value)?,
+            // Attributes which are not stored, such as the email or phone numbers, are ignored
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn from_operations(operations: &[ScimPatchOperation]) -> Result<Self, ScimError> {
+        let mut patch = ScimUserPatch::default();
+
+        for operation in operations {
+            match (
+                operation.op.to_lowercase().as_str(),
+                operation.path.as_deref(),
+                operation.value.as_ref(),
+            ) {
+                ("add" | "replace", Some(path), Some(value)) => {
+                    patch.apply_attribute(path, value)?
+                }
+                ("add" | "replace", None, Some(Value::Object(attributes))) => {
+                    for (attribute, value) in attributes {
+                        patch.apply_attribute(attribute, value)?;
+                    }
+                }
+                ("remove", Some(path), _) if path.to_lowercase().starts_with("roles") => {
+                    patch.role = Some(UserRole::User)
+                }
+                ("add" | "replace" | "remove", _, _) => {}
+                (op, _, _) => {
+                    return Err(ScimError::bad_request(
+                        "invalidSyntax",
+                        format!("Unknown patch operation {}", op),
+                    ))
+                }
+            }
+        }
+
+        Ok(patch)
+    }
+}
+
+/// Update SCIM User
+///
+/// Apply patch operations to the active flag, name or role of the user. A user who is made inactive is removed from the organization and has their api keys which can access it revoked.
+#[tracing::instrument(skip(pool, redis_pool, event_queue, body))]
+pub async fn update_scim_user(
+    user_id: web::Path<String>,
+    body: web::Bytes,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<HttpResponse, ScimError> {
+    let data: ScimPatchRequest = parse_scim_body(&body)?;
+    let patch = ScimUserPatch::from_operations(&data.operations)?;
+    let (mut user, current_role) =
+        get_scim_user(scim_org.organization_id, &user_id, pool.clone()).await?;
+
+    let name = patch.name.filter(|name| Some(name) != user.name.as_ref());
+    if name.is_some() {
+        user.name = name.clone();
+    }
+
+    let target_role = match patch.active {
+        Some(false) => None,
+        Some(true) => Some(
+            patch
+                .role
+                .or(current_role.clone())
+                .unwrap_or(UserRole::User),
+        ),
+        None => current_role
+            .clone()
+            .map(|current_role| patch.role.unwrap_or(current_role)),
+    };
+    apply_scim_user_changes(
+        &scim_org,
+        &user,
+        ScimUserChanges {
+            create_user: false,
+            name,
+            current_role,
+            target_role: target_role.clone(),
+        },
+        pool,
+        redis_pool,
+        &event_queue,
+    )
+    .await?;
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &get_scim_user_resource(&user, target_role.as_ref()),
+    ))
+}
+
+/// Delete SCIM User
+///
+/// Remove the user from the organization and revoke their api keys which can access it. Their account is kept as they may be a member of other organizations.
+#[tracing::instrument(skip(pool, redis_pool, event_queue))]
+pub async fn delete_scim_user(
+    user_id: web::Path<String>,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<HttpResponse, ScimError> {
+    let (user, current_role) =
+        get_scim_user(scim_org.organization_id, &user_id, pool.clone()).await?;
+
+    apply_scim_user_changes(
+        &scim_org,
+        &user,
+        ScimUserChanges {
+            current_role,
+            ..Default::default()
+        },
+        pool,
+        redis_pool,
+        &event_queue,
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// List SCIM Groups
+///
+/// List the groups of the organization, one for each role. Supports the `displayName eq "<name>"` filter.
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_groups(
+    query: web::Query<ScimListQuery>,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ScimError> {
+    let display_name = query
+        .filter
+        .as_deref()
+        .map(|filter| parse_scim_eq_filter(filter, "displayName"))
+        .transpose()?;
+    let (start_index, count) = query.get_page();
+
+    let roles = SCIM_GROUP_ROLES
+        .into_iter()
+        .filter(|role| {
+            display_name.as_deref().map_or(true, |display_name| {
+                parse_scim_role(display_name) == Some(role.clone())
+            })
+        })
+        .collect::<Vec<UserRole>>();
+
+    let mut resources = vec![];
+    for role in roles
+        .iter()
+        .skip((start_index - 1) as usize)
+        .take(count as usize)
+    {
+        resources
+            .push(get_scim_group_resource(scim_org.organization_id, role, pool.clone()).await?);
+    }
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &ScimListResponse::new(resources, roles.len() as i64, start_index),
+    ))
+}
+
+/// Get SCIM Group
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_group_by_id(
+    group_id: web::Path<String>,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ScimError> {
+    let role = parse_scim_role(&group_id).ok_or_else(|| ScimError::not_found("Group"))?;
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &get_scim_group_resource(scim_org.organization_id, &role, pool).await?,
+    ))
+}
+
+/// Moves a member into the role of the group when they are added to it, or back to the user role when they are removed from it. Users who are not members of the organization are left alone.
+async fn update_scim_group_member(
+    scim_org: &ScimOrganization,
+    member_id: &str,
+    group_role: &UserRole,
+    added: bool,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: &EventQueue,
+) -> Result<(), ScimError> {
+    let (user, current_role) =
+        get_scim_user(scim_org.organization_id, member_id, pool.clone()).await?;
+    let Some(current_role) = current_role else {
+        return Ok(());
+    };
+
+    let target_role = if added {
+        group_role.clone()
+    } else if &current_role == group_role {
+        UserRole::User
+    } else {
+        return Ok(());
+    };
+
+    apply_scim_user_changes(
+        scim_org,
+        &user,
+        ScimUserChanges {
+            current_role: Some(current_role),
+            target_role: Some(target_role),
+            ..Default::default()
+        },
+        pool,
+        redis_pool,
+        event_queue,
+    )
+    .await
+}
+
+/// Makes the given users the members of the group, members who are not among them are moved back to the user role
+async fn replace_scim_group_members(
+    scim_org: &ScimOrganization,
+    group_role: &UserRole,
+    member_ids: Vec<String>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: &EventQueue,
+) -> Result<(), ScimError> {
+    let current_members = get_scim_org_members_with_role_query(
+        scim_org.organization_id,
+        group_role.clone(),
+        pool.clone(),
+    )
+    .await?;
+
+    for member in current_members {
+        if !member_ids.contains(&member.id.to_string()) {
+            update_scim_group_member(
+                scim_org,
+                &member.id.to_string(),
+                group_role,
+                false,
+                pool.clone(),
+                redis_pool.clone(),
+                event_queue,
+            )
+            .await?;
+        }
+    }
+
+    for member_id in member_ids {
+        update_scim_group_member(
+            scim_org,
+            &member_id,
+            group_role,
+            true,
+            pool.clone(),
+            redis_pool.clone(),
+            event_queue,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Update SCIM Group
+///
+/// Add, remove or replace members of the group to change their role. Members removed from a group are moved back to the user role. The names of groups can not be changed.
+#[tracing::instrument(skip(pool, redis_pool, event_queue, body))]
+pub async fn update_scim_group(
+    group_id: web::Path<String>,
+    body: web::Bytes,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<HttpResponse, ScimError> {
+    let group_role = parse_scim_role(&group_id).ok_or_else(|| ScimError::not_found("Group"))?;
+    let data: ScimPatchRequest = parse_scim_body(&body)?;
+
+    for operation in data.operations {
+        let path = operation.path.as_deref().map(|path| path.trim());
+        let members_path = path.is_some_and(|path| path.eq_ignore_ascii_case("members"));
+
+        match operation.op.to_lowercase().as_str() {
+            "add" if members_path || path.is_none() => {
+                for member_id in parse_scim_member_ids(operation.value.as_ref())? {
+                    update_scim_group_member(
+                        &scim_org,
+                        &member_id,
+                        &group_role,
+                        true,
+                        pool.clone(),
+                        redis_pool.clone(),
+                        &event_queue,
+                    )
+                    .await?;
+                }
+            }
+            "replace" if members_path => {
+                replace_scim_group_members(
+                    &scim_org,
+                    &group_role,
+                    parse_scim_member_ids(operation.value.as_ref())?,
+                    pool.clone(),
+                    redis_pool.clone(),
+                    &event_queue,
+                )
+                .await?;
+            }
+            "remove" => {
+                let member_ids = match path {
+                    Some(_) if members_path => {
+                        let member_ids = parse_scim_member_ids(operation.value.as_ref())?;
+                        if member_ids.is_empty() {
+                            get_scim_org_members_with_role_query(
+                                scim_org.organization_id,
+                                group_role.clone(),
+                                pool.clone(),
+                            )
+                            .await?
+                            .into_iter()
+                            .map(|member| member.id.to_string())
+                            .collect()
+                        } else {
+                            member_ids
+                        }
+                    }
+                    Some(path)
+                        if path
+                            .get(..8)
+                            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("members[")) =>
+                    {
+                        let filter = path[8..].strip_suffix(']').unwrap_or_default();
+                        vec![parse_scim_eq_filter(filter, "value")?]
+                    }
+                    _ => vec![],
+                };
+
+                for member_id in member_ids {
+                    update_scim_group_member(
+                        &scim_org,
+                        &member_id,
+                        &group_role,
+                        false,
+                        pool.clone(),
+                        redis_pool.clone(),
+                        &event_queue,
+                    )
+                    .await?;
+                }
+            }
+            // Groups are fixed to the roles of the organization so changes to their names are ignored
+            "add" | "replace" => {}
+            op => {
+                return Err(ScimError::bad_request(
+                    "invalidSyntax",
+                    format!("Unknown patch operation {}", op),
+                ))
+            }
+        }
+    }
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &get_scim_group_resource(scim_org.organization_id, &group_role, pool).await?,
+    ))
+}
+
+/// Replace SCIM Group
+///
+/// Make the given users the members of the group. Members who are left out are moved back to the user role.
+#[tracing::instrument(skip(pool, redis_pool, event_queue, body))]
+pub async fn replace_scim_group(
+    group_id: web::Path<String>,
+    body: web::Bytes,
+    scim_org: ScimOrganization,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<HttpResponse, ScimError> {
+    let group_role = parse_scim_role(&group_id).ok_or_else(|| ScimError::not_found("Group"))?;
+    let data: ScimGroup = parse_scim_body(&body)?;
+
+    replace_scim_group_members(
+        &scim_org,
+        &group_role,
+        data.members
+            .into_iter()
+            .map(|member| member.value)
+            .collect(),
+        pool.clone(),
+        redis_pool,
+        &event_queue,
+    )
+    .await?;
+
+    Ok(scim_response(
+        HttpResponse::Ok(),
+        &get_scim_group_resource(scim_org.organization_id, &group_role, pool).await?,
+    ))
+}
+
+/// Create or Delete SCIM Group
+///
+/// Groups are fixed to the roles of the organization so they can not be created or deleted.
+#[tracing::instrument]
+pub async fn create_or_delete_scim_group(
+    _scim_org: ScimOrganization,
+) -> Result<HttpResponse, ScimError> {
+    Err(ScimError {
+        status: StatusCode::FORBIDDEN,
+        scim_type: None,
+        detail: "Groups are fixed to the owner, admin and user roles of the organization"
+            .to_string(),
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "token": "tr-scim-Xk2lPq8vR4tY7wZ1Xk2lPq8vR4tY7wZ1",
+    "scim_token": {
+        "id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+        "organization_id": "e3e3e3e3-e3e3-e3e3-e3e3-e3e3e3e3e3e3",
+        "token_prefix": "tr-scim-Xk2",
+        "created_at": "2021-01-01 00:00:00.000",
+        "last_used_at": null,
+        "can_grant_owner": false,
+    },
+}))]
+pub struct CreateScimTokenResponse {
+    /// Bearer token to configure in the identity provider. It is only returned once.
+    pub token: String,
+    pub scim_token: OrganizationScimTokenDTO,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+#[schema(example = json!({
+    "can_grant_owner": false,
+}))]
+pub struct CreateScimTokenReqPayload {
+    /// Whether the identity provider can make members owners of the organization. Defaults to false, in which case it can grant at most the admin role.
+    pub can_grant_owner: Option<bool>,
+}
+
+/// Create SCIM Token
+///
+/// Create the token identity providers such as Okta and Azure AD use to provision members of the organization at `/scim/v2/Users` and `/scim/v2/Groups`, replacing the previous token. Users who do not have an account yet can only be provisioned when their email is at a domain verified by the organization. The token can only grant the owner role when it is created with can_grant_owner, and it can never remove or demote the last owner. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    post,
+    path = "/organization/scim_token",
+    context_path = "/api",
+    tag = "Organization",
+    request_body(content = Option<CreateScimTokenReqPayload>, description = "JSON request payload to create the SCIM token", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The SCIM token, which is only returned once", body = CreateScimTokenResponse),
+        (status = 400, description = "Service error relating to creating the SCIM token", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool, event_queue))]
+pub async fn create_scim_token(
+    body: Option<web::Json<CreateScimTokenReqPayload>>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let previous_token = get_scim_token_query(organization_id, pool.clone()).await?;
+    let can_grant_owner = body.and_then(|body| body.can_grant_owner).unwrap_or(false);
+    let (token, scim_token) =
+        create_scim_token_query(organization_id, can_grant_owner, pool).await?;
+
+    record_audit_event(
+        organization_id,
+        &actor,
+        AuditAction::ScimTokenCreated,
+        organization_id.to_string(),
+        get_audit_diff(
+            json!({
+                "scim_token_prefix": previous_token.as_ref().map(|token| token.token_prefix.clone()),
+                "can_grant_owner": previous_token.map(|token| token.can_grant_owner),
+            }),
+            json!({
+                "scim_token_prefix": scim_token.token_prefix,
+                "can_grant_owner": scim_token.can_grant_owner,
+            }),
+        ),
+        &event_queue,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(CreateScimTokenResponse {
+        token,
+        scim_token: scim_token.into(),
+    }))
+}
+
+/// Get SCIM Token
+///
+/// Get the prefix of the organization's SCIM token and when it was last used. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    get,
+    path = "/organization/scim_token",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 200, description = "The SCIM token of the organization", body = OrganizationScimTokenDTO),
+        (status = 404, description = "The organization does not have a SCIM token", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_token(
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let scim_token = get_scim_token_query(organization_id, pool)
+        .await?
+        .ok_or_else(|| {
+            ServiceError::NotFound("Organization does not have a SCIM token".to_string())
+        })?;
+
+    Ok(HttpResponse::Ok().json(OrganizationScimTokenDTO::from(scim_token)))
+}
+
+/// Delete SCIM Token
+///
+/// Delete the organization's SCIM token such that identity providers can no longer provision its members. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    delete,
+    path = "/organization/scim_token",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 204, description = "Confirmation that the SCIM token was deleted"),
+        (status = 400, description = "Service error relating to deleting the SCIM token", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool, event_queue))]
+pub async fn delete_scim_token(
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let previous_token = get_scim_token_query(organization_id, pool.clone()).await?;
+    delete_scim_token_query(organization_id, pool).await?;
+
+    record_audit_event(
+        organization_id,
+        &actor,
+        AuditAction::ScimTokenDeleted,
+        organization_id.to_string(),
+        get_audit_diff(
+            json!({ "scim_token_prefix": previous_token.map(|token| token.token_prefix) }),
+            json!({ "scim_token_prefix": null }),
+        ),
+        &event_queue,
+    )
+    .await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn patch_operations(operations: Value) -> Vec<ScimPatchOperation> {
+        serde_json::from_value(operations).unwrap()
+    }
+
+    #[test]
+    fn test_parse_scim_eq_filter() {
+        assert_eq!(
+            parse_scim_eq_filter("userName eq \"jane@example.com\"", "userName").unwrap(),
+            "jane@example.com"
+        );
+        assert_eq!(
+            parse_scim_eq_filter("  username EQ \"jane@example.com\" ", "userName").unwrap(),
+            "jane@example.com"
+        );
+        assert_eq!(
+            parse_scim_eq_filter("displayName eq \"Admins \\\"EU\\\"\"", "displayName").unwrap(),
+            "Admins \"EU\""
+        );
+        assert_eq!(
+            parse_scim_eq_filter("displayName eq \"Site Admins\"", "displayName").unwrap(),
+            "Site Admins"
+        );
+
+        for filter in [
+            "",
+            "userName",
+            "userName eq",
+            "userName eq jane@example.com",
+            "userName co \"jane\"",
+            "emails eq \"jane@example.com\"",
+            "userName eq \"jane@example.com",
+        ] {
+            let err = parse_scim_eq_filter(filter, "userName").unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST, "{}", filter);
+            assert_eq!(err.scim_type, Some("invalidFilter"), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn test_scim_user_patch_with_paths() {
+        let patch = ScimUserPatch::from_operations(&patch_operations(json!([
+            { "op": "Replace", "path": "active", "value": "False" },
+            { "op": "replace", "path": "displayName", "value": "Jane Doe" },
+            { "op": "add", "path": "roles", "value": [{ "value": "admin", "primary": true }] },
+        ])))
+        .unwrap();
+
+        assert_eq!(patch.active, Some(false));
+        assert_eq!(patch.name.as_deref(), Some("Jane Doe"));
+        assert_eq!(patch.role, Some(UserRole::Admin));
+    }
+
+    #[test]
+    fn test_scim_user_patch_without_path() {
+        let patch = ScimUserPatch::from_operations(&patch_operations(json!([{
+            "op": "replace",
+            "value": {
+                "active": true,
+                "name": { "givenName": "Jane", "familyName": "Doe" },
+                "roles": "Owners",
+                "emails": [{ "value": "other@example.com" }],
+            },
+        }])))
+        .unwrap();
+
+        assert_eq!(patch.active, Some(true));
+        assert_eq!(patch.name.as_deref(), Some("Jane Doe"));
+        assert_eq!(patch.role, Some(UserRole::Owner));
+    }
+
+    #[test]
+    fn test_scim_user_patch_remove_role() {
+        let patch = ScimUserPatch::from_operations(&patch_operations(json!([
+            { "op": "replace", "path": "roles", "value": "admin" },
+            { "op": "remove", "path": "roles[value eq \"admin\"]" },
+            { "op": "remove", "path": "phoneNumbers" },
+        ])))
+        .unwrap();
+
+        assert_eq!(patch.active, None);
+        assert_eq!(patch.name, None);
+        assert_eq!(patch.role, Some(UserRole::User));
+    }
+
+    #[test]
+    fn test_scim_can_only_grant_owner_when_allowed() {
+        let grant_owner = |current_role: Option<UserRole>| ScimUserChanges {
+            current_role,
+            target_role: Some(UserRole::Owner),
+            ..Default::default()
+        };
+
+        for current_role in [None, Some(UserRole::User), Some(UserRole::Admin)] {
+            let err = check_scim_role_grant(&grant_owner(current_role.clone()), false).unwrap_err();
+            assert_eq!(err.status, StatusCode::FORBIDDEN);
+            assert!(check_scim_role_grant(&grant_owner(current_role), true).is_ok());
+        }
+
+        // Owners can be renamed or kept as owners without can_grant_owner
+        assert!(check_scim_role_grant(&grant_owner(Some(UserRole::Owner)), false).is_ok());
+        assert!(check_scim_role_grant(
+            &ScimUserChanges {
+                current_role: Some(UserRole::User),
+                target_role: Some(UserRole::Admin),
+                ..Default::default()
+            },
+            false
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_scim_changes_can_not_remove_the_last_owner() {
+        let changes = |target_role: Option<UserRole>| ScimUserChanges {
+            current_role: Some(UserRole::Owner),
+            target_role,
+            ..Default::default()
+        };
+
+        for target_role in [None, Some(UserRole::Admin), Some(UserRole::User)] {
+            assert!(changes(target_role.clone()).removes_last_owner(1));
+            assert!(!changes(target_role).removes_last_owner(2));
+        }
+        assert!(!changes(Some(UserRole::Owner)).removes_last_owner(1));
+
+        let remove_admin = ScimUserChanges {
+            current_role: Some(UserRole::Admin),
+            ..Default::default()
+        };
+        assert!(!remove_admin.removes_last_owner(1));
+    }
+
+    #[test]
+    fn test_scim_user_patch_rejects_invalid_operations() {
+        for operations in [
+            json!([{ "op": "move", "path": "active", "value": true }]),
+            json!([{ "op": "replace", "path": "active", "value": "maybe" }]),
+            json!([{ "op": "replace", "path": "roles", "value": "superuser" }]),
+        ] {
+            let err =
+                ScimUserPatch::from_operations(&patch_operations(operations.clone())).unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST, "{}", operations);
+        }
+    }
+}
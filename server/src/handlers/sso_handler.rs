@@ -0,0 +1,860 @@
+use super::auth_handler::{
+    get_verified_oidc_claims, login_user, OpCallback, OpenIdConnectState, OwnerOnly,
+};
+#[cfg(not(feature = "saml"))]
+use crate::operators::sso_operator::saml_not_enabled_error;
+#[cfg(feature = "saml")]
+use crate::operators::sso_operator::{build_saml_service_provider, get_saml_user_details};
+use crate::{
+    data::models::{
+        AuditAction, AuditActor, OrganizationSsoConfig, OrganizationSsoConfigDTO,
+        OrganizationWithSubAndPlan, Pool, RedisPool, SsoProvider, UserRole,
+    },
+    errors::ServiceError,
+    middleware::auth_middleware::verify_owner,
+    operators::{
+        audit_operator::{get_audit_diff, record_audit_event},
+        clickhouse_operator::EventQueue,
+        secret_operator::encrypt_secret,
+        sso_operator::{
+            build_sso_oidc_client, create_org_domain_query, delete_org_domain_query,
+            delete_sso_config_query, get_org_domains_query, get_sso_config_query,
+            normalize_org_domain, provision_sso_user_query, set_sso_config_query,
+            validate_sso_config, verify_org_domain_query,
+        },
+    },
+};
+use actix_session::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
+use oauth2::{CsrfToken, PkceCodeChallenge, Scope};
+use openidconnect::{core::CoreAuthenticationFlow, Nonce};
+#[cfg(feature = "saml")]
+use redis::AsyncCommands;
+#[cfg(feature = "saml")]
+use samael::metadata::HTTP_REDIRECT_BINDING;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+/// Session key holding the organization the user last signed in to through SSO, checked for organizations which enforce SSO
+pub const SSO_ORGANIZATION_SESSION_KEY: &str = "sso_organization_id";
+
+const SSO_OIDC_SESSION_KEY: &str = "sso_oidc_state";
+const SSO_LOGIN_STATE_SESSION_KEY: &str = "sso_login_state";
+
+/// How long a user has to finish signing in with a SAML identity provider
+#[cfg(feature = "saml")]
+const SAML_RELAY_STATE_TTL_SECONDS: u64 = 600;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SsoLoginState {
+    organization_id: uuid::Uuid,
+    redirect_uri: String,
+}
+
+/// SAML responses are posted cross-site so the login state is kept in redis under the relay state instead of the session
+#[cfg(feature = "saml")]
+#[derive(Serialize, Deserialize, Debug)]
+struct SamlLoginState {
+    organization_id: uuid::Uuid,
+    request_id: String,
+    redirect_uri: String,
+}
+
+/// Redirects to the identity provider of the organization to sign in
+pub async fn start_sso_login(
+    session: &Session,
+    config: OrganizationSsoConfig,
+    redirect_uri: String,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HttpResponse, ServiceError> {
+    match SsoProvider::from(config.provider.clone()) {
+        SsoProvider::Oidc => {
+            let oidc_client = build_sso_oidc_client(&config).await?;
+            let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+            let (auth_url, csrf_token, nonce) = oidc_client
+                .authorize_url(
+                    CoreAuthenticationFlow::AuthorizationCode,
+                    CsrfToken::new_random,
+                    Nonce::new_random,
+                )
+                .add_scopes([
+                    Scope::new("profile".to_owned()),
+                    Scope::new("email".to_owned()),
+                ])
+                .set_pkce_challenge(pkce_challenge)
+                .url();
+
+            session
+                .insert(
+                    SSO_OIDC_SESSION_KEY,
+                    OpenIdConnectState {
+                        pkce_verifier,
+                        csrf_token,
+                        nonce,
+                    },
+                )
+                .map_err(|_| {
+                    ServiceError::InternalServerError("Could not set OIDC Session".into())
+                })?;
+
+            session
+                .insert(
+                    SSO_LOGIN_STATE_SESSION_KEY,
+                    SsoLoginState {
+                        organization_id: config.organization_id,
+                        redirect_uri,
+                    },
+                )
+                .map_err(|_| {
+                    ServiceError::InternalServerError("Could not set redirect url".into())
+                })?;
+
+            Ok(HttpResponse::SeeOther()
+                .insert_header(("Location", auth_url.as_str()))
+                .finish())
+        }
+        SsoProvider::Saml => start_saml_login(config, redirect_uri, redis_pool).await,
+    }
+}
+
+/// Redirects to the SAML identity provider of the organization, remembering the request under the relay state
+#[cfg(feature = "saml")]
+async fn start_saml_login(
+    config: OrganizationSsoConfig,
+    redirect_uri: String,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HttpResponse, ServiceError> {
+    let service_provider = build_saml_service_provider(&config)?;
+
+    let idp_sso_url = service_provider
+        .sso_binding_location(HTTP_REDIRECT_BINDING)
+        .ok_or_else(|| {
+            ServiceError::BadRequest(
+                "SAML identity provider does not support the HTTP-Redirect binding".to_string(),
+            )
+        })?;
+
+    let authn_request = service_provider
+        .make_authentication_request(&idp_sso_url)
+        .map_err(|err| {
+            log::error!("Failed to create SAML authentication request {:?}", err);
+            ServiceError::BadRequest("Failed to create SAML authentication request".to_string())
+        })?;
+
+    let relay_state = uuid::Uuid::new_v4().to_string();
+    let redirect_url = authn_request
+        .redirect(&relay_state)
+        .map_err(|err| {
+            log::error!("Failed to encode SAML authentication request {:?}", err);
+            ServiceError::BadRequest("Failed to encode SAML authentication request".to_string())
+        })?
+        .ok_or_else(|| {
+            ServiceError::BadRequest("Failed to encode SAML authentication request".to_string())
+        })?;
+
+    let login_state = serde_json::to_string(&SamlLoginState {
+        organization_id: config.organization_id,
+        request_id: authn_request.id.clone(),
+        redirect_uri,
+    })
+    .map_err(|_| {
+        ServiceError::InternalServerError("Failed to serialize SAML login state".into())
+    })?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    redis_conn
+        .set_ex::<_, _, ()>(
+            format!("saml_relay_state:{}", relay_state),
+            login_state,
+            SAML_RELAY_STATE_TTL_SECONDS,
+        )
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header(("Location", redirect_url.as_str()))
+        .finish())
+}
+
+#[cfg(not(feature = "saml"))]
+async fn start_saml_login(
+    _config: OrganizationSsoConfig,
+    _redirect_uri: String,
+    _redis_pool: web::Data<RedisPool>,
+) -> Result<HttpResponse, ServiceError> {
+    Err(saml_not_enabled_error())
+}
+
+/// Signs the user in, remembering which organization's SSO they came through
+async fn complete_sso_login(
+    req: &HttpRequest,
+    session: &Session,
+    organization_id: uuid::Uuid,
+    (subject, email, name): (String, String, String),
+    redirect_uri: String,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HttpResponse, ServiceError> {
+    let (user, user_orgs, orgs) = provision_sso_user_query(
+        organization_id,
+        subject,
+        email,
+        name,
+        pool,
+        redis_pool.clone(),
+    )
+    .await?;
+
+    login_user(req, user, user_orgs, orgs, redis_pool).await?;
+    session
+        .insert(SSO_ORGANIZATION_SESSION_KEY, organization_id)
+        .map_err(|_| ServiceError::InternalServerError("Could not set SSO Session".into()))?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header(("Location", redirect_uri))
+        .finish())
+}
+
+/// SSO OpenID Connect callback
+///
+/// This is the callback route for the OIDC provider of an organization, it should not be called directly. It has to be allowed as a redirect URI in the provider's client settings. Redirects to browser with set-cookie header.
+#[utoipa::path(
+    get,
+    path = "/auth/sso/callback",
+    context_path = "/api",
+    tag = "Auth",
+    responses(
+        (status = 303, description = "Response that redirects to the page login was started from with a set-cookie header"),
+        (status = 400, description = "The email is not verified by the provider, is not at a domain verified by the organization or belongs to an account outside of it", body = ErrorResponseBody),
+    )
+)]
+#[tracing::instrument(skip(session, pool, redis_pool))]
+pub async fn sso_callback(
+    req: HttpRequest,
+    session: Session,
+    query: web::Query<OpCallback>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HttpResponse, ServiceError> {
+    let state: OpenIdConnectState = session
+        .get(SSO_OIDC_SESSION_KEY)
+        .map_err(|_| ServiceError::InternalServerError("Could not get OIDC Session".into()))?
+        .ok_or(ServiceError::Unauthorized)?;
+
+    let login_state: SsoLoginState = session
+        .get(SSO_LOGIN_STATE_SESSION_KEY)
+        .map_err(|_| ServiceError::InternalServerError("Could not get redirect url".into()))?
+        .ok_or(ServiceError::Unauthorized)?;
+
+    session.remove(SSO_OIDC_SESSION_KEY);
+    session.remove(SSO_LOGIN_STATE_SESSION_KEY);
+
+    let config = get_sso_config_query(login_state.organization_id, pool.clone())
+        .await?
+        .ok_or_else(|| {
+            ServiceError::BadRequest("Organization no longer has SSO configured".to_string())
+        })?;
+
+    let oidc_client = build_sso_oidc_client(&config).await?;
+    let claims = get_verified_oidc_claims(&oidc_client, state, query.into_inner()).await?;
+
+    let email = claims
+        .email()
+        .ok_or_else(|| {
+            ServiceError::InternalServerError("Failed to parse email from claims".into())
+        })?
+        .to_string();
+
+    // Whether the email is at a verified domain is decided by the email, so it has to be one the provider verified
+    if claims.email_verified() != Some(true) {
+        return Err(ServiceError::BadRequest(format!(
+            "The identity provider has not verified {}",
+            email
+        )));
+    }
+
+    let name = claims
+        .name()
+        .and_then(|name| name.iter().next().map(|(_, name)| name.to_string()))
+        .unwrap_or(email.clone());
+
+    complete_sso_login(
+        &req,
+        &session,
+        config.organization_id,
+        (claims.subject().to_string(), email, name),
+        login_state.redirect_uri,
+        pool,
+        redis_pool,
+    )
+    .await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SamlAcsForm {
+    #[serde(rename = "SAMLResponse")]
+    pub saml_response: String,
+    #[serde(rename = "RelayState")]
+    pub relay_state: Option<String>,
+}
+
+/// SAML Assertion Consumer Service
+///
+/// This is the route the SAML identity provider of an organization posts its response to, it should not be called directly. Only responses to sign ins started from Trieve are accepted. Redirects to browser with set-cookie header.
+#[utoipa::path(
+    post,
+    path = "/auth/sso/saml/{organization_id}/acs",
+    context_path = "/api",
+    tag = "Auth",
+    params(
+        ("organization_id" = uuid::Uuid, Path, description = "The id of the organization the identity provider belongs to"),
+    ),
+    responses(
+        (status = 303, description = "Response that redirects to the page login was started from with a set-cookie header"),
+        (status = 400, description = "The email is not at a domain verified by the organization or belongs to an account outside of it", body = ErrorResponseBody),
+        (status = 401, description = "The SAML response is invalid or was not requested", body = ErrorResponseBody),
+    )
+)]
+#[tracing::instrument(skip(session, form, pool, redis_pool))]
+pub async fn saml_acs(
+    req: HttpRequest,
+    session: Session,
+    organization_id: web::Path<uuid::Uuid>,
+    form: web::Form<SamlAcsForm>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = organization_id.into_inner();
+    let (redirect_uri, user_details) = verify_saml_response(
+        organization_id,
+        form.into_inner(),
+        pool.clone(),
+        redis_pool.clone(),
+    )
+    .await?;
+
+    complete_sso_login(
+        &req,
+        &session,
+        organization_id,
+        user_details,
+        redirect_uri,
+        pool,
+        redis_pool,
+    )
+    .await
+}
+
+/// Checks the SAML response answers a sign in started for the organization and returns where to redirect to along with the subject, email and name of the user
+#[cfg(feature = "saml")]
+async fn verify_saml_response(
+    organization_id: uuid::Uuid,
+    form: SamlAcsForm,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(String, (String, String, String)), ServiceError> {
+    let relay_state = form.relay_state.clone().ok_or(ServiceError::Unauthorized)?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    // Taking the login state out of redis makes every response usable only once
+    let login_state: Option<String> = redis_conn
+        .get_del(format!("saml_relay_state:{}", relay_state))
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    let login_state: SamlLoginState = login_state
+        .and_then(|login_state| serde_json::from_str(&login_state).ok())
+        .ok_or(ServiceError::Unauthorized)?;
+
+    if login_state.organization_id != organization_id {
+        return Err(ServiceError::Unauthorized);
+    }
+
+    let config = get_sso_config_query(organization_id, pool.clone())
+        .await?
+        .ok_or_else(|| {
+            ServiceError::BadRequest("Organization no longer has SSO configured".to_string())
+        })?;
+
+    let service_provider = build_saml_service_provider(&config)?;
+    let assertion = service_provider
+        .parse_base64_response(
+            &form.saml_response,
+            Some(&[login_state.request_id.as_str()]),
+        )
+        .map_err(|err| {
+            log::error!("Invalid SAML response {:?}", err);
+            ServiceError::Unauthorized
+        })?;
+
+    Ok((login_state.redirect_uri, get_saml_user_details(&assertion)?))
+}
+
+#[cfg(not(feature = "saml"))]
+async fn verify_saml_response(
+    _organization_id: uuid::Uuid,
+    _form: SamlAcsForm,
+    _pool: web::Data<Pool>,
+    _redis_pool: web::Data<RedisPool>,
+) -> Result<(String, (String, String, String)), ServiceError> {
+    Err(saml_not_enabled_error())
+}
+
+/// Get SAML Service Provider Metadata
+///
+/// Get the SAML metadata of Trieve as a service provider for the organization, which can be uploaded to its identity provider. The entity id is the URL of this route and the assertion consumer service is at /api/auth/sso/saml/{organization_id}/acs.
+#[utoipa::path(
+    get,
+    path = "/auth/sso/saml/{organization_id}/metadata",
+    context_path = "/api",
+    tag = "Auth",
+    params(
+        ("organization_id" = uuid::Uuid, Path, description = "The id of the organization to get the service provider metadata for"),
+    ),
+    responses(
+        (status = 200, description = "SAML metadata of the service provider", body = String, content_type = "application/samlmetadata+xml"),
+        (status = 404, description = "The organization does not have SAML SSO configured", body = ErrorResponseBody),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_saml_metadata(
+    organization_id: web::Path<uuid::Uuid>,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, ServiceError> {
+    let config = get_sso_config_query(organization_id.into_inner(), pool)
+        .await?
+        .filter(|config| SsoProvider::from(config.provider.clone()) == SsoProvider::Saml)
+        .ok_or_else(|| {
+            ServiceError::NotFound("Organization does not have SAML SSO configured".to_string())
+        })?;
+
+    let metadata = get_saml_metadata_xml(&config)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/samlmetadata+xml")
+        .body(metadata))
+}
+
+#[cfg(feature = "saml")]
+fn get_saml_metadata_xml(config: &OrganizationSsoConfig) -> Result<String, ServiceError> {
+    build_saml_service_provider(config)?
+        .metadata()
+        .map_err(|err| {
+            log::error!("Failed to build SAML metadata {:?}", err);
+            ServiceError::InternalServerError("Failed to build SAML metadata".to_string())
+        })?
+        .to_xml()
+        .map_err(|err| {
+            log::error!("Failed to serialize SAML metadata {:?}", err);
+            ServiceError::InternalServerError("Failed to serialize SAML metadata".to_string())
+        })
+}
+
+#[cfg(not(feature = "saml"))]
+fn get_saml_metadata_xml(_config: &OrganizationSsoConfig) -> Result<String, ServiceError> {
+    Err(saml_not_enabled_error())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "provider": "oidc",
+    "oidc_issuer_url": "https://acme.okta.com",
+    "oidc_client_id": "0oa1b2c3d4e5f6g7h8i9",
+    "oidc_client_secret": "secret",
+    "sso_only": false,
+}))]
+pub struct SetSsoConfigReqPayload {
+    pub provider: SsoProvider,
+    /// Issuer URL of the OIDC provider, required for OIDC. The provider has to allow {BASE_SERVER_URL}/api/auth/sso/callback as a redirect URI.
+    pub oidc_issuer_url: Option<String>,
+    /// Client ID of the OIDC provider, required for OIDC
+    pub oidc_client_id: Option<String>,
+    /// Client secret of the OIDC provider, required for OIDC. It is stored encrypted and never returned.
+    pub oidc_client_secret: Option<String>,
+    /// XML metadata of the SAML identity provider, required for SAML. The identity provider has to be given the service provider metadata at /api/auth/sso/saml/{organization_id}/metadata.
+    pub saml_idp_metadata: Option<String>,
+    /// Whether members have to sign in through SSO to use the organization with a session. Requires a verified domain. Api keys keep working, so keep one to fix a broken configuration. If not provided, the current setting is kept.
+    pub sso_only: Option<bool>,
+}
+
+/// Fields of an SSO configuration which are recorded in the audit log, the client secret is left out
+fn get_sso_config_audit_state(
+    config: Option<&OrganizationSsoConfig>,
+    sso_only: bool,
+) -> serde_json::Value {
+    match config {
+        Some(config) => json!({
+            "provider": config.provider,
+            "oidc_issuer_url": config.oidc_issuer_url,
+            "oidc_client_id": config.oidc_client_id,
+            "saml_idp_metadata": config.saml_idp_metadata,
+            "sso_only": sso_only,
+        }),
+        None => json!({ "sso_only": sso_only }),
+    }
+}
+
+/// Get SSO Configuration
+///
+/// Get the SSO configuration of the organization. The OIDC client secret is not returned. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    get,
+    path = "/organization/sso",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 200, description = "SSO configuration of the organization", body = OrganizationSsoConfigDTO),
+        (status = 404, description = "The organization does not have SSO configured", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_sso_config(
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let config = get_sso_config_query(organization_id, pool)
+        .await?
+        .ok_or_else(|| {
+            ServiceError::NotFound("Organization does not have SSO configured".to_string())
+        })?;
+
+    Ok(HttpResponse::Ok().json(OrganizationSsoConfigDTO::from(config)))
+}
+
+/// Set SSO Configuration
+///
+/// Create or replace the SSO configuration of the organization. Members whose email is at a domain verified by the organization are sent to its identity provider when signing in with their email. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    put,
+    path = "/organization/sso",
+    context_path = "/api",
+    tag = "Organization",
+    request_body(content = SetSsoConfigReqPayload, description = "JSON request payload to set the SSO configuration", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The SSO configuration of the organization", body = OrganizationSsoConfigDTO),
+        (status = 400, description = "The configuration is incomplete or invalid", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool, event_queue, data))]
+pub async fn set_sso_config(
+    data: web::Json<SetSsoConfigReqPayload>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization = org_with_plan_and_sub.organization;
+    if !verify_owner(&user, &organization.id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let data = data.into_inner();
+    let config = OrganizationSsoConfig::from_details(
+        organization.id,
+        data.provider,
+        data.oidc_issuer_url,
+        data.oidc_client_id,
+        data.oidc_client_secret
+            .as_deref()
+            .map(encrypt_secret)
+            .transpose()?,
+        data.saml_idp_metadata,
+    );
+    validate_sso_config(&config)?;
+
+    if data.sso_only == Some(true)
+        && !get_org_domains_query(organization.id, pool.clone())
+            .await?
+            .iter()
+            .any(|domain| domain.verified_at.is_some())
+    {
+        return Err(ServiceError::BadRequest(
+            "The organization needs a verified domain before SSO can be enforced".to_string(),
+        ));
+    }
+
+    let previous_config = get_sso_config_query(organization.id, pool.clone()).await?;
+    let config = set_sso_config_query(config, data.sso_only, pool).await?;
+
+    record_audit_event(
+        organization.id,
+        &actor,
+        AuditAction::SsoConfigUpdated,
+        organization.id.to_string(),
+        get_audit_diff(
+            get_sso_config_audit_state(previous_config.as_ref(), organization.sso_only),
+            get_sso_config_audit_state(
+                Some(&config),
+                data.sso_only.unwrap_or(organization.sso_only),
+            ),
+        ),
+        &event_queue,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(OrganizationSsoConfigDTO::from(config)))
+}
+
+/// Delete SSO Configuration
+///
+/// Delete the SSO configuration of the organization. SSO stops being enforced so members can sign in with the default provider again. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    delete,
+    path = "/organization/sso",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 204, description = "Confirmation that the SSO configuration was deleted"),
+        (status = 400, description = "Service error relating to deleting the SSO configuration", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool, event_queue))]
+pub async fn delete_sso_config(
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization = org_with_plan_and_sub.organization;
+    if !verify_owner(&user, &organization.id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let previous_config = get_sso_config_query(organization.id, pool.clone()).await?;
+    delete_sso_config_query(organization.id, pool).await?;
+
+    record_audit_event(
+        organization.id,
+        &actor,
+        AuditAction::SsoConfigDeleted,
+        organization.id.to_string(),
+        get_audit_diff(
+            get_sso_config_audit_state(previous_config.as_ref(), organization.sso_only),
+            get_sso_config_audit_state(None, false),
+        ),
+        &event_queue,
+    )
+    .await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "domain": "acme.com",
+    "default_role": 0,
+}))]
+pub struct CreateOrganizationDomainReqPayload {
+    /// Email domain to claim for the organization, e.g. acme.com
+    pub domain: String,
+    /// Role given to users who join the organization through the domain, 0 is User and 1 is Admin. Default is 0.
+    pub default_role: Option<i32>,
+}
+
+/// Create Organization Domain
+///
+/// Claim an email domain for the organization. The domain has to be verified by adding the returned verification_token as a TXT record on `_trieve-verification.<domain>` and calling verify. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    post,
+    path = "/organization/domains",
+    context_path = "/api",
+    tag = "Organization",
+    request_body(content = CreateOrganizationDomainReqPayload, description = "JSON request payload to claim a domain", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The claimed domain along with its verification token", body = OrganizationDomain),
+        (status = 400, description = "The domain is invalid or was already claimed by the organization", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn create_organization_domain(
+    data: web::Json<CreateOrganizationDomainReqPayload>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let domain = normalize_org_domain(&data.domain).ok_or_else(|| {
+        ServiceError::BadRequest(format!("{} is not a valid domain", data.domain))
+    })?;
+
+    let default_role = data.default_role.unwrap_or(UserRole::User.into());
+    if default_role != i32::from(UserRole::User) && default_role != i32::from(UserRole::Admin) {
+        return Err(ServiceError::BadRequest(
+            "default_role must be 0 (User) or 1 (Admin)".to_string(),
+        ));
+    }
+
+    let domain = create_org_domain_query(organization_id, domain, default_role, pool).await?;
+
+    Ok(HttpResponse::Ok().json(domain))
+}
+
+/// Get Organization Domains
+///
+/// Get the email domains claimed by the organization along with whether they are verified. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    get,
+    path = "/organization/domains",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 200, description = "Domains claimed by the organization", body = Vec<OrganizationDomain>),
+        (status = 400, description = "Service error relating to getting the domains", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_organization_domains(
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let domains = get_org_domains_query(organization_id, pool).await?;
+
+    Ok(HttpResponse::Ok().json(domains))
+}
+
+/// Verify Organization Domain
+///
+/// Check the TXT record of the domain and mark it as verified if it contains the verification token. Once verified, users with an email at the domain join the organization when they sign in. A domain can only be verified by one organization. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    post,
+    path = "/organization/domains/{domain_id}/verify",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 200, description = "The verified domain", body = OrganizationDomain),
+        (status = 400, description = "The TXT record was not found or the domain is verified by another organization", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+        ("domain_id" = uuid::Uuid, Path, description = "The id of the domain to verify"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool, event_queue))]
+pub async fn verify_organization_domain(
+    domain_id: web::Path<uuid::Uuid>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    let domain = verify_org_domain_query(organization_id, domain_id.into_inner(), pool).await?;
+
+    record_audit_event(
+        organization_id,
+        &actor,
+        AuditAction::DomainVerified,
+        domain.id.to_string(),
+        get_audit_diff(
+            serde_json::Value::Null,
+            json!({ "domain": domain.domain, "default_role": domain.default_role }),
+        ),
+        &event_queue,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(domain))
+}
+
+/// Delete Organization Domain
+///
+/// Release an email domain claimed by the organization. Users who already joined through it stay in the organization. Auth'ed user or api key must have an owner role for the specified organization.
+#[utoipa::path(
+    delete,
+    path = "/organization/domains/{domain_id}",
+    context_path = "/api",
+    tag = "Organization",
+    responses(
+        (status = 204, description = "Confirmation that the domain was deleted"),
+        (status = 404, description = "The domain was not found in the organization", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Organization" = uuid::Uuid, Header, description = "The organization id to use for the request"),
+        ("domain_id" = uuid::Uuid, Path, description = "The id of the domain to delete"),
+    ),
+    security(
+        ("ApiKey" = ["owner"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn delete_organization_domain(
+    domain_id: web::Path<uuid::Uuid>,
+    org_with_plan_and_sub: OrganizationWithSubAndPlan,
+    pool: web::Data<Pool>,
+    user: OwnerOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let organization_id = org_with_plan_and_sub.organization.id;
+    if !verify_owner(&user, &organization_id) {
+        return Err(ServiceError::Forbidden);
+    }
+
+    delete_org_domain_query(organization_id, domain_id.into_inner(), pool).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,9 +1,11 @@
 use crate::{
-    data::models::Pool,
+    data::models::{AuditAction, AuditActor, Pool},
     errors::ServiceError,
     get_env,
     middleware::auth_middleware::verify_owner,
     operators::{
+        audit_operator::{get_audit_diff, record_audit_event},
+        clickhouse_operator::EventQueue,
         organization_operator::{get_org_from_id_query, get_org_id_from_subscription_id_query},
         stripe_operator::{
             cancel_stripe_subscription, create_invoice_query, create_stripe_payment_link,
@@ -23,11 +25,12 @@ use utoipa::ToSchema;
 
 use super::auth_handler::OwnerOnly;
 
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn webhook(
     req: HttpRequest,
     payload: web::Bytes,
     pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let payload_str = String::from_utf8(payload.to_vec())
         .map_err(|_| ServiceError::BadRequest("Failed to parse payload".to_string()))?;
@@ -144,6 +147,10 @@ pub async fn webhook(
                                 )
                                 .await?;
 
+                            let previous_plan_id = optional_existing_subscription
+                                .as_ref()
+                                .map(|existing_subscription| existing_subscription.plan_id);
+
                             if let Some(existing_subscription) = optional_existing_subscription {
                                 let delete_subscription_pool = pool.clone();
 
@@ -155,13 +162,26 @@ pub async fn webhook(
                             }
 
                             create_stripe_subscription_query(
-                                subscription_stripe_id,
+                                subscription_stripe_id.clone(),
                                 plan_id,
                                 organization_id,
                                 pool.clone(),
                             )
                             .await?;
 
+                            record_audit_event(
+                                organization_id,
+                                &AuditActor::stripe_webhook(),
+                                AuditAction::SubscriptionCreated,
+                                subscription_stripe_id,
+                                get_audit_diff(
+                                    serde_json::json!({ "plan_id": previous_plan_id }),
+                                    serde_json::json!({ "plan_id": plan_id }),
+                                ),
+                                &event_queue,
+                            )
+                            .await;
+
                             let invoice = checkout_session.clone().invoice;
                             if invoice.is_some() {
                                 let invoice_id = invoice.unwrap().id();
@@ -193,12 +213,34 @@ pub async fn webhook(
                         "Failed to convert current_period_end to NaiveDateTime".to_string(),
                     ))?;
 
+                    let org_id = get_org_id_from_subscription_id_query(
+                        subscription_stripe_id.clone(),
+                        pool.clone(),
+                    )
+                    .await
+                    .ok();
+
                     set_stripe_subscription_current_period_end(
-                        subscription_stripe_id,
+                        subscription_stripe_id.clone(),
                         current_period_end,
                         pool,
                     )
                     .await?;
+
+                    if let Some(org_id) = org_id {
+                        record_audit_event(
+                            org_id,
+                            &AuditActor::stripe_webhook(),
+                            AuditAction::SubscriptionCanceled,
+                            subscription_stripe_id,
+                            get_audit_diff(
+                                serde_json::json!({ "current_period_end": null }),
+                                serde_json::json!({ "current_period_end": current_period_end }),
+                            ),
+                            &event_queue,
+                        )
+                        .await;
+                    }
                 }
             }
             EventType::InvoicePaid => {
@@ -301,11 +343,13 @@ pub async fn direct_to_payment_link(
         ("ApiKey" = ["owner"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn cancel_subscription(
     subscription_id: web::Path<uuid::Uuid>,
     user: OwnerOnly,
     pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
 ) -> Result<HttpResponse, actix_web::Error> {
     let get_sub_pool = pool.clone();
     let subscription =
@@ -315,7 +359,20 @@ pub async fn cancel_subscription(
         return Err(ServiceError::Forbidden.into());
     };
 
-    cancel_stripe_subscription(subscription.stripe_id).await?;
+    cancel_stripe_subscription(subscription.stripe_id.clone()).await?;
+
+    record_audit_event(
+        subscription.organization_id,
+        &actor,
+        AuditAction::SubscriptionCanceled,
+        subscription.stripe_id,
+        get_audit_diff(
+            serde_json::json!({ "plan_id": subscription.plan_id }),
+            serde_json::json!({ "plan_id": null }),
+        ),
+        &event_queue,
+    )
+    .await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -347,11 +404,13 @@ pub struct UpdateSubscriptionData {
         ("ApiKey" = ["owner"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn update_subscription_plan(
     path_data: web::Path<UpdateSubscriptionData>,
     user: OwnerOnly,
     pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
 ) -> Result<HttpResponse, actix_web::Error> {
     let get_subscription_pool = pool.clone();
     let get_plan_pool = pool.clone();
@@ -367,11 +426,24 @@ pub async fn update_subscription_plan(
     let plan_id = path_data.plan_id;
     let plan = get_plan_by_id_query(plan_id, get_plan_pool).await?;
 
-    update_stripe_subscription(subscription.stripe_id, plan.stripe_id).await?;
+    update_stripe_subscription(subscription.stripe_id.clone(), plan.stripe_id).await?;
 
     update_stripe_subscription_plan_query(subscription.id, plan.id, update_subscription_plan_pool)
         .await?;
 
+    record_audit_event(
+        subscription.organization_id,
+        &actor,
+        AuditAction::SubscriptionPlanUpdated,
+        subscription.stripe_id,
+        get_audit_diff(
+            serde_json::json!({ "plan_id": subscription.plan_id }),
+            serde_json::json!({ "plan_id": plan.id }),
+        ),
+        &event_queue,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -0,0 +1,239 @@
+use super::auth_handler::AdminOnly;
+use crate::{
+    data::models::{DatasetAndOrgWithSubAndPlan, DatasetSynonymSet, Pool, RedisPool},
+    errors::ServiceError,
+    operators::synonym_operator::{
+        create_synonym_set_query, delete_synonym_set_query, get_synonym_sets_query,
+        refresh_synonym_map, update_synonym_set_query,
+    },
+};
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "synonyms": ["tv", "television", "telly"],
+}))]
+pub struct SynonymSetReqPayload {
+    /// Terms of the synonym set. Terms are matched case-insensitively and can be multiple words, e.g. "smart tv".
+    pub synonyms: Vec<String>,
+    /// Makes the synonym set one-way. Queries containing `input` are expanded with `synonyms`, but queries containing one of the `synonyms` are not expanded with `input`. If not set, every term is expanded with all of the other terms.
+    pub input: Option<String>,
+}
+
+fn validate_synonym_set(
+    payload: SynonymSetReqPayload,
+) -> Result<(Vec<String>, Option<String>), ServiceError> {
+    let synonyms = payload
+        .synonyms
+        .into_iter()
+        .map(|term| term.trim().to_string())
+        .filter(|term| !term.is_empty())
+        .collect::<Vec<String>>();
+
+    let input = match payload.input.map(|input| input.trim().to_string()) {
+        Some(input) if input.is_empty() => {
+            return Err(ServiceError::BadRequest(
+                "input must not be empty if provided".to_string(),
+            ))
+        }
+        input => input,
+    };
+
+    let min_synonyms = if input.is_some() { 1 } else { 2 };
+    if synonyms.len() < min_synonyms {
+        return Err(ServiceError::BadRequest(format!(
+            "A synonym set needs at least {} non-empty synonyms",
+            min_synonyms
+        )));
+    }
+
+    Ok((synonyms, input))
+}
+
+/// Create Synonym Set
+///
+/// Create a set of synonyms for the dataset. Queries for full text and BM25 search are expanded with the synonyms of any term they contain and typo correction will not alter terms which are part of a synonym set. Synonyms are applied at query time only, chunks are not re-indexed with them. Changes can take up to 30 seconds to apply to searches. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    post,
+    path = "/synonym",
+    context_path = "/api",
+    tag = "Synonym",
+    request_body(content = SynonymSetReqPayload, description = "JSON request payload to create a synonym set", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The created synonym set", body = DatasetSynonymSet),
+        (status = 400, description = "Service error relating to creating the synonym set", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn create_synonym_set(
+    data: web::Json<SynonymSetReqPayload>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+    let (synonyms, input) = validate_synonym_set(data.into_inner())?;
+
+    let synonym_set = create_synonym_set_query(
+        DatasetSynonymSet::from_details(dataset_id, synonyms, input),
+        pool.clone(),
+    )
+    .await?;
+
+    refresh_synonym_map(dataset_id, pool, redis_pool).await?;
+
+    Ok(HttpResponse::Ok().json(synonym_set))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetSynonymSetsQuery {
+    /// Page number to return, 1-indexed. Default is 1.
+    pub page: Option<u64>,
+    /// Number of synonym sets to return per page. Default is 100.
+    pub page_size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct GetSynonymSetsResponse {
+    pub synonym_sets: Vec<DatasetSynonymSet>,
+    pub total_pages: i64,
+}
+
+/// Get Synonym Sets
+///
+/// Get the synonym sets of the dataset, newest first. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    get,
+    path = "/synonym",
+    context_path = "/api",
+    tag = "Synonym",
+    responses(
+        (status = 200, description = "Synonym sets of the dataset", body = GetSynonymSetsResponse),
+        (status = 400, description = "Service error relating to getting the synonym sets", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("page" = Option<u64>, Query, description = "Page number to return, 1-indexed. Default is 1."),
+        ("page_size" = Option<u64>, Query, description = "Number of synonym sets to return per page. Default is 100."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_synonym_sets(
+    query: web::Query<GetSynonymSetsQuery>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let page_size = query.page_size.unwrap_or(100).max(1);
+
+    let (synonym_sets, total_count) = get_synonym_sets_query(
+        dataset_org_plan_sub.dataset.id,
+        query.page.unwrap_or(1),
+        page_size,
+        pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(GetSynonymSetsResponse {
+        synonym_sets,
+        total_pages: (total_count as f64 / page_size as f64).ceil() as i64,
+    }))
+}
+
+/// Update Synonym Set
+///
+/// Replace the terms of a synonym set. Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    put,
+    path = "/synonym/{synonym_set_id}",
+    context_path = "/api",
+    tag = "Synonym",
+    request_body(content = SynonymSetReqPayload, description = "JSON request payload to update a synonym set", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The updated synonym set", body = DatasetSynonymSet),
+        (status = 400, description = "Service error relating to updating the synonym set", body = ErrorResponseBody),
+        (status = 404, description = "Synonym set not found", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("synonym_set_id" = uuid::Uuid, Path, description = "Id of the synonym set to update."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn update_synonym_set(
+    synonym_set_id: web::Path<uuid::Uuid>,
+    data: web::Json<SynonymSetReqPayload>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+    let (synonyms, input) = validate_synonym_set(data.into_inner())?;
+
+    let synonym_set = update_synonym_set_query(
+        synonym_set_id.into_inner(),
+        dataset_id,
+        synonyms,
+        input,
+        pool.clone(),
+    )
+    .await?;
+
+    refresh_synonym_map(dataset_id, pool, redis_pool).await?;
+
+    Ok(HttpResponse::Ok().json(synonym_set))
+}
+
+/// Delete Synonym Set
+///
+/// Auth'ed user or api key must have an admin or owner role for the specified dataset's organization.
+#[utoipa::path(
+    delete,
+    path = "/synonym/{synonym_set_id}",
+    context_path = "/api",
+    tag = "Synonym",
+    responses(
+        (status = 204, description = "Confirmation that the synonym set was deleted"),
+        (status = 400, description = "Service error relating to deleting the synonym set", body = ErrorResponseBody),
+        (status = 404, description = "Synonym set not found", body = ErrorResponseBody),
+    ),
+    params(
+        ("TR-Dataset" = uuid::Uuid, Header, description = "The dataset id or tracking_id to use for the request. We assume you intend to use an id if the value is a valid uuid."),
+        ("synonym_set_id" = uuid::Uuid, Path, description = "Id of the synonym set to delete."),
+    ),
+    security(
+        ("ApiKey" = ["admin"]),
+    )
+)]
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn delete_synonym_set(
+    synonym_set_id: web::Path<uuid::Uuid>,
+    dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+    _user: AdminOnly,
+) -> Result<HttpResponse, ServiceError> {
+    let dataset_id = dataset_org_plan_sub.dataset.id;
+
+    delete_synonym_set_query(synonym_set_id.into_inner(), dataset_id, pool.clone()).await?;
+
+    refresh_synonym_map(dataset_id, pool, redis_pool).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,10 +1,17 @@
-use super::auth_handler::LoggedUser;
+use super::auth_handler::{LoggedUser, NoApiKey};
 use crate::{
-    data::models::{OrganizationWithSubAndPlan, Pool, RedisPool, UserRole},
+    data::models::{
+        ApiKeyPermission, ApiKeyRespBody, AuditAction, AuditActor, OrganizationWithSubAndPlan,
+        Pool, RateLimits, RedisPool, UserRole,
+    },
     errors::ServiceError,
-    operators::user_operator::{
-        delete_user_api_keys_query, get_user_api_keys_query, get_user_by_id_query,
-        set_user_api_key_query, update_user_org_role_query,
+    operators::{
+        audit_operator::{get_audit_diff, record_audit_event},
+        clickhouse_operator::EventQueue,
+        user_operator::{
+            delete_user_api_keys_query, get_user_api_keys_query, get_user_by_id_query,
+            rotate_user_api_key_query, set_user_api_key_query, update_user_org_role_query,
+        },
     },
 };
 use actix_web::{web, HttpResponse};
@@ -44,13 +51,15 @@ pub struct GetUserWithChunksData {
         ("ApiKey" = ["readonly"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn update_user(
     data: web::Json<UpdateUserOrgRoleData>,
     user: LoggedUser,
     pool: web::Data<Pool>,
     org_with_plan_and_sub: OrganizationWithSubAndPlan,
     redis_pool: web::Data<RedisPool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
 ) -> Result<HttpResponse, ServiceError> {
     let update_user_data = data.into_inner();
     let org_role = user
@@ -69,6 +78,8 @@ pub async fn update_user(
         ));
     }
 
+    let mut role_before = org_role;
+
     if let Some(user_id) = update_user_data.user_id {
         if org_role < 1 {
             return Err(ServiceError::BadRequest(
@@ -78,22 +89,24 @@ pub async fn update_user(
 
         let user_info = get_user_by_id_query(&user_id, pool.clone()).await?;
 
-        let already_in_org = user_info
+        let user_org = user_info
             .1
             .iter()
-            .any(|org| org.organization_id == org_with_plan_and_sub.organization.id);
+            .find(|org| org.organization_id == org_with_plan_and_sub.organization.id);
 
-        if !already_in_org {
-            return Err(ServiceError::BadRequest(
+        match user_org {
+            Some(user_org) => role_before = user_org.role,
+            None => return Err(ServiceError::BadRequest(
                 "The user who you would like to update the role of must be added to the specified org first before their role can be updated".to_string(),
-            ));
+            )),
         }
     }
 
     let user_role = UserRole::from(update_user_data.role);
+    let target_user_id = update_user_data.user_id.unwrap_or(user.id);
 
     update_user_org_role_query(
-        update_user_data.user_id.unwrap_or(user.id),
+        target_user_id,
         org_with_plan_and_sub.organization.id,
         user_role,
         pool,
@@ -101,6 +114,19 @@ pub async fn update_user(
     )
     .await?;
 
+    record_audit_event(
+        org_with_plan_and_sub.organization.id,
+        &actor,
+        AuditAction::UserRoleUpdated,
+        target_user_id.to_string(),
+        get_audit_diff(
+            serde_json::json!({ "role": role_before }),
+            serde_json::json!({ "role": update_user_data.role }),
+        ),
+        &event_queue,
+    )
+    .await;
+
     Ok(HttpResponse::NoContent().finish())
 }
 
@@ -114,8 +140,16 @@ pub struct SetUserApiKeyRequest {
     pub dataset_ids: Option<Vec<uuid::Uuid>>,
     /// The organization ids which the api key will have access to. If not provided or empty, the api key will have access to all organizations the auth'ed user has access to.
     pub organization_ids: Option<Vec<uuid::Uuid>>,
-    /// The routes which the api key will have access to. If not provided or empty, the api key will have access to all routes the auth'ed user has access to. Specify the routes as a list of strings. For example, ["GET /api/dataset", "POST /api/dataset"].
+    /// Deprecated, use `permissions` instead. Routes the api key is restricted to, given as strings like "GET /api/dataset". Matching a route no longer grants any role beyond the key's own.
     pub scopes: Option<Vec<String>>,
+    /// The permissions which will be granted to the api key. If not provided or empty, the api key can do everything its role allows. Possible values are "chunk:read", "chunk:write", "group:write", "rag:create" and "analytics:read".
+    pub permissions: Option<Vec<ApiKeyPermission>>,
+    /// Time after which the api key stops working. If not provided, the api key never expires.
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    /// CIDR blocks, i.e. "10.0.0.0/8" or "2001:db8::/32", which requests using the api key must come from. If not provided or empty, requests from any address are allowed.
+    pub allowed_cidrs: Option<Vec<String>>,
+    /// Requests per minute the api key is allowed to make to search, ingest and RAG routes, counted on top of the limits of the dataset. If not provided, only the dataset's limits apply.
+    pub rate_limits: Option<RateLimits>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -126,7 +160,7 @@ pub struct SetUserApiKeyResponse {
 
 /// Set User Api Key
 ///
-/// Create a new api key for the auth'ed user. Successful response will contain the newly created api key. If a write role is assigned the api key will have permission level of the auth'ed user who calls this endpoint.
+/// Create a new api key for the auth'ed user. Successful response will contain the newly created api key. If a write role is assigned the api key will have permission level of the auth'ed user who calls this endpoint. Api keys can not be used to create api keys, the user must be signed in.
 #[utoipa::path(
     post,
     path = "/user/api_key",
@@ -136,30 +170,37 @@ pub struct SetUserApiKeyResponse {
     responses(
         (status = 200, description = "JSON body representing the api_key for the user", body = SetUserApiKeyResponse),
         (status = 400, description = "Service error relating to creating api_key for the user", body = ErrorResponseBody),
+        (status = 403, description = "The request was authenticated with an api key", body = ErrorResponseBody),
     ),
     security(
         ("ApiKey" = ["readonly"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn set_user_api_key(
     user: LoggedUser,
+    _no_api_key: NoApiKey,
     data: web::Json<SetUserApiKeyRequest>,
     pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let role = data.role;
+    let (new_api_key, api_key) = set_user_api_key_query(user.id, data.into_inner(), pool)
+        .await
+        .map_err(|err| match err {
+            ServiceError::BadRequest(_) => err,
+            _ => ServiceError::BadRequest("Failed to set new API key for user".into()),
+        })?;
 
-    let new_api_key = set_user_api_key_query(
-        user.id,
-        data.name.clone(),
-        role.into(),
-        data.dataset_ids.clone(),
-        data.organization_ids.clone(),
-        data.scopes.clone(),
-        pool,
+    record_api_key_audit_events(
+        &user,
+        &api_key,
+        &actor,
+        AuditAction::ApiKeyCreated,
+        get_audit_diff(serde_json::Value::Null, serde_json::json!(api_key)),
+        &event_queue,
     )
-    .await
-    .map_err(|_err| ServiceError::BadRequest("Failed to set new API key for user".into()))?;
+    .await;
 
     Ok(HttpResponse::Ok().json(SetUserApiKeyResponse {
         api_key: new_api_key,
@@ -219,15 +260,154 @@ pub struct DeleteUserApiKeyRequest {
         ("ApiKey" = ["readonly"]),
     )
 )]
-#[tracing::instrument(skip(pool))]
+#[tracing::instrument(skip(pool, event_queue))]
 pub async fn delete_user_api_key(
     user: LoggedUser,
     data: web::Path<uuid::Uuid>,
     pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
 ) -> Result<HttpResponse, actix_web::Error> {
-    delete_user_api_keys_query(user.id, data.into_inner(), pool)
+    let api_key_id = data.into_inner();
+    let api_key = get_user_api_keys_query(user.id, pool.clone())
+        .await?
+        .into_iter()
+        .find(|user_api_key| user_api_key.id == api_key_id);
+
+    delete_user_api_keys_query(user.id, api_key_id, pool)
         .await
         .map_err(|_err| ServiceError::BadRequest("Failed to get API keys for user".into()))?;
 
+    if let Some(api_key) = api_key {
+        record_api_key_audit_events(
+            &user,
+            &api_key,
+            &actor,
+            AuditAction::ApiKeyDeleted,
+            get_audit_diff(serde_json::json!(api_key), serde_json::Value::Null),
+            &event_queue,
+        )
+        .await;
+    }
+
     Ok(HttpResponse::NoContent().finish())
 }
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RotateUserApiKeyRequest {
+    /// Number of seconds the current secret keeps working after the rotation such that it can be replaced without downtime. Default is 86400 (1 day), the maximum is 2592000 (30 days). Use 0 to revoke the current secret immediately.
+    pub overlap_seconds: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RotateUserApiKeyResponse {
+    /// The new secret of the api key. This is the value which should be used in the Authorization header.
+    api_key: String,
+    /// Time until which the previous secret is still accepted.
+    previous_key_expires_at: chrono::NaiveDateTime,
+}
+
+/// Rotate User Api Key
+///
+/// Issue a new secret for an api key of the auth'ed user while keeping its name, role and permissions. The previous secret keeps working until the end of the overlap window. Api keys can not be used to rotate api keys, the user must be signed in.
+#[utoipa::path(
+    post,
+    path = "/user/api_key/{api_key_id}/rotate",
+    context_path = "/api",
+    tag = "User",
+    request_body(content = RotateUserApiKeyRequest, description = "JSON request payload to rotate the api key", content_type = "application/json"),
+    responses(
+        (status = 200, description = "JSON body representing the new secret of the api key", body = RotateUserApiKeyResponse),
+        (status = 400, description = "Service error relating to rotating the api key", body = ErrorResponseBody),
+        (status = 403, description = "The request was authenticated with an api key", body = ErrorResponseBody),
+        (status = 404, description = "Api key not found", body = ErrorResponseBody),
+    ),
+    params(
+        ("api_key_id" = uuid::Uuid, Path, description = "The id of the api key to rotate"),
+    ),
+    security(
+        ("ApiKey" = ["readonly"]),
+    )
+)]
+#[tracing::instrument(skip(pool, event_queue))]
+pub async fn rotate_user_api_key(
+    user: LoggedUser,
+    _no_api_key: NoApiKey,
+    api_key_id: web::Path<uuid::Uuid>,
+    data: web::Json<RotateUserApiKeyRequest>,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+    actor: AuditActor,
+) -> Result<HttpResponse, actix_web::Error> {
+    let overlap_seconds = data.overlap_seconds.unwrap_or(86400).min(2592000);
+    let api_key_id = api_key_id.into_inner();
+
+    let (api_key, previous_key_expires_at) = rotate_user_api_key_query(
+        user.id,
+        api_key_id,
+        chrono::Duration::seconds(overlap_seconds as i64),
+        pool.clone(),
+    )
+    .await?;
+
+    if let Some(rotated_api_key) = get_user_api_keys_query(user.id, pool)
+        .await?
+        .into_iter()
+        .find(|user_api_key| user_api_key.id == api_key_id)
+    {
+        record_api_key_audit_events(
+            &user,
+            &rotated_api_key,
+            &actor,
+            AuditAction::ApiKeyRotated,
+            serde_json::json!({
+                "previous_key_expires_at": { "before": null, "after": previous_key_expires_at }
+            }),
+            &event_queue,
+        )
+        .await;
+    }
+
+    Ok(HttpResponse::Ok().json(RotateUserApiKeyResponse {
+        api_key,
+        previous_key_expires_at,
+    }))
+}
+
+/// Records the event in the audit log of every organization the api key can access
+async fn record_api_key_audit_events(
+    user: &LoggedUser,
+    api_key: &ApiKeyRespBody,
+    actor: &AuditActor,
+    action: AuditAction,
+    diff: serde_json::Value,
+    event_queue: &EventQueue,
+) {
+    let organization_ids = match api_key
+        .organization_ids
+        .as_ref()
+        .filter(|organization_ids| !organization_ids.is_empty())
+    {
+        Some(organization_ids) => organization_ids
+            .iter()
+            .filter_map(|organization_id| organization_id.parse::<uuid::Uuid>().ok())
+            .collect::<Vec<uuid::Uuid>>(),
+        None => user
+            .user_orgs
+            .iter()
+            .map(|user_org| user_org.organization_id)
+            .collect(),
+    };
+
+    for organization_id in organization_ids {
+        record_audit_event(
+            organization_id,
+            actor,
+            action,
+            api_key.id.to_string(),
+            diff.clone(),
+            event_queue,
+        )
+        .await;
+    }
+}
@@ -4,6 +4,9 @@ use crate::data::models::Pool;
 use crate::data::models::RedisPool;
 use crate::data::models::UnifiedId;
 use crate::middleware::auth_middleware::verify_member;
+use crate::operators::crawl_operator::{
+    enqueue_crawl_request, get_crawl_request_by_dataset_id_query,
+};
 use crate::operators::dataset_operator::get_dataset_and_organization_from_dataset_id_query;
 use crate::operators::user_operator::get_user_from_api_key_query;
 use crate::operators::webhook_operator::delete_content;
@@ -153,3 +156,54 @@ pub async fn builder_io_webhook(
         message: "Webhook received".to_string(),
     }))
 }
+
+/// Queues a sync of the dataset's connector (git repo, S3 prefix, RSS feed or sitemap) so pushes and uploads are picked up without waiting for the next scheduled crawl
+pub async fn connector_sync_webhook(
+    query: web::Query<WebhookQueryParams>,
+    redis_pool: web::Data<RedisPool>,
+    pool: web::Data<Pool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if query.trieve_key.is_empty() || query.trieve_dataset.is_empty() {
+        return Err(ServiceError::BadRequest(
+            "trieve_key and trieve_dataset are required".to_string(),
+        )
+        .into());
+    }
+
+    let query = query.into_inner();
+    let dataset_id = uuid::Uuid::from_str(query.trieve_dataset.as_str()).map_err(|_| {
+        ServiceError::BadRequest(format!("Invalid dataset id: {}", query.trieve_dataset))
+    })?;
+
+    let dataset_and_org = get_dataset_and_organization_from_dataset_id_query(
+        UnifiedId::TrieveUuid(dataset_id),
+        None,
+        pool.clone(),
+    )
+    .await?;
+
+    let (user, _) = get_user_from_api_key_query(&query.trieve_key, pool.clone()).await?;
+
+    if !verify_member(&user, &dataset_and_org.organization.organization.id) {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let crawl_request = get_crawl_request_by_dataset_id_query(dataset_id, pool)
+        .await?
+        .ok_or(ServiceError::NotFound(
+            "No crawl configured for this dataset".to_string(),
+        ))?;
+
+    if !crawl_request.crawl_options.is_connector() {
+        return Err(ServiceError::BadRequest(
+            "The dataset's crawl is not a sync connector".to_string(),
+        )
+        .into());
+    }
+
+    enqueue_crawl_request(crawl_request, redis_pool).await?;
+
+    Ok(HttpResponse::Ok().json(WebhookRespose {
+        message: "Sync queued".to_string(),
+    }))
+}
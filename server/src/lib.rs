@@ -160,12 +160,33 @@ impl Modify for SecurityAddon {
         handlers::auth_handler::logout,
         handlers::auth_handler::get_me,
         handlers::auth_handler::callback,
+        handlers::sso_handler::sso_callback,
+        handlers::sso_handler::saml_acs,
+        handlers::sso_handler::get_saml_metadata,
+        handlers::sso_handler::get_sso_config,
+        handlers::sso_handler::set_sso_config,
+        handlers::sso_handler::delete_sso_config,
+        handlers::sso_handler::create_organization_domain,
+        handlers::sso_handler::get_organization_domains,
+        handlers::sso_handler::verify_organization_domain,
+        handlers::sso_handler::delete_organization_domain,
+        handlers::scim_handler::create_scim_token,
+        handlers::scim_handler::get_scim_token,
+        handlers::scim_handler::delete_scim_token,
         handlers::auth_handler::health_check,
         handlers::topic_handler::create_topic,
         handlers::topic_handler::delete_topic,
         handlers::topic_handler::update_topic,
         handlers::topic_handler::clone_topic,
         handlers::topic_handler::get_all_topics_for_owner_id,
+        handlers::synonym_handler::create_synonym_set,
+        handlers::synonym_handler::get_synonym_sets,
+        handlers::synonym_handler::update_synonym_set,
+        handlers::synonym_handler::delete_synonym_set,
+        handlers::merchandising_handler::create_merchandising_rule,
+        handlers::merchandising_handler::get_merchandising_rules,
+        handlers::merchandising_handler::update_merchandising_rule,
+        handlers::merchandising_handler::delete_merchandising_rule,
         handlers::message_handler::create_message,
         handlers::message_handler::get_all_topic_messages,
         handlers::message_handler::edit_message,
@@ -188,10 +209,14 @@ impl Modify for SecurityAddon {
         handlers::chunk_handler::get_chunks_by_ids,
         handlers::chunk_handler::scroll_dataset_chunks,
         handlers::chunk_handler::bulk_delete_chunk,
+        handlers::chunk_version_handler::get_chunk_versions,
+        handlers::chunk_version_handler::diff_chunk_versions,
+        handlers::chunk_version_handler::restore_chunk_version,
         handlers::dataset_handler::get_all_tags,
         handlers::user_handler::update_user,
         handlers::user_handler::set_user_api_key,
         handlers::user_handler::delete_user_api_key,
+        handlers::user_handler::rotate_user_api_key,
         handlers::group_handler::search_over_groups,
         handlers::group_handler::get_recommended_groups,
         handlers::group_handler::get_groups_for_dataset,
@@ -221,6 +246,8 @@ impl Modify for SecurityAddon {
         handlers::organization_handler::get_organization_usage,
         handlers::organization_handler::get_organization_users,
         handlers::organization_handler::update_all_org_dataset_configs,
+        handlers::organization_handler::get_audit_log,
+        handlers::organization_handler::export_audit_log,
         handlers::dataset_handler::create_dataset,
         handlers::dataset_handler::update_dataset,
         handlers::dataset_handler::delete_dataset,
@@ -228,9 +255,15 @@ impl Modify for SecurityAddon {
         handlers::dataset_handler::get_dataset,
         handlers::dataset_handler::get_dataset_by_tracking_id,
         handlers::dataset_handler::get_dataset_crawl_options,
+        handlers::crawl_run_handler::get_crawl_runs,
+        handlers::crawl_run_handler::get_crawl_run_pages,
+        handlers::crawl_run_handler::diff_crawl_runs,
         handlers::dataset_handler::get_usage_by_dataset_id,
         handlers::dataset_handler::get_datasets_from_organization,
         handlers::dataset_handler::clear_dataset,
+        handlers::dataset_handler::export_dataset,
+        handlers::dataset_handler::get_dataset_export,
+        handlers::dataset_handler::import_dataset,
         handlers::stripe_handler::direct_to_payment_link,
         handlers::stripe_handler::cancel_subscription,
         handlers::stripe_handler::update_subscription_plan,
@@ -254,10 +287,24 @@ impl Modify for SecurityAddon {
     components(
         schemas(
             handlers::auth_handler::AuthQuery,
+            handlers::sso_handler::SetSsoConfigReqPayload,
+            handlers::sso_handler::CreateOrganizationDomainReqPayload,
+            handlers::scim_handler::CreateScimTokenReqPayload,
+            handlers::scim_handler::CreateScimTokenResponse,
             handlers::topic_handler::CreateTopicReqPayload,
             handlers::topic_handler::CloneTopicReqPayload,
             handlers::topic_handler::DeleteTopicData,
             handlers::topic_handler::UpdateTopicReqPayload,
+            handlers::synonym_handler::SynonymSetReqPayload,
+            handlers::synonym_handler::GetSynonymSetsQuery,
+            handlers::synonym_handler::GetSynonymSetsResponse,
+            data::models::DatasetSynonymSet,
+            handlers::merchandising_handler::MerchandisingRuleReqPayload,
+            handlers::merchandising_handler::GetMerchandisingRulesQuery,
+            handlers::merchandising_handler::GetMerchandisingRulesResponse,
+            data::models::MerchandisingRule,
+            data::models::MerchandisingAction,
+            data::models::MerchandisingQueryMatch,
             handlers::message_handler::CreateMessageReqPayload,
             handlers::message_handler::RegenerateMessageReqPayload,
             handlers::message_handler::EditMessageReqPayload,
@@ -296,10 +343,25 @@ impl Modify for SecurityAddon {
             handlers::chunk_handler::ScrollChunksReqPayload,
             handlers::chunk_handler::ScrollChunksResponseBody,
             handlers::chunk_handler::V1RecommendChunksResponseBody,
+            handlers::chunk_version_handler::GetChunkVersionsQuery,
+            handlers::chunk_version_handler::GetChunkVersionsResponse,
+            handlers::chunk_version_handler::DiffChunkVersionsQuery,
+            handlers::chunk_version_handler::DiffChunkVersionsResponse,
+            operators::chunk_version_operator::ChunkVersionFieldDiff,
+            data::models::ChunkMetadataVersion,
+            data::models::ChunkVersionAction,
+            data::models::ChunkVersionActor,
             handlers::dataset_handler::TagsWithCount,
             handlers::dataset_handler::GetAllTagsReqPayload,
             handlers::dataset_handler::GetAllTagsResponse,
             handlers::dataset_handler::GetCrawlOptionsResponse,
+            handlers::crawl_run_handler::GetCrawlRunsQuery,
+            handlers::crawl_run_handler::GetCrawlRunsResponse,
+            handlers::crawl_run_handler::GetCrawlPagesQuery,
+            handlers::crawl_run_handler::GetCrawlPagesResponse,
+            handlers::crawl_run_handler::DiffCrawlRunsQuery,
+            handlers::crawl_run_handler::DiffCrawlRunsResponse,
+            operators::crawl_run_operator::CrawlRunDiff,
             handlers::group_handler::RecommendGroupsReqPayload,
             handlers::group_handler::RecommendGroupsResponse,
             handlers::group_handler::SearchWithinGroupReqPayload,
@@ -326,16 +388,22 @@ impl Modify for SecurityAddon {
             handlers::group_handler::RecommendGroupsResponseBody,
             handlers::user_handler::UpdateUserOrgRoleData,
             handlers::user_handler::SetUserApiKeyRequest,
+            handlers::user_handler::RotateUserApiKeyRequest,
+            handlers::user_handler::RotateUserApiKeyResponse,
             handlers::user_handler::SetUserApiKeyResponse,
             handlers::user_handler::DeleteUserApiKeyRequest,
             operators::group_operator::GroupsForChunk,
             handlers::file_handler::UploadFileReqPayload,
+            handlers::file_handler::ChunkingStrategy,
             handlers::file_handler::UploadFileResult,
             handlers::invitation_handler::InvitationData,
             handlers::event_handler::GetEventsData,
             handlers::organization_handler::CreateOrganizationReqPayload,
             handlers::organization_handler::UpdateOrganizationReqPayload,
             handlers::organization_handler::UpdateAllOrgDatasetConfigsReqPayload,
+            handlers::organization_handler::GetAuditLogReqPayload,
+            handlers::organization_handler::GetAuditLogResponse,
+            handlers::organization_handler::ExportAuditLogReqPayload,
             operators::event_operator::EventReturn,
             operators::search_operator::DeprecatedSearchOverGroupsResponseBody,
             operators::search_operator::GroupScoreChunk,
@@ -345,11 +413,25 @@ impl Modify for SecurityAddon {
             handlers::dataset_handler::CreateDatasetRequest,
             handlers::dataset_handler::UpdateDatasetRequest,
             handlers::dataset_handler::GetDatasetsPagination,
+            handlers::dataset_handler::ImportDatasetReqPayload,
+            handlers::dataset_handler::ImportDatasetResponse,
+            handlers::dataset_handler::ExportDatasetQuery,
+            handlers::dataset_handler::ExportDatasetResponse,
+            handlers::dataset_handler::GetDatasetExportResponse,
             data::models::DatasetConfigurationDTO,
+            data::models::DatasetArchiveManifest,
             handlers::chunk_handler::CrawlOpenAPIOptions,
             handlers::chunk_handler::CrawlInterval,
             data::models::ScrapeOptions,
             data::models::CrawlShopifyOptions,
+            data::models::CrawlGitOptions,
+            data::models::CrawlS3Options,
+            data::models::CrawlRssOptions,
+            data::models::CrawlSitemapOptions,
+            data::models::CrawlRun,
+            data::models::CrawlRunStatus,
+            data::models::CrawlPage,
+            data::models::CrawlPageStatus,
             handlers::analytics_handler::GetTopDatasetsRequestBody,
             handlers::analytics_handler::CTRDataRequestBody,
             data::models::CTRType,
@@ -392,6 +474,13 @@ impl Modify for SecurityAddon {
             data::models::SearchType,
             data::models::SuggestType,
             data::models::ApiKeyRespBody,
+            data::models::ApiKeyPermission,
+            data::models::RateLimits,
+            data::models::RateLimitRouteClass,
+            data::models::AuditEvent,
+            data::models::AuditAction,
+            data::models::AuditAuthMethod,
+            data::models::AuditLogFilter,
             data::models::UsageGraphPoint,
             data::models::SearchResultType,
             data::models::RoleProxy,
@@ -445,6 +534,12 @@ impl Modify for SecurityAddon {
             data::models::SortOptions,
             data::models::ContextOptions,
             data::models::LLMOptions,
+            data::models::AgentOptions,
+            data::models::RagStreamFormat,
+            data::models::MessageCitation,
+            operators::message_operator::RagStreamEvent,
+            operators::message_operator::RagRetrievedChunk,
+            operators::message_operator::RagUsage,
             data::models::ImageConfig,
             data::models::HighlightOptions,
             data::models::TypoOptions,
@@ -453,6 +548,18 @@ impl Modify for SecurityAddon {
             data::models::ChunkWithPosition,
             data::models::SortBySearchType,
             data::models::ReRankOptions,
+            data::models::HybridSearchOptions,
+            data::models::HybridFusionStrategy,
+            data::models::HybridLeg,
+            data::models::FacetOptions,
+            data::models::TermsFacet,
+            data::models::NumValueRange,
+            data::models::DateHistogramInterval,
+            data::models::FacetResults,
+            data::models::TermsFacetResult,
+            data::models::FacetValueCount,
+            data::models::NumValueRangeCount,
+            data::models::DateHistogramBucket,
             data::models::Topic,
             data::models::Message,
             data::models::ChunkMetadata,
@@ -483,6 +590,8 @@ impl Modify for SecurityAddon {
             data::models::GeoInfo,
             data::models::CrawlOptions,
             data::models::GeoInfoWithBias,
+            data::models::RecencyBias,
+            data::models::RecencyDecayFunction,
             data::models::GeoTypes,
             data::models::ChunkMetadataWithPosition,
             data::models::ScoreChunkDTO,
@@ -492,8 +601,15 @@ impl Modify for SecurityAddon {
             data::models::ConditionType,
             data::models::HasIDCondition,
             data::models::DistanceMetric,
+            data::models::EmbeddingProviderType,
+            data::models::DatasetLanguage,
+            data::models::SparseVector,
             data::models::PublicDatasetOptions,
             data::models::Invitation,
+            data::models::SsoProvider,
+            data::models::OrganizationSsoConfigDTO,
+            data::models::OrganizationDomain,
+            data::models::OrganizationScimTokenDTO,
             errors::ErrorResponseBody,
             middleware::api_version::APIVersion,
         )
@@ -508,6 +624,8 @@ impl Modify for SecurityAddon {
         (name = "Chunk Group", description = "Chunk groups endpoint. Think of a chunk_group as a bookmark folder within the dataset."),
         (name = "File", description = "File endpoint. When files are uploaded, they are stored in S3 and broken up into chunks with text extraction from Apache Tika. You can upload files of pretty much any type up to 1GB in size. See chunking algorithm details at `docs.trieve.ai` for more information on how chunking works. Improved default chunking is on our roadmap."),
         (name = "Events", description = "Notifications endpoint. Files are uploaded asynchronously and events are sent to the user when the upload is complete."),
+        (name = "Synonym", description = "Synonym endpoint. Synonym sets expand full text and BM25 search queries with equivalent terms, such as brand names or SKU aliases, and are never altered by typo correction."),
+        (name = "Merchandising", description = "Merchandising endpoint. Merchandising rules pin, bury, hide or boost chunks in the results of chunk searches whose query matches a pattern, optionally within a date window."),
         (name = "Topic", description = "Topic chat endpoint. Think of topics as the storage system for gen-ai chat memory. Gen AI messages belong to topics."),
         (name = "Message", description = "Message chat endpoint. Messages are units belonging to a topic in the context of a chat with a LLM. There are system, user, and assistant messages."),
         (name = "Stripe", description = "Stripe endpoint. Used for the managed SaaS version of this app. Eventually this will become a micro-service. Reach out to the team using contact info found at `docs.trieve.ai` for more information."),
@@ -705,6 +823,7 @@ pub fn main() -> std::io::Result<()> {
                 .app_data(web::Data::new(metrics.clone()))
                 .wrap(sentry_actix::Sentry::new())
                 .wrap(middleware::api_version::ApiVersionCheckFactory)
+                .wrap(middleware::rate_limit_middleware::RateLimitMiddlewareFactory)
                 .wrap(middleware::auth_middleware::AuthMiddlewareFactory)
                 .wrap(
                     IdentityMiddleware::builder()
@@ -764,10 +883,46 @@ pub fn main() -> std::io::Result<()> {
                     web::resource("/builder-webhook")
                     .route(web::post().to(handlers::webhook_handler::builder_io_webhook))
                 )
+                .service(
+                    web::resource("/connector-webhook")
+                    .route(web::post().to(handlers::webhook_handler::connector_sync_webhook))
+                )
                 .service(
                     web::resource("/public_page")
                         .route(web::get().to(handlers::page_handler::public_page))
                 )
+                // SCIM 2.0 provisioning, authenticated with the organization's SCIM token
+                .service(
+                    web::scope("/scim/v2")
+                        .service(
+                            web::resource("/ServiceProviderConfig")
+                                .route(web::get().to(handlers::scim_handler::get_scim_service_provider_config)),
+                        )
+                        .service(
+                            web::resource("/Users")
+                                .route(web::get().to(handlers::scim_handler::get_scim_users))
+                                .route(web::post().to(handlers::scim_handler::create_scim_user)),
+                        )
+                        .service(
+                            web::resource("/Users/{user_id}")
+                                .route(web::get().to(handlers::scim_handler::get_scim_user_by_id))
+                                .route(web::put().to(handlers::scim_handler::replace_scim_user))
+                                .route(web::patch().to(handlers::scim_handler::update_scim_user))
+                                .route(web::delete().to(handlers::scim_handler::delete_scim_user)),
+                        )
+                        .service(
+                            web::resource("/Groups")
+                                .route(web::get().to(handlers::scim_handler::get_scim_groups))
+                                .route(web::post().to(handlers::scim_handler::create_or_delete_scim_group)),
+                        )
+                        .service(
+                            web::resource("/Groups/{group_id}")
+                                .route(web::get().to(handlers::scim_handler::get_scim_group_by_id))
+                                .route(web::put().to(handlers::scim_handler::replace_scim_group))
+                                .route(web::patch().to(handlers::scim_handler::update_scim_group))
+                                .route(web::delete().to(handlers::scim_handler::create_or_delete_scim_group)),
+                        )
+                )
                 // everything under '/api/' route
                 .service(
                     web::scope("/api")
@@ -809,6 +964,18 @@ pub fn main() -> std::io::Result<()> {
                                     web::resource("/crawl_options/{dataset_id}")
                                         .route(web::get().to(handlers::dataset_handler::get_dataset_crawl_options)),
                                 )
+                                .service(
+                                    web::resource("/crawl_runs")
+                                        .route(web::get().to(handlers::crawl_run_handler::get_crawl_runs)),
+                                )
+                                .service(
+                                    web::resource("/crawl_runs/{run_id}/pages")
+                                        .route(web::get().to(handlers::crawl_run_handler::get_crawl_run_pages)),
+                                )
+                                .service(
+                                    web::resource("/crawl_runs/{run_id}/diff")
+                                        .route(web::get().to(handlers::crawl_run_handler::diff_crawl_runs)),
+                                )
                                 .service(
                                     web::resource("/get_all_tags")
                                         .route(web::post().to(handlers::dataset_handler::get_all_tags)),
@@ -817,6 +984,18 @@ pub fn main() -> std::io::Result<()> {
                                     web::resource("/events")
                                         .route(web::post().to(handlers::event_handler::get_events)),
                                 )
+                                .service(
+                                    web::resource("/import")
+                                        .route(web::post().to(handlers::dataset_handler::import_dataset)),
+                                )
+                                .service(
+                                    web::resource("/{dataset_id}/export")
+                                        .route(web::post().to(handlers::dataset_handler::export_dataset)),
+                                )
+                                .service(
+                                    web::resource("/{dataset_id}/export/{export_id}")
+                                        .route(web::get().to(handlers::dataset_handler::get_dataset_export)),
+                                )
                                 .service(
                                     web::resource("/{dataset_id}")
                                         .route(web::get().to(handlers::dataset_handler::get_dataset))
@@ -860,8 +1039,40 @@ pub fn main() -> std::io::Result<()> {
                                 .service(
                                     web::resource("/callback")
                                         .route(web::get().to(handlers::auth_handler::callback)),
+                                )
+                                .service(
+                                    web::resource("/sso/callback")
+                                        .route(web::get().to(handlers::sso_handler::sso_callback)),
+                                )
+                                .service(
+                                    web::resource("/sso/saml/{organization_id}/acs")
+                                        .route(web::post().to(handlers::sso_handler::saml_acs)),
+                                )
+                                .service(
+                                    web::resource("/sso/saml/{organization_id}/metadata")
+                                        .route(web::get().to(handlers::sso_handler::get_saml_metadata)),
                                 ),
                         )
+                        .service(
+                            web::resource("/synonym")
+                                .route(web::post().to(handlers::synonym_handler::create_synonym_set))
+                                .route(web::get().to(handlers::synonym_handler::get_synonym_sets)),
+                        )
+                        .service(
+                            web::resource("/synonym/{synonym_set_id}")
+                                .route(web::put().to(handlers::synonym_handler::update_synonym_set))
+                                .route(web::delete().to(handlers::synonym_handler::delete_synonym_set)),
+                        )
+                        .service(
+                            web::resource("/merchandising_rule")
+                                .route(web::post().to(handlers::merchandising_handler::create_merchandising_rule))
+                                .route(web::get().to(handlers::merchandising_handler::get_merchandising_rules)),
+                        )
+                        .service(
+                            web::resource("/merchandising_rule/{rule_id}")
+                                .route(web::put().to(handlers::merchandising_handler::update_merchandising_rule))
+                                .route(web::delete().to(handlers::merchandising_handler::delete_merchandising_rule)),
+                        )
                         .service(
                             web::resource("/topic")
                                 .route(web::post().to(handlers::topic_handler::create_topic))
@@ -956,6 +1167,15 @@ pub fn main() -> std::io::Result<()> {
                                             ),
                                         ),
                                 )
+                                .service(web::resource("/{chunk_id}/versions").route(
+                                    web::get().to(handlers::chunk_version_handler::get_chunk_versions),
+                                ))
+                                .service(web::resource("/{chunk_id}/versions/diff").route(
+                                    web::get().to(handlers::chunk_version_handler::diff_chunk_versions),
+                                ))
+                                .service(web::resource("/{chunk_id}/versions/{version_id}/restore").route(
+                                    web::post().to(handlers::chunk_version_handler::restore_chunk_version),
+                                ))
                         )
                         .service(
                             web::scope("/user")
@@ -974,6 +1194,10 @@ pub fn main() -> std::io::Result<()> {
                                             web::delete().to(handlers::user_handler::delete_user_api_key),
                                         ),
                                 )
+                                .service(
+                                    web::resource("/api_key/{api_key_id}/rotate")
+                                        .route(web::post().to(handlers::user_handler::rotate_user_api_key)),
+                                )
                         )
                         .service(
                             web::scope("/chunk_group")
@@ -1105,6 +1329,39 @@ pub fn main() -> std::io::Result<()> {
                                     web::resource("/update_dataset_configs")
                                         .route(web::post().to(handlers::organization_handler::update_all_org_dataset_configs)),
                                 )
+                                .service(
+                                    web::resource("/sso")
+                                        .route(web::get().to(handlers::sso_handler::get_sso_config))
+                                        .route(web::put().to(handlers::sso_handler::set_sso_config))
+                                        .route(web::delete().to(handlers::sso_handler::delete_sso_config)),
+                                )
+                                .service(
+                                    web::resource("/domains")
+                                        .route(web::post().to(handlers::sso_handler::create_organization_domain))
+                                        .route(web::get().to(handlers::sso_handler::get_organization_domains)),
+                                )
+                                .service(
+                                    web::resource("/domains/{domain_id}")
+                                        .route(web::delete().to(handlers::sso_handler::delete_organization_domain)),
+                                )
+                                .service(
+                                    web::resource("/domains/{domain_id}/verify")
+                                        .route(web::post().to(handlers::sso_handler::verify_organization_domain)),
+                                )
+                                .service(
+                                    web::resource("/scim_token")
+                                        .route(web::post().to(handlers::scim_handler::create_scim_token))
+                                        .route(web::get().to(handlers::scim_handler::get_scim_token))
+                                        .route(web::delete().to(handlers::scim_handler::delete_scim_token)),
+                                )
+                                .service(
+                                    web::resource("/audit_log")
+                                        .route(web::post().to(handlers::organization_handler::get_audit_log)),
+                                )
+                                .service(
+                                    web::resource("/audit_log/export")
+                                        .route(web::post().to(handlers::organization_handler::export_audit_log)),
+                                )
                                 .service(
                                     web::resource("/{organization_id}/user/{user_id}")
                                         .route(web::delete().to(handlers::organization_handler::remove_user_from_org)),
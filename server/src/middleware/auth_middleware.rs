@@ -1,27 +1,36 @@
 use crate::{
     data::models::{Pool, RedisPool, SlimUser, UnifiedId, User, UserApiKey, UserRole},
     errors::ServiceError,
-    handlers::auth_handler::{AdminOnly, LoggedUser, OrganizationRole, OwnerOnly},
+    handlers::{
+        auth_handler::{AdminOnly, LoggedUser, OrganizationRole, OwnerOnly},
+        sso_handler::SSO_ORGANIZATION_SESSION_KEY,
+    },
     operators::{
         dataset_operator::get_dataset_and_organization_from_dataset_id_query,
         organization_operator::{
             get_arbitrary_org_owner_from_dataset_id, get_arbitrary_org_owner_from_org_id,
             get_org_from_id_query,
         },
-        user_operator::{get_user_by_id_query, get_user_from_api_key_query},
+        user_operator::{
+            get_user_by_id_query, get_user_from_api_key_query, ip_in_cidr,
+            update_api_key_last_used_query,
+        },
     },
 };
 use actix_identity::Identity;
+use actix_session::SessionExt;
 use actix_web::{
     dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
     http::header::HeaderMap,
     web, Error, FromRequest, HttpMessage, HttpRequest,
 };
 use futures_util::future::LocalBoxFuture;
+use once_cell::sync::Lazy;
 use redis::AsyncCommands;
 use sentry::Transaction;
 use std::{
     future::{ready, Ready},
+    net::{IpAddr, SocketAddr},
     rc::Rc,
 };
 
@@ -56,6 +65,7 @@ where
             let (http_req, pl) = req.parts_mut();
             let mut user = get_user(http_req, pl, transaction.clone(), pool.clone()).await;
             let mut api_key = None;
+            let authed_with_session = user.is_some();
             if user.is_none() {
                 (user, api_key) =
                     auth_with_api_key(http_req, transaction.clone(), pool.clone()).await?;
@@ -65,9 +75,13 @@ where
                 req.extensions_mut().insert(user);
             }
 
+            if let Some(api_key) = api_key.clone() {
+                req.extensions_mut().insert(api_key);
+            }
+
             get_user_span.finish();
 
-            let org_id = match get_dataset_id_from_headers(req.headers()) {
+            let organization = match get_dataset_id_from_headers(req.headers()) {
                 Some(dataset_id) => {
                     let get_dataset_and_org_span = transaction
                         .start_child("get_dataset_and_org", "Getting dataset and organization");
@@ -129,20 +143,12 @@ where
                             }
                         }
 
+                        // Legacy route scopes only restrict the key, permissions are checked by the `Permitted` extractor
                         let route = format!("{} {}", req.method(), req.match_info().as_str());
 
                         if let Some(api_key_scopes) = user_api_key.scopes {
-                            if !api_key_scopes.is_empty() && api_key_scopes.contains(&Some(route)) {
-                                if let Some(ref mut user) = user {
-                                    user.user_orgs.iter_mut().for_each(|org| {
-                                        if org.organization_id
-                                            == dataset_org_plan_sub.organization.organization.id
-                                        {
-                                            org.role = UserRole::Admin.into();
-                                        }
-                                    });
-                                }
-                            } else {
+                            if !api_key_scopes.is_empty() && !api_key_scopes.contains(&Some(route))
+                            {
                                 return Err(ServiceError::Unauthorized.into());
                             }
                         }
@@ -152,7 +158,7 @@ where
                     req.extensions_mut()
                         .insert(dataset_org_plan_sub.organization.clone());
 
-                    dataset_org_plan_sub.organization.organization.id
+                    dataset_org_plan_sub.organization.organization
                 }
                 None => {
                     if let Some(org_header) = get_org_id_from_headers(req.headers()) {
@@ -163,7 +169,7 @@ where
                         })?;
                         let org_plan_and_sub = get_org_from_id_query(org_id, pool.clone()).await?;
                         req.extensions_mut().insert(org_plan_and_sub.clone());
-                        org_id
+                        org_plan_and_sub.organization
                     } else {
                         let res = srv.call(req).await?;
                         return Ok(res);
@@ -171,6 +177,15 @@ where
                 }
             };
 
+            let org_id = organization.id;
+
+            if organization.sso_only
+                && authed_with_session
+                && !signed_in_with_org_sso(&req, &org_id)
+            {
+                return Err(ServiceError::Forbidden.into());
+            }
+
             if let Some(ref user) = user {
                 let find_user_org_span =
                     transaction.start_child("find_user_org_role", "Finding user org role");
@@ -204,6 +219,15 @@ where
     }
 }
 
+/// Whether the session was signed in to through the SSO provider of the organization
+fn signed_in_with_org_sso(req: &ServiceRequest, org_id: &uuid::Uuid) -> bool {
+    req.get_session()
+        .get::<uuid::Uuid>(SSO_ORGANIZATION_SESSION_KEY)
+        .ok()
+        .flatten()
+        .is_some_and(|sso_org_id| sso_org_id == *org_id)
+}
+
 async fn get_user(
     req: &HttpRequest,
     pl: &mut Payload,
@@ -250,7 +274,7 @@ async fn get_user(
 }
 
 // Can either be Bearer {}, or x-api-key, or Authorization
-fn get_api_key_from_headers(headers: &HeaderMap) -> Option<String> {
+pub fn get_api_key_from_headers(headers: &HeaderMap) -> Option<String> {
     if let Some(auth_header_value) = headers.get("Authorization") {
         // Check if the Authorization header is a Bearer token
         if let Ok(auth_header_value) = auth_header_value.to_str() {
@@ -343,6 +367,23 @@ async fn auth_with_api_key(
         if let Ok((user, api_key)) =
             get_user_from_api_key_query(authen_header.as_str(), pool.clone()).await
         {
+            if !is_api_key_ip_allowed(req, &api_key) {
+                return Err(ServiceError::Forbidden);
+            }
+
+            let last_used_stale = api_key.last_used_at.map_or(true, |last_used_at| {
+                last_used_at < chrono::Utc::now().naive_local() - chrono::Duration::minutes(1)
+            });
+            if last_used_stale {
+                let api_key_id = api_key.id;
+                let pool = pool.clone();
+                actix_web::rt::spawn(async move {
+                    if let Err(err) = update_api_key_last_used_query(api_key_id, pool).await {
+                        log::error!("Failed to update api key last used {:?}", err);
+                    }
+                });
+            }
+
             return Ok((Some(user), Some(api_key)));
         }
 
@@ -354,6 +395,73 @@ async fn auth_with_api_key(
     Ok((None, None))
 }
 
+/// Whether the request comes from one of the CIDR blocks the api key is restricted to, keys without any are allowed from everywhere
+fn is_api_key_ip_allowed(req: &HttpRequest, api_key: &UserApiKey) -> bool {
+    let allowed_cidrs = api_key
+        .allowed_cidrs
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>();
+
+    if allowed_cidrs.is_empty() {
+        return true;
+    }
+
+    match get_client_ip(req) {
+        Some(client_ip) => allowed_cidrs.iter().any(|cidr| ip_in_cidr(client_ip, cidr)),
+        None => false,
+    }
+}
+
+/// CIDR blocks of the reverse proxies in front of the server, from the comma separated TRUSTED_PROXIES. Forwarding headers are only honored on connections from these.
+static TRUSTED_PROXIES: Lazy<Vec<String>> = Lazy::new(|| {
+    std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|cidr| cidr.trim().to_string())
+        .filter(|cidr| !cidr.is_empty())
+        .collect()
+});
+
+/// Address of the client which made the request. This is the peer address of the connection unless it is a trusted proxy, in which case the X-Forwarded-For header is followed back to the first address which is not a trusted proxy.
+pub fn get_client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let forwarded_for = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(','))
+        .collect::<Vec<&str>>();
+
+    resolve_client_ip(req.peer_addr()?.ip(), &forwarded_for, &TRUSTED_PROXIES)
+}
+
+fn resolve_client_ip(
+    peer_ip: IpAddr,
+    forwarded_for: &[&str],
+    trusted_proxies: &[String],
+) -> Option<IpAddr> {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|cidr| ip_in_cidr(ip, cidr));
+
+    let mut client_ip = peer_ip;
+    for address in forwarded_for.iter().rev() {
+        if !is_trusted(client_ip) {
+            break;
+        }
+
+        let address = address.trim();
+        client_ip = address.parse::<IpAddr>().ok().or_else(|| {
+            address
+                .parse::<SocketAddr>()
+                .ok()
+                .map(|socket_address| socket_address.ip())
+        })?;
+    }
+
+    Some(client_ip)
+}
+
 pub struct AuthMiddlewareFactory;
 
 impl<S, B> Transform<S, ServiceRequest> for AuthMiddlewareFactory
@@ -405,3 +513,49 @@ pub fn verify_member(user: &LoggedUser, org_id: &uuid::Uuid) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve_client_ip() {
+        let trusted_proxies = vec!["10.0.0.0/8".to_string()];
+        let client_ip = "203.0.113.7".parse::<IpAddr>().unwrap();
+        let proxy_ip = "10.0.0.2".parse::<IpAddr>().unwrap();
+
+        // Forwarding headers of untrusted peers are ignored
+        assert_eq!(
+            resolve_client_ip(client_ip, &["192.168.1.1"], &trusted_proxies),
+            Some(client_ip)
+        );
+        assert_eq!(
+            resolve_client_ip(client_ip, &["192.168.1.1"], &[]),
+            Some(client_ip)
+        );
+
+        // Addresses the client put in front of the chain are skipped
+        assert_eq!(
+            resolve_client_ip(proxy_ip, &["192.168.1.1", " 203.0.113.7"], &trusted_proxies),
+            Some(client_ip)
+        );
+        assert_eq!(
+            resolve_client_ip(
+                proxy_ip,
+                &["192.168.1.1", "203.0.113.7", "10.0.0.3"],
+                &trusted_proxies
+            ),
+            Some(client_ip)
+        );
+
+        // A trusted proxy without a forwarded address is the client
+        assert_eq!(
+            resolve_client_ip(proxy_ip, &[], &trusted_proxies),
+            Some(proxy_ip)
+        );
+        assert_eq!(
+            resolve_client_ip(proxy_ip, &["not an ip"], &trusted_proxies),
+            None
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use crate::{
+    data::models::{
+        DatasetAndOrgWithSubAndPlan, DatasetConfiguration, RateLimitRouteClass, RedisPool,
+        UserApiKey,
+    },
+    errors::ServiceError,
+    handlers::metrics_handler::Metrics,
+};
+use actix_http::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method,
+};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+
+/// Refills every bucket for the time passed since it was last used, then takes a token from all of them if each has one, so a request rejected by one bucket does not use up the others.
+/// KEYS are the buckets, ARGV[1] is the current time in milliseconds followed by the capacity and refill per millisecond of each bucket.
+/// Returns whether the tokens were taken followed by the tokens left, the milliseconds until the bucket is full again and the milliseconds until the next token of each bucket.
+const TOKEN_BUCKETS_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local buckets = {}
+local allowed = 1
+
+for i, key in ipairs(KEYS) do
+    local capacity = tonumber(ARGV[i * 2])
+    local refill_per_ms = tonumber(ARGV[i * 2 + 1])
+
+    local bucket = redis.call('HMGET', key, 'tokens', 'updated_at')
+    local tokens = tonumber(bucket[1])
+    local updated_at = tonumber(bucket[2])
+    if tokens == nil or updated_at == nil then
+        tokens = capacity
+        updated_at = now
+    end
+
+    tokens = math.min(capacity, tokens + math.max(0, now - updated_at) * refill_per_ms)
+    if tokens < 1 then
+        allowed = 0
+    end
+
+    buckets[i] = { capacity = capacity, refill_per_ms = refill_per_ms, tokens = tokens }
+end
+
+local result = { allowed }
+for i, key in ipairs(KEYS) do
+    local bucket = buckets[i]
+    if allowed == 1 then
+        bucket.tokens = bucket.tokens - 1
+    end
+
+    redis.call('HSET', key, 'tokens', tostring(bucket.tokens), 'updated_at', now)
+    redis.call('PEXPIRE', key, math.ceil(bucket.capacity / bucket.refill_per_ms))
+
+    table.insert(result, math.floor(bucket.tokens))
+    table.insert(result, math.ceil((bucket.capacity - bucket.tokens) / bucket.refill_per_ms))
+    table.insert(result, math.ceil(math.max(0, 1 - bucket.tokens) / bucket.refill_per_ms))
+end
+
+return result
+"#;
+
+/// Class of the route a request is made to, only search, ingest and RAG routes are rate limited
+fn get_route_class(method: &Method, path: &str) -> Option<RateLimitRouteClass> {
+    let path = path.trim_end_matches('/');
+
+    match (method, path) {
+        (
+            &Method::POST,
+            "/api/chunk/search"
+            | "/api/chunk/autocomplete"
+            | "/api/chunk/count"
+            | "/api/chunk/recommend"
+            | "/api/chunk_group/search"
+            | "/api/chunk_group/group_oriented_search"
+            | "/api/chunk_group/recommend",
+        ) => Some(RateLimitRouteClass::Search),
+        (&Method::POST | &Method::PUT, "/api/chunk")
+        | (&Method::PUT, "/api/chunk/tracking_id/update")
+        | (&Method::POST, "/api/file") => Some(RateLimitRouteClass::Ingest),
+        (&Method::POST | &Method::PUT | &Method::PATCH | &Method::DELETE, "/api/message")
+        | (&Method::POST, "/api/chunk/suggestions" | "/api/chunk/generate") => {
+            Some(RateLimitRouteClass::Rag)
+        }
+        _ => None,
+    }
+}
+
+/// Redis key and requests per minute of each bucket a request has to take a token from
+fn get_rate_limit_buckets(
+    req: &ServiceRequest,
+    route_class: RateLimitRouteClass,
+) -> Vec<(String, u32)> {
+    let extensions = req.extensions();
+    let mut buckets = vec![];
+
+    if let Some(api_key) = extensions.get::<UserApiKey>() {
+        if let Some(limit) = api_key.get_rate_limits().get(route_class) {
+            buckets.push((
+                format!("rate_limit:api_key:{}:{}", api_key.id, route_class),
+                limit,
+            ));
+        }
+    }
+
+    if let Some(dataset_org_plan_sub) = extensions.get::<DatasetAndOrgWithSubAndPlan>() {
+        let dataset_config = DatasetConfiguration::from_json(
+            dataset_org_plan_sub.dataset.server_configuration.clone(),
+        );
+        let limit = dataset_org_plan_sub
+            .organization
+            .plan
+            .clone()
+            .unwrap_or_default()
+            .dataset_rate_limit(&dataset_config.RATE_LIMITS, route_class);
+
+        buckets.push((
+            format!(
+                "rate_limit:dataset:{}:{}",
+                dataset_org_plan_sub.dataset.id, route_class
+            ),
+            limit,
+        ));
+    }
+
+    buckets
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct RateLimitStatus {
+    limit: u32,
+    remaining: u64,
+    reset_seconds: u64,
+    retry_after_seconds: Option<u64>,
+}
+
+/// Status of the bucket which decided the outcome of the token buckets script. When the request was rejected it is the empty bucket which takes longest to get a token again, otherwise the bucket with the fewest tokens left.
+fn get_rate_limit_status(limits: &[u32], script_result: &[i64]) -> Option<RateLimitStatus> {
+    let allowed = *script_result.first()? == 1;
+
+    let statuses = limits
+        .iter()
+        .zip(script_result[1..].chunks_exact(3))
+        .map(|(limit, bucket)| {
+            let retry_after_ms = bucket[2].max(0) as u64;
+
+            RateLimitStatus {
+                limit: *limit,
+                remaining: bucket[0].max(0) as u64,
+                reset_seconds: (bucket[1].max(0) as u64).div_ceil(1000),
+                retry_after_seconds: (!allowed && retry_after_ms > 0)
+                    .then(|| retry_after_ms.div_ceil(1000).max(1)),
+            }
+        });
+
+    if allowed {
+        statuses.min_by_key(|status| status.remaining)
+    } else {
+        statuses
+            .filter(|status| status.retry_after_seconds.is_some())
+            .max_by_key(|status| status.retry_after_seconds)
+    }
+}
+
+/// Takes a token from every bucket at once, or from none of them if any is empty. Returns the status of the bucket which decided the outcome.
+async fn take_rate_limit_tokens(
+    redis_pool: web::Data<RedisPool>,
+    buckets: Vec<(String, u32)>,
+) -> Result<Option<RateLimitStatus>, ServiceError> {
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::InternalServerError(err.to_string()))?;
+
+    let script = redis::Script::new(TOKEN_BUCKETS_SCRIPT);
+    let mut invocation = script.prepare_invoke();
+    invocation.arg(chrono::Utc::now().timestamp_millis());
+
+    let limits = buckets
+        .iter()
+        .map(|(key, limit)| {
+            let limit = (*limit).max(1);
+            invocation.key(key).arg(limit).arg(limit as f64 / 60000.0);
+            limit
+        })
+        .collect::<Vec<u32>>();
+
+    let script_result: Vec<i64> = invocation
+        .invoke_async(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::InternalServerError(err.to_string()))?;
+
+    Ok(get_rate_limit_status(&limits, &script_result))
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    for (name, value) in [
+        ("ratelimit-limit", status.limit as u64),
+        ("ratelimit-remaining", status.remaining),
+        ("ratelimit-reset", status.reset_seconds),
+    ] {
+        headers.insert(
+            HeaderName::from_static(name),
+            HeaderValue::from_str(&value.to_string()).expect("A valid header string"),
+        );
+    }
+}
+
+pub struct RateLimitMiddlewareFactory;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddlewareFactory
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+        Box::pin(async move {
+            let Some(route_class) = get_route_class(req.method(), req.path()) else {
+                return Ok(srv.call(req).await?.map_into_left_body());
+            };
+
+            let buckets = get_rate_limit_buckets(&req, route_class);
+            let redis_pool = req.app_data::<web::Data<RedisPool>>().cloned();
+
+            // Requests are let through when redis is unavailable rather than failing every search, ingest and RAG route
+            let status = match redis_pool {
+                Some(redis_pool) if !buckets.is_empty() => {
+                    take_rate_limit_tokens(redis_pool, buckets)
+                        .await
+                        .unwrap_or_else(|err| {
+                            log::error!("Failed to check rate limit {:?}", err);
+                            None
+                        })
+                }
+                _ => None,
+            };
+
+            if let Some(status) = status.as_ref() {
+                if let Some(retry_after_seconds) = status.retry_after_seconds {
+                    if let Some(metrics) = req.app_data::<web::Data<Metrics>>() {
+                        metrics
+                            .rate_limited_counter
+                            .with_label_values(&[&route_class.to_string()])
+                            .inc();
+                    }
+
+                    let mut res =
+                        req.error_response(ServiceError::TooManyRequests(retry_after_seconds));
+                    insert_rate_limit_headers(res.headers_mut(), status);
+
+                    return Ok(res.map_into_right_body());
+                }
+            }
+
+            let mut res = srv.call(req).await?;
+
+            if let Some(status) = status.as_ref() {
+                insert_rate_limit_headers(res.headers_mut(), status);
+            }
+
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::models::{RateLimits, StripePlan};
+
+    #[test]
+    fn test_route_classes() {
+        assert_eq!(
+            get_route_class(&Method::POST, "/api/chunk/search/"),
+            Some(RateLimitRouteClass::Search)
+        );
+        assert_eq!(
+            get_route_class(&Method::PUT, "/api/chunk"),
+            Some(RateLimitRouteClass::Ingest)
+        );
+        assert_eq!(
+            get_route_class(&Method::DELETE, "/api/message"),
+            Some(RateLimitRouteClass::Rag)
+        );
+        assert_eq!(get_route_class(&Method::GET, "/api/chunk/search"), None);
+        assert_eq!(get_route_class(&Method::POST, "/api/dataset"), None);
+    }
+
+    #[test]
+    fn test_dataset_rate_limits_are_capped_at_the_plan() {
+        let plan = StripePlan::default();
+        let plan_limit = plan.default_rate_limit(RateLimitRouteClass::Search);
+        let dataset_rate_limits = RateLimits {
+            search_per_minute: Some(plan_limit * 10),
+            ingest_per_minute: Some(1),
+            rag_per_minute: None,
+        };
+
+        assert_eq!(
+            plan.dataset_rate_limit(&dataset_rate_limits, RateLimitRouteClass::Search),
+            plan_limit
+        );
+        assert_eq!(
+            plan.dataset_rate_limit(&dataset_rate_limits, RateLimitRouteClass::Ingest),
+            1
+        );
+        assert_eq!(
+            plan.dataset_rate_limit(&dataset_rate_limits, RateLimitRouteClass::Rag),
+            plan.default_rate_limit(RateLimitRouteClass::Rag)
+        );
+    }
+
+    #[test]
+    fn test_allowed_status_is_the_bucket_with_the_fewest_tokens() {
+        let status = get_rate_limit_status(&[10, 600], &[1, 4, 36000, 0, 550, 5000, 0]);
+
+        assert_eq!(
+            status,
+            Some(RateLimitStatus {
+                limit: 10,
+                remaining: 4,
+                reset_seconds: 36,
+                retry_after_seconds: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejected_status_is_the_empty_bucket() {
+        let status = get_rate_limit_status(&[10, 600], &[0, 4, 36000, 0, 0, 60000, 100]);
+
+        assert_eq!(
+            status,
+            Some(RateLimitStatus {
+                limit: 600,
+                remaining: 0,
+                reset_seconds: 60,
+                retry_after_seconds: Some(1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejected_status_waits_for_the_slowest_empty_bucket() {
+        let status = get_rate_limit_status(&[10, 600], &[0, 0, 60000, 6000, 0, 60000, 100])
+            .expect("A status for the rejected request");
+
+        assert_eq!(status.limit, 10);
+        assert_eq!(status.retry_after_seconds, Some(6));
+    }
+}
@@ -0,0 +1,695 @@
+use super::chunk_operator::get_metadata_from_tracking_id_query;
+use super::message_operator::{create_messages_query, get_usage_from_response};
+use super::qdrant_operator::scroll_dataset_points;
+use super::search_operator::{
+    assemble_qdrant_filter, count_chunks_query, full_text_search_over_groups,
+    hybrid_search_over_groups, search_chunks_query, search_hybrid_chunks,
+    semantic_search_over_groups,
+};
+use crate::data::models::{
+    AgentOptions, ChunkMetadataStringTagSet, ChunkMetadataTypes, ConditionType, CountSearchMethod,
+    Dataset, DatasetConfiguration, HasIDCondition, Message, Pool, QueryTypes, RedisPool,
+    ScoreChunkDTO, SearchMethod,
+};
+use crate::errors::ServiceError;
+use crate::handlers::chunk_handler::{
+    ChunkFilter, CountChunksReqPayload, ParsedQuery, ParsedQueryTypes, SearchChunksReqPayload,
+};
+use crate::handlers::group_handler::SearchOverGroupsReqPayload;
+use crate::operators::parse_operator::convert_html_to_text;
+use actix_web::web;
+use openai_dive::v1::api::Client;
+use openai_dive::v1::resources::chat::{
+    ChatCompletionFunction, ChatCompletionParameters, ChatCompletionTool, ChatCompletionToolChoice,
+    ChatCompletionToolType, ChatMessage, ChatMessageContent, ToolCall,
+};
+use serde::Deserialize;
+use serde_json::json;
+use simple_server_timing_header::Timer;
+
+pub const AGENT_TOOL_CALL_ROLE: &str = "tool_call";
+pub const AGENT_TOOL_RESULT_ROLE: &str = "tool";
+
+const AGENT_PROMPT: &str = "You can search the dataset with the provided tools before answering. Search as many times as you need, with different queries and filters, to answer the question. Every chunk returned by a tool has a doc number, include the doc numbers that you used in square brackets at the end of the sentences that you used the docs for.";
+
+/// Roles of the messages which agent mode stores for its tool calls. These are kept in the topic so the transcript is auditable, but are not replayed to the LLM on later messages.
+pub fn is_agent_tool_message(message: &Message) -> bool {
+    message.role == AGENT_TOOL_CALL_ROLE || message.role == AGENT_TOOL_RESULT_ROLE
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchToolArgs {
+    query: String,
+    search_type: Option<SearchMethod>,
+    filters: Option<ChunkFilter>,
+    page_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupSearchToolArgs {
+    query: String,
+    search_type: Option<SearchMethod>,
+    filters: Option<ChunkFilter>,
+    page_size: Option<u64>,
+    group_size: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetChunkByTrackingIdToolArgs {
+    tracking_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CountToolArgs {
+    query: String,
+    search_type: Option<CountSearchMethod>,
+    filters: Option<ChunkFilter>,
+}
+
+fn function_tool(
+    name: &str,
+    description: &str,
+    parameters: serde_json::Value,
+) -> ChatCompletionTool {
+    ChatCompletionTool {
+        r#type: ChatCompletionToolType::Function,
+        function: ChatCompletionFunction {
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            parameters,
+        },
+    }
+}
+
+/// Trieve's search, group search, get chunk by tracking id and count endpoints exposed as tools
+pub fn get_agent_tools() -> Vec<ChatCompletionTool> {
+    let filters = json!({
+        "type": "object",
+        "description": "Trieve ChunkFilter with must, must_not and should lists of field conditions, e.g. {\"must\": [{\"field\": \"tag_set\", \"match_all\": [\"docs\"]}]}"
+    });
+
+    vec![
+        function_tool(
+            "search",
+            "Search the dataset for the chunks most relevant to a query.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "search_type": { "type": "string", "enum": ["semantic", "fulltext", "hybrid", "bm25"] },
+                    "filters": filters,
+                    "page_size": { "type": "integer", "description": "Number of chunks to return, at most 20" }
+                },
+                "required": ["query"]
+            }),
+        ),
+        function_tool(
+            "group_search",
+            "Search the dataset for the groups of chunks most relevant to a query. Use this when chunks belong to larger documents.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "search_type": { "type": "string", "enum": ["semantic", "fulltext", "hybrid"] },
+                    "filters": filters,
+                    "page_size": { "type": "integer", "description": "Number of groups to return, at most 10" },
+                    "group_size": { "type": "integer", "description": "Number of chunks to return per group, at most 5" }
+                },
+                "required": ["query"]
+            }),
+        ),
+        function_tool(
+            "get_chunk_by_tracking_id",
+            "Get a single chunk by its tracking id.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "tracking_id": { "type": "string" }
+                },
+                "required": ["tracking_id"]
+            }),
+        ),
+        function_tool(
+            "count",
+            "Count the chunks which match a query and filters.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "search_type": { "type": "string", "enum": ["semantic", "fulltext", "bm25"] },
+                    "filters": filters
+                },
+                "required": ["query"]
+            }),
+        ),
+    ]
+}
+
+pub struct AgentRun {
+    /// The agent prompt followed by the tool calls and their results, to be placed after the chat history for the final answer
+    pub messages: Vec<ChatMessage>,
+    /// Chunks returned by the tools, in the order of their doc numbers
+    pub chunks: Vec<ChunkMetadataStringTagSet>,
+    /// Number of tool call and tool result messages stored in the topic
+    pub stored_message_count: usize,
+}
+
+struct AgentToolContext<'a> {
+    dataset: &'a Dataset,
+    dataset_config: &'a DatasetConfiguration,
+    /// Filters of the request, which every tool call is limited to
+    filters: Option<ChunkFilter>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+}
+
+/// Filters which match the chunks matching both the request's and the tool call's filters. The should lists of both can not be combined into one filter, so tool calls can only use should when the request does not.
+fn and_filters(
+    request_filters: &Option<ChunkFilter>,
+    tool_filters: Option<ChunkFilter>,
+) -> Result<Option<ChunkFilter>, ServiceError> {
+    let (Some(request_filters), Some(tool_filters)) = (request_filters, tool_filters.clone())
+    else {
+        return Ok(request_filters.clone().or(tool_filters));
+    };
+
+    let has_should = |filters: &ChunkFilter| {
+        filters
+            .should
+            .as_ref()
+            .is_some_and(|should| !should.is_empty())
+    };
+    if has_should(request_filters) && has_should(&tool_filters) {
+        return Err(ServiceError::BadRequest(
+            "The request already has should filters, use must filters instead".to_string(),
+        ));
+    }
+
+    let concat = |request: &Option<Vec<ConditionType>>, tool: Option<Vec<ConditionType>>| match (
+        request.clone(),
+        tool,
+    ) {
+        (None, None) => None,
+        (request, tool) => Some(
+            request
+                .unwrap_or_default()
+                .into_iter()
+                .chain(tool.unwrap_or_default())
+                .collect::<Vec<ConditionType>>(),
+        ),
+    };
+
+    Ok(Some(ChunkFilter {
+        should: concat(&request_filters.should, tool_filters.should),
+        must: concat(&request_filters.must, tool_filters.must),
+        must_not: concat(&request_filters.must_not, tool_filters.must_not),
+        jsonb_prefilter: request_filters
+            .jsonb_prefilter
+            .or(tool_filters.jsonb_prefilter),
+    }))
+}
+
+/// Adds the chunk to the docs if it isn't already one and returns what the LLM sees of it
+fn chunk_to_doc(
+    chunk: ChunkMetadataStringTagSet,
+    score: Option<f64>,
+    docs: &mut Vec<ChunkMetadataStringTagSet>,
+) -> serde_json::Value {
+    let doc = match docs.iter().position(|doc| doc.id == chunk.id) {
+        Some(idx) => idx + 1,
+        None => {
+            docs.push(chunk.clone());
+            docs.len()
+        }
+    };
+
+    let text = convert_html_to_text(&chunk.chunk_html.clone().unwrap_or_default())
+        .split_whitespace()
+        .take(300)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    json!({
+        "doc": doc,
+        "tracking_id": chunk.tracking_id,
+        "link": chunk.link,
+        "score": score,
+        "text": text,
+    })
+}
+
+fn score_chunk_to_doc(
+    score_chunk: ScoreChunkDTO,
+    docs: &mut Vec<ChunkMetadataStringTagSet>,
+) -> Option<serde_json::Value> {
+    match score_chunk.metadata.into_iter().next()? {
+        ChunkMetadataTypes::Metadata(chunk) => {
+            Some(chunk_to_doc(chunk, Some(score_chunk.score), docs))
+        }
+        _ => None,
+    }
+}
+
+fn parse_tool_args<'a, T: Deserialize<'a>>(arguments: &'a str) -> Result<T, ServiceError> {
+    serde_json::from_str(arguments)
+        .map_err(|err| ServiceError::BadRequest(format!("Invalid tool arguments: {}", err)))
+}
+
+async fn run_agent_tool(
+    tool_call: &ToolCall,
+    context: &AgentToolContext<'_>,
+    docs: &mut Vec<ChunkMetadataStringTagSet>,
+) -> Result<serde_json::Value, actix_web::Error> {
+    let arguments = tool_call.function.arguments.as_str();
+    let mut timer = Timer::new();
+
+    match tool_call.function.name.as_str() {
+        "search" => {
+            let args: SearchToolArgs = parse_tool_args(arguments)?;
+            let search_type = args.search_type.unwrap_or(SearchMethod::Hybrid);
+            let data = SearchChunksReqPayload {
+                search_type: search_type.clone(),
+                query: QueryTypes::Single(args.query.clone()),
+                filters: and_filters(&context.filters, args.filters)?,
+                page_size: Some(args.page_size.unwrap_or(10).min(20)),
+                ..Default::default()
+            };
+            let parsed_query = ParsedQuery {
+                query: args.query,
+                quote_words: None,
+                negated_words: None,
+                vectors: None,
+            };
+
+            let results = match search_type {
+                SearchMethod::Hybrid => {
+                    search_hybrid_chunks(
+                        data,
+                        parsed_query,
+                        context.pool.clone(),
+                        context.redis_pool.clone(),
+                        context.dataset.clone(),
+                        context.dataset_config,
+                        &mut timer,
+                    )
+                    .await?
+                }
+                _ => {
+                    search_chunks_query(
+                        data,
+                        ParsedQueryTypes::Single(parsed_query),
+                        context.pool.clone(),
+                        context.redis_pool.clone(),
+                        context.dataset.clone(),
+                        context.dataset_config,
+                        &mut timer,
+                    )
+                    .await?
+                }
+            };
+
+            Ok(json!({
+                "chunks": results
+                    .score_chunks
+                    .into_iter()
+                    .filter_map(|score_chunk| score_chunk_to_doc(score_chunk, docs))
+                    .collect::<Vec<serde_json::Value>>()
+            }))
+        }
+        "group_search" => {
+            let args: GroupSearchToolArgs = parse_tool_args(arguments)?;
+            let search_type = args.search_type.unwrap_or(SearchMethod::Hybrid);
+            let data = SearchOverGroupsReqPayload {
+                search_type: search_type.clone(),
+                query: QueryTypes::Single(args.query.clone()),
+                filters: and_filters(&context.filters, args.filters)?,
+                page_size: Some(args.page_size.unwrap_or(5).min(10)),
+                group_size: Some(args.group_size.unwrap_or(3).min(5)),
+                ..Default::default()
+            };
+            let parsed_query = ParsedQuery {
+                query: args.query,
+                quote_words: None,
+                negated_words: None,
+                vectors: None,
+            };
+
+            let results = match search_type {
+                SearchMethod::Hybrid => {
+                    hybrid_search_over_groups(
+                        data,
+                        parsed_query,
+                        context.pool.clone(),
+                        context.redis_pool.clone(),
+                        context.dataset.clone(),
+                        context.dataset_config,
+                        &mut timer,
+                    )
+                    .await?
+                }
+                SearchMethod::FullText | SearchMethod::BM25 => {
+                    full_text_search_over_groups(
+                        data,
+                        ParsedQueryTypes::Single(parsed_query),
+                        context.pool.clone(),
+                        context.redis_pool.clone(),
+                        context.dataset.clone(),
+                        context.dataset_config,
+                        &mut timer,
+                    )
+                    .await?
+                }
+                _ => {
+                    semantic_search_over_groups(
+                        data,
+                        ParsedQueryTypes::Single(parsed_query),
+                        context.pool.clone(),
+                        context.redis_pool.clone(),
+                        context.dataset.clone(),
+                        context.dataset_config,
+                        &mut timer,
+                    )
+                    .await?
+                }
+            };
+
+            Ok(json!({
+                "groups": results
+                    .group_chunks
+                    .into_iter()
+                    .map(|group| json!({
+                        "group_name": group.group_name,
+                        "group_tracking_id": group.group_tracking_id,
+                        "chunks": group
+                            .metadata
+                            .into_iter()
+                            .filter_map(|score_chunk| score_chunk_to_doc(score_chunk, docs))
+                            .collect::<Vec<serde_json::Value>>()
+                    }))
+                    .collect::<Vec<serde_json::Value>>()
+            }))
+        }
+        "get_chunk_by_tracking_id" => {
+            let args: GetChunkByTrackingIdToolArgs = parse_tool_args(arguments)?;
+
+            // Chunks outside of the request's filters are not found
+            if context.filters.is_some() {
+                let tracking_id_filter = ChunkFilter {
+                    should: None,
+                    must: Some(vec![ConditionType::HasID(HasIDCondition {
+                        ids: None,
+                        tracking_ids: Some(vec![args.tracking_id.clone()]),
+                    })]),
+                    must_not: None,
+                    jsonb_prefilter: None,
+                };
+                let filter = assemble_qdrant_filter(
+                    and_filters(&context.filters, Some(tracking_id_filter))?,
+                    None,
+                    None,
+                    context.dataset.id,
+                    context.pool.clone(),
+                )
+                .await?;
+                let (point_ids, _) =
+                    scroll_dataset_points(1, None, None, context.dataset_config.clone(), filter)
+                        .await?;
+
+                if point_ids.is_empty() {
+                    return Err(ServiceError::NotFound(format!(
+                        "Chunk with tracking id {} not found",
+                        args.tracking_id
+                    ))
+                    .into());
+                }
+            }
+
+            let chunk = get_metadata_from_tracking_id_query(
+                args.tracking_id,
+                context.dataset.id,
+                context.pool.clone(),
+            )
+            .await?;
+
+            Ok(chunk_to_doc(chunk.into(), None, docs))
+        }
+        "count" => {
+            let args: CountToolArgs = parse_tool_args(arguments)?;
+            let data = CountChunksReqPayload {
+                search_type: args.search_type.unwrap_or(CountSearchMethod::Semantic),
+                query: QueryTypes::Single(args.query.clone()),
+                filters: and_filters(&context.filters, args.filters)?,
+                score_threshold: None,
+                limit: None,
+                use_quote_negated_terms: None,
+            };
+            let parsed_query = ParsedQuery {
+                query: args.query,
+                quote_words: None,
+                negated_words: None,
+                vectors: None,
+            };
+
+            let result = count_chunks_query(
+                data,
+                ParsedQueryTypes::Single(parsed_query),
+                context.pool.clone(),
+                context.dataset.clone(),
+                context.dataset_config,
+            )
+            .await?;
+
+            Ok(json!({ "count": result.count }))
+        }
+        name => Err(ServiceError::BadRequest(format!("Unknown tool {}", name)).into()),
+    }
+}
+
+/// Rough number of tokens in the messages for providers which don't report usage
+fn estimate_tokens(messages: &[ChatMessage]) -> u32 {
+    (serde_json::to_string(messages).unwrap_or_default().len() / 4) as u32
+}
+
+/// Most steps and tokens an agent run can use whatever the request asks for, from AGENT_MAX_STEPS and AGENT_MAX_TOKENS
+fn get_agent_limits() -> (u32, u32) {
+    let max_steps = std::env::var("AGENT_MAX_STEPS")
+        .ok()
+        .and_then(|max_steps| max_steps.parse().ok())
+        .unwrap_or(10);
+    let max_tokens = std::env::var("AGENT_MAX_TOKENS")
+        .ok()
+        .and_then(|max_tokens| max_tokens.parse().ok())
+        .unwrap_or(50000);
+
+    (max_steps, max_tokens)
+}
+
+/// Lets the LLM call the agent tools until it is ready to answer or runs out of steps or tokens. Every tool call is limited to the filters of the request. Every tool call and tool result is stored as a message in the topic starting at `first_sort_order`. The final answer is not generated here such that it can be streamed like any other completion.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(client, pool, redis_pool, conversation))]
+pub async fn run_agent_query(
+    agent_options: AgentOptions,
+    filters: Option<ChunkFilter>,
+    conversation: Vec<ChatMessage>,
+    client: &Client,
+    model: String,
+    topic_id: uuid::Uuid,
+    first_sort_order: usize,
+    dataset: Dataset,
+    dataset_config: DatasetConfiguration,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<AgentRun, actix_web::Error> {
+    let (server_max_steps, server_max_tokens) = get_agent_limits();
+    let max_steps = agent_options.max_steps.unwrap_or(5).min(server_max_steps);
+    let max_tokens = agent_options
+        .max_tokens
+        .unwrap_or(20000)
+        .min(server_max_tokens);
+    let tools = get_agent_tools();
+
+    let context = AgentToolContext {
+        dataset: &dataset,
+        dataset_config: &dataset_config,
+        filters,
+        pool: pool.clone(),
+        redis_pool,
+    };
+
+    let mut agent_messages = vec![ChatMessage::System {
+        content: ChatMessageContent::Text(AGENT_PROMPT.to_string()),
+        name: None,
+    }];
+    let mut docs: Vec<ChunkMetadataStringTagSet> = vec![];
+    let mut stored_message_count = 0;
+    let mut tokens_used = 0;
+
+    for _ in 0..max_steps {
+        if tokens_used >= max_tokens {
+            break;
+        }
+
+        let messages = conversation
+            .iter()
+            .chain(agent_messages.iter())
+            .cloned()
+            .collect::<Vec<ChatMessage>>();
+
+        let parameters = ChatCompletionParameters {
+            model: model.clone(),
+            messages: messages.clone(),
+            stream: Some(false),
+            tools: Some(tools.clone()),
+            tool_choice: Some(ChatCompletionToolChoice::Auto),
+            temperature: dataset_config.TEMPERATURE.map(|temp| temp as f32),
+            max_completion_tokens: Some(max_tokens.saturating_sub(tokens_used).max(1)),
+            ..Default::default()
+        };
+
+        let completion = client.chat().create(parameters).await.map_err(|err| {
+            ServiceError::BadRequest(format!("Bad response from LLM server provider: {}", err))
+        })?;
+
+        tokens_used += get_usage_from_response(&completion)
+            .and_then(|usage| usage.total_tokens)
+            .unwrap_or_else(|| estimate_tokens(&messages));
+
+        let tool_calls = match completion.choices.first().map(|choice| &choice.message) {
+            Some(ChatMessage::Assistant {
+                tool_calls: Some(tool_calls),
+                ..
+            }) if !tool_calls.is_empty() => tool_calls.clone(),
+            // The LLM is ready to answer
+            _ => break,
+        };
+
+        let mut step_messages = vec![Message::from_details(
+            serde_json::to_string(&tool_calls).unwrap_or_default(),
+            topic_id,
+            (first_sort_order + stored_message_count) as i32,
+            AGENT_TOOL_CALL_ROLE.to_string(),
+            None,
+            None,
+            dataset.id,
+            uuid::Uuid::new_v4(),
+        )];
+        agent_messages.push(ChatMessage::Assistant {
+            content: None,
+            tool_calls: Some(tool_calls.clone()),
+            name: None,
+            refusal: None,
+        });
+
+        for tool_call in tool_calls.iter() {
+            let result = match run_agent_tool(tool_call, &context, &mut docs).await {
+                Ok(result) => result,
+                Err(err) => json!({ "error": err.to_string() }),
+            };
+
+            step_messages.push(Message::from_details(
+                json!({
+                    "tool_call_id": tool_call.id,
+                    "name": tool_call.function.name,
+                    "result": result,
+                })
+                .to_string(),
+                topic_id,
+                (first_sort_order + stored_message_count + step_messages.len()) as i32,
+                AGENT_TOOL_RESULT_ROLE.to_string(),
+                None,
+                None,
+                dataset.id,
+                uuid::Uuid::new_v4(),
+            ));
+            agent_messages.push(ChatMessage::Tool {
+                content: result.to_string(),
+                tool_call_id: tool_call.id.clone(),
+            });
+        }
+
+        stored_message_count += step_messages.len();
+        create_messages_query(step_messages, &pool).await?;
+    }
+
+    Ok(AgentRun {
+        messages: agent_messages,
+        chunks: docs,
+        stored_message_count,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::models::{FieldCondition, MatchCondition};
+
+    fn filter(
+        should: Option<Vec<ConditionType>>,
+        must: Option<Vec<ConditionType>>,
+        must_not: Option<Vec<ConditionType>>,
+    ) -> ChunkFilter {
+        ChunkFilter {
+            should,
+            must,
+            must_not,
+            jsonb_prefilter: None,
+        }
+    }
+
+    fn tag_condition(tag: &str) -> ConditionType {
+        ConditionType::Field(FieldCondition {
+            field: "tag_set".to_string(),
+            match_any: None,
+            match_all: Some(vec![MatchCondition::Text(tag.to_string())]),
+            range: None,
+            date_range: None,
+            geo_bounding_box: None,
+            geo_radius: None,
+            geo_polygon: None,
+        })
+    }
+
+    fn condition_count(conditions: &Option<Vec<ConditionType>>) -> usize {
+        conditions.as_ref().map_or(0, |conditions| conditions.len())
+    }
+
+    #[test]
+    fn test_and_filters_keeps_request_filters() {
+        let request_filters = Some(filter(
+            None,
+            Some(vec![tag_condition("public")]),
+            Some(vec![tag_condition("draft")]),
+        ));
+
+        let filters = and_filters(&request_filters, None).unwrap().unwrap();
+        assert_eq!(condition_count(&filters.must), 1);
+        assert_eq!(condition_count(&filters.must_not), 1);
+
+        let filters = and_filters(
+            &request_filters,
+            Some(filter(
+                Some(vec![tag_condition("api"), tag_condition("guides")]),
+                Some(vec![tag_condition("docs")]),
+                None,
+            )),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(condition_count(&filters.must), 2);
+        assert_eq!(condition_count(&filters.must_not), 1);
+        assert_eq!(condition_count(&filters.should), 2);
+
+        assert!(and_filters(&None, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_and_filters_rejects_two_should_lists() {
+        let request_filters = Some(filter(Some(vec![tag_condition("public")]), None, None));
+
+        assert!(and_filters(
+            &request_filters,
+            Some(filter(Some(vec![tag_condition("internal")]), None, None)),
+        )
+        .is_err());
+    }
+}
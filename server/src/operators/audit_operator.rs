@@ -0,0 +1,176 @@
+use crate::{
+    data::models::{
+        AuditAction, AuditActor, AuditEvent, AuditEventClickhouse, AuditLogFilter, Dataset,
+        DatasetConfiguration,
+    },
+    errors::ServiceError,
+    operators::clickhouse_operator::{ClickHouseEvent, EventQueue},
+};
+use actix_web::web;
+use serde_json::{json, Map, Value};
+
+/// Maximum number of events returned by an export of the audit log
+pub const MAX_AUDIT_EXPORT_EVENTS: u64 = 100000;
+
+fn push_audit_diff(path: String, before: &Value, after: &Value, diff: &mut Map<String, Value>) {
+    match (before, after) {
+        (Value::Object(before_fields), Value::Object(after_fields)) => {
+            let mut keys = before_fields
+                .keys()
+                .chain(after_fields.keys())
+                .collect::<Vec<&String>>();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+
+                push_audit_diff(
+                    field_path,
+                    before_fields.get(key).unwrap_or(&Value::Null),
+                    after_fields.get(key).unwrap_or(&Value::Null),
+                    diff,
+                );
+            }
+        }
+        _ if before != after => {
+            diff.insert(path, json!({ "before": before, "after": after }));
+        }
+        _ => {}
+    }
+}
+
+/// Maps the dot separated path of every field which differs between the two values to its value before and after. Pass null as the before or after of something which was created or deleted.
+pub fn get_audit_diff(before: Value, after: Value) -> Value {
+    let as_object = |value: Value| match value {
+        Value::Null => Value::Object(Map::new()),
+        value => value,
+    };
+
+    let mut diff = Map::new();
+    push_audit_diff(
+        "".to_string(),
+        &as_object(before),
+        &as_object(after),
+        &mut diff,
+    );
+
+    Value::Object(diff)
+}
+
+/// Fields of a dataset which are recorded in the audit log, secrets of the configuration are left out
+pub fn get_dataset_audit_state(dataset: &Dataset) -> Value {
+    json!({
+        "name": dataset.name,
+        "tracking_id": dataset.tracking_id,
+        "server_configuration": DatasetConfiguration::from_json(dataset.server_configuration.clone()),
+    })
+}
+
+pub async fn record_audit_event(
+    organization_id: uuid::Uuid,
+    actor: &AuditActor,
+    action: AuditAction,
+    target_id: String,
+    diff: Value,
+    event_queue: &EventQueue,
+) {
+    event_queue
+        .send(ClickHouseEvent::AuditEvent(
+            AuditEventClickhouse::from_details(organization_id, actor, action, target_id, diff),
+        ))
+        .await;
+}
+
+#[tracing::instrument(skip(clickhouse_client))]
+pub async fn get_audit_events_query(
+    organization_id: uuid::Uuid,
+    filter: Option<AuditLogFilter>,
+    page: u64,
+    page_size: u64,
+    clickhouse_client: web::Data<clickhouse::Client>,
+) -> Result<(Vec<AuditEvent>, i64), ServiceError> {
+    let mut query_string =
+        String::from("SELECT ?fields FROM audit_events WHERE organization_id = ?");
+    let mut count_query_string =
+        String::from("SELECT count(*) FROM audit_events WHERE organization_id = ?");
+
+    if let Some(filter) = &filter {
+        query_string = filter.add_to_query(query_string)?;
+        count_query_string = filter.add_to_query(count_query_string)?;
+    }
+
+    query_string.push_str(&format!(
+        " ORDER BY created_at DESC, id LIMIT {} OFFSET {}",
+        page_size,
+        (page.max(1) - 1) * page_size
+    ));
+
+    let events: Vec<AuditEventClickhouse> = clickhouse_client
+        .query(&query_string)
+        .bind(organization_id)
+        .fetch_all()
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get audit events {:?}", err);
+            ServiceError::BadRequest("Failed to get audit events".to_string())
+        })?;
+
+    let total_count: u64 = clickhouse_client
+        .query(&count_query_string)
+        .bind(organization_id)
+        .fetch_one()
+        .await
+        .map_err(|err| {
+            log::error!("Failed to count audit events {:?}", err);
+            ServiceError::BadRequest("Failed to count audit events".to_string())
+        })?;
+
+    Ok((
+        events.into_iter().map(|event| event.into()).collect(),
+        total_count as i64,
+    ))
+}
+
+/// Every event of the organization matching the filter, oldest first, serialized as one JSON object per line
+#[tracing::instrument(skip(clickhouse_client))]
+pub async fn export_audit_events_query(
+    organization_id: uuid::Uuid,
+    filter: Option<AuditLogFilter>,
+    clickhouse_client: web::Data<clickhouse::Client>,
+) -> Result<String, ServiceError> {
+    let mut query_string =
+        String::from("SELECT ?fields FROM audit_events WHERE organization_id = ?");
+
+    if let Some(filter) = &filter {
+        query_string = filter.add_to_query(query_string)?;
+    }
+
+    query_string.push_str(&format!(
+        " ORDER BY created_at ASC, id LIMIT {}",
+        MAX_AUDIT_EXPORT_EVENTS
+    ));
+
+    let events: Vec<AuditEventClickhouse> = clickhouse_client
+        .query(&query_string)
+        .bind(organization_id)
+        .fetch_all()
+        .await
+        .map_err(|err| {
+            log::error!("Failed to export audit events {:?}", err);
+            ServiceError::BadRequest("Failed to export audit events".to_string())
+        })?;
+
+    events
+        .into_iter()
+        .map(|event| serde_json::to_string(&AuditEvent::from(event)).map(|line| line + "\n"))
+        .collect::<Result<String, serde_json::Error>>()
+        .map_err(|err| {
+            log::error!("Failed to serialize audit events {:?}", err);
+            ServiceError::InternalServerError("Failed to serialize audit events".to_string())
+        })
+}
@@ -1,6 +1,7 @@
 use crate::data::models::{
     uuid_between, ChunkData, ChunkGroup, ChunkGroupBookmark, ChunkMetadataTable, ChunkMetadataTags,
-    ChunkMetadataTypes, ContentChunkMetadata, Dataset, DatasetConfiguration, DatasetTags,
+    ChunkMetadataTypes, ChunkMetadataVersionPG, ChunkVersionAction, ChunkVersionActor,
+    ContentChunkMetadata, Dataset, DatasetConfiguration, DatasetLanguage, DatasetTags,
     IngestSpecificChunkMetadata, SlimChunkMetadata, SlimChunkMetadataTable, UnifiedId,
 };
 use crate::handlers::chunk_handler::{BulkUploadIngestionMessage, ChunkReqPayload};
@@ -8,9 +9,11 @@ use crate::handlers::chunk_handler::{ChunkFilter, UploadIngestionMessage};
 use crate::operators::group_operator::{
     check_group_ids_exist_query, get_group_ids_from_tracking_ids_query,
 };
+use crate::operators::model_operator::{is_stop_word, tokenize};
 use crate::operators::parse_operator::convert_html_to_text;
 use crate::operators::qdrant_operator::{
-    delete_points_from_qdrant, get_qdrant_collection_from_dataset_config, scroll_dataset_points,
+    delete_dataset_points_from_qdrant, get_qdrant_collection_from_dataset_config,
+    scroll_dataset_points,
 };
 use crate::{
     data::models::{ChunkMetadata, Pool},
@@ -33,6 +36,7 @@ use std::collections::{HashMap, HashSet};
 use time::OffsetDateTime;
 use utoipa::ToSchema;
 
+use super::chunk_version_operator::set_chunk_version_group_ids_query;
 use super::group_operator::create_groups_query;
 use super::search_operator::assemble_qdrant_filter;
 
@@ -554,6 +558,29 @@ pub async fn get_metadata_from_ids_query(
     Ok(chunk_metadatas)
 }
 
+#[tracing::instrument(skip(pool))]
+async fn get_metadata_from_point_ids_query(
+    point_ids: Vec<uuid::Uuid>,
+    dataset_uuid: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Vec<ChunkMetadata>, ServiceError> {
+    use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let chunk_ids = chunk_metadata_columns::chunk_metadata
+        .filter(chunk_metadata_columns::qdrant_point_id.eq_any(point_ids))
+        .filter(chunk_metadata_columns::dataset_id.eq(dataset_uuid))
+        .select(chunk_metadata_columns::id)
+        .load::<uuid::Uuid>(&mut conn)
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to get chunk ids".to_string()))?;
+
+    get_metadata_from_ids_query(chunk_ids, dataset_uuid, pool).await
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn get_metadata_from_tracking_ids_query(
     tracking_ids: Vec<String>,
@@ -607,10 +634,13 @@ pub async fn bulk_delete_chunks_query(
     filter: ChunkFilter,
     dataset_id: uuid::Uuid,
     dataset_config: DatasetConfiguration,
+    actor: Option<ChunkVersionActor>,
     pool: web::Data<Pool>,
 ) -> Result<(), ServiceError> {
     use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
     use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
+    use crate::data::schema::chunk_metadata_versions::dsl as chunk_metadata_versions_columns;
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
 
     let filter = assemble_qdrant_filter(Some(filter), None, None, dataset_id, pool.clone()).await?;
     let qdrant_collection = get_qdrant_collection_from_dataset_config(&dataset_config);
@@ -629,6 +659,17 @@ pub async fn bulk_delete_chunks_query(
 
         log::info!("Deleting {:?} chunks with point_ids", point_ids.len());
 
+        let mut deleted_chunk_versions =
+            get_metadata_from_point_ids_query(point_ids.clone(), dataset_id, pool.clone())
+                .await?
+                .into_iter()
+                .map(|chunk| {
+                    ChunkMetadataVersionPG::from_details(chunk, ChunkVersionAction::Delete, actor)
+                })
+                .collect_vec();
+
+        set_chunk_version_group_ids_query(&mut deleted_chunk_versions, pool.clone()).await?;
+
         let transaction_result = conn
             .transaction::<_, diesel::result::Error, _>(|conn| {
                 async move {
@@ -646,6 +687,18 @@ pub async fn bulk_delete_chunks_query(
                         .get_results::<uuid::Uuid>(conn)
                         .await?;
 
+                        diesel::insert_into(
+                            chunk_metadata_versions_columns::chunk_metadata_versions,
+                        )
+                        .values(
+                            deleted_chunk_versions
+                                .into_iter()
+                                .filter(|version| deleted_chunks.contains(&version.chunk_id))
+                                .collect_vec(),
+                        )
+                        .execute(conn)
+                        .await?;
+
                         diesel::delete(
                             chunk_group_bookmarks_columns::chunk_group_bookmarks.filter(
                                 chunk_group_bookmarks_columns::chunk_metadata_id
@@ -655,6 +708,16 @@ pub async fn bulk_delete_chunks_query(
                         .execute(conn)
                         .await?;
 
+                        diesel::delete(
+                            rag_answer_caches_columns::rag_answer_caches.filter(
+                                rag_answer_caches_columns::chunk_ids.overlaps_with(
+                                    deleted_chunks.into_iter().map(Some).collect_vec(),
+                                ),
+                            ),
+                        )
+                        .execute(conn)
+                        .await?;
+
                         Ok(point_ids)
                     }
                 }
@@ -664,7 +727,7 @@ pub async fn bulk_delete_chunks_query(
 
         match transaction_result {
             Ok(point_ids) => {
-                delete_points_from_qdrant(point_ids, qdrant_collection.clone()).await?;
+                delete_dataset_points_from_qdrant(point_ids, qdrant_collection.clone()).await?;
             }
             Err(e) => {
                 log::error!("Failed to delete chunks: {:?}", e);
@@ -1361,9 +1424,24 @@ pub async fn delete_chunk_metadata_query(
     dataset: Dataset,
     pool: web::Data<Pool>,
     dataset_config: DatasetConfiguration,
+    actor: Option<ChunkVersionActor>,
 ) -> Result<(), ServiceError> {
     use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
     use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
+    use crate::data::schema::chunk_metadata_versions::dsl as chunk_metadata_versions_columns;
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
+
+    let mut deleted_chunk_versions =
+        get_metadata_from_ids_query(chunk_uuid.clone(), dataset.id, pool.clone())
+            .await?
+            .into_iter()
+            .map(|chunk| {
+                ChunkMetadataVersionPG::from_details(chunk, ChunkVersionAction::Delete, actor)
+            })
+            .collect_vec();
+
+    set_chunk_version_group_ids_query(&mut deleted_chunk_versions, pool.clone()).await?;
+
     let mut conn = pool.get().await.map_err(|_e| {
         ServiceError::InternalServerError("Failed to get postgres connection".to_string())
     })?;
@@ -1384,14 +1462,39 @@ pub async fn delete_chunk_metadata_query(
                     .await?;
 
                     // if there were no collisions, just delete the chunk_metadata without issue
-                    let deleted_points = diesel::delete(
-                        chunk_metadata_columns::chunk_metadata
-                            .filter(chunk_metadata_columns::id.eq_any(chunk_uuid.clone()))
-                            .filter(chunk_metadata_columns::dataset_id.eq(dataset.id))
-                            .filter(chunk_metadata_columns::created_at.le(deleted_at)),
+                    let (deleted_chunks, deleted_points): (Vec<uuid::Uuid>, Vec<uuid::Uuid>) =
+                        diesel::delete(
+                            chunk_metadata_columns::chunk_metadata
+                                .filter(chunk_metadata_columns::id.eq_any(chunk_uuid.clone()))
+                                .filter(chunk_metadata_columns::dataset_id.eq(dataset.id))
+                                .filter(chunk_metadata_columns::created_at.le(deleted_at)),
+                        )
+                        .returning((
+                            chunk_metadata_columns::id,
+                            chunk_metadata_columns::qdrant_point_id,
+                        ))
+                        .get_results::<(uuid::Uuid, uuid::Uuid)>(conn)
+                        .await?
+                        .into_iter()
+                        .unzip();
+
+                    diesel::insert_into(chunk_metadata_versions_columns::chunk_metadata_versions)
+                        .values(
+                            deleted_chunk_versions
+                                .into_iter()
+                                .filter(|version| deleted_chunks.contains(&version.chunk_id))
+                                .collect_vec(),
+                        )
+                        .execute(conn)
+                        .await?;
+
+                    diesel::delete(
+                        rag_answer_caches_columns::rag_answer_caches.filter(
+                            rag_answer_caches_columns::chunk_ids
+                                .overlaps_with(deleted_chunks.into_iter().map(Some).collect_vec()),
+                        ),
                     )
-                    .returning(chunk_metadata_columns::qdrant_point_id)
-                    .get_results::<uuid::Uuid>(conn)
+                    .execute(conn)
                     .await?;
 
                     Ok(deleted_points)
@@ -1404,7 +1507,7 @@ pub async fn delete_chunk_metadata_query(
     let qdrant_collection = get_qdrant_collection_from_dataset_config(&dataset_config);
 
     match transaction_result {
-        Ok(deleted_points) => delete_points_from_qdrant(deleted_points, qdrant_collection)
+        Ok(deleted_points) => delete_dataset_points_from_qdrant(deleted_points, qdrant_collection)
             .await
             .map_err(|_e| {
                 ServiceError::BadRequest("Failed to delete chunk from qdrant".to_string())
@@ -1509,11 +1612,34 @@ pub fn get_slice_from_vec_string(vec: Vec<String>, index: usize) -> Result<Strin
     }
 }
 
-pub fn get_stop_words() -> Vec<String> {
-    include_str!("../stop-words.txt")
-        .lines()
-        .map(|x| x.to_string())
-        .collect()
+/// Stop words of a dataset's LANGUAGE, english uses the list in stop-words.txt and the other languages use tantivy's lists
+pub struct StopWords {
+    language: DatasetLanguage,
+    english_stop_words: Vec<String>,
+}
+
+impl StopWords {
+    pub fn contains(&self, word: &str) -> bool {
+        match self.language {
+            DatasetLanguage::English => self.english_stop_words.iter().any(|x| x == word),
+            language => is_stop_word(word, language),
+        }
+    }
+}
+
+pub fn get_stop_words(language: DatasetLanguage) -> StopWords {
+    let english_stop_words = match language {
+        DatasetLanguage::English => include_str!("../stop-words.txt")
+            .lines()
+            .map(|x| x.to_string())
+            .collect(),
+        _ => vec![],
+    };
+
+    StopWords {
+        language,
+        english_stop_words,
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
@@ -1534,6 +1660,7 @@ pub fn get_highlights_with_exact_match(
     window_size: Option<u32>,
     pre_tag: Option<String>,
     post_tag: Option<String>,
+    language: DatasetLanguage,
 ) -> Result<(ChunkMetadata, Vec<String>), ServiceError> {
     let content = convert_html_to_text(&(input.chunk_html.clone().unwrap_or_default()));
     let cleaned_query = query.replace(
@@ -1543,7 +1670,7 @@ pub fn get_highlights_with_exact_match(
     let pre_tag = pre_tag.unwrap_or("<mark><b>".to_string());
     let post_tag = post_tag.unwrap_or("</b></mark>".to_string());
 
-    let stop_words = get_stop_words();
+    let stop_words = get_stop_words(language);
     let query_parts_split_by_stop_words: Vec<String> = cleaned_query
         .split_whitespace()
         .collect_vec()
@@ -2142,6 +2269,7 @@ pub fn get_highlights(
     window_size: Option<u32>,
     pre_tag: Option<String>,
     post_tag: Option<String>,
+    language: DatasetLanguage,
 ) -> Result<(ChunkMetadata, Vec<String>), ServiceError> {
     let pre_tag = pre_tag.unwrap_or("<mark><b>".to_string());
     let post_tag = post_tag.unwrap_or("</b></mark>".to_string());
@@ -2152,22 +2280,40 @@ pub fn get_highlights(
     let split_content = content
         .split_inclusive(|c: char| delimiters.contains(&c.to_string()))
         .flat_map(|x| {
-            x.to_string()
-                .split_inclusive(' ')
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>()
+            // CJK text has no spaces between words so its phrases are measured in characters
+            let words = if language.is_cjk() {
+                x.chars().map(|c| c.to_string()).collect::<Vec<String>>()
+            } else {
+                x.split_inclusive(' ')
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+            };
+
+            words
                 .chunks(max_length.unwrap_or(5) as usize)
                 .map(|x| x.join(""))
                 .collect::<Vec<String>>()
         })
         .collect::<Vec<String>>();
 
-    split_content.iter().enumerate().for_each(|(i, x)| {
-        engine.insert(i, x);
-    });
+    // CJK phrases are matched on the same character n-grams that BM25 indexes them with
+    let results: Vec<usize> = if language.is_cjk() {
+        split_content.iter().enumerate().for_each(|(i, x)| {
+            let tokens = tokenize(x.clone(), language);
+            engine.insert_tokens(i, &tokens.iter().map(|x| x.as_str()).collect_vec());
+        });
+
+        let query_tokens = tokenize(query.clone(), language);
+        engine.search_tokens(&query_tokens.iter().map(|x| x.as_str()).collect_vec())
+    } else {
+        split_content.iter().enumerate().for_each(|(i, x)| {
+            engine.insert(i, x);
+        });
+
+        engine.search(&query)
+    };
 
     let new_output = input;
-    let results: Vec<usize> = engine.search(&query);
 
     let mut matched_idxs = vec![];
     let mut matched_idxs_set = HashSet::new();
@@ -2370,6 +2516,23 @@ pub async fn get_row_count_for_organization_id_query(
     Ok(chunk_metadata_count as usize)
 }
 
+/// Vector fields of a chunk have to be declared in the VECTOR_FIELDS of its dataset to be mapped to a vector slot.
+pub fn check_chunk_vector_fields(
+    vector_fields: &HashMap<String, String>,
+    dataset_config: &DatasetConfiguration,
+) -> Result<(), ServiceError> {
+    match vector_fields
+        .keys()
+        .find(|field| !dataset_config.VECTOR_FIELDS.contains(field))
+    {
+        Some(field) => Err(ServiceError::BadRequest(format!(
+            "Vector field {} is not one of the VECTOR_FIELDS of the dataset",
+            field
+        ))),
+        None => Ok(()),
+    }
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn create_chunk_metadata(
     chunks: Vec<ChunkReqPayload>,
@@ -2458,6 +2621,10 @@ pub async fn create_chunk_metadata(
                 .transpose()?
         };
 
+        if let Some(vector_fields) = &chunk.vector_fields {
+            check_chunk_vector_fields(vector_fields, &dataset_configuration)?;
+        }
+
         let chunk_metadata = ChunkMetadata::from_details(
             &chunk.chunk_html.clone(),
             &chunk.link,
@@ -2533,6 +2700,7 @@ pub async fn create_chunk_metadata(
             attempt_number: 0,
             dataset_id: dataset_uuid,
             ingestion_messages,
+            actor: None,
         },
         chunk_metadatas,
     ))
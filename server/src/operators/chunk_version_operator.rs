@@ -0,0 +1,452 @@
+use crate::{
+    data::models::{
+        ChunkMetadata, ChunkMetadataVersion, ChunkMetadataVersionPG, ChunkVersionActor,
+        IngestSpecificChunkMetadata, Pool, UnifiedId, UpdateSpecificChunkMetadata,
+    },
+    errors::ServiceError,
+    handlers::chunk_handler::{
+        BulkUploadIngestionMessage, ChunkReqPayload, UpdateIngestionMessage, UploadIngestionMessage,
+    },
+};
+use actix_web::web;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Fills in the groups each versioned chunk currently belongs to, must run before the chunk's bookmarks are changed or deleted
+#[tracing::instrument(skip(versions, pool))]
+pub async fn set_chunk_version_group_ids_query(
+    versions: &mut [ChunkMetadataVersionPG],
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
+
+    if versions.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let chunk_ids = versions
+        .iter()
+        .map(|version| version.chunk_id)
+        .collect::<Vec<uuid::Uuid>>();
+
+    let bookmarks = chunk_group_bookmarks_columns::chunk_group_bookmarks
+        .filter(chunk_group_bookmarks_columns::chunk_metadata_id.eq_any(chunk_ids))
+        .select((
+            chunk_group_bookmarks_columns::chunk_metadata_id,
+            chunk_group_bookmarks_columns::group_id,
+        ))
+        .load::<(uuid::Uuid, uuid::Uuid)>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get chunk group bookmarks {:?}", err);
+            ServiceError::BadRequest("Failed to get chunk group bookmarks".to_string())
+        })?;
+
+    for version in versions.iter_mut() {
+        version.group_ids = Some(
+            bookmarks
+                .iter()
+                .filter(|(chunk_id, _)| *chunk_id == version.chunk_id)
+                .map(|(_, group_id)| Some(*group_id))
+                .collect(),
+        );
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn insert_chunk_metadata_versions_query(
+    mut versions: Vec<ChunkMetadataVersionPG>,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::chunk_metadata_versions::dsl as chunk_metadata_versions_columns;
+
+    if versions.is_empty() {
+        return Ok(());
+    }
+
+    set_chunk_version_group_ids_query(&mut versions, pool.clone()).await?;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::insert_into(chunk_metadata_versions_columns::chunk_metadata_versions)
+        .values(&versions)
+        .execute(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to insert chunk metadata versions {:?}", err);
+            ServiceError::BadRequest("Failed to insert chunk metadata versions".to_string())
+        })?;
+
+    Ok(())
+}
+
+/// Versions of the chunk, newest first, along with the total number of versions
+#[tracing::instrument(skip(pool))]
+pub async fn get_chunk_versions_query(
+    chunk_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    page: u64,
+    page_size: u64,
+    pool: web::Data<Pool>,
+) -> Result<(Vec<ChunkMetadataVersion>, i64), ServiceError> {
+    use crate::data::schema::chunk_metadata_versions::dsl as chunk_metadata_versions_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let versions = chunk_metadata_versions_columns::chunk_metadata_versions
+        .filter(chunk_metadata_versions_columns::chunk_id.eq(chunk_id))
+        .filter(chunk_metadata_versions_columns::dataset_id.eq(dataset_id))
+        .order_by(chunk_metadata_versions_columns::created_at.desc())
+        .offset(((page.max(1) - 1) * page_size) as i64)
+        .limit(page_size as i64)
+        .select(ChunkMetadataVersionPG::as_select())
+        .load::<ChunkMetadataVersionPG>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get chunk versions {:?}", err);
+            ServiceError::BadRequest("Failed to get chunk versions".to_string())
+        })?
+        .into_iter()
+        .map(ChunkMetadataVersion::from)
+        .collect();
+
+    let total_count = chunk_metadata_versions_columns::chunk_metadata_versions
+        .filter(chunk_metadata_versions_columns::chunk_id.eq(chunk_id))
+        .filter(chunk_metadata_versions_columns::dataset_id.eq(dataset_id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to count chunk versions {:?}", err);
+            ServiceError::BadRequest("Failed to count chunk versions".to_string())
+        })?;
+
+    Ok((versions, total_count))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_chunk_version_by_id_query(
+    version_id: uuid::Uuid,
+    chunk_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<ChunkMetadataVersion, ServiceError> {
+    use crate::data::schema::chunk_metadata_versions::dsl as chunk_metadata_versions_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    chunk_metadata_versions_columns::chunk_metadata_versions
+        .filter(chunk_metadata_versions_columns::id.eq(version_id))
+        .filter(chunk_metadata_versions_columns::chunk_id.eq(chunk_id))
+        .filter(chunk_metadata_versions_columns::dataset_id.eq(dataset_id))
+        .select(ChunkMetadataVersionPG::as_select())
+        .first::<ChunkMetadataVersionPG>(&mut conn)
+        .await
+        .optional()
+        .map_err(|err| {
+            log::error!("Failed to get chunk version {:?}", err);
+            ServiceError::BadRequest("Failed to get chunk version".to_string())
+        })?
+        .map(ChunkMetadataVersion::from)
+        .ok_or_else(|| {
+            ServiceError::NotFound(format!(
+                "Version {} of chunk {} not found",
+                version_id, chunk_id
+            ))
+        })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[schema(example = json!({
+    "field": "chunk_html",
+    "from": "<p>Hello, world!</p>",
+    "to": "<p>Hello, Trieve!</p>",
+}))]
+pub struct ChunkVersionFieldDiff {
+    /// Name of the chunk field which differs between the two versions
+    pub field: String,
+    /// Value of the field in the older version
+    pub from: serde_json::Value,
+    /// Value of the field in the newer version
+    pub to: serde_json::Value,
+}
+
+const VERSIONED_CHUNK_FIELDS: [&str; 11] = [
+    "link",
+    "chunk_html",
+    "metadata",
+    "tracking_id",
+    "time_stamp",
+    "location",
+    "image_urls",
+    "tag_set",
+    "num_value",
+    "weight",
+    "group_ids",
+];
+
+/// Fields of the chunk whose values differ between the two versions
+pub fn get_chunk_version_changes(
+    from: &ChunkMetadataVersion,
+    to: &ChunkMetadataVersion,
+) -> Vec<ChunkVersionFieldDiff> {
+    let from = serde_json::json!(from);
+    let to = serde_json::json!(to);
+
+    VERSIONED_CHUNK_FIELDS
+        .iter()
+        .filter_map(|field| {
+            let from_value = from.get(field).cloned().unwrap_or_default();
+            let to_value = to.get(field).cloned().unwrap_or_default();
+
+            (from_value != to_value).then(|| ChunkVersionFieldDiff {
+                field: field.to_string(),
+                from: from_value,
+                to: to_value,
+            })
+        })
+        .collect()
+}
+
+/// Ingestion message which updates a chunk that still exists back to the content and groups of the version
+pub fn get_restore_update_message(
+    version: ChunkMetadataVersion,
+    existing_chunk: &ChunkMetadata,
+    group_ids: Option<Vec<uuid::Uuid>>,
+    actor: ChunkVersionActor,
+) -> UpdateIngestionMessage {
+    UpdateIngestionMessage {
+        chunk_metadata: UpdateSpecificChunkMetadata {
+            id: version.chunk_id,
+            link: version.link,
+            qdrant_point_id: existing_chunk.qdrant_point_id,
+            created_at: existing_chunk.created_at,
+            updated_at: chrono::Utc::now().naive_local(),
+            chunk_html: version.chunk_html,
+            metadata: version.metadata,
+            tracking_id: version.tracking_id,
+            time_stamp: version.time_stamp,
+            dataset_id: version.dataset_id,
+            weight: version.weight,
+            location: version.location,
+            image_urls: version
+                .image_urls
+                .map(|urls| urls.into_iter().map(Some).collect()),
+            tag_set: Some(
+                version
+                    .tag_set
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Some)
+                    .collect(),
+            ),
+            num_value: version.num_value,
+        },
+        dataset_id: version.dataset_id,
+        group_ids: group_ids
+            .map(|group_ids| group_ids.into_iter().map(UnifiedId::TrieveUuid).collect()),
+        convert_html_to_text: None,
+        fulltext_boost: None,
+        semantic_boost: None,
+        vector_fields: None,
+        actor: Some(actor),
+    }
+}
+
+/// Ingestion message which re-creates a deleted chunk under its original id with the content and groups of the version
+pub fn get_restore_upload_message(
+    version: ChunkMetadataVersion,
+    group_ids: Option<Vec<uuid::Uuid>>,
+    actor: ChunkVersionActor,
+) -> BulkUploadIngestionMessage {
+    BulkUploadIngestionMessage {
+        attempt_number: 0,
+        dataset_id: version.dataset_id,
+        ingestion_messages: vec![UploadIngestionMessage {
+            ingest_specific_chunk_metadata: IngestSpecificChunkMetadata {
+                id: version.chunk_id,
+                dataset_id: version.dataset_id,
+                qdrant_point_id: uuid::Uuid::new_v4(),
+            },
+            chunk: ChunkReqPayload {
+                chunk_html: version.chunk_html,
+                semantic_content: None,
+                link: version.link,
+                tag_set: version.tag_set,
+                num_value: version.num_value,
+                metadata: version.metadata,
+                tracking_id: version.tracking_id,
+                upsert_by_tracking_id: None,
+                group_ids,
+                group_tracking_ids: None,
+                time_stamp: version
+                    .time_stamp
+                    .map(|time_stamp| time_stamp.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+                location: version.location,
+                image_urls: version.image_urls,
+                weight: Some(version.weight),
+                split_avg: None,
+                convert_html_to_text: None,
+                fulltext_boost: None,
+                semantic_boost: None,
+                vector_fields: None,
+            },
+            dataset_id: version.dataset_id,
+            upsert_by_tracking_id: false,
+        }],
+        actor: Some(actor),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::models::ChunkVersionAction;
+
+    fn version(
+        chunk_html: &str,
+        tag_set: Vec<&str>,
+        group_ids: Vec<uuid::Uuid>,
+    ) -> ChunkMetadataVersion {
+        ChunkMetadataVersion {
+            id: uuid::Uuid::new_v4(),
+            chunk_id: uuid::Uuid::nil(),
+            dataset_id: uuid::Uuid::new_v4(),
+            action: ChunkVersionAction::Update,
+            link: Some("https://trieve.ai".to_string()),
+            chunk_html: Some(chunk_html.to_string()),
+            metadata: Some(serde_json::json!({"key": "value"})),
+            tracking_id: Some("tracking".to_string()),
+            time_stamp: chrono::NaiveDate::from_ymd_opt(2021, 1, 1)
+                .and_then(|date| date.and_hms_opt(0, 0, 0)),
+            location: None,
+            image_urls: Some(vec!["https://trieve.ai/image.png".to_string()]),
+            tag_set: Some(tag_set.into_iter().map(|tag| tag.to_string()).collect()),
+            num_value: Some(1.0),
+            weight: 2.0,
+            actor: ChunkVersionActor::default(),
+            created_at: chrono::Utc::now().naive_local(),
+            group_ids: Some(group_ids),
+        }
+    }
+
+    #[test]
+    fn changes_list_only_fields_which_differ() {
+        let group_id = uuid::Uuid::new_v4();
+        let from = version("<p>Hello, world!</p>", vec!["a"], vec![]);
+        let to = version("<p>Hello, Trieve!</p>", vec!["a", "b"], vec![group_id]);
+
+        let changes = get_chunk_version_changes(&from, &to);
+        let fields: Vec<&str> = changes.iter().map(|change| change.field.as_str()).collect();
+        assert_eq!(fields, vec!["chunk_html", "tag_set", "group_ids"]);
+
+        assert_eq!(changes[0].from, serde_json::json!("<p>Hello, world!</p>"));
+        assert_eq!(changes[0].to, serde_json::json!("<p>Hello, Trieve!</p>"));
+        assert_eq!(changes[2].from, serde_json::json!([]));
+        assert_eq!(changes[2].to, serde_json::json!([group_id]));
+    }
+
+    #[test]
+    fn identical_versions_have_no_changes() {
+        let from = version("<p>Hello</p>", vec!["a"], vec![uuid::Uuid::nil()]);
+        let mut to = from.clone();
+        to.id = uuid::Uuid::new_v4();
+        to.action = ChunkVersionAction::Delete;
+
+        assert!(get_chunk_version_changes(&from, &to).is_empty());
+    }
+
+    #[test]
+    fn restoring_existing_chunk_updates_content_and_groups() {
+        let group_id = uuid::Uuid::new_v4();
+        let version = version("<p>Old</p>", vec!["a", "b"], vec![group_id]);
+        let existing_chunk = ChunkMetadata::from_details(
+            &Some("<p>New</p>".to_string()),
+            &None,
+            &None,
+            uuid::Uuid::new_v4(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            version.dataset_id,
+            0.0,
+            None,
+        );
+        let actor = ChunkVersionActor {
+            user_id: Some(uuid::Uuid::new_v4()),
+            api_key_id: None,
+        };
+
+        let message = get_restore_update_message(
+            version.clone(),
+            &existing_chunk,
+            Some(vec![group_id]),
+            actor,
+        );
+
+        let chunk = &message.chunk_metadata;
+        assert_eq!(chunk.id, version.chunk_id);
+        assert_eq!(chunk.qdrant_point_id, existing_chunk.qdrant_point_id);
+        assert_eq!(chunk.chunk_html, version.chunk_html);
+        assert_eq!(chunk.link, version.link);
+        assert_eq!(chunk.metadata, version.metadata);
+        assert_eq!(chunk.time_stamp, version.time_stamp);
+        assert_eq!(chunk.weight, version.weight);
+        assert_eq!(
+            chunk.tag_set,
+            Some(vec![Some("a".to_string()), Some("b".to_string())])
+        );
+        assert_eq!(
+            message
+                .group_ids
+                .map(|ids| ids.iter().filter_map(|id| id.as_uuid()).collect::<Vec<_>>()),
+            Some(vec![group_id])
+        );
+        assert_eq!(message.actor, Some(actor));
+    }
+
+    #[test]
+    fn restoring_deleted_chunk_recreates_it_with_its_groups() {
+        let group_id = uuid::Uuid::new_v4();
+        let version = version("<p>Deleted</p>", vec!["a"], vec![group_id]);
+
+        let message = get_restore_upload_message(
+            version.clone(),
+            Some(vec![group_id]),
+            ChunkVersionActor::default(),
+        );
+
+        assert_eq!(message.ingestion_messages.len(), 1);
+        let ingestion_message = &message.ingestion_messages[0];
+        assert_eq!(
+            ingestion_message.ingest_specific_chunk_metadata.id,
+            version.chunk_id
+        );
+        assert!(!ingestion_message.upsert_by_tracking_id);
+
+        let chunk = &ingestion_message.chunk;
+        assert_eq!(chunk.chunk_html, version.chunk_html);
+        assert_eq!(chunk.tracking_id, version.tracking_id);
+        assert_eq!(chunk.tag_set, version.tag_set);
+        assert_eq!(chunk.image_urls, version.image_urls);
+        assert_eq!(chunk.weight, Some(version.weight));
+        assert_eq!(chunk.time_stamp.as_deref(), Some("2021-01-01 00:00:00"));
+        assert_eq!(chunk.group_ids, Some(vec![group_id]));
+    }
+}
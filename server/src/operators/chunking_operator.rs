@@ -0,0 +1,603 @@
+use super::crawl_operator::html_heading_sections;
+use super::file_parser_operator::{
+    format_delimited_row, parse_delimited_row_spans, ParsedFile, ParsedFileSource,
+};
+use super::parse_operator::{
+    build_chunking_regex, coarse_doc_chunker, coarse_remove_large_chunks, convert_html_to_text,
+};
+use crate::errors::ServiceError;
+use crate::handlers::file_handler::{ChunkingStrategy, UploadFileReqPayload};
+use regex::Regex;
+
+pub const DEFAULT_MAX_TOKENS_PER_CHUNK: usize = 300;
+pub const DEFAULT_TOKEN_OVERLAP: usize = 50;
+
+/// A chunk of a file along with where it came from in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileChunk {
+    pub chunk_html: String,
+    pub heading_path: Vec<String>,
+    pub page_number: Option<u32>,
+    /// Character offsets of the chunk. Strategies which split the decoded source (HTML, Markdown or table rows) are relative to the source, the others are relative to the parsed text where sections are joined by a blank line.
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+fn char_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset].chars().count()
+}
+
+/// Character offset of each section within the parsed text of the file.
+fn section_char_offsets(parsed_file: &ParsedFile) -> Vec<usize> {
+    let mut offset = 0;
+    parsed_file
+        .sections
+        .iter()
+        .map(|section| {
+            let section_offset = offset;
+            offset += section.text.chars().count() + 2;
+            section_offset
+        })
+        .collect()
+}
+
+/// How many characters of the source are searched for the next character of a chunk before the chunk character is considered to be missing from the source.
+const LOCATE_CHUNK_LOOKAHEAD: usize = 32;
+
+/// Byte range of the source text which a chunk derived from it covers, searching from `from`. Chunkers may replace newlines, strip markup or decode entities so whitespace is ignored, source characters which are not in the chunk are skipped and chunk characters which can not be found nearby are ignored.
+fn locate_chunk(source: &str, from: usize, chunk: &str) -> (usize, usize) {
+    let mut source_chars = source[from..]
+        .char_indices()
+        .map(|(i, c)| (from + i, c))
+        .filter(|(_, c)| !c.is_whitespace());
+    let mut range: Option<(usize, usize)> = None;
+
+    for chunk_char in chunk.chars().filter(|c| !c.is_whitespace()) {
+        let lookahead = source_chars
+            .clone()
+            .take(LOCATE_CHUNK_LOOKAHEAD)
+            .position(|(_, c)| c == chunk_char);
+
+        if let Some(skip) = lookahead {
+            let (i, c) = source_chars
+                .nth(skip)
+                .expect("position is within the iterator");
+            let end = i + c.len_utf8();
+            range = Some(range.map_or((i, end), |(start, _)| (start, end)));
+        }
+    }
+
+    range.unwrap_or((from, from))
+}
+
+/// Splits each section on the delimiter regex. The delimiter chunker cleans the text it splits, so each chunk is located in the section text to get its offsets.
+pub fn chunk_by_delimiter(
+    parsed_file: &ParsedFile,
+    split_regex: Option<Regex>,
+    rebalance_chunks: bool,
+    target_splits_per_chunk: usize,
+) -> Vec<FileChunk> {
+    let section_offsets = section_char_offsets(parsed_file);
+    let mut chunks = vec![];
+
+    for (section, section_offset) in parsed_file.sections.iter().zip(section_offsets) {
+        let mut search_start = 0;
+        for chunk_html in coarse_doc_chunker(
+            section.text.clone(),
+            split_regex.clone(),
+            rebalance_chunks,
+            target_splits_per_chunk,
+        ) {
+            let (byte_start, byte_end) = locate_chunk(&section.text, search_start, &chunk_html);
+            search_start = byte_end;
+            chunks.push(FileChunk {
+                chunk_html,
+                heading_path: section.heading_path.clone(),
+                page_number: section.page_number,
+                char_start: section_offset + char_offset(&section.text, byte_start),
+                char_end: section_offset + char_offset(&section.text, byte_end),
+            });
+        }
+    }
+
+    chunks
+}
+
+/// One chunk per section, with oversized sections split evenly.
+fn chunk_by_section(parsed_file: &ParsedFile) -> Vec<FileChunk> {
+    let section_offsets = section_char_offsets(parsed_file);
+    let mut chunks = vec![];
+
+    for (section, section_offset) in parsed_file.sections.iter().zip(section_offsets) {
+        let mut search_start = 0;
+        for chunk_html in coarse_remove_large_chunks(vec![section.text.clone()]) {
+            let (byte_start, byte_end) = locate_chunk(&section.text, search_start, &chunk_html);
+            search_start = byte_end;
+            chunks.push(FileChunk {
+                chunk_html,
+                heading_path: section.heading_path.clone(),
+                page_number: section.page_number,
+                char_start: section_offset + char_offset(&section.text, byte_start),
+                char_end: section_offset + char_offset(&section.text, byte_end),
+            });
+        }
+    }
+
+    chunks
+}
+
+/// One chunk per heading section of the HTML, split at the same headings as crawled pages. Unlike crawled pages, content before the first heading and short sections are kept such that no part of the file is lost.
+pub fn chunk_html_by_heading_hierarchy(html: &str) -> Vec<FileChunk> {
+    html_heading_sections(html)
+        .into_iter()
+        .filter_map(|(heading_path, range)| {
+            let section = &html[range.clone()];
+            let trimmed_section = section.trim();
+            if convert_html_to_text(trimmed_section).trim().is_empty() {
+                return None;
+            }
+
+            let byte_start = range.start + (section.len() - section.trim_start().len());
+            let byte_end = byte_start + trimmed_section.len();
+
+            Some(FileChunk {
+                chunk_html: trimmed_section.to_string(),
+                heading_path,
+                page_number: None,
+                char_start: char_offset(html, byte_start),
+                char_end: char_offset(html, byte_end),
+            })
+        })
+        .collect()
+}
+
+pub fn chunk_by_heading_hierarchy(parsed_file: &ParsedFile) -> Vec<FileChunk> {
+    match &parsed_file.source {
+        Some(ParsedFileSource::Html(html)) => chunk_html_by_heading_hierarchy(html),
+        _ => chunk_by_section(parsed_file),
+    }
+}
+
+/// Byte ranges of the whitespace separated words in the text.
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = vec![];
+    let mut word_start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), word_start) {
+            (true, Some(start)) => {
+                spans.push((start, i));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(i),
+            _ => {}
+        }
+    }
+
+    if let Some(start) = word_start {
+        spans.push((start, text.len()));
+    }
+
+    spans
+}
+
+/// Byte ranges of the words in the text along with how many tokens each takes up. Tokens are counted with cl100k_base, the tokenizer of OpenAI's embedding models, which other embedding models are close to.
+/// Words of more than `max_tokens` tokens, like long runs of CJK text, are split into pieces of `max_tokens` tokens.
+fn token_counted_spans(text: &str, max_tokens: usize) -> Vec<(usize, usize, usize)> {
+    let bpe = tiktoken_rs::cl100k_base_singleton();
+    let bpe = bpe.lock();
+    let mut spans = vec![];
+
+    for (word_start, word_end) in word_spans(text) {
+        let tokens = bpe.encode_ordinary(&text[word_start..word_end]);
+        if tokens.len() <= max_tokens {
+            spans.push((word_start, word_end, tokens.len()));
+            continue;
+        }
+
+        let token_lengths = bpe
+            ._decode_native_and_split(tokens)
+            .map(|token_bytes| token_bytes.len())
+            .collect::<Vec<usize>>();
+
+        let mut piece_start = word_start;
+        let mut tokens_end = word_start;
+        for piece_lengths in token_lengths.chunks(max_tokens) {
+            tokens_end += piece_lengths.iter().sum::<usize>();
+            // Tokens can end within a character, pieces end after it
+            let mut piece_end = tokens_end.min(word_end);
+            while !text.is_char_boundary(piece_end) {
+                piece_end += 1;
+            }
+            if piece_end > piece_start {
+                spans.push((piece_start, piece_end, piece_lengths.len()));
+            }
+            piece_start = piece_end;
+        }
+    }
+
+    spans
+}
+
+/// Windows of at most `max_tokens` embedding tokens within each section, where consecutive windows share up to `overlap` tokens. Windows start and end at whitespace unless a single word takes up more than `max_tokens` tokens.
+pub fn chunk_by_token_window(
+    parsed_file: &ParsedFile,
+    max_tokens: usize,
+    overlap: usize,
+) -> Vec<FileChunk> {
+    let max_tokens = max_tokens.max(1);
+    let section_offsets = section_char_offsets(parsed_file);
+    let mut chunks = vec![];
+
+    for (section, section_offset) in parsed_file.sections.iter().zip(section_offsets) {
+        let spans = token_counted_spans(&section.text, max_tokens);
+        let mut window_start = 0;
+
+        while window_start < spans.len() {
+            let mut window_end = window_start;
+            let mut window_tokens = 0;
+            while window_end < spans.len()
+                && (window_end == window_start || window_tokens + spans[window_end].2 <= max_tokens)
+            {
+                window_tokens += spans[window_end].2;
+                window_end += 1;
+            }
+
+            let byte_start = spans[window_start].0;
+            let byte_end = spans[window_end - 1].1;
+
+            chunks.push(FileChunk {
+                chunk_html: section.text[byte_start..byte_end].to_string(),
+                heading_path: section.heading_path.clone(),
+                page_number: section.page_number,
+                char_start: section_offset + char_offset(&section.text, byte_start),
+                char_end: section_offset + char_offset(&section.text, byte_end),
+            });
+
+            if window_end == spans.len() {
+                break;
+            }
+
+            // The next window starts with the last words of this one which fit in the overlap, and always moves forward
+            let mut next_start = window_end;
+            let mut overlap_tokens = 0;
+            while next_start > window_start + 1
+                && overlap_tokens + spans[next_start - 1].2 <= overlap
+            {
+                overlap_tokens += spans[next_start - 1].2;
+                next_start -= 1;
+            }
+            window_start = next_start;
+        }
+    }
+
+    chunks
+}
+
+/// One chunk per ATX heading section of the Markdown source, ignoring headings inside fenced code blocks.
+pub fn chunk_markdown_sections(markdown: &str) -> Vec<FileChunk> {
+    let mut chunks = vec![];
+    let mut headings: Vec<(usize, String)> = vec![];
+    let mut section_start = 0;
+    let mut line_start = 0;
+    let mut in_code_block = false;
+
+    let mut push_section = |start: usize, end: usize, headings: &[(usize, String)]| {
+        let section = &markdown[start..end];
+        let trimmed_section = section.trim();
+        if trimmed_section.is_empty() {
+            return;
+        }
+
+        let byte_start = start + (section.len() - section.trim_start().len());
+        let byte_end = byte_start + trimmed_section.len();
+        chunks.push(FileChunk {
+            chunk_html: trimmed_section.to_string(),
+            heading_path: headings
+                .iter()
+                .map(|(_, heading)| heading.clone())
+                .collect(),
+            page_number: None,
+            char_start: char_offset(markdown, byte_start),
+            char_end: char_offset(markdown, byte_end),
+        });
+    };
+
+    for line in markdown.split_inclusive('\n') {
+        let trimmed_line = line.trim();
+        if trimmed_line.starts_with("```") || trimmed_line.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+
+        let level = trimmed_line.chars().take_while(|c| *c == '#').count();
+        let is_heading =
+            !in_code_block && (1..=6).contains(&level) && trimmed_line[level..].starts_with(' ');
+
+        if is_heading {
+            push_section(section_start, line_start, &headings);
+
+            while headings
+                .last()
+                .is_some_and(|(last_level, _)| *last_level >= level)
+            {
+                headings.pop();
+            }
+            headings.push((
+                level,
+                trimmed_line[level..]
+                    .trim()
+                    .trim_end_matches('#')
+                    .trim()
+                    .to_string(),
+            ));
+            section_start = line_start;
+        }
+
+        line_start += line.len();
+    }
+
+    push_section(section_start, markdown.len(), &headings);
+
+    chunks
+}
+
+/// One chunk per data row where every value is prefixed by its column header.
+pub fn chunk_table_rows(text: &str, delimiter: char) -> Vec<FileChunk> {
+    let mut rows = parse_delimited_row_spans(text, delimiter).into_iter();
+    let headers = match rows.next() {
+        Some((_, headers)) => headers,
+        None => return vec![],
+    };
+
+    rows.map(|(span, row)| FileChunk {
+        chunk_html: format_delimited_row(&headers, &row),
+        heading_path: vec![],
+        page_number: None,
+        char_start: char_offset(text, span.start),
+        char_end: char_offset(text, span.end),
+    })
+    .collect()
+}
+
+fn chunk_by_upload_delimiters(
+    parsed_file: &ParsedFile,
+    upload_file_data: &UploadFileReqPayload,
+) -> Result<Vec<FileChunk>, ServiceError> {
+    let split_regex: Option<Regex> = upload_file_data
+        .split_delimiters
+        .clone()
+        .map(|delimiters| {
+            build_chunking_regex(delimiters).map_err(|e| {
+                log::error!("Could not parse chunking delimiters {:?}", e);
+                ServiceError::BadRequest("Could not parse chunking delimiters".to_string())
+            })
+        })
+        .transpose()?;
+
+    Ok(chunk_by_delimiter(
+        parsed_file,
+        split_regex,
+        upload_file_data.rebalance_chunks.unwrap_or(true),
+        upload_file_data.target_splits_per_chunk.unwrap_or(20),
+    ))
+}
+
+/// Splits a parsed file into chunks with the strategy requested in the upload payload.
+pub fn chunk_parsed_file(
+    parsed_file: &ParsedFile,
+    upload_file_data: &UploadFileReqPayload,
+) -> Result<Vec<FileChunk>, ServiceError> {
+    let chunks = match upload_file_data.chunking_strategy.unwrap_or_default() {
+        ChunkingStrategy::Delimiter => chunk_by_upload_delimiters(parsed_file, upload_file_data)?,
+        ChunkingStrategy::HeadingHierarchy => chunk_by_heading_hierarchy(parsed_file),
+        ChunkingStrategy::TokenWindow => chunk_by_token_window(
+            parsed_file,
+            upload_file_data
+                .max_tokens_per_chunk
+                .unwrap_or(DEFAULT_MAX_TOKENS_PER_CHUNK),
+            upload_file_data
+                .token_overlap
+                .unwrap_or(DEFAULT_TOKEN_OVERLAP),
+        ),
+        ChunkingStrategy::MarkdownSections => match &parsed_file.source {
+            Some(ParsedFileSource::Markdown(markdown)) => chunk_markdown_sections(markdown),
+            _ => chunk_by_heading_hierarchy(parsed_file),
+        },
+        ChunkingStrategy::TableRows => match &parsed_file.source {
+            Some(ParsedFileSource::Table { text, delimiter }) => chunk_table_rows(text, *delimiter),
+            _ => chunk_by_upload_delimiters(parsed_file, upload_file_data)?,
+        },
+    };
+
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::operators::file_parser_operator::ParsedFileSection;
+
+    fn parsed_file(sections: &[&str]) -> ParsedFile {
+        ParsedFile {
+            sections: sections
+                .iter()
+                .map(|text| ParsedFileSection {
+                    text: text.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            source: None,
+        }
+    }
+
+    /// Text the offsets of section based strategies refer to
+    fn parsed_text(parsed_file: &ParsedFile) -> String {
+        parsed_file
+            .sections
+            .iter()
+            .map(|section| section.text.clone())
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+
+    fn char_slice(text: &str, chunk: &FileChunk) -> String {
+        text.chars()
+            .skip(chunk.char_start)
+            .take(chunk.char_end - chunk.char_start)
+            .collect()
+    }
+
+    #[test]
+    fn test_delimiter_offsets_point_into_section_text() {
+        let file = parsed_file(&[
+            "First line.\nSecond <b>bold</b> line.",
+            "Über café. Naïve end.",
+        ]);
+        let text = parsed_text(&file);
+
+        let chunks = chunk_by_delimiter(&file, None, false, 1);
+        let slices = chunks
+            .iter()
+            .map(|chunk| char_slice(&text, chunk))
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            slices,
+            vec![
+                "First line.",
+                "Second <b>bold</b> line.",
+                "Über café.",
+                "Naïve end.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_heading_hierarchy_keeps_preamble_and_short_sections() {
+        let html = "<p>Intro text</p>\n<h1>Title</h1><p>Short</p>\n<h2>Sub</h2><p>Body é</p>\n";
+
+        let chunks = chunk_html_by_heading_hierarchy(html);
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.heading_path.clone(), chunk.chunk_html.as_str()))
+                .collect::<Vec<(Vec<String>, &str)>>(),
+            vec![
+                (vec![], "<p>Intro text</p>"),
+                (vec!["Title".to_string()], "<h1>Title</h1><p>Short</p>"),
+                (
+                    vec!["Title".to_string(), "Sub".to_string()],
+                    "<h2>Sub</h2><p>Body é</p>"
+                ),
+            ]
+        );
+        for chunk in chunks.iter() {
+            assert_eq!(char_slice(html, chunk), chunk.chunk_html);
+        }
+    }
+
+    #[test]
+    fn test_token_window_offsets() {
+        let file = parsed_file(&["zero", "one  two\nthree four"]);
+        let text = parsed_text(&file);
+
+        let chunks = chunk_by_token_window(&file, 2, 1);
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| char_slice(&text, chunk))
+                .collect::<Vec<String>>(),
+            vec!["zero", "one  two", "two\nthree", "three four"]
+        );
+        assert!(chunks
+            .iter()
+            .all(|chunk| char_slice(&text, chunk) == chunk.chunk_html));
+    }
+
+    #[test]
+    fn test_token_window_counts_embedding_tokens() {
+        let long_word = "antidisestablishmentarianism";
+        let long_word_tokens = tiktoken_rs::cl100k_base_singleton()
+            .lock()
+            .encode_ordinary(long_word)
+            .len();
+        assert!(long_word_tokens > 2);
+
+        let file = parsed_file(&[&format!("a {} b", long_word)]);
+
+        let chunks = chunk_by_token_window(&file, long_word_tokens, 0);
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.chunk_html.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["a", long_word, "b"]
+        );
+    }
+
+    #[test]
+    fn test_token_window_splits_words_over_the_limit() {
+        let cjk = "東京都は日本の首都であり世界で最も人口の多い都市圏の一つです";
+
+        let spans = token_counted_spans(cjk, 4);
+
+        assert!(spans.len() > 1);
+        assert!(spans.iter().all(|(_, _, tokens)| *tokens <= 4));
+        assert_eq!(
+            spans
+                .iter()
+                .map(|(start, end, _)| &cjk[*start..*end])
+                .collect::<String>(),
+            cjk
+        );
+
+        let file = parsed_file(&[cjk]);
+        let text = parsed_text(&file);
+        let chunks = chunk_by_token_window(&file, 4, 0);
+
+        assert_eq!(chunks.len(), spans.len());
+        for chunk in chunks.iter() {
+            assert_eq!(char_slice(&text, chunk), chunk.chunk_html);
+        }
+    }
+
+    #[test]
+    fn test_markdown_section_offsets() {
+        let markdown = "Intro\n\n# A\nText\n```\n# not a heading\n```\n## B\nMore\n";
+
+        let chunks = chunk_markdown_sections(markdown);
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.heading_path.clone())
+                .collect::<Vec<Vec<String>>>(),
+            vec![
+                vec![],
+                vec!["A".to_string()],
+                vec!["A".to_string(), "B".to_string()],
+            ]
+        );
+        for chunk in chunks.iter() {
+            assert_eq!(char_slice(markdown, chunk), chunk.chunk_html);
+        }
+    }
+
+    #[test]
+    fn test_table_row_offsets() {
+        let text = "name,city\nBob,Köln\n\"Ann\nMarie\",Paris\n";
+
+        let chunks = chunk_table_rows(text, ',');
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| char_slice(text, chunk))
+                .collect::<Vec<String>>(),
+            vec!["Bob,Köln", "\"Ann\nMarie\",Paris"]
+        );
+    }
+}
@@ -4,8 +4,8 @@ use tokio::sync::mpsc;
 
 use crate::{
     data::models::{
-        RagQueryEventClickhouse, RecommendationEventClickhouse, SearchQueryEventClickhouse,
-        WorkerEventClickhouse,
+        AuditEventClickhouse, RagQueryEventClickhouse, RecommendationEventClickhouse,
+        SearchQueryEventClickhouse, WorkerEventClickhouse,
     },
     errors::ServiceError,
 };
@@ -16,6 +16,7 @@ pub enum ClickHouseEvent {
     RecommendationEvent(RecommendationEventClickhouse),
     RagQueryEvent(RagQueryEventClickhouse),
     WorkerEvent(WorkerEventClickhouse),
+    AuditEvent(AuditEventClickhouse),
 }
 
 pub fn get_latency_from_header(header: String) -> f32 {
@@ -59,6 +60,12 @@ pub async fn send_to_clickhouse(
         ServiceError::InternalServerError(format!("Error inserting recommendations: {:?}", e))
     })?;
 
+    let mut audit_events_inserter = clickhouse_client.insert("audit_events").map_err(|e| {
+        log::error!("Error inserting audit events: {:?}", e);
+        sentry::capture_message("Error inserting audit events", sentry::Level::Error);
+        ServiceError::InternalServerError(format!("Error inserting audit events: {:?}", e))
+    })?;
+
     for event in events {
         match event {
             ClickHouseEvent::SearchQueryEvent(mut event) => {
@@ -133,6 +140,13 @@ pub async fn send_to_clickhouse(
                     ))
                 })?;
             }
+            ClickHouseEvent::AuditEvent(event) => {
+                audit_events_inserter.write(&event).await.map_err(|e| {
+                    log::error!("Error writing audit event: {:?}", e);
+                    sentry::capture_message("Error writing audit event", sentry::Level::Error);
+                    ServiceError::InternalServerError(format!("Error writing audit event: {:?}", e))
+                })?;
+            }
         }
     }
 
@@ -169,6 +183,11 @@ pub async fn send_to_clickhouse(
         sentry::capture_message("Error ending worker events inserter", sentry::Level::Error);
         ServiceError::InternalServerError(format!("Error ending worker events inserter: {:?}", e))
     })?;
+    audit_events_inserter.end().await.map_err(|e| {
+        log::error!("Error ending audit events inserter: {:?}", e);
+        sentry::capture_message("Error ending audit events inserter", sentry::Level::Error);
+        ServiceError::InternalServerError(format!("Error ending audit events inserter: {:?}", e))
+    })?;
 
     Ok(())
 }
@@ -0,0 +1,1529 @@
+use super::chunk_operator::{delete_chunk_metadata_query, get_metadata_from_tracking_ids_query};
+use super::chunking_operator::{chunk_parsed_file, FileChunk};
+use super::crawl_operator::{chunk_html, get_tags};
+use super::file_parser_operator::parse_file;
+use super::user_operator::hash_function;
+use crate::data::models::{
+    ChunkVersionActor, ConnectorCredentials, ConnectorDocumentPG, CrawlGitOptions, CrawlOptions,
+    CrawlPageResult, CrawlPageStatus, CrawlRssOptions, CrawlS3Options, CrawlSitemapOptions,
+    Dataset, DatasetConfiguration, Pool, RedisPool, ScrapeOptions,
+};
+use crate::errors::ServiceError;
+use crate::handlers::chunk_handler::{ChunkReqPayload, FullTextBoost, SemanticBoost};
+use crate::handlers::file_handler::{ChunkingStrategy, UploadFileReqPayload};
+use actix_web::web;
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use futures::StreamExt;
+use quick_xml::events::Event;
+use regex::Regex;
+use reqwest::url::{Host, Url};
+use s3::{creds::Credentials, Bucket, Region};
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+const DEFAULT_GIT_FILE_EXTENSIONS: [&str; 2] = ["md", "mdx"];
+const CODE_LINES_PER_CHUNK: usize = 80;
+const MAX_SITEMAP_DEPTH: usize = 3;
+const PAGE_FETCH_CONCURRENCY: usize = 8;
+
+/// A document read from a connector's source along with the chunks it was split into
+#[derive(Debug, Clone)]
+pub struct ConnectorDocument {
+    /// Path, object key or url identifying the document within its source
+    pub source: String,
+    /// Version reported by the source such as an ETag, a hash of the chunks is used when there is none
+    pub version: Option<String>,
+    /// None when the source reported the document as unchanged such that it was not downloaded again
+    pub chunks: Option<Vec<ChunkReqPayload>>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConnectorSyncResult {
+    pub documents: Vec<ConnectorDocument>,
+    /// Documents which could not be read during this run along with the error, their previously ingested chunks are kept
+    pub failed_sources: HashMap<String, String>,
+    /// Documents left out because of the include_paths or exclude_paths of the crawl
+    pub skipped_sources: Vec<String>,
+}
+
+/// What a previous run ingested for a document, stored per crawl request in the connector_documents table
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IngestedDocument {
+    pub version: String,
+    pub tracking_ids: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConnectorDiff {
+    pub chunks_to_upsert: Vec<ChunkReqPayload>,
+    pub tracking_ids_to_delete: Vec<String>,
+    pub documents_changed: usize,
+    pub documents_unchanged: usize,
+    pub ingested_documents: HashMap<String, IngestedDocument>,
+    /// Outcome of every document for the crawl run
+    pub pages: Vec<CrawlPageResult>,
+}
+
+/// Redis hash which held the ingested documents before they were stored in postgres
+fn get_legacy_ingested_documents_key(crawl_request_id: uuid::Uuid) -> String {
+    format!("connector_ingested_documents:{}", crawl_request_id)
+}
+
+async fn get_legacy_ingested_documents(
+    crawl_request_id: uuid::Uuid,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HashMap<String, IngestedDocument>, ServiceError> {
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    let serialized_documents: HashMap<String, String> = redis::cmd("HGETALL")
+        .arg(get_legacy_ingested_documents_key(crawl_request_id))
+        .query_async(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(serialized_documents
+        .into_iter()
+        .filter_map(|(source, serialized_document)| {
+            serde_json::from_str::<IngestedDocument>(&serialized_document)
+                .ok()
+                .map(|document| (source, document))
+        })
+        .collect())
+}
+
+/// Documents ingested by the previous runs of a connector keyed by their source. Connectors which were last synced while the documents were kept in redis are read from there once.
+pub async fn get_ingested_documents(
+    crawl_request_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<HashMap<String, IngestedDocument>, ServiceError> {
+    use crate::data::schema::connector_documents::dsl as connector_documents_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let documents = connector_documents_columns::connector_documents
+        .filter(connector_documents_columns::crawl_request_id.eq(crawl_request_id))
+        .select(ConnectorDocumentPG::as_select())
+        .load::<ConnectorDocumentPG>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get connector documents {:?}", err);
+            ServiceError::InternalServerError("Failed to get connector documents".to_string())
+        })?;
+
+    if documents.is_empty() {
+        return get_legacy_ingested_documents(crawl_request_id, redis_pool).await;
+    }
+
+    Ok(documents
+        .into_iter()
+        .map(|document| {
+            (
+                document.source,
+                IngestedDocument {
+                    version: document.version,
+                    tracking_ids: document.tracking_ids.into_iter().flatten().collect(),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Replaces the documents recorded for a connector with the ones ingested by the latest run
+pub async fn set_ingested_documents(
+    crawl_request_id: uuid::Uuid,
+    ingested_documents: &HashMap<String, IngestedDocument>,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::connector_documents::dsl as connector_documents_columns;
+
+    let updated_at = chrono::Utc::now().naive_utc();
+    let documents = ingested_documents
+        .iter()
+        .map(|(source, document)| ConnectorDocumentPG {
+            id: uuid::Uuid::new_v4(),
+            crawl_request_id,
+            source: source.clone(),
+            version: document.version.clone(),
+            tracking_ids: document.tracking_ids.iter().cloned().map(Some).collect(),
+            updated_at,
+        })
+        .collect::<Vec<ConnectorDocumentPG>>();
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            diesel::delete(
+                connector_documents_columns::connector_documents
+                    .filter(connector_documents_columns::crawl_request_id.eq(crawl_request_id)),
+            )
+            .execute(conn)
+            .await?;
+
+            for documents in documents.chunks(1000) {
+                diesel::insert_into(connector_documents_columns::connector_documents)
+                    .values(documents)
+                    .execute(conn)
+                    .await?;
+            }
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|err| {
+        log::error!("Failed to set connector documents {:?}", err);
+        ServiceError::InternalServerError("Failed to set connector documents".to_string())
+    })?;
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    redis::cmd("DEL")
+        .arg(get_legacy_ingested_documents_key(crawl_request_id))
+        .query_async::<redis::aio::MultiplexedConnection, usize>(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(())
+}
+
+fn get_chunks_version(chunks: &[ChunkReqPayload]) -> String {
+    let serialized_chunks = chunks
+        .iter()
+        .map(|chunk| {
+            serde_json::json!({
+                "tracking_id": chunk.tracking_id,
+                "chunk_html": chunk.chunk_html,
+                "link": chunk.link,
+                "metadata": chunk.metadata,
+                "tag_set": chunk.tag_set,
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    hash_function(&serde_json::Value::Array(serialized_chunks).to_string())
+}
+
+/// Compares the documents read from a connector's source against the previous runs. Unchanged documents are skipped, changed ones are upserted and chunks of documents or sections which disappeared are deleted. Documents which failed to be read keep what was ingested for them before.
+pub fn diff_connector_documents(
+    sync_result: ConnectorSyncResult,
+    mut previous_documents: HashMap<String, IngestedDocument>,
+) -> ConnectorDiff {
+    let mut diff = ConnectorDiff::default();
+
+    for document in sync_result.documents {
+        let previous_document = previous_documents.remove(&document.source);
+
+        let chunks = match document.chunks {
+            Some(chunks) => chunks,
+            None => {
+                if let Some(previous_document) = previous_document {
+                    diff.documents_unchanged += 1;
+                    diff.pages.push(CrawlPageResult::from_details(
+                        document.source.clone(),
+                        CrawlPageStatus::Unchanged,
+                        0,
+                        Some(previous_document.version.clone()),
+                    ));
+                    diff.ingested_documents
+                        .insert(document.source, previous_document);
+                }
+                continue;
+            }
+        };
+
+        let version = document
+            .version
+            .unwrap_or_else(|| get_chunks_version(&chunks));
+        let tracking_ids = chunks
+            .iter()
+            .filter_map(|chunk| chunk.tracking_id.clone())
+            .collect::<Vec<String>>();
+
+        match previous_document {
+            Some(previous_document) if previous_document.version == version => {
+                diff.documents_unchanged += 1;
+                diff.pages.push(CrawlPageResult::from_details(
+                    document.source.clone(),
+                    CrawlPageStatus::Unchanged,
+                    0,
+                    Some(version.clone()),
+                ));
+            }
+            previous_document => {
+                diff.documents_changed += 1;
+                diff.pages.push(CrawlPageResult::from_details(
+                    document.source.clone(),
+                    if chunks.is_empty() {
+                        CrawlPageStatus::Empty
+                    } else {
+                        CrawlPageStatus::Ingested
+                    },
+                    chunks.len(),
+                    Some(version.clone()),
+                ));
+                if let Some(previous_document) = previous_document {
+                    let current_tracking_ids = tracking_ids.iter().collect::<HashSet<&String>>();
+                    diff.tracking_ids_to_delete.extend(
+                        previous_document
+                            .tracking_ids
+                            .into_iter()
+                            .filter(|tracking_id| !current_tracking_ids.contains(tracking_id)),
+                    );
+                }
+                diff.chunks_to_upsert.extend(chunks);
+            }
+        }
+
+        diff.ingested_documents.insert(
+            document.source,
+            IngestedDocument {
+                version,
+                tracking_ids,
+            },
+        );
+    }
+
+    for (failed_source, error) in sync_result.failed_sources {
+        if let Some(previous_document) = previous_documents.remove(&failed_source) {
+            diff.ingested_documents
+                .insert(failed_source.clone(), previous_document);
+        }
+        diff.pages
+            .push(CrawlPageResult::failed(failed_source, error));
+    }
+
+    diff.pages
+        .extend(sync_result.skipped_sources.into_iter().map(|source| {
+            CrawlPageResult::from_details(source, CrawlPageStatus::SkippedByPath, 0, None)
+        }));
+
+    diff.tracking_ids_to_delete.extend(
+        previous_documents
+            .into_values()
+            .flat_map(|document| document.tracking_ids),
+    );
+
+    diff
+}
+
+/// Deletes the chunks of a dataset with the given tracking ids, ids which no longer exist are ignored. The deletes are attributed to the actor who configured the connector.
+pub async fn delete_chunks_by_tracking_ids(
+    tracking_ids: Vec<String>,
+    dataset: Dataset,
+    dataset_config: DatasetConfiguration,
+    actor: ChunkVersionActor,
+    pool: web::Data<Pool>,
+) -> Result<usize, ServiceError> {
+    let mut deleted_count = 0;
+
+    for tracking_ids in tracking_ids.chunks(500) {
+        let chunk_ids =
+            get_metadata_from_tracking_ids_query(tracking_ids.to_vec(), dataset.id, pool.clone())
+                .await?
+                .into_iter()
+                .map(|chunk| chunk.id)
+                .collect::<Vec<uuid::Uuid>>();
+
+        if chunk_ids.is_empty() {
+            continue;
+        }
+
+        deleted_count += chunk_ids.len();
+        delete_chunk_metadata_query(
+            chunk_ids,
+            chrono::Utc::now().naive_utc(),
+            dataset.clone(),
+            pool.clone(),
+            dataset_config.clone(),
+            Some(actor),
+        )
+        .await?;
+    }
+
+    Ok(deleted_count)
+}
+
+/// Reads the documents of the source configured in the crawl options with the credentials stored for the crawl request. The previously ingested documents let sources which report versions skip downloading unchanged documents.
+pub async fn get_connector_documents(
+    crawl_options: &CrawlOptions,
+    credentials: Option<ConnectorCredentials>,
+    previous_documents: &HashMap<String, IngestedDocument>,
+) -> Result<ConnectorSyncResult, ServiceError> {
+    match &crawl_options.scrape_options {
+        Some(ScrapeOptions::Git(git_options)) => {
+            ensure_public_host(&parse_git_repo_url(&git_options.repo_url)?).await?;
+            let git_options = git_options.clone();
+            let crawl_options = crawl_options.clone();
+            tokio::task::spawn_blocking(move || get_git_documents(&git_options, &crawl_options))
+                .await
+                .map_err(|err| {
+                    log::error!("Git sync task failed {:?}", err);
+                    ServiceError::InternalServerError("Git sync task failed".to_string())
+                })?
+        }
+        Some(ScrapeOptions::S3(s3_options)) => {
+            get_s3_documents(s3_options, credentials, crawl_options, previous_documents).await
+        }
+        Some(ScrapeOptions::Rss(rss_options)) => {
+            get_rss_documents(rss_options, crawl_options).await
+        }
+        Some(ScrapeOptions::Sitemap(sitemap_options)) => {
+            get_sitemap_documents(sitemap_options, crawl_options).await
+        }
+        _ => Err(ServiceError::BadRequest(
+            "The crawl is not a sync connector".to_string(),
+        )),
+    }
+}
+
+fn create_connector_chunk(
+    source: &str,
+    index: usize,
+    heading_path: Vec<String>,
+    chunk_html: String,
+    link: Option<String>,
+    tag_set: Vec<String>,
+    crawl_options: &CrawlOptions,
+) -> ChunkReqPayload {
+    let heading = heading_path.last().cloned().unwrap_or_default();
+
+    ChunkReqPayload {
+        chunk_html: Some(chunk_html),
+        link: link.clone(),
+        tag_set: Some(tag_set),
+        metadata: Some(serde_json::json!({
+            "source": source,
+            "url": link,
+            "heading": heading,
+            "hierarchy": heading_path,
+        })),
+        tracking_id: Some(hash_function(&format!("{}#{}", source, index))),
+        upsert_by_tracking_id: Some(true),
+        group_tracking_ids: Some(vec![source.to_string()]),
+        fulltext_boost: if crawl_options.boost_titles.unwrap_or(true) && !heading.is_empty() {
+            Some(FullTextBoost {
+                phrase: heading.clone(),
+                boost_factor: 1.3,
+            })
+        } else {
+            None
+        },
+        semantic_boost: if crawl_options.boost_titles.unwrap_or(true) && !heading.is_empty() {
+            Some(SemanticBoost {
+                phrase: heading,
+                distance_factor: 0.3,
+            })
+        } else {
+            None
+        },
+        convert_html_to_text: Some(true),
+        ..Default::default()
+    }
+}
+
+fn remove_configured_strings(
+    mut heading_path: Vec<String>,
+    mut chunk_html: String,
+    crawl_options: &CrawlOptions,
+) -> (Vec<String>, String) {
+    if let Some(heading_remove_strings) = &crawl_options.heading_remove_strings {
+        heading_path = heading_path
+            .into_iter()
+            .map(|heading| {
+                heading_remove_strings
+                    .iter()
+                    .fold(heading, |heading, remove_string| {
+                        heading.replace(remove_string, "")
+                    })
+            })
+            .collect();
+    }
+    if let Some(body_remove_strings) = &crawl_options.body_remove_strings {
+        chunk_html = body_remove_strings
+            .iter()
+            .fold(chunk_html, |chunk_html, remove_string| {
+                chunk_html.replace(remove_string, "")
+            });
+    }
+
+    (heading_path, chunk_html)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Blocks of lines for files the parsers do not understand such as source code
+fn chunk_text_lines(text: &str) -> Vec<FileChunk> {
+    let lines = text.lines().collect::<Vec<&str>>();
+
+    lines
+        .chunks(CODE_LINES_PER_CHUNK)
+        .filter(|block| block.iter().any(|line| !line.trim().is_empty()))
+        .map(|block| FileChunk {
+            chunk_html: format!("<pre>{}</pre>", escape_html(&block.join("\n"))),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Splits a file of a connector the same way uploaded files are split with the `markdown_sections` strategy
+async fn chunk_connector_file(
+    file_name: &str,
+    file_data: &[u8],
+) -> Result<Vec<FileChunk>, ServiceError> {
+    let parsed_file = parse_file(file_name, None, file_data).await?;
+
+    chunk_parsed_file(
+        &parsed_file,
+        &UploadFileReqPayload {
+            base64_file: "".to_string(),
+            file_name: file_name.to_string(),
+            file_mime_type: None,
+            tag_set: None,
+            description: None,
+            link: None,
+            time_stamp: None,
+            metadata: None,
+            create_chunks: Some(true),
+            rebalance_chunks: None,
+            split_delimiters: None,
+            target_splits_per_chunk: None,
+            chunking_strategy: Some(ChunkingStrategy::MarkdownSections),
+            max_tokens_per_chunk: None,
+            token_overlap: None,
+            group_tracking_id: None,
+        },
+    )
+}
+
+fn file_chunks_to_document(
+    source: String,
+    version: Option<String>,
+    file_chunks: Vec<FileChunk>,
+    link: Option<String>,
+    tag_set: Vec<String>,
+    crawl_options: &CrawlOptions,
+) -> ConnectorDocument {
+    let chunks = file_chunks
+        .into_iter()
+        .map(|file_chunk| {
+            remove_configured_strings(
+                file_chunk.heading_path,
+                file_chunk.chunk_html,
+                crawl_options,
+            )
+        })
+        .filter(|(_, chunk_html)| !chunk_html.trim().is_empty())
+        .enumerate()
+        .map(|(index, (heading_path, chunk_html))| {
+            create_connector_chunk(
+                &source,
+                index,
+                heading_path,
+                chunk_html,
+                link.clone(),
+                tag_set.clone(),
+                crawl_options,
+            )
+        })
+        .collect();
+
+    ConnectorDocument {
+        source,
+        version,
+        chunks: Some(chunks),
+    }
+}
+
+fn run_git_command(
+    args: &[&str],
+    current_dir: Option<&std::path::Path>,
+) -> Result<String, ServiceError> {
+    let mut command = std::process::Command::new("git");
+    command.args(args);
+    if let Some(current_dir) = current_dir {
+        command.current_dir(current_dir);
+    }
+
+    let output = command.output().map_err(|err| {
+        log::error!("Failed to run git {:?}", err);
+        ServiceError::InternalServerError("Failed to run git".to_string())
+    })?;
+
+    if !output.status.success() {
+        log::error!(
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(ServiceError::BadRequest(format!(
+            "git {} failed",
+            args.first().unwrap_or(&"")
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Parses the url of a repository which is cloned over https or ssh, scp-like `user@host:path` urls are returned as ssh urls. Local paths, `file://` and other transports are rejected.
+pub fn parse_git_repo_url(repo_url: &str) -> Result<Url, ServiceError> {
+    let invalid_url = || {
+        ServiceError::BadRequest(
+            "repo_url must be an https or ssh url of a public repository".to_string(),
+        )
+    };
+
+    let repo_url = repo_url.trim();
+    if repo_url.starts_with('-') {
+        return Err(invalid_url());
+    }
+
+    let url = if repo_url.starts_with("https://") || repo_url.starts_with("ssh://") {
+        Url::parse(repo_url).map_err(|_| invalid_url())?
+    } else {
+        let (user, rest) = repo_url.split_once('@').ok_or_else(invalid_url)?;
+        let (host, path) = rest.split_once(':').ok_or_else(invalid_url)?;
+        if user.is_empty() || user.contains('/') || host.contains('/') || path.is_empty() {
+            return Err(invalid_url());
+        }
+        Url::parse(&format!(
+            "ssh://{}@{}/{}",
+            user,
+            host,
+            path.trim_start_matches('/')
+        ))
+        .map_err(|_| invalid_url())?
+    };
+
+    if is_private_host(&url) || url.host_str().is_some_and(|host| host.starts_with('-')) {
+        return Err(invalid_url());
+    }
+
+    Ok(url)
+}
+
+fn get_git_documents(
+    git_options: &CrawlGitOptions,
+    crawl_options: &CrawlOptions,
+) -> Result<ConnectorSyncResult, ServiceError> {
+    let clone_dir = std::env::temp_dir().join(format!("trieve-git-sync-{}", uuid::Uuid::new_v4()));
+    let clone_dir_str = clone_dir.to_string_lossy().to_string();
+
+    let mut clone_args = vec!["-c", "protocol.file.allow=never", "clone", "--depth", "1"];
+    if let Some(branch) = &git_options.branch {
+        clone_args.extend(["--branch", branch.as_str()]);
+    }
+    clone_args.extend(["--", git_options.repo_url.as_str(), clone_dir_str.as_str()]);
+
+    run_git_command(&clone_args, None)?;
+
+    let result = read_git_documents(&clone_dir, git_options, crawl_options);
+
+    if let Err(err) = std::fs::remove_dir_all(&clone_dir) {
+        log::error!("Failed to remove git clone {:?}", err);
+    }
+
+    result
+}
+
+fn read_git_documents(
+    clone_dir: &std::path::Path,
+    git_options: &CrawlGitOptions,
+    crawl_options: &CrawlOptions,
+) -> Result<ConnectorSyncResult, ServiceError> {
+    let commit = run_git_command(&["rev-parse", "HEAD"], Some(clone_dir))?;
+    let file_extensions = git_options
+        .file_extensions
+        .clone()
+        .unwrap_or(
+            DEFAULT_GIT_FILE_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+        )
+        .into_iter()
+        .map(|extension| extension.trim_start_matches('.').to_lowercase())
+        .collect::<HashSet<String>>();
+    let path_prefix = git_options
+        .path_prefix
+        .clone()
+        .unwrap_or_default()
+        .trim_matches('/')
+        .to_string();
+    let web_url = if git_options.repo_url.starts_with("https://") {
+        Some(git_options.repo_url.trim_end_matches(".git").to_string())
+    } else {
+        None
+    };
+
+    let pattern = format!("{}/**/*", clone_dir.to_string_lossy());
+    let paths = glob::glob(&pattern).map_err(|err| {
+        log::error!("Invalid glob pattern for git sync {:?}", err);
+        ServiceError::InternalServerError("Failed to list repository files".to_string())
+    })?;
+
+    let mut sync_result = ConnectorSyncResult::default();
+    let runtime = tokio::runtime::Handle::current();
+
+    for path in paths.flatten() {
+        if !path.is_file() {
+            continue;
+        }
+
+        let relative_path = match path.strip_prefix(clone_dir) {
+            Ok(relative_path) => relative_path.to_string_lossy().replace('\\', "/"),
+            Err(_) => continue,
+        };
+        if relative_path.starts_with(".git/") || !relative_path.starts_with(&path_prefix) {
+            continue;
+        }
+
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if !file_extensions.contains(&extension) {
+            continue;
+        }
+
+        let file_data = match std::fs::read(&path) {
+            Ok(file_data) => file_data,
+            Err(err) => {
+                log::error!("Failed to read {} {:?}", relative_path, err);
+                sync_result
+                    .failed_sources
+                    .insert(relative_path, format!("Failed to read file: {}", err));
+                continue;
+            }
+        };
+
+        let file_chunks = match runtime.block_on(chunk_connector_file(&relative_path, &file_data)) {
+            Ok(file_chunks) => file_chunks,
+            Err(_) => match String::from_utf8(file_data) {
+                Ok(text) => chunk_text_lines(&text),
+                Err(_) => {
+                    log::info!("Skipping binary file {}", relative_path);
+                    continue;
+                }
+            },
+        };
+
+        let link = web_url
+            .as_ref()
+            .map(|web_url| format!("{}/blob/{}/{}", web_url, commit, relative_path));
+        let tag_set = relative_path
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_string())
+            .collect();
+
+        sync_result.documents.push(file_chunks_to_document(
+            relative_path,
+            None,
+            file_chunks,
+            link,
+            tag_set,
+            crawl_options,
+        ));
+    }
+
+    Ok(sync_result)
+}
+
+/// Whether the address is not reachable from the internet, such that connectors can not be pointed at the network of the server
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Shared address space 100.64.0.0/10
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ip(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local fc00::/7 and link local fe80::/10
+                    || (ip.segments()[0] & 0xfe00) == 0xfc00
+                    || (ip.segments()[0] & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+/// Whether the host of the url is missing, local or a private address. Hostnames which resolve to private addresses are caught by [ensure_public_host].
+pub fn is_private_host(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Ipv4(ip)) => is_private_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_private_ip(IpAddr::V6(ip)),
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".internal")
+        }
+        None => true,
+    }
+}
+
+/// Resolves the host of the url and errors if it or any of its addresses is private
+pub async fn ensure_public_host(url: &Url) -> Result<(), ServiceError> {
+    let not_public = || ServiceError::BadRequest(format!("{} is not a public url", url));
+
+    if is_private_host(url) {
+        return Err(not_public());
+    }
+
+    let host = url.host_str().ok_or_else(not_public)?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|err| {
+            log::error!("Could not resolve {} {:?}", host, err);
+            ServiceError::BadRequest(format!("Could not resolve {}", host))
+        })?
+        .collect::<Vec<_>>();
+
+    if addresses.is_empty() || addresses.iter().any(|address| is_private_ip(address.ip())) {
+        return Err(not_public());
+    }
+
+    Ok(())
+}
+
+/// Bucket read with the connector's own credentials. The server's credentials are never used, the endpoint defaults to AWS S3 and must be public.
+async fn get_connector_bucket(
+    s3_options: &CrawlS3Options,
+    credentials: Option<ConnectorCredentials>,
+) -> Result<Bucket, ServiceError> {
+    let credentials = credentials.ok_or_else(|| {
+        ServiceError::BadRequest(
+            "access_key_id and secret_access_key must be provided to sync an S3 prefix".to_string(),
+        )
+    })?;
+
+    if std::env::var("S3_BUCKET")
+        .is_ok_and(|server_bucket| server_bucket.eq_ignore_ascii_case(s3_options.bucket.trim()))
+    {
+        return Err(ServiceError::BadRequest(
+            "The bucket can not be synced".to_string(),
+        ));
+    }
+
+    let region = s3_options.region.clone().unwrap_or("us-east-1".to_string());
+    let endpoint = s3_options
+        .endpoint
+        .clone()
+        .unwrap_or(format!("https://s3.{}.amazonaws.com", region));
+
+    let endpoint_url = Url::parse(&endpoint)
+        .map_err(|_| ServiceError::BadRequest("endpoint must be a valid url".to_string()))?;
+    if endpoint_url.scheme() != "https" {
+        return Err(ServiceError::BadRequest(
+            "endpoint must be a public https url".to_string(),
+        ));
+    }
+    ensure_public_host(&endpoint_url).await?;
+
+    let credentials = Credentials {
+        access_key: Some(credentials.access_key_id),
+        secret_key: Some(credentials.secret_access_key),
+        security_token: None,
+        session_token: None,
+        expiration: None,
+    };
+
+    Ok(Bucket::new(
+        &s3_options.bucket,
+        Region::Custom { region, endpoint },
+        credentials,
+    )
+    .map_err(|err| {
+        log::error!("Could not get bucket {:?}", err);
+        ServiceError::BadRequest(format!("Could not get bucket {}", s3_options.bucket))
+    })?
+    .with_path_style())
+}
+
+async fn get_s3_documents(
+    s3_options: &CrawlS3Options,
+    credentials: Option<ConnectorCredentials>,
+    crawl_options: &CrawlOptions,
+    previous_documents: &HashMap<String, IngestedDocument>,
+) -> Result<ConnectorSyncResult, ServiceError> {
+    let bucket = get_connector_bucket(s3_options, credentials).await?;
+
+    let list_results = bucket
+        .list(s3_options.prefix.clone().unwrap_or_default(), None)
+        .await
+        .map_err(|err| {
+            log::error!("Could not list objects {:?}", err);
+            ServiceError::BadRequest(format!(
+                "Could not list the objects of bucket {}",
+                s3_options.bucket
+            ))
+        })?;
+
+    let mut sync_result = ConnectorSyncResult::default();
+    let limit = crawl_options.limit.unwrap_or(1000).max(0) as usize;
+
+    for object in list_results
+        .into_iter()
+        .flat_map(|list_result| list_result.contents)
+        .filter(|object| !object.key.ends_with('/'))
+        .take(limit)
+    {
+        let version = object
+            .e_tag
+            .clone()
+            .map(|e_tag| e_tag.trim_matches('"').to_string());
+
+        let is_unchanged = version.as_ref().is_some_and(|version| {
+            previous_documents
+                .get(&object.key)
+                .is_some_and(|previous_document| previous_document.version == *version)
+        });
+        if is_unchanged {
+            sync_result.documents.push(ConnectorDocument {
+                source: object.key,
+                version,
+                chunks: None,
+            });
+            continue;
+        }
+
+        let file_data = match bucket.get_object(&object.key).await {
+            Ok(response) => response.as_slice().to_vec(),
+            Err(err) => {
+                log::error!("Could not get {} from S3 {:?}", object.key, err);
+                sync_result
+                    .failed_sources
+                    .insert(object.key, format!("Could not get object: {}", err));
+                continue;
+            }
+        };
+
+        let file_chunks = match chunk_connector_file(&object.key, &file_data).await {
+            Ok(file_chunks) => file_chunks,
+            Err(err) => {
+                log::error!("Could not parse {} {:?}", object.key, err);
+                sync_result
+                    .failed_sources
+                    .insert(object.key, format!("Could not parse object: {}", err));
+                continue;
+            }
+        };
+
+        let tag_set = object
+            .key
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| part.to_string())
+            .collect();
+        let link = Some(format!("s3://{}/{}", s3_options.bucket, object.key));
+
+        sync_result.documents.push(file_chunks_to_document(
+            object.key,
+            version,
+            file_chunks,
+            link,
+            tag_set,
+            crawl_options,
+        ));
+    }
+
+    Ok(sync_result)
+}
+
+/// Resolves hosts only to public addresses, such that feeds, sitemaps and the pages they list can not reach the network of the server
+struct PublicAddressResolver;
+
+impl reqwest::dns::Resolve for PublicAddressResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addresses = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|address| !is_private_ip(address.ip()))
+                .collect::<Vec<_>>();
+
+            if addresses.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+
+            Ok(Box::new(addresses.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Client for fetching user given urls which only connects to public addresses, including when following redirects
+fn get_public_http_client() -> Result<reqwest::Client, ServiceError> {
+    reqwest::Client::builder()
+        .dns_resolver(std::sync::Arc::new(PublicAddressResolver))
+        .redirect(reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= 10 {
+                attempt.error("too many redirects")
+            } else if !matches!(attempt.url().scheme(), "http" | "https")
+                || is_private_host(attempt.url())
+            {
+                attempt.error("redirect to a private url")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .map_err(|err| {
+            log::error!("Failed to build http client {:?}", err);
+            ServiceError::InternalServerError("Failed to build http client".to_string())
+        })
+}
+
+async fn fetch_text(client: &reqwest::Client, url: &str) -> Result<String, ServiceError> {
+    let parsed_url = Url::parse(url)
+        .map_err(|_| ServiceError::BadRequest(format!("{} is not a valid url", url)))?;
+    if !matches!(parsed_url.scheme(), "http" | "https") || is_private_host(&parsed_url) {
+        return Err(ServiceError::BadRequest(format!(
+            "{} is not a public url",
+            url
+        )));
+    }
+
+    let response = client.get(url).send().await.map_err(|err| {
+        log::error!("Error fetching {} {:?}", url, err);
+        ServiceError::BadRequest(format!("Could not fetch {}", url))
+    })?;
+
+    if !response.status().is_success() {
+        return Err(ServiceError::BadRequest(format!(
+            "Fetching {} returned {}",
+            url,
+            response.status()
+        )));
+    }
+
+    response.text().await.map_err(|err| {
+        log::error!("Error reading {} {:?}", url, err);
+        ServiceError::BadRequest(format!("Could not read {}", url))
+    })
+}
+
+/// Splits HTML on its headings like crawled pages, pages without headings become a single chunk under their title
+fn chunk_page_html(
+    source: &str,
+    title: &str,
+    html: &str,
+    link: Option<String>,
+    crawl_options: &CrawlOptions,
+) -> ConnectorDocument {
+    let mut chunked_html = chunk_html(html, crawl_options);
+
+    if chunked_html.is_empty() && !html.trim().is_empty() {
+        chunked_html.push((vec![title.to_string()], html.to_string()));
+    }
+
+    let tag_set = link.clone().map(get_tags).unwrap_or_default();
+
+    let chunks = chunked_html
+        .into_iter()
+        .enumerate()
+        .map(|(index, (mut heading_path, chunk_html))| {
+            if !title.is_empty() && heading_path.first() != Some(&title.to_string()) {
+                heading_path.insert(0, title.to_string());
+            }
+            create_connector_chunk(
+                source,
+                index,
+                heading_path,
+                chunk_html,
+                link.clone(),
+                tag_set.clone(),
+                crawl_options,
+            )
+        })
+        .collect();
+
+    ConnectorDocument {
+        source: source.to_string(),
+        version: None,
+        chunks: Some(chunks),
+    }
+}
+
+#[derive(Debug, Default)]
+struct FeedItem {
+    id: String,
+    title: String,
+    link: String,
+    content: String,
+}
+
+fn push_feed_text(item: &mut Option<FeedItem>, field: &Option<String>, text: &str) {
+    let (Some(item), Some(field)) = (item.as_mut(), field.as_deref()) else {
+        return;
+    };
+    match field {
+        "title" => item.title.push_str(text),
+        "link" => item.link.push_str(text.trim()),
+        "guid" | "id" => item.id.push_str(text.trim()),
+        "encoded" | "content" => item.content = text.to_string(),
+        "description" | "summary" if item.content.is_empty() => item.content.push_str(text),
+        _ => {}
+    }
+}
+
+fn parse_feed(feed: &str) -> Vec<FeedItem> {
+    let mut reader = quick_xml::Reader::from_str(feed);
+    let mut items = vec![];
+    let mut current_item: Option<FeedItem> = None;
+    let mut current_field: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "item" | "entry" => current_item = Some(FeedItem::default()),
+                    "link" => {
+                        if let Some(href) = element
+                            .try_get_attribute("href")
+                            .ok()
+                            .flatten()
+                            .and_then(|href| href.unescape_value().ok())
+                        {
+                            if let Some(item) = current_item.as_mut() {
+                                item.link = href.to_string();
+                            }
+                        }
+                        current_field = Some(name);
+                    }
+                    _ => current_field = Some(name),
+                }
+            }
+            Ok(Event::Empty(element)) => {
+                if element.local_name().as_ref() == b"link" {
+                    let is_alternate = element
+                        .try_get_attribute("rel")
+                        .ok()
+                        .flatten()
+                        .and_then(|rel| rel.unescape_value().ok())
+                        .map_or(true, |rel| rel == "alternate");
+                    if let (Some(item), true) = (current_item.as_mut(), is_alternate) {
+                        if let Some(href) = element
+                            .try_get_attribute("href")
+                            .ok()
+                            .flatten()
+                            .and_then(|href| href.unescape_value().ok())
+                        {
+                            item.link = href.to_string();
+                        }
+                    }
+                }
+            }
+            Ok(Event::Text(text)) => {
+                if let Ok(text) = text.unescape() {
+                    push_feed_text(&mut current_item, &current_field, &text);
+                }
+            }
+            Ok(Event::CData(text)) => {
+                let text = String::from_utf8_lossy(&text.into_inner()).to_string();
+                push_feed_text(&mut current_item, &current_field, &text);
+            }
+            Ok(Event::End(element)) => {
+                if matches!(element.local_name().as_ref(), b"item" | b"entry") {
+                    if let Some(item) = current_item.take() {
+                        items.push(item);
+                    }
+                }
+                current_field = None;
+            }
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                log::error!("Error parsing feed {:?}", err);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    items
+}
+
+async fn get_rss_documents(
+    rss_options: &CrawlRssOptions,
+    crawl_options: &CrawlOptions,
+) -> Result<ConnectorSyncResult, ServiceError> {
+    let client = get_public_http_client()?;
+    let feed = fetch_text(&client, &rss_options.feed_url).await?;
+    let limit = crawl_options.limit.unwrap_or(1000).max(0) as usize;
+
+    let documents = parse_feed(&feed)
+        .into_iter()
+        .filter(|item| !item.id.is_empty() || !item.link.is_empty())
+        .take(limit)
+        .map(|item| {
+            let source = if item.id.is_empty() {
+                item.link.clone()
+            } else {
+                item.id.clone()
+            };
+            let title = item.title.trim().to_string();
+            let html = format!("<h1>{}</h1>{}", escape_html(&title), item.content);
+            let link = if item.link.is_empty() {
+                None
+            } else {
+                Some(item.link.clone())
+            };
+
+            chunk_page_html(&source, &title, &html, link, crawl_options)
+        })
+        .collect();
+
+    Ok(ConnectorSyncResult {
+        documents,
+        ..Default::default()
+    })
+}
+
+/// Whether the url matches the include_paths of the crawl, or there are none, and none of its exclude_paths
+pub fn matches_crawl_paths(url: &str, crawl_options: &CrawlOptions) -> bool {
+    let matches_any = |patterns: &Vec<String>| {
+        patterns.iter().any(|pattern| {
+            glob::Pattern::new(pattern)
+                .map(|pattern| pattern.matches(url))
+                .unwrap_or(false)
+        })
+    };
+
+    let included = crawl_options
+        .include_paths
+        .as_ref()
+        .filter(|include_paths| !include_paths.is_empty())
+        .map_or(true, matches_any);
+    let excluded = crawl_options
+        .exclude_paths
+        .as_ref()
+        .is_some_and(matches_any);
+
+    included && !excluded
+}
+
+/// Page urls of a sitemap, following sitemap indexes
+async fn get_sitemap_urls(
+    client: &reqwest::Client,
+    sitemap_url: &str,
+    limit: usize,
+) -> Result<Vec<String>, ServiceError> {
+    let mut page_urls = vec![];
+    let mut sitemap_urls = vec![(sitemap_url.to_string(), 0)];
+    let loc_re = Regex::new(r"(?s)<loc>\s*(.*?)\s*</loc>").expect("Regex pattern is always valid");
+
+    while let Some((sitemap_url, depth)) = sitemap_urls.pop() {
+        let sitemap = match fetch_text(client, &sitemap_url).await {
+            Ok(sitemap) => sitemap,
+            Err(err) if depth > 0 => {
+                log::error!("Skipping sitemap {} {:?}", sitemap_url, err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let is_index = sitemap.contains("<sitemapindex");
+
+        for capture in loc_re.captures_iter(&sitemap) {
+            let url = capture[1].replace("&amp;", "&");
+            if is_index {
+                if depth < MAX_SITEMAP_DEPTH {
+                    sitemap_urls.push((url, depth + 1));
+                }
+            } else if page_urls.len() < limit {
+                page_urls.push(url);
+            }
+        }
+    }
+
+    Ok(page_urls)
+}
+
+fn extract_page(html: &str) -> (String, String) {
+    let document = Html::parse_document(html);
+    let title_selector = Selector::parse("title").expect("Selector is always valid");
+    let content_selector = Selector::parse("main, body").expect("Selector is always valid");
+    let script_re = Regex::new(r"(?is)<(script|style|noscript)\b.*?</(script|style|noscript)>")
+        .expect("Regex pattern is always valid");
+
+    let title = document
+        .select(&title_selector)
+        .next()
+        .map(|title| title.text().collect::<String>().trim().to_string())
+        .unwrap_or_default();
+    let content = document
+        .select(&content_selector)
+        .next()
+        .map(|content| content.inner_html())
+        .unwrap_or_default();
+
+    (title, script_re.replace_all(&content, "").to_string())
+}
+
+async fn get_sitemap_documents(
+    sitemap_options: &CrawlSitemapOptions,
+    crawl_options: &CrawlOptions,
+) -> Result<ConnectorSyncResult, ServiceError> {
+    let client = get_public_http_client()?;
+    let limit = crawl_options.limit.unwrap_or(1000).max(0) as usize;
+
+    let mut sync_result = ConnectorSyncResult::default();
+
+    let (page_urls, skipped_urls): (Vec<String>, Vec<String>) =
+        get_sitemap_urls(&client, &sitemap_options.sitemap_url, usize::MAX)
+            .await?
+            .into_iter()
+            .partition(|url| matches_crawl_paths(url, crawl_options));
+    let page_urls = page_urls.into_iter().take(limit).collect::<Vec<String>>();
+    sync_result.skipped_sources = skipped_urls
+        .into_iter()
+        .map(|url| url.trim_end_matches('/').to_string())
+        .collect();
+
+    let pages = futures::stream::iter(page_urls)
+        .map(|url| {
+            let client = client.clone();
+            async move {
+                let page = fetch_text(&client, &url).await;
+                (url, page)
+            }
+        })
+        .buffer_unordered(PAGE_FETCH_CONCURRENCY)
+        .collect::<Vec<(String, Result<String, ServiceError>)>>()
+        .await;
+
+    for (url, page) in pages {
+        match page {
+            Ok(page) => {
+                let (title, html) = extract_page(&page);
+                let source = url.trim_end_matches('/').to_string();
+                sync_result.documents.push(chunk_page_html(
+                    &source,
+                    &title,
+                    &html,
+                    Some(url),
+                    crawl_options,
+                ));
+            }
+            Err(err) => {
+                log::error!("Could not fetch page {} {:?}", url, err);
+                sync_result
+                    .failed_sources
+                    .insert(url.trim_end_matches('/').to_string(), err.to_string());
+            }
+        }
+    }
+
+    Ok(sync_result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_git_repo_url() {
+        assert!(parse_git_repo_url("https://github.com/devflowinc/trieve.git").is_ok());
+        assert!(parse_git_repo_url("ssh://git@github.com/devflowinc/trieve.git").is_ok());
+        assert_eq!(
+            parse_git_repo_url("git@github.com:devflowinc/trieve.git")
+                .unwrap()
+                .host_str(),
+            Some("github.com")
+        );
+
+        for repo_url in [
+            "/srv/repos/trieve",
+            "../trieve",
+            "file:///srv/repos/trieve",
+            "--upload-pack=touch /tmp/pwned",
+            "-uhttps://github.com/devflowinc/trieve.git",
+            "ext::sh -c touch% /tmp/pwned",
+            "http://github.com/devflowinc/trieve.git",
+            "https://127.0.0.1/repo.git",
+            "https://localhost/repo.git",
+            "git@10.0.0.4:repo.git",
+            "ssh://git@[::1]/repo.git",
+        ] {
+            assert!(
+                parse_git_repo_url(repo_url).is_err(),
+                "{} should be rejected",
+                repo_url
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_private_ip() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{} is private", ip);
+        }
+
+        for ip in ["8.8.8.8", "52.216.0.1", "2606:4700:4700::1111"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{} is public", ip);
+        }
+    }
+
+    fn chunk(tracking_id: &str, chunk_html: &str) -> ChunkReqPayload {
+        ChunkReqPayload {
+            tracking_id: Some(tracking_id.to_string()),
+            chunk_html: Some(chunk_html.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn document(
+        source: &str,
+        version: Option<&str>,
+        chunks: Option<Vec<ChunkReqPayload>>,
+    ) -> ConnectorDocument {
+        ConnectorDocument {
+            source: source.to_string(),
+            version: version.map(|version| version.to_string()),
+            chunks,
+        }
+    }
+
+    fn ingested(version: &str, tracking_ids: &[&str]) -> IngestedDocument {
+        IngestedDocument {
+            version: version.to_string(),
+            tracking_ids: tracking_ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn page_status(diff: &ConnectorDiff, source: &str) -> Option<CrawlPageStatus> {
+        diff.pages
+            .iter()
+            .find(|page| page.url == source)
+            .map(|page| page.status)
+    }
+
+    #[test]
+    fn test_diff_unchanged_documents() {
+        let previous_documents = HashMap::from([
+            ("a.md".to_string(), ingested("v1", &["a.md#0"])),
+            ("b.md".to_string(), ingested("v2", &["b.md#0"])),
+        ]);
+        let sync_result = ConnectorSyncResult {
+            documents: vec![
+                document("a.md", Some("v1"), Some(vec![chunk("a.md#0", "a")])),
+                // Sources which report versions skip downloading unchanged documents
+                document("b.md", Some("v2"), None),
+            ],
+            ..Default::default()
+        };
+
+        let diff = diff_connector_documents(sync_result, previous_documents);
+
+        assert_eq!(diff.documents_unchanged, 2);
+        assert_eq!(diff.documents_changed, 0);
+        assert!(diff.chunks_to_upsert.is_empty());
+        assert!(diff.tracking_ids_to_delete.is_empty());
+        assert_eq!(page_status(&diff, "a.md"), Some(CrawlPageStatus::Unchanged));
+        assert_eq!(page_status(&diff, "b.md"), Some(CrawlPageStatus::Unchanged));
+        assert_eq!(diff.ingested_documents["b.md"].tracking_ids, vec!["b.md#0"]);
+    }
+
+    #[test]
+    fn test_diff_changed_document() {
+        let previous_documents =
+            HashMap::from([("a.md".to_string(), ingested("v1", &["a.md#0", "a.md#1"]))]);
+        let sync_result = ConnectorSyncResult {
+            documents: vec![document(
+                "a.md",
+                None,
+                Some(vec![chunk("a.md#0", "new text")]),
+            )],
+            ..Default::default()
+        };
+
+        let diff = diff_connector_documents(sync_result, previous_documents);
+
+        assert_eq!(diff.documents_changed, 1);
+        assert_eq!(diff.chunks_to_upsert.len(), 1);
+        assert_eq!(diff.tracking_ids_to_delete, vec!["a.md#1"]);
+        assert_eq!(page_status(&diff, "a.md"), Some(CrawlPageStatus::Ingested));
+        assert_eq!(diff.ingested_documents["a.md"].tracking_ids, vec!["a.md#0"]);
+        assert_ne!(diff.ingested_documents["a.md"].version, "v1");
+    }
+
+    #[test]
+    fn test_diff_removed_document() {
+        let previous_documents = HashMap::from([
+            ("a.md".to_string(), ingested("v1", &["a.md#0"])),
+            (
+                "gone.md".to_string(),
+                ingested("v1", &["gone.md#0", "gone.md#1"]),
+            ),
+        ]);
+        let sync_result = ConnectorSyncResult {
+            documents: vec![document("a.md", Some("v1"), None)],
+            ..Default::default()
+        };
+
+        let mut diff = diff_connector_documents(sync_result, previous_documents);
+        diff.tracking_ids_to_delete.sort();
+
+        assert_eq!(diff.tracking_ids_to_delete, vec!["gone.md#0", "gone.md#1"]);
+        assert!(!diff.ingested_documents.contains_key("gone.md"));
+    }
+
+    #[test]
+    fn test_diff_failed_document_keeps_previous_chunks() {
+        let previous_documents = HashMap::from([("a.md".to_string(), ingested("v1", &["a.md#0"]))]);
+        let sync_result = ConnectorSyncResult {
+            failed_sources: HashMap::from([("a.md".to_string(), "timed out".to_string())]),
+            skipped_sources: vec!["private/b.md".to_string()],
+            ..Default::default()
+        };
+
+        let diff = diff_connector_documents(sync_result, previous_documents);
+
+        assert!(diff.tracking_ids_to_delete.is_empty());
+        assert_eq!(diff.ingested_documents["a.md"].version, "v1");
+        assert_eq!(page_status(&diff, "a.md"), Some(CrawlPageStatus::Failed));
+        assert_eq!(
+            page_status(&diff, "private/b.md"),
+            Some(CrawlPageStatus::SkippedByPath)
+        );
+    }
+}
@@ -4,8 +4,15 @@ use crate::data::models::FirecrawlCrawlRequest;
 use crate::data::models::RedisPool;
 use crate::handlers::chunk_handler::CrawlInterval;
 use crate::{
-    data::models::{CrawlRequest, CrawlRequestPG, Pool, ScrapeOptions},
+    data::models::{
+        ChunkVersionActor, ConnectorCredentials, CrawlRequest, CrawlRequestPG, CrawlS3Options,
+        Pool, ScrapeOptions,
+    },
     errors::ServiceError,
+    operators::{
+        connector_operator::{is_private_host, parse_git_repo_url},
+        secret_operator::{decrypt_secret, encrypt_secret},
+    },
 };
 use actix_web::web;
 use diesel::prelude::*;
@@ -121,6 +128,11 @@ pub struct Sitemap {
     pub changefreq: String,
 }
 
+fn is_public_http_url(url: &str) -> bool {
+    Url::parse(url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && !is_private_host(&url))
+}
+
 pub fn validate_crawl_options(crawl_options: &CrawlOptions) -> Result<CrawlOptions, ServiceError> {
     if crawl_options.allow_external_links.is_some_and(|v| v)
         && !crawl_options
@@ -134,26 +146,206 @@ pub fn validate_crawl_options(crawl_options: &CrawlOptions) -> Result<CrawlOptio
             "If allow_external_links is true, include_paths must contain at least one path that is not '*'".to_string(),
         ));
     }
+
+    match &crawl_options.scrape_options {
+        Some(ScrapeOptions::Git(git_options)) if git_options.repo_url.trim().is_empty() => {
+            return Err(ServiceError::BadRequest(
+                "repo_url must be provided to sync a git repository".to_string(),
+            ));
+        }
+        Some(ScrapeOptions::Git(git_options)) => {
+            parse_git_repo_url(&git_options.repo_url)?;
+            if git_options
+                .branch
+                .as_ref()
+                .is_some_and(|branch| branch.starts_with('-'))
+            {
+                return Err(ServiceError::BadRequest(
+                    "branch must be a valid branch name".to_string(),
+                ));
+            }
+        }
+        Some(ScrapeOptions::S3(s3_options)) if s3_options.bucket.trim().is_empty() => {
+            return Err(ServiceError::BadRequest(
+                "bucket must be provided to sync an S3 prefix".to_string(),
+            ));
+        }
+        Some(ScrapeOptions::Rss(rss_options)) if !is_public_http_url(&rss_options.feed_url) => {
+            return Err(ServiceError::BadRequest(
+                "feed_url must be a valid public url".to_string(),
+            ));
+        }
+        Some(ScrapeOptions::Sitemap(sitemap_options))
+            if !is_public_http_url(&sitemap_options.sitemap_url) =>
+        {
+            return Err(ServiceError::BadRequest(
+                "sitemap_url must be a valid public url".to_string(),
+            ));
+        }
+        _ => {}
+    }
+
+    if let Some(ScrapeOptions::S3(s3_options)) = &crawl_options.scrape_options {
+        validate_s3_options(s3_options)?;
+    }
+
     Ok(crawl_options.clone())
 }
 
+/// S3 connectors read with their own access key from a bucket other than the server's, at a public https endpoint
+fn validate_s3_options(s3_options: &CrawlS3Options) -> Result<(), ServiceError> {
+    if s3_options.access_key_id.is_some() != s3_options.secret_access_key.is_some() {
+        return Err(ServiceError::BadRequest(
+            "access_key_id and secret_access_key must be provided together".to_string(),
+        ));
+    }
+
+    if std::env::var("S3_BUCKET")
+        .is_ok_and(|server_bucket| server_bucket.eq_ignore_ascii_case(s3_options.bucket.trim()))
+    {
+        return Err(ServiceError::BadRequest(
+            "The bucket can not be synced".to_string(),
+        ));
+    }
+
+    if let Some(endpoint) = &s3_options.endpoint {
+        let endpoint = Url::parse(endpoint)
+            .map_err(|_| ServiceError::BadRequest("endpoint must be a valid url".to_string()))?;
+        if endpoint.scheme() != "https" || is_private_host(&endpoint) {
+            return Err(ServiceError::BadRequest(
+                "endpoint must be a public https url".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Credentials to store encrypted with a new crawl request. Connectors which were given no credentials keep the ones stored for the dataset's previous crawl request.
+async fn get_new_crawl_credentials(
+    crawl_options: &CrawlOptions,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Option<String>, ServiceError> {
+    use crate::data::schema::crawl_requests::dsl as crawl_requests_table;
+
+    let Some(ScrapeOptions::S3(s3_options)) = &crawl_options.scrape_options else {
+        return Ok(None);
+    };
+
+    if let Some(credentials) = s3_options.get_credentials() {
+        let credentials = serde_json::to_string(&credentials).map_err(|_| {
+            ServiceError::InternalServerError("Failed to serialize credentials".to_string())
+        })?;
+        return encrypt_secret(&credentials).map(Some);
+    }
+
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+
+    let previous_credentials = crawl_requests_table::crawl_requests
+        .filter(crawl_requests_table::dataset_id.eq(dataset_id))
+        .filter(crawl_requests_table::encrypted_credentials.is_not_null())
+        .order(crawl_requests_table::created_at.desc())
+        .select(crawl_requests_table::encrypted_credentials)
+        .first::<Option<String>>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?
+        .flatten();
+
+    match previous_credentials {
+        Some(previous_credentials) => Ok(Some(previous_credentials)),
+        None => Err(ServiceError::BadRequest(
+            "access_key_id and secret_access_key must be provided to sync an S3 prefix".to_string(),
+        )),
+    }
+}
+
+/// Decrypted credentials the connector of the crawl request reads its source with
+pub async fn get_crawl_request_credentials_query(
+    crawl_request_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Option<ConnectorCredentials>, ServiceError> {
+    use crate::data::schema::crawl_requests::dsl as crawl_requests_table;
+
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+
+    let encrypted_credentials = crawl_requests_table::crawl_requests
+        .filter(crawl_requests_table::id.eq(crawl_request_id))
+        .select(crawl_requests_table::encrypted_credentials)
+        .first::<Option<String>>(&mut conn)
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+
+    encrypted_credentials
+        .map(|encrypted_credentials| {
+            serde_json::from_str(&decrypt_secret(&encrypted_credentials)?).map_err(|_| {
+                ServiceError::InternalServerError("Failed to read credentials".to_string())
+            })
+        })
+        .transpose()
+}
+
+/// The user or API key which configured the crawl request, chunk versions written by its syncs are attributed to them
+pub async fn get_crawl_request_actor_query(
+    crawl_request_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<ChunkVersionActor, ServiceError> {
+    use crate::data::schema::crawl_requests::dsl as crawl_requests_table;
+
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+
+    let (user_id, api_key_id) = crawl_requests_table::crawl_requests
+        .filter(crawl_requests_table::id.eq(crawl_request_id))
+        .select((
+            crawl_requests_table::actor_user_id,
+            crawl_requests_table::actor_api_key_id,
+        ))
+        .first::<(Option<uuid::Uuid>, Option<uuid::Uuid>)>(&mut conn)
+        .await
+        .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
+
+    Ok(ChunkVersionActor {
+        user_id,
+        api_key_id,
+    })
+}
+
 pub async fn crawl(
     crawl_options: CrawlOptions,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
     dataset_id: uuid::Uuid,
+    actor: ChunkVersionActor,
 ) -> Result<uuid::Uuid, ServiceError> {
     validate_crawl_options(&crawl_options)?;
 
-    let scrape_id = if let Some(ScrapeOptions::Shopify(_)) = crawl_options.scrape_options {
-        uuid::Uuid::nil()
-    } else {
+    let scrape_id = if crawl_options.uses_firecrawl() {
         crawl_site(crawl_options.clone())
             .await
             .map_err(|err| ServiceError::BadRequest(format!("Could not crawl site: {}", err)))?
+    } else {
+        uuid::Uuid::new_v4()
     };
 
-    create_crawl_request(crawl_options, dataset_id, scrape_id, pool, redis_pool).await?;
+    create_crawl_request(
+        crawl_options,
+        dataset_id,
+        scrape_id,
+        actor,
+        pool,
+        redis_pool,
+    )
+    .await?;
 
     Ok(scrape_id)
 }
@@ -248,6 +440,7 @@ pub async fn create_crawl_request(
     crawl_options: CrawlOptions,
     dataset_id: uuid::Uuid,
     scrape_id: uuid::Uuid,
+    actor: ChunkVersionActor,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
 ) -> Result<uuid::Uuid, ServiceError> {
@@ -260,9 +453,12 @@ pub async fn create_crawl_request(
         None => std::time::Duration::from_secs(60 * 60 * 24),
     };
 
+    let encrypted_credentials =
+        get_new_crawl_credentials(&crawl_options, dataset_id, pool.clone()).await?;
+
     let new_crawl_request: CrawlRequestPG = CrawlRequest {
         id: uuid::Uuid::new_v4(),
-        url: crawl_options.site_url.clone().unwrap_or_default(),
+        url: crawl_options.source_url().unwrap_or_default(),
         status: CrawlStatus::Pending,
         interval,
         next_crawl_at: chrono::Utc::now().naive_utc(),
@@ -280,13 +476,30 @@ pub async fn create_crawl_request(
         .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
 
     diesel::insert_into(crawl_requests_table::crawl_requests)
-        .values(&new_crawl_request)
+        .values((
+            &new_crawl_request,
+            crawl_requests_table::encrypted_credentials.eq(encrypted_credentials),
+            crawl_requests_table::actor_user_id.eq(actor.user_id),
+            crawl_requests_table::actor_api_key_id.eq(actor.api_key_id),
+        ))
         .execute(&mut conn)
         .await
         .map_err(|e| ServiceError::InternalServerError(e.to_string()))?;
 
-    let serialized_message =
-        serde_json::to_string(&CrawlRequest::from(new_crawl_request.clone())).unwrap();
+    enqueue_crawl_request(CrawlRequest::from(new_crawl_request.clone()), redis_pool).await?;
+
+    Ok(new_crawl_request.scrape_id)
+}
+
+/// Queues a crawl request for the crawl worker
+pub async fn enqueue_crawl_request(
+    crawl_request: CrawlRequest,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    let serialized_message = serde_json::to_string(&crawl_request).map_err(|e| {
+        log::error!("Failed to serialize crawl request: {:?}", e);
+        ServiceError::BadRequest("Failed to serialize crawl request".to_string())
+    })?;
     let mut redis_conn = redis_pool
         .get()
         .await
@@ -299,7 +512,7 @@ pub async fn create_crawl_request(
         .await
         .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
 
-    Ok(new_crawl_request.scrape_id)
+    Ok(())
 }
 
 pub async fn update_crawl_status(
@@ -348,6 +561,7 @@ pub async fn update_next_crawl_at(
 pub async fn update_crawl_settings_for_dataset(
     crawl_options: CrawlOptions,
     dataset_id: uuid::Uuid,
+    actor: ChunkVersionActor,
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
 ) -> Result<(), ServiceError> {
@@ -374,7 +588,7 @@ pub async fn update_crawl_settings_for_dataset(
         .await
         .optional()?;
 
-    if let Some(ref url) = crawl_options.site_url {
+    if let Some(ref url) = crawl_options.source_url() {
         diesel::update(
             crawl_requests_table::crawl_requests
                 .filter(crawl_requests_table::dataset_id.eq(dataset_id)),
@@ -442,6 +656,7 @@ pub async fn update_crawl_settings_for_dataset(
         pool.clone(),
         redis_pool.clone(),
         dataset_id,
+        actor,
     )
     .await?;
 
@@ -605,52 +820,63 @@ pub fn get_tags(url: String) -> Vec<String> {
     Vec::new()
 }
 
-pub fn chunk_html(html: &str, crawl_options: &CrawlOptions) -> Vec<(Vec<String>, String)> {
+/// Byte ranges of the HTML split right before every heading tag along with the heading path of each range. Content before the first heading has an empty heading path.
+pub fn html_heading_sections(html: &str) -> Vec<(Vec<String>, std::ops::Range<usize>)> {
     let re = Regex::new(r"(?i)<h[1-6].*?>").unwrap();
-    let mut chunks = Vec::new();
-    let mut current_chunk = String::new();
-    let mut last_end = 0;
+    let closing_re = Regex::new(r"(?i)</h[1-6]\s*>").unwrap();
+    let mut sections = Vec::new();
+    let mut section_start = 0;
     let mut heading_stack: Vec<String> = Vec::new();
 
     for cap in re.find_iter(html) {
-        if last_end != cap.start() {
-            current_chunk.push_str(&html[last_end..cap.start()]);
-        }
-
-        if !current_chunk.is_empty() {
-            let current_chunk = current_chunk.trim().to_string();
-
-            let chunk_text = convert_html_to_text(&current_chunk);
-
-            if chunk_text.split_whitespace().count() > 10 {
-                let headings_text = extract_all_headings(&current_chunk);
-
-                if !chunk_text
-                    .replace(headings_text.as_str(), "")
-                    .trim()
-                    .is_empty()
-                {
-                    chunks.push((heading_stack.clone(), current_chunk));
-                }
-            }
+        if section_start != cap.start() {
+            sections.push((heading_stack.clone(), section_start..cap.start()));
         }
 
         let heading_level = cap.as_str().chars().nth(2).unwrap().to_digit(10).unwrap() as usize;
         heading_stack.truncate(heading_level - 1);
-        heading_stack.push(extract_heading_text(&html[cap.start()..cap.end()]));
+        let heading_end = closing_re
+            .find_at(html, cap.end())
+            .map(|closing| closing.end())
+            .unwrap_or(html.len());
+        heading_stack.push(extract_heading_text(&html[cap.start()..heading_end]));
 
-        current_chunk = cap.as_str().to_string();
-        last_end = cap.end();
+        section_start = cap.start();
     }
 
-    if last_end < html.len() {
-        current_chunk.push_str(&html[last_end..]);
+    if section_start < html.len() {
+        sections.push((heading_stack, section_start..html.len()));
     }
 
-    if !current_chunk.is_empty() {
-        let current_chunk = current_chunk.trim().to_string();
+    sections
+}
+
+pub fn chunk_html(html: &str, crawl_options: &CrawlOptions) -> Vec<(Vec<String>, String)> {
+    let sections = html_heading_sections(html);
+    let last_section_index = sections.len().saturating_sub(1);
+    let mut chunks = Vec::new();
 
-        chunks.push((heading_stack.clone(), current_chunk));
+    for (i, (heading_stack, range)) in sections.into_iter().enumerate() {
+        let current_chunk = html[range].trim().to_string();
+
+        if i == last_section_index {
+            chunks.push((heading_stack, current_chunk));
+            continue;
+        }
+
+        let chunk_text = convert_html_to_text(&current_chunk);
+
+        if chunk_text.split_whitespace().count() > 10 {
+            let headings_text = extract_all_headings(&current_chunk);
+
+            if !chunk_text
+                .replace(headings_text.as_str(), "")
+                .trim()
+                .is_empty()
+            {
+                chunks.push((heading_stack, current_chunk));
+            }
+        }
     }
 
     chunks = chunks
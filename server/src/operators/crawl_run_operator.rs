@@ -0,0 +1,593 @@
+use crate::{
+    data::models::{
+        CrawlPage, CrawlPagePG, CrawlPageResult, CrawlPageStatus, CrawlRun, CrawlRunPG,
+        CrawlRunStatus, Pool,
+    },
+    errors::ServiceError,
+};
+use actix_web::web;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[tracing::instrument(skip(pool))]
+pub async fn create_crawl_run_query(
+    crawl_run: CrawlRunPG,
+    pool: web::Data<Pool>,
+) -> Result<CrawlRun, ServiceError> {
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::insert_into(crawl_runs_columns::crawl_runs)
+        .values(&crawl_run)
+        .get_result::<CrawlRunPG>(&mut conn)
+        .await
+        .map(CrawlRun::from)
+        .map_err(|err| {
+            log::error!("Failed to create crawl run {:?}", err);
+            ServiceError::BadRequest("Failed to create crawl run".to_string())
+        })
+}
+
+/// Runs are considered abandoned by a crashed worker once they have been running for this long, even when their crawl is not retried
+const STALE_CRAWL_RUN_HOURS: i64 = 24;
+
+/// Marks the runs of the crawl request which are still running, and runs of any crawl which have been running for too long, as failed. A crawl request is only processed by one worker at a time so a running run of it belongs to a worker which stopped before finishing it.
+#[tracing::instrument(skip(pool))]
+pub async fn fail_interrupted_crawl_runs_query(
+    crawl_request_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let now = chrono::Utc::now().naive_local();
+
+    diesel::update(
+        crawl_runs_columns::crawl_runs
+            .filter(crawl_runs_columns::status.eq(CrawlRunStatus::Running.to_string()))
+            .filter(
+                crawl_runs_columns::crawl_request_id
+                    .eq(crawl_request_id)
+                    .or(crawl_runs_columns::started_at
+                        .lt(now - chrono::Duration::hours(STALE_CRAWL_RUN_HOURS))),
+            ),
+    )
+    .set((
+        crawl_runs_columns::status.eq(CrawlRunStatus::Failed.to_string()),
+        crawl_runs_columns::error.eq(Some(
+            "The crawl worker stopped before the run finished".to_string(),
+        )),
+        crawl_runs_columns::finished_at.eq(Some(now)),
+    ))
+    .execute(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to fail interrupted crawl runs {:?}", err);
+        ServiceError::BadRequest("Failed to fail interrupted crawl runs".to_string())
+    })?;
+
+    Ok(())
+}
+
+/// Number of the dataset's most recent runs whose pages are kept, set with CRAWL_PAGES_RETAINED_RUNS
+pub fn get_crawl_pages_retained_runs() -> i64 {
+    std::env::var("CRAWL_PAGES_RETAINED_RUNS")
+        .ok()
+        .and_then(|runs| runs.parse().ok())
+        .unwrap_or(10)
+}
+
+/// Deletes the pages of all but the dataset's most recent runs. The pages of the last completed run are always kept since the next run compares its content hashes against them. The runs themselves and their totals are kept.
+#[tracing::instrument(skip(pool))]
+pub async fn prune_crawl_pages_query(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<usize, ServiceError> {
+    use crate::data::schema::crawl_pages::dsl as crawl_pages_columns;
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let last_completed_run =
+        get_previous_crawl_run_query(dataset_id, chrono::Utc::now().naive_local(), pool.clone())
+            .await?;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let expired_run_ids = crawl_runs_columns::crawl_runs
+        .filter(crawl_runs_columns::dataset_id.eq(dataset_id))
+        .order_by(crawl_runs_columns::started_at.desc())
+        .offset(get_crawl_pages_retained_runs())
+        .select(crawl_runs_columns::id)
+        .load::<uuid::Uuid>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get expired crawl runs {:?}", err);
+            ServiceError::BadRequest("Failed to get expired crawl runs".to_string())
+        })?
+        .into_iter()
+        .filter(|run_id| Some(*run_id) != last_completed_run.as_ref().map(|run| run.id))
+        .collect::<Vec<uuid::Uuid>>();
+
+    if expired_run_ids.is_empty() {
+        return Ok(0);
+    }
+
+    diesel::delete(
+        crawl_pages_columns::crawl_pages
+            .filter(crawl_pages_columns::run_id.eq_any(expired_run_ids)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to prune crawl pages {:?}", err);
+        ServiceError::BadRequest("Failed to prune crawl pages".to_string())
+    })
+}
+
+/// Stores the outcome of every page of the run and marks the run as finished with the totals of each outcome
+#[tracing::instrument(skip(pages, pool))]
+pub async fn finish_crawl_run_query(
+    crawl_run: CrawlRun,
+    status: CrawlRunStatus,
+    error: Option<String>,
+    pages: Vec<CrawlPageResult>,
+    chunks_deleted: usize,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::crawl_pages::dsl as crawl_pages_columns;
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let count_pages = |statuses: &[CrawlPageStatus]| {
+        pages
+            .iter()
+            .filter(|page| statuses.contains(&page.status))
+            .count() as i32
+    };
+    let pages_ingested = count_pages(&[CrawlPageStatus::Ingested]);
+    let pages_unchanged = count_pages(&[CrawlPageStatus::Unchanged]);
+    let pages_skipped = count_pages(&[CrawlPageStatus::SkippedByPath, CrawlPageStatus::Empty]);
+    let pages_failed = count_pages(&[CrawlPageStatus::Failed]);
+    let chunks_created = pages
+        .iter()
+        .map(|page| page.chunks_created as i32)
+        .sum::<i32>();
+
+    let crawl_pages = pages
+        .into_iter()
+        .map(|page| CrawlPagePG::from_result(crawl_run.id, crawl_run.dataset_id, page))
+        .collect::<Vec<CrawlPagePG>>();
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    for crawl_pages in crawl_pages.chunks(1000) {
+        diesel::insert_into(crawl_pages_columns::crawl_pages)
+            .values(crawl_pages)
+            .execute(&mut conn)
+            .await
+            .map_err(|err| {
+                log::error!("Failed to insert crawl pages {:?}", err);
+                ServiceError::BadRequest("Failed to insert crawl pages".to_string())
+            })?;
+    }
+
+    diesel::update(crawl_runs_columns::crawl_runs.filter(crawl_runs_columns::id.eq(crawl_run.id)))
+        .set((
+            crawl_runs_columns::status.eq(status.to_string()),
+            crawl_runs_columns::error.eq(error),
+            crawl_runs_columns::pages_ingested.eq(pages_ingested),
+            crawl_runs_columns::pages_unchanged.eq(pages_unchanged),
+            crawl_runs_columns::pages_skipped.eq(pages_skipped),
+            crawl_runs_columns::pages_failed.eq(pages_failed),
+            crawl_runs_columns::chunks_created.eq(chunks_created),
+            crawl_runs_columns::chunks_deleted.eq(chunks_deleted as i32),
+            crawl_runs_columns::finished_at.eq(Some(chrono::Utc::now().naive_local())),
+        ))
+        .execute(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to finish crawl run {:?}", err);
+            ServiceError::BadRequest("Failed to finish crawl run".to_string())
+        })?;
+
+    Ok(())
+}
+
+/// Runs of the dataset's crawl, newest first, along with the total number of runs
+#[tracing::instrument(skip(pool))]
+pub async fn get_crawl_runs_query(
+    dataset_id: uuid::Uuid,
+    page: u64,
+    page_size: u64,
+    pool: web::Data<Pool>,
+) -> Result<(Vec<CrawlRun>, i64), ServiceError> {
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let runs = crawl_runs_columns::crawl_runs
+        .filter(crawl_runs_columns::dataset_id.eq(dataset_id))
+        .order_by(crawl_runs_columns::started_at.desc())
+        .offset(((page.max(1) - 1) * page_size) as i64)
+        .limit(page_size as i64)
+        .select(CrawlRunPG::as_select())
+        .load::<CrawlRunPG>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get crawl runs {:?}", err);
+            ServiceError::BadRequest("Failed to get crawl runs".to_string())
+        })?
+        .into_iter()
+        .map(CrawlRun::from)
+        .collect();
+
+    let total_count = crawl_runs_columns::crawl_runs
+        .filter(crawl_runs_columns::dataset_id.eq(dataset_id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to count crawl runs {:?}", err);
+            ServiceError::BadRequest("Failed to count crawl runs".to_string())
+        })?;
+
+    Ok((runs, total_count))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_crawl_run_by_id_query(
+    run_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<CrawlRun, ServiceError> {
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    crawl_runs_columns::crawl_runs
+        .filter(crawl_runs_columns::id.eq(run_id))
+        .filter(crawl_runs_columns::dataset_id.eq(dataset_id))
+        .select(CrawlRunPG::as_select())
+        .first::<CrawlRunPG>(&mut conn)
+        .await
+        .optional()
+        .map_err(|err| {
+            log::error!("Failed to get crawl run {:?}", err);
+            ServiceError::BadRequest("Failed to get crawl run".to_string())
+        })?
+        .map(CrawlRun::from)
+        .ok_or_else(|| ServiceError::NotFound(format!("Crawl run {} not found", run_id)))
+}
+
+/// The latest completed run of the dataset which started before the given time
+#[tracing::instrument(skip(pool))]
+pub async fn get_previous_crawl_run_query(
+    dataset_id: uuid::Uuid,
+    started_before: chrono::NaiveDateTime,
+    pool: web::Data<Pool>,
+) -> Result<Option<CrawlRun>, ServiceError> {
+    use crate::data::schema::crawl_runs::dsl as crawl_runs_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    crawl_runs_columns::crawl_runs
+        .filter(crawl_runs_columns::dataset_id.eq(dataset_id))
+        .filter(crawl_runs_columns::status.eq(CrawlRunStatus::Completed.to_string()))
+        .filter(crawl_runs_columns::started_at.lt(started_before))
+        .order_by(crawl_runs_columns::started_at.desc())
+        .select(CrawlRunPG::as_select())
+        .first::<CrawlRunPG>(&mut conn)
+        .await
+        .optional()
+        .map(|run| run.map(CrawlRun::from))
+        .map_err(|err| {
+            log::error!("Failed to get previous crawl run {:?}", err);
+            ServiceError::BadRequest("Failed to get previous crawl run".to_string())
+        })
+}
+
+/// Page results of a run, optionally only the ones with the given status, along with the total number of matching pages
+#[tracing::instrument(skip(pool))]
+pub async fn get_crawl_pages_query(
+    run_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    status: Option<CrawlPageStatus>,
+    page: u64,
+    page_size: u64,
+    pool: web::Data<Pool>,
+) -> Result<(Vec<CrawlPage>, i64), ServiceError> {
+    use crate::data::schema::crawl_pages::dsl as crawl_pages_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let mut pages_query = crawl_pages_columns::crawl_pages
+        .filter(crawl_pages_columns::run_id.eq(run_id))
+        .filter(crawl_pages_columns::dataset_id.eq(dataset_id))
+        .into_boxed();
+    let mut count_query = crawl_pages_columns::crawl_pages
+        .filter(crawl_pages_columns::run_id.eq(run_id))
+        .filter(crawl_pages_columns::dataset_id.eq(dataset_id))
+        .into_boxed();
+
+    if let Some(status) = status {
+        pages_query = pages_query.filter(crawl_pages_columns::status.eq(status.to_string()));
+        count_query = count_query.filter(crawl_pages_columns::status.eq(status.to_string()));
+    }
+
+    let pages = pages_query
+        .order_by(crawl_pages_columns::url.asc())
+        .offset(((page.max(1) - 1) * page_size) as i64)
+        .limit(page_size as i64)
+        .select(CrawlPagePG::as_select())
+        .load::<CrawlPagePG>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get crawl pages {:?}", err);
+            ServiceError::BadRequest("Failed to get crawl pages".to_string())
+        })?
+        .into_iter()
+        .map(CrawlPage::from)
+        .collect();
+
+    let total_count = count_query
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to count crawl pages {:?}", err);
+            ServiceError::BadRequest("Failed to count crawl pages".to_string())
+        })?;
+
+    Ok((pages, total_count))
+}
+
+/// Status and content hash of every page of a run keyed by url
+#[tracing::instrument(skip(pool))]
+pub async fn get_crawl_page_states_query(
+    run_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<HashMap<String, (CrawlPageStatus, Option<String>)>, ServiceError> {
+    use crate::data::schema::crawl_pages::dsl as crawl_pages_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let page_states = crawl_pages_columns::crawl_pages
+        .filter(crawl_pages_columns::run_id.eq(run_id))
+        .select((
+            crawl_pages_columns::url,
+            crawl_pages_columns::status,
+            crawl_pages_columns::content_hash,
+        ))
+        .load::<(String, String, Option<String>)>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get crawl page states {:?}", err);
+            ServiceError::BadRequest("Failed to get crawl page states".to_string())
+        })?;
+
+    Ok(page_states
+        .into_iter()
+        .map(|(url, status, content_hash)| (url, (status.into(), content_hash)))
+        .collect())
+}
+
+/// Content hashes of the pages which were present in the dataset's last completed run, used to tell whether a page changed since
+#[tracing::instrument(skip(pool))]
+pub async fn get_previous_page_hashes_query(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<HashMap<String, String>, ServiceError> {
+    let previous_run =
+        get_previous_crawl_run_query(dataset_id, chrono::Utc::now().naive_local(), pool.clone())
+            .await?;
+
+    let Some(previous_run) = previous_run else {
+        return Ok(HashMap::new());
+    };
+
+    Ok(get_crawl_page_states_query(previous_run.id, pool)
+        .await?
+        .into_iter()
+        .filter_map(|(url, (status, content_hash))| {
+            if is_page_present(status) {
+                content_hash.map(|content_hash| (url, content_hash))
+            } else {
+                None
+            }
+        })
+        .collect())
+}
+
+fn is_page_present(status: CrawlPageStatus) -> bool {
+    matches!(
+        status,
+        CrawlPageStatus::Ingested | CrawlPageStatus::Unchanged
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+#[schema(example = json!({
+    "added": ["https://example.com/docs/new-page"],
+    "removed": ["https://example.com/docs/old-page"],
+    "changed": ["https://example.com/docs/getting-started"],
+    "unchanged_count": 412,
+}))]
+/// Urls whose presence or content differs between two runs. Pages which failed in the newer run are not reported as removed since their chunks are kept.
+pub struct CrawlRunDiff {
+    /// Pages which are present in the newer run but were not in the older one
+    pub added: Vec<String>,
+    /// Pages which were present in the older run but are no longer in the newer one
+    pub removed: Vec<String>,
+    /// Pages present in both runs whose content changed
+    pub changed: Vec<String>,
+    pub unchanged_count: usize,
+}
+
+pub fn get_crawl_run_diff(
+    previous_pages: &HashMap<String, (CrawlPageStatus, Option<String>)>,
+    current_pages: &HashMap<String, (CrawlPageStatus, Option<String>)>,
+) -> CrawlRunDiff {
+    let mut diff = CrawlRunDiff::default();
+
+    for (url, (status, content_hash)) in current_pages {
+        if !is_page_present(*status) {
+            continue;
+        }
+
+        match previous_pages.get(url) {
+            Some((previous_status, previous_content_hash)) if is_page_present(*previous_status) => {
+                if *status == CrawlPageStatus::Unchanged || content_hash == previous_content_hash {
+                    diff.unchanged_count += 1;
+                } else {
+                    diff.changed.push(url.clone());
+                }
+            }
+            _ => diff.added.push(url.clone()),
+        }
+    }
+
+    for (url, (previous_status, _)) in previous_pages {
+        if !is_page_present(*previous_status) {
+            continue;
+        }
+
+        let still_present = current_pages.get(url).is_some_and(|(status, _)| {
+            is_page_present(*status) || *status == CrawlPageStatus::Failed
+        });
+        if !still_present {
+            diff.removed.push(url.clone());
+        }
+    }
+
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_pages(
+        pages: &[(&str, CrawlPageStatus, Option<&str>)],
+    ) -> HashMap<String, (CrawlPageStatus, Option<String>)> {
+        pages
+            .iter()
+            .map(|(url, status, content_hash)| {
+                (
+                    url.to_string(),
+                    (
+                        *status,
+                        content_hash.map(|content_hash| content_hash.to_string()),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_and_changed_pages() {
+        let previous_pages = get_pages(&[
+            ("https://a.com/kept", CrawlPageStatus::Ingested, Some("1")),
+            ("https://a.com/edited", CrawlPageStatus::Ingested, Some("2")),
+            ("https://a.com/gone", CrawlPageStatus::Unchanged, Some("3")),
+        ]);
+        let current_pages = get_pages(&[
+            ("https://a.com/kept", CrawlPageStatus::Ingested, Some("1")),
+            ("https://a.com/edited", CrawlPageStatus::Ingested, Some("4")),
+            ("https://a.com/new", CrawlPageStatus::Ingested, Some("5")),
+        ]);
+
+        let diff = get_crawl_run_diff(&previous_pages, &current_pages);
+
+        assert_eq!(diff.added, vec!["https://a.com/new"]);
+        assert_eq!(diff.removed, vec!["https://a.com/gone"]);
+        assert_eq!(diff.changed, vec!["https://a.com/edited"]);
+        assert_eq!(diff.unchanged_count, 1);
+    }
+
+    #[test]
+    fn test_diff_counts_unchanged_pages_without_comparing_hashes() {
+        let previous_pages = get_pages(&[("https://a.com", CrawlPageStatus::Ingested, Some("1"))]);
+        let current_pages = get_pages(&[("https://a.com", CrawlPageStatus::Unchanged, None)]);
+
+        let diff = get_crawl_run_diff(&previous_pages, &current_pages);
+
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.unchanged_count, 1);
+    }
+
+    #[test]
+    fn test_diff_does_not_report_failed_pages_as_removed() {
+        let previous_pages = get_pages(&[("https://a.com", CrawlPageStatus::Ingested, Some("1"))]);
+        let current_pages = get_pages(&[("https://a.com", CrawlPageStatus::Failed, None)]);
+
+        let diff = get_crawl_run_diff(&previous_pages, &current_pages);
+
+        assert!(diff.removed.is_empty());
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.unchanged_count, 0);
+    }
+
+    #[test]
+    fn test_diff_reports_skipped_and_empty_pages_as_removed() {
+        let previous_pages = get_pages(&[
+            (
+                "https://a.com/skipped",
+                CrawlPageStatus::Ingested,
+                Some("1"),
+            ),
+            ("https://a.com/empty", CrawlPageStatus::Ingested, Some("2")),
+        ]);
+        let current_pages = get_pages(&[
+            (
+                "https://a.com/skipped",
+                CrawlPageStatus::SkippedByPath,
+                None,
+            ),
+            ("https://a.com/empty", CrawlPageStatus::Empty, Some("3")),
+        ]);
+
+        let diff = get_crawl_run_diff(&previous_pages, &current_pages);
+
+        assert_eq!(
+            diff.removed,
+            vec!["https://a.com/empty", "https://a.com/skipped"]
+        );
+        assert!(diff.added.is_empty());
+    }
+
+    #[test]
+    fn test_diff_reports_pages_which_recovered_as_added() {
+        let previous_pages = get_pages(&[("https://a.com", CrawlPageStatus::Failed, None)]);
+        let current_pages = get_pages(&[("https://a.com", CrawlPageStatus::Ingested, Some("1"))]);
+
+        let diff = get_crawl_run_diff(&previous_pages, &current_pages);
+
+        assert_eq!(diff.added, vec!["https://a.com"]);
+        assert!(diff.removed.is_empty());
+    }
+}
@@ -0,0 +1,1016 @@
+use super::chunk_operator::{
+    bulk_insert_chunk_metadata_query, bulk_revert_insert_chunk_metadata_query,
+    create_chunk_metadata,
+};
+use super::file_operator::get_aws_bucket;
+use super::group_operator::create_groups_query;
+use super::model_operator::get_bm25_embeddings;
+use super::parse_operator::convert_html_to_text;
+use super::qdrant_operator::{bulk_upsert_qdrant_points_query, get_point_vectors_query};
+use crate::data::models::{
+    ArchivedChunk, ArchivedFile, ChunkData, ChunkGroup, ChunkMetadata, ChunkMetadataTable, Dataset,
+    DatasetArchiveManifest, DatasetConfiguration, File, FileGroup, Pool, QdrantPayload,
+    DATASET_ARCHIVE_VERSION,
+};
+use crate::errors::ServiceError;
+use crate::handlers::chunk_handler::ChunkReqPayload;
+use actix_web::web;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types;
+use diesel_async::RunQueryDsl;
+use itertools::Itertools;
+use qdrant_client::qdrant::{PointStruct, Vector};
+use redis::aio::MultiplexedConnection;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, Write};
+
+const ARCHIVE_PAGE_SIZE: i64 = 1000;
+const IMPORT_BATCH_SIZE: usize = 120;
+
+pub const ARCHIVE_MANIFEST_PATH: &str = "manifest.json";
+pub const ARCHIVE_CHUNKS_PATH: &str = "chunks.jsonl";
+pub const ARCHIVE_GROUPS_PATH: &str = "groups.jsonl";
+pub const ARCHIVE_FILES_PATH: &str = "files.jsonl";
+
+/// S3 key the uploaded archive is stored under until the import worker has processed it.
+pub fn get_dataset_import_key(import_id: uuid::Uuid) -> String {
+    format!("dataset_imports/{}", import_id)
+}
+
+/// S3 key the archive of a finished export is stored under for the download link.
+pub fn get_dataset_export_key(dataset_id: uuid::Uuid, export_id: uuid::Uuid) -> String {
+    format!("dataset_exports/{}/{}.zip", dataset_id, export_id)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn scroll_archive_chunks_query(
+    dataset_id: uuid::Uuid,
+    offset: uuid::Uuid,
+    limit: i64,
+    pool: web::Data<Pool>,
+) -> Result<Vec<ChunkMetadata>, ServiceError> {
+    use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
+    use crate::data::schema::chunk_metadata_tags::dsl as chunk_metadata_tags_columns;
+    use crate::data::schema::dataset_tags::dsl as dataset_tags_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let chunk_metadata_pairs: Vec<(ChunkMetadataTable, Option<Vec<String>>)> =
+        chunk_metadata_columns::chunk_metadata
+            .left_join(
+                chunk_metadata_tags_columns::chunk_metadata_tags
+                    .on(chunk_metadata_tags_columns::chunk_metadata_id
+                        .eq(chunk_metadata_columns::id)),
+            )
+            .left_join(
+                dataset_tags_columns::dataset_tags
+                    .on(dataset_tags_columns::id.eq(chunk_metadata_tags_columns::tag_id)),
+            )
+            .filter(chunk_metadata_columns::dataset_id.eq(dataset_id))
+            .filter(chunk_metadata_columns::id.gt(offset))
+            .select((
+                ChunkMetadataTable::as_select(),
+                sql::<sql_types::Array<sql_types::Text>>(
+                    "array_remove(array_agg(dataset_tags.tag), null)",
+                )
+                .nullable(),
+            ))
+            .group_by(chunk_metadata_columns::id)
+            .order_by(chunk_metadata_columns::id)
+            .limit(limit)
+            .load::<(ChunkMetadataTable, Option<Vec<String>>)>(&mut conn)
+            .await
+            .map_err(|err| {
+                log::error!("Failed to scroll chunks for archive {:?}", err);
+                ServiceError::BadRequest("Failed to scroll chunks for archive".to_string())
+            })?;
+
+    Ok(chunk_metadata_pairs
+        .into_iter()
+        .map(|(table, tag_set)| {
+            ChunkMetadata::from_table_and_tag_set(table, tag_set.unwrap_or_default())
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(pool, chunk_ids))]
+pub async fn get_group_ids_for_chunks_query(
+    chunk_ids: Vec<uuid::Uuid>,
+    pool: web::Data<Pool>,
+) -> Result<HashMap<uuid::Uuid, Vec<uuid::Uuid>>, ServiceError> {
+    use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let bookmarks = chunk_group_bookmarks_columns::chunk_group_bookmarks
+        .filter(chunk_group_bookmarks_columns::chunk_metadata_id.eq_any(chunk_ids))
+        .select((
+            chunk_group_bookmarks_columns::chunk_metadata_id,
+            chunk_group_bookmarks_columns::group_id,
+        ))
+        .load::<(uuid::Uuid, uuid::Uuid)>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get groups for chunks {:?}", err);
+            ServiceError::BadRequest("Failed to get groups for chunks".to_string())
+        })?;
+
+    Ok(bookmarks.into_iter().into_group_map())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn scroll_archive_groups_query(
+    dataset_id: uuid::Uuid,
+    offset: uuid::Uuid,
+    limit: i64,
+    pool: web::Data<Pool>,
+) -> Result<Vec<ChunkGroup>, ServiceError> {
+    use crate::data::schema::chunk_group::dsl as chunk_group_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    chunk_group_columns::chunk_group
+        .filter(chunk_group_columns::dataset_id.eq(dataset_id))
+        .filter(chunk_group_columns::id.gt(offset))
+        .order_by(chunk_group_columns::id)
+        .limit(limit)
+        .select(ChunkGroup::as_select())
+        .load::<ChunkGroup>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to scroll groups for archive {:?}", err);
+            ServiceError::BadRequest("Failed to scroll groups for archive".to_string())
+        })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_archive_files_query(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Vec<ArchivedFile>, ServiceError> {
+    use crate::data::schema::files::dsl as files_columns;
+    use crate::data::schema::groups_from_files::dsl as groups_from_files_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let files = files_columns::files
+        .filter(files_columns::dataset_id.eq(dataset_id))
+        .select(File::as_select())
+        .load::<File>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get files for archive {:?}", err);
+            ServiceError::BadRequest("Failed to get files for archive".to_string())
+        })?;
+
+    let mut file_group_ids = groups_from_files_columns::groups_from_files
+        .filter(groups_from_files_columns::file_id.eq_any(files.iter().map(|file| file.id)))
+        .select((
+            groups_from_files_columns::file_id,
+            groups_from_files_columns::group_id,
+        ))
+        .load::<(uuid::Uuid, uuid::Uuid)>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get file groups for archive {:?}", err);
+            ServiceError::BadRequest("Failed to get file groups for archive".to_string())
+        })?
+        .into_iter()
+        .into_group_map();
+
+    Ok(files
+        .into_iter()
+        .map(|file| ArchivedFile {
+            group_ids: file_group_ids.remove(&file.id).unwrap_or_default(),
+            file,
+            blob_path: None,
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(pool, files, file_groups))]
+pub async fn insert_archived_files_query(
+    files: Vec<File>,
+    file_groups: Vec<FileGroup>,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::files::dsl as files_columns;
+    use crate::data::schema::groups_from_files::dsl as groups_from_files_columns;
+
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::insert_into(files_columns::files)
+        .values(&files)
+        .execute(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to insert archived files {:?}", err);
+            ServiceError::BadRequest("Failed to insert archived files".to_string())
+        })?;
+
+    diesel::insert_into(groups_from_files_columns::groups_from_files)
+        .values(&file_groups)
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to insert archived file groups {:?}", err);
+            ServiceError::BadRequest("Failed to insert archived file groups".to_string())
+        })?;
+
+    Ok(())
+}
+
+fn archive_write_error(err: impl std::fmt::Debug) -> ServiceError {
+    log::error!("Failed to write dataset archive {:?}", err);
+    ServiceError::InternalServerError("Failed to write dataset archive".to_string())
+}
+
+fn start_archive_entry<W: Write + Seek>(
+    zip_writer: &mut zip::ZipWriter<W>,
+    path: impl Into<String>,
+) -> Result<(), ServiceError> {
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    zip_writer
+        .start_file(path.into(), options)
+        .map_err(archive_write_error)
+}
+
+fn write_archive_manifest<W: Write + Seek>(
+    zip_writer: &mut zip::ZipWriter<W>,
+    manifest: &DatasetArchiveManifest,
+) -> Result<(), ServiceError> {
+    start_archive_entry(zip_writer, ARCHIVE_MANIFEST_PATH)?;
+    serde_json::to_writer_pretty(zip_writer, manifest).map_err(archive_write_error)
+}
+
+fn write_jsonl_line<W: Write + Seek, T: Serialize>(
+    zip_writer: &mut zip::ZipWriter<W>,
+    value: &T,
+) -> Result<(), ServiceError> {
+    serde_json::to_writer(&mut *zip_writer, value).map_err(archive_write_error)?;
+    zip_writer.write_all(b"\n").map_err(archive_write_error)
+}
+
+/// Writes a zip archive with the dataset configuration, chunks, groups and files of a dataset into `writer` and returns it with the number of chunks written. Vectors are included when `include_vectors` is set such that an import into a dataset with the same embedding size can skip re-embedding.
+#[tracing::instrument(skip(writer, pool))]
+pub async fn create_dataset_archive<W: Write + Seek>(
+    writer: W,
+    dataset: Dataset,
+    include_vectors: bool,
+    include_file_blobs: bool,
+    pool: web::Data<Pool>,
+) -> Result<(W, usize), ServiceError> {
+    let dataset_config = DatasetConfiguration::from_json(dataset.server_configuration.clone());
+    let mut zip_writer = zip::ZipWriter::new(writer);
+
+    start_archive_entry(&mut zip_writer, ARCHIVE_CHUNKS_PATH)?;
+
+    let mut chunk_count = 0;
+    let mut offset = uuid::Uuid::nil();
+    loop {
+        let chunk_metadatas =
+            scroll_archive_chunks_query(dataset.id, offset, ARCHIVE_PAGE_SIZE, pool.clone())
+                .await?;
+
+        let last_chunk_id = match chunk_metadatas.last() {
+            Some(chunk_metadata) => chunk_metadata.id,
+            None => break,
+        };
+
+        let mut group_ids = get_group_ids_for_chunks_query(
+            chunk_metadatas.iter().map(|chunk| chunk.id).collect(),
+            pool.clone(),
+        )
+        .await?;
+
+        let mut point_vectors = if include_vectors {
+            get_point_vectors_query(
+                chunk_metadatas
+                    .iter()
+                    .map(|chunk| chunk.qdrant_point_id)
+                    .collect(),
+                dataset_config.clone(),
+            )
+            .await?
+        } else {
+            HashMap::new()
+        };
+
+        for chunk_metadata in chunk_metadatas {
+            let (dense_vector, sparse_vector) = point_vectors
+                .remove(&chunk_metadata.qdrant_point_id)
+                .unwrap_or_default();
+
+            let archived_chunk = ArchivedChunk::from_details(
+                chunk_metadata.clone(),
+                group_ids.remove(&chunk_metadata.id).unwrap_or_default(),
+                dense_vector,
+                sparse_vector,
+            );
+
+            write_jsonl_line(&mut zip_writer, &archived_chunk)?;
+            chunk_count += 1;
+        }
+
+        offset = last_chunk_id;
+    }
+
+    start_archive_entry(&mut zip_writer, ARCHIVE_GROUPS_PATH)?;
+
+    let mut group_count = 0;
+    let mut offset = uuid::Uuid::nil();
+    loop {
+        let groups =
+            scroll_archive_groups_query(dataset.id, offset, ARCHIVE_PAGE_SIZE, pool.clone())
+                .await?;
+
+        let last_group_id = match groups.last() {
+            Some(group) => group.id,
+            None => break,
+        };
+
+        for group in groups {
+            write_jsonl_line(&mut zip_writer, &group)?;
+            group_count += 1;
+        }
+
+        offset = last_group_id;
+    }
+
+    let mut archived_files = get_archive_files_query(dataset.id, pool.clone()).await?;
+    if include_file_blobs {
+        let bucket = get_aws_bucket()?;
+        for archived_file in archived_files.iter_mut() {
+            let file_data = match bucket.get_object(archived_file.file.id.to_string()).await {
+                Ok(response) => response.as_slice().to_vec(),
+                Err(err) => {
+                    log::error!(
+                        "Could not get file {} from S3, exporting without its contents {:?}",
+                        archived_file.file.id,
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            let blob_path = format!("files/{}", archived_file.file.id);
+            start_archive_entry(&mut zip_writer, blob_path.clone())?;
+            zip_writer
+                .write_all(&file_data)
+                .map_err(archive_write_error)?;
+            archived_file.blob_path = Some(blob_path);
+        }
+    }
+
+    start_archive_entry(&mut zip_writer, ARCHIVE_FILES_PATH)?;
+    for archived_file in archived_files.iter() {
+        write_jsonl_line(&mut zip_writer, archived_file)?;
+    }
+
+    let manifest = DatasetArchiveManifest {
+        version: DATASET_ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now().naive_local(),
+        dataset_id: dataset.id,
+        dataset_name: dataset.name,
+        tracking_id: dataset.tracking_id,
+        server_configuration: serde_json::json!(dataset_config),
+        embedding_size: dataset_config.EMBEDDING_SIZE,
+        includes_vectors: include_vectors,
+        includes_file_blobs: include_file_blobs,
+        chunk_count,
+        group_count,
+        file_count: archived_files.len(),
+    };
+
+    write_archive_manifest(&mut zip_writer, &manifest)?;
+
+    Ok((
+        zip_writer.finish().map_err(archive_write_error)?,
+        chunk_count,
+    ))
+}
+
+fn read_archive_entry(archive: &[u8], path: &str) -> Result<Option<Vec<u8>>, ServiceError> {
+    let mut zip_archive = zip::ZipArchive::new(Cursor::new(archive))
+        .map_err(|_| ServiceError::BadRequest("Dataset archive is not a valid zip".to_string()))?;
+
+    let mut entry = match zip_archive.by_name(path) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => {
+            log::error!("Failed to read {} from dataset archive {:?}", path, err);
+            return Err(ServiceError::BadRequest(format!(
+                "Failed to read {} from dataset archive",
+                path
+            )));
+        }
+    };
+
+    let mut contents = vec![];
+    entry.read_to_end(&mut contents).map_err(|err| {
+        log::error!("Failed to read {} from dataset archive {:?}", path, err);
+        ServiceError::BadRequest(format!("Failed to read {} from dataset archive", path))
+    })?;
+
+    Ok(Some(contents))
+}
+
+pub fn read_archive_manifest(archive: &[u8]) -> Result<DatasetArchiveManifest, ServiceError> {
+    let manifest_bytes = read_archive_entry(archive, ARCHIVE_MANIFEST_PATH)?.ok_or(
+        ServiceError::BadRequest("Dataset archive is missing manifest.json".to_string()),
+    )?;
+
+    let manifest: DatasetArchiveManifest =
+        serde_json::from_slice(&manifest_bytes).map_err(|err| {
+            ServiceError::BadRequest(format!("Invalid dataset archive manifest: {}", err))
+        })?;
+
+    if manifest.version > DATASET_ARCHIVE_VERSION {
+        return Err(ServiceError::BadRequest(format!(
+            "Dataset archive version {} is newer than the supported version {}",
+            manifest.version, DATASET_ARCHIVE_VERSION
+        )));
+    }
+
+    Ok(manifest)
+}
+
+/// Parses every line of a newline-delimited JSON entry of the archive. Missing entries are treated as empty.
+pub fn read_archive_jsonl<T: DeserializeOwned>(
+    archive: &[u8],
+    path: &str,
+) -> Result<Vec<T>, ServiceError> {
+    let contents = match read_archive_entry(archive, path)? {
+        Some(contents) => contents,
+        None => return Ok(vec![]),
+    };
+
+    String::from_utf8_lossy(&contents)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|err| {
+                ServiceError::BadRequest(format!("Invalid line {} in {}: {}", i + 1, path, err))
+            })
+        })
+        .collect()
+}
+
+/// Whether the archived chunk's vectors can be inserted as is instead of re-embedding it with the dataset's models
+fn can_reuse_archived_vectors(
+    manifest: &DatasetArchiveManifest,
+    archived_chunk: &ArchivedChunk,
+    dataset_config: &DatasetConfiguration,
+) -> bool {
+    manifest.includes_vectors
+        && manifest.embedding_size == dataset_config.EMBEDDING_SIZE
+        && archived_chunk
+            .dense_vector
+            .as_ref()
+            .is_some_and(|vector| vector.len() == dataset_config.EMBEDDING_SIZE)
+        && (archived_chunk.sparse_vector.is_some() || !dataset_config.FULLTEXT_ENABLED)
+}
+
+fn get_archived_chunk_group_ids(
+    archived_chunk: &ArchivedChunk,
+    group_id_map: &HashMap<uuid::Uuid, ChunkGroup>,
+) -> Option<Vec<uuid::Uuid>> {
+    let group_ids = archived_chunk
+        .group_ids
+        .iter()
+        .filter_map(|group_id| group_id_map.get(group_id).map(|group| group.id))
+        .collect::<Vec<uuid::Uuid>>();
+
+    if group_ids.is_empty() {
+        None
+    } else {
+        Some(group_ids)
+    }
+}
+
+/// Inserts chunks whose archived vectors match the dataset straight into postgres and qdrant, without calling the embedding servers.
+#[tracing::instrument(skip(archived_chunks, group_id_map, pool))]
+pub async fn insert_archived_chunks_with_vectors(
+    archived_chunks: Vec<ArchivedChunk>,
+    group_id_map: &HashMap<uuid::Uuid, ChunkGroup>,
+    dataset_id: uuid::Uuid,
+    dataset_config: DatasetConfiguration,
+    pool: web::Data<Pool>,
+) -> Result<usize, ServiceError> {
+    if archived_chunks.is_empty() {
+        return Ok(0);
+    }
+
+    let mut vectors_by_chunk_id = HashMap::new();
+    let chunk_datas = archived_chunks
+        .into_iter()
+        .map(|archived_chunk| {
+            let chunk_metadata = archived_chunk.to_chunk_metadata(dataset_id);
+            let content =
+                convert_html_to_text(&chunk_metadata.chunk_html.clone().unwrap_or_default());
+
+            vectors_by_chunk_id.insert(
+                chunk_metadata.id,
+                (
+                    archived_chunk.dense_vector.clone().unwrap_or_default(),
+                    archived_chunk
+                        .sparse_vector
+                        .clone()
+                        .unwrap_or(vec![(0, 0.0)]),
+                ),
+            );
+
+            ChunkData {
+                chunk_metadata,
+                content,
+                group_ids: get_archived_chunk_group_ids(&archived_chunk, group_id_map),
+                upsert_by_tracking_id: false,
+                fulltext_boost: None,
+                semantic_boost: None,
+            }
+        })
+        .collect::<Vec<ChunkData>>();
+
+    let inserted_chunk_datas =
+        bulk_insert_chunk_metadata_query(chunk_datas, dataset_id, false, pool.clone()).await?;
+
+    if inserted_chunk_datas.is_empty() {
+        return Ok(0);
+    }
+
+    let group_tag_sets = group_id_map
+        .values()
+        .map(|group| (group.id, group.tag_set.clone().unwrap_or_default()))
+        .collect::<HashMap<uuid::Uuid, Vec<Option<String>>>>();
+
+    let bm25_active = dataset_config.BM25_ENABLED
+        && std::env::var("BM25_ACTIVE").unwrap_or("false".to_string()) == "true";
+    let dense_vector_name = format!("{}_vectors", dataset_config.EMBEDDING_SIZE);
+
+    let qdrant_points = inserted_chunk_datas
+        .iter()
+        .map(|chunk_data| {
+            let (dense_vector, sparse_vector) = vectors_by_chunk_id
+                .remove(&chunk_data.chunk_metadata.id)
+                .unwrap_or_default();
+
+            let group_tag_set = chunk_data.group_ids.as_ref().map(|group_ids| {
+                group_ids
+                    .iter()
+                    .filter_map(|group_id| group_tag_sets.get(group_id))
+                    .flatten()
+                    .cloned()
+                    .dedup()
+                    .collect::<Vec<Option<String>>>()
+            });
+
+            let mut vector_payload = HashMap::from([
+                (dense_vector_name.clone(), Vector::from(dense_vector)),
+                ("sparse_vectors".to_string(), Vector::from(sparse_vector)),
+            ]);
+
+            if bm25_active {
+                if let Some(bm25_vector) = get_bm25_embeddings(
+                    vec![(chunk_data.content.clone(), None)],
+                    dataset_config.BM25_AVG_LEN,
+                    dataset_config.BM25_B,
+                    dataset_config.BM25_K,
+                    dataset_config.LANGUAGE,
+                )
+                .pop()
+                {
+                    vector_payload.insert("bm25_vectors".to_string(), Vector::from(bm25_vector));
+                }
+            }
+
+            PointStruct::new(
+                chunk_data.chunk_metadata.qdrant_point_id.to_string(),
+                vector_payload,
+                QdrantPayload::new(
+                    chunk_data.chunk_metadata.clone(),
+                    chunk_data.group_ids.clone(),
+                    None,
+                    group_tag_set,
+                ),
+            )
+        })
+        .collect::<Vec<PointStruct>>();
+
+    if let Err(err) = bulk_upsert_qdrant_points_query(qdrant_points, dataset_config).await {
+        bulk_revert_insert_chunk_metadata_query(
+            inserted_chunk_datas
+                .iter()
+                .map(|chunk_data| chunk_data.chunk_metadata.id)
+                .collect(),
+            pool.clone(),
+        )
+        .await?;
+
+        return Err(err);
+    }
+
+    Ok(inserted_chunk_datas.len())
+}
+
+/// Queues chunks which have no usable vectors on the ingestion queue such that they are embedded with the dataset's models.
+#[tracing::instrument(skip(archived_chunks, group_id_map, pool, redis_conn))]
+pub async fn queue_archived_chunks_for_ingestion(
+    archived_chunks: Vec<ArchivedChunk>,
+    group_id_map: &HashMap<uuid::Uuid, ChunkGroup>,
+    dataset_id: uuid::Uuid,
+    dataset_config: DatasetConfiguration,
+    pool: web::Data<Pool>,
+    mut redis_conn: MultiplexedConnection,
+) -> Result<usize, ServiceError> {
+    if archived_chunks.is_empty() {
+        return Ok(0);
+    }
+
+    let chunks = archived_chunks
+        .iter()
+        .map(|archived_chunk| ChunkReqPayload {
+            chunk_html: archived_chunk.chunk_html.clone(),
+            semantic_content: None,
+            link: archived_chunk.link.clone(),
+            tag_set: archived_chunk
+                .tag_set
+                .clone()
+                .map(|tag_set| tag_set.into_iter().flatten().collect()),
+            metadata: archived_chunk.metadata.clone(),
+            group_ids: get_archived_chunk_group_ids(archived_chunk, group_id_map),
+            group_tracking_ids: None,
+            location: archived_chunk.location,
+            tracking_id: archived_chunk.tracking_id.clone(),
+            upsert_by_tracking_id: None,
+            time_stamp: archived_chunk
+                .time_stamp
+                .map(|time_stamp| time_stamp.and_utc().to_rfc3339()),
+            weight: Some(archived_chunk.weight),
+            split_avg: None,
+            convert_html_to_text: None,
+            image_urls: archived_chunk
+                .image_urls
+                .clone()
+                .map(|image_urls| image_urls.into_iter().flatten().collect()),
+            num_value: archived_chunk.num_value,
+            fulltext_boost: None,
+            semantic_boost: None,
+            vector_fields: None,
+        })
+        .collect::<Vec<ChunkReqPayload>>();
+
+    let chunk_count = chunks.len();
+    let (ingestion_message, _) =
+        create_chunk_metadata(chunks, dataset_id, dataset_config, pool).await?;
+
+    let serialized_message = serde_json::to_string(&ingestion_message).map_err(|_| {
+        ServiceError::BadRequest("Failed to Serialize BulkUploadMessage".to_string())
+    })?;
+
+    redis::cmd("lpush")
+        .arg("ingestion")
+        .arg(&serialized_message)
+        .query_async::<redis::aio::MultiplexedConnection, ()>(&mut redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(chunk_count)
+}
+
+/// Rehydrates an archive into the dataset created for the import. Groups and files get new ids, chunks with vectors of the dataset's embedding size skip re-embedding and the rest are sent through the ingestion worker. Returns the number of chunks inserted directly and the number queued for embedding.
+#[tracing::instrument(skip(archive, pool, redis_conn))]
+pub async fn import_dataset_archive(
+    archive: &[u8],
+    dataset: Dataset,
+    pool: web::Data<Pool>,
+    redis_conn: MultiplexedConnection,
+) -> Result<(usize, usize), ServiceError> {
+    let manifest = read_archive_manifest(archive)?;
+    let dataset_config = DatasetConfiguration::from_json(dataset.server_configuration.clone());
+
+    let mut group_id_map: HashMap<uuid::Uuid, ChunkGroup> = HashMap::new();
+    let archived_groups: Vec<ChunkGroup> = read_archive_jsonl(archive, ARCHIVE_GROUPS_PATH)?;
+    for group_batch in archived_groups.chunks(ARCHIVE_PAGE_SIZE as usize) {
+        let new_groups = group_batch
+            .iter()
+            .map(|group| {
+                let new_group = ChunkGroup {
+                    id: uuid::Uuid::new_v4(),
+                    dataset_id: dataset.id,
+                    ..group.clone()
+                };
+                group_id_map.insert(group.id, new_group.clone());
+                new_group
+            })
+            .collect::<Vec<ChunkGroup>>();
+
+        create_groups_query(new_groups, false, pool.clone()).await?;
+    }
+
+    let archived_files: Vec<ArchivedFile> = read_archive_jsonl(archive, ARCHIVE_FILES_PATH)?;
+    let bucket = get_aws_bucket()?;
+    let mut new_files = vec![];
+    let mut new_file_groups = vec![];
+    for archived_file in archived_files {
+        let new_file = File {
+            id: uuid::Uuid::new_v4(),
+            dataset_id: dataset.id,
+            ..archived_file.file
+        };
+
+        if let Some(blob_path) = archived_file.blob_path {
+            if let Some(file_data) = read_archive_entry(archive, &blob_path)? {
+                bucket
+                    .put_object(new_file.id.to_string(), &file_data)
+                    .await
+                    .map_err(|err| {
+                        log::error!("Could not upload archived file to S3 {:?}", err);
+                        ServiceError::BadRequest("Could not upload archived file to S3".to_string())
+                    })?;
+            }
+        }
+
+        new_file_groups.extend(archived_file.group_ids.iter().filter_map(|group_id| {
+            group_id_map
+                .get(group_id)
+                .map(|group| FileGroup::from_details(new_file.id, group.id))
+        }));
+        new_files.push(new_file);
+    }
+    insert_archived_files_query(new_files, new_file_groups, pool.clone()).await?;
+
+    let archived_chunks: Vec<ArchivedChunk> = read_archive_jsonl(archive, ARCHIVE_CHUNKS_PATH)?;
+    let mut chunks_imported = 0;
+    let mut chunks_queued_for_embedding = 0;
+    for chunk_batch in archived_chunks.chunks(IMPORT_BATCH_SIZE) {
+        let (chunks_with_vectors, chunks_to_embed): (Vec<ArchivedChunk>, Vec<ArchivedChunk>) =
+            chunk_batch.iter().cloned().partition(|archived_chunk| {
+                can_reuse_archived_vectors(&manifest, archived_chunk, &dataset_config)
+            });
+
+        chunks_imported += insert_archived_chunks_with_vectors(
+            chunks_with_vectors,
+            &group_id_map,
+            dataset.id,
+            dataset_config.clone(),
+            pool.clone(),
+        )
+        .await?;
+
+        chunks_queued_for_embedding += queue_archived_chunks_for_ingestion(
+            chunks_to_embed,
+            &group_id_map,
+            dataset.id,
+            dataset_config.clone(),
+            pool.clone(),
+            redis_conn.clone(),
+        )
+        .await?;
+    }
+
+    Ok((chunks_imported, chunks_queued_for_embedding))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn archived_chunk(
+        chunk_html: &str,
+        tag_set: Vec<&str>,
+        group_ids: Vec<uuid::Uuid>,
+        dense_vector: Option<Vec<f32>>,
+    ) -> ArchivedChunk {
+        let chunk_metadata = ChunkMetadata::from_details(
+            &Some(chunk_html.to_string()),
+            &Some("https://trieve.ai".to_string()),
+            &Some(
+                tag_set
+                    .into_iter()
+                    .map(|tag| Some(tag.to_string()))
+                    .collect(),
+            ),
+            uuid::Uuid::new_v4(),
+            Some(serde_json::json!({"key": "value"})),
+            Some(format!("tracking-{}", chunk_html)),
+            None,
+            None,
+            None,
+            uuid::Uuid::new_v4(),
+            1.5,
+            Some(2.0),
+        );
+
+        ArchivedChunk::from_details(
+            chunk_metadata,
+            group_ids,
+            dense_vector,
+            Some(vec![(1, 0.5)]),
+        )
+    }
+
+    fn manifest(
+        dataset_config: &DatasetConfiguration,
+        chunk_count: usize,
+    ) -> DatasetArchiveManifest {
+        DatasetArchiveManifest {
+            version: DATASET_ARCHIVE_VERSION,
+            exported_at: chrono::Utc::now().naive_local(),
+            dataset_id: uuid::Uuid::new_v4(),
+            dataset_name: "dataset".to_string(),
+            tracking_id: Some("dataset-tracking-id".to_string()),
+            server_configuration: serde_json::json!(dataset_config),
+            embedding_size: dataset_config.EMBEDDING_SIZE,
+            includes_vectors: true,
+            includes_file_blobs: false,
+            chunk_count,
+            group_count: 1,
+            file_count: 0,
+        }
+    }
+
+    fn write_archive(
+        manifest: &DatasetArchiveManifest,
+        chunks: &[ArchivedChunk],
+        groups: &[ChunkGroup],
+    ) -> Vec<u8> {
+        let mut zip_writer = zip::ZipWriter::new(Cursor::new(vec![]));
+
+        start_archive_entry(&mut zip_writer, ARCHIVE_CHUNKS_PATH).unwrap();
+        for chunk in chunks {
+            write_jsonl_line(&mut zip_writer, chunk).unwrap();
+        }
+        start_archive_entry(&mut zip_writer, ARCHIVE_GROUPS_PATH).unwrap();
+        for group in groups {
+            write_jsonl_line(&mut zip_writer, group).unwrap();
+        }
+        write_archive_manifest(&mut zip_writer, manifest).unwrap();
+
+        zip_writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn archive_round_trips_chunks_groups_and_config() {
+        let group = ChunkGroup::from_details(
+            Some("group".to_string()),
+            Some("description".to_string()),
+            uuid::Uuid::new_v4(),
+            Some("group-tracking-id".to_string()),
+            None,
+            Some(vec![Some("group-tag".to_string())]),
+        );
+        let chunks = vec![
+            archived_chunk(
+                "first",
+                vec!["a", "b"],
+                vec![group.id],
+                Some(vec![0.1, 0.2, 0.3]),
+            ),
+            archived_chunk("second", vec![], vec![], None),
+        ];
+        let dataset_config = DatasetConfiguration {
+            EMBEDDING_SIZE: 3,
+            ..Default::default()
+        };
+        let manifest = manifest(&dataset_config, chunks.len());
+
+        let archive = write_archive(&manifest, &chunks, &[group.clone()]);
+
+        let read_manifest = read_archive_manifest(&archive).unwrap();
+        assert_eq!(read_manifest.dataset_id, manifest.dataset_id);
+        assert_eq!(read_manifest.chunk_count, 2);
+        assert_eq!(read_manifest.embedding_size, 3);
+        let read_config = DatasetConfiguration::from_json(read_manifest.server_configuration);
+        assert_eq!(read_config.EMBEDDING_SIZE, 3);
+        assert_eq!(
+            read_config.FULLTEXT_ENABLED,
+            dataset_config.FULLTEXT_ENABLED
+        );
+
+        let read_chunks: Vec<ArchivedChunk> =
+            read_archive_jsonl(&archive, ARCHIVE_CHUNKS_PATH).unwrap();
+        assert_eq!(read_chunks.len(), 2);
+        for (read_chunk, chunk) in read_chunks.iter().zip(chunks.iter()) {
+            assert_eq!(read_chunk.id, chunk.id);
+            assert_eq!(read_chunk.chunk_html, chunk.chunk_html);
+            assert_eq!(read_chunk.tracking_id, chunk.tracking_id);
+            assert_eq!(read_chunk.metadata, chunk.metadata);
+            assert_eq!(read_chunk.tag_set, chunk.tag_set);
+            assert_eq!(read_chunk.group_ids, chunk.group_ids);
+            assert_eq!(read_chunk.dense_vector, chunk.dense_vector);
+            assert_eq!(read_chunk.sparse_vector, chunk.sparse_vector);
+        }
+
+        let read_groups: Vec<ChunkGroup> =
+            read_archive_jsonl(&archive, ARCHIVE_GROUPS_PATH).unwrap();
+        assert_eq!(read_groups.len(), 1);
+        assert_eq!(read_groups[0].id, group.id);
+        assert_eq!(read_groups[0].tracking_id, group.tracking_id);
+        assert_eq!(read_groups[0].tag_set, group.tag_set);
+
+        let read_files: Vec<ArchivedFile> =
+            read_archive_jsonl(&archive, ARCHIVE_FILES_PATH).unwrap();
+        assert!(read_files.is_empty());
+    }
+
+    #[test]
+    fn chunk_memberships_map_to_imported_groups() {
+        let archived_group_id = uuid::Uuid::new_v4();
+        let imported_group = ChunkGroup::from_details(
+            Some("group".to_string()),
+            None,
+            uuid::Uuid::new_v4(),
+            None,
+            None,
+            None,
+        );
+        let group_id_map = HashMap::from([(archived_group_id, imported_group.clone())]);
+
+        let member = archived_chunk(
+            "member",
+            vec![],
+            vec![archived_group_id, uuid::Uuid::new_v4()],
+            None,
+        );
+        assert_eq!(
+            get_archived_chunk_group_ids(&member, &group_id_map),
+            Some(vec![imported_group.id])
+        );
+
+        let non_member = archived_chunk("non-member", vec![], vec![], None);
+        assert_eq!(
+            get_archived_chunk_group_ids(&non_member, &group_id_map),
+            None
+        );
+    }
+
+    #[test]
+    fn vectors_are_reused_only_when_embedding_size_matches() {
+        let chunk = archived_chunk("chunk", vec![], vec![], Some(vec![0.1, 0.2, 0.3]));
+        let export_config = DatasetConfiguration {
+            EMBEDDING_SIZE: 3,
+            ..Default::default()
+        };
+        let manifest = manifest(&export_config, 1);
+
+        assert!(can_reuse_archived_vectors(
+            &manifest,
+            &chunk,
+            &export_config
+        ));
+
+        let larger_config = DatasetConfiguration {
+            EMBEDDING_SIZE: 1536,
+            ..Default::default()
+        };
+        assert!(!can_reuse_archived_vectors(
+            &manifest,
+            &chunk,
+            &larger_config
+        ));
+
+        let without_vectors = DatasetArchiveManifest {
+            includes_vectors: false,
+            ..manifest.clone()
+        };
+        assert!(!can_reuse_archived_vectors(
+            &without_vectors,
+            &chunk,
+            &export_config
+        ));
+
+        let short_chunk = archived_chunk("short", vec![], vec![], Some(vec![0.1, 0.2]));
+        assert!(!can_reuse_archived_vectors(
+            &manifest,
+            &short_chunk,
+            &export_config
+        ));
+
+        let unembedded_chunk = archived_chunk("unembedded", vec![], vec![], None);
+        assert!(!can_reuse_archived_vectors(
+            &manifest,
+            &unembedded_chunk,
+            &export_config
+        ));
+    }
+}
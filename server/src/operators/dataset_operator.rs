@@ -1,13 +1,13 @@
 use crate::data::models::{
-    DatasetAndOrgWithSubAndPlan, DatasetAndUsage, DatasetConfiguration, DatasetUsageCount,
-    Organization, OrganizationWithSubAndPlan, RedisPool, StripePlan, StripeSubscription, UnifiedId,
-    WordDataset,
+    Bm25ReindexMessage, ChunkVersionActor, DatasetAndOrgWithSubAndPlan, DatasetAndUsage,
+    DatasetConfiguration, DatasetUsageCount, MigratePointMessage, MigrationMode, Organization,
+    OrganizationWithSubAndPlan, RedisPool, StripePlan, StripeSubscription, UnifiedId, WordDataset,
 };
 use crate::handlers::chunk_handler::ChunkFilter;
 use crate::handlers::dataset_handler::{GetDatasetsPagination, TagsWithCount};
 use crate::operators::clickhouse_operator::ClickHouseEvent;
 use crate::operators::qdrant_operator::{
-    delete_points_from_qdrant, get_qdrant_collection_from_dataset_config,
+    delete_dataset_points_from_qdrant, get_qdrant_collection_from_dataset_config,
 };
 use crate::{
     data::models::{Dataset, EventType, Pool, WorkerEvent},
@@ -221,6 +221,7 @@ pub struct ChunkDeleteMessage {
     pub dataset_id: uuid::Uuid,
     pub attempt_number: usize,
     pub filter: ChunkFilter,
+    pub actor: Option<ChunkVersionActor>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -352,6 +353,7 @@ pub async fn clear_dataset_query(
     use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
     use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
     use crate::data::schema::files::dsl as files_column;
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
 
     let mut conn = pool.get().await.map_err(|_e| {
         ServiceError::InternalServerError("Failed to get postgres connection".to_string())
@@ -405,6 +407,17 @@ pub async fn clear_dataset_query(
         ServiceError::BadRequest("Could not delete files".to_string())
     })?;
 
+    diesel::delete(
+        rag_answer_caches_columns::rag_answer_caches
+            .filter(rag_answer_caches_columns::dataset_id.eq(id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Could not delete cached answers: {}", err);
+        ServiceError::BadRequest("Could not delete cached answers".to_string())
+    })?;
+
     let mut last_offset_id = uuid::Uuid::nil();
 
     loop {
@@ -455,7 +468,7 @@ pub async fn clear_dataset_query(
             ServiceError::BadRequest("Could not delete chunks in current batch".to_string())
         })?;
 
-        delete_points_from_qdrant(qdrant_point_ids, qdrant_collection.clone())
+        delete_dataset_points_from_qdrant(qdrant_point_ids, qdrant_collection.clone())
             .await
             .map_err(|err| {
                 ServiceError::BadRequest(format!(
@@ -637,6 +650,100 @@ pub async fn update_dataset_query(
     Ok(new_dataset)
 }
 
+/// Queues a BM25 reindex of the dataset, used when the dataset's LANGUAGE changes. The reindex-worker pages through the chunks in the background.
+#[tracing::instrument(skip(redis_pool))]
+pub async fn queue_bm25_reindex_query(
+    dataset_id: uuid::Uuid,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    let mut redis_conn = redis_pool.get().await.map_err(|_| {
+        ServiceError::InternalServerError("Failed to get redis connection".to_string())
+    })?;
+
+    let message = serde_json::to_string(&Bm25ReindexMessage { dataset_id })
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    redis::cmd("lpush")
+        .arg("bm25_reindex")
+        .arg(&message)
+        .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(())
+}
+
+/// Queues every chunk of the dataset for the reindex-worker to recompute its BM25 vector in place
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn queue_bm25_reindex_points_query(
+    dataset_id: uuid::Uuid,
+    dataset_config: DatasetConfiguration,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let mut redis_conn = redis_pool.get().await.map_err(|_| {
+        ServiceError::InternalServerError("Failed to get redis connection".to_string())
+    })?;
+
+    let collection = get_qdrant_collection_from_dataset_config(&dataset_config);
+    let mut last_id = uuid::Uuid::nil();
+
+    loop {
+        let chunk_ids_and_point_ids: Vec<(uuid::Uuid, uuid::Uuid)> =
+            chunk_metadata_columns::chunk_metadata
+                .filter(chunk_metadata_columns::dataset_id.eq(dataset_id))
+                .filter(chunk_metadata_columns::id.gt(last_id))
+                .order_by(chunk_metadata_columns::id)
+                .select((
+                    chunk_metadata_columns::id,
+                    chunk_metadata_columns::qdrant_point_id,
+                ))
+                .limit(1000)
+                .load(&mut conn)
+                .await
+                .map_err(|err| {
+                    log::error!("Failed to get chunks to reindex {:?}", err);
+                    ServiceError::BadRequest("Failed to get chunks to reindex".to_string())
+                })?;
+
+        let Some((batch_last_id, _)) = chunk_ids_and_point_ids.last() else {
+            break;
+        };
+        last_id = *batch_last_id;
+
+        let message = serde_json::to_string(&MigratePointMessage {
+            qdrant_point_ids: chunk_ids_and_point_ids
+                .into_iter()
+                .map(|(_, qdrant_point_id)| qdrant_point_id)
+                .collect(),
+            from_collection: collection.clone(),
+            to_collection: collection.clone(),
+            mode: MigrationMode::BM25 {
+                average_len: dataset_config.BM25_AVG_LEN,
+                k: dataset_config.BM25_K,
+                b: dataset_config.BM25_B,
+                language: dataset_config.LANGUAGE,
+            },
+        })
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+        redis::cmd("lpush")
+            .arg("collection_migration")
+            .arg(&message)
+            .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *redis_conn)
+            .await
+            .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+    }
+
+    Ok(())
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn get_datasets_by_organization_id(
     org_id: uuid::Uuid,
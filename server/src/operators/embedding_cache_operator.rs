@@ -0,0 +1,384 @@
+use crate::{
+    data::models::{DatasetConfiguration, RedisPool},
+    errors::ServiceError,
+    handlers::chunk_handler::{FullTextBoost, SemanticBoost},
+};
+use actix_web::web;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::model_operator::{get_dense_vectors, get_sparse_vectors};
+
+/// Seconds a cached embedding is kept after it was last written, defaults to a week. A dense entry takes 4 bytes per dimension (6 KB for 1536 dimensions) of the shared Redis, 0 disables the cache.
+fn get_embedding_cache_ttl() -> u64 {
+    std::env::var("EMBEDDING_CACHE_TTL_SECONDS")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(60 * 60 * 24 * 7)
+}
+
+/// Counts of the chunks of an ingestion whose vectors were all reused from the cache and the ones which had to be embedded
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EmbeddingCacheStats {
+    pub skipped: usize,
+    pub embedded: usize,
+}
+
+impl EmbeddingCacheStats {
+    /// A chunk counts as skipped only when neither its dense nor its sparse vector had to be created
+    pub fn from_cache_hits(dense_hits: &[bool], sparse_hits: &[bool]) -> Self {
+        dense_hits.iter().zip(sparse_hits.iter()).fold(
+            EmbeddingCacheStats::default(),
+            |mut stats, (dense_hit, sparse_hit)| {
+                if *dense_hit && *sparse_hit {
+                    stats.skipped += 1;
+                } else {
+                    stats.embedded += 1;
+                }
+                stats
+            },
+        )
+    }
+}
+
+/// Key of the dense vector of a document, changes whenever the text, the semantic boost or the dataset's embedding model do
+pub fn get_dense_embedding_cache_key(
+    content: &str,
+    semantic_boost: &Option<SemanticBoost>,
+    dataset_config: &DatasetConfiguration,
+) -> String {
+    let hashed = serde_json::json!({
+        "provider": dataset_config.EMBEDDING_PROVIDER,
+        "base_url": dataset_config.EMBEDDING_BASE_URL,
+        "model": dataset_config.EMBEDDING_MODEL_NAME,
+        "size": dataset_config.EMBEDDING_SIZE,
+        "semantic_boost": semantic_boost,
+        "content": content,
+    });
+
+    format!(
+        "embedding_cache:dense:{}",
+        blake3::hash(hashed.to_string().as_bytes()).to_hex()
+    )
+}
+
+/// Key of the sparse vector of a document, changes whenever the text, the fulltext boost or the dataset's SPLADE model do
+pub fn get_sparse_embedding_cache_key(
+    content: &str,
+    fulltext_boost: &Option<FullTextBoost>,
+    dataset_config: &DatasetConfiguration,
+) -> String {
+    let hashed = serde_json::json!({
+        "provider": dataset_config.SPARSE_EMBEDDING_PROVIDER,
+        "server_origin": std::env::var("SPARSE_SERVER_DOC_ORIGIN").unwrap_or_default(),
+        "local_model": std::env::var("LOCAL_SPARSE_DOC_MODEL").unwrap_or_default(),
+        "fulltext_boost": fulltext_boost,
+        "content": content,
+    });
+
+    format!(
+        "embedding_cache:sparse:{}",
+        blake3::hash(hashed.to_string().as_bytes()).to_hex()
+    )
+}
+
+/// Looks up the cached vectors for each key, a missing or unreadable entry is returned as None so the caller embeds it again
+#[tracing::instrument(skip_all)]
+pub async fn get_cached_embeddings<T: DeserializeOwned>(
+    keys: &[String],
+    redis_pool: web::Data<RedisPool>,
+) -> Vec<Option<T>> {
+    if keys.is_empty() {
+        return vec![];
+    }
+
+    if get_embedding_cache_ttl() == 0 {
+        return keys.iter().map(|_| None).collect();
+    }
+
+    let mut redis_conn = match redis_pool.get().await {
+        Ok(redis_conn) => redis_conn,
+        Err(err) => {
+            log::error!(
+                "Failed to get redis connection for embedding cache {:?}",
+                err
+            );
+            return keys.iter().map(|_| None).collect();
+        }
+    };
+
+    let cached_embeddings = match redis::cmd("MGET")
+        .arg(keys)
+        .query_async::<redis::aio::MultiplexedConnection, Vec<Option<Vec<u8>>>>(&mut *redis_conn)
+        .await
+    {
+        Ok(cached_embeddings) => cached_embeddings,
+        Err(err) => {
+            log::error!("Failed to get cached embeddings {:?}", err);
+            return keys.iter().map(|_| None).collect();
+        }
+    };
+
+    cached_embeddings
+        .into_iter()
+        .map(|cached_embedding| {
+            cached_embedding.and_then(|bytes| bincode::deserialize::<T>(&bytes).ok())
+        })
+        .collect()
+}
+
+/// Stores freshly created vectors under their keys, failing to do so only means they are embedded again next time
+#[tracing::instrument(skip_all)]
+pub async fn cache_embeddings<T: Serialize>(
+    keys: &[String],
+    embeddings: &[T],
+    redis_pool: web::Data<RedisPool>,
+) {
+    let ttl = get_embedding_cache_ttl();
+    if keys.is_empty() || ttl == 0 {
+        return;
+    }
+
+    let mut redis_conn = match redis_pool.get().await {
+        Ok(redis_conn) => redis_conn,
+        Err(err) => {
+            log::error!(
+                "Failed to get redis connection for embedding cache {:?}",
+                err
+            );
+            return;
+        }
+    };
+
+    let mut pipeline = redis::pipe();
+
+    for (key, embedding) in keys.iter().zip(embeddings.iter()) {
+        let Ok(serialized_embedding) = bincode::serialize(embedding) else {
+            continue;
+        };
+
+        pipeline
+            .cmd("SET")
+            .arg(key)
+            .arg(serialized_embedding)
+            .arg("EX")
+            .arg(ttl)
+            .ignore();
+    }
+
+    if let Err(err) = pipeline
+        .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *redis_conn)
+        .await
+    {
+        log::error!("Failed to cache embeddings {:?}", err);
+    }
+}
+
+/// Places the vectors embedded for the contents which missed the cache into their slots. Errors if the provider returned a different number of vectors than were requested, as they can not be matched to their contents.
+fn merge_embedded_vectors<T>(
+    mut vectors: Vec<Option<T>>,
+    missing_indices: Vec<usize>,
+    embedded_vectors: Vec<T>,
+) -> Result<Vec<T>, ServiceError> {
+    if missing_indices.len() != embedded_vectors.len() {
+        return Err(ServiceError::InternalServerError(format!(
+            "Embedding provider returned {} vectors for {} documents",
+            embedded_vectors.len(),
+            missing_indices.len()
+        )));
+    }
+
+    for (i, vector) in missing_indices.into_iter().zip(embedded_vectors) {
+        vectors[i] = Some(vector);
+    }
+
+    vectors
+        .into_iter()
+        .collect::<Option<Vec<T>>>()
+        .ok_or(ServiceError::InternalServerError(
+            "Missing vector for a document".to_string(),
+        ))
+}
+
+/// Dense document vectors of each content where only the ones missing from the cache are embedded, along with whether each vector was a cache hit
+#[tracing::instrument(skip_all)]
+pub async fn get_dense_vectors_with_cache(
+    content_and_boosts: Vec<(String, Option<SemanticBoost>)>,
+    dataset_config: DatasetConfiguration,
+    reqwest_client: reqwest::Client,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(Vec<Vec<f32>>, Vec<bool>), ServiceError> {
+    let keys: Vec<String> = content_and_boosts
+        .iter()
+        .map(|(content, boost)| get_dense_embedding_cache_key(content, boost, &dataset_config))
+        .collect();
+
+    let vectors: Vec<Option<Vec<f32>>> = get_cached_embeddings(&keys, redis_pool.clone()).await;
+    let cache_hits: Vec<bool> = vectors.iter().map(|vector| vector.is_some()).collect();
+
+    let missing_indices: Vec<usize> = (0..vectors.len()).filter(|i| !cache_hits[*i]).collect();
+
+    if !missing_indices.is_empty() {
+        let embedded_vectors = get_dense_vectors(
+            missing_indices
+                .iter()
+                .map(|i| content_and_boosts[*i].clone())
+                .collect(),
+            "doc",
+            dataset_config,
+            reqwest_client,
+        )
+        .await?;
+
+        if embedded_vectors.len() == missing_indices.len() {
+            let missing_keys: Vec<String> =
+                missing_indices.iter().map(|i| keys[*i].clone()).collect();
+            cache_embeddings(&missing_keys, &embedded_vectors, redis_pool).await;
+        }
+
+        let vectors = merge_embedded_vectors(vectors, missing_indices, embedded_vectors)?;
+        return Ok((vectors, cache_hits));
+    }
+
+    Ok((vectors.into_iter().flatten().collect(), cache_hits))
+}
+
+/// Sparse document vectors of each content where only the ones missing from the cache are embedded, along with whether each vector was a cache hit
+#[tracing::instrument(skip_all)]
+pub async fn get_sparse_vectors_with_cache(
+    content_and_boosts: Vec<(String, Option<FullTextBoost>)>,
+    dataset_config: DatasetConfiguration,
+    reqwest_client: reqwest::Client,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(Vec<Vec<(u32, f32)>>, Vec<bool>), ServiceError> {
+    let keys: Vec<String> = content_and_boosts
+        .iter()
+        .map(|(content, boost)| get_sparse_embedding_cache_key(content, boost, &dataset_config))
+        .collect();
+
+    let vectors: Vec<Option<Vec<(u32, f32)>>> =
+        get_cached_embeddings(&keys, redis_pool.clone()).await;
+    let cache_hits: Vec<bool> = vectors.iter().map(|vector| vector.is_some()).collect();
+
+    let missing_indices: Vec<usize> = (0..vectors.len()).filter(|i| !cache_hits[*i]).collect();
+
+    if !missing_indices.is_empty() {
+        let embedded_vectors = get_sparse_vectors(
+            missing_indices
+                .iter()
+                .map(|i| content_and_boosts[*i].clone())
+                .collect(),
+            "doc",
+            dataset_config,
+            reqwest_client,
+        )
+        .await?;
+
+        if embedded_vectors.len() == missing_indices.len() {
+            let missing_keys: Vec<String> =
+                missing_indices.iter().map(|i| keys[*i].clone()).collect();
+            cache_embeddings(&missing_keys, &embedded_vectors, redis_pool).await;
+        }
+
+        let vectors = merge_embedded_vectors(vectors, missing_indices, embedded_vectors)?;
+        return Ok((vectors, cache_hits));
+    }
+
+    Ok((vectors.into_iter().flatten().collect(), cache_hits))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dense_cache_key_changes_with_content_boost_and_model() {
+        let dataset_config = DatasetConfiguration::from_json(serde_json::json!({}));
+        let key = get_dense_embedding_cache_key("hello", &None, &dataset_config);
+
+        assert!(key.starts_with("embedding_cache:dense:"));
+        assert_eq!(
+            key,
+            get_dense_embedding_cache_key("hello", &None, &dataset_config)
+        );
+        assert_ne!(
+            key,
+            get_dense_embedding_cache_key("hello world", &None, &dataset_config)
+        );
+        assert_ne!(
+            key,
+            get_dense_embedding_cache_key(
+                "hello",
+                &Some(SemanticBoost {
+                    phrase: "world".to_string(),
+                    distance_factor: 0.5,
+                }),
+                &dataset_config
+            )
+        );
+
+        let other_model_config = DatasetConfiguration::from_json(serde_json::json!({
+            "EMBEDDING_MODEL_NAME": "some-other-model",
+        }));
+        assert_ne!(
+            key,
+            get_dense_embedding_cache_key("hello", &None, &other_model_config)
+        );
+    }
+
+    #[test]
+    fn test_sparse_cache_key_differs_from_dense_key() {
+        let dataset_config = DatasetConfiguration::from_json(serde_json::json!({}));
+        let sparse_key = get_sparse_embedding_cache_key("hello", &None, &dataset_config);
+
+        assert!(sparse_key.starts_with("embedding_cache:sparse:"));
+        assert_ne!(
+            sparse_key,
+            get_dense_embedding_cache_key("hello", &None, &dataset_config)
+        );
+        assert_ne!(
+            sparse_key,
+            get_sparse_embedding_cache_key(
+                "hello",
+                &Some(FullTextBoost {
+                    phrase: "world".to_string(),
+                    boost_factor: 2.0,
+                }),
+                &dataset_config
+            )
+        );
+    }
+
+    #[test]
+    fn test_stats_only_skip_chunks_with_both_hits() {
+        let stats = EmbeddingCacheStats::from_cache_hits(
+            &[true, true, false, false],
+            &[true, false, true, false],
+        );
+
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.embedded, 3);
+    }
+
+    #[test]
+    fn test_merge_places_vectors_into_missing_slots() {
+        let vectors = merge_embedded_vectors(
+            vec![Some(vec![1.0]), None, Some(vec![3.0]), None],
+            vec![1, 3],
+            vec![vec![2.0], vec![4.0]],
+        )
+        .expect("Vectors should merge");
+
+        assert_eq!(vectors, vec![vec![1.0], vec![2.0], vec![3.0], vec![4.0]]);
+    }
+
+    #[test]
+    fn test_merge_rejects_wrong_number_of_vectors() {
+        let merged = merge_embedded_vectors(
+            vec![None, Some(vec![2.0]), None],
+            vec![0, 2],
+            vec![vec![1.0]],
+        );
+
+        assert!(merged.is_err());
+    }
+}
@@ -0,0 +1,1211 @@
+use crate::{
+    data::models::{DatasetConfiguration, EmbeddingProviderType},
+    errors::ServiceError,
+    get_env,
+};
+use futures::future::BoxFuture;
+use ndarray::{Array2, ArrayViewD, Axis};
+use serde::{Deserialize, Serialize};
+
+/// A backend which creates dense and sparse embeddings and reranks results. Each dataset picks one for each of these in its EMBEDDING_PROVIDER, SPARSE_EMBEDDING_PROVIDER and RERANKER_PROVIDER.
+pub trait EmbeddingProvider: Send + Sync {
+    /// Dense vectors of the inputs, in the same order. `embed_type` is either "doc" or "query".
+    fn dense_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>, ServiceError>>;
+
+    /// Sparse vectors of the inputs as (index, value) pairs, in the same order. `embed_type` is either "doc" or "query".
+    fn sparse_vectors<'a>(
+        &'a self,
+        _inputs: Vec<String>,
+        _embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<(u32, f32)>>, ServiceError>> {
+        Box::pin(async move {
+            Err(ServiceError::BadRequest(format!(
+                "The {} provider does not support sparse vectors",
+                self.provider_type()
+            )))
+        })
+    }
+
+    /// Relevance scores of the texts for the query, in the same order as the texts
+    fn rerank<'a>(
+        &'a self,
+        _query: String,
+        _texts: Vec<String>,
+    ) -> BoxFuture<'a, Result<Vec<f32>, ServiceError>> {
+        Box::pin(async move {
+            Err(ServiceError::BadRequest(format!(
+                "The {} provider does not support reranking",
+                self.provider_type()
+            )))
+        })
+    }
+
+    fn provider_type(&self) -> EmbeddingProviderType;
+}
+
+pub fn get_embedding_provider(
+    provider_type: EmbeddingProviderType,
+    dataset_config: &DatasetConfiguration,
+    reqwest_client: reqwest::Client,
+) -> Box<dyn EmbeddingProvider> {
+    match provider_type {
+        EmbeddingProviderType::OpenaiCompatible => Box::new(OpenAICompatibleProvider::new(
+            dataset_config,
+            reqwest_client,
+        )),
+        EmbeddingProviderType::Cohere => {
+            Box::new(CohereProvider::new(dataset_config, reqwest_client))
+        }
+        EmbeddingProviderType::Tei => Box::new(TeiProvider::new(dataset_config, reqwest_client)),
+        EmbeddingProviderType::Local => Box::new(LocalProvider::new(dataset_config)),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScorePair {
+    index: usize,
+    score: f32,
+}
+
+fn scores_in_order(pairs: Vec<ScorePair>, len: usize) -> Result<Vec<f32>, ServiceError> {
+    let mut scores = vec![0.0; len];
+    for pair in pairs {
+        let score = scores.get_mut(pair.index).ok_or_else(|| {
+            ServiceError::InternalServerError(
+                "Reranker returned a score for a text which was not sent".to_string(),
+            )
+        })?;
+        *score = pair.score;
+    }
+
+    Ok(scores)
+}
+
+/// Any server with an OpenAI compatible /embeddings route
+pub struct OpenAICompatibleProvider {
+    base_url: String,
+    api_key: String,
+    model: String,
+    reqwest_client: reqwest::Client,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIEmbeddingParameters {
+    input: Vec<String>,
+    model: String,
+    truncate: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIEmbeddingData {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIEmbedding {
+    embedding: Vec<f32>,
+}
+
+impl OpenAICompatibleProvider {
+    pub fn new(dataset_config: &DatasetConfiguration, reqwest_client: reqwest::Client) -> Self {
+        let config_embedding_base_url = dataset_config.EMBEDDING_BASE_URL.clone();
+
+        let base_url = match config_embedding_base_url.as_str() {
+            "" | "https://api.openai.com/v1" => {
+                get_env!("OPENAI_BASE_URL", "OPENAI_BASE_URL must be set").to_string()
+            }
+            "https://embedding.trieve.ai" => std::env::var("EMBEDDING_SERVER_ORIGIN")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or("https://embedding.trieve.ai".to_string()),
+            "https://embedding.trieve.ai/bge-m3" => std::env::var("EMBEDDING_SERVER_ORIGIN_BGEM3")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or("https://embedding.trieve.ai/bge-m3".to_string()),
+            "https://embedding.trieve.ai/jina-code" => {
+                std::env::var("EMBEDDING_SERVER_ORIGIN_JINA_CODE")
+                    .ok()
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or("https://embedding.trieve.ai/jina-code".to_string())
+            }
+            _ => config_embedding_base_url.clone(),
+        };
+
+        let openai_api_key = get_env!("OPENAI_API_KEY", "OPENAI_API_KEY should be set");
+        let api_key = if config_embedding_base_url == "https://embedding.trieve.ai/jina-code" {
+            std::env::var("JINA_CODE_API_KEY")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or(openai_api_key.to_string())
+        } else {
+            openai_api_key.to_string()
+        };
+
+        Self {
+            base_url,
+            api_key,
+            model: dataset_config.EMBEDDING_MODEL_NAME.clone(),
+            reqwest_client,
+        }
+    }
+}
+
+impl EmbeddingProvider for OpenAICompatibleProvider {
+    fn dense_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        _embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>, ServiceError>> {
+        Box::pin(async move {
+            let parameters = OpenAIEmbeddingParameters {
+                input: inputs,
+                model: self.model.clone(),
+                truncate: true,
+            };
+
+            let embeddings_resp = self
+                .reqwest_client
+                .post(format!(
+                    "{}/embeddings?api-version=2023-05-15",
+                    self.base_url
+                ))
+                .header("Authorization", &format!("Bearer {}", &self.api_key))
+                .header("api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .json(&parameters)
+                .send()
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest(
+                        "Failed to send message to embedding server".to_string(),
+                    )
+                })?
+                .json::<OpenAIEmbeddingData>()
+                .await
+                .map_err(|err| {
+                    ServiceError::BadRequest(format!(
+                        "Failed to format text from embeddings {}",
+                        err
+                    ))
+                })?;
+
+            let vectors: Vec<Vec<f32>> = embeddings_resp
+                .data
+                .into_iter()
+                .map(|inner| inner.embedding)
+                .collect();
+
+            if vectors.iter().any(|vector| vector.is_empty()) {
+                return Err(ServiceError::InternalServerError(
+                    "Embedding server responded with Base64 and that is not currently supported for embeddings".to_owned(),
+                ));
+            }
+
+            Ok(vectors)
+        })
+    }
+
+    fn provider_type(&self) -> EmbeddingProviderType {
+        EmbeddingProviderType::OpenaiCompatible
+    }
+}
+
+const COHERE_BASE_URL: &str = "https://api.cohere.com";
+const COHERE_DEFAULT_RERANKER_MODEL: &str = "rerank-english-v3.0";
+
+/// COHERE_API_KEY is only sent to Cohere itself. Datasets choose their own base url, so a server which mirrors Cohere's routes is called without a key instead of being handed the server's one.
+fn get_cohere_api_key(base_url: &str) -> Option<String> {
+    if base_url.trim_end_matches('/') != COHERE_BASE_URL {
+        return None;
+    }
+
+    std::env::var("COHERE_API_KEY")
+        .ok()
+        .filter(|api_key| !api_key.is_empty())
+}
+
+/// Cohere's v2 /embed and /rerank routes, or any server which mirrors them
+pub struct CohereProvider {
+    embedding_base_url: String,
+    reranker_base_url: String,
+    embedding_api_key: Option<String>,
+    reranker_api_key: Option<String>,
+    model: String,
+    reranker_model: String,
+    reqwest_client: reqwest::Client,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereEmbedParameters {
+    model: String,
+    texts: Vec<String>,
+    input_type: String,
+    embedding_types: Vec<String>,
+    truncate: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereEmbedResponse {
+    embeddings: CohereEmbeddings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereEmbeddings {
+    float: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereRerankParameters {
+    model: String,
+    query: String,
+    documents: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereRerankResponse {
+    results: Vec<CohereRerankResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CohereRerankResult {
+    index: usize,
+    relevance_score: f32,
+}
+
+impl CohereProvider {
+    pub fn new(dataset_config: &DatasetConfiguration, reqwest_client: reqwest::Client) -> Self {
+        let embedding_base_url = match dataset_config.EMBEDDING_BASE_URL.as_str() {
+            "" | "https://api.openai.com/v1" => COHERE_BASE_URL.to_string(),
+            base_url => base_url.to_string(),
+        };
+        let reranker_base_url = match dataset_config.RERANKER_BASE_URL.as_str() {
+            "" => COHERE_BASE_URL.to_string(),
+            base_url => base_url.to_string(),
+        };
+
+        Self {
+            embedding_api_key: get_cohere_api_key(&embedding_base_url),
+            reranker_api_key: get_cohere_api_key(&reranker_base_url),
+            embedding_base_url,
+            reranker_base_url,
+            model: dataset_config.EMBEDDING_MODEL_NAME.clone(),
+            reranker_model: dataset_config
+                .RERANKER_MODEL_NAME
+                .clone()
+                .unwrap_or(COHERE_DEFAULT_RERANKER_MODEL.to_string()),
+            reqwest_client,
+        }
+    }
+}
+
+impl EmbeddingProvider for CohereProvider {
+    fn dense_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>, ServiceError>> {
+        Box::pin(async move {
+            let input_type = match embed_type {
+                "query" => "search_query",
+                _ => "search_document",
+            };
+
+            let parameters = CohereEmbedParameters {
+                model: self.model.clone(),
+                texts: inputs,
+                input_type: input_type.to_string(),
+                embedding_types: vec!["float".to_string()],
+                truncate: "END".to_string(),
+            };
+
+            let mut embed_req = self
+                .reqwest_client
+                .post(format!("{}/v2/embed", self.embedding_base_url))
+                .header("Content-Type", "application/json")
+                .json(&parameters);
+            if let Some(api_key) = &self.embedding_api_key {
+                embed_req = embed_req.bearer_auth(api_key);
+            }
+
+            let embed_resp = embed_req
+                .send()
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest(
+                        "Failed to send message to embedding server".to_string(),
+                    )
+                })?
+                .json::<CohereEmbedResponse>()
+                .await
+                .map_err(|err| {
+                    ServiceError::BadRequest(format!(
+                        "Failed to format text from embeddings {}",
+                        err
+                    ))
+                })?;
+
+            Ok(embed_resp.embeddings.float)
+        })
+    }
+
+    fn rerank<'a>(
+        &'a self,
+        query: String,
+        texts: Vec<String>,
+    ) -> BoxFuture<'a, Result<Vec<f32>, ServiceError>> {
+        Box::pin(async move {
+            let texts_len = texts.len();
+            let parameters = CohereRerankParameters {
+                model: self.reranker_model.clone(),
+                query,
+                documents: texts,
+            };
+
+            let mut rerank_req = self
+                .reqwest_client
+                .post(format!("{}/v2/rerank", self.reranker_base_url))
+                .header("Content-Type", "application/json")
+                .json(&parameters);
+            if let Some(api_key) = &self.reranker_api_key {
+                rerank_req = rerank_req.bearer_auth(api_key);
+            }
+
+            let rerank_resp = rerank_req
+                .send()
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest("Failed to send message to reranker".to_string())
+                })?
+                .json::<CohereRerankResponse>()
+                .await
+                .map_err(|err| {
+                    log::error!("Failed to format response from reranker {:?}", err);
+                    ServiceError::InternalServerError(
+                        "Failed to format response from reranker".to_owned(),
+                    )
+                })?;
+
+            scores_in_order(
+                rerank_resp
+                    .results
+                    .into_iter()
+                    .map(|result| ScorePair {
+                        index: result.index,
+                        score: result.relevance_score,
+                    })
+                    .collect(),
+                texts_len,
+            )
+        })
+    }
+
+    fn provider_type(&self) -> EmbeddingProviderType {
+        EmbeddingProviderType::Cohere
+    }
+}
+
+/// HuggingFace text-embeddings-inference servers. Sparse vectors come from the SPLADE servers at SPARSE_SERVER_DOC_ORIGIN and SPARSE_SERVER_QUERY_ORIGIN.
+pub struct TeiProvider {
+    embedding_base_url: String,
+    reranker_base_url: String,
+    api_key: String,
+    reqwest_client: reqwest::Client,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TeiEmbedParameters {
+    inputs: Vec<String>,
+    truncate: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomSparseEmbedData {
+    pub inputs: Vec<String>,
+    pub encode_type: String,
+    pub truncate: bool,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+struct SpladeIndicies {
+    index: u32,
+    value: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrossEncoderData {
+    pub query: String,
+    pub texts: Vec<String>,
+    pub truncate: bool,
+}
+
+impl TeiProvider {
+    pub fn new(dataset_config: &DatasetConfiguration, reqwest_client: reqwest::Client) -> Self {
+        Self {
+            embedding_base_url: dataset_config.EMBEDDING_BASE_URL.clone(),
+            reranker_base_url: dataset_config.RERANKER_BASE_URL.clone(),
+            api_key: get_env!("OPENAI_API_KEY", "OPENAI_API_KEY should be set").to_string(),
+            reqwest_client,
+        }
+    }
+}
+
+impl EmbeddingProvider for TeiProvider {
+    fn dense_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        _embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>, ServiceError>> {
+        Box::pin(async move {
+            self.reqwest_client
+                .post(format!("{}/embed", self.embedding_base_url))
+                .header("Authorization", &format!("Bearer {}", &self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&TeiEmbedParameters {
+                    inputs,
+                    truncate: true,
+                })
+                .send()
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest(
+                        "Failed to send message to embedding server".to_string(),
+                    )
+                })?
+                .json::<Vec<Vec<f32>>>()
+                .await
+                .map_err(|err| {
+                    ServiceError::BadRequest(format!(
+                        "Failed to format text from embeddings {}",
+                        err
+                    ))
+                })
+        })
+    }
+
+    fn sparse_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<(u32, f32)>>, ServiceError>> {
+        Box::pin(async move {
+            let origin_key = match embed_type {
+                "doc" => "SPARSE_SERVER_DOC_ORIGIN",
+                "query" => "SPARSE_SERVER_QUERY_ORIGIN",
+                _ => unreachable!("Invalid embed_type passed"),
+            };
+
+            let server_origin = std::env::var(origin_key)
+                .ok()
+                .filter(|s| !s.is_empty())
+                .ok_or(ServiceError::BadRequest(format!(
+                    "env flag {} is not set",
+                    origin_key
+                )))?;
+
+            let sparse_embed_req = CustomSparseEmbedData {
+                inputs,
+                encode_type: embed_type.to_string(),
+                truncate: true,
+            };
+
+            let embedding_response = self
+                .reqwest_client
+                .post(format!("{}/embed_sparse", server_origin))
+                .header("Content-Type", "application/json")
+                .header("Authorization", &format!("Bearer {}", &self.api_key))
+                .json(&sparse_embed_req)
+                .send()
+                .await
+                .map_err(|err| {
+                    log::error!(
+                        "Failed sending request from custom embedding server {:?}",
+                        err
+                    );
+                    ServiceError::InternalServerError(format!(
+                        "Failed making call to server {:?}",
+                        err
+                    ))
+                })?
+                .text()
+                .await
+                .map_err(|_| {
+                    ServiceError::InternalServerError(
+                        "Failed to get text from embeddings".to_string(),
+                    )
+                })?;
+
+            let sparse_vectors = serde_json::from_str::<Vec<Vec<SpladeIndicies>>>(
+                &embedding_response,
+            )
+            .map_err(|_e| {
+                log::error!(
+                    "Failed parsing response from custom embedding server {:?}",
+                    embedding_response
+                );
+                ServiceError::InternalServerError(format!(
+                    "Failed parsing response from custom embedding server {:?}",
+                    embedding_response
+                ))
+            })?;
+
+            Ok(sparse_vectors
+                .into_iter()
+                .map(|sparse_vector| {
+                    sparse_vector
+                        .into_iter()
+                        .map(|splade_idx| (splade_idx.index, splade_idx.value))
+                        .collect()
+                })
+                .collect())
+        })
+    }
+
+    fn rerank<'a>(
+        &'a self,
+        query: String,
+        texts: Vec<String>,
+    ) -> BoxFuture<'a, Result<Vec<f32>, ServiceError>> {
+        Box::pin(async move {
+            let texts_len = texts.len();
+            let parameters = CrossEncoderData {
+                query,
+                texts,
+                truncate: true,
+            };
+
+            let rerank_resp = self
+                .reqwest_client
+                .post(format!("{}/rerank", self.reranker_base_url))
+                .header("Authorization", &format!("Bearer {}", &self.api_key))
+                .header("api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .json(&parameters)
+                .send()
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest(
+                        "Failed to send message to embedding server".to_string(),
+                    )
+                })?
+                .text()
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest("Failed to get text from embeddings".to_string())
+                })?;
+
+            let score_pairs: Vec<ScorePair> = serde_json::from_str(&rerank_resp).map_err(|e| {
+                log::error!("Failed to format response from embeddings server {:?}", e);
+                ServiceError::InternalServerError(
+                    "Failed to format response from embeddings server".to_owned(),
+                )
+            })?;
+
+            scores_in_order(score_pairs, texts_len)
+        })
+    }
+
+    fn provider_type(&self) -> EmbeddingProviderType {
+        EmbeddingProviderType::Tei
+    }
+}
+
+/// Runs ONNX models in process on the CPU, so no embedding server is needed. Each model is a directory under LOCAL_MODELS_DIR holding a `model.onnx` and its `tokenizer.json`.
+/// The dense model is the dataset's EMBEDDING_MODEL_NAME, the reranker is its RERANKER_MODEL_NAME and the SPLADE models are LOCAL_SPARSE_DOC_MODEL and LOCAL_SPARSE_QUERY_MODEL.
+/// Requires the server to be built with the `local-embeddings` feature. Only the models listed in LOCAL_MODELS can be used.
+pub struct LocalProvider {
+    model: String,
+    reranker_model: String,
+}
+
+const LOCAL_DEFAULT_RERANKER_MODEL: &str = "ms-marco-MiniLM-L-6-v2";
+/// Models scripts/download-local-models.sh puts into the server images
+const LOCAL_DEFAULT_MODELS: &str =
+    "bge-small-en-v1.5:cls,ms-marco-MiniLM-L-6-v2,splade-doc,splade-query";
+
+/// How a dense local model which returns one vector per token is pooled into a single vector. Models which already pool their output are used as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalPooling {
+    /// The vector of the first ([CLS]) token, which the bge models were trained with
+    Cls,
+    /// The mean of the token vectors over the attention mask, which sentence-transformers models were trained with
+    Mean,
+}
+
+impl LocalPooling {
+    /// bge models were trained for CLS pooling, every other model defaults to mean pooling
+    fn default_for_model(model_name: &str) -> Self {
+        if model_name.starts_with("bge-") {
+            LocalPooling::Cls
+        } else {
+            LocalPooling::Mean
+        }
+    }
+}
+
+/// Model names come from dataset configurations and are joined onto LOCAL_MODELS_DIR, so only the name of a single directory which is in the allow-list is accepted.
+/// Allow-list entries are `name` or `name:cls` / `name:mean` to set the pooling of a dense model. Returns the pooling of the model if it is allowed.
+fn find_allowed_local_model(model_name: &str, allowed_models: &str) -> Option<LocalPooling> {
+    let is_single_directory = !model_name.is_empty()
+        && !model_name.starts_with('.')
+        && model_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    if !is_single_directory {
+        return None;
+    }
+
+    allowed_models.split(',').find_map(|allowed_model| {
+        let (name, pooling) = match allowed_model.trim().split_once(':') {
+            Some((name, pooling)) => (name.trim(), Some(pooling.trim())),
+            None => (allowed_model.trim(), None),
+        };
+
+        if name != model_name {
+            return None;
+        }
+
+        match pooling.map(|pooling| pooling.to_lowercase()).as_deref() {
+            Some("cls") => Some(LocalPooling::Cls),
+            Some("mean") => Some(LocalPooling::Mean),
+            _ => Some(LocalPooling::default_for_model(model_name)),
+        }
+    })
+}
+
+fn get_local_model(model_name: &str) -> Result<(String, LocalPooling), ServiceError> {
+    let allowed_models = std::env::var("LOCAL_MODELS")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or(LOCAL_DEFAULT_MODELS.to_string());
+
+    let pooling = find_allowed_local_model(model_name, &allowed_models).ok_or_else(|| {
+        ServiceError::BadRequest(format!(
+            "{} is not one of the local models available on this server",
+            model_name
+        ))
+    })?;
+
+    Ok((model_name.to_string(), pooling))
+}
+
+/// Pools the output of a dense model into one vector per input. `output` is either [batch, hidden] for models which already pool or [batch, sequence, hidden].
+#[cfg_attr(not(feature = "local-embeddings"), allow(dead_code))]
+fn pool_dense_output(
+    output: ArrayViewD<f32>,
+    attention_mask: &Array2<i64>,
+    pooling: LocalPooling,
+) -> Vec<Vec<f32>> {
+    if output.ndim() == 2 {
+        return output
+            .outer_iter()
+            .map(|row| row.iter().copied().collect())
+            .collect();
+    }
+
+    output
+        .outer_iter()
+        .zip(attention_mask.outer_iter())
+        .map(|(tokens, mask)| match pooling {
+            LocalPooling::Cls => tokens
+                .axis_iter(Axis(0))
+                .next()
+                .map(|token| token.iter().copied().collect())
+                .unwrap_or_default(),
+            LocalPooling::Mean => {
+                let hidden_size = tokens.shape().last().copied().unwrap_or(0);
+                let mut sum = vec![0.0_f32; hidden_size];
+                let mut count = 0.0_f32;
+                for (token, mask) in tokens.axis_iter(Axis(0)).zip(mask.iter()) {
+                    if *mask == 0 {
+                        continue;
+                    }
+                    count += 1.0;
+                    sum.iter_mut()
+                        .zip(token.iter())
+                        .for_each(|(acc, value)| *acc += value);
+                }
+                sum.iter().map(|value| value / count.max(1.0)).collect()
+            }
+        })
+        .collect()
+}
+
+impl LocalProvider {
+    pub fn new(dataset_config: &DatasetConfiguration) -> Self {
+        Self {
+            model: dataset_config.EMBEDDING_MODEL_NAME.clone(),
+            reranker_model: dataset_config
+                .RERANKER_MODEL_NAME
+                .clone()
+                .unwrap_or(LOCAL_DEFAULT_RERANKER_MODEL.to_string()),
+        }
+    }
+
+    fn sparse_model(embed_type: &str) -> String {
+        let (env_key, default_model) = match embed_type {
+            "query" => ("LOCAL_SPARSE_QUERY_MODEL", "splade-query"),
+            _ => ("LOCAL_SPARSE_DOC_MODEL", "splade-doc"),
+        };
+
+        std::env::var(env_key)
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or(default_model.to_string())
+    }
+}
+
+impl EmbeddingProvider for LocalProvider {
+    fn dense_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        _embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>, ServiceError>> {
+        Box::pin(async move {
+            let (model_name, pooling) = get_local_model(&self.model)?;
+            local_models::dense_vectors(model_name, pooling, inputs).await
+        })
+    }
+
+    fn sparse_vectors<'a>(
+        &'a self,
+        inputs: Vec<String>,
+        embed_type: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Vec<(u32, f32)>>, ServiceError>> {
+        Box::pin(async move {
+            let (model_name, _) = get_local_model(&Self::sparse_model(embed_type))?;
+            local_models::sparse_vectors(model_name, inputs).await
+        })
+    }
+
+    fn rerank<'a>(
+        &'a self,
+        query: String,
+        texts: Vec<String>,
+    ) -> BoxFuture<'a, Result<Vec<f32>, ServiceError>> {
+        Box::pin(async move {
+            let (model_name, _) = get_local_model(&self.reranker_model)?;
+            local_models::rerank(model_name, query, texts).await
+        })
+    }
+
+    fn provider_type(&self) -> EmbeddingProviderType {
+        EmbeddingProviderType::Local
+    }
+}
+
+#[cfg(feature = "local-embeddings")]
+mod local_models {
+    use super::{pool_dense_output, LocalPooling};
+    use crate::errors::ServiceError;
+    use actix_web::web;
+    use dashmap::DashMap;
+    use ndarray::{Array2, ArrayViewD, Axis, CowArray};
+    use once_cell::sync::Lazy;
+    use ort::{Environment, GraphOptimizationLevel, Session, SessionBuilder, Value};
+    use std::{path::PathBuf, sync::Arc};
+    use tokenizers::{EncodeInput, Encoding, PaddingParams, Tokenizer, TruncationParams};
+
+    const MAX_SEQUENCE_LENGTH: usize = 512;
+
+    static ORT_ENVIRONMENT: Lazy<Arc<Environment>> = Lazy::new(|| {
+        Environment::builder()
+            .with_name("trieve-local-embeddings")
+            .build()
+            .expect("Failed to create ONNX runtime environment")
+            .into_arc()
+    });
+
+    /// Models are loaded on first use and kept for the life of the process
+    static LOCAL_MODELS: Lazy<DashMap<String, Arc<LocalModel>>> = Lazy::new(DashMap::new);
+
+    struct LocalModel {
+        session: Session,
+        tokenizer: Tokenizer,
+    }
+
+    fn models_dir() -> PathBuf {
+        PathBuf::from(
+            std::env::var("LOCAL_MODELS_DIR")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or("./models".to_string()),
+        )
+    }
+
+    fn load_model(model_name: &str) -> Result<Arc<LocalModel>, ServiceError> {
+        if let Some(model) = LOCAL_MODELS.get(model_name) {
+            return Ok(model.clone());
+        }
+
+        let model_dir = models_dir().join(model_name);
+
+        let session = SessionBuilder::new(&ORT_ENVIRONMENT)
+            .and_then(|builder| builder.with_optimization_level(GraphOptimizationLevel::Level3))
+            .and_then(|builder| builder.with_model_from_file(model_dir.join("model.onnx")))
+            .map_err(|err| {
+                log::error!("Failed to load local model {}: {:?}", model_name, err);
+                ServiceError::BadRequest(format!("Failed to load local model {}", model_name))
+            })?;
+
+        let mut tokenizer =
+            Tokenizer::from_file(model_dir.join("tokenizer.json")).map_err(|err| {
+                log::error!("Failed to load tokenizer of {}: {:?}", model_name, err);
+                ServiceError::BadRequest(format!(
+                    "Failed to load tokenizer of local model {}",
+                    model_name
+                ))
+            })?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_SEQUENCE_LENGTH,
+                ..Default::default()
+            }))
+            .map_err(|err| {
+                ServiceError::BadRequest(format!(
+                    "Failed to set truncation of local model {}: {:?}",
+                    model_name, err
+                ))
+            })?;
+
+        let model = Arc::new(LocalModel { session, tokenizer });
+        LOCAL_MODELS.insert(model_name.to_string(), model.clone());
+
+        Ok(model)
+    }
+
+    /// Runs the model over the encodings and returns its first output along with the attention mask
+    fn run_model<F, T>(
+        model: &LocalModel,
+        encodings: Vec<Encoding>,
+        read_output: F,
+    ) -> Result<T, ServiceError>
+    where
+        F: FnOnce(ArrayViewD<f32>, &Array2<i64>) -> T,
+    {
+        let batch_size = encodings.len();
+        let sequence_length = encodings
+            .first()
+            .map(|encoding| encoding.len())
+            .unwrap_or(0);
+
+        let to_array = |values: Vec<i64>| {
+            Array2::from_shape_vec((batch_size, sequence_length), values).map_err(|err| {
+                ServiceError::InternalServerError(format!("Failed to shape model inputs {:?}", err))
+            })
+        };
+
+        let input_ids = to_array(
+            encodings
+                .iter()
+                .flat_map(|encoding| encoding.get_ids().iter().map(|id| *id as i64))
+                .collect(),
+        )?;
+        let attention_mask = to_array(
+            encodings
+                .iter()
+                .flat_map(|encoding| encoding.get_attention_mask().iter().map(|m| *m as i64))
+                .collect(),
+        )?;
+        let token_type_ids = to_array(
+            encodings
+                .iter()
+                .flat_map(|encoding| encoding.get_type_ids().iter().map(|t| *t as i64))
+                .collect(),
+        )?;
+
+        let input_ids_dyn = CowArray::from(input_ids.into_dyn());
+        let attention_mask_dyn = CowArray::from(attention_mask.clone().into_dyn());
+        let token_type_ids_dyn = CowArray::from(token_type_ids.into_dyn());
+
+        let map_ort_err = |err: ort::OrtError| {
+            log::error!("Local model inference failed {:?}", err);
+            ServiceError::InternalServerError("Local model inference failed".to_string())
+        };
+
+        let allocator = model.session.allocator();
+        let mut inputs = vec![
+            Value::from_array(allocator, &input_ids_dyn).map_err(map_ort_err)?,
+            Value::from_array(allocator, &attention_mask_dyn).map_err(map_ort_err)?,
+        ];
+        // Not every model takes token type ids
+        if model.session.inputs.len() > 2 {
+            inputs.push(Value::from_array(allocator, &token_type_ids_dyn).map_err(map_ort_err)?);
+        }
+
+        let outputs = model.session.run(inputs).map_err(map_ort_err)?;
+        let output = outputs
+            .first()
+            .ok_or(ServiceError::InternalServerError(
+                "Local model returned no outputs".to_string(),
+            ))?
+            .try_extract::<f32>()
+            .map_err(map_ort_err)?;
+
+        Ok(read_output(output.view(), &attention_mask))
+    }
+
+    fn encode<I: Into<EncodeInput<'static>> + Send>(
+        model: &LocalModel,
+        inputs: Vec<I>,
+    ) -> Result<Vec<Encoding>, ServiceError> {
+        model
+            .tokenizer
+            .encode_batch(inputs, true)
+            .map_err(|err| ServiceError::BadRequest(format!("Failed to tokenize inputs {:?}", err)))
+    }
+
+    pub async fn dense_vectors(
+        model_name: String,
+        pooling: LocalPooling,
+        inputs: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, ServiceError> {
+        web::block(move || {
+            let model = load_model(&model_name)?;
+            let encodings = encode(&model, inputs)?;
+
+            run_model(&model, encodings, |output, attention_mask| {
+                pool_dense_output(output, attention_mask, pooling)
+                    .into_iter()
+                    .map(|vector| {
+                        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-12);
+                        vector.into_iter().map(|x| x / norm).collect()
+                    })
+                    .collect()
+            })
+        })
+        .await
+        .map_err(|err| ServiceError::BadRequest(format!("Thread error {:?}", err)))?
+    }
+
+    pub async fn sparse_vectors(
+        model_name: String,
+        inputs: Vec<String>,
+    ) -> Result<Vec<Vec<(u32, f32)>>, ServiceError> {
+        web::block(move || {
+            let model = load_model(&model_name)?;
+            let encodings = encode(&model, inputs)?;
+
+            // SPLADE: the weight of each vocabulary term is its max log(1 + relu(logit)) over the tokens of the input
+            run_model(&model, encodings, |output, attention_mask| {
+                output
+                    .outer_iter()
+                    .zip(attention_mask.outer_iter())
+                    .map(|(tokens, mask)| {
+                        let vocab_size = tokens.shape().last().copied().unwrap_or(0);
+                        let mut weights = vec![0.0_f32; vocab_size];
+                        for (token, mask) in tokens.axis_iter(Axis(0)).zip(mask.iter()) {
+                            if *mask == 0 {
+                                continue;
+                            }
+                            weights
+                                .iter_mut()
+                                .zip(token.iter())
+                                .for_each(|(weight, logit)| {
+                                    *weight = weight.max((1.0 + logit.max(0.0)).ln())
+                                });
+                        }
+
+                        weights
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, weight)| *weight > 0.0)
+                            .map(|(index, weight)| (index as u32, weight))
+                            .collect()
+                    })
+                    .collect()
+            })
+        })
+        .await
+        .map_err(|err| ServiceError::BadRequest(format!("Thread error {:?}", err)))?
+    }
+
+    pub async fn rerank(
+        model_name: String,
+        query: String,
+        texts: Vec<String>,
+    ) -> Result<Vec<f32>, ServiceError> {
+        web::block(move || {
+            let model = load_model(&model_name)?;
+            let encodings = encode(
+                &model,
+                texts
+                    .into_iter()
+                    .map(|text| (query.clone(), text))
+                    .collect(),
+            )?;
+
+            run_model(&model, encodings, |output, _| {
+                output
+                    .outer_iter()
+                    .map(|logits| {
+                        let logit = logits.iter().next().copied().unwrap_or(0.0);
+                        1.0 / (1.0 + (-logit).exp())
+                    })
+                    .collect()
+            })
+        })
+        .await
+        .map_err(|err| ServiceError::BadRequest(format!("Thread error {:?}", err)))?
+    }
+}
+
+#[cfg(not(feature = "local-embeddings"))]
+mod local_models {
+    use crate::errors::ServiceError;
+
+    fn not_built() -> ServiceError {
+        ServiceError::BadRequest(
+            "The local embedding provider requires the server to be built with the local-embeddings feature".to_string(),
+        )
+    }
+
+    pub async fn dense_vectors(
+        _model_name: String,
+        _pooling: super::LocalPooling,
+        _inputs: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, ServiceError> {
+        Err(not_built())
+    }
+
+    pub async fn sparse_vectors(
+        _model_name: String,
+        _inputs: Vec<String>,
+    ) -> Result<Vec<Vec<(u32, f32)>>, ServiceError> {
+        Err(not_built())
+    }
+
+    pub async fn rerank(
+        _model_name: String,
+        _query: String,
+        _texts: Vec<String>,
+    ) -> Result<Vec<f32>, ServiceError> {
+        Err(not_built())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_local_models_are_limited_to_the_allow_list() {
+        let allowed_models = "bge-small-en-v1.5, splade-doc";
+
+        assert!(find_allowed_local_model("bge-small-en-v1.5", allowed_models).is_some());
+        assert!(find_allowed_local_model("splade-doc", allowed_models).is_some());
+        assert!(find_allowed_local_model("splade-query", allowed_models).is_none());
+        assert!(find_allowed_local_model("", allowed_models).is_none());
+    }
+
+    #[test]
+    fn test_local_model_pooling_comes_from_the_allow_list() {
+        let allowed_models = "bge-small-en-v1.5, all-MiniLM-L6-v2, e5-small-v2:cls, bge-m3:mean";
+
+        assert_eq!(
+            find_allowed_local_model("bge-small-en-v1.5", allowed_models),
+            Some(LocalPooling::Cls)
+        );
+        assert_eq!(
+            find_allowed_local_model("all-MiniLM-L6-v2", allowed_models),
+            Some(LocalPooling::Mean)
+        );
+        assert_eq!(
+            find_allowed_local_model("e5-small-v2", allowed_models),
+            Some(LocalPooling::Cls)
+        );
+        assert_eq!(
+            find_allowed_local_model("bge-m3", allowed_models),
+            Some(LocalPooling::Mean)
+        );
+        assert_eq!(
+            find_allowed_local_model("bge-small-en-v1.5", LOCAL_DEFAULT_MODELS),
+            Some(LocalPooling::Cls)
+        );
+    }
+
+    /// Two inputs of three tokens with a hidden size of two, the last token of the second input is padding
+    fn token_outputs() -> (ndarray::ArrayD<f32>, Array2<i64>) {
+        let output = ndarray::Array3::from_shape_vec(
+            (2, 3, 2),
+            vec![
+                1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 10.0, 20.0, 30.0, 40.0, 100.0, 100.0,
+            ],
+        )
+        .unwrap()
+        .into_dyn();
+        let attention_mask = Array2::from_shape_vec((2, 3), vec![1, 1, 1, 1, 1, 0]).unwrap();
+
+        (output, attention_mask)
+    }
+
+    #[test]
+    fn test_cls_pooling_takes_the_first_token() {
+        let (output, attention_mask) = token_outputs();
+
+        assert_eq!(
+            pool_dense_output(output.view(), &attention_mask, LocalPooling::Cls),
+            vec![vec![1.0, 2.0], vec![10.0, 20.0]]
+        );
+    }
+
+    #[test]
+    fn test_mean_pooling_skips_padding() {
+        let (output, attention_mask) = token_outputs();
+
+        assert_eq!(
+            pool_dense_output(output.view(), &attention_mask, LocalPooling::Mean),
+            vec![vec![3.0, 4.0], vec![20.0, 30.0]]
+        );
+    }
+
+    #[test]
+    fn test_pooled_outputs_are_used_as_is() {
+        let output = Array2::from_shape_vec((2, 2), vec![1.0_f32, 2.0, 3.0, 4.0])
+            .unwrap()
+            .into_dyn();
+        let attention_mask = Array2::from_shape_vec((2, 1), vec![1, 1]).unwrap();
+
+        for pooling in [LocalPooling::Cls, LocalPooling::Mean] {
+            assert_eq!(
+                pool_dense_output(output.view(), &attention_mask, pooling),
+                vec![vec![1.0, 2.0], vec![3.0, 4.0]]
+            );
+        }
+    }
+
+    #[test]
+    fn test_local_models_must_be_a_single_directory() {
+        for model_name in ["../../etc", "/etc/passwd", "models/bge", "..", ".hidden"] {
+            assert!(
+                find_allowed_local_model(model_name, model_name).is_none(),
+                "{} should be rejected",
+                model_name
+            );
+        }
+    }
+
+    #[test]
+    fn test_cohere_api_key_is_only_sent_to_cohere() {
+        std::env::set_var("COHERE_API_KEY", "cohere-key");
+
+        assert_eq!(
+            get_cohere_api_key("https://api.cohere.com"),
+            Some("cohere-key".to_string())
+        );
+        assert_eq!(
+            get_cohere_api_key("https://api.cohere.com/"),
+            Some("cohere-key".to_string())
+        );
+        assert_eq!(
+            get_cohere_api_key("https://api.cohere.com.attacker.io"),
+            None
+        );
+        assert_eq!(get_cohere_api_key("https://cohere-mirror.internal"), None);
+    }
+}
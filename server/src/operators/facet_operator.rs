@@ -0,0 +1,257 @@
+use super::qdrant_operator::scroll_dataset_points;
+use super::search_operator::assemble_qdrant_filter;
+use crate::data::models::{
+    DatasetConfiguration, DateHistogramBucket, FacetOptions, FacetResults, FacetValueCount,
+    NumValueRange, NumValueRangeCount, Pool, TermsFacetResult,
+};
+use crate::errors::ServiceError;
+use crate::handlers::chunk_handler::ChunkFilter;
+use actix_web::web;
+use diesel::dsl::count;
+use diesel::sql_types::{Array, BigInt, Float8, Nullable, Text, Uuid as SqlUuid};
+use diesel::{ExpressionMethods, QueryDsl, QueryableByName};
+use diesel_async::RunQueryDsl;
+
+#[tracing::instrument(skip(pool, point_ids))]
+pub async fn get_tag_set_facet_query(
+    point_ids: Vec<uuid::Uuid>,
+    dataset_id: uuid::Uuid,
+    limit: i64,
+    pool: web::Data<Pool>,
+) -> Result<Vec<FacetValueCount>, ServiceError> {
+    use crate::data::schema::chunk_metadata::dsl as chunk_metadata_columns;
+    use crate::data::schema::chunk_metadata_tags::dsl as chunk_metadata_tags_columns;
+    use crate::data::schema::dataset_tags::dsl as dataset_tags_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let tag_counts = chunk_metadata_tags_columns::chunk_metadata_tags
+        .inner_join(chunk_metadata_columns::chunk_metadata)
+        .inner_join(dataset_tags_columns::dataset_tags)
+        .filter(dataset_tags_columns::dataset_id.eq(dataset_id))
+        .filter(chunk_metadata_columns::qdrant_point_id.eq_any(point_ids))
+        .group_by(dataset_tags_columns::tag)
+        .select((
+            dataset_tags_columns::tag,
+            count(chunk_metadata_tags_columns::chunk_metadata_id),
+        ))
+        .order_by(count(chunk_metadata_tags_columns::chunk_metadata_id).desc())
+        .limit(limit)
+        .load::<FacetValueCount>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get tag_set facet counts {:?}", err);
+            ServiceError::BadRequest("Failed to get tag_set facet counts".to_string())
+        })?;
+
+    Ok(tag_counts)
+}
+
+#[tracing::instrument(skip(pool, point_ids))]
+pub async fn get_metadata_facet_query(
+    point_ids: Vec<uuid::Uuid>,
+    dataset_id: uuid::Uuid,
+    metadata_path: Vec<String>,
+    limit: i64,
+    pool: web::Data<Pool>,
+) -> Result<Vec<FacetValueCount>, ServiceError> {
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let value_counts = diesel::sql_query(
+        "SELECT metadata #>> $1 AS value, COUNT(*) AS count FROM chunk_metadata \
+        WHERE dataset_id = $2 AND qdrant_point_id = ANY($3) AND metadata #>> $1 IS NOT NULL \
+        GROUP BY value ORDER BY count DESC LIMIT $4",
+    )
+    .bind::<Array<Text>, _>(metadata_path)
+    .bind::<SqlUuid, _>(dataset_id)
+    .bind::<Array<SqlUuid>, _>(point_ids)
+    .bind::<BigInt, _>(limit)
+    .load::<FacetValueCount>(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to get metadata facet counts {:?}", err);
+        ServiceError::BadRequest("Failed to get metadata facet counts".to_string())
+    })?;
+
+    Ok(value_counts)
+}
+
+#[derive(QueryableByName)]
+struct NumValueRangeCountRow {
+    #[diesel(sql_type = BigInt)]
+    idx: i64,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Counts the chunks whose num_value falls in each range with a single query, in the order the ranges were given.
+#[tracing::instrument(skip(pool, point_ids))]
+pub async fn get_num_value_range_counts_query(
+    point_ids: Vec<uuid::Uuid>,
+    dataset_id: uuid::Uuid,
+    ranges: &[NumValueRange],
+    pool: web::Data<Pool>,
+) -> Result<Vec<NumValueRangeCount>, ServiceError> {
+    if ranges.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let range_counts = diesel::sql_query(
+        "SELECT ranges.idx AS idx, COUNT(chunk_metadata.id) AS count \
+        FROM unnest($3::float8[], $4::float8[]) WITH ORDINALITY AS ranges(gte, lt, idx) \
+        LEFT JOIN chunk_metadata ON chunk_metadata.dataset_id = $1 \
+        AND chunk_metadata.qdrant_point_id = ANY($2) \
+        AND chunk_metadata.num_value IS NOT NULL \
+        AND (ranges.gte IS NULL OR chunk_metadata.num_value >= ranges.gte) \
+        AND (ranges.lt IS NULL OR chunk_metadata.num_value < ranges.lt) \
+        GROUP BY ranges.idx ORDER BY ranges.idx",
+    )
+    .bind::<SqlUuid, _>(dataset_id)
+    .bind::<Array<SqlUuid>, _>(point_ids)
+    .bind::<Array<Nullable<Float8>>, _>(ranges.iter().map(|range| range.gte).collect::<Vec<_>>())
+    .bind::<Array<Nullable<Float8>>, _>(ranges.iter().map(|range| range.lt).collect::<Vec<_>>())
+    .load::<NumValueRangeCountRow>(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to get num_value range counts {:?}", err);
+        ServiceError::BadRequest("Failed to get num_value range counts".to_string())
+    })?;
+
+    Ok(ranges
+        .iter()
+        .enumerate()
+        .map(|(i, range)| NumValueRangeCount {
+            gte: range.gte,
+            lt: range.lt,
+            count: range_counts
+                .iter()
+                .find(|row| row.idx == i as i64 + 1)
+                .map(|row| row.count)
+                .unwrap_or(0),
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip(pool, point_ids))]
+pub async fn get_time_stamp_histogram_query(
+    point_ids: Vec<uuid::Uuid>,
+    dataset_id: uuid::Uuid,
+    interval: String,
+    pool: web::Data<Pool>,
+) -> Result<Vec<DateHistogramBucket>, ServiceError> {
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let buckets = diesel::sql_query(
+        "SELECT date_trunc($1, time_stamp) AS key, COUNT(*) AS count FROM chunk_metadata \
+        WHERE dataset_id = $2 AND qdrant_point_id = ANY($3) AND time_stamp IS NOT NULL \
+        GROUP BY key ORDER BY key",
+    )
+    .bind::<Text, _>(interval)
+    .bind::<SqlUuid, _>(dataset_id)
+    .bind::<Array<SqlUuid>, _>(point_ids)
+    .load::<DateHistogramBucket>(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to get time_stamp histogram {:?}", err);
+        ServiceError::BadRequest("Failed to get time_stamp histogram".to_string())
+    })?;
+
+    Ok(buckets)
+}
+
+/// Computes the requested facets over every chunk matching the filters of a search. The candidate set is resolved from qdrant with the same filter the search uses, then aggregated in postgres.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(pool))]
+pub async fn get_facets_query(
+    facet_options: FacetOptions,
+    filters: Option<ChunkFilter>,
+    quote_words: Option<Vec<String>>,
+    negated_words: Option<Vec<String>>,
+    dataset_id: uuid::Uuid,
+    config: &DatasetConfiguration,
+    pool: web::Data<Pool>,
+) -> Result<FacetResults, ServiceError> {
+    let filter = assemble_qdrant_filter(
+        filters,
+        quote_words,
+        negated_words,
+        dataset_id,
+        pool.clone(),
+    )
+    .await?;
+
+    let max_candidates = facet_options
+        .max_candidates
+        .unwrap_or(config.MAX_LIMIT)
+        .min(config.MAX_LIMIT);
+
+    let (point_ids, next_offset) =
+        scroll_dataset_points(max_candidates, None, None, config.clone(), filter).await?;
+
+    let mut terms = vec![];
+    for terms_facet in facet_options.terms.unwrap_or_default() {
+        let limit = terms_facet.limit.unwrap_or(10) as i64;
+
+        let values = if terms_facet.field == "tag_set" {
+            get_tag_set_facet_query(point_ids.clone(), dataset_id, limit, pool.clone()).await?
+        } else if let Some(metadata_key) = terms_facet.field.strip_prefix("metadata.") {
+            get_metadata_facet_query(
+                point_ids.clone(),
+                dataset_id,
+                metadata_key.split('.').map(|key| key.to_string()).collect(),
+                limit,
+                pool.clone(),
+            )
+            .await?
+        } else {
+            return Err(ServiceError::BadRequest(format!(
+                "Cannot facet on field {}. Field must be tag_set or metadata.<key>",
+                terms_facet.field
+            )));
+        };
+
+        terms.push(TermsFacetResult {
+            field: terms_facet.field,
+            values,
+        });
+    }
+
+    let num_value_ranges = get_num_value_range_counts_query(
+        point_ids.clone(),
+        dataset_id,
+        &facet_options.num_value_ranges.unwrap_or_default(),
+        pool.clone(),
+    )
+    .await?;
+
+    let time_stamp_histogram = match facet_options.time_stamp_interval {
+        Some(interval) => {
+            get_time_stamp_histogram_query(
+                point_ids.clone(),
+                dataset_id,
+                interval.to_string(),
+                pool.clone(),
+            )
+            .await?
+        }
+        None => vec![],
+    };
+
+    Ok(FacetResults {
+        terms,
+        num_value_ranges,
+        time_stamp_histogram,
+        candidate_count: point_ids.len() as u64,
+        truncated: next_offset.is_some(),
+    })
+}
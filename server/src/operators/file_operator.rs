@@ -1,8 +1,10 @@
 use super::chunk_operator::{create_chunk_metadata, get_row_count_for_organization_id_query};
+use super::chunking_operator::{chunk_parsed_file, FileChunk};
 use super::clickhouse_operator::{ClickHouseEvent, EventQueue};
+use super::file_parser_operator::ParsedFile;
 use super::group_operator::{create_group_from_file_query, create_groups_query};
-use super::parse_operator::{build_chunking_regex, coarse_doc_chunker, convert_html_to_text};
 use crate::data::models::ChunkGroup;
+use crate::data::models::ChunkVersionActor;
 use crate::data::models::FileDTO;
 use crate::data::models::{Dataset, DatasetAndOrgWithSubAndPlan, DatasetConfiguration, EventType};
 use crate::handlers::chunk_handler::ChunkReqPayload;
@@ -19,7 +21,6 @@ use diesel::prelude::*;
 use diesel::sql_types::BigInt;
 use diesel_async::RunQueryDsl;
 use redis::aio::MultiplexedConnection;
-use regex::Regex;
 use s3::{creds::Credentials, Bucket, Region};
 use std::collections::HashMap;
 
@@ -99,38 +100,46 @@ pub async fn create_file_query(
     Ok(created_file)
 }
 
+/// Adds where a chunk came from in the file (heading path, page number and character offsets) to the metadata of the uploaded file. Non-object metadata is left as is.
+pub fn get_file_chunk_metadata(
+    file_metadata: Option<serde_json::Value>,
+    file_chunk: &FileChunk,
+) -> Option<serde_json::Value> {
+    let mut metadata = match file_metadata {
+        Some(serde_json::Value::Object(metadata)) => metadata,
+        None => serde_json::Map::new(),
+        Some(metadata) => return Some(metadata),
+    };
+
+    if let Some(page_number) = file_chunk.page_number {
+        metadata.insert("page_number".to_string(), page_number.into());
+    }
+
+    if !file_chunk.heading_path.is_empty() {
+        metadata.insert(
+            "heading_path".to_string(),
+            file_chunk.heading_path.clone().into(),
+        );
+    }
+
+    metadata.insert("char_start".to_string(), file_chunk.char_start.into());
+    metadata.insert("char_end".to_string(), file_chunk.char_end.into());
+
+    Some(serde_json::Value::Object(metadata))
+}
+
 #[allow(clippy::too_many_arguments)]
-#[tracing::instrument(skip(pool, redis_conn, event_queue))]
+#[tracing::instrument(skip(pool, redis_conn, event_queue, parsed_file))]
 pub async fn create_file_chunks(
     created_file_id: uuid::Uuid,
     upload_file_data: UploadFileReqPayload,
-    html_content: String,
+    parsed_file: ParsedFile,
     dataset_org_plan_sub: DatasetAndOrgWithSubAndPlan,
     pool: web::Data<Pool>,
     event_queue: web::Data<EventQueue>,
     mut redis_conn: MultiplexedConnection,
 ) -> Result<(), ServiceError> {
-    let file_text = convert_html_to_text(&html_content);
-
-    let split_regex: Option<Regex> = upload_file_data
-        .split_delimiters
-        .map(|delimiters| {
-            build_chunking_regex(delimiters).map_err(|e| {
-                log::error!("Could not parse chunking delimiters {:?}", e);
-                ServiceError::BadRequest("Could not parse chunking delimiters".to_string())
-            })
-        })
-        .transpose()?;
-
-    let rebalance_chunks = upload_file_data.rebalance_chunks.unwrap_or(true);
-    let target_splits_per_chunk = upload_file_data.target_splits_per_chunk.unwrap_or(20);
-
-    let chunk_htmls = coarse_doc_chunker(
-        file_text,
-        split_regex,
-        rebalance_chunks,
-        target_splits_per_chunk,
-    );
+    let file_chunks = chunk_parsed_file(&parsed_file, &upload_file_data)?;
 
     let mut chunks: Vec<ChunkReqPayload> = [].to_vec();
 
@@ -174,13 +183,15 @@ pub async fn create_file_chunks(
             e
         })?;
 
-    for (i, chunk_html) in chunk_htmls.iter().enumerate() {
+    for (i, file_chunk) in file_chunks.into_iter().enumerate() {
+        let chunk_metadata =
+            get_file_chunk_metadata(upload_file_data.metadata.clone(), &file_chunk);
         let create_chunk_data = ChunkReqPayload {
-            chunk_html: Some(chunk_html.clone()),
+            chunk_html: Some(file_chunk.chunk_html),
             semantic_content: None,
             link: upload_file_data.link.clone(),
             tag_set: upload_file_data.tag_set.clone(),
-            metadata: upload_file_data.metadata.clone(),
+            metadata: chunk_metadata,
             group_ids: Some(vec![group_id]),
             group_tracking_ids: None,
             location: None,
@@ -197,6 +208,7 @@ pub async fn create_file_chunks(
             num_value: None,
             fulltext_boost: None,
             semantic_boost: None,
+            vector_fields: None,
         };
         chunks.push(create_chunk_data);
     }
@@ -362,6 +374,7 @@ pub async fn delete_file_query(
     dataset: Dataset,
     pool: web::Data<Pool>,
     dataset_config: DatasetConfiguration,
+    actor: ChunkVersionActor,
 ) -> Result<(), actix_web::Error> {
     use crate::data::schema::files::dsl as files_columns;
 
@@ -403,6 +416,7 @@ pub async fn delete_file_query(
             Some(true),
             pool.clone(),
             dataset_config,
+            Some(actor),
         )
         .await?;
     }
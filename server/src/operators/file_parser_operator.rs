@@ -0,0 +1,999 @@
+use crate::errors::ServiceError;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use quick_xml::events::Event;
+use scraper::{ElementRef, Html, Node};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// A contiguous piece of a parsed file which shares the same page and heading path.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ParsedFileSection {
+    /// Headings which enclose the section from outermost to innermost.
+    pub heading_path: Vec<String>,
+    /// 1-indexed page the section starts on, if the source format has pages.
+    pub page_number: Option<u32>,
+    pub text: String,
+}
+
+/// Decoded source of a file for chunking strategies which work on the document structure rather than the parsed sections.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum ParsedFileSource {
+    Html(String),
+    Markdown(String),
+    Table { text: String, delimiter: char },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ParsedFile {
+    pub sections: Vec<ParsedFileSection>,
+    pub source: Option<ParsedFileSource>,
+}
+
+impl ParsedFile {
+    pub fn is_empty(&self) -> bool {
+        self.sections
+            .iter()
+            .all(|section| section.text.trim().is_empty())
+    }
+
+    pub fn with_source(mut self, source: ParsedFileSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+pub trait FileParser: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether the parser can handle a file with the given mime type and lowercased extension.
+    fn supports(&self, mime_type: Option<&str>, extension: Option<&str>) -> bool;
+
+    fn parse<'a>(
+        &'a self,
+        file_name: &'a str,
+        mime_type: Option<&'a str>,
+        file_data: &'a [u8],
+    ) -> BoxFuture<'a, Result<ParsedFile, ServiceError>>;
+}
+
+/// Accumulates sections while walking a document, tracking the heading stack and current page.
+#[derive(Default)]
+struct SectionBuilder {
+    sections: Vec<ParsedFileSection>,
+    headings: Vec<(usize, String)>,
+    page_number: Option<u32>,
+    text: String,
+}
+
+impl SectionBuilder {
+    fn flush(&mut self) {
+        if self.text.trim().is_empty() {
+            self.text.clear();
+            return;
+        }
+
+        self.sections.push(ParsedFileSection {
+            heading_path: self
+                .headings
+                .iter()
+                .map(|(_, heading)| heading.clone())
+                .collect(),
+            page_number: self.page_number,
+            text: std::mem::take(&mut self.text).trim().to_string(),
+        });
+    }
+
+    fn heading(&mut self, level: usize, heading: &str) {
+        self.flush();
+        while self
+            .headings
+            .last()
+            .is_some_and(|(last_level, _)| *last_level >= level)
+        {
+            self.headings.pop();
+        }
+
+        let heading = heading.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !heading.is_empty() {
+            self.headings.push((level, heading.clone()));
+            self.text.push_str(&heading);
+            self.text.push('\n');
+        }
+    }
+
+    fn page(&mut self, page_number: u32) {
+        self.flush();
+        self.page_number = Some(page_number);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+
+    fn finish(mut self) -> ParsedFile {
+        self.flush();
+        ParsedFile {
+            sections: self.sections,
+            source: None,
+        }
+    }
+}
+
+fn heading_level(tag_name: &str) -> Option<usize> {
+    match tag_name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+enum HtmlWalkStep<'a> {
+    Enter(ElementRef<'a>),
+    Text(&'a str),
+    Exit(&'a str),
+}
+
+/// Walks the element with an explicit stack rather than recursion such that deeply nested documents cannot overflow the stack.
+fn walk_html_element(element: ElementRef, builder: &mut SectionBuilder, page_count: &mut u32) {
+    let mut steps = vec![HtmlWalkStep::Enter(element)];
+
+    while let Some(step) = steps.pop() {
+        let element = match step {
+            HtmlWalkStep::Enter(element) => element,
+            HtmlWalkStep::Text(text) => {
+                builder.push_text(text);
+                continue;
+            }
+            HtmlWalkStep::Exit(tag_name) => {
+                if matches!(
+                    tag_name,
+                    "p" | "div"
+                        | "li"
+                        | "br"
+                        | "tr"
+                        | "table"
+                        | "pre"
+                        | "blockquote"
+                        | "ul"
+                        | "ol"
+                        | "dt"
+                        | "dd"
+                        | "section"
+                        | "article"
+                ) {
+                    builder.push_text("\n");
+                } else if matches!(tag_name, "td" | "th") {
+                    builder.push_text("\t");
+                }
+                continue;
+            }
+        };
+
+        let tag_name = element.value().name();
+
+        if matches!(
+            tag_name,
+            "head" | "script" | "style" | "noscript" | "template"
+        ) {
+            continue;
+        }
+
+        if let Some(level) = heading_level(tag_name) {
+            builder.heading(level, &element.text().collect::<String>());
+            continue;
+        }
+
+        // Tika renders each page of paginated formats such as PDF as a div with the page class
+        let is_page = tag_name == "div" && element.value().classes().any(|class| class == "page");
+        if is_page {
+            *page_count += 1;
+            builder.page(*page_count);
+        }
+
+        steps.push(HtmlWalkStep::Exit(tag_name));
+        for child in element.children().rev() {
+            match child.value() {
+                Node::Text(text) => steps.push(HtmlWalkStep::Text(text)),
+                Node::Element(_) => {
+                    if let Some(child_element) = ElementRef::wrap(child) {
+                        steps.push(HtmlWalkStep::Enter(child_element));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Splits an HTML document into sections on heading tags and Tika page divs.
+pub fn parse_html_sections(html: &str) -> ParsedFile {
+    let document = Html::parse_document(html);
+    let mut builder = SectionBuilder::default();
+    let mut page_count = 0;
+
+    walk_html_element(document.root_element(), &mut builder, &mut page_count);
+
+    builder.finish()
+}
+
+/// Splits a Markdown document into sections on ATX headings, ignoring headings inside fenced code blocks.
+pub fn parse_markdown_sections(markdown: &str) -> ParsedFile {
+    let mut builder = SectionBuilder::default();
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let trimmed_line = line.trim_start();
+        if trimmed_line.starts_with("```") || trimmed_line.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            builder.push_line(line);
+            continue;
+        }
+
+        let level = trimmed_line.chars().take_while(|c| *c == '#').count();
+        let is_heading =
+            !in_code_block && (1..=6).contains(&level) && trimmed_line[level..].starts_with(' ');
+
+        if is_heading {
+            builder.heading(level, trimmed_line[level..].trim().trim_end_matches('#'));
+        } else {
+            builder.push_line(line);
+        }
+    }
+
+    builder.finish()
+}
+
+/// Plain text has no headings, but form feeds are treated as page breaks.
+pub fn parse_plain_text_sections(text: &str) -> ParsedFile {
+    let pages = text.split('\x0c').collect::<Vec<&str>>();
+    let mut builder = SectionBuilder::default();
+
+    for (i, page) in pages.iter().enumerate() {
+        if pages.len() > 1 {
+            builder.page(i as u32 + 1);
+        }
+        builder.push_text(page);
+    }
+
+    builder.finish()
+}
+
+fn split_delimited_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+        .into_iter()
+        .map(|field| field.trim().to_string())
+        .collect()
+}
+
+/// Parses CSV or TSV into rows along with the byte range each row spans in the text. Quoted fields may contain the delimiter and newlines.
+pub fn parse_delimited_row_spans(
+    text: &str,
+    delimiter: char,
+) -> Vec<(std::ops::Range<usize>, Vec<String>)> {
+    let mut rows = vec![];
+    let mut row_start = 0;
+    let mut quote_count = 0;
+    let mut line_start = 0;
+
+    for line in text.split_inclusive('\n') {
+        quote_count += line.matches('"').count();
+        line_start += line.len();
+
+        if quote_count % 2 == 1 {
+            continue;
+        }
+
+        let row = text[row_start..line_start].trim_end_matches(['\r', '\n']);
+        if !row.trim().is_empty() {
+            rows.push((
+                row_start..row_start + row.len(),
+                split_delimited_line(row, delimiter),
+            ));
+        }
+        row_start = line_start;
+        quote_count = 0;
+    }
+
+    let row = text[row_start..].trim_end_matches(['\r', '\n']);
+    if !row.trim().is_empty() {
+        rows.push((
+            row_start..row_start + row.len(),
+            split_delimited_line(row, delimiter),
+        ));
+    }
+
+    rows
+}
+
+/// Parses CSV or TSV into header and data rows.
+pub fn parse_delimited_rows(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    parse_delimited_row_spans(text, delimiter)
+        .into_iter()
+        .map(|(_, row)| row)
+        .collect()
+}
+
+/// Joins the non-empty values of a row with their column headers.
+pub fn format_delimited_row(headers: &[String], row: &[String]) -> String {
+    let line = row
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(i, value)| match headers.get(i) {
+            Some(header) if !header.is_empty() => format!("{}: {}", header, value),
+            _ => value.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("{}.", line.trim_end_matches('.'))
+}
+
+/// Each data row becomes a line of `header: value` pairs so the column names stay next to their values.
+pub fn parse_delimited_sections(text: &str, delimiter: char) -> ParsedFile {
+    let mut rows = parse_delimited_rows(text, delimiter).into_iter();
+    let headers = rows.next().unwrap_or_default();
+    let mut builder = SectionBuilder::default();
+
+    for row in rows {
+        builder.push_line(&format_delimited_row(&headers, &row));
+    }
+
+    builder.finish()
+}
+
+fn flatten_json_value(value: &serde_json::Value, path: &str, lines: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten_json_value(value, &path, lines);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten_json_value(value, &format!("{}[{}]", path, i), lines);
+            }
+        }
+        serde_json::Value::Null => {}
+        serde_json::Value::String(value) => lines.push(format!("{}: {}", path, value)),
+        value => lines.push(format!("{}: {}", path, value)),
+    }
+}
+
+/// JSON is flattened into `path: value` lines. For JSONL every record becomes its own section.
+pub fn parse_json_sections(text: &str, json_lines: bool) -> Result<ParsedFile, ServiceError> {
+    let records = if json_lines {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<serde_json::Value>)
+            .collect::<Result<Vec<serde_json::Value>, serde_json::Error>>()
+    } else {
+        serde_json::from_str::<serde_json::Value>(text).map(|value| vec![value])
+    }
+    .map_err(|err| {
+        log::error!("Could not parse json file {:?}", err);
+        ServiceError::BadRequest(format!("Could not parse json file: {}", err))
+    })?;
+
+    let mut builder = SectionBuilder::default();
+    for record in records {
+        let mut lines = vec![];
+        flatten_json_value(&record, "", &mut lines);
+        builder.push_text(&lines.join("\n"));
+        builder.flush();
+    }
+
+    Ok(builder.finish())
+}
+
+fn docx_heading_level(style: &str) -> Option<usize> {
+    let style = style.to_lowercase();
+    if style == "title" {
+        return Some(1);
+    }
+
+    style
+        .strip_prefix("heading")
+        .and_then(|level| level.trim().parse::<usize>().ok())
+        .filter(|level| (1..=6).contains(level))
+}
+
+/// Upper bound on the decompressed size of `word/document.xml`, such that a small zip cannot expand into an unbounded amount of memory.
+const MAX_DOCX_DOCUMENT_XML_BYTES: u64 = 100 * 1024 * 1024;
+
+fn read_docx_document_xml(document_xml: impl Read, max_bytes: u64) -> Result<String, ServiceError> {
+    let mut contents = String::new();
+    document_xml
+        .take(max_bytes + 1)
+        .read_to_string(&mut contents)
+        .map_err(|err| {
+            log::error!("Could not read word/document.xml {:?}", err);
+            ServiceError::BadRequest("Could not read docx file".to_string())
+        })?;
+
+    if contents.len() as u64 > max_bytes {
+        return Err(ServiceError::BadRequest(format!(
+            "Docx file is too large, word/document.xml must be at most {} bytes",
+            max_bytes
+        )));
+    }
+
+    Ok(contents)
+}
+
+/// Reads `word/document.xml` out of the DOCX zip. Paragraph styles named Title or HeadingN become headings and explicit or rendered page breaks advance the page number.
+pub fn parse_docx_sections(file_data: &[u8]) -> Result<ParsedFile, ServiceError> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(file_data)).map_err(|err| {
+        log::error!("Could not open docx archive {:?}", err);
+        ServiceError::BadRequest("Could not open docx file".to_string())
+    })?;
+
+    let document_xml = read_docx_document_xml(
+        archive.by_name("word/document.xml").map_err(|err| {
+            log::error!("Docx is missing word/document.xml {:?}", err);
+            ServiceError::BadRequest("Docx file is missing word/document.xml".to_string())
+        })?,
+        MAX_DOCX_DOCUMENT_XML_BYTES,
+    )?;
+
+    let mut reader = quick_xml::Reader::from_str(&document_xml);
+    let mut builder = SectionBuilder::default();
+    builder.page(1);
+    let mut page_number = 1;
+
+    let mut paragraph_text = String::new();
+    let mut paragraph_heading_level: Option<usize> = None;
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => match element.name().as_ref() {
+                b"w:p" => {
+                    paragraph_text.clear();
+                    paragraph_heading_level = None;
+                }
+                b"w:t" => in_text = true,
+                _ => {}
+            },
+            Ok(Event::Empty(element)) => match element.name().as_ref() {
+                b"w:pStyle" => {
+                    paragraph_heading_level = element
+                        .try_get_attribute("w:val")
+                        .ok()
+                        .flatten()
+                        .and_then(|style| style.unescape_value().ok())
+                        .and_then(|style| docx_heading_level(&style));
+                }
+                b"w:tab" => paragraph_text.push('\t'),
+                b"w:br" | b"w:lastRenderedPageBreak" => {
+                    let is_page_break = element.name().as_ref() == b"w:lastRenderedPageBreak"
+                        || element
+                            .try_get_attribute("w:type")
+                            .ok()
+                            .flatten()
+                            .and_then(|break_type| break_type.unescape_value().ok())
+                            .is_some_and(|break_type| break_type == "page");
+
+                    if is_page_break {
+                        builder.push_line(&std::mem::take(&mut paragraph_text));
+                        page_number += 1;
+                        builder.page(page_number);
+                    } else {
+                        paragraph_text.push('\n');
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Text(text)) if in_text => {
+                if let Ok(text) = text.unescape() {
+                    paragraph_text.push_str(&text);
+                }
+            }
+            Ok(Event::End(element)) => match element.name().as_ref() {
+                b"w:t" => in_text = false,
+                b"w:p" => {
+                    let text = std::mem::take(&mut paragraph_text);
+                    match paragraph_heading_level {
+                        Some(level) => builder.heading(level, &text),
+                        None => builder.push_line(&text),
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                log::error!("Could not parse word/document.xml {:?}", err);
+                return Err(ServiceError::BadRequest(
+                    "Could not parse docx file".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+fn decode_utf8(file_data: &[u8]) -> String {
+    String::from_utf8_lossy(file_data)
+        .trim_start_matches('\u{feff}')
+        .to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeFileType {
+    Markdown,
+    PlainText,
+    Html,
+    Csv,
+    Tsv,
+    Json,
+    JsonLines,
+    Docx,
+}
+
+impl NativeFileType {
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        let mime_type = mime_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+
+        match mime_type.as_str() {
+            "text/markdown" | "text/x-markdown" => Some(Self::Markdown),
+            "text/plain" => Some(Self::PlainText),
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "text/csv" | "application/csv" => Some(Self::Csv),
+            "text/tab-separated-values" => Some(Self::Tsv),
+            "application/json" => Some(Self::Json),
+            "application/jsonl" | "application/x-ndjson" | "application/jsonlines" => {
+                Some(Self::JsonLines)
+            }
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => {
+                Some(Self::Docx)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "md" | "markdown" | "mdx" => Some(Self::Markdown),
+            "txt" | "text" | "log" => Some(Self::PlainText),
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "csv" => Some(Self::Csv),
+            "tsv" | "tab" => Some(Self::Tsv),
+            "json" => Some(Self::Json),
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            "docx" => Some(Self::Docx),
+            _ => None,
+        }
+    }
+
+    pub fn parse(&self, file_data: &[u8]) -> Result<ParsedFile, ServiceError> {
+        match self {
+            Self::Markdown => {
+                let markdown = decode_utf8(file_data);
+                Ok(parse_markdown_sections(&markdown)
+                    .with_source(ParsedFileSource::Markdown(markdown)))
+            }
+            Self::PlainText => Ok(parse_plain_text_sections(&decode_utf8(file_data))),
+            Self::Html => {
+                let html = decode_utf8(file_data);
+                Ok(parse_html_sections(&html).with_source(ParsedFileSource::Html(html)))
+            }
+            Self::Csv | Self::Tsv => {
+                let text = decode_utf8(file_data);
+                let delimiter = if *self == Self::Csv { ',' } else { '\t' };
+                Ok(parse_delimited_sections(&text, delimiter)
+                    .with_source(ParsedFileSource::Table { text, delimiter }))
+            }
+            Self::Json => parse_json_sections(&decode_utf8(file_data), false),
+            Self::JsonLines => parse_json_sections(&decode_utf8(file_data), true),
+            Self::Docx => parse_docx_sections(file_data),
+        }
+    }
+}
+
+fn native_file_type(mime_type: Option<&str>, extension: Option<&str>) -> Option<NativeFileType> {
+    // Generic binary mime types say nothing about the format so the extension wins
+    mime_type
+        .filter(|mime_type| !mime_type.starts_with("application/octet-stream"))
+        .and_then(NativeFileType::from_mime_type)
+        .or_else(|| extension.and_then(NativeFileType::from_extension))
+}
+
+/// Parses the formats which do not need an external service.
+pub struct NativeFileParser;
+
+impl FileParser for NativeFileParser {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn supports(&self, mime_type: Option<&str>, extension: Option<&str>) -> bool {
+        native_file_type(mime_type, extension).is_some()
+    }
+
+    fn parse<'a>(
+        &'a self,
+        file_name: &'a str,
+        mime_type: Option<&'a str>,
+        file_data: &'a [u8],
+    ) -> BoxFuture<'a, Result<ParsedFile, ServiceError>> {
+        async move {
+            let extension = get_file_extension(file_name);
+            match native_file_type(mime_type, extension.as_deref()) {
+                Some(file_type) => file_type.parse(file_data),
+                None => Err(ServiceError::BadRequest(
+                    "File type is not supported by the native parser".to_string(),
+                )),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Sends the file to Apache Tika and splits the returned XHTML on pages and headings.
+pub struct TikaFileParser {
+    pub tika_url: String,
+}
+
+impl FileParser for TikaFileParser {
+    fn name(&self) -> &'static str {
+        "tika"
+    }
+
+    fn supports(&self, _mime_type: Option<&str>, _extension: Option<&str>) -> bool {
+        true
+    }
+
+    fn parse<'a>(
+        &'a self,
+        _file_name: &'a str,
+        _mime_type: Option<&'a str>,
+        file_data: &'a [u8],
+    ) -> BoxFuture<'a, Result<ParsedFile, ServiceError>> {
+        async move {
+            let tika_response = reqwest::Client::new()
+                .put(format!("{}/tika", self.tika_url))
+                .header("Accept", "text/html")
+                .body(file_data.to_vec())
+                .send()
+                .await
+                .map_err(|err| {
+                    log::error!("Could not send file to tika {:?}", err);
+                    ServiceError::BadRequest("Could not send file to tika".to_string())
+                })?
+                .error_for_status()
+                .map_err(|err| {
+                    log::error!("Tika returned an error {:?}", err);
+                    ServiceError::BadRequest(format!("Tika returned an error: {}", err))
+                })?;
+
+            let tika_html = tika_response.text().await.map_err(|err| {
+                log::error!("Could not get tika response bytes {:?}", err);
+                ServiceError::BadRequest("Could not get tika response bytes".to_string())
+            })?;
+
+            Ok(parse_html_sections(&tika_html).with_source(ParsedFileSource::Html(tika_html)))
+        }
+        .boxed()
+    }
+}
+
+/// Posts the raw file to any service which responds with HTML, e.g. a self-hosted Unstructured or Docling wrapper.
+pub struct HttpFileParser {
+    pub parser_url: String,
+}
+
+impl FileParser for HttpFileParser {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn supports(&self, _mime_type: Option<&str>, _extension: Option<&str>) -> bool {
+        true
+    }
+
+    fn parse<'a>(
+        &'a self,
+        file_name: &'a str,
+        mime_type: Option<&'a str>,
+        file_data: &'a [u8],
+    ) -> BoxFuture<'a, Result<ParsedFile, ServiceError>> {
+        async move {
+            let response = reqwest::Client::new()
+                .post(&self.parser_url)
+                .header(
+                    "Content-Type",
+                    mime_type.unwrap_or("application/octet-stream"),
+                )
+                .header("X-File-Name", file_name)
+                .header("Accept", "text/html")
+                .body(file_data.to_vec())
+                .send()
+                .await
+                .map_err(|err| {
+                    log::error!("Could not send file to parser {:?}", err);
+                    ServiceError::BadRequest("Could not send file to parser".to_string())
+                })?
+                .error_for_status()
+                .map_err(|err| {
+                    log::error!("Parser returned an error {:?}", err);
+                    ServiceError::BadRequest(format!("Parser returned an error: {}", err))
+                })?;
+
+            let html = response.text().await.map_err(|err| {
+                log::error!("Could not get parser response {:?}", err);
+                ServiceError::BadRequest("Could not get parser response".to_string())
+            })?;
+
+            Ok(parse_html_sections(&html).with_source(ParsedFileSource::Html(html)))
+        }
+        .boxed()
+    }
+}
+
+/// Native parsers are always available. Tika and the generic HTTP parser are only registered when TIKA_URL or FILE_PARSER_URL are set.
+pub fn get_file_parsers() -> Vec<Box<dyn FileParser>> {
+    let mut parsers: Vec<Box<dyn FileParser>> = vec![Box::new(NativeFileParser)];
+
+    if let Ok(tika_url) = std::env::var("TIKA_URL") {
+        if !tika_url.is_empty() {
+            parsers.push(Box::new(TikaFileParser { tika_url }));
+        }
+    }
+
+    if let Ok(parser_url) = std::env::var("FILE_PARSER_URL") {
+        if !parser_url.is_empty() {
+            parsers.push(Box::new(HttpFileParser { parser_url }));
+        }
+    }
+
+    parsers
+}
+
+pub fn get_file_extension(file_name: &str) -> Option<String> {
+    file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .filter(|extension| !extension.is_empty())
+}
+
+/// Tries each parser which supports the file's mime type or extension in order until one returns sections. Native parsing failures, e.g. a corrupt or encrypted DOCX, therefore fall back to Tika or the HTTP parser when they are registered.
+pub async fn parse_file_with_parsers(
+    parsers: &[Box<dyn FileParser>],
+    file_name: &str,
+    mime_type: Option<&str>,
+    file_data: &[u8],
+) -> Result<ParsedFile, ServiceError> {
+    let extension = get_file_extension(file_name);
+    let mut last_error = None;
+
+    for parser in parsers
+        .iter()
+        .filter(|parser| parser.supports(mime_type, extension.as_deref()))
+    {
+        log::info!("Parsing {} with the {} parser", file_name, parser.name());
+
+        match parser.parse(file_name, mime_type, file_data).await {
+            Ok(parsed_file) if !parsed_file.is_empty() => return Ok(parsed_file),
+            Ok(_) => {
+                log::warn!(
+                    "The {} parser found no text in {}",
+                    parser.name(),
+                    file_name
+                );
+                last_error = Some(ServiceError::BadRequest(format!(
+                    "Could not parse file with the {} parser",
+                    parser.name()
+                )));
+            }
+            Err(err) => {
+                log::warn!(
+                    "The {} parser failed on {} {:?}",
+                    parser.name(),
+                    file_name,
+                    err
+                );
+                last_error = Some(err);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        ServiceError::BadRequest(format!(
+            "No parser available for {}. Set TIKA_URL or FILE_PARSER_URL to parse this file type",
+            file_name
+        ))
+    }))
+}
+
+/// Parses the file with the registered parsers, see `parse_file_with_parsers`.
+#[tracing::instrument(skip(file_data))]
+pub async fn parse_file(
+    file_name: &str,
+    mime_type: Option<&str>,
+    file_data: &[u8],
+) -> Result<ParsedFile, ServiceError> {
+    parse_file_with_parsers(&get_file_parsers(), file_name, mime_type, file_data).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parser standing in for Tika which returns the file as a single section
+    struct FallbackParser;
+
+    impl FileParser for FallbackParser {
+        fn name(&self) -> &'static str {
+            "fallback"
+        }
+
+        fn supports(&self, _mime_type: Option<&str>, _extension: Option<&str>) -> bool {
+            true
+        }
+
+        fn parse<'a>(
+            &'a self,
+            _file_name: &'a str,
+            _mime_type: Option<&'a str>,
+            file_data: &'a [u8],
+        ) -> BoxFuture<'a, Result<ParsedFile, ServiceError>> {
+            async move { Ok(parse_plain_text_sections(&decode_utf8(file_data))) }.boxed()
+        }
+    }
+
+    fn parsers(with_fallback: bool) -> Vec<Box<dyn FileParser>> {
+        let mut parsers: Vec<Box<dyn FileParser>> = vec![Box::new(NativeFileParser)];
+        if with_fallback {
+            parsers.push(Box::new(FallbackParser));
+        }
+        parsers
+    }
+
+    #[test]
+    fn test_deeply_nested_html_is_walked() {
+        let depth = 20_000;
+        let html = format!(
+            "<html><body>{}<p>Deep text</p>{}</body></html>",
+            "<span>".repeat(depth),
+            "</span>".repeat(depth)
+        );
+
+        let parsed_file = parse_html_sections(&html);
+        assert_eq!(parsed_file.sections.len(), 1);
+        assert_eq!(parsed_file.sections[0].text, "Deep text");
+    }
+
+    #[test]
+    fn test_html_text_keeps_document_order() {
+        let parsed_file = parse_html_sections(
+            "<html><head><title>Skipped</title></head><body><h1>Title</h1><p>One <b>two</b> three</p><table><tr><td>a</td><td>b</td></tr></table></body></html>",
+        );
+
+        assert_eq!(parsed_file.sections.len(), 1);
+        assert_eq!(parsed_file.sections[0].heading_path, vec!["Title"]);
+        assert_eq!(parsed_file.sections[0].text, "Title\nOne two three\na\tb");
+    }
+
+    #[test]
+    fn test_docx_document_xml_is_capped() {
+        let document_xml = "<w:document></w:document>";
+
+        assert_eq!(
+            read_docx_document_xml(document_xml.as_bytes(), document_xml.len() as u64).unwrap(),
+            document_xml
+        );
+        assert!(matches!(
+            read_docx_document_xml(document_xml.as_bytes(), document_xml.len() as u64 - 1),
+            Err(ServiceError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_native_parser_is_used_first() {
+        let parsed_file = futures::executor::block_on(parse_file_with_parsers(
+            &parsers(true),
+            "notes.md",
+            None,
+            b"# Title\nSome text",
+        ))
+        .unwrap();
+
+        assert_eq!(parsed_file.sections[0].heading_path, vec!["Title"]);
+        assert!(matches!(
+            parsed_file.source,
+            Some(ParsedFileSource::Markdown(_))
+        ));
+    }
+
+    #[test]
+    fn test_falls_back_when_native_parser_fails() {
+        let parsed_file = futures::executor::block_on(parse_file_with_parsers(
+            &parsers(true),
+            "broken.docx",
+            None,
+            b"not a zip archive",
+        ))
+        .unwrap();
+
+        assert_eq!(parsed_file.sections[0].text.trim(), "not a zip archive");
+    }
+
+    #[test]
+    fn test_falls_back_when_native_parser_finds_no_text() {
+        let parsed_file = futures::executor::block_on(parse_file_with_parsers(
+            &parsers(true),
+            "empty.json",
+            None,
+            b"{}",
+        ))
+        .unwrap();
+
+        assert_eq!(parsed_file.sections[0].text.trim(), "{}");
+    }
+
+    #[test]
+    fn test_native_error_is_returned_without_fallback() {
+        let result = futures::executor::block_on(parse_file_with_parsers(
+            &parsers(false),
+            "broken.docx",
+            None,
+            b"not a zip archive",
+        ));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unsupported_file_without_fallback() {
+        let result = futures::executor::block_on(parse_file_with_parsers(
+            &parsers(false),
+            "slides.pptx",
+            None,
+            b"data",
+        ));
+
+        assert!(
+            matches!(result, Err(ServiceError::BadRequest(message)) if message.contains("No parser available"))
+        );
+    }
+}
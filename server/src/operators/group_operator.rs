@@ -5,8 +5,8 @@ use crate::operators::qdrant_operator::{
 };
 use crate::{
     data::models::{
-        ChunkGroup, ChunkGroupAndFileId, ChunkGroupBookmark, ChunkMetadataTable, Dataset,
-        DatasetConfiguration, FileGroup, Pool, RedisPool, UnifiedId,
+        ChunkGroup, ChunkGroupAndFileId, ChunkGroupBookmark, ChunkMetadataTable, ChunkVersionActor,
+        Dataset, DatasetConfiguration, FileGroup, Pool, RedisPool, UnifiedId,
     },
     handlers::group_handler::GroupsBookmarkQueryResult,
     operators::chunk_operator::{delete_chunk_metadata_query, get_chunk_metadatas_from_point_ids},
@@ -273,6 +273,7 @@ pub async fn delete_group_by_id_query(
     delete_chunks: Option<bool>,
     pool: web::Data<Pool>,
     dataset_config: DatasetConfiguration,
+    actor: Option<ChunkVersionActor>,
 ) -> Result<(), ServiceError> {
     use crate::data::schema::chunk_group::dsl as chunk_group_columns;
     use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
@@ -334,6 +335,7 @@ pub async fn delete_group_by_id_query(
             dataset.clone(),
             pool.clone(),
             dataset_config.clone(),
+            actor,
         )
         .await?;
     } else {
@@ -362,6 +364,7 @@ pub async fn delete_group_by_file_id_query(
     delete_chunks: Option<bool>,
     pool: web::Data<Pool>,
     dataset_config: DatasetConfiguration,
+    actor: Option<ChunkVersionActor>,
 ) -> Result<(), ServiceError> {
     use crate::data::schema::chunk_group::dsl as chunk_group_columns;
     use crate::data::schema::chunk_group_bookmarks::dsl as chunk_group_bookmarks_columns;
@@ -432,6 +435,7 @@ pub async fn delete_group_by_file_id_query(
             dataset.clone(),
             pool.clone(),
             dataset_config.clone(),
+            actor,
         )
         .await?;
     } else {
@@ -0,0 +1,757 @@
+use super::chunk_operator::get_point_ids_from_unified_chunk_ids;
+use super::search_operator::{
+    retrieve_chunks_from_point_ids, DeprecatedSearchOverGroupsResponseBody, GroupScoreChunk,
+    SearchChunkQueryResult, SearchResult,
+};
+use crate::{
+    data::models::{
+        DatasetLanguage, MerchandisingAction, MerchandisingQueryMatch, MerchandisingRule,
+        MerchandisingRulePG, Pool, QdrantSortBy, ScoreChunkDTO, UnifiedId,
+    },
+    errors::ServiceError,
+    handlers::{
+        chunk_handler::{SearchChunkQueryResponseBody, SearchChunksReqPayload},
+        group_handler::{
+            SearchOverGroupsReqPayload, SearchWithinGroupReqPayload, SearchWithinGroupResults,
+        },
+    },
+};
+use actix_web::web;
+use dashmap::DashMap;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[tracing::instrument(skip(pool))]
+pub async fn create_merchandising_rule_query(
+    rule: MerchandisingRule,
+    pool: web::Data<Pool>,
+) -> Result<MerchandisingRule, ServiceError> {
+    use crate::data::schema::merchandising_rules::dsl as merchandising_rules_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    invalidate_merchandising_rules_cache(rule.dataset_id);
+
+    diesel::insert_into(merchandising_rules_columns::merchandising_rules)
+        .values(&MerchandisingRulePG::from(rule))
+        .get_result::<MerchandisingRulePG>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to create merchandising rule {:?}", err);
+            ServiceError::BadRequest("Failed to create merchandising rule".to_string())
+        })?
+        .try_into()
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_merchandising_rules_query(
+    dataset_id: uuid::Uuid,
+    page: u64,
+    page_size: u64,
+    pool: web::Data<Pool>,
+) -> Result<(Vec<MerchandisingRule>, i64), ServiceError> {
+    use crate::data::schema::merchandising_rules::dsl as merchandising_rules_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let rules = merchandising_rules_columns::merchandising_rules
+        .filter(merchandising_rules_columns::dataset_id.eq(dataset_id))
+        .order_by(merchandising_rules_columns::created_at.desc())
+        .offset(((page.max(1) - 1) * page_size) as i64)
+        .limit(page_size as i64)
+        .select(MerchandisingRulePG::as_select())
+        .load::<MerchandisingRulePG>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get merchandising rules {:?}", err);
+            ServiceError::BadRequest("Failed to get merchandising rules".to_string())
+        })?
+        .into_iter()
+        .map(MerchandisingRule::try_from)
+        .collect::<Result<Vec<MerchandisingRule>, ServiceError>>()?;
+
+    let total_count = merchandising_rules_columns::merchandising_rules
+        .filter(merchandising_rules_columns::dataset_id.eq(dataset_id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to count merchandising rules {:?}", err);
+            ServiceError::BadRequest("Failed to count merchandising rules".to_string())
+        })?;
+
+    Ok((rules, total_count))
+}
+
+/// How long the enabled rules of a dataset are cached in-process. Changes made through this server are visible immediately, changes made through other servers after at most this long.
+const MERCHANDISING_RULES_CACHE_TTL: Duration = Duration::from_secs(30);
+
+static MERCHANDISING_RULES_CACHE: Lazy<
+    DashMap<uuid::Uuid, (Instant, Arc<Vec<MerchandisingRule>>)>,
+> = Lazy::new(DashMap::new);
+
+fn invalidate_merchandising_rules_cache(dataset_id: uuid::Uuid) {
+    MERCHANDISING_RULES_CACHE.remove(&dataset_id);
+}
+
+/// Enabled rules of the dataset regardless of their schedule, oldest first. Searches read these from an in-process cache such that they do not cost a postgres query each.
+#[tracing::instrument(skip(pool))]
+async fn get_enabled_merchandising_rules_query(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Arc<Vec<MerchandisingRule>>, ServiceError> {
+    use crate::data::schema::merchandising_rules::dsl as merchandising_rules_columns;
+
+    if let Some(entry) = MERCHANDISING_RULES_CACHE.get(&dataset_id) {
+        let (cached_at, rules) = entry.value();
+        if cached_at.elapsed() < MERCHANDISING_RULES_CACHE_TTL {
+            return Ok(Arc::clone(rules));
+        }
+    }
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let rules = Arc::new(
+        merchandising_rules_columns::merchandising_rules
+            .filter(merchandising_rules_columns::dataset_id.eq(dataset_id))
+            .filter(merchandising_rules_columns::enabled.eq(true))
+            .order_by(merchandising_rules_columns::created_at)
+            .select(MerchandisingRulePG::as_select())
+            .load::<MerchandisingRulePG>(&mut conn)
+            .await
+            .map_err(|err| {
+                log::error!("Failed to get active merchandising rules {:?}", err);
+                ServiceError::BadRequest("Failed to get active merchandising rules".to_string())
+            })?
+            .into_iter()
+            .map(MerchandisingRule::try_from)
+            .collect::<Result<Vec<MerchandisingRule>, ServiceError>>()?,
+    );
+
+    MERCHANDISING_RULES_CACHE.insert(dataset_id, (Instant::now(), Arc::clone(&rules)));
+
+    Ok(rules)
+}
+
+fn rule_is_scheduled(rule: &MerchandisingRule, now: chrono::NaiveDateTime) -> bool {
+    rule.starts_at.map_or(true, |starts_at| starts_at <= now)
+        && rule.ends_at.map_or(true, |ends_at| ends_at > now)
+}
+
+/// Rules of the dataset which are enabled, whose schedule includes the current time and which match the query, oldest first.
+pub async fn get_matching_merchandising_rules(
+    query: &str,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Vec<MerchandisingRule>, ServiceError> {
+    let now = chrono::Utc::now().naive_utc();
+
+    Ok(get_enabled_merchandising_rules_query(dataset_id, pool)
+        .await?
+        .iter()
+        .filter(|rule| rule_is_scheduled(rule, now) && rule_matches_query(rule, query))
+        .cloned()
+        .collect())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn update_merchandising_rule_query(
+    rule: MerchandisingRule,
+    pool: web::Data<Pool>,
+) -> Result<MerchandisingRule, ServiceError> {
+    use crate::data::schema::merchandising_rules::dsl as merchandising_rules_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let rule = MerchandisingRulePG::from(rule);
+    invalidate_merchandising_rules_cache(rule.dataset_id);
+
+    diesel::update(
+        merchandising_rules_columns::merchandising_rules
+            .filter(merchandising_rules_columns::id.eq(rule.id))
+            .filter(merchandising_rules_columns::dataset_id.eq(rule.dataset_id)),
+    )
+    .set((
+        merchandising_rules_columns::name.eq(rule.name),
+        merchandising_rules_columns::query_match.eq(rule.query_match),
+        merchandising_rules_columns::query_pattern.eq(rule.query_pattern),
+        merchandising_rules_columns::action.eq(rule.action),
+        merchandising_rules_columns::starts_at.eq(rule.starts_at),
+        merchandising_rules_columns::ends_at.eq(rule.ends_at),
+        merchandising_rules_columns::enabled.eq(rule.enabled),
+        merchandising_rules_columns::updated_at.eq(chrono::Utc::now().naive_local()),
+    ))
+    .get_result::<MerchandisingRulePG>(&mut conn)
+    .await
+    .map_err(|err| match err {
+        diesel::result::Error::NotFound => {
+            ServiceError::NotFound("Merchandising rule not found".to_string())
+        }
+        err => {
+            log::error!("Failed to update merchandising rule {:?}", err);
+            ServiceError::BadRequest("Failed to update merchandising rule".to_string())
+        }
+    })?
+    .try_into()
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn delete_merchandising_rule_query(
+    rule_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::merchandising_rules::dsl as merchandising_rules_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    invalidate_merchandising_rules_cache(dataset_id);
+
+    let deleted_count = diesel::delete(
+        merchandising_rules_columns::merchandising_rules
+            .filter(merchandising_rules_columns::id.eq(rule_id))
+            .filter(merchandising_rules_columns::dataset_id.eq(dataset_id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to delete merchandising rule {:?}", err);
+        ServiceError::BadRequest("Failed to delete merchandising rule".to_string())
+    })?;
+
+    if deleted_count == 0 {
+        return Err(ServiceError::NotFound(
+            "Merchandising rule not found".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn rule_matches_query(rule: &MerchandisingRule, query: &str) -> bool {
+    let pattern = rule
+        .query_pattern
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let query = query.trim().to_lowercase();
+
+    match rule.query_match {
+        MerchandisingQueryMatch::Any => true,
+        MerchandisingQueryMatch::Exact => query == pattern,
+        MerchandisingQueryMatch::Contains => query.contains(&pattern),
+        MerchandisingQueryMatch::Regex => regex::RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map(|pattern_regex| pattern_regex.is_match(&query))
+            .unwrap_or(false),
+    }
+}
+
+fn chunk_tracking_id(chunk: &ScoreChunkDTO) -> Option<String> {
+    chunk
+        .metadata
+        .first()
+        .and_then(|metadata| metadata.metadata().tracking_id)
+}
+
+fn flag_chunk(chunk: &mut ScoreChunkDTO, rule_id: uuid::Uuid) {
+    let rule_ids = chunk.merchandising_rule_ids.get_or_insert_with(Vec::new);
+    if !rule_ids.contains(&rule_id) {
+        rule_ids.push(rule_id);
+    }
+}
+
+/// Fetches a chunk pinned by a rule which the search did not return. The chunk is given the score of the top result such that it does not look out of place.
+async fn get_pinned_chunk(
+    tracking_id: &str,
+    score: f32,
+    data: &SearchChunksReqPayload,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+    language: DatasetLanguage,
+) -> Result<Option<ScoreChunkDTO>, actix_web::Error> {
+    let point_ids = get_point_ids_from_unified_chunk_ids(
+        vec![UnifiedId::TrackingId(tracking_id.to_string())],
+        dataset_id,
+        pool.clone(),
+    )
+    .await?;
+
+    let point_id = match point_ids.first() {
+        Some(point_id) => *point_id,
+        None => return Ok(None),
+    };
+
+    let pinned_chunks = retrieve_chunks_from_point_ids(
+        SearchChunkQueryResult {
+            search_results: vec![SearchResult { score, point_id }],
+            total_chunk_pages: 0,
+            batch_lengths: vec![],
+        },
+        None,
+        data,
+        pool,
+        language,
+    )
+    .await?;
+
+    Ok(pinned_chunks.score_chunks.into_iter().next())
+}
+
+/// Applies hide and boost rules, re-sorts by score unless the results are sorted by a field, then applies bury rules. The ids of the rules which changed the results are added to `applied_rule_ids`.
+fn apply_ranking_rules(
+    mut chunks: Vec<ScoreChunkDTO>,
+    rules: &[MerchandisingRule],
+    sorted_by_field: bool,
+    applied_rule_ids: &mut Vec<uuid::Uuid>,
+) -> Vec<ScoreChunkDTO> {
+    for rule in rules.iter() {
+        match &rule.action {
+            MerchandisingAction::Hide { tracking_ids } => {
+                let chunk_count = chunks.len();
+                chunks.retain(|chunk| {
+                    !chunk_tracking_id(chunk).is_some_and(|id| tracking_ids.contains(&id))
+                });
+                if chunks.len() != chunk_count {
+                    applied_rule_ids.push(rule.id);
+                }
+            }
+            MerchandisingAction::Boost { tag, factor } => {
+                for chunk in chunks.iter_mut() {
+                    let has_tag = chunk.metadata.first().is_some_and(|metadata| {
+                        metadata
+                            .metadata()
+                            .tag_set
+                            .is_some_and(|tag_set| tag_set.contains(&Some(tag.clone())))
+                    });
+                    if has_tag {
+                        chunk.score *= factor;
+                        flag_chunk(chunk, rule.id);
+                        if !applied_rule_ids.contains(&rule.id) {
+                            applied_rule_ids.push(rule.id);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !sorted_by_field {
+        chunks.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    }
+
+    for rule in rules.iter() {
+        if let MerchandisingAction::Bury { tracking_ids } = &rule.action {
+            let (mut buried, kept): (Vec<ScoreChunkDTO>, Vec<ScoreChunkDTO>) =
+                chunks.into_iter().partition(|chunk| {
+                    chunk_tracking_id(chunk).is_some_and(|id| tracking_ids.contains(&id))
+                });
+            if !buried.is_empty() {
+                buried
+                    .iter_mut()
+                    .for_each(|chunk| flag_chunk(chunk, rule.id));
+                applied_rule_ids.push(rule.id);
+            }
+            chunks = kept;
+            chunks.extend(buried);
+        }
+    }
+
+    chunks
+}
+
+/// Pin rules ordered by position as (rule id, tracking id, position)
+fn get_pins(rules: &[MerchandisingRule]) -> Vec<(uuid::Uuid, String, usize)> {
+    let mut pins = rules
+        .iter()
+        .filter_map(|rule| match &rule.action {
+            MerchandisingAction::Pin {
+                tracking_id,
+                position,
+            } => Some((rule.id, tracking_id.clone(), *position as usize)),
+            _ => None,
+        })
+        .collect::<Vec<(uuid::Uuid, String, usize)>>();
+    pins.sort_by_key(|(_, _, position)| *position);
+    pins
+}
+
+/// Index of a pin's 1-based position on the page which starts at `page_offset`, None if the position is on another page
+fn get_pin_page_index(position: usize, page_offset: usize, page_size: usize) -> Option<usize> {
+    position
+        .saturating_sub(1)
+        .checked_sub(page_offset)
+        .filter(|page_index| *page_index < page_size)
+}
+
+/// Puts a pinned item into its slot on a full page. The item which held the slot takes the pinned item's previous slot if it was on the page, otherwise it makes room for the pin such that every other item keeps its position. Pages with room to spare only shift the items after the slot.
+fn place_pinned<T>(
+    items: &mut Vec<T>,
+    existing_index: Option<usize>,
+    pinned: Option<T>,
+    page_index: usize,
+    page_size: usize,
+) {
+    match (existing_index, pinned) {
+        (Some(existing_index), _) => {
+            if page_index < items.len() {
+                items.swap(existing_index, page_index);
+            } else {
+                let item = items.remove(existing_index);
+                items.push(item);
+            }
+        }
+        (None, Some(pinned)) => {
+            if items.len() < page_size || page_index >= items.len() {
+                items.insert(page_index.min(items.len()), pinned);
+            } else {
+                items[page_index] = pinned;
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+/// Applies the active merchandising rules of the dataset which match the query to a page of search results. Hide and boost rules are applied first, then bury rules, then pins such that pinned chunks always land on their position. Chunks affected by a rule are flagged with the rule's id.
+#[tracing::instrument(skip(result_chunks, data, pool))]
+pub async fn apply_merchandising_rules(
+    mut result_chunks: SearchChunkQueryResponseBody,
+    data: &SearchChunksReqPayload,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+    language: DatasetLanguage,
+) -> Result<SearchChunkQueryResponseBody, actix_web::Error> {
+    let query = match data.query.clone().to_single_query() {
+        Ok(query) => query,
+        Err(_) => return Ok(result_chunks),
+    };
+
+    let rules = get_matching_merchandising_rules(&query, dataset_id, pool.clone()).await?;
+
+    if rules.is_empty() {
+        return Ok(result_chunks);
+    }
+
+    let page_size = data.page_size.unwrap_or(10) as usize;
+    let page_offset = (data.page.unwrap_or(1).max(1) as usize - 1) * page_size;
+    let sorted_by_field = matches!(
+        data.sort_options
+            .as_ref()
+            .and_then(|sort_options| sort_options.sort_by.as_ref()),
+        Some(QdrantSortBy::Field(_))
+    );
+    let mut applied_rule_ids: Vec<uuid::Uuid> = vec![];
+    let mut chunks = apply_ranking_rules(
+        result_chunks.score_chunks,
+        &rules,
+        sorted_by_field,
+        &mut applied_rule_ids,
+    );
+
+    for (rule_id, tracking_id, position) in get_pins(&rules) {
+        let existing_index = chunks
+            .iter()
+            .position(|chunk| chunk_tracking_id(chunk).as_ref() == Some(&tracking_id));
+
+        let page_index = match get_pin_page_index(position, page_offset, page_size) {
+            Some(page_index) => page_index,
+            None => {
+                // The pinned chunk belongs to another page so it must not also show up here.
+                if let Some(existing_index) = existing_index {
+                    chunks.remove(existing_index);
+                    applied_rule_ids.push(rule_id);
+                }
+                continue;
+            }
+        };
+
+        let pinned_chunk = match existing_index {
+            Some(_) => None,
+            None => {
+                let top_score = chunks.first().map(|chunk| chunk.score).unwrap_or(1.0);
+                match get_pinned_chunk(
+                    &tracking_id,
+                    top_score as f32,
+                    data,
+                    dataset_id,
+                    pool.clone(),
+                    language,
+                )
+                .await?
+                {
+                    Some(pinned_chunk) => Some(pinned_chunk),
+                    None => continue,
+                }
+            }
+        };
+
+        place_pinned(
+            &mut chunks,
+            existing_index,
+            pinned_chunk,
+            page_index,
+            page_size,
+        );
+        if let Some(chunk) = chunks.get_mut(page_index.min(chunks.len().saturating_sub(1))) {
+            flag_chunk(chunk, rule_id);
+        }
+        applied_rule_ids.push(rule_id);
+    }
+
+    chunks.truncate(page_size);
+
+    result_chunks.score_chunks = chunks;
+    result_chunks.merchandising_rule_ids = if applied_rule_ids.is_empty() {
+        None
+    } else {
+        Some(applied_rule_ids)
+    };
+
+    Ok(result_chunks)
+}
+
+/// Applies the matching merchandising rules to a page of search within group results. Pins only move chunks which the search returned since chunks outside of the group must not show up.
+#[tracing::instrument(skip(result_chunks, data, pool))]
+pub async fn apply_merchandising_rules_within_group(
+    mut result_chunks: SearchWithinGroupResults,
+    data: &SearchWithinGroupReqPayload,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<SearchWithinGroupResults, actix_web::Error> {
+    let query = match data.query.clone().to_single_query() {
+        Ok(query) => query,
+        Err(_) => return Ok(result_chunks),
+    };
+
+    let rules = get_matching_merchandising_rules(&query, dataset_id, pool).await?;
+
+    if rules.is_empty() {
+        return Ok(result_chunks);
+    }
+
+    let page_size = data.page_size.unwrap_or(10) as usize;
+    let page_offset = (data.page.unwrap_or(1).max(1) as usize - 1) * page_size;
+    let sorted_by_field = matches!(
+        data.sort_options
+            .as_ref()
+            .and_then(|sort_options| sort_options.sort_by.as_ref()),
+        Some(QdrantSortBy::Field(_))
+    );
+    let mut applied_rule_ids: Vec<uuid::Uuid> = vec![];
+    let mut chunks = apply_ranking_rules(
+        result_chunks.bookmarks,
+        &rules,
+        sorted_by_field,
+        &mut applied_rule_ids,
+    );
+
+    for (rule_id, tracking_id, position) in get_pins(&rules) {
+        let Some(existing_index) = chunks
+            .iter()
+            .position(|chunk| chunk_tracking_id(chunk).as_ref() == Some(&tracking_id))
+        else {
+            continue;
+        };
+
+        match get_pin_page_index(position, page_offset, page_size) {
+            Some(page_index) => {
+                place_pinned(
+                    &mut chunks,
+                    Some(existing_index),
+                    None,
+                    page_index,
+                    page_size,
+                );
+                let pinned_index = page_index.min(chunks.len() - 1);
+                flag_chunk(&mut chunks[pinned_index], rule_id);
+            }
+            None => {
+                chunks.remove(existing_index);
+            }
+        }
+        applied_rule_ids.push(rule_id);
+    }
+
+    result_chunks.bookmarks = chunks;
+    result_chunks.merchandising_rule_ids = if applied_rule_ids.is_empty() {
+        None
+    } else {
+        Some(applied_rule_ids)
+    };
+
+    Ok(result_chunks)
+}
+
+/// Applies the matching merchandising rules to a page of search over groups results. Hide, boost and bury rules act on the chunks within each group, groups are then ranked by their top chunk and groups whose chunks are all buried go last. A pin moves the group holding the pinned chunk into the pin's position.
+#[tracing::instrument(skip(result_groups, data, pool))]
+pub async fn apply_merchandising_rules_over_groups(
+    mut result_groups: DeprecatedSearchOverGroupsResponseBody,
+    data: &SearchOverGroupsReqPayload,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<DeprecatedSearchOverGroupsResponseBody, actix_web::Error> {
+    let query = match data.query.clone().to_single_query() {
+        Ok(query) => query,
+        Err(_) => return Ok(result_groups),
+    };
+
+    let rules = get_matching_merchandising_rules(&query, dataset_id, pool).await?;
+
+    if rules.is_empty() {
+        return Ok(result_groups);
+    }
+
+    let page_size = data.page_size.unwrap_or(10) as usize;
+    let page_offset = (data.page.unwrap_or(1).max(1) as usize - 1) * page_size;
+    let mut applied_rule_ids: Vec<uuid::Uuid> = vec![];
+    let buried_rule_ids = rules
+        .iter()
+        .filter(|rule| matches!(rule.action, MerchandisingAction::Bury { .. }))
+        .map(|rule| rule.id)
+        .collect::<Vec<uuid::Uuid>>();
+
+    let mut groups = result_groups
+        .group_chunks
+        .into_iter()
+        .filter_map(|mut group| {
+            group.metadata =
+                apply_ranking_rules(group.metadata, &rules, false, &mut applied_rule_ids);
+            if group.metadata.is_empty() {
+                None
+            } else {
+                Some(group)
+            }
+        })
+        .collect::<Vec<GroupScoreChunk>>();
+    applied_rule_ids = applied_rule_ids.into_iter().unique().collect();
+
+    let is_buried = |group: &GroupScoreChunk| {
+        group.metadata.iter().all(|chunk| {
+            chunk
+                .merchandising_rule_ids
+                .as_ref()
+                .is_some_and(|rule_ids| rule_ids.iter().any(|id| buried_rule_ids.contains(id)))
+        })
+    };
+    let group_score = |group: &GroupScoreChunk| {
+        group
+            .metadata
+            .first()
+            .map(|chunk| chunk.score)
+            .unwrap_or(0.0)
+    };
+    if !applied_rule_ids.is_empty() {
+        groups.sort_by(|a, b| {
+            is_buried(a).cmp(&is_buried(b)).then(
+                group_score(b)
+                    .partial_cmp(&group_score(a))
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+        });
+    }
+
+    for (rule_id, tracking_id, position) in get_pins(&rules) {
+        let Some(existing_index) = groups.iter().position(|group| {
+            group
+                .metadata
+                .iter()
+                .any(|chunk| chunk_tracking_id(chunk).as_ref() == Some(&tracking_id))
+        }) else {
+            continue;
+        };
+
+        match get_pin_page_index(position, page_offset, page_size) {
+            Some(page_index) => {
+                place_pinned(
+                    &mut groups,
+                    Some(existing_index),
+                    None,
+                    page_index,
+                    page_size,
+                );
+                let pinned_index = page_index.min(groups.len() - 1);
+                if let Some(chunk) = groups[pinned_index]
+                    .metadata
+                    .iter_mut()
+                    .find(|chunk| chunk_tracking_id(chunk).as_ref() == Some(&tracking_id))
+                {
+                    flag_chunk(chunk, rule_id);
+                }
+            }
+            None => {
+                groups.remove(existing_index);
+            }
+        }
+        applied_rule_ids.push(rule_id);
+    }
+
+    result_groups.group_chunks = groups;
+    result_groups.merchandising_rule_ids = if applied_rule_ids.is_empty() {
+        None
+    } else {
+        Some(applied_rule_ids)
+    };
+
+    Ok(result_groups)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pin_on_page_swaps_with_slot_occupant() {
+        let mut items = vec!["a", "b", "c", "d"];
+        place_pinned(&mut items, Some(3), None, 0, 4);
+        assert_eq!(items, vec!["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_fetched_pin_replaces_slot_on_full_page() {
+        let mut items = vec!["a", "b", "c", "d"];
+        place_pinned(&mut items, None, Some("pinned"), 1, 4);
+        assert_eq!(items, vec!["a", "pinned", "c", "d"]);
+    }
+
+    #[test]
+    fn test_fetched_pin_fills_page_with_room() {
+        let mut items = vec!["a", "b"];
+        place_pinned(&mut items, None, Some("pinned"), 0, 4);
+        assert_eq!(items, vec!["pinned", "a", "b"]);
+
+        let mut items = vec!["a", "b"];
+        place_pinned(&mut items, None, Some("pinned"), 3, 4);
+        assert_eq!(items, vec!["a", "b", "pinned"]);
+    }
+
+    #[test]
+    fn test_pin_page_index() {
+        assert_eq!(get_pin_page_index(1, 0, 10), Some(0));
+        assert_eq!(get_pin_page_index(12, 10, 10), Some(1));
+        assert_eq!(get_pin_page_index(3, 10, 10), None);
+        assert_eq!(get_pin_page_index(21, 10, 10), None);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::data::models::{
-    self, escape_quotes, ChunkMetadataStringTagSet, ChunkMetadataTypes, Dataset,
-    DatasetConfiguration, LLMOptions, QueryTypes, RagQueryEventClickhouse, RedisPool, SearchMethod,
+    self, escape_quotes, ChunkMetadata, ChunkMetadataStringTagSet, ChunkMetadataTypes, Dataset,
+    DatasetConfiguration, LLMOptions, MessageCitation, QueryTypes, RagAnswerCache,
+    RagQueryEventClickhouse, RagStreamFormat, RedisPool, SearchMethod,
 };
 use crate::diesel::prelude::*;
 use crate::get_env;
@@ -20,7 +21,10 @@ use crossbeam_channel::unbounded;
 use diesel_async::RunQueryDsl;
 use futures::StreamExt;
 use futures_util::stream;
-use openai_dive::v1::resources::chat::{DeltaChatMessage, ImageUrl, ImageUrlType};
+use openai_dive::v1::resources::chat::{
+    ChatCompletionChunkResponse, ChatCompletionStreamOptions, ChatCompletionToolChoice,
+    DeltaChatMessage, ImageUrl, ImageUrlType,
+};
 use openai_dive::v1::{
     api::Client,
     resources::{
@@ -30,20 +34,219 @@ use openai_dive::v1::{
 };
 use serde::{Deserialize, Serialize};
 use simple_server_timing_header::Timer;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 use ureq::json;
+use utoipa::ToSchema;
 
+use super::agent_operator::{get_agent_tools, is_agent_tool_message, run_agent_query};
+use super::chunk_operator::get_metadata_from_ids_query;
 use super::clickhouse_operator::{get_latency_from_header, EventQueue};
+use super::model_operator::get_dense_vector;
+use super::rag_cache_operator::{
+    get_cached_answer_query, get_filters_hash, insert_cached_answer_query,
+};
 use super::search_operator::{
     full_text_search_over_groups, hybrid_search_over_groups, search_chunks_query,
     search_hybrid_chunks, semantic_search_over_groups,
 };
 
+/// Role of the stored messages which summarize a topic's older messages once its chat history exceeds the dataset's CHAT_HISTORY_TOKEN_BUDGET
+pub const SUMMARY_ROLE: &str = "summary";
+
+/// Summaries are kept out of the conversation's ordering and linked to the messages they cover by their summarized_through
+pub const SUMMARY_SORT_ORDER: i32 = -1;
+
+/// How long a completion waits for the summary of the chat history before it is sent without it. The summary is still stored once it finishes and used from the next completion on.
+const CHAT_HISTORY_SUMMARY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+const CHAT_HISTORY_SUMMARY_PROMPT: &str = "Summarize the following conversation between a user and an assistant. Keep every fact, question, decision and open issue which may be needed to continue the conversation. Respond with only the summary.";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatCompletionDTO {
     pub completion_message: Message,
     pub completion_tokens: i32,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct RagRetrievedChunk {
+    /// The doc number the chunk was given to the LLM as. This is what `[n]` citations in the completion refer to.
+    pub doc: usize,
+    /// Score of the chunk from the retrieval search. None if the chunk was chosen by the caller or found by agent mode's tools.
+    pub score: Option<f64>,
+    pub chunk: ChunkMetadataStringTagSet,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct RagUsage {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+/// Events of a RAG response streamed with the `sse` stream_format. The SSE event name is the `event` field and the SSE data is the JSON of the `data` field.
+pub enum RagStreamEvent {
+    /// Sent first with the chunks which were placed into the context window.
+    Retrieval {
+        search_id: Option<uuid::Uuid>,
+        chunks: Vec<RagRetrievedChunk>,
+    },
+    /// A piece of the completion.
+    Delta { content: String },
+    /// A `[n]` doc citation which has been completed in the text streamed so far.
+    Citation(MessageCitation),
+    /// Sent if the LLM provider fails while streaming the completion, no more deltas follow it.
+    Error { message: String },
+    /// Token usage of the completion, sent once the completion has finished.
+    Usage(RagUsage),
+    /// Sent last. For messages, query_id is also the id of the stored assistant message.
+    Done { query_id: uuid::Uuid },
+}
+
+impl RagStreamEvent {
+    pub fn to_sse_bytes(&self) -> Bytes {
+        let value = serde_json::to_value(self).unwrap_or_default();
+
+        Bytes::from(format!(
+            "event: {}\ndata: {}\n\n",
+            value["event"].as_str().unwrap_or_default(),
+            value["data"]
+        ))
+    }
+}
+
+/// Resolves `[n]` and `[n, m]` doc citations in a completion to chunk ids as the completion is streamed in.
+pub struct CitationParser {
+    chunk_ids: Vec<uuid::Uuid>,
+    text: String,
+    scanned_bytes: usize,
+    scanned_chars: usize,
+}
+
+impl CitationParser {
+    pub fn new(chunk_ids: Vec<uuid::Uuid>) -> Self {
+        CitationParser {
+            chunk_ids,
+            text: String::new(),
+            scanned_bytes: 0,
+            scanned_chars: 0,
+        }
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.scanned_chars += self.text[self.scanned_bytes..self.scanned_bytes + bytes]
+            .chars()
+            .count();
+        self.scanned_bytes += bytes;
+    }
+
+    /// Appends the text and returns the citations which were completed by it
+    pub fn push(&mut self, text: &str) -> Vec<MessageCitation> {
+        self.text.push_str(text);
+
+        let mut citations = vec![];
+        loop {
+            let rest = &self.text[self.scanned_bytes..];
+            let Some(open) = rest.find('[') else {
+                let rest_len = rest.len();
+                self.advance(rest_len);
+                break;
+            };
+            self.advance(open);
+
+            let rest = &self.text[self.scanned_bytes..];
+            let Some(close) = rest.find(']') else {
+                // Wait for more text if this could still become a citation
+                if !rest[1..]
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || c == ',' || c == ' ')
+                {
+                    self.advance(1);
+                    continue;
+                }
+                break;
+            };
+
+            let docs = rest[1..close]
+                .split(',')
+                .map(|doc| doc.trim().parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>();
+
+            match docs {
+                Ok(docs) => {
+                    let start = self.scanned_chars;
+                    let end = start + rest[..=close].chars().count();
+                    citations.extend(docs.into_iter().filter_map(|doc| {
+                        self.chunk_ids
+                            .get(doc.checked_sub(1)?)
+                            .map(|chunk_id| MessageCitation {
+                                doc,
+                                chunk_id: *chunk_id,
+                                start,
+                                end,
+                            })
+                    }));
+                    self.advance(close + 1);
+                }
+                Err(_) => self.advance(1),
+            }
+        }
+
+        citations
+    }
+}
+
+pub fn resolve_citations(completion: &str, chunk_ids: Vec<uuid::Uuid>) -> Vec<MessageCitation> {
+    CitationParser::new(chunk_ids).push(completion)
+}
+
+/// Usage reported by the LLM provider in a completion or completion chunk, if any
+pub fn get_usage_from_response<T: Serialize>(response: &T) -> Option<RagUsage> {
+    serde_json::to_value(response)
+        .ok()?
+        .get("usage")
+        .filter(|usage| !usage.is_null())
+        .and_then(|usage| serde_json::from_value(usage.clone()).ok())
+}
+
+/// Text of the first choice of a completion chunk. Some("") once the completion has finished.
+pub fn get_delta_text(response: &ChatCompletionChunkResponse) -> Option<String> {
+    response.choices.first().and_then(|choice| {
+        if choice.finish_reason.is_some() {
+            Some("".to_string())
+        } else {
+            match &choice.delta {
+                DeltaChatMessage::User {
+                    content: ChatMessageContent::Text(text),
+                    ..
+                }
+                | DeltaChatMessage::System {
+                    content: ChatMessageContent::Text(text),
+                    ..
+                }
+                | DeltaChatMessage::Assistant {
+                    content: Some(ChatMessageContent::Text(text)),
+                    ..
+                }
+                | DeltaChatMessage::Untagged {
+                    content: Some(ChatMessageContent::Text(text)),
+                    ..
+                } => Some(text.clone()),
+                _ => {
+                    log::error!(
+                        "Delta of first choice did not have text or was either Tool or Function {:?}",
+                        choice
+                    );
+                    None
+                }
+            }
+        }
+    })
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn get_topic_messages(
     messages_topic_id: uuid::Uuid,
@@ -60,6 +263,7 @@ pub async fn get_topic_messages(
         .filter(topic_id.eq(messages_topic_id))
         .filter(dataset_id.eq(given_dataset_id))
         .filter(deleted.eq(false))
+        .filter(role.ne(SUMMARY_ROLE))
         .order(sort_order.asc())
         .load::<Message>(&mut conn)
         .await
@@ -104,12 +308,13 @@ pub async fn create_generic_system_message(
         crate::operators::topic_operator::get_topic_query(messages_topic_id, dataset_id, pool)
             .await?;
 
+    let system_prompt_tokens = count_tokens(&system_prompt) as i32;
     let system_message = Message::from_details(
         system_prompt,
         topic.id,
         0,
         "system".into(),
-        Some(0),
+        Some(system_prompt_tokens),
         Some(0),
         dataset_id,
         uuid::Uuid::new_v4(),
@@ -169,6 +374,7 @@ pub async fn get_message_by_sort_for_topic_query(
         .filter(topic_id.eq(message_topic_id))
         .filter(sort_order.eq(message_sort_order))
         .filter(dataset_id.eq(given_dataset_id))
+        .filter(role.ne(SUMMARY_ROLE))
         .first::<Message>(&mut conn)
         .await
         .map_err(|_db_error| {
@@ -194,6 +400,7 @@ pub async fn get_messages_for_topic_query(
         .filter(topic_id.eq(message_topic_id))
         .filter(deleted.eq(false))
         .filter(dataset_id.eq(given_dataset_id))
+        .filter(role.ne(SUMMARY_ROLE))
         .order_by(sort_order.asc())
         .load::<Message>(&mut conn)
         .await
@@ -204,6 +411,264 @@ pub async fn get_messages_for_topic_query(
         })
 }
 
+/// The most recent summary of the topic's older messages which has not been deleted by an edit
+#[tracing::instrument(skip(pool))]
+pub async fn get_latest_topic_summary_query(
+    message_topic_id: uuid::Uuid,
+    given_dataset_id: uuid::Uuid,
+    pool: &web::Data<Pool>,
+) -> Result<Option<Message>, ServiceError> {
+    use crate::data::schema::messages::dsl::*;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    messages
+        .filter(topic_id.eq(message_topic_id))
+        .filter(dataset_id.eq(given_dataset_id))
+        .filter(deleted.eq(false))
+        .filter(role.eq(SUMMARY_ROLE))
+        .order_by((summarized_through.desc(), created_at.desc()))
+        .first::<Message>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_db_error| ServiceError::BadRequest("Error getting topic summary".to_string()))
+}
+
+/// Number of tokens of the text for the LLM, counted with the o200k_base tokenizer of the OpenAI models
+pub fn count_tokens(text: &str) -> u64 {
+    tiktoken_rs::o200k_base_singleton()
+        .lock()
+        .encode_ordinary(text)
+        .len() as u64
+}
+
+/// Tokens a message takes up in the chat history. The counts stored with the message are used, the completion tokens of assistant messages and summaries and the prompt tokens of user and system messages. Messages stored without a count, or with a count of 0 like the system messages of older topics, are counted with the tokenizer.
+fn get_message_token_count(message: &Message) -> u64 {
+    let stored_tokens = match message.role.as_str() {
+        "assistant" | SUMMARY_ROLE => message.completion_tokens,
+        _ => message.prompt_tokens,
+    };
+
+    match stored_tokens {
+        Some(tokens) if tokens > 0 => tokens as u64,
+        _ => count_tokens(&message.content),
+    }
+}
+
+/// Messages of the conversation which come after the ones the summary covers
+fn get_messages_after_summary(
+    conversation: Vec<Message>,
+    summary: Option<&Message>,
+) -> Vec<Message> {
+    let summarized_through = summary.and_then(|summary| summary.summarized_through);
+
+    conversation
+        .into_iter()
+        .filter(|message| {
+            !summarized_through
+                .is_some_and(|summarized_through| message.sort_order <= summarized_through)
+        })
+        .collect()
+}
+
+/// Index of the first message kept as is once the history is over the budget. The most recent messages within half of the budget are kept, and always the last one, every message before them is summarized.
+fn get_summary_split(conversation: &[Message], token_budget: u64) -> usize {
+    let mut kept_tokens = 0;
+    let mut split = conversation.len();
+    while split > 0 {
+        let message_tokens = get_message_token_count(&conversation[split - 1]);
+        if split < conversation.len() && kept_tokens + message_tokens > token_budget / 2 {
+            break;
+        }
+        kept_tokens += message_tokens;
+        split -= 1;
+    }
+
+    split
+}
+
+/// A summary message covering `summarized_messages`, linked to the sort order of the last of them
+fn new_summary_message(
+    summary_text: String,
+    summary_tokens: Option<u32>,
+    summarized_messages: &[Message],
+    topic_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+) -> Message {
+    let mut summary = Message::from_details(
+        summary_text,
+        topic_id,
+        SUMMARY_SORT_ORDER,
+        SUMMARY_ROLE.to_string(),
+        None,
+        summary_tokens.map(|tokens| tokens as i32),
+        dataset_id,
+        uuid::Uuid::new_v4(),
+    );
+    summary.summarized_through = summarized_messages.last().map(|message| message.sort_order);
+
+    summary
+}
+
+async fn summarize_messages(
+    previous_summary: Option<&Message>,
+    messages_to_summarize: &[Message],
+    client: &Client,
+    model: String,
+) -> Result<(String, Option<u32>), ServiceError> {
+    let transcript = previous_summary
+        .map(|summary| format!("summary of earlier messages: {}", summary.content))
+        .into_iter()
+        .chain(
+            messages_to_summarize
+                .iter()
+                .map(|message| format!("{}: {}", message.role, message.content)),
+        )
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    let parameters = ChatCompletionParameters {
+        model,
+        messages: vec![
+            ChatMessage::System {
+                content: ChatMessageContent::Text(CHAT_HISTORY_SUMMARY_PROMPT.to_string()),
+                name: None,
+            },
+            ChatMessage::User {
+                content: ChatMessageContent::Text(transcript),
+                name: None,
+            },
+        ],
+        stream: Some(false),
+        ..Default::default()
+    };
+
+    let completion = client.chat().create(parameters).await.map_err(|err| {
+        ServiceError::BadRequest(format!("Bad response from LLM server provider: {}", err))
+    })?;
+
+    let summary = match completion.choices.first().map(|choice| &choice.message) {
+        Some(ChatMessage::Assistant {
+            content: Some(ChatMessageContent::Text(text)),
+            ..
+        }) => text.clone(),
+        _ => {
+            return Err(ServiceError::InternalServerError(
+                "Failed to summarize chat history; no text in completion".to_string(),
+            ))
+        }
+    };
+
+    Ok((
+        summary,
+        get_usage_from_response(&completion).and_then(|usage| usage.completion_tokens),
+    ))
+}
+
+/// Summarizes the messages along with the previous summary and stores the new summary
+async fn create_topic_summary_query(
+    previous_summary: Option<Message>,
+    summarized_messages: Vec<Message>,
+    client: Client,
+    model: String,
+    topic_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Message, ServiceError> {
+    let (summary_text, summary_tokens) = summarize_messages(
+        previous_summary.as_ref(),
+        &summarized_messages,
+        &client,
+        model,
+    )
+    .await?;
+
+    let summary = new_summary_message(
+        summary_text,
+        summary_tokens,
+        &summarized_messages,
+        topic_id,
+        dataset_id,
+    );
+    create_messages_query(vec![summary.clone()], &pool).await?;
+
+    Ok(summary)
+}
+
+/// Chat history of the topic to send to the LLM, kept within the token budget. When the budget is exceeded, the older messages are rolled into a new summary message linked to the last message it covers by its summarized_through. Editing a message deletes the summaries which cover it, so they are regenerated on the next completion.
+/// The summary is generated in the background and the completion waits for it for at most CHAT_HISTORY_SUMMARY_TIMEOUT. If it takes longer or fails, the completion is sent the previous summary and the recent messages.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(messages, client, pool))]
+pub async fn get_chat_history_within_budget(
+    messages: Vec<Message>,
+    token_budget: u64,
+    client: &Client,
+    model: String,
+    topic_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    pool: &web::Data<Pool>,
+) -> Result<Vec<ChatMessage>, ServiceError> {
+    let (system_messages, conversation): (Vec<Message>, Vec<Message>) = messages
+        .into_iter()
+        .filter(|message| !is_agent_tool_message(message))
+        .partition(|message| message.role == "system");
+
+    let mut summary = get_latest_topic_summary_query(topic_id, dataset_id, pool).await?;
+
+    let mut conversation = get_messages_after_summary(conversation, summary.as_ref());
+
+    let history_tokens = summary.iter().map(get_message_token_count).sum::<u64>()
+        + conversation
+            .iter()
+            .map(get_message_token_count)
+            .sum::<u64>();
+
+    if history_tokens > token_budget {
+        let split = get_summary_split(&conversation, token_budget);
+
+        if split > 0 {
+            let recent_messages = conversation.split_off(split);
+
+            let mut summary_task = tokio::spawn(create_topic_summary_query(
+                summary.clone(),
+                conversation,
+                client.clone(),
+                model,
+                topic_id,
+                dataset_id,
+                pool.clone(),
+            ));
+
+            match tokio::time::timeout(CHAT_HISTORY_SUMMARY_TIMEOUT, &mut summary_task).await {
+                Ok(Ok(Ok(new_summary))) => summary = Some(new_summary),
+                Ok(Ok(Err(err))) => log::error!("Failed to summarize chat history {:?}", err),
+                Ok(Err(err)) => log::error!("Chat history summary task failed {:?}", err),
+                Err(_) => log::info!(
+                    "Chat history summary of topic {} is still running, sending the completion without it",
+                    topic_id
+                ),
+            }
+
+            conversation = recent_messages;
+        }
+    }
+
+    Ok(system_messages
+        .into_iter()
+        .map(ChatMessage::from)
+        .chain(summary.map(|summary| ChatMessage::System {
+            content: ChatMessageContent::Text(format!(
+                "Summary of the earlier conversation: {}",
+                summary.content
+            )),
+            name: None,
+        }))
+        .chain(conversation.into_iter().map(ChatMessage::from))
+        .collect())
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn delete_message_query(
     given_message_id: uuid::Uuid,
@@ -234,6 +699,19 @@ pub async fn delete_message_query(
     .await
     .map_err(|_| ServiceError::BadRequest("Error deleting message".to_string()))?;
 
+    // Summaries which cover the deleted messages are regenerated on the next completion
+    diesel::update(
+        messages
+            .filter(topic_id.eq(given_topic_id))
+            .filter(dataset_id.eq(given_dataset_id))
+            .filter(role.eq(SUMMARY_ROLE))
+            .filter(summarized_through.ge(target_message.sort_order)),
+    )
+    .set(deleted.eq(true))
+    .execute(&mut conn)
+    .await
+    .map_err(|_| ServiceError::BadRequest("Error deleting topic summaries".to_string()))?;
+
     Ok(())
 }
 
@@ -248,7 +726,7 @@ pub async fn get_rag_chunks_query(
     pool: web::Data<Pool>,
     redis_pool: web::Data<RedisPool>,
     event_queue: web::Data<EventQueue>,
-) -> Result<(uuid::Uuid, Vec<ChunkMetadataStringTagSet>), actix_web::Error> {
+) -> Result<(uuid::Uuid, Vec<(ChunkMetadataStringTagSet, f64)>), actix_web::Error> {
     let mut query =
         if let Some(create_message_query) = create_message_req_payload.search_query.clone() {
             create_message_query
@@ -334,6 +812,7 @@ pub async fn get_rag_chunks_query(
             highlight_options: create_message_req_payload.highlight_options,
             filters: create_message_req_payload.filters,
             group_size: Some(1),
+            recency_bias: create_message_req_payload.recency_bias,
             ..Default::default()
         };
 
@@ -341,6 +820,7 @@ pub async fn get_rag_chunks_query(
             query: query.clone(),
             quote_words: None,
             negated_words: None,
+            vectors: None,
         };
 
         let mut search_timer = Timer::new();
@@ -412,6 +892,13 @@ pub async fn get_rag_chunks_query(
                 .user_id
                 .clone()
                 .unwrap_or_default(),
+            merchandising_rule_ids: result_groups
+                .merchandising_rule_ids
+                .clone()
+                .unwrap_or_default()
+                .iter()
+                .map(|rule_id| rule_id.to_string())
+                .collect(),
         };
 
         event_queue
@@ -432,16 +919,16 @@ pub async fn get_rag_chunks_query(
                         .map(|score_chunk| {
                             match score_chunk.metadata.get(0).expect("No metadata found") {
                                 ChunkMetadataTypes::Metadata(chunk_metadata) => {
-                                    chunk_metadata.clone()
+                                    (chunk_metadata.clone(), score_chunk.score)
                                 }
                                 _ => unreachable!(
                                     "The operator should never return slim chunks for this"
                                 ),
                             }
                         })
-                        .collect::<Vec<ChunkMetadataStringTagSet>>()
+                        .collect::<Vec<(ChunkMetadataStringTagSet, f64)>>()
                 })
-                .collect::<Vec<ChunkMetadataStringTagSet>>(),
+                .collect::<Vec<(ChunkMetadataStringTagSet, f64)>>(),
         ))
     } else {
         let search_chunk_data = SearchChunksReqPayload {
@@ -455,12 +942,14 @@ pub async fn get_rag_chunks_query(
             ),
             highlight_options: create_message_req_payload.highlight_options,
             filters: create_message_req_payload.filters,
+            recency_bias: create_message_req_payload.recency_bias,
             ..Default::default()
         };
         let parsed_query = ParsedQuery {
             query: query.clone(),
             quote_words: None,
             negated_words: None,
+            vectors: None,
         };
         let mut search_timer = Timer::new();
 
@@ -520,6 +1009,13 @@ pub async fn get_rag_chunks_query(
                 .user_id
                 .clone()
                 .unwrap_or_default(),
+            merchandising_rule_ids: result_chunks
+                .merchandising_rule_ids
+                .clone()
+                .unwrap_or_default()
+                .iter()
+                .map(|rule_id| rule_id.to_string())
+                .collect(),
         };
 
         event_queue
@@ -535,11 +1031,13 @@ pub async fn get_rag_chunks_query(
                 .iter()
                 .map(
                     |score_chunk| match score_chunk.metadata.get(0).expect("No metadata found") {
-                        ChunkMetadataTypes::Metadata(chunk_metadata) => chunk_metadata.clone(),
+                        ChunkMetadataTypes::Metadata(chunk_metadata) => {
+                            (chunk_metadata.clone(), score_chunk.score)
+                        }
                         _ => unreachable!("The operator should never return slim chunks for this"),
                     },
                 )
-                .collect::<Vec<ChunkMetadataStringTagSet>>(),
+                .collect::<Vec<(ChunkMetadataStringTagSet, f64)>>(),
         ))
     }
 }
@@ -576,10 +1074,8 @@ pub async fn stream_response(
         },
     };
 
-    let openai_messages: Vec<ChatMessage> = messages
-        .iter()
-        .map(|message| ChatMessage::from(message.clone()))
-        .collect();
+    // Cached answers are only reused for the first question of a topic, later ones depend on the rest of the conversation
+    let is_first_question = messages.iter().all(|message| message.role != "assistant");
 
     let base_url = dataset_config.LLM_BASE_URL.clone();
 
@@ -604,6 +1100,26 @@ pub async fn stream_response(
         organization: None,
     };
 
+    let openai_messages: Vec<ChatMessage> = match dataset_config.CHAT_HISTORY_TOKEN_BUDGET {
+        Some(token_budget) => {
+            get_chat_history_within_budget(
+                messages.clone(),
+                token_budget,
+                &client,
+                dataset_config.LLM_DEFAULT_MODEL.clone(),
+                topic_id,
+                dataset.id,
+                &pool,
+            )
+            .await?
+        }
+        None => messages
+            .iter()
+            .filter(|message| !is_agent_tool_message(message))
+            .map(|message| ChatMessage::from(message.clone()))
+            .collect(),
+    };
+
     let next_message_order = move || {
         let messages_len = messages.len();
         if messages_len == 0 {
@@ -615,18 +1131,115 @@ pub async fn stream_response(
     let rag_prompt = dataset_config.RAG_PROMPT.clone();
     let chosen_model = dataset_config.LLM_DEFAULT_MODEL.clone();
 
-    let (search_id, chunk_metadatas) = get_rag_chunks_query(
-        create_message_req_payload.clone(),
-        dataset_config.clone(),
-        dataset.clone(),
-        user_message_query.clone(),
-        chosen_model.clone(),
-        &client,
-        pool.clone(),
-        redis_pool.clone(),
-        event_queue.clone(),
-    )
-    .await?;
+    let answer_cache_key = match dataset_config.ANSWER_CACHE_DISTANCE_THRESHOLD {
+        Some(distance_threshold)
+            if is_first_question
+                && dataset_config.SEMANTIC_ENABLED
+                && create_message_req_payload.agent_options.is_none() =>
+        {
+            // The question itself is embedded, a search_query only changes the retrieval and is part of the filters hash
+            let cache_query = user_message_query.clone();
+            let query_embedding =
+                get_dense_vector(cache_query.clone(), None, "query", dataset_config.clone())
+                    .await?;
+            let filters_hash = get_filters_hash(&create_message_req_payload, &dataset_config);
+
+            if let Some(cached_answer) = get_cached_answer_query(
+                dataset.id,
+                filters_hash.clone(),
+                query_embedding.clone(),
+                distance_threshold,
+                pool.clone(),
+            )
+            .await?
+            {
+                if let Some(response) = respond_with_cached_answer(
+                    cached_answer,
+                    topic_id,
+                    next_message_order(),
+                    dataset.clone(),
+                    user_message_query.clone(),
+                    create_message_req_payload.clone(),
+                    pool.clone(),
+                    event_queue.clone(),
+                )
+                .await?
+                {
+                    return Ok(response);
+                }
+            }
+
+            Some(AnswerCacheKey {
+                query: cache_query,
+                query_embedding,
+                filters_hash,
+            })
+        }
+        _ => None,
+    };
+
+    let (search_id, scored_chunks, agent_messages, agent_message_count) =
+        match create_message_req_payload.agent_options.clone() {
+            Some(agent_options) => {
+                let agent_run = run_agent_query(
+                    agent_options,
+                    create_message_req_payload.filters.clone(),
+                    openai_messages.clone(),
+                    &client,
+                    chosen_model.clone(),
+                    topic_id,
+                    next_message_order(),
+                    dataset.clone(),
+                    dataset_config.clone(),
+                    pool.clone(),
+                    redis_pool.clone(),
+                )
+                .await?;
+
+                (
+                    uuid::Uuid::nil(),
+                    agent_run
+                        .chunks
+                        .into_iter()
+                        .map(|chunk| (chunk, None))
+                        .collect::<Vec<(ChunkMetadataStringTagSet, Option<f64>)>>(),
+                    Some(agent_run.messages),
+                    agent_run.stored_message_count,
+                )
+            }
+            None => {
+                let (search_id, scored_chunks) = get_rag_chunks_query(
+                    create_message_req_payload.clone(),
+                    dataset_config.clone(),
+                    dataset.clone(),
+                    user_message_query.clone(),
+                    chosen_model.clone(),
+                    &client,
+                    pool.clone(),
+                    redis_pool.clone(),
+                    event_queue.clone(),
+                )
+                .await?;
+
+                (
+                    search_id,
+                    scored_chunks
+                        .into_iter()
+                        .map(|(chunk, score)| (chunk, Some(score)))
+                        .collect(),
+                    None,
+                    0,
+                )
+            }
+        };
+    let assistant_sort_order = next_message_order() + agent_message_count;
+
+    let (chunk_metadatas, scores): (Vec<ChunkMetadataStringTagSet>, Vec<Option<f64>>) =
+        scored_chunks.into_iter().unzip();
+    let chunk_ids = chunk_metadatas
+        .iter()
+        .map(|chunk| chunk.id)
+        .collect::<Vec<uuid::Uuid>>();
 
     let chunk_data = chunk_metadatas
         .clone()
@@ -691,35 +1304,38 @@ pub async fn stream_response(
         })
         .collect();
 
-    // replace the last message with the last message with evidence
-    let mut open_ai_messages: Vec<ChatMessage> = openai_messages
-        .clone()
-        .into_iter()
-        .enumerate()
-        .map(|(index, message)| {
-            if index == openai_messages.len() - 1 {
-                match message {
-                    ChatMessage::Assistant { name, .. } => ChatMessage::Assistant {
-                        content: Some(last_message.clone()),
-                        name,
-                        tool_calls: None,
-                        refusal: None,
-                    },
-                    ChatMessage::System { name, .. } => ChatMessage::System {
-                        content: last_message.clone(),
-                        name,
-                    },
-                    ChatMessage::User { name, .. } => ChatMessage::User {
-                        content: last_message.clone(),
-                        name,
-                    },
-                    _ => message,
+    // replace the last message with the last message with evidence, agent mode already has the evidence in its tool results
+    let mut open_ai_messages: Vec<ChatMessage> = match agent_messages {
+        Some(agent_messages) => openai_messages.into_iter().chain(agent_messages).collect(),
+        None => openai_messages
+            .clone()
+            .into_iter()
+            .enumerate()
+            .map(|(index, message)| {
+                if index == openai_messages.len() - 1 {
+                    match message {
+                        ChatMessage::Assistant { name, .. } => ChatMessage::Assistant {
+                            content: Some(last_message.clone()),
+                            name,
+                            tool_calls: None,
+                            refusal: None,
+                        },
+                        ChatMessage::System { name, .. } => ChatMessage::System {
+                            content: last_message.clone(),
+                            name,
+                        },
+                        ChatMessage::User { name, .. } => ChatMessage::User {
+                            content: last_message.clone(),
+                            name,
+                        },
+                        _ => message,
+                    }
+                } else {
+                    message
                 }
-            } else {
-                message
-            }
-        })
-        .collect();
+            })
+            .collect(),
+    };
 
     if !images.is_empty() {
         if let Some(LLMOptions {
@@ -755,6 +1371,12 @@ pub async fn stream_response(
         ..Default::default()
     };
 
+    if create_message_req_payload.agent_options.is_some() {
+        // The tool calls in the conversation need the tools, but the final answer must not call more of them
+        parameters.tools = Some(get_agent_tools());
+        parameters.tool_choice = Some(ChatCompletionToolChoice::None);
+    }
+
     if let Some(llm_options) = create_message_req_payload.llm_options.clone() {
         parameters.stream = llm_options.stream_response;
         parameters.temperature = dataset_config
@@ -814,6 +1436,8 @@ pub async fn stream_response(
                     ))
                 })?;
 
+        let usage = get_usage_from_response(&assistant_completion).unwrap_or_default();
+
         let completion_content = match &assistant_completion
             .choices
             .get(0)
@@ -834,6 +1458,8 @@ pub async fn stream_response(
             _ => "".to_string(),
         };
 
+        let citations = resolve_citations(&completion_content, chunk_ids.clone());
+
         let new_message = models::Message::from_details(
             format!(
                 "{}{}",
@@ -841,20 +1467,26 @@ pub async fn stream_response(
                 completion_content.clone()
             ),
             topic_id,
-            next_message_order()
+            assistant_sort_order
                 .try_into()
                 .expect("usize to i32 conversion should always succeed"),
             "assistant".to_string(),
-            None,
+            usage.prompt_tokens.map(|tokens| tokens as i32),
             Some(
-                completion_content
-                    .len()
-                    .try_into()
-                    .expect("usize to i32 conversion should always succeed"),
+                usage
+                    .completion_tokens
+                    .map(|tokens| tokens as i32)
+                    .unwrap_or(
+                        completion_content
+                            .len()
+                            .try_into()
+                            .expect("usize to i32 conversion should always succeed"),
+                    ),
             ),
             dataset.id,
             query_id,
-        );
+        )
+        .with_citations(&citations);
 
         let clickhouse_rag_event = RagQueryEventClickhouse {
             id: query_id,
@@ -871,6 +1503,7 @@ pub async fn stream_response(
                 .user_id
                 .clone()
                 .unwrap_or_default(),
+            cache_hit: false,
         };
 
         let response_string = if create_message_req_payload
@@ -888,13 +1521,34 @@ pub async fn stream_response(
 
         create_messages_query(vec![new_message], &pool).await?;
 
+        cache_answer(
+            answer_cache_key,
+            dataset.id,
+            completion_content,
+            chunk_ids,
+            pool,
+        )
+        .await;
+
         return Ok(HttpResponse::Ok()
             .insert_header(("TR-QueryID", query_id.to_string()))
             .json(response_string));
     }
 
     let (s, r) = unbounded::<String>();
-    let stream = client.chat().create_stream(parameters).await.unwrap();
+    parameters.stream_options = Some(ChatCompletionStreamOptions {
+        include_usage: Some(true),
+    });
+    let stream = client
+        .chat()
+        .create_stream(parameters)
+        .await
+        .map_err(|err| {
+            ServiceError::BadRequest(format!(
+                "Model Response Error. Please try again later. {:?}",
+                err
+            ))
+        })?;
 
     let completion_first = create_message_req_payload
         .llm_options
@@ -903,11 +1557,26 @@ pub async fn stream_response(
         .unwrap_or(Some(false))
         .unwrap_or(false);
 
+    let stream_format = create_message_req_payload
+        .llm_options
+        .as_ref()
+        .and_then(|x| x.stream_format)
+        .unwrap_or_default();
+
     let query_id_arb = query_id;
+    let usage = Arc::new(Mutex::new(None::<RagUsage>));
+    let usage_arb = usage.clone();
+    let chunk_ids_arb = chunk_ids.clone();
 
     Arbiter::new().spawn(async move {
         let chunk_v: Vec<String> = r.iter().collect();
         let completion = chunk_v.join("");
+        let usage = usage_arb
+            .lock()
+            .ok()
+            .and_then(|usage| usage.clone())
+            .unwrap_or_default();
+        let citations = resolve_citations(&completion, chunk_ids_arb.clone());
 
         let message_to_be_stored = if completion_first {
             format!("{}{}", completion, chunk_metadatas_stringified)
@@ -918,13 +1587,19 @@ pub async fn stream_response(
         let new_message = models::Message::from_details(
             message_to_be_stored,
             topic_id,
-            next_message_order().try_into().unwrap(),
+            assistant_sort_order.try_into().unwrap(),
             "assistant".to_string(),
-            None,
-            Some(chunk_v.len().try_into().unwrap()),
+            usage.prompt_tokens.map(|tokens| tokens as i32),
+            Some(
+                usage
+                    .completion_tokens
+                    .map(|tokens| tokens as i32)
+                    .unwrap_or(chunk_v.len().try_into().unwrap()),
+            ),
             dataset.id,
             query_id_arb,
-        );
+        )
+        .with_citations(&citations);
 
         let clickhouse_rag_event = RagQueryEventClickhouse {
             id: query_id_arb,
@@ -941,6 +1616,7 @@ pub async fn stream_response(
                 .user_id
                 .clone()
                 .unwrap_or_default(),
+            cache_hit: false,
         };
 
         event_queue
@@ -948,43 +1624,55 @@ pub async fn stream_response(
             .await;
 
         let _ = create_messages_query(vec![new_message], &pool).await;
+
+        cache_answer(
+            answer_cache_key,
+            dataset.id,
+            completion,
+            chunk_ids_arb,
+            pool,
+        )
+        .await;
     });
 
+    if stream_format == RagStreamFormat::Sse {
+        let retrieval_event = RagStreamEvent::Retrieval {
+            search_id: (!search_id.is_nil()).then_some(search_id),
+            chunks: chunk_metadatas
+                .into_iter()
+                .zip(scores)
+                .enumerate()
+                .map(|(idx, (chunk, score))| RagRetrievedChunk {
+                    doc: idx + 1,
+                    score,
+                    chunk,
+                })
+                .collect(),
+        };
+
+        return Ok(HttpResponse::Ok()
+            .insert_header(("TR-QueryID", query_id.to_string()))
+            .content_type("text/event-stream")
+            .streaming(sse_completion_stream(
+                retrieval_event,
+                stream,
+                chunk_ids,
+                s,
+                usage,
+                query_id,
+            )));
+    }
+
     let chunk_stream = stream::iter(vec![Ok(Bytes::from(chunk_metadatas_stringified1))]);
     let completion_stream = stream.map(move |response| -> Result<Bytes, actix_web::Error> {
         if let Ok(response) = response {
-            let chat_content = response
-                .choices
-                .get(0)
-                .map(|choice| {
-                    if choice.finish_reason.is_some() {
-                        Some("".to_string())
-                    } else {
-                        match &choice.delta {
-                            DeltaChatMessage::User {
-                                content: ChatMessageContent::Text(text),
-                                ..
-                            }
-                            | DeltaChatMessage::System {
-                                content: ChatMessageContent::Text(text),
-                                ..
-                            }
-                            | DeltaChatMessage::Assistant {
-                                content: Some(ChatMessageContent::Text(text)),
-                                ..
-                            }
-                            | DeltaChatMessage::Untagged {
-                                content: Some(ChatMessageContent::Text(text)),
-                                ..
-                            } => Some(text.clone()),
-                            _ => {
-                                log::error!("Delta of first choice did not have text or was either Tool or Function {:?}", choice);
-                                None
-                            },
-                        }
-                    }
-                })
-                .unwrap_or(None);
+            if let Some(response_usage) = get_usage_from_response(&response) {
+                if let Ok(mut usage) = usage.lock() {
+                    *usage = Some(response_usage);
+                }
+            }
+
+            let chat_content = get_delta_text(&response);
 
             if let Some(message) = chat_content.clone() {
                 s.send(message).unwrap();
@@ -998,13 +1686,7 @@ pub async fn stream_response(
         .into())
     });
 
-    if create_message_req_payload
-        .llm_options
-        .as_ref()
-        .map(|x| x.completion_first)
-        .unwrap_or(Some(false))
-        .unwrap_or(false)
-    {
+    if completion_first {
         return Ok(HttpResponse::Ok()
             .insert_header(("TR-QueryID", query_id.to_string()))
             .streaming(completion_stream.chain(chunk_stream)));
@@ -1015,6 +1697,294 @@ pub async fn stream_response(
         .streaming(chunk_stream.chain(completion_stream)))
 }
 
+/// The query of a message which missed the answer cache, its answer is cached under it once the completion finishes
+struct AnswerCacheKey {
+    query: String,
+    query_embedding: Vec<f32>,
+    filters_hash: String,
+}
+
+/// Failing to cache an answer does not fail the message
+async fn cache_answer(
+    answer_cache_key: Option<AnswerCacheKey>,
+    dataset_id: uuid::Uuid,
+    completion: String,
+    chunk_ids: Vec<uuid::Uuid>,
+    pool: web::Data<Pool>,
+) {
+    let Some(answer_cache_key) = answer_cache_key else {
+        return;
+    };
+
+    if completion.is_empty() {
+        return;
+    }
+
+    if let Err(err) = insert_cached_answer_query(
+        RagAnswerCache::from_details(
+            dataset_id,
+            answer_cache_key.query,
+            answer_cache_key.query_embedding.len(),
+            answer_cache_key.filters_hash,
+            completion,
+            chunk_ids,
+        ),
+        answer_cache_key.query_embedding,
+        pool,
+    )
+    .await
+    {
+        log::error!("Failed to cache answer: {:?}", err);
+    }
+}
+
+/// Answers the message with a cached answer in the same format as a generated one. Returns None if any of the chunks it cites no longer exist.
+#[allow(clippy::too_many_arguments)]
+async fn respond_with_cached_answer(
+    cached_answer: RagAnswerCache,
+    topic_id: uuid::Uuid,
+    sort_order: usize,
+    dataset: Dataset,
+    user_message_query: String,
+    create_message_req_payload: CreateMessageReqPayload,
+    pool: web::Data<Pool>,
+    event_queue: web::Data<EventQueue>,
+) -> Result<Option<HttpResponse>, actix_web::Error> {
+    let chunk_ids = cached_answer
+        .chunk_ids
+        .into_iter()
+        .flatten()
+        .collect::<Vec<uuid::Uuid>>();
+
+    let mut chunks_by_id: HashMap<uuid::Uuid, ChunkMetadata> =
+        get_metadata_from_ids_query(chunk_ids.clone(), dataset.id, pool.clone())
+            .await?
+            .into_iter()
+            .map(|chunk| (chunk.id, chunk))
+            .collect();
+
+    let Some(chunk_metadatas) = chunk_ids
+        .iter()
+        .map(|chunk_id| {
+            chunks_by_id
+                .remove(chunk_id)
+                .map(ChunkMetadataStringTagSet::from)
+        })
+        .collect::<Option<Vec<ChunkMetadataStringTagSet>>>()
+    else {
+        return Ok(None);
+    };
+
+    let completion = cached_answer.completion;
+    let citations = resolve_citations(&completion, chunk_ids);
+    let query_id = uuid::Uuid::new_v4();
+
+    let completion_first = create_message_req_payload
+        .llm_options
+        .as_ref()
+        .and_then(|x| x.completion_first)
+        .unwrap_or(false);
+
+    let chunk_metadatas_stringified = serde_json::to_string(&chunk_metadatas)
+        .expect("Failed to serialize citation chunks")
+        .replace("||", "");
+    let response_string = if completion_first {
+        format!("{}||{}", completion, chunk_metadatas_stringified)
+    } else {
+        format!("{}||{}", chunk_metadatas_stringified, completion)
+    };
+
+    let new_message = models::Message::from_details(
+        response_string.clone(),
+        topic_id,
+        sort_order
+            .try_into()
+            .expect("usize to i32 conversion should always succeed"),
+        "assistant".to_string(),
+        None,
+        None,
+        dataset.id,
+        query_id,
+    )
+    .with_citations(&citations);
+
+    let clickhouse_rag_event = RagQueryEventClickhouse {
+        id: query_id,
+        created_at: time::OffsetDateTime::now_utc(),
+        dataset_id: dataset.id,
+        search_id: uuid::Uuid::nil(),
+        results: vec![],
+        json_results: chunk_metadatas
+            .iter()
+            .map(|x| {
+                let mut json = serde_json::to_value(x).unwrap_or_default();
+                escape_quotes(&mut json);
+                json.to_string()
+            })
+            .collect(),
+        user_message: user_message_query,
+        query_rating: String::new(),
+        rag_type: "all_chunks".to_string(),
+        llm_response: completion.clone(),
+        user_id: create_message_req_payload
+            .user_id
+            .clone()
+            .unwrap_or_default(),
+        cache_hit: true,
+    };
+
+    event_queue
+        .send(ClickHouseEvent::RagQueryEvent(clickhouse_rag_event))
+        .await;
+
+    create_messages_query(vec![new_message], &pool).await?;
+
+    let llm_options = create_message_req_payload.llm_options.unwrap_or_default();
+
+    if !llm_options.stream_response.unwrap_or(true) {
+        return Ok(Some(
+            HttpResponse::Ok()
+                .insert_header(("TR-QueryID", query_id.to_string()))
+                .json(response_string),
+        ));
+    }
+
+    if llm_options.stream_format.unwrap_or_default() == RagStreamFormat::Sse {
+        let mut events = vec![
+            RagStreamEvent::Retrieval {
+                search_id: None,
+                chunks: chunk_metadatas
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, chunk)| RagRetrievedChunk {
+                        doc: idx + 1,
+                        score: None,
+                        chunk,
+                    })
+                    .collect(),
+            },
+            RagStreamEvent::Delta {
+                content: completion,
+            },
+        ];
+        events.extend(citations.into_iter().map(RagStreamEvent::Citation));
+        events.push(RagStreamEvent::Usage(RagUsage {
+            prompt_tokens: Some(0),
+            completion_tokens: Some(0),
+            total_tokens: Some(0),
+        }));
+        events.push(RagStreamEvent::Done { query_id });
+
+        return Ok(Some(
+            HttpResponse::Ok()
+                .insert_header(("TR-QueryID", query_id.to_string()))
+                .content_type("text/event-stream")
+                .body(
+                    events
+                        .iter()
+                        .flat_map(|event| event.to_sse_bytes().to_vec())
+                        .collect::<Vec<u8>>(),
+                ),
+        ));
+    }
+
+    Ok(Some(
+        HttpResponse::Ok()
+            .insert_header(("TR-QueryID", query_id.to_string()))
+            .body(response_string),
+    ))
+}
+
+/// Streams the retrieval event followed by the delta and citation events of the completion, then the usage and done events once it has finished. An upstream failure is sent as an error event and ends the completion. Deltas are also sent to `sender` so the caller can store the completion.
+pub fn sse_completion_stream<S, E>(
+    retrieval_event: RagStreamEvent,
+    completion_stream: S,
+    chunk_ids: Vec<uuid::Uuid>,
+    sender: crossbeam_channel::Sender<String>,
+    usage: Arc<Mutex<Option<RagUsage>>>,
+    query_id: uuid::Uuid,
+) -> impl futures::Stream<Item = Result<Bytes, actix_web::Error>>
+where
+    S: futures::Stream<Item = Result<ChatCompletionChunkResponse, E>>,
+    E: std::fmt::Debug,
+{
+    let mut citation_parser = CitationParser::new(chunk_ids);
+    let delta_count = Arc::new(AtomicU32::new(0));
+    let delta_count_done = delta_count.clone();
+    let usage_done = usage.clone();
+
+    let retrieval_stream = stream::iter(vec![Ok(retrieval_event.to_sse_bytes())]);
+
+    let mut failed = false;
+    let event_stream = completion_stream.map(move |response| -> Result<Bytes, actix_web::Error> {
+        if failed {
+            return Ok(Bytes::new());
+        }
+
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                log::error!("Model response error while streaming {:?}", err);
+                failed = true;
+                return Ok(RagStreamEvent::Error {
+                    message: "Model Response Error. Please try again later.".to_string(),
+                }
+                .to_sse_bytes());
+            }
+        };
+
+        if let Some(response_usage) = get_usage_from_response(&response) {
+            if let Ok(mut usage) = usage.lock() {
+                *usage = Some(response_usage);
+            }
+        }
+
+        let content = match get_delta_text(&response) {
+            Some(content) if !content.is_empty() => content,
+            _ => return Ok(Bytes::new()),
+        };
+
+        delta_count.fetch_add(1, Ordering::Relaxed);
+        let _ = sender.send(content.clone());
+
+        let mut events = citation_parser
+            .push(&content)
+            .into_iter()
+            .map(RagStreamEvent::Citation)
+            .collect::<Vec<RagStreamEvent>>();
+        events.insert(0, RagStreamEvent::Delta { content });
+
+        Ok(Bytes::from(
+            events
+                .iter()
+                .flat_map(|event| event.to_sse_bytes().to_vec())
+                .collect::<Vec<u8>>(),
+        ))
+    });
+
+    let done_stream = stream::once(async move {
+        // Not every provider reports usage for streamed completions, fall back to the number of deltas
+        let usage = usage_done
+            .lock()
+            .ok()
+            .and_then(|usage| usage.clone())
+            .unwrap_or(RagUsage {
+                completion_tokens: Some(delta_count_done.load(Ordering::Relaxed)),
+                ..Default::default()
+            });
+
+        Ok(Bytes::from(
+            [
+                RagStreamEvent::Usage(usage).to_sse_bytes(),
+                RagStreamEvent::Done { query_id }.to_sse_bytes(),
+            ]
+            .concat(),
+        ))
+    });
+
+    retrieval_stream.chain(event_stream).chain(done_stream)
+}
+
 #[tracing::instrument]
 pub async fn get_topic_string(
     model: String,
@@ -1110,3 +2080,225 @@ pub async fn get_topic_string(
 
     Ok(topic)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn topic_message(
+        sort_order: i32,
+        role: &str,
+        prompt_tokens: Option<i32>,
+        completion_tokens: Option<i32>,
+    ) -> Message {
+        Message::from_details(
+            format!("{} message {}", role, sort_order),
+            uuid::Uuid::nil(),
+            sort_order,
+            role.to_string(),
+            prompt_tokens,
+            completion_tokens,
+            uuid::Uuid::nil(),
+            uuid::Uuid::new_v4(),
+        )
+    }
+
+    #[test]
+    fn test_message_token_count_uses_stored_counts() {
+        assert_eq!(
+            get_message_token_count(&topic_message(2, "assistant", Some(900), Some(42))),
+            42
+        );
+        assert_eq!(
+            get_message_token_count(&topic_message(1, "user", Some(7), None)),
+            7
+        );
+        assert_eq!(
+            get_message_token_count(&topic_message(0, SUMMARY_ROLE, None, Some(12))),
+            12
+        );
+    }
+
+    #[test]
+    fn test_message_token_count_falls_back_to_the_tokenizer() {
+        assert_eq!(count_tokens("hello world"), 2);
+
+        let mut user_message = topic_message(1, "user", None, None);
+        user_message.content = "東京の天気はどうですか？明日は雨が降りますか？".to_string();
+        assert_eq!(
+            get_message_token_count(&user_message),
+            count_tokens(&user_message.content)
+        );
+
+        // Older topics stored their system message with a count of 0
+        let system_message = topic_message(0, "system", Some(0), Some(0));
+        assert_eq!(
+            get_message_token_count(&system_message),
+            count_tokens(&system_message.content)
+        );
+    }
+
+    #[test]
+    fn test_summary_split_keeps_recent_messages_within_half_the_budget() {
+        let conversation = (1..=4)
+            .map(|sort_order| topic_message(sort_order, "assistant", None, Some(100)))
+            .collect::<Vec<Message>>();
+
+        assert_eq!(get_summary_split(&conversation, 300), 3);
+        assert_eq!(get_summary_split(&conversation, 400), 2);
+        assert_eq!(get_summary_split(&conversation, 1000), 0);
+    }
+
+    #[test]
+    fn test_summary_split_always_keeps_the_last_message() {
+        let conversation = vec![
+            topic_message(1, "user", Some(10), None),
+            topic_message(2, "user", Some(1000), None),
+        ];
+
+        assert_eq!(get_summary_split(&conversation, 100), 1);
+    }
+
+    #[test]
+    fn test_summary_is_linked_to_the_messages_it_covers() {
+        let conversation = (1..=6)
+            .map(|sort_order| topic_message(sort_order, "user", Some(10), None))
+            .collect::<Vec<Message>>();
+
+        let summary = new_summary_message(
+            "The user asked about returns".to_string(),
+            Some(6),
+            &conversation[..3],
+            uuid::Uuid::nil(),
+            uuid::Uuid::nil(),
+        );
+
+        assert_eq!(summary.role, SUMMARY_ROLE);
+        assert_eq!(summary.summarized_through, Some(3));
+        assert_eq!(summary.completion_tokens, Some(6));
+        assert!(conversation
+            .iter()
+            .all(|message| message.sort_order != summary.sort_order));
+
+        assert_eq!(
+            get_messages_after_summary(conversation.clone(), Some(&summary))
+                .iter()
+                .map(|message| message.sort_order)
+                .collect::<Vec<i32>>(),
+            vec![4, 5, 6]
+        );
+        assert_eq!(
+            get_messages_after_summary(conversation.clone(), None).len(),
+            conversation.len()
+        );
+    }
+
+    fn citation(doc: usize, chunk_id: uuid::Uuid, start: usize, end: usize) -> MessageCitation {
+        MessageCitation {
+            doc,
+            chunk_id,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_citation_split_across_deltas() {
+        let chunk_ids = vec![uuid::Uuid::new_v4(), uuid::Uuid::new_v4()];
+        let mut parser = CitationParser::new(chunk_ids.clone());
+
+        assert!(parser.push("Trieve is fast [").is_empty());
+        assert!(parser.push("2").is_empty());
+        let citations = parser.push("] and cheap.");
+
+        assert_eq!(citations, vec![citation(2, chunk_ids[1], 15, 18)]);
+    }
+
+    #[test]
+    fn test_multiple_docs_split_across_deltas() {
+        let chunk_ids = vec![uuid::Uuid::new_v4(), uuid::Uuid::new_v4()];
+        let mut parser = CitationParser::new(chunk_ids.clone());
+
+        assert!(parser.push("Both [1,").is_empty());
+        let citations = parser.push(" 2] agree.");
+
+        assert_eq!(
+            citations,
+            vec![
+                citation(1, chunk_ids[0], 5, 11),
+                citation(2, chunk_ids[1], 5, 11)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_streamed_citations_match_whole_completion() {
+        let chunk_ids = vec![uuid::Uuid::new_v4(), uuid::Uuid::new_v4()];
+        let completion = "Héllo [1]. Wörld [2][1] and [x] [3].";
+        let mut parser = CitationParser::new(chunk_ids.clone());
+
+        let streamed = completion
+            .chars()
+            .flat_map(|c| parser.push(&c.to_string()))
+            .collect::<Vec<MessageCitation>>();
+
+        assert_eq!(streamed, resolve_citations(completion, chunk_ids.clone()));
+        assert_eq!(
+            streamed,
+            vec![
+                citation(1, chunk_ids[0], 6, 9),
+                citation(2, chunk_ids[1], 17, 20),
+                citation(1, chunk_ids[0], 20, 23),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_citation_bracket_does_not_block_later_citations() {
+        let chunk_ids = vec![uuid::Uuid::new_v4()];
+        let mut parser = CitationParser::new(chunk_ids.clone());
+
+        assert!(parser.push("See [the").is_empty());
+        let citations = parser.push(" docs] [1]");
+
+        assert_eq!(citations, vec![citation(1, chunk_ids[0], 15, 18)]);
+    }
+
+    #[test]
+    fn test_sse_stream_sends_error_event_on_upstream_failure() {
+        let (sender, receiver) = crossbeam_channel::unbounded::<String>();
+        let query_id = uuid::Uuid::new_v4();
+        let completion_stream = stream::iter(vec![
+            Err::<ChatCompletionChunkResponse, &str>("connection reset"),
+            Err("connection reset"),
+        ]);
+
+        let body = futures::executor::block_on(
+            sse_completion_stream(
+                RagStreamEvent::Retrieval {
+                    search_id: None,
+                    chunks: vec![],
+                },
+                completion_stream,
+                vec![],
+                sender,
+                Arc::new(Mutex::new(None)),
+                query_id,
+            )
+            .collect::<Vec<Result<Bytes, actix_web::Error>>>(),
+        )
+        .into_iter()
+        .map(|bytes| String::from_utf8(bytes.expect("Stream should not fail").to_vec()).unwrap())
+        .collect::<String>();
+
+        let events = body
+            .split("\n\n")
+            .filter_map(|event| event.strip_prefix("event: "))
+            .filter_map(|event| event.split('\n').next())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(events, vec!["retrieval", "error", "usage", "done"]);
+        assert!(body.contains(&query_id.to_string()));
+        assert!(receiver.try_recv().is_err());
+    }
+}
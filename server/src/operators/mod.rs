@@ -1,21 +1,38 @@
+pub mod agent_operator;
 pub mod analytics_operator;
+pub mod audit_operator;
 pub mod chunk_operator;
+pub mod chunk_version_operator;
+pub mod chunking_operator;
 pub mod clickhouse_operator;
+pub mod connector_operator;
 pub mod crawl_operator;
+pub mod crawl_run_operator;
+pub mod dataset_archive_operator;
 pub mod dataset_operator;
 pub mod dittofeed_operator;
 pub mod email_operator;
+pub mod embedding_cache_operator;
+pub mod embedding_provider_operator;
 pub mod event_operator;
+pub mod facet_operator;
 pub mod file_operator;
+pub mod file_parser_operator;
 pub mod group_operator;
 pub mod invitation_operator;
+pub mod merchandising_operator;
 pub mod message_operator;
 pub mod model_operator;
 pub mod organization_operator;
 pub mod parse_operator;
 pub mod qdrant_operator;
+pub mod rag_cache_operator;
+pub mod scim_operator;
 pub mod search_operator;
+pub mod secret_operator;
+pub mod sso_operator;
 pub mod stripe_operator;
+pub mod synonym_operator;
 pub mod topic_operator;
 pub mod typo_operator;
 pub mod user_operator;
@@ -1,46 +1,15 @@
 use crate::{
-    data::models::{ChunkMetadataTypes, DatasetConfiguration, ScoreChunkDTO},
+    data::models::{ChunkMetadataTypes, DatasetConfiguration, DatasetLanguage, ScoreChunkDTO},
     errors::ServiceError,
-    get_env,
     handlers::chunk_handler::{FullTextBoost, SemanticBoost},
 };
-use actix_web::web;
 use murmur3::murmur3_32;
-use openai_dive::v1::resources::embedding::EmbeddingInput;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io::Cursor, ops::IndexMut};
+use std::{collections::HashMap, io::Cursor};
 
+use super::embedding_provider_operator::get_embedding_provider;
 use super::parse_operator::convert_html_to_text;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmbeddingParameters {
-    /// Input text to embed, encoded as a string or array of tokens.
-    /// To embed multiple inputs in a single request, pass an array of strings or array of token arrays.
-    pub input: EmbeddingInput,
-    /// ID of the model to use.
-    pub model: String,
-    /// Truncate the input to the maximum length of the model.
-    pub truncate: bool,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DenseEmbedData {
-    pub data: Vec<EmbeddingInner>,
-}
-
-impl DenseEmbedData {
-    pub fn to_vec(&self) -> Vec<Vec<f32>> {
-        self.data
-            .iter()
-            .map(|inner| inner.embedding.clone())
-            .collect()
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmbeddingInner {
-    embedding: Vec<f32>,
-}
+use super::qdrant_operator::get_vector_field_name;
 
 #[tracing::instrument]
 pub async fn get_dense_vector(
@@ -64,49 +33,19 @@ pub async fn get_dense_vector(
     };
     sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone())));
 
-    let embedding_api_key = get_env!("OPENAI_API_KEY", "OPENAI_API_KEY should be set");
-    let config_embedding_base_url = dataset_config.EMBEDDING_BASE_URL;
+    transaction.set_data(
+        "EMBEDDING_PROVIDER",
+        dataset_config.EMBEDDING_PROVIDER.to_string().into(),
+    );
     transaction.set_data(
         "EMBEDDING_SERVER",
-        config_embedding_base_url.as_str().into(),
+        dataset_config.EMBEDDING_BASE_URL.as_str().into(),
     );
     transaction.set_data(
         "EMBEDDING_MODEL",
         dataset_config.EMBEDDING_MODEL_NAME.as_str().into(),
     );
 
-    let embedding_base_url = match config_embedding_base_url.as_str() {
-        "" => get_env!("OPENAI_BASE_URL", "OPENAI_BASE_URL must be set").to_string(),
-        "https://api.openai.com/v1" => {
-            get_env!("OPENAI_BASE_URL", "OPENAI_BASE_URL must be set").to_string()
-        }
-        "https://embedding.trieve.ai" => std::env::var("EMBEDDING_SERVER_ORIGIN")
-            .ok()
-            .filter(|s| !s.is_empty())
-            .unwrap_or("https://embedding.trieve.ai".to_string()),
-        "https://embedding.trieve.ai/bge-m3" => std::env::var("EMBEDDING_SERVER_ORIGIN_BGEM3")
-            .ok()
-            .filter(|s| !s.is_empty())
-            .unwrap_or("https://embedding.trieve.ai/bge-m3".to_string()),
-        "https://embedding.trieve.ai/jina-code" => {
-            std::env::var("EMBEDDING_SERVER_ORIGIN_JINA_CODE")
-                .ok()
-                .filter(|s| !s.is_empty())
-                .unwrap_or("https://embedding.trieve.ai/jina-code".to_string())
-        }
-        _ => config_embedding_base_url.clone(),
-    };
-
-    let embedding_api_key =
-        if config_embedding_base_url.as_str() == "https://embedding.trieve.ai/jina-code" {
-            std::env::var("JINA_CODE_API_KEY")
-                .ok()
-                .filter(|s| !s.is_empty())
-                .unwrap_or(embedding_api_key.to_string())
-        } else {
-            embedding_api_key.to_string()
-        };
-
     let clipped_message: String = message.chars().take(20000).collect();
     let mut messages = vec![format!(
         "{}{}",
@@ -124,75 +63,45 @@ pub async fn get_dense_vector(
         messages.push(clipped_boost);
     }
 
-    let input = EmbeddingInput::StringArray(messages);
-    let parameters = EmbeddingParameters {
-        model: dataset_config.EMBEDDING_MODEL_NAME.to_string(),
-        input,
-        truncate: true,
-    };
-
-    let resp = web::block(move || {
-        let embeddings_resp_a = ureq::post(&format!(
-            "{}/embeddings?api-version=2023-05-15",
-            embedding_base_url
-        ))
-        .set("Authorization", &format!("Bearer {}", &embedding_api_key))
-        .set("api-key", &embedding_api_key)
-        .set("Content-Type", "application/json")
-        .send_json(serde_json::to_value(parameters).unwrap())
-        .map_err(|e| {
-            ServiceError::InternalServerError(format!(
-                "Could not get embeddings from server: {:?}, {:?}",
-                e,
-                e.to_string()
-            ))
-        })?;
-
-        let embeddings_resp = embeddings_resp_a
-            .into_json::<DenseEmbedData>()
-            .map_err(|err| {
-                ServiceError::InternalServerError(format!(
-                    "Failed to format response from embeddings server {:?}",
-                    err
+    let provider = get_embedding_provider(
+        dataset_config.EMBEDDING_PROVIDER,
+        &dataset_config,
+        reqwest::Client::new(),
+    );
+    let mut vectors = provider.dense_vectors(messages, embed_type).await?;
+
+    let resp = if let Some(semantic_boost) = semantic_boost {
+        let distance_factor = semantic_boost.distance_factor;
+        let boost_vector = match vectors.pop() {
+            Some(v) => v,
+            None => {
+                return Err(ServiceError::InternalServerError(
+                    "No dense embedding returned from server for boost_vector".to_owned(),
                 ))
-            })?;
-
-        let mut vectors = embeddings_resp.to_vec();
-        if let Some(semantic_boost) = semantic_boost {
-            let distance_factor = semantic_boost.distance_factor;
-            let boost_vector = match vectors.pop() {
-                Some(v) => v,
-                None => {
-                    return Err(ServiceError::InternalServerError(
-                        "No dense embedding returned from server for boost_vector".to_owned(),
-                    ))
-                }
-            };
-            let embedding_vector = match vectors.pop() {
-                Some(v) => v,
-                None => {
-                    return Err(ServiceError::InternalServerError(
-                        "No dense embedding returned from server for embedding_vector".to_owned(),
-                    ))
-                }
-            };
-
-            return Ok(embedding_vector
-                .iter()
-                .zip(boost_vector)
-                .map(|(vec_elem, boost_vec_elem)| vec_elem + distance_factor * boost_vec_elem)
-                .collect());
-        }
+            }
+        };
+        let embedding_vector = match vectors.pop() {
+            Some(v) => v,
+            None => {
+                return Err(ServiceError::InternalServerError(
+                    "No dense embedding returned from server for embedding_vector".to_owned(),
+                ))
+            }
+        };
 
+        Ok(embedding_vector
+            .iter()
+            .zip(boost_vector)
+            .map(|(vec_elem, boost_vec_elem)| vec_elem + distance_factor * boost_vec_elem)
+            .collect())
+    } else {
         match vectors.first() {
             Some(v) => Ok(v.clone()),
             None => Err(ServiceError::InternalServerError(
                 "No dense embeddings returned from server".to_owned(),
             )),
         }
-    })
-    .await
-    .map_err(|err| ServiceError::BadRequest(format!("Thread error {:?}", err)))?;
+    };
 
     transaction.finish();
     resp
@@ -203,21 +112,8 @@ pub async fn get_sparse_vector(
     message: String,
     fulltext_boost: Option<FullTextBoost>,
     embed_type: &str,
+    dataset_config: DatasetConfiguration,
 ) -> Result<Vec<(u32, f32)>, ServiceError> {
-    let origin_key = match embed_type {
-        "doc" => "SPARSE_SERVER_DOC_ORIGIN",
-        "query" => "SPARSE_SERVER_QUERY_ORIGIN",
-        _ => unreachable!("Invalid embed_type passed"),
-    };
-
-    let server_origin = std::env::var(origin_key)
-        .ok()
-        .filter(|s| !s.is_empty())
-        .ok_or(ServiceError::BadRequest(format!(
-            "{} does not exist",
-            origin_key
-        )))?;
-
     let clipped_message: String = message.chars().take(20000).collect();
     let mut inputs = vec![clipped_message.clone()];
     if let Some(fulltext_boost) = fulltext_boost.as_ref() {
@@ -231,98 +127,63 @@ pub async fn get_sparse_vector(
         inputs.push(clipped_boost);
     }
 
-    let embedding_server_call = format!("{}/embed_sparse", server_origin);
-    let embed_type_string = embed_type.to_owned();
-
-    web::block(move || {
-        let mut sparse_vectors = ureq::post(&embedding_server_call)
-            .set("Content-Type", "application/json")
-            .set(
-                "Authorization",
-                &format!(
-                    "Bearer {}",
-                    get_env!("OPENAI_API_KEY", "OPENAI_API should be set")
-                ),
-            )
-            .send_json(CustomSparseEmbedData {
-                inputs,
-                encode_type: embed_type_string,
-                truncate: true,
-            })
-            .map_err(|err| {
-                log::error!(
-                    "Failed parsing response from custom embedding server {:?}",
-                    err
-                );
-                ServiceError::BadRequest(format!("Failed making call to server {:?}", err))
-            })?
-            .into_json::<Vec<Vec<SpladeIndicies>>>()
-            .map_err(|_e| {
-                log::error!(
-                    "Failed parsing response from custom embedding server {:?}",
-                    _e
-                );
-                ServiceError::BadRequest(
-                    "Failed parsing response from custom embedding server".to_string(),
-                )
-            })?;
-
-        if let Some(fulltext_boost) = fulltext_boost {
-            let boost_amt = fulltext_boost.boost_factor;
-            let boost_vector = match sparse_vectors.pop() {
-                Some(v) => v,
-                None => {
-                    return Err(ServiceError::InternalServerError(
-                        "No sparse vector returned from server for boost_vector".to_owned(),
-                    ))
-                }
-            };
-            let query_vector = match sparse_vectors.pop() {
-                Some(v) => v,
-                None => {
-                    return Err(ServiceError::InternalServerError(
-                        "No sparse vector returned from server for embedding_vector".to_owned(),
-                    ))
-                }
-            };
+    let provider = get_embedding_provider(
+        dataset_config.SPARSE_EMBEDDING_PROVIDER,
+        &dataset_config,
+        reqwest::Client::new(),
+    );
+    let mut sparse_vectors = provider.sparse_vectors(inputs, embed_type).await?;
+
+    if let Some(fulltext_boost) = fulltext_boost {
+        let boost_amt = fulltext_boost.boost_factor;
+        let boost_vector = match sparse_vectors.pop() {
+            Some(v) => v,
+            None => {
+                return Err(ServiceError::InternalServerError(
+                    "No sparse vector returned from server for boost_vector".to_owned(),
+                ))
+            }
+        };
+        let query_vector = match sparse_vectors.pop() {
+            Some(v) => v,
+            None => {
+                return Err(ServiceError::InternalServerError(
+                    "No sparse vector returned from server for embedding_vector".to_owned(),
+                ))
+            }
+        };
 
-            let boosted_query_vector = query_vector
-                .iter()
-                .map(|splade_indice| {
-                    if boost_vector
-                        .iter()
-                        .any(|boost_splade_indice| boost_splade_indice.index == splade_indice.index)
-                    {
-                        SpladeIndicies {
-                            index: splade_indice.index,
-                            value: splade_indice.value * (boost_amt as f32),
-                        }
-                        .into_tuple()
-                    } else {
-                        SpladeIndicies {
-                            index: splade_indice.index,
-                            value: splade_indice.value,
-                        }
-                        .into_tuple()
-                    }
-                })
-                .collect();
+        return Ok(boost_sparse_vector(query_vector, &boost_vector, boost_amt));
+    }
 
-            return Ok(boosted_query_vector);
-        }
+    match sparse_vectors.into_iter().next() {
+        Some(v) => Ok(v),
+        None => Err(ServiceError::InternalServerError(
+            "No sparse embeddings returned from server".to_owned(),
+        )),
+    }
+}
 
-        match sparse_vectors.first() {
-            Some(v) => Ok(v
+/// Multiplies the values of the indices of the sparse vector which are also in the boost vector by the boost amount
+fn boost_sparse_vector(
+    sparse_vector: Vec<(u32, f32)>,
+    boost_vector: &[(u32, f32)],
+    boost_amt: f64,
+) -> Vec<(u32, f32)> {
+    sparse_vector
+        .into_iter()
+        .map(|(index, value)| {
+            // Any is here because we multiply all of the matching indices by the boost amount and the boost amount is not unique to any index
+            if boost_vector
                 .iter()
-                .map(|splade_idx| (*splade_idx).into_tuple())
-                .collect()),
-            None => Err(ServiceError::InternalServerError(
-                "No sparse embeddings returned from server".to_owned(),
-            )),
-        }
-    })
-    .await
-    .map_err(|err| ServiceError::BadRequest(format!("Thread error {:?}", err)))?
+                .any(|(boost_index, _)| *boost_index == index)
+            {
+                (index, value * (boost_amt as f32))
+            } else {
+                (index, value)
+            }
+        })
+        .collect()
 }
 
 #[tracing::instrument]
@@ -347,41 +208,12 @@ pub async fn get_dense_vectors(
     };
     sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone())));
 
-    let embedding_api_key = get_env!("OPENAI_API_KEY", "OPENAI_API_KEY should be set");
-    let config_embedding_base_url = dataset_config.EMBEDDING_BASE_URL;
-    let embedding_base_url = match config_embedding_base_url.as_str() {
-        "" => get_env!("OPENAI_BASE_URL", "OPENAI_BASE_URL must be set").to_string(),
-        "https://api.openai.com/v1" => {
-            get_env!("OPENAI_BASE_URL", "OPENAI_BASE_URL must be set").to_string()
-        }
-        "https://embedding.trieve.ai" => std::env::var("EMBEDDING_SERVER_ORIGIN")
-            .ok()
-            .filter(|s| !s.is_empty())
-            .unwrap_or("https://embedding.trieve.ai".to_string()),
-        "https://embedding.trieve.ai/bge-m3" => std::env::var("EMBEDDING_SERVER_ORIGIN_BGEM3")
-            .ok()
-            .filter(|s| !s.is_empty())
-            .unwrap_or("https://embedding.trieve.ai/bge-m3".to_string())
-            .to_string(),
-        "https://embedding.trieve.ai/jina-code" => {
-            std::env::var("EMBEDDING_SERVER_ORIGIN_JINA_CODE")
-                .ok()
-                .filter(|s| !s.is_empty())
-                .unwrap_or("https://embedding.trieve.ai/jina-code".to_string())
-                .to_string()
-        }
-        _ => config_embedding_base_url.clone(),
-    };
-
-    let embedding_api_key =
-        if config_embedding_base_url.as_str() == "https://embedding.trieve.ai/jina-code" {
-            std::env::var("JINA_CODE_API_KEY")
-                .ok()
-                .filter(|s| !s.is_empty())
-                .unwrap_or(embedding_api_key.to_string())
-        } else {
-            embedding_api_key.to_string()
-        };
+    let provider = get_embedding_provider(
+        dataset_config.EMBEDDING_PROVIDER,
+        &dataset_config,
+        reqwest_client,
+    );
+    let provider = provider.as_ref();
 
     let (contents, distance_phrases): (Vec<_>, Vec<_>) =
         content_and_distances.clone().into_iter().unzip();
@@ -399,74 +231,33 @@ pub async fn get_dense_vectors(
         .collect::<Vec<(usize, SemanticBoost)>>();
     let thirty_filterted_distances_with_indices = filtered_distances_with_index.chunks(30);
 
+    let query_inputs = |clipped_messages: Vec<String>| match embed_type {
+        "query" => vec![format!(
+            "{}{}",
+            dataset_config.EMBEDDING_QUERY_PREFIX,
+            clipped_messages.first().unwrap_or(&"".to_string())
+        )],
+        _ => clipped_messages,
+    };
+
     let vec_distance_futures: Vec<_> = thirty_filterted_distances_with_indices
         .map(|thirty_distances| {
-            let distance_phrases = thirty_distances
+            let clipped_messages = thirty_distances
                 .iter()
-                .map(|(_, x)| x.phrase.clone())
+                .map(|(_, x)| x.phrase.chars().take(12000).collect())
                 .collect::<Vec<String>>();
 
-            let clipped_messages = distance_phrases
-                .iter()
-                .map(|message| {
-                        message.chars().take(12000).collect()
-                })
-                .collect::<Vec<String>>();
-
-            let input = match embed_type {
-                "doc" => EmbeddingInput::StringArray(clipped_messages),
-                "query" => EmbeddingInput::String(
-                    format!(
-                        "{}{}",
-                        dataset_config.EMBEDDING_QUERY_PREFIX, &clipped_messages[0]
-                    )
-                    .to_string(),
-                ),
-                _ => EmbeddingInput::StringArray(clipped_messages),
-            };
-
-            let parameters = EmbeddingParameters {
-                model: dataset_config.EMBEDDING_MODEL_NAME.to_string(),
-                input,
-                truncate: true
-            };
-
-            let cur_client = reqwest_client.clone();
-            let url = embedding_base_url.clone();
-
-            let embedding_api_key = embedding_api_key.clone();
+            let inputs = query_inputs(clipped_messages);
 
             async move {
-                let embeddings_resp = cur_client
-                    .post(format!("{}/embeddings?api-version=2023-05-15", url))
-                    .header("Authorization", &format!("Bearer {}", &embedding_api_key.clone()))
-                    .header("api-key", &embedding_api_key.clone())
-                    .header("Content-Type", "application/json")
-                    .json(&parameters)
-                    .send()
-                    .await
-                    .map_err(|_| {
-                        ServiceError::BadRequest("Failed to send message to embedding server".to_string())
-                    })?
-                    .json::<DenseEmbedData>()
-                    .await
-                    .map_err(|err| {
-                        ServiceError::BadRequest(format!("Failed to format text from embeddings {}", err))
-                    })?;
-
-                let vectors_and_boosts: Vec<(Vec<f32>, &(usize, SemanticBoost))> = embeddings_resp
-                    .to_vec()
+                let vectors_and_boosts: Vec<(Vec<f32>, &(usize, SemanticBoost))> = provider
+                    .dense_vectors(inputs, embed_type)
+                    .await?
                     .into_iter()
                     .zip(thirty_distances)
                     .collect();
 
-                    if vectors_and_boosts.iter().any(|x| x.0.is_empty()) {
-                        return Err(ServiceError::InternalServerError(
-                            "Embedding server responded with Base64 and that is not currently supported for embeddings".to_owned(),
-                        ));
-                    }
-
-                Ok(vectors_and_boosts)
+                Ok::<_, ServiceError>(vectors_and_boosts)
             }
         })
         .collect();
@@ -478,60 +269,7 @@ pub async fn get_dense_vectors(
                 .map(|message| message.chars().take(12000).collect())
                 .collect::<Vec<String>>();
 
-            let input = match embed_type {
-                "doc" => EmbeddingInput::StringArray(clipped_messages),
-                "query" => EmbeddingInput::String(
-                    format!(
-                        "{}{}",
-                        dataset_config.EMBEDDING_QUERY_PREFIX,
-                        &clipped_messages.get(0).unwrap_or(&"".to_string())
-                    )
-                    .to_string(),
-                ),
-                _ => EmbeddingInput::StringArray(clipped_messages),
-            };
-
-            let parameters = EmbeddingParameters {
-                model: dataset_config.EMBEDDING_MODEL_NAME.to_string(),
-                input,
-                truncate: true,
-            };
-
-            let cur_client = reqwest_client.clone();
-            let url = embedding_base_url.clone();
-
-            let embedding_api_key = embedding_api_key.clone();
-
-            async move {
-                let embeddings_resp = cur_client
-                    .post(format!("{}/embeddings?api-version=2023-05-15", url))
-                    .header(
-                        "Authorization",
-                        &format!("Bearer {}", &embedding_api_key.clone()),
-                    )
-                    .header("api-key", &embedding_api_key.clone())
-                    .header("Content-Type", "application/json")
-                    .json(&parameters)
-                    .send()
-                    .await
-                    .map_err(|_| {
-                        ServiceError::BadRequest(
-                            "Failed to send message to embedding server".to_string(),
-                        )
-                    })?
-                    .json::<DenseEmbedData>()
-                    .await
-                    .map_err(|err| {
-                        ServiceError::BadRequest(format!(
-                            "Failed to get text from embeddings {:?}",
-                            err
-                        ))
-                    })?;
-
-                let vectors: Vec<Vec<f32>> = embeddings_resp.to_vec();
-
-                Ok(vectors)
-            }
+            provider.dense_vectors(query_inputs(clipped_messages), embed_type)
         })
         .collect();
 
@@ -580,33 +318,68 @@ pub async fn get_dense_vectors(
     Ok(content_vectors)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SpladeEmbedding {
-    pub embeddings: Vec<(u32, f32)>,
-}
+/// Embeds the vector fields of each chunk, keyed by the name of the vector slot of the field in the dataset's VECTOR_FIELDS
+pub async fn get_vector_field_embeddings(
+    vector_fields: Vec<Option<HashMap<String, String>>>,
+    dataset_config: &DatasetConfiguration,
+    reqwest_client: reqwest::Client,
+) -> Result<Vec<HashMap<String, Vec<f32>>>, ServiceError> {
+    let mut field_embeddings = vec![HashMap::new(); vector_fields.len()];
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
-struct SpladeIndicies {
-    index: u32,
-    value: f32,
-}
-impl SpladeIndicies {
-    pub fn into_tuple(self) -> (u32, f32) {
-        (self.index, self.value)
+    if !dataset_config.SEMANTIC_ENABLED {
+        return Ok(field_embeddings);
+    }
+
+    for (slot, field) in dataset_config.VECTOR_FIELDS.iter().enumerate() {
+        let (chunk_indices, contents): (Vec<usize>, Vec<(String, Option<SemanticBoost>)>) =
+            vector_fields
+                .iter()
+                .enumerate()
+                .filter_map(|(i, fields)| {
+                    fields
+                        .as_ref()?
+                        .get(field)
+                        .filter(|content| !content.trim().is_empty())
+                        .map(|content| (i, (content.clone(), None)))
+                })
+                .unzip();
+
+        if contents.is_empty() {
+            continue;
+        }
+
+        let vectors = get_dense_vectors(
+            contents,
+            "doc",
+            dataset_config.clone(),
+            reqwest_client.clone(),
+        )
+        .await
+        .map_err(|err| {
+            ServiceError::InternalServerError(format!(
+                "Failed to create embeddings for vector field {}: {:?}",
+                field, err
+            ))
+        })?;
+
+        for (chunk_index, vector) in chunk_indices.into_iter().zip(vectors) {
+            field_embeddings[chunk_index].insert(get_vector_field_name(vector.len(), slot), vector);
+        }
     }
+
+    Ok(field_embeddings)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct CustomSparseEmbedData {
-    pub inputs: Vec<String>,
-    pub encode_type: String,
-    pub truncate: bool,
+pub struct SpladeEmbedding {
+    pub embeddings: Vec<(u32, f32)>,
 }
 
 #[tracing::instrument]
 pub async fn get_sparse_vectors(
     content_and_boosts: Vec<(String, Option<FullTextBoost>)>,
     embed_type: &str,
+    dataset_config: DatasetConfiguration,
     reqwest_client: reqwest::Client,
 ) -> Result<Vec<Vec<(u32, f32)>>, ServiceError> {
     if content_and_boosts.is_empty() {
@@ -615,6 +388,13 @@ pub async fn get_sparse_vectors(
         ));
     }
 
+    let provider = get_embedding_provider(
+        dataset_config.SPARSE_EMBEDDING_PROVIDER,
+        &dataset_config,
+        reqwest_client,
+    );
+    let provider = provider.as_ref();
+
     let contents = content_and_boosts
         .clone()
         .into_iter()
@@ -630,83 +410,18 @@ pub async fn get_sparse_vectors(
     let thirty_filtered_boosts_with_indices = filtered_boosts_with_index.chunks(30);
 
     let vec_boost_futures: Vec<_> = thirty_filtered_boosts_with_indices
-        .enumerate()
-        .map(|(i, thirty_boosts)| {
-            let cur_client = reqwest_client.clone();
-
-            let origin_key = match embed_type {
-                "doc" => "SPARSE_SERVER_DOC_ORIGIN",
-                "query" => "SPARSE_SERVER_QUERY_ORIGIN",
-                _ => unreachable!("Invalid embed_type passed"),
-            };
+        .map(|thirty_boosts| {
+            let clipped_messages = thirty_boosts
+                .iter()
+                .map(|(_, message)| message.phrase.chars().take(50000).collect())
+                .collect::<Vec<String>>();
 
             async move {
-                let server_origin = std::env::var(origin_key)
-                    .ok()
-                    .filter(|s| !s.is_empty())
-                    .ok_or(ServiceError::BadRequest(format!(
-                        "env flag {} is not set",
-                        origin_key
-                    )))?;
-                let embedding_server_call = format!("{}/embed_sparse", server_origin);
-
-                let clipped_messages = thirty_boosts
-                    .iter()
-                    .map(|(_, message)| message.phrase.chars().take(50000).collect())
-                    .collect::<Vec<String>>();
+                let sparse_vectors = provider
+                    .sparse_vectors(clipped_messages, embed_type)
+                    .await?;
 
-                let sparse_embed_req = CustomSparseEmbedData {
-                    inputs: clipped_messages,
-                    encode_type: embed_type.to_string(),
-                    truncate: true,
-                };
-
-                let embedding_response = cur_client
-                    .post(&embedding_server_call)
-                    .header("Content-Type", "application/json")
-                    .header(
-                        "Authorization",
-                        &format!(
-                            "Bearer {}",
-                            get_env!("OPENAI_API_KEY", "OPENAI_API should be set")
-                        ),
-                    )
-                    .json(&sparse_embed_req)
-                    .send()
-                    .await
-                    .map_err(|err| {
-                        log::error!(
-                            "Failed sending request from custom embedding server {:?}",
-                            err
-                        );
-                        ServiceError::InternalServerError(format!(
-                            "Failed making call to server {:?}",
-                            err
-                        ))
-                    })?
-                    .text()
-                    .await
-                    .map_err(|_| {
-                        ServiceError::InternalServerError(
-                            "Failed to get text from embeddings".to_string(),
-                        )
-                    })?;
-
-                let sparse_vectors = serde_json::from_str::<Vec<Vec<SpladeIndicies>>>(
-                    &embedding_response,
-                )
-                .map_err(|_e| {
-                    log::error!(
-                        "Failed parsing response from custom embedding server {:?}",
-                        embedding_response
-                    );
-                    ServiceError::InternalServerError(format!(
-                        "Failed parsing response from custom embedding server {:?}",
-                        embedding_response
-                    ))
-                })?;
-
-                let index_vector_boosts: Vec<(usize, f64, Vec<SpladeIndicies>)> = thirty_boosts
+                let index_vector_boosts: Vec<(usize, f64, Vec<(u32, f32)>)> = thirty_boosts
                     .iter()
                     .zip(sparse_vectors)
                     .map(|((og_index, y), sparse_vector)| {
@@ -714,166 +429,44 @@ pub async fn get_sparse_vectors(
                     })
                     .collect();
 
-                Ok((i, index_vector_boosts))
+                Ok::<_, ServiceError>(index_vector_boosts)
             }
         })
         .collect();
 
     let vec_content_futures: Vec<_> = thirty_content_groups
-        .enumerate()
-        .map(|(i, thirty_messages)| {
-            let cur_client = reqwest_client.clone();
-
-            let origin_key = match embed_type {
-                "doc" => "SPARSE_SERVER_DOC_ORIGIN",
-                "query" => "SPARSE_SERVER_QUERY_ORIGIN",
-                _ => unreachable!("Invalid embed_type passed"),
-            };
-
-            async move {
-                let server_origin = std::env::var(origin_key)
-                    .ok()
-                    .filter(|s| !s.is_empty())
-                    .ok_or(ServiceError::BadRequest(format!(
-                        "env flag {} is not set",
-                        origin_key
-                    )))?;
-                let embedding_server_call = format!("{}/embed_sparse", server_origin);
-
-                let clipped_messages = thirty_messages
-                    .iter()
-                    .map(|message| message.chars().take(50000).collect())
-                    .collect::<Vec<String>>();
-
-                let sparse_embed_req = CustomSparseEmbedData {
-                    inputs: clipped_messages,
-                    encode_type: embed_type.to_string(),
-                    truncate: true,
-                };
+        .map(|thirty_messages| {
+            let clipped_messages = thirty_messages
+                .iter()
+                .map(|message| message.chars().take(50000).collect())
+                .collect::<Vec<String>>();
 
-                let embedding_response = cur_client
-                    .post(&embedding_server_call)
-                    .header("Content-Type", "application/json")
-                    .header(
-                        "Authorization",
-                        &format!(
-                            "Bearer {}",
-                            get_env!("OPENAI_API_KEY", "OPENAI_API should be set")
-                        ),
-                    )
-                    .json(&sparse_embed_req)
-                    .send()
-                    .await
-                    .map_err(|err| {
-                        log::error!(
-                            "Failed sending request from custom embedding server {:?}",
-                            err
-                        );
-                        ServiceError::InternalServerError(format!(
-                            "Failed making call to server {:?}",
-                            err
-                        ))
-                    })?
-                    .text()
-                    .await
-                    .map_err(|_| {
-                        ServiceError::InternalServerError(
-                            "Failed to get text from embeddings".to_string(),
-                        )
-                    })?;
-
-                let sparse_vectors = serde_json::from_str::<Vec<Vec<SpladeIndicies>>>(
-                    &embedding_response,
-                )
-                .map_err(|_e| {
-                    log::error!(
-                        "Failed parsing response from custom embedding server {:?}",
-                        embedding_response
-                    );
-                    ServiceError::InternalServerError(format!(
-                        "Failed parsing response from custom embedding server {:?}",
-                        embedding_response
-                    ))
-                })?;
-
-                Ok((i, sparse_vectors))
-            }
+            provider.sparse_vectors(clipped_messages, embed_type)
         })
         .collect();
 
-    let all_content_vectors: Vec<(usize, Vec<Vec<SpladeIndicies>>)> =
-        futures::future::join_all(vec_content_futures)
-            .await
-            .into_iter()
-            .collect::<Result<Vec<(usize, Vec<Vec<SpladeIndicies>>)>, ServiceError>>()?;
-
-    let mut content_vectors_sorted = vec![];
-    for index in 0..all_content_vectors.len() {
-        let (_, vectors_i) = all_content_vectors
-            .iter()
-            .find(|(i, _)| *i == index)
-            .ok_or(ServiceError::InternalServerError(
-                "Failed to get index i (this should never happen)".to_string(),
-            ))?;
-
-        content_vectors_sorted.extend(vectors_i.clone());
-    }
+    let mut content_vectors: Vec<Vec<(u32, f32)>> = futures::future::join_all(vec_content_futures)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, ServiceError>>()?
+        .into_iter()
+        .flatten()
+        .collect();
 
-    #[allow(clippy::type_complexity)]
-    let all_boost_vectors: Vec<(usize, Vec<(usize, f64, Vec<SpladeIndicies>)>)> =
-        futures::future::join_all(vec_boost_futures)
-            .await
-            .into_iter()
-            .collect::<Result<Vec<(usize, Vec<(usize, f64, Vec<SpladeIndicies>)>)>, ServiceError>>(
-            )?;
+    let all_boost_vectors = futures::future::join_all(vec_boost_futures)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, ServiceError>>()?;
 
-    for (_, boost_vectors) in all_boost_vectors {
+    for boost_vectors in all_boost_vectors {
         for (og_index, boost_amt, boost_vector) in boost_vectors {
-            content_vectors_sorted[og_index] = content_vectors_sorted[og_index]
-                .iter()
-                .map(|splade_indice| {
-                    // Any is here because we multiply all of the matching indices by the boost amount and the boost amount is not unique to any index
-                    if boost_vector
-                        .iter()
-                        .any(|boost_splade_indice| boost_splade_indice.index == splade_indice.index)
-                    {
-                        SpladeIndicies {
-                            index: splade_indice.index,
-                            value: splade_indice.value * (boost_amt as f32),
-                        }
-                    } else {
-                        SpladeIndicies {
-                            index: splade_indice.index,
-                            value: splade_indice.value,
-                        }
-                    }
-                })
-                .collect();
+            let content_vector = std::mem::take(&mut content_vectors[og_index]);
+            content_vectors[og_index] =
+                boost_sparse_vector(content_vector, &boost_vector, boost_amt);
         }
     }
 
-    Ok(content_vectors_sorted
-        .iter()
-        .map(|sparse_vector| {
-            sparse_vector
-                .iter()
-                .map(|splade_idx| (*splade_idx).into_tuple())
-                .collect()
-        })
-        .collect())
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ScorePair {
-    index: usize,
-    score: f32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CrossEncoderData {
-    pub query: String,
-    pub texts: Vec<String>,
-    pub truncate: bool,
+    Ok(content_vectors)
 }
 
 #[tracing::instrument]
@@ -898,145 +491,55 @@ pub async fn cross_encoder(
     };
     sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone())));
 
-    let server_origin: String = dataset_config.RERANKER_BASE_URL.clone();
-
-    let embedding_server_call = format!("{}/rerank", server_origin);
-
     if results.is_empty() {
         return Ok(vec![]);
     }
 
+    let provider = get_embedding_provider(
+        dataset_config.RERANKER_PROVIDER,
+        dataset_config,
+        reqwest::Client::new(),
+    );
+    let provider = provider.as_ref();
+
     let mut results = results.clone();
 
-    if results.len() <= 20 {
-        let request_docs = results
-            .clone()
-            .into_iter()
-            .map(|x| {
-                let chunk = match x.metadata[0].clone() {
-                    ChunkMetadataTypes::Metadata(metadata) => Ok(metadata.clone()),
-                    _ => Err(ServiceError::BadRequest("Metadata not found".to_string())),
-                }?;
-
-                Ok(convert_html_to_text(
-                    &(chunk.chunk_html.unwrap_or_default()),
-                ))
-            })
-            .collect::<Result<Vec<String>, ServiceError>>()?;
-        let resp = ureq::post(&embedding_server_call)
-            .set("Content-Type", "application/json")
-            .set(
-                "Authorization",
-                &format!(
-                    "Bearer {}",
-                    get_env!("OPENAI_API_KEY", "OPENAI_API should be set")
-                ),
-            )
-            .send_json(CrossEncoderData {
-                query: query.clone(),
-                texts: request_docs,
-                truncate: true,
-            })
-            .map_err(|err| {
-                ServiceError::BadRequest(format!("Failed making call to server {:?}", err))
-            })?
-            .into_json::<Vec<ScorePair>>()
-            .map_err(|_e| {
-                log::error!(
-                    "Failed parsing response from custom embedding server {:?}",
-                    _e
-                );
-                ServiceError::BadRequest(
-                    "Failed parsing response from custom embedding server".to_string(),
-                )
-            })?;
-
-        resp.into_iter().for_each(|pair| {
-            results.index_mut(pair.index).score = pair.score as f64;
-        });
-    } else {
-        let vec_futures: Vec<_> = results
-            .chunks_mut(20)
-            .map(|docs_chunk| {
-                let query = query.clone();
-                let cur_client = reqwest::Client::new();
-                let embedding_api_key = get_env!("OPENAI_API_KEY", "OPENAI_API should be set");
-                let url = embedding_server_call.clone();
-
-                let vectors_resp = async move {
-                    let request_docs = docs_chunk
-                        .iter_mut()
-                        .map(|x| {
-                            let chunk = match x.metadata[0].clone() {
-                                ChunkMetadataTypes::Metadata(metadata) => Ok(metadata.clone()),
-                                _ => {
-                                    Err(ServiceError::BadRequest("Metadata not found".to_string()))
-                                }
-                            }?;
-
-                            Ok(convert_html_to_text(
-                                &(chunk.chunk_html.unwrap_or_default()),
-                            ))
-                        })
-                        .collect::<Result<Vec<String>, ServiceError>>()?;
-
-                    let parameters = CrossEncoderData {
-                        query: query.clone(),
-                        texts: request_docs,
-                        truncate: true,
-                    };
-
-                    let embeddings_resp = cur_client
-                        .post(&url)
-                        .header(
-                            "Authorization",
-                            &format!(
-                                "Bearer {}",
-                                get_env!("OPENAI_API_KEY", "OPENAI_API should be set")
-                            ),
-                        )
-                        .header("api-key", &embedding_api_key.to_string())
-                        .header("Content-Type", "application/json")
-                        .json(&parameters)
-                        .send()
-                        .await
-                        .map_err(|_| {
-                            ServiceError::BadRequest(
-                                "Failed to send message to embedding server".to_string(),
-                            )
-                        })?
-                        .text()
-                        .await
-                        .map_err(|_| {
-                            ServiceError::BadRequest(
-                                "Failed to get text from embeddings".to_string(),
-                            )
-                        })?;
-
-                    let embeddings: Vec<ScorePair> = serde_json::from_str(&embeddings_resp)
-                        .map_err(|e| {
-                            log::error!("Failed to format response from embeddings server {:?}", e);
-                            ServiceError::InternalServerError(
-                                "Failed to format response from embeddings server".to_owned(),
-                            )
-                        })?;
-
-                    embeddings.into_iter().for_each(|pair| {
-                        docs_chunk.index_mut(pair.index).score = pair.score as f64;
-                    });
-
-                    Ok(())
-                };
+    let vec_futures: Vec<_> = results
+        .chunks_mut(20)
+        .map(|docs_chunk| {
+            let query = query.clone();
 
-                vectors_resp
-            })
-            .collect();
+            async move {
+                let request_docs = docs_chunk
+                    .iter()
+                    .map(|x| {
+                        let chunk = match x.metadata[0].clone() {
+                            ChunkMetadataTypes::Metadata(metadata) => Ok(metadata.clone()),
+                            _ => Err(ServiceError::BadRequest("Metadata not found".to_string())),
+                        }?;
+
+                        Ok(convert_html_to_text(
+                            &(chunk.chunk_html.unwrap_or_default()),
+                        ))
+                    })
+                    .collect::<Result<Vec<String>, ServiceError>>()?;
 
-        futures::future::join_all(vec_futures)
-            .await
-            .into_iter()
-            .collect::<Result<(), ServiceError>>()?;
-    }
+                let scores = provider.rerank(query, request_docs).await?;
+
+                docs_chunk
+                    .iter_mut()
+                    .zip(scores)
+                    .for_each(|(doc, score)| doc.score = score as f64);
+
+                Ok(())
+            }
+        })
+        .collect();
+
+    futures::future::join_all(vec_futures)
+        .await
+        .into_iter()
+        .collect::<Result<(), ServiceError>>()?;
 
     results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
 
@@ -1051,35 +554,101 @@ pub fn get_bm25_embeddings(
     avg_len: f32,
     b: f32,
     k: f32,
+    language: DatasetLanguage,
 ) -> Vec<Vec<(u32, f32)>> {
-    term_frequency(tokenize_batch(chunks_and_boost), avg_len, b, k)
+    term_frequency(
+        tokenize_batch(chunks_and_boost, language),
+        avg_len,
+        b,
+        k,
+        language,
+    )
 }
 
-fn tokenize(text: String) -> Vec<String> {
-    let mut en_stem =
-        tantivy::tokenizer::TextAnalyzer::builder(tantivy::tokenizer::SimpleTokenizer::default())
-            .filter(tantivy::tokenizer::RemoveLongFilter::limit(40))
-            .filter(tantivy::tokenizer::LowerCaser)
-            .filter(tantivy::tokenizer::Stemmer::new(
-                tantivy::tokenizer::Language::English,
-            ))
-            .build();
+/// The BM25 analyzer of a language. Word based languages are lowercased, stripped of their stop words and stemmed while CJK text is lowercased and split into character unigrams and bigrams. English keeps its stop words such that the vectors of datasets created before LANGUAGE existed stay valid without a reindex.
+fn get_text_analyzer(language: DatasetLanguage) -> tantivy::tokenizer::TextAnalyzer {
+    use tantivy::tokenizer::{
+        LowerCaser, NgramTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter,
+        TextAnalyzer,
+    };
+
+    match language.tantivy_language() {
+        Some(tantivy_language) => {
+            let builder = TextAnalyzer::builder(SimpleTokenizer::default())
+                .filter(RemoveLongFilter::limit(40))
+                .filter(LowerCaser)
+                .dynamic();
+
+            let builder = match StopWordFilter::new(tantivy_language) {
+                Some(stop_word_filter) if language != DatasetLanguage::English => {
+                    builder.filter_dynamic(stop_word_filter)
+                }
+                _ => builder,
+            };
+
+            builder
+                .filter_dynamic(Stemmer::new(tantivy_language))
+                .build()
+        }
+        None => TextAnalyzer::builder(
+            NgramTokenizer::new(1, 2, false).expect("Unigram and bigram sizes are valid"),
+        )
+        .filter(LowerCaser)
+        .build(),
+    }
+}
+
+pub fn tokenize(text: String, language: DatasetLanguage) -> Vec<String> {
+    let mut analyzer = get_text_analyzer(language);
+
+    // N-grams are only taken within runs of letters and digits so that they never span whitespace or punctuation
+    let segments: Vec<&str> = if language.is_cjk() {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|segment| !segment.is_empty())
+            .collect()
+    } else {
+        vec![text.as_str()]
+    };
 
-    let mut stream = en_stem.token_stream(&text);
     let mut tokens: Vec<String> = vec![];
-    while stream.advance() {
-        tokens.push(stream.token().text.clone());
+    for segment in segments {
+        let mut stream = analyzer.token_stream(segment);
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
     }
 
     tokens
 }
 
+/// Whether the dataset's language treats the word as a stop word, CJK languages have none
+pub fn is_stop_word(word: &str, language: DatasetLanguage) -> bool {
+    use tantivy::tokenizer::{LowerCaser, SimpleTokenizer, StopWordFilter, TextAnalyzer};
+
+    let Some(stop_word_filter) = language.tantivy_language().and_then(StopWordFilter::new) else {
+        return false;
+    };
+
+    if !word.chars().any(|c| c.is_alphanumeric()) {
+        return false;
+    }
+
+    let mut analyzer = TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(LowerCaser)
+        .filter(stop_word_filter)
+        .build();
+
+    let mut stream = analyzer.token_stream(word);
+    !stream.advance()
+}
+
 pub fn tokenize_batch(
     chunks: Vec<(String, Option<FullTextBoost>)>,
+    language: DatasetLanguage,
 ) -> Vec<(Vec<String>, Option<FullTextBoost>)> {
     chunks
         .into_iter()
-        .map(|(chunk, boost)| (tokenize(chunk), boost))
+        .map(|(chunk, boost)| (tokenize(chunk, language), boost))
         .collect()
 }
 
@@ -1088,6 +657,7 @@ pub fn term_frequency(
     avg_len: f32,
     b: f32,
     k: f32,
+    language: DatasetLanguage,
 ) -> Vec<Vec<(u32, f32)>> {
     batched_tokens
         .iter()
@@ -1117,7 +687,7 @@ pub fn term_frequency(
             }
 
             if let Some(fulltext_boost) = fulltext_boost_option {
-                let tokenized_phrase = tokenize(fulltext_boost.phrase.clone());
+                let tokenized_phrase = tokenize(fulltext_boost.phrase.clone(), language);
                 for token in tokenized_phrase {
                     let token_id =
                         (murmur3_32(&mut Cursor::new(token), 0).unwrap() as i32).unsigned_abs();
@@ -1131,3 +701,56 @@ pub fn term_frequency(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_english_tokens_keep_stop_words() {
+        assert_eq!(
+            tokenize("The Running dogs".to_string(), DatasetLanguage::English),
+            vec!["the", "run", "dog"]
+        );
+    }
+
+    #[test]
+    fn test_german_tokens_are_stemmed_without_stop_words() {
+        let tokens = tokenize(
+            "Die Häuser und die Gärten".to_string(),
+            DatasetLanguage::German,
+        );
+
+        assert_eq!(tokens, vec!["haus", "gart"]);
+        assert_eq!(
+            tokenize("Haus".to_string(), DatasetLanguage::German),
+            tokenize("Häuser".to_string(), DatasetLanguage::German)
+        );
+    }
+
+    #[test]
+    fn test_cjk_tokens_are_unigrams_and_bigrams() {
+        assert_eq!(
+            tokenize("東京都".to_string(), DatasetLanguage::Japanese),
+            vec!["東", "東京", "京", "京都", "都"]
+        );
+    }
+
+    #[test]
+    fn test_cjk_bigrams_do_not_span_punctuation_or_whitespace() {
+        let tokens = tokenize("北京，上海 Ab".to_string(), DatasetLanguage::Chinese);
+
+        assert!(tokens.contains(&"北京".to_string()));
+        assert!(tokens.contains(&"上海".to_string()));
+        assert!(tokens.contains(&"ab".to_string()));
+        assert!(!tokens.contains(&"京上".to_string()));
+        assert!(!tokens.iter().any(|token| token.contains(' ')));
+    }
+
+    #[test]
+    fn test_stop_words_by_language() {
+        assert!(is_stop_word("und", DatasetLanguage::German));
+        assert!(!is_stop_word("haus", DatasetLanguage::German));
+        assert!(!is_stop_word("の", DatasetLanguage::Japanese));
+    }
+}
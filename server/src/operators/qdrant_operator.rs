@@ -16,15 +16,18 @@ use itertools::Itertools;
 use qdrant_client::{
     qdrant::{
         group_id::Kind, payload_index_params::IndexParams, point_id::PointIdOptions,
-        quantization_config::Quantization, query, BinaryQuantization, CreateCollectionBuilder,
-        CreateFieldIndexCollectionBuilder, DeleteFieldIndexCollectionBuilder, DeletePointsBuilder,
-        Distance, FieldType, Filter, GetPointsBuilder, HnswConfigDiff, OrderBy, PayloadIndexParams,
-        PointId, PointStruct, PrefetchQuery, QuantizationConfig, Query, QueryBatchPoints,
-        QueryPoints, RecommendPointGroups, RecommendPoints, RecommendStrategy, RetrievedPoint,
+        quantization_config::Quantization, query, vectors::VectorsOptions,
+        with_vectors_selector::SelectorOptions, BinaryQuantization, Condition,
+        CreateCollectionBuilder, CreateFieldIndexCollectionBuilder,
+        DeleteFieldIndexCollectionBuilder, DeletePointsBuilder, Distance, FieldType, Filter,
+        GetPointsBuilder, HnswConfigDiff, OrderBy, PayloadIndexParams, PointId, PointStruct,
+        PrefetchQuery, QuantizationConfig, Query, QueryBatchPoints, QueryPoints,
+        RecommendPointGroups, RecommendPoints, RecommendStrategy, RetrievedPoint,
         ScrollPointsBuilder, SearchBatchPoints, SearchParams, SearchPointGroups, SearchPoints,
         SetPayloadPointsBuilder, SparseIndexConfig, SparseVectorConfig, SparseVectorParams,
         TextIndexParams, TokenizerType, UpsertPointsBuilder, Value, Vector, VectorInput,
-        VectorParams, VectorParamsMap, VectorsConfig, WithPayloadSelector, WithVectorsSelector,
+        VectorParams, VectorParamsMap, VectorsConfig, VectorsSelector, WithPayloadSelector,
+        WithVectorsSelector,
     },
     Payload, Qdrant,
 };
@@ -52,7 +55,109 @@ pub async fn get_qdrant_connection(
         .map_err(|_err| ServiceError::BadRequest("Failed to connect to Qdrant".to_string()))
 }
 
+/// Stage of copying collections created without vector field slots into `{collection}_fields` collections, set with VECTOR_FIELDS_MIGRATION.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFieldsMigrationStage {
+    /// Collections already have the vector field slots, or have not started migrating
+    None,
+    /// queue-vector-fields-migration is copying points, writes go to both collections and reads to the old one
+    DualWrite,
+    /// Every point has been copied, reads and writes only go to the `{collection}_fields` collections
+    Migrated,
+}
+
+pub fn get_vector_fields_migration_stage() -> VectorFieldsMigrationStage {
+    match std::env::var("VECTOR_FIELDS_MIGRATION")
+        .unwrap_or_default()
+        .as_str()
+    {
+        "dual_write" => VectorFieldsMigrationStage::DualWrite,
+        "migrated" => VectorFieldsMigrationStage::Migrated,
+        _ => VectorFieldsMigrationStage::None,
+    }
+}
+
+pub fn get_vector_fields_collection_name(collection: &str) -> String {
+    format!("{}_fields", collection)
+}
+
 pub fn get_qdrant_collection_from_dataset_config(dataset_config: &DatasetConfiguration) -> String {
+    let collection = get_base_qdrant_collection(dataset_config);
+
+    match get_vector_fields_migration_stage() {
+        VectorFieldsMigrationStage::Migrated => get_vector_fields_collection_name(&collection),
+        _ => collection,
+    }
+}
+
+/// Collections every write to the points of `qdrant_collection` has to go to, the first one is the one which holds vector fields
+pub fn get_qdrant_write_collections(qdrant_collection: String) -> Vec<String> {
+    match get_vector_fields_migration_stage() {
+        VectorFieldsMigrationStage::DualWrite => vec![
+            get_vector_fields_collection_name(&qdrant_collection),
+            qdrant_collection,
+        ],
+        _ => vec![qdrant_collection],
+    }
+}
+
+/// Whether `qdrant_collection` is an old collection without vector field slots which is still written to during the migration
+fn lacks_vector_fields(qdrant_collection: &str) -> bool {
+    get_vector_fields_migration_stage() == VectorFieldsMigrationStage::DualWrite
+        && !qdrant_collection.ends_with("_fields")
+}
+
+/// Drops the vector field vectors of points written to a collection which has no slots for them
+fn without_vector_fields(mut point: PointStruct) -> PointStruct {
+    if let Some(VectorsOptions::Vectors(named_vectors)) = point
+        .vectors
+        .as_mut()
+        .and_then(|vectors| vectors.vectors_options.as_mut())
+    {
+        named_vectors
+            .vectors
+            .retain(|vector_name, _| !vector_name.contains("_field_"));
+    }
+
+    point
+}
+
+/// Whether the collection has the named vectors the `VECTOR_FIELDS` of a dataset are stored in
+#[tracing::instrument]
+pub async fn collection_has_vector_fields_query(
+    qdrant_collection: String,
+    embedding_size: usize,
+) -> Result<bool, ServiceError> {
+    let qdrant_client = get_qdrant_connection(
+        Some(get_env!("QDRANT_URL", "QDRANT_URL should be set")),
+        Some(get_env!("QDRANT_API_KEY", "QDRANT_API_KEY should be set")),
+    )
+    .await?;
+
+    let collection_info = qdrant_client
+        .collection_info(qdrant_collection)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get collection info from qdrant {:?}", err);
+            ServiceError::BadRequest("Failed to get collection info from qdrant".to_string())
+        })?;
+
+    let vectors_config = collection_info
+        .result
+        .and_then(|info| info.config)
+        .and_then(|config| config.params)
+        .and_then(|params| params.vectors_config)
+        .and_then(|vectors_config| vectors_config.config);
+
+    Ok(match vectors_config {
+        Some(qdrant_client::qdrant::vectors_config::Config::ParamsMap(params_map)) => params_map
+            .map
+            .contains_key(&get_vector_field_name(embedding_size, 0)),
+        _ => false,
+    })
+}
+
+fn get_base_qdrant_collection(dataset_config: &DatasetConfiguration) -> String {
     match dataset_config.DISTANCE_METRIC {
         DistanceMetric::Euclidean => {
             format!("{}_vectors_euclidian", dataset_config.EMBEDDING_SIZE)
@@ -69,6 +174,14 @@ pub fn get_qdrant_collection_from_dataset_config(dataset_config: &DatasetConfigu
     }
 }
 
+/// Number of named vector slots every collection reserves for the `VECTOR_FIELDS` of a dataset.
+pub const MAX_VECTOR_FIELDS: usize = 4;
+
+/// Name of the dense vector holding the embedding of the vector field in `slot`.
+pub fn get_vector_field_name(embedding_size: usize, slot: usize) -> String {
+    format!("{}_field_{}_vectors", embedding_size, slot)
+}
+
 /// Create Qdrant collection and indexes needed
 #[tracing::instrument(skip(qdrant_url, qdrant_api_key))]
 pub async fn create_new_qdrant_collection_query(
@@ -111,91 +224,15 @@ pub async fn create_new_qdrant_collection_query(
         match collection {
             true => log::info!("Avoided creating collection as it already exists"),
             false => {
-                let mut sparse_vector_config = HashMap::new();
-                sparse_vector_config.insert(
-                    "sparse_vectors".to_string(),
-                    SparseVectorParams {
-                        modifier: None,
-                        index: Some(SparseIndexConfig {
-                            on_disk: Some(false),
-                            ..Default::default()
-                        }),
-                    },
-                );
-
-                sparse_vector_config.insert(
-                    "bm25_vectors".to_string(),
-                    SparseVectorParams {
-                        modifier: Some(1),
-                        index: Some(SparseIndexConfig {
-                            on_disk: Some(false),
-                            ..Default::default()
-                        }),
-                    },
-                );
-
-                let quantization_config = if quantize {
-                    //TODO: make this scalar
-                    Some(QuantizationConfig {
-                        quantization: Some(Quantization::Binary(BinaryQuantization {
-                            always_ram: Some(true),
-                        })),
-                    })
-                } else {
-                    None
-                };
-
-                let on_disk = if quantize {
-                    //TODO: make this scalar
-                    Some(true)
-                } else {
-                    None
-                };
-
-                let vectors_hash_map = HashMap::from_iter(
-                    vec![(
-                        format!("{}_vectors", size).to_string(),
-                        VectorParams {
-                            size,
-                            distance: distance.into(),
-                            quantization_config,
-                            on_disk,
-                            ..Default::default()
-                        },
-                    )]
-                    .into_iter(),
-                );
-
-                qdrant_client
-                    .create_collection(
-                        CreateCollectionBuilder::new(collection_name.clone())
-                            .vectors_config(VectorsConfig {
-                                config: Some(
-                                    qdrant_client::qdrant::vectors_config::Config::ParamsMap(
-                                        VectorParamsMap {
-                                            map: vectors_hash_map,
-                                        },
-                                    ),
-                                ),
-                            })
-                            .sparse_vectors_config(SparseVectorConfig {
-                                map: sparse_vector_config,
-                            })
-                            .hnsw_config(HnswConfigDiff {
-                                payload_m: Some(16),
-                                m: Some(0),
-                                ..Default::default()
-                            })
-                            .write_consistency_factor(1)
-                            .replication_factor(replication_factor),
-                    )
-                    .await
-                    .map_err(|err| {
-                        if err.to_string().contains("already exists") {
-                            return ServiceError::BadRequest("Collection already exists".into());
-                        }
-                        ServiceError::BadRequest(err.to_string())
-                    })?;
+                create_qdrant_collection_query(
+                    &qdrant_client,
+                    collection_name.clone(),
+                    size,
+                    distance,
+                    quantize,
+                    replication_factor,
+                )
+                .await?
             }
         };
 
@@ -273,110 +310,372 @@ pub async fn create_new_qdrant_collection_query(
                 .map_err(|_| ServiceError::BadRequest("Failed to delete index".into()))?;
         }
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "link",
-                FieldType::Keyword,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+        create_qdrant_payload_indexes_query(&qdrant_client, collection_name).await?;
+    }
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "tag_set",
-                FieldType::Keyword,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    for size in accepted_vectors {
+        create_rag_answer_cache_collection_query(&qdrant_client, size, replication_factor).await?;
+    }
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "dataset_id",
-                FieldType::Keyword,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    Ok(())
+}
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "metadata",
-                FieldType::Keyword,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+/// Create the payload indexes used for filtering on a collection
+#[tracing::instrument(skip(qdrant_client))]
+pub async fn create_qdrant_payload_indexes_query(
+    qdrant_client: &Qdrant,
+    collection_name: String,
+) -> Result<(), ServiceError> {
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "link",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "time_stamp",
-                FieldType::Integer,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "tag_set",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "group_ids",
-                FieldType::Keyword,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "dataset_id",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "location",
-                FieldType::Geo,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "metadata",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
 
-        qdrant_client
-            .create_field_index(
-                CreateFieldIndexCollectionBuilder::new(
-                    collection_name.clone(),
-                    "content",
-                    FieldType::Text,
-                )
-                .field_index_params(PayloadIndexParams {
-                    index_params: Some(IndexParams::TextIndexParams(TextIndexParams {
-                        tokenizer: TokenizerType::Prefix as i32,
-                        min_token_len: Some(2),
-                        max_token_len: Some(10),
-                        lowercase: Some(true),
-                    })),
-                }),
-            )
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "time_stamp",
+            FieldType::Integer,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
-                collection_name.clone(),
-                "num_value",
-                FieldType::Float,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "group_ids",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
 
-        qdrant_client
-            .create_field_index(CreateFieldIndexCollectionBuilder::new(
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "location",
+            FieldType::Geo,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+
+    qdrant_client
+        .create_field_index(
+            CreateFieldIndexCollectionBuilder::new(
                 collection_name.clone(),
-                "group_tag_set",
-                FieldType::Keyword,
-            ))
-            .await
-            .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+                "content",
+                FieldType::Text,
+            )
+            .field_index_params(PayloadIndexParams {
+                index_params: Some(IndexParams::TextIndexParams(TextIndexParams {
+                    tokenizer: TokenizerType::Prefix as i32,
+                    min_token_len: Some(2),
+                    max_token_len: Some(10),
+                    lowercase: Some(true),
+                })),
+            }),
+        )
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "num_value",
+            FieldType::Float,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "group_tag_set",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+
+    Ok(())
+}
+
+/// Create a single collection with the dense, vector field and sparse vectors every dataset expects
+#[tracing::instrument(skip(qdrant_client))]
+pub async fn create_qdrant_collection_query(
+    qdrant_client: &Qdrant,
+    collection_name: String,
+    size: u64,
+    distance: Distance,
+    quantize: bool,
+    replication_factor: u32,
+) -> Result<(), ServiceError> {
+    let mut sparse_vector_config = HashMap::new();
+    sparse_vector_config.insert(
+        "sparse_vectors".to_string(),
+        SparseVectorParams {
+            modifier: None,
+            index: Some(SparseIndexConfig {
+                on_disk: Some(false),
+                ..Default::default()
+            }),
+        },
+    );
+
+    sparse_vector_config.insert(
+        "bm25_vectors".to_string(),
+        SparseVectorParams {
+            modifier: Some(1),
+            index: Some(SparseIndexConfig {
+                on_disk: Some(false),
+                ..Default::default()
+            }),
+        },
+    );
+
+    let quantization_config = if quantize {
+        //TODO: make this scalar
+        Some(QuantizationConfig {
+            quantization: Some(Quantization::Binary(BinaryQuantization {
+                always_ram: Some(true),
+            })),
+        })
+    } else {
+        None
+    };
+
+    let on_disk = if quantize {
+        //TODO: make this scalar
+        Some(true)
+    } else {
+        None
+    };
+
+    let vector_params = VectorParams {
+        size,
+        distance: distance.into(),
+        quantization_config,
+        on_disk,
+        ..Default::default()
+    };
+
+    let vectors_hash_map = HashMap::from_iter(
+        std::iter::once(format!("{}_vectors", size))
+            .chain((0..MAX_VECTOR_FIELDS).map(|slot| get_vector_field_name(size as usize, slot)))
+            .map(|vector_name| (vector_name, vector_params.clone())),
+    );
+
+    qdrant_client
+        .create_collection(
+            CreateCollectionBuilder::new(collection_name.clone())
+                .vectors_config(VectorsConfig {
+                    config: Some(qdrant_client::qdrant::vectors_config::Config::ParamsMap(
+                        VectorParamsMap {
+                            map: vectors_hash_map,
+                        },
+                    )),
+                })
+                .sparse_vectors_config(SparseVectorConfig {
+                    map: sparse_vector_config,
+                })
+                .hnsw_config(HnswConfigDiff {
+                    payload_m: Some(16),
+                    m: Some(0),
+                    ..Default::default()
+                })
+                .write_consistency_factor(1)
+                .replication_factor(replication_factor),
+        )
+        .await
+        .map_err(|err| {
+            if err.to_string().contains("already exists") {
+                return ServiceError::BadRequest("Collection already exists".into());
+            }
+            ServiceError::BadRequest(err.to_string())
+        })?;
+
+    Ok(())
+}
+
+pub fn get_rag_answer_cache_collection_name(size: usize) -> String {
+    format!("rag_answer_caches_{}", size)
+}
+
+/// Create the collection the question embeddings of cached RAG answers of one size are searched in, if it does not exist yet
+#[tracing::instrument(skip(qdrant_client))]
+pub async fn create_rag_answer_cache_collection_query(
+    qdrant_client: &Qdrant,
+    size: u64,
+    replication_factor: u32,
+) -> Result<(), ServiceError> {
+    let collection_name = get_rag_answer_cache_collection_name(size as usize);
+
+    let collection_exists = qdrant_client
+        .collection_exists(collection_name.clone())
+        .await
+        .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+
+    if collection_exists {
+        log::info!("Avoided creating collection as it already exists");
+        return Ok(());
     }
 
+    qdrant_client
+        .create_collection(
+            CreateCollectionBuilder::new(collection_name.clone())
+                .vectors_config(VectorsConfig {
+                    config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
+                        VectorParams {
+                            size,
+                            distance: Distance::Cosine.into(),
+                            ..Default::default()
+                        },
+                    )),
+                })
+                .write_consistency_factor(1)
+                .replication_factor(replication_factor),
+        )
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name.clone(),
+            "dataset_id",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+
+    qdrant_client
+        .create_field_index(CreateFieldIndexCollectionBuilder::new(
+            collection_name,
+            "filters_hash",
+            FieldType::Keyword,
+        ))
+        .await
+        .map_err(|_| ServiceError::BadRequest("Failed to create index".into()))?;
+
     Ok(())
 }
 
+#[tracing::instrument(skip(query_embedding))]
+pub async fn upsert_rag_answer_cache_point_query(
+    cached_answer_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    filters_hash: String,
+    query_embedding: Vec<f32>,
+) -> Result<(), ServiceError> {
+    let qdrant_collection = get_rag_answer_cache_collection_name(query_embedding.len());
+
+    let qdrant_client = get_qdrant_connection(
+        Some(get_env!("QDRANT_URL", "QDRANT_URL should be set")),
+        Some(get_env!("QDRANT_API_KEY", "QDRANT_API_KEY should be set")),
+    )
+    .await?;
+
+    let payload: Payload = serde_json::json!({
+        "dataset_id": dataset_id.to_string(),
+        "filters_hash": filters_hash,
+    })
+    .try_into()
+    .map_err(|_| ServiceError::BadRequest("Failed to build cached answer payload".to_string()))?;
+
+    let point = PointStruct::new(cached_answer_id.to_string(), query_embedding, payload);
+
+    qdrant_client
+        .upsert_points(UpsertPointsBuilder::new(qdrant_collection, vec![point]))
+        .await
+        .map_err(|err| {
+            log::error!("Failed inserting cached answer to qdrant {:?}", err);
+            ServiceError::BadRequest(format!(
+                "Failed inserting cached answer to qdrant {:?}",
+                err
+            ))
+        })?;
+
+    Ok(())
+}
+
+/// Ids of the cached answers for the dataset and filters whose question is at least `score_threshold` cosine similar to the query, closest first
+#[tracing::instrument(skip(query_embedding))]
+pub async fn search_rag_answer_cache_query(
+    dataset_id: uuid::Uuid,
+    filters_hash: String,
+    query_embedding: Vec<f32>,
+    score_threshold: f32,
+    limit: u64,
+) -> Result<Vec<uuid::Uuid>, ServiceError> {
+    let qdrant_collection = get_rag_answer_cache_collection_name(query_embedding.len());
+
+    let qdrant_client = get_qdrant_connection(
+        Some(get_env!("QDRANT_URL", "QDRANT_URL should be set")),
+        Some(get_env!("QDRANT_API_KEY", "QDRANT_API_KEY should be set")),
+    )
+    .await?;
+
+    let filter = Filter::must([
+        Condition::matches("dataset_id", dataset_id.to_string()),
+        Condition::matches("filters_hash", filters_hash),
+    ]);
+
+    let search_response = qdrant_client
+        .search_points(SearchPoints {
+            collection_name: qdrant_collection,
+            vector: query_embedding,
+            limit,
+            score_threshold: Some(score_threshold),
+            with_payload: Some(WithPayloadSelector::from(false)),
+            with_vectors: Some(WithVectorsSelector::from(false)),
+            filter: Some(filter),
+            timeout: Some(60),
+            ..Default::default()
+        })
+        .await
+        .map_err(|err| {
+            log::error!("Failed to search cached answers in qdrant {:?}", err);
+            ServiceError::BadRequest("Failed to search cached answers in qdrant".to_string())
+        })?;
+
+    Ok(search_response
+        .result
+        .into_iter()
+        .filter_map(|point| match point.id?.point_id_options? {
+            PointIdOptions::Uuid(id) => uuid::Uuid::parse_str(&id).ok(),
+            PointIdOptions::Num(_) => None,
+        })
+        .collect())
+}
+
 #[tracing::instrument(skip(points))]
 pub async fn bulk_upsert_qdrant_points_query(
     points: Vec<PointStruct>,
@@ -397,14 +696,22 @@ pub async fn bulk_upsert_qdrant_points_query(
     )
     .await?;
 
-    qdrant_client
-        .upsert_points(UpsertPointsBuilder::new(qdrant_collection, points))
-        .await
-        .map_err(|err| {
-            sentry::capture_message(&format!("Error {:?}", err), sentry::Level::Error);
-            log::error!("Failed inserting chunk to qdrant {:?}", err);
-            ServiceError::BadRequest(format!("Failed inserting chunk to qdrant {:?}", err))
-        })?;
+    for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+        let points = if lacks_vector_fields(&qdrant_collection) {
+            points.iter().cloned().map(without_vector_fields).collect()
+        } else {
+            points.clone()
+        };
+
+        qdrant_client
+            .upsert_points(UpsertPointsBuilder::new(qdrant_collection, points))
+            .await
+            .map_err(|err| {
+                sentry::capture_message(&format!("Error {:?}", err), sentry::Level::Error);
+                log::error!("Failed inserting chunk to qdrant {:?}", err);
+                ServiceError::BadRequest(format!("Failed inserting chunk to qdrant {:?}", err))
+            })?;
+    }
 
     Ok(())
 }
@@ -463,23 +770,30 @@ pub async fn create_new_qdrant_point_query(
     )
     .await?;
 
-    qdrant_client
-        .upsert_points(UpsertPointsBuilder::new(qdrant_collection, vec![point]))
-        .await
-        .map_err(|err| {
-            sentry::capture_message(&format!("Error {:?}", err), sentry::Level::Error);
-            log::error!("Failed inserting chunk to qdrant {:?}", err);
-            ServiceError::BadRequest(format!("Failed inserting chunk to qdrant {:?}", err))
-        })?;
+    for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+        qdrant_client
+            .upsert_points(UpsertPointsBuilder::new(
+                qdrant_collection,
+                vec![point.clone()],
+            ))
+            .await
+            .map_err(|err| {
+                sentry::capture_message(&format!("Error {:?}", err), sentry::Level::Error);
+                log::error!("Failed inserting chunk to qdrant {:?}", err);
+                ServiceError::BadRequest(format!("Failed inserting chunk to qdrant {:?}", err))
+            })?;
+    }
 
     Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
-#[tracing::instrument(skip(updated_vector, web_pool))]
+#[tracing::instrument(skip(updated_vector, field_vectors, web_pool))]
 pub async fn update_qdrant_point_query(
     metadata: ChunkMetadata,
     updated_vector: Option<Vec<f32>>,
+    field_vectors: HashMap<String, Vec<f32>>,
+    vector_fields: Option<HashMap<String, String>>,
     group_ids: Option<Vec<uuid::Uuid>>,
     dataset_id: uuid::Uuid,
     splade_vector: Vec<(u32, f32)>,
@@ -531,73 +845,136 @@ pub async fn update_qdrant_point_query(
             vec![]
         };
 
-        let chunk_tags: Vec<Option<String>> =
-            get_groups_from_group_ids_query(group_ids.clone(), web_pool.clone())
-                .await?
-                .iter()
-                .filter_map(|group| group.tag_set.clone())
-                .flatten()
-                .collect();
+        let chunk_tags: Vec<Option<String>> =
+            get_groups_from_group_ids_query(group_ids.clone(), web_pool.clone())
+                .await?
+                .iter()
+                .filter_map(|group| group.tag_set.clone())
+                .flatten()
+                .collect();
+
+        // Texts of the vector fields which are not being re-embedded are carried over
+        let mut chunk_vector_fields: HashMap<String, String> = current_point
+            .and_then(|point| point.payload.get("vector_fields").cloned())
+            .and_then(|value| serde_json::from_value(value.into()).ok())
+            .unwrap_or_default();
+        chunk_vector_fields.extend(vector_fields.unwrap_or_default());
+
+        QdrantPayload {
+            vector_fields: Some(chunk_vector_fields).filter(|fields| !fields.is_empty()),
+            ..QdrantPayload::new(
+                metadata.clone(),
+                group_ids.into(),
+                Some(dataset_id),
+                Some(chunk_tags),
+            )
+        }
+    };
+
+    let Some(updated_vector) = updated_vector else {
+        for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+            qdrant_client
+                .overwrite_payload(
+                    SetPayloadPointsBuilder::new(
+                        qdrant_collection,
+                        <QdrantPayload as std::convert::Into<Payload>>::into(payload.clone()),
+                    )
+                    .points_selector(qdrant_point_id.clone()),
+                )
+                .await
+                .map_err(|_err| {
+                    ServiceError::BadRequest("Failed updating chunk payload in qdrant".into())
+                })?;
+        }
+
+        return Ok(());
+    };
 
-        QdrantPayload::new(
-            metadata.clone(),
-            group_ids.into(),
-            Some(dataset_id),
-            Some(chunk_tags),
-        )
+    let vector_name = match updated_vector.len() {
+        384 => "384_vectors",
+        512 => "512_vectors",
+        768 => "768_vectors",
+        1024 => "1024_vectors",
+        3072 => "3072_vectors",
+        1536 => "1536_vectors",
+        _ => return Err(ServiceError::BadRequest("Invalid embedding vector size".into()).into()),
     };
 
-    if let Some(updated_vector) = updated_vector {
-        let vector_name = match updated_vector.len() {
-            384 => "384_vectors",
-            512 => "512_vectors",
-            768 => "768_vectors",
-            1024 => "1024_vectors",
-            3072 => "3072_vectors",
-            1536 => "1536_vectors",
-            _ => {
-                return Err(ServiceError::BadRequest("Invalid embedding vector size".into()).into())
+    for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+        let lacks_vector_fields = lacks_vector_fields(&qdrant_collection);
+
+        // Vector fields which are not being re-embedded have to be carried over, upserting replaces every vector of the point
+        let vector_field_names = if lacks_vector_fields {
+            vec![]
+        } else {
+            (0..dataset_config.VECTOR_FIELDS.len())
+                .map(|slot| get_vector_field_name(dataset_config.EMBEDDING_SIZE, slot))
+                .collect_vec()
+        };
+
+        let mut vector_payload: HashMap<String, Vector> = if vector_field_names.is_empty() {
+            HashMap::new()
+        } else {
+            let current_vectors = qdrant_client
+                .get_points(
+                    GetPointsBuilder::new(qdrant_collection.clone(), qdrant_point_id.clone())
+                        .with_payload(false)
+                        .with_vectors(WithVectorsSelector {
+                            selector_options: Some(SelectorOptions::Include(VectorsSelector {
+                                names: vector_field_names,
+                            })),
+                        })
+                        .build(),
+                )
+                .await
+                .map_err(|_err| {
+                    ServiceError::BadRequest("Failed to search_points from qdrant".into())
+                })?
+                .result
+                .into_iter()
+                .next()
+                .and_then(|point| point.vectors)
+                .and_then(|vectors| vectors.vectors_options);
+
+            match current_vectors {
+                Some(VectorsOptions::Vectors(named_vectors)) => named_vectors.vectors,
+                _ => HashMap::new(),
             }
         };
-        let mut vector_payload = HashMap::from([
-            (vector_name.to_string(), Vector::from(updated_vector)),
-            ("sparse_vectors".to_string(), Vector::from(splade_vector)),
-        ]);
 
-        if let Some(bm25_vector) = bm25_vector.clone() {
-            vector_payload.insert(
-                "bm25_vectors".to_string(),
-                Vector::from(bm25_vector.clone()),
+        if !lacks_vector_fields {
+            vector_payload.extend(
+                field_vectors
+                    .clone()
+                    .into_iter()
+                    .map(|(vector_name, vector)| (vector_name, Vector::from(vector))),
             );
         }
+        vector_payload.insert(
+            vector_name.to_string(),
+            Vector::from(updated_vector.clone()),
+        );
+        vector_payload.insert(
+            "sparse_vectors".to_string(),
+            Vector::from(splade_vector.clone()),
+        );
+
+        if let Some(bm25_vector) = bm25_vector.clone() {
+            vector_payload.insert("bm25_vectors".to_string(), Vector::from(bm25_vector));
+        }
 
         let point = PointStruct::new(
             metadata.qdrant_point_id.clone().to_string(),
             vector_payload,
-            payload,
+            payload.clone(),
         );
 
         qdrant_client
             .upsert_points(UpsertPointsBuilder::new(qdrant_collection, vec![point]))
             .await
             .map_err(|_err| ServiceError::BadRequest("Failed upserting chunk in qdrant".into()))?;
-
-        return Ok(());
     }
 
-    qdrant_client
-        .overwrite_payload(
-            SetPayloadPointsBuilder::new(
-                qdrant_collection,
-                <QdrantPayload as std::convert::Into<Payload>>::into(payload),
-            )
-            .points_selector(qdrant_point_id),
-        )
-        .await
-        .map_err(|_err| {
-            ServiceError::BadRequest("Failed updating chunk payload in qdrant".into())
-        })?;
-
     Ok(())
 }
 
@@ -615,64 +992,70 @@ pub async fn add_bookmark_to_qdrant_query(
     )
     .await?;
 
-    let qdrant_point_id: Vec<PointId> = vec![point_id.to_string().into()];
+    let read_collection = qdrant_collection.clone();
 
-    let current_point_vec = qdrant_client
-        .get_points(
-            GetPointsBuilder::new(qdrant_collection.clone(), qdrant_point_id.clone())
-                .with_payload(true)
-                .with_vectors(false)
-                .build(),
-        )
-        .await
-        .map_err(|_err| {
-            ServiceError::BadRequest("Failed to search_points from qdrant".to_string())
-        })?
-        .result;
+    for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+        let qdrant_point_id: Vec<PointId> = vec![point_id.to_string().into()];
 
-    let current_point = match current_point_vec.first() {
-        Some(point) => point,
-        None => {
-            return Err(ServiceError::BadRequest(
-                "Failed getting vec.first chunk from qdrant".to_string(),
-            ))
-        }
-    };
+        let current_point_vec = qdrant_client
+            .get_points(
+                GetPointsBuilder::new(qdrant_collection.clone(), qdrant_point_id.clone())
+                    .with_payload(true)
+                    .with_vectors(false)
+                    .build(),
+            )
+            .await
+            .map_err(|_err| {
+                ServiceError::BadRequest("Failed to search_points from qdrant".to_string())
+            })?
+            .result;
+
+        let current_point = match current_point_vec.first() {
+            Some(point) => point,
+            // Points which have not been copied yet get the new payload from the migration
+            None if qdrant_collection != read_collection => continue,
+            None => {
+                return Err(ServiceError::BadRequest(
+                    "Failed getting vec.first chunk from qdrant".to_string(),
+                ))
+            }
+        };
 
-    let group_ids = if current_point.payload.contains_key("group_ids") {
-        let mut group_ids_qdrant = current_point
-            .payload
-            .get("group_ids")
-            .unwrap_or(&Value::from(vec![] as Vec<&str>))
-            .iter_list()
-            .unwrap_or(Value::from(vec![] as Vec<&str>).iter_list().unwrap())
-            .map(|id| {
-                id.as_str()
-                    .unwrap_or(&"".to_owned())
-                    .parse::<uuid::Uuid>()
-                    .unwrap_or_default()
-            })
-            .collect::<Vec<uuid::Uuid>>();
-        group_ids_qdrant.append(&mut vec![group_id]);
-        group_ids_qdrant
-    } else {
-        vec![group_id]
-    };
+        let group_ids = if current_point.payload.contains_key("group_ids") {
+            let mut group_ids_qdrant = current_point
+                .payload
+                .get("group_ids")
+                .unwrap_or(&Value::from(vec![] as Vec<&str>))
+                .iter_list()
+                .unwrap_or(Value::from(vec![] as Vec<&str>).iter_list().unwrap())
+                .map(|id| {
+                    id.as_str()
+                        .unwrap_or(&"".to_owned())
+                        .parse::<uuid::Uuid>()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<uuid::Uuid>>();
+            group_ids_qdrant.append(&mut vec![group_id]);
+            group_ids_qdrant
+        } else {
+            vec![group_id]
+        };
 
-    let payload = QdrantPayload::new_from_point(current_point.clone(), Some(group_ids));
+        let payload = QdrantPayload::new_from_point(current_point.clone(), Some(group_ids));
 
-    qdrant_client
-        .overwrite_payload(
-            SetPayloadPointsBuilder::new(
-                qdrant_collection,
-                <QdrantPayload as std::convert::Into<Payload>>::into(payload),
+        qdrant_client
+            .overwrite_payload(
+                SetPayloadPointsBuilder::new(
+                    qdrant_collection,
+                    <QdrantPayload as std::convert::Into<Payload>>::into(payload),
+                )
+                .points_selector(qdrant_point_id),
             )
-            .points_selector(qdrant_point_id),
-        )
-        .await
-        .map_err(|_err| {
-            ServiceError::BadRequest("Failed updating chunk payload in qdrant".into())
-        })?;
+            .await
+            .map_err(|_err| {
+                ServiceError::BadRequest("Failed updating chunk payload in qdrant".into())
+            })?;
+    }
 
     Ok(())
 }
@@ -691,64 +1074,70 @@ pub async fn remove_bookmark_from_qdrant_query(
     )
     .await?;
 
-    let qdrant_point_id: Vec<PointId> = vec![point_id.to_string().into()];
+    let read_collection = qdrant_collection.clone();
 
-    let current_point_vec = qdrant_client
-        .get_points(
-            GetPointsBuilder::new(qdrant_collection.clone(), qdrant_point_id.clone())
-                .with_payload(true)
-                .with_vectors(false)
-                .build(),
-        )
-        .await
-        .map_err(|_err| {
-            ServiceError::BadRequest("Failed to search_points from qdrant".to_string())
-        })?
-        .result;
+    for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+        let qdrant_point_id: Vec<PointId> = vec![point_id.to_string().into()];
 
-    let current_point = match current_point_vec.first() {
-        Some(point) => point,
-        None => {
-            return Err(ServiceError::BadRequest(
-                "Failed getting vec.first chunk from qdrant".to_string(),
-            ))
-        }
-    };
+        let current_point_vec = qdrant_client
+            .get_points(
+                GetPointsBuilder::new(qdrant_collection.clone(), qdrant_point_id.clone())
+                    .with_payload(true)
+                    .with_vectors(false)
+                    .build(),
+            )
+            .await
+            .map_err(|_err| {
+                ServiceError::BadRequest("Failed to search_points from qdrant".to_string())
+            })?
+            .result;
+
+        let current_point = match current_point_vec.first() {
+            Some(point) => point,
+            // Points which have not been copied yet get the new payload from the migration
+            None if qdrant_collection != read_collection => continue,
+            None => {
+                return Err(ServiceError::BadRequest(
+                    "Failed getting vec.first chunk from qdrant".to_string(),
+                ))
+            }
+        };
 
-    let group_ids = if current_point.payload.contains_key("group_ids") {
-        let mut group_ids_qdrant = current_point
-            .payload
-            .get("group_ids")
-            .unwrap_or(&Value::from(vec![] as Vec<&str>))
-            .iter_list()
-            .unwrap()
-            .map(|id| {
-                id.as_str()
-                    .unwrap_or(&"".to_owned())
-                    .parse::<uuid::Uuid>()
-                    .unwrap_or_default()
-            })
-            .collect::<Vec<uuid::Uuid>>();
-        group_ids_qdrant.retain(|id| id != &group_id);
-        group_ids_qdrant
-    } else {
-        vec![]
-    };
+        let group_ids = if current_point.payload.contains_key("group_ids") {
+            let mut group_ids_qdrant = current_point
+                .payload
+                .get("group_ids")
+                .unwrap_or(&Value::from(vec![] as Vec<&str>))
+                .iter_list()
+                .unwrap()
+                .map(|id| {
+                    id.as_str()
+                        .unwrap_or(&"".to_owned())
+                        .parse::<uuid::Uuid>()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<uuid::Uuid>>();
+            group_ids_qdrant.retain(|id| id != &group_id);
+            group_ids_qdrant
+        } else {
+            vec![]
+        };
 
-    let payload = QdrantPayload::new_from_point(current_point.clone(), Some(group_ids));
+        let payload = QdrantPayload::new_from_point(current_point.clone(), Some(group_ids));
 
-    qdrant_client
-        .overwrite_payload(
-            SetPayloadPointsBuilder::new(
-                qdrant_collection,
-                <QdrantPayload as std::convert::Into<Payload>>::into(payload),
+        qdrant_client
+            .overwrite_payload(
+                SetPayloadPointsBuilder::new(
+                    qdrant_collection,
+                    <QdrantPayload as std::convert::Into<Payload>>::into(payload),
+                )
+                .points_selector(qdrant_point_id),
             )
-            .points_selector(qdrant_point_id),
-        )
-        .await
-        .map_err(|_err| {
-            ServiceError::BadRequest("Failed updating chunk payload in qdrant".to_string())
-        })?;
+            .await
+            .map_err(|_err| {
+                ServiceError::BadRequest("Failed updating chunk payload in qdrant".to_string())
+            })?;
+    }
 
     Ok(())
 }
@@ -764,6 +1153,42 @@ pub enum VectorType {
     SpladeSparse(Vec<(u32, f32)>),
     BM25Sparse(Vec<(u32, f32)>),
     Dense(Vec<f32>),
+    /// Dense vector compared against the vector field stored in the given slot
+    FieldDense(usize, Vec<f32>),
+}
+
+impl VectorType {
+    /// Name of the vector in the collection which this vector is scored against
+    pub fn vector_name(&self) -> Result<String, ServiceError> {
+        match self {
+            VectorType::SpladeSparse(_) => Ok("sparse_vectors".to_string()),
+            VectorType::BM25Sparse(_) => Ok("bm25_vectors".to_string()),
+            VectorType::Dense(embedding_vector) => match embedding_vector.len() {
+                384 | 512 | 768 | 1024 | 3072 | 1536 => {
+                    Ok(format!("{}_vectors", embedding_vector.len()))
+                }
+                _ => Err(ServiceError::BadRequest(
+                    "Invalid embedding vector size".to_string(),
+                )),
+            },
+            VectorType::FieldDense(slot, embedding_vector) => {
+                Ok(get_vector_field_name(embedding_vector.len(), *slot))
+            }
+        }
+    }
+
+    /// Name and values of a dense vector, sparse vectors are rejected
+    pub fn into_dense(self) -> Result<(String, Vec<f32>), ServiceError> {
+        let vector_name = self.vector_name()?;
+        match self {
+            VectorType::Dense(embedding_vector) | VectorType::FieldDense(_, embedding_vector) => {
+                Ok((vector_name, embedding_vector))
+            }
+            VectorType::SpladeSparse(_) | VectorType::BM25Sparse(_) => Err(
+                ServiceError::BadRequest("Expected a dense vector".to_string()),
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -796,26 +1221,11 @@ pub async fn search_over_groups_query(
     )
     .await?;
 
-    let vector_name = match vector {
-        VectorType::SpladeSparse(_) => "sparse_vectors",
-        VectorType::BM25Sparse(_) => "bm25_vectors",
-        VectorType::Dense(ref embedding_vector) => match embedding_vector.len() {
-            384 => "384_vectors",
-            512 => "512_vectors",
-            768 => "768_vectors",
-            1024 => "1024_vectors",
-            3072 => "3072_vectors",
-            1536 => "1536_vectors",
-            _ => {
-                return Err(ServiceError::BadRequest(
-                    "Invalid embedding vector size".to_string(),
-                ))
-            }
-        },
-    };
+    let vector_name = vector.vector_name()?;
 
     let search_point_groups = match vector {
-        VectorType::Dense(ref embedding_vector) => SearchPointGroups {
+        VectorType::Dense(ref embedding_vector)
+        | VectorType::FieldDense(_, ref embedding_vector) => SearchPointGroups {
             collection_name: qdrant_collection.to_string(),
             vector: embedding_vector.clone(),
             vector_name: Some(vector_name.to_string()),
@@ -922,38 +1332,27 @@ pub async fn search_over_groups_query(
     Ok((point_ids, count?))
 }
 
-fn get_qdrant_vector(query: QdrantSearchQuery) -> (String, VectorInput) {
+fn get_qdrant_vector(query: QdrantSearchQuery) -> Result<(String, VectorInput), ServiceError> {
     match query.vector {
         VectorType::SpladeSparse(vector) => {
             let indices = vector.iter().map(|(index, _)| *index).collect::<Vec<u32>>();
             let data = vector.iter().map(|(_, value)| *value).collect::<Vec<f32>>();
-            (
+            Ok((
                 "sparse_vectors".to_string(),
                 VectorInput::new_sparse(indices, data),
-            )
+            ))
         }
         VectorType::BM25Sparse(vector) => {
             let indices = vector.iter().map(|(index, _)| *index).collect::<Vec<u32>>();
             let data = vector.iter().map(|(_, value)| *value).collect::<Vec<f32>>();
-            (
+            Ok((
                 "bm25_vectors".to_string(),
                 VectorInput::new_sparse(indices, data),
-            )
+            ))
         }
-        VectorType::Dense(embedding_vector) => {
-            let vector_name = match embedding_vector.len() {
-                384 => "384_vectors",
-                512 => "512_vectors",
-                768 => "768_vectors",
-                1024 => "1024_vectors",
-                3072 => "3072_vectors",
-                1536 => "1536_vectors",
-                _ => "invalid",
-            };
-            (
-                vector_name.to_string(),
-                VectorInput::new_dense(embedding_vector),
-            )
+        VectorType::Dense(_) | VectorType::FieldDense(_, _) => {
+            let (vector_name, embedding_vector) = query.vector.into_dense()?;
+            Ok((vector_name, VectorInput::new_dense(embedding_vector)))
         }
     }
 }
@@ -961,11 +1360,11 @@ fn get_qdrant_vector(query: QdrantSearchQuery) -> (String, VectorInput) {
 fn get_prefetch_query(
     query: QdrantSearchQuery,
     dataset_config: DatasetConfiguration,
-) -> (Vec<PrefetchQuery>, (Option<String>, Query)) {
+) -> Result<(Vec<PrefetchQuery>, (Option<String>, Query)), ServiceError> {
     if let Some(ref rerank_query) = *query.rerank_by {
-        let (rerank_vector_name, rerank_vector) = get_qdrant_vector(rerank_query.clone());
-        let (name, vector) = get_qdrant_vector(query.clone());
-        (
+        let (rerank_vector_name, rerank_vector) = get_qdrant_vector(rerank_query.clone())?;
+        let (name, vector) = get_qdrant_vector(query.clone())?;
+        Ok((
             vec![PrefetchQuery {
                 query: Some(Query::new_nearest(vector)),
                 limit: Some(rerank_query.limit),
@@ -974,9 +1373,9 @@ fn get_prefetch_query(
                 ..Default::default()
             }],
             (Some(rerank_vector_name), Query::new_nearest(rerank_vector)),
-        )
+        ))
     } else if let Some(ref sort_by) = query.sort_by {
-        let (name, vector) = get_qdrant_vector(query.clone());
+        let (name, vector) = get_qdrant_vector(query.clone())?;
         let prefetch_amount = sort_by.prefetch_amount.unwrap_or(1000);
         let prefetch_amount = if prefetch_amount > dataset_config.MAX_LIMIT {
             dataset_config.MAX_LIMIT
@@ -984,7 +1383,7 @@ fn get_prefetch_query(
             prefetch_amount
         };
 
-        (
+        Ok((
             vec![PrefetchQuery {
                 query: Some(Query::new_nearest(vector)),
                 limit: Some(prefetch_amount),
@@ -1001,10 +1400,10 @@ fn get_prefetch_query(
                     ..Default::default()
                 }),
             ),
-        )
+        ))
     } else {
-        let (name, vector) = get_qdrant_vector(query.clone());
-        (vec![], (Some(name), Query::new_nearest(vector)))
+        let (name, vector) = get_qdrant_vector(query.clone())?;
+        Ok((vec![], (Some(name), Query::new_nearest(vector))))
     }
 }
 
@@ -1035,7 +1434,7 @@ pub async fn search_qdrant_query(
         .into_iter()
         .map(|query| {
             let (mut prefetch, (vector_name, qdrant_query)) =
-                get_prefetch_query(query.clone(), dataset_config.clone());
+                get_prefetch_query(query.clone(), dataset_config.clone())?;
 
             let offset = query.limit * page.saturating_sub(1);
             if let Some(prefetch) = prefetch.get_mut(0) {
@@ -1053,7 +1452,7 @@ pub async fn search_qdrant_query(
                 _ => query.score_threshold,
             };
 
-            QueryPoints {
+            Ok(QueryPoints {
                 collection_name: qdrant_collection.to_string(),
                 limit: Some(query.limit),
                 offset: Some(offset),
@@ -1071,9 +1470,9 @@ pub async fn search_qdrant_query(
                     ..Default::default()
                 }),
                 ..Default::default()
-            }
+            })
         })
-        .collect::<Vec<QueryPoints>>();
+        .collect::<Result<Vec<QueryPoints>, ServiceError>>()?;
 
     let batch_points = QueryBatchPoints {
         collection_name: qdrant_collection.to_string(),
@@ -1444,6 +1843,18 @@ pub async fn delete_points_from_qdrant(
     Ok(())
 }
 
+/// Deletes the points of a dataset from its collection and, while vector fields are being migrated, from the collection they are copied to
+pub async fn delete_dataset_points_from_qdrant(
+    point_ids: Vec<uuid::Uuid>,
+    qdrant_collection: String,
+) -> Result<(), ServiceError> {
+    for qdrant_collection in get_qdrant_write_collections(qdrant_collection) {
+        delete_points_from_qdrant(point_ids.clone(), qdrant_collection).await?;
+    }
+
+    Ok(())
+}
+
 pub async fn get_qdrant_collections() -> Result<Vec<String>, ServiceError> {
     let qdrant_client = get_qdrant_connection(
         Some(get_env!("QDRANT_URL", "QDRANT_URL should be set")),
@@ -1630,20 +2041,8 @@ pub async fn count_qdrant_group_query(
                     ..Default::default()
                 })
             }
-            VectorType::Dense(embedding_vector) => {
-                let vector_name = match embedding_vector.len() {
-                    384 => "384_vectors",
-                    512 => "512_vectors",
-                    768 => "768_vectors",
-                    1024 => "1024_vectors",
-                    3072 => "3072_vectors",
-                    1536 => "1536_vectors",
-                    _ => {
-                        return Err(ServiceError::BadRequest(
-                            "Invalid embedding vector size".to_string(),
-                        ))
-                    }
-                };
+            VectorType::Dense(_) | VectorType::FieldDense(_, _) => {
+                let (vector_name, embedding_vector) = query.vector.into_dense()?;
 
                 Ok(SearchPointGroups {
                     collection_name: qdrant_collection.to_string(),
@@ -1755,20 +2154,8 @@ pub async fn count_qdrant_query(
                     ..Default::default()
                 })
             }
-            VectorType::Dense(embedding_vector) => {
-                let vector_name = match embedding_vector.len() {
-                    384 => "384_vectors",
-                    512 => "512_vectors",
-                    768 => "768_vectors",
-                    1024 => "1024_vectors",
-                    3072 => "3072_vectors",
-                    1536 => "1536_vectors",
-                    _ => {
-                        return Err(ServiceError::BadRequest(
-                            "Invalid embedding vector size".to_string(),
-                        ))
-                    }
-                };
+            VectorType::Dense(_) | VectorType::FieldDense(_, _) => {
+                let (vector_name, embedding_vector) = query.vector.into_dense()?;
 
                 Ok(SearchPoints {
                     collection_name: qdrant_collection.to_string(),
@@ -1832,62 +2219,138 @@ pub async fn update_group_tag_sets_in_qdrant_query(
 
     let points: Vec<PointId> = point_ids.iter().map(|x| x.to_string().into()).collect();
 
-    let results = qdrant_client
-        .get_points(
-            GetPointsBuilder::new(collection_name.clone(), points.clone())
-                .with_payload(true)
-                .with_vectors(false)
-                .build(),
-        )
-        .await
-        .map_err(|e| {
-            log::info!("Failed to fetch points from qdrant {:?}", e);
-            ServiceError::BadRequest("Failed to fetch points from qdrant".to_string())
-        })?;
-    let qdrant_payloads: Vec<QdrantPayload> = results
-        .result
-        .iter()
-        .map(|x| x.clone().into())
-        .collect::<Vec<QdrantPayload>>();
-
-    for (point, payload) in points.iter().zip(qdrant_payloads) {
-        let mut payload_tags: Vec<Option<String>> = payload
-            .group_tag_set
-            .clone()
-            .unwrap_or_default()
+    for collection_name in get_qdrant_write_collections(collection_name) {
+        let results = qdrant_client
+            .get_points(
+                GetPointsBuilder::new(collection_name.clone(), points.clone())
+                    .with_payload(true)
+                    .with_vectors(false)
+                    .build(),
+            )
+            .await
+            .map_err(|e| {
+                log::info!("Failed to fetch points from qdrant {:?}", e);
+                ServiceError::BadRequest("Failed to fetch points from qdrant".to_string())
+            })?;
+        let qdrant_payloads: Vec<QdrantPayload> = results
+            .result
             .iter()
-            .filter(|tag| match tag {
-                Some(tag) => !prev_group_tag_set.contains(tag),
-                None => false,
-            })
-            .cloned()
-            .collect();
+            .map(|x| x.clone().into())
+            .collect::<Vec<QdrantPayload>>();
+
+        for (point, payload) in points.iter().zip(qdrant_payloads) {
+            let mut payload_tags: Vec<Option<String>> = payload
+                .group_tag_set
+                .clone()
+                .unwrap_or_default()
+                .iter()
+                .filter(|tag| match tag {
+                    Some(tag) => !prev_group_tag_set.contains(tag),
+                    None => false,
+                })
+                .cloned()
+                .collect();
 
-        let new_tags = new_group_tag_set.iter().map(|x| Some(x.clone()));
-        payload_tags.extend(new_tags);
-        payload_tags.dedup();
+            let new_tags = new_group_tag_set.iter().map(|x| Some(x.clone()));
+            payload_tags.extend(new_tags);
+            payload_tags.dedup();
 
-        let new_payload = QdrantPayload {
-            group_tag_set: Some(payload_tags.clone()),
-            ..payload
-        };
-        qdrant_client
-            .overwrite_payload(
-                SetPayloadPointsBuilder::new(
-                    collection_name.clone(),
-                    <QdrantPayload as std::convert::Into<Payload>>::into(new_payload),
+            let new_payload = QdrantPayload {
+                group_tag_set: Some(payload_tags.clone()),
+                ..payload
+            };
+            qdrant_client
+                .overwrite_payload(
+                    SetPayloadPointsBuilder::new(
+                        collection_name.clone(),
+                        <QdrantPayload as std::convert::Into<Payload>>::into(new_payload),
+                    )
+                    .points_selector(vec![point.clone()]),
                 )
-                .points_selector(vec![point.clone()]),
-            )
-            .await
-            .map_err(|_| {
-                ServiceError::BadRequest("Failed updating chunk payload in qdrant".into())
-            })?;
+                .await
+                .map_err(|_| {
+                    ServiceError::BadRequest("Failed updating chunk payload in qdrant".into())
+                })?;
+        }
     }
 
     Ok(())
 }
 
+/// Dense and sparse vectors of the given points, keyed by point id. Points without a vector of the dataset's embedding size get `None` for the dense vector.
+#[tracing::instrument(skip(point_ids))]
+pub async fn get_point_vectors_query(
+    point_ids: Vec<uuid::Uuid>,
+    dataset_config: DatasetConfiguration,
+) -> Result<HashMap<uuid::Uuid, (Option<Vec<f32>>, Option<Vec<(u32, f32)>>)>, ServiceError> {
+    if point_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let qdrant_collection = get_qdrant_collection_from_dataset_config(&dataset_config);
+    let dense_vector_name = format!("{}_vectors", dataset_config.EMBEDDING_SIZE);
+
+    let qdrant_client = get_qdrant_connection(
+        Some(get_env!("QDRANT_URL", "QDRANT_URL should be set")),
+        Some(get_env!("QDRANT_API_KEY", "QDRANT_API_KEY should be set")),
+    )
+    .await?;
+
+    let points = qdrant_client
+        .get_points(
+            GetPointsBuilder::new(
+                qdrant_collection,
+                point_ids
+                    .iter()
+                    .map(|point_id| point_id.to_string().into())
+                    .collect::<Vec<PointId>>(),
+            )
+            .with_payload(false)
+            .with_vectors(true)
+            .build(),
+        )
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get point vectors from qdrant {:?}", err);
+            ServiceError::BadRequest("Failed to get point vectors from qdrant".to_string())
+        })?
+        .result;
+
+    let point_vectors = points
+        .into_iter()
+        .filter_map(|point| {
+            let point_id = match point.id?.point_id_options? {
+                PointIdOptions::Uuid(id) => uuid::Uuid::parse_str(&id).ok()?,
+                PointIdOptions::Num(_) => return None,
+            };
+
+            let named_vectors = match point.vectors?.vectors_options? {
+                VectorsOptions::Vectors(named_vectors) => named_vectors.vectors,
+                VectorsOptions::Vector(_) => return None,
+            };
+
+            let dense_vector = named_vectors
+                .get(&dense_vector_name)
+                .map(|vector| vector.data.clone());
+
+            let sparse_vector = named_vectors.get("sparse_vectors").and_then(|vector| {
+                vector.indices.as_ref().map(|indices| {
+                    indices
+                        .data
+                        .iter()
+                        .copied()
+                        .zip(vector.data.iter().copied())
+                        .collect::<Vec<(u32, f32)>>()
+                })
+            });
+
+            Some((point_id, (dense_vector, sparse_vector)))
+        })
+        .collect();
+
+    Ok(point_vectors)
+}
+
 pub async fn scroll_dataset_points(
     limit: u64,
     offset: Option<uuid::Uuid>,
@@ -0,0 +1,396 @@
+use super::qdrant_operator::{
+    delete_points_from_qdrant, get_rag_answer_cache_collection_name, search_rag_answer_cache_query,
+    upsert_rag_answer_cache_point_query,
+};
+use crate::{
+    data::models::{DatasetConfiguration, Pool, RagAnswerCache},
+    errors::ServiceError,
+    handlers::message_handler::CreateMessageReqPayload,
+};
+use actix_web::web;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use itertools::Itertools;
+
+/// Hash of everything besides the question which changes the answer to a message: its retrieval parameters (including the search query used in place of the question), its LLM and context options, the model and the dataset's prompts. Answers are only reused for messages with the same ones.
+pub fn get_filters_hash(
+    create_message_req_payload: &CreateMessageReqPayload,
+    dataset_config: &DatasetConfiguration,
+) -> String {
+    // Options which only change how the answer is streamed are left out
+    let llm_options = create_message_req_payload
+        .llm_options
+        .as_ref()
+        .map(|llm_options| {
+            serde_json::json!({
+                "temperature": llm_options.temperature,
+                "frequency_penalty": llm_options.frequency_penalty,
+                "presence_penalty": llm_options.presence_penalty,
+                "max_tokens": llm_options.max_tokens,
+                "stop_tokens": llm_options.stop_tokens,
+                "system_prompt": llm_options.system_prompt,
+                "image_config": llm_options.image_config,
+            })
+        });
+
+    let answer_params = serde_json::json!({
+        "search_query": create_message_req_payload.search_query,
+        "concat_user_messages_query": create_message_req_payload.concat_user_messages_query,
+        "highlight_options": create_message_req_payload.highlight_options,
+        "filters": create_message_req_payload.filters,
+        "search_type": create_message_req_payload.search_type,
+        "use_group_search": create_message_req_payload.use_group_search,
+        "page_size": create_message_req_payload.page_size,
+        "score_threshold": create_message_req_payload.score_threshold,
+        "recency_bias": create_message_req_payload.recency_bias,
+        "llm_options": llm_options,
+        "context_options": create_message_req_payload.context_options,
+        "model": dataset_config.LLM_DEFAULT_MODEL,
+        "rag_prompt": dataset_config.RAG_PROMPT,
+        "system_prompt": dataset_config.SYSTEM_PROMPT,
+    });
+
+    blake3::hash(answer_params.to_string().as_bytes())
+        .to_hex()
+        .to_string()
+}
+
+/// Number of the closest cached questions fetched from Qdrant, the newest of them which has not expired is used
+const CACHED_ANSWER_CANDIDATES: u64 = 10;
+
+/// How long a cached answer is reused for, set with RAG_ANSWER_CACHE_TTL_SECONDS
+pub fn get_answer_cache_ttl() -> chrono::Duration {
+    let ttl_seconds = std::env::var("RAG_ANSWER_CACHE_TTL_SECONDS")
+        .ok()
+        .and_then(|ttl| ttl.parse::<i64>().ok())
+        .unwrap_or(604800);
+
+    chrono::Duration::seconds(ttl_seconds)
+}
+
+/// Number of cached answers kept per dataset before the oldest are evicted, set with RAG_ANSWER_CACHE_MAX_ENTRIES
+pub fn get_answer_cache_max_entries() -> i64 {
+    std::env::var("RAG_ANSWER_CACHE_MAX_ENTRIES")
+        .ok()
+        .and_then(|max_entries| max_entries.parse::<i64>().ok())
+        .unwrap_or(1000)
+}
+
+/// Closest cached answer within `distance_threshold` of the query embedding which has not expired, its hit count is bumped when one is found
+#[tracing::instrument(skip(query_embedding, pool))]
+pub async fn get_cached_answer_query(
+    dataset_id: uuid::Uuid,
+    filters_hash: String,
+    query_embedding: Vec<f32>,
+    distance_threshold: f64,
+    pool: web::Data<Pool>,
+) -> Result<Option<RagAnswerCache>, ServiceError> {
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
+
+    let closest_ids = search_rag_answer_cache_query(
+        dataset_id,
+        filters_hash,
+        query_embedding,
+        (1.0 - distance_threshold) as f32,
+        CACHED_ANSWER_CANDIDATES,
+    )
+    .await?;
+
+    if closest_ids.is_empty() {
+        return Ok(None);
+    }
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let cutoff = chrono::Utc::now().naive_local() - get_answer_cache_ttl();
+
+    let candidates = rag_answer_caches_columns::rag_answer_caches
+        .filter(rag_answer_caches_columns::id.eq_any(&closest_ids))
+        .filter(rag_answer_caches_columns::created_at.ge(cutoff))
+        .select(RagAnswerCache::as_select())
+        .load::<RagAnswerCache>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get cached answers {:?}", err);
+            ServiceError::BadRequest("Failed to get cached answers".to_string())
+        })?;
+
+    let Some(cached_answer) = closest_ids.iter().find_map(|id| {
+        candidates
+            .iter()
+            .find(|candidate| candidate.id == *id)
+            .cloned()
+    }) else {
+        return Ok(None);
+    };
+
+    diesel::update(
+        rag_answer_caches_columns::rag_answer_caches
+            .filter(rag_answer_caches_columns::id.eq(cached_answer.id)),
+    )
+    .set((
+        rag_answer_caches_columns::hit_count.eq(rag_answer_caches_columns::hit_count + 1),
+        rag_answer_caches_columns::last_hit_at.eq(Some(chrono::Utc::now().naive_local())),
+    ))
+    .execute(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to record cached answer hit {:?}", err);
+        ServiceError::BadRequest("Failed to record cached answer hit".to_string())
+    })?;
+
+    Ok(Some(cached_answer))
+}
+
+/// Stores the answer and its question's embedding, then evicts the dataset's expired answers and the oldest ones over the limit
+#[tracing::instrument(skip(cached_answer, query_embedding, pool))]
+pub async fn insert_cached_answer_query(
+    cached_answer: RagAnswerCache,
+    query_embedding: Vec<f32>,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
+
+    upsert_rag_answer_cache_point_query(
+        cached_answer.id,
+        cached_answer.dataset_id,
+        cached_answer.filters_hash.clone(),
+        query_embedding,
+    )
+    .await?;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::insert_into(rag_answer_caches_columns::rag_answer_caches)
+        .values(&cached_answer)
+        .execute(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to insert cached answer {:?}", err);
+            ServiceError::BadRequest("Failed to insert cached answer".to_string())
+        })?;
+
+    evict_cached_answers_query(cached_answer.dataset_id, pool).await
+}
+
+/// Deletes the dataset's cached answers which are past the TTL or beyond the newest `RAG_ANSWER_CACHE_MAX_ENTRIES`
+#[tracing::instrument(skip(pool))]
+pub async fn evict_cached_answers_query(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let cutoff = chrono::Utc::now().naive_local() - get_answer_cache_ttl();
+
+    let kept_ids = rag_answer_caches_columns::rag_answer_caches
+        .filter(rag_answer_caches_columns::dataset_id.eq(dataset_id))
+        .filter(rag_answer_caches_columns::created_at.ge(cutoff))
+        .order_by(rag_answer_caches_columns::created_at.desc())
+        .limit(get_answer_cache_max_entries())
+        .select(rag_answer_caches_columns::id);
+
+    let evicted: Vec<(uuid::Uuid, i32)> = diesel::delete(
+        rag_answer_caches_columns::rag_answer_caches
+            .filter(rag_answer_caches_columns::dataset_id.eq(dataset_id))
+            .filter(diesel::dsl::not(
+                rag_answer_caches_columns::id.eq_any(kept_ids),
+            )),
+    )
+    .returning((
+        rag_answer_caches_columns::id,
+        rag_answer_caches_columns::embedding_size,
+    ))
+    .get_results(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to evict cached answers {:?}", err);
+        ServiceError::BadRequest("Failed to evict cached answers".to_string())
+    })?;
+
+    delete_cached_answer_points(evicted).await;
+
+    Ok(())
+}
+
+/// Removes the question embeddings of deleted cached answers from Qdrant, a point left behind is ignored on lookup since it has no row
+async fn delete_cached_answer_points(deleted: Vec<(uuid::Uuid, i32)>) {
+    let ids_by_size = deleted
+        .into_iter()
+        .map(|(id, embedding_size)| (embedding_size, id))
+        .into_group_map();
+
+    for (embedding_size, ids) in ids_by_size {
+        if let Err(err) = delete_points_from_qdrant(
+            ids,
+            get_rag_answer_cache_collection_name(embedding_size as usize),
+        )
+        .await
+        {
+            log::error!("Failed to delete cached answers from qdrant {:?}", err);
+        }
+    }
+}
+
+/// Deletes the cached answers which cite any of the chunks
+#[tracing::instrument(skip(pool))]
+pub async fn invalidate_cached_answers_query(
+    chunk_ids: Vec<uuid::Uuid>,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::rag_answer_caches::dsl as rag_answer_caches_columns;
+
+    if chunk_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let invalidated: Vec<(uuid::Uuid, i32)> = diesel::delete(
+        rag_answer_caches_columns::rag_answer_caches.filter(
+            rag_answer_caches_columns::chunk_ids
+                .overlaps_with(chunk_ids.into_iter().map(Some).collect::<Vec<_>>()),
+        ),
+    )
+    .returning((
+        rag_answer_caches_columns::id,
+        rag_answer_caches_columns::embedding_size,
+    ))
+    .get_results(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to invalidate cached answers {:?}", err);
+        ServiceError::BadRequest("Failed to invalidate cached answers".to_string())
+    })?;
+
+    delete_cached_answer_points(invalidated).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::models::{DecayDuration, HighlightOptions, LLMOptions, RecencyBias};
+
+    fn get_create_message_req_payload() -> CreateMessageReqPayload {
+        CreateMessageReqPayload {
+            new_message_content: "What is the return policy?".to_string(),
+            topic_id: uuid::Uuid::nil(),
+            user_id: None,
+            highlight_options: None,
+            search_type: None,
+            use_group_search: None,
+            concat_user_messages_query: None,
+            search_query: None,
+            page_size: None,
+            filters: None,
+            score_threshold: None,
+            llm_options: None,
+            context_options: None,
+            recency_bias: None,
+            agent_options: None,
+        }
+    }
+
+    #[test]
+    fn test_filters_hash_changes_with_the_answer_parameters() {
+        let dataset_config = DatasetConfiguration::from_json(serde_json::json!({}));
+        let payload = get_create_message_req_payload();
+        let filters_hash = get_filters_hash(&payload, &dataset_config);
+
+        let mut with_system_prompt = get_create_message_req_payload();
+        with_system_prompt.llm_options = Some(LLMOptions {
+            system_prompt: Some("Answer like a pirate".to_string()),
+            ..Default::default()
+        });
+        assert_ne!(
+            get_filters_hash(&with_system_prompt, &dataset_config),
+            filters_hash
+        );
+
+        let mut with_recency_bias = get_create_message_req_payload();
+        with_recency_bias.recency_bias = Some(RecencyBias {
+            decay_function: None,
+            scale: DecayDuration(chrono::Duration::days(30)),
+            offset: None,
+            origin: None,
+            decay: None,
+        });
+        assert_ne!(
+            get_filters_hash(&with_recency_bias, &dataset_config),
+            filters_hash
+        );
+
+        let mut with_search_query = get_create_message_req_payload();
+        with_search_query.search_query = Some("return policy".to_string());
+        assert_ne!(
+            get_filters_hash(&with_search_query, &dataset_config),
+            filters_hash
+        );
+
+        let mut with_highlight_options = get_create_message_req_payload();
+        with_highlight_options.highlight_options = Some(HighlightOptions {
+            highlight_window: Some(10),
+            ..Default::default()
+        });
+        assert_ne!(
+            get_filters_hash(&with_highlight_options, &dataset_config),
+            filters_hash
+        );
+
+        let mut with_concat_user_messages = get_create_message_req_payload();
+        with_concat_user_messages.concat_user_messages_query = Some(true);
+        assert_ne!(
+            get_filters_hash(&with_concat_user_messages, &dataset_config),
+            filters_hash
+        );
+
+        let mut other_model_config = dataset_config.clone();
+        other_model_config.LLM_DEFAULT_MODEL = "another-model".to_string();
+        assert_ne!(
+            get_filters_hash(&payload, &other_model_config),
+            filters_hash
+        );
+
+        let mut other_prompt_config = dataset_config.clone();
+        other_prompt_config.RAG_PROMPT = "Answer in one sentence:".to_string();
+        assert_ne!(
+            get_filters_hash(&payload, &other_prompt_config),
+            filters_hash
+        );
+    }
+
+    #[test]
+    fn test_filters_hash_ignores_streaming_options() {
+        let dataset_config = DatasetConfiguration::from_json(serde_json::json!({}));
+
+        let mut streamed = get_create_message_req_payload();
+        streamed.llm_options = Some(LLMOptions {
+            stream_response: Some(true),
+            completion_first: Some(true),
+            ..Default::default()
+        });
+
+        let mut not_streamed = get_create_message_req_payload();
+        not_streamed.llm_options = Some(LLMOptions {
+            stream_response: Some(false),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            get_filters_hash(&streamed, &dataset_config),
+            get_filters_hash(&not_streamed, &dataset_config)
+        );
+    }
+}
@@ -0,0 +1,507 @@
+use crate::{
+    data::models::{
+        OrganizationScimToken, Pool, RedisPool, User, UserApiKey, UserOrganization, UserRole,
+    },
+    errors::ServiceError,
+    operators::{
+        sso_operator::get_verified_domain_for_email_query,
+        user_operator::{get_api_key_prefix, hash_function},
+    },
+};
+use actix_web::web;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::{
+    scoped_futures::ScopedFutureExt, AsyncConnection, AsyncPgConnection, RunQueryDsl,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use redis::AsyncCommands;
+
+pub fn generate_scim_token() -> String {
+    format!(
+        "tr-scim-{}",
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect::<String>()
+    )
+}
+
+/// Creates the SCIM token of the organization, replacing the previous one. Returns the token, which is only stored hashed, along with its record.
+#[tracing::instrument(skip(pool))]
+pub async fn create_scim_token_query(
+    organization_id: uuid::Uuid,
+    can_grant_owner: bool,
+    pool: web::Data<Pool>,
+) -> Result<(String, OrganizationScimToken), ServiceError> {
+    use crate::data::schema::organization_scim_tokens::dsl as organization_scim_tokens_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let token = generate_scim_token();
+    let scim_token = OrganizationScimToken::from_details(
+        organization_id,
+        hash_function(&token),
+        get_api_key_prefix(&token),
+        can_grant_owner,
+    );
+
+    let scim_token =
+        diesel::insert_into(organization_scim_tokens_columns::organization_scim_tokens)
+            .values(&scim_token)
+            .on_conflict(organization_scim_tokens_columns::organization_id)
+            .do_update()
+            .set((
+                organization_scim_tokens_columns::blake3_hash
+                    .eq(excluded(organization_scim_tokens_columns::blake3_hash)),
+                organization_scim_tokens_columns::token_prefix
+                    .eq(excluded(organization_scim_tokens_columns::token_prefix)),
+                organization_scim_tokens_columns::created_at
+                    .eq(excluded(organization_scim_tokens_columns::created_at)),
+                organization_scim_tokens_columns::last_used_at.eq(None::<chrono::NaiveDateTime>),
+                organization_scim_tokens_columns::can_grant_owner
+                    .eq(excluded(organization_scim_tokens_columns::can_grant_owner)),
+            ))
+            .get_result::<OrganizationScimToken>(&mut conn)
+            .await
+            .map_err(|e| {
+                log::error!("Error creating SCIM token: {:?}", e);
+                ServiceError::BadRequest("Error creating SCIM token".to_string())
+            })?;
+
+    Ok((token, scim_token))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_token_query(
+    organization_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Option<OrganizationScimToken>, ServiceError> {
+    use crate::data::schema::organization_scim_tokens::dsl as organization_scim_tokens_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    organization_scim_tokens_columns::organization_scim_tokens
+        .filter(organization_scim_tokens_columns::organization_id.eq(organization_id))
+        .select(OrganizationScimToken::as_select())
+        .first::<OrganizationScimToken>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_| ServiceError::BadRequest("Error loading SCIM token".to_string()))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn delete_scim_token_query(
+    organization_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::organization_scim_tokens::dsl as organization_scim_tokens_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::delete(
+        organization_scim_tokens_columns::organization_scim_tokens
+            .filter(organization_scim_tokens_columns::organization_id.eq(organization_id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|_| ServiceError::BadRequest("Error deleting SCIM token".to_string()))?;
+
+    Ok(())
+}
+
+/// Looks up the SCIM token of an organization which is not deleted by the token itself
+#[tracing::instrument(skip(token, pool))]
+pub async fn get_scim_token_by_value_query(
+    token: &str,
+    pool: web::Data<Pool>,
+) -> Result<Option<OrganizationScimToken>, ServiceError> {
+    use crate::data::schema::organization_scim_tokens::dsl as organization_scim_tokens_columns;
+    use crate::data::schema::organizations::dsl as organization_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    organization_scim_tokens_columns::organization_scim_tokens
+        .inner_join(organization_columns::organizations)
+        .filter(organization_scim_tokens_columns::blake3_hash.eq(hash_function(token)))
+        .filter(organization_columns::deleted.eq(0))
+        .select(OrganizationScimToken::as_select())
+        .first::<OrganizationScimToken>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_| ServiceError::BadRequest("Error loading SCIM token".to_string()))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn update_scim_token_last_used_query(
+    scim_token_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::organization_scim_tokens::dsl as organization_scim_tokens_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::update(
+        organization_scim_tokens_columns::organization_scim_tokens
+            .filter(organization_scim_tokens_columns::id.eq(scim_token_id)),
+    )
+    .set(organization_scim_tokens_columns::last_used_at.eq(chrono::Utc::now().naive_local()))
+    .execute(&mut conn)
+    .await
+    .map_err(|_| ServiceError::BadRequest("Error updating SCIM token".to_string()))?;
+
+    Ok(())
+}
+
+/// Page of the members of the organization ordered by when they joined, optionally only the one with the given email. Returns the members and the total number of matching members.
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_org_members_query(
+    organization_id: uuid::Uuid,
+    email: Option<String>,
+    offset: i64,
+    limit: i64,
+    pool: web::Data<Pool>,
+) -> Result<(Vec<(User, UserOrganization)>, i64), ServiceError> {
+    use crate::data::schema::user_organizations::dsl as user_organizations_columns;
+    use crate::data::schema::users::dsl as users_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let mut members_query = users_columns::users
+        .inner_join(user_organizations_columns::user_organizations)
+        .filter(user_organizations_columns::organization_id.eq(organization_id))
+        .into_boxed();
+    let mut count_query = users_columns::users
+        .inner_join(user_organizations_columns::user_organizations)
+        .filter(user_organizations_columns::organization_id.eq(organization_id))
+        .into_boxed();
+
+    if let Some(email) = email {
+        members_query = members_query.filter(users_columns::email.eq(email.clone()));
+        count_query = count_query.filter(users_columns::email.eq(email));
+    }
+
+    let members = members_query
+        .order((
+            user_organizations_columns::created_at.asc(),
+            user_organizations_columns::id.asc(),
+        ))
+        .offset(offset)
+        .limit(limit)
+        .select((User::as_select(), UserOrganization::as_select()))
+        .load::<(User, UserOrganization)>(&mut conn)
+        .await
+        .map_err(|_| ServiceError::BadRequest("Error loading organization members".to_string()))?;
+
+    let total = count_query
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(|_| ServiceError::BadRequest("Error counting organization members".to_string()))?;
+
+    Ok((members, total))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_org_member_query(
+    organization_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Option<(User, UserOrganization)>, ServiceError> {
+    use crate::data::schema::user_organizations::dsl as user_organizations_columns;
+    use crate::data::schema::users::dsl as users_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    users_columns::users
+        .inner_join(user_organizations_columns::user_organizations)
+        .filter(user_organizations_columns::organization_id.eq(organization_id))
+        .filter(users_columns::id.eq(user_id))
+        .select((User::as_select(), UserOrganization::as_select()))
+        .first::<(User, UserOrganization)>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_| ServiceError::BadRequest("Error loading organization member".to_string()))
+}
+
+/// User who is not a member of the organization but has an email at a domain verified by it, such that users deactivated over SCIM can be reactivated
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_former_member_query(
+    organization_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Option<User>, ServiceError> {
+    use crate::data::schema::users::dsl as users_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let user = users_columns::users
+        .filter(users_columns::id.eq(user_id))
+        .select(User::as_select())
+        .first::<User>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_| ServiceError::BadRequest("Error loading user".to_string()))?;
+
+    let Some(user) = user else {
+        return Ok(None);
+    };
+
+    let verified_by_org = get_verified_domain_for_email_query(&user.email, pool)
+        .await?
+        .is_some_and(|(domain, _)| domain.organization_id == organization_id);
+
+    Ok(verified_by_org.then_some(user))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_scim_org_members_with_role_query(
+    organization_id: uuid::Uuid,
+    role: UserRole,
+    pool: web::Data<Pool>,
+) -> Result<Vec<User>, ServiceError> {
+    use crate::data::schema::user_organizations::dsl as user_organizations_columns;
+    use crate::data::schema::users::dsl as users_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    users_columns::users
+        .inner_join(user_organizations_columns::user_organizations)
+        .filter(user_organizations_columns::organization_id.eq(organization_id))
+        .filter(user_organizations_columns::role.eq(Into::<i32>::into(role)))
+        .order(user_organizations_columns::created_at.asc())
+        .select(User::as_select())
+        .load::<User>(&mut conn)
+        .await
+        .map_err(|_| ServiceError::BadRequest("Error loading organization members".to_string()))
+}
+
+/// Changes made to a user and their membership of the organization by one SCIM request
+#[derive(Debug, Default)]
+pub struct ScimUserChanges {
+    /// Whether the user does not have an account yet and is created
+    pub create_user: bool,
+    /// New name of an existing user
+    pub name: Option<String>,
+    pub current_role: Option<UserRole>,
+    /// Role the user ends up with, none removes them from the organization
+    pub target_role: Option<UserRole>,
+}
+
+impl ScimUserChanges {
+    /// Whether the changes take the owner role away from the user when they are one of `owner_count` owners of the organization
+    pub fn removes_last_owner(&self, owner_count: usize) -> bool {
+        self.current_role == Some(UserRole::Owner)
+            && self.target_role != Some(UserRole::Owner)
+            && owner_count <= 1
+    }
+}
+
+/// Creates or renames the user, adds them to the organization, changes their role or removes them from it and revokes their api keys for it in one transaction. Returns the api keys which were changed by a removal.
+/// Changes which would leave the organization without an owner are rejected, the owners are locked so concurrent requests can not remove the last two at once.
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn apply_scim_user_changes_query(
+    user: &User,
+    organization_id: uuid::Uuid,
+    changes: &ScimUserChanges,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<RevokedApiKeys, ServiceError> {
+    use crate::data::schema::user_organizations::dsl as user_organizations_columns;
+    use crate::data::schema::users::dsl as users_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let revoked_api_keys = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                if changes.create_user {
+                    diesel::insert_into(users_columns::users)
+                        .values(user)
+                        .execute(conn)
+                        .await?;
+                } else if let Some(name) = &changes.name {
+                    diesel::update(users_columns::users.filter(users_columns::id.eq(user.id)))
+                        .set((
+                            users_columns::name.eq(name),
+                            users_columns::updated_at.eq(chrono::Utc::now().naive_local()),
+                        ))
+                        .execute(conn)
+                        .await?;
+                }
+
+                if changes.current_role == Some(UserRole::Owner) {
+                    let owner_ids = user_organizations_columns::user_organizations
+                        .filter(user_organizations_columns::organization_id.eq(organization_id))
+                        .filter(
+                            user_organizations_columns::role.eq(Into::<i32>::into(UserRole::Owner)),
+                        )
+                        .select(user_organizations_columns::user_id)
+                        .for_update()
+                        .load::<uuid::Uuid>(conn)
+                        .await?;
+
+                    if changes.removes_last_owner(owner_ids.len()) {
+                        return Err(diesel::result::Error::RollbackTransaction);
+                    }
+                }
+
+                let mut revoked_api_keys = RevokedApiKeys::default();
+
+                match (&changes.current_role, &changes.target_role) {
+                    (None, Some(target_role)) => {
+                        diesel::insert_into(user_organizations_columns::user_organizations)
+                            .values(UserOrganization::from_details(
+                                user.id,
+                                organization_id,
+                                target_role.clone(),
+                            ))
+                            .execute(conn)
+                            .await?;
+                    }
+                    (Some(current_role), Some(target_role)) if current_role != target_role => {
+                        diesel::update(
+                            user_organizations_columns::user_organizations
+                                .filter(user_organizations_columns::user_id.eq(user.id))
+                                .filter(
+                                    user_organizations_columns::organization_id.eq(organization_id),
+                                ),
+                        )
+                        .set(
+                            user_organizations_columns::role
+                                .eq(Into::<i32>::into(target_role.clone())),
+                        )
+                        .execute(conn)
+                        .await?;
+                    }
+                    (Some(_), None) => {
+                        diesel::delete(
+                            user_organizations_columns::user_organizations
+                                .filter(user_organizations_columns::user_id.eq(user.id))
+                                .filter(
+                                    user_organizations_columns::organization_id.eq(organization_id),
+                                ),
+                        )
+                        .execute(conn)
+                        .await?;
+
+                        revoked_api_keys =
+                            revoke_user_org_api_keys(user.id, organization_id, conn).await?;
+                    }
+                    _ => {}
+                }
+
+                Ok(revoked_api_keys)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|e| match e {
+            diesel::result::Error::RollbackTransaction => ServiceError::BadRequest(
+                "The organization must keep at least one owner".to_string(),
+            ),
+            e => {
+                log::error!("Error applying SCIM user changes: {:?}", e);
+                ServiceError::BadRequest("Error updating user".to_string())
+            }
+        })?;
+
+    let mut redis_conn = redis_pool.get().await.map_err(|_| {
+        ServiceError::InternalServerError("Failed to get redis connection".to_string())
+    })?;
+
+    redis_conn
+        .del::<_, ()>(user.id.to_string())
+        .await
+        .map_err(|_| {
+            ServiceError::InternalServerError("Failed to delete user from redis".to_string())
+        })?;
+
+    Ok(revoked_api_keys)
+}
+
+/// Api keys of a user which were changed because the user left an organization
+#[derive(Debug, Default)]
+pub struct RevokedApiKeys {
+    /// Keys which could only be used with the organization
+    pub deleted: Vec<UserApiKey>,
+    /// Keys which can still be used with other organizations as (before, after) the organization was removed from them
+    pub limited: Vec<(UserApiKey, UserApiKey)>,
+}
+
+/// Deletes the api keys of the user which are limited to only the organization and removes the organization from the keys limited to it and others. Keys which are not limited to any organization are kept, they lose access to the organization along with the membership.
+async fn revoke_user_org_api_keys(
+    user_id: uuid::Uuid,
+    organization_id: uuid::Uuid,
+    conn: &mut AsyncPgConnection,
+) -> Result<RevokedApiKeys, diesel::result::Error> {
+    use crate::data::schema::user_api_key::dsl as user_api_key_columns;
+
+    let organization_id = Some(organization_id.to_string());
+
+    let api_keys = user_api_key_columns::user_api_key
+        .filter(user_api_key_columns::user_id.eq(user_id))
+        .filter(user_api_key_columns::organization_ids.contains(vec![organization_id.clone()]))
+        .select(UserApiKey::as_select())
+        .for_update()
+        .load::<UserApiKey>(conn)
+        .await?;
+
+    let mut revoked_api_keys = RevokedApiKeys::default();
+
+    for api_key in api_keys {
+        let other_organization_ids = api_key
+            .organization_ids
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| id.is_some() && *id != organization_id)
+            .collect::<Vec<Option<String>>>();
+
+        if other_organization_ids.is_empty() {
+            diesel::delete(
+                user_api_key_columns::user_api_key.filter(user_api_key_columns::id.eq(api_key.id)),
+            )
+            .execute(conn)
+            .await?;
+
+            revoked_api_keys.deleted.push(api_key);
+        } else {
+            let limited_api_key = diesel::update(
+                user_api_key_columns::user_api_key.filter(user_api_key_columns::id.eq(api_key.id)),
+            )
+            .set((
+                user_api_key_columns::organization_ids.eq(other_organization_ids),
+                user_api_key_columns::updated_at.eq(chrono::Utc::now().naive_local()),
+            ))
+            .returning(UserApiKey::as_returning())
+            .get_result::<UserApiKey>(conn)
+            .await?;
+
+            revoked_api_keys.limited.push((api_key, limited_api_key));
+        }
+    }
+
+    Ok(revoked_api_keys)
+}
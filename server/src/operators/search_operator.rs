@@ -1,27 +1,38 @@
 use super::chunk_operator::{
     get_chunk_metadatas_and_collided_chunks_from_point_ids_query,
     get_content_chunk_from_point_ids_query, get_highlights, get_highlights_with_exact_match,
+    get_metadata_from_id_query, get_metadata_from_tracking_id_query,
     get_qdrant_ids_from_chunk_ids_query, get_slim_chunks_from_point_ids_query, HighlightStrategy,
 };
 use super::group_operator::{
     get_group_ids_from_tracking_ids_query, get_groups_from_group_ids_query,
 };
+use super::merchandising_operator::{
+    apply_merchandising_rules, apply_merchandising_rules_over_groups,
+    apply_merchandising_rules_within_group,
+};
 use super::model_operator::{
     cross_encoder, get_bm25_embeddings, get_dense_vector, get_sparse_vector,
 };
+use super::parse_operator::convert_html_to_text;
 use super::qdrant_operator::{
-    count_qdrant_query, search_over_groups_query, GroupSearchResults, QdrantSearchQuery, VectorType,
+    count_qdrant_query, get_point_vectors_query, get_vector_fields_migration_stage,
+    search_over_groups_query, GroupSearchResults, QdrantSearchQuery, VectorFieldsMigrationStage,
+    VectorType,
 };
+use super::synonym_operator::get_synonym_map;
 use super::typo_operator::correct_query;
 use crate::data::models::{
     convert_to_date_time, ChunkGroup, ChunkGroupAndFileId, ChunkMetadata, ChunkMetadataTypes,
-    ConditionType, ContentChunkMetadata, Dataset, DatasetConfiguration, GeoInfoWithBias,
-    HasIDCondition, QdrantSortBy, QueryTypes, ReRankOptions, RedisPool, ScoreChunk, ScoreChunkDTO,
-    SearchMethod, SlimChunkMetadata, SortByField, SortBySearchType, SortOrder, UnifiedId,
+    ConditionType, ContentChunkMetadata, Dataset, DatasetConfiguration, DatasetLanguage,
+    FacetResults, GeoInfoWithBias, HasIDCondition, HybridFusionStrategy, HybridLeg,
+    HybridSearchOptions, QdrantSortBy, QueryTypes, ReRankOptions, RecencyBias, RedisPool,
+    ScoreChunk, ScoreChunkDTO, SearchMethod, SlimChunkMetadata, SortByField, SortBySearchType,
+    SortOrder, SparseVector, UnifiedId,
 };
 use crate::handlers::chunk_handler::{
     AutocompleteReqPayload, ChunkFilter, CountChunkQueryResponseBody, CountChunksReqPayload,
-    ParsedQuery, ParsedQueryTypes, ScoringOptions, SearchChunkQueryResponseBody,
+    ParsedQuery, ParsedQueryTypes, QueryVectors, ScoringOptions, SearchChunkQueryResponseBody,
     SearchChunksReqPayload,
 };
 use crate::handlers::group_handler::{
@@ -278,6 +289,20 @@ pub async fn assemble_qdrant_filter(
     Ok(filter)
 }
 
+/// Keeps the chunk a chunk query was made with out of its own results
+fn exclude_query_point(filter: &mut Filter, parsed_query: Option<&ParsedQuery>) {
+    if let Some(point_id) = parsed_query
+        .and_then(|query| query.vectors.as_ref())
+        .and_then(|vectors| vectors.qdrant_point_id)
+    {
+        filter.must_not.push(Condition {
+            condition_one_of: Some(HasId(HasIdCondition {
+                has_id: vec![point_id.to_string().into()],
+            })),
+        });
+    }
+}
+
 #[derive(Debug)]
 pub struct RetrievePointQuery {
     vector: VectorType,
@@ -325,6 +350,8 @@ impl RetrievePointQuery {
                 .push(Condition::matches("group_ids", group_id.to_string()));
         }
 
+        exclude_query_point(&mut filter, parsed_query.as_ref());
+
         let rerank_query = if let Some(parsed_query) = parsed_query {
             if let Some(rerank_by) = self.rerank_by {
                 match rerank_by.rerank_type {
@@ -481,6 +508,376 @@ pub async fn retrieve_qdrant_points_query(
     })
 }
 
+/// Records which hybrid legs returned each point so the response can report where a result came from.
+pub fn get_hybrid_legs(
+    semantic_results: &[SearchResult],
+    fulltext_results: &[SearchResult],
+) -> HashMap<uuid::Uuid, Vec<HybridLeg>> {
+    let mut legs: HashMap<uuid::Uuid, Vec<HybridLeg>> = HashMap::new();
+
+    for result in semantic_results {
+        let point_legs = legs.entry(result.point_id).or_default();
+        if !point_legs.contains(&HybridLeg::Semantic) {
+            point_legs.push(HybridLeg::Semantic);
+        }
+    }
+
+    for result in fulltext_results {
+        let point_legs = legs.entry(result.point_id).or_default();
+        if !point_legs.contains(&HybridLeg::Fulltext) {
+            point_legs.push(HybridLeg::Fulltext);
+        }
+    }
+
+    legs
+}
+
+/// Weights of the "rrf" and "linear" fusion strategies must not be negative and at least one of them must be positive
+pub fn check_hybrid_search_options(options: &HybridSearchOptions) -> Result<(), ServiceError> {
+    if options.fusion_strategy.clone().unwrap_or_default() == HybridFusionStrategy::CrossEncoder {
+        return Ok(());
+    }
+
+    let semantic_weight = options.semantic_weight.unwrap_or(1.0);
+    let fulltext_weight = options.fulltext_weight.unwrap_or(1.0);
+
+    for (name, weight) in [
+        ("semantic_weight", semantic_weight),
+        ("fulltext_weight", fulltext_weight),
+    ] {
+        if !(weight.is_finite() && weight >= 0.0) {
+            return Err(ServiceError::BadRequest(format!(
+                "hybrid_options.{} must be a finite number which is not negative",
+                name
+            )));
+        }
+    }
+
+    if semantic_weight == 0.0 && fulltext_weight == 0.0 {
+        return Err(ServiceError::BadRequest(
+            "hybrid_options.semantic_weight and hybrid_options.fulltext_weight cannot both be 0"
+                .to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Merges the semantic and fulltext result sets using reciprocal rank fusion or a weighted sum of min-max normalized scores. Results are returned sorted by their fused score. Fused scores are scaled to the 0 to 1 range so a score_threshold means the same for both strategies. The cross encoder strategy is not handled here as it needs the chunk content.
+pub fn fuse_hybrid_results(
+    semantic_results: &[SearchResult],
+    fulltext_results: &[SearchResult],
+    options: &HybridSearchOptions,
+) -> Vec<SearchResult> {
+    let semantic_weight = options.semantic_weight.unwrap_or(1.0);
+    let fulltext_weight = options.fulltext_weight.unwrap_or(1.0);
+    let total_weight = semantic_weight + fulltext_weight;
+    let total_weight = if total_weight == 0.0 {
+        1.0
+    } else {
+        total_weight
+    };
+
+    // Scores are kept in first seen order such that ties keep a stable ranking, with their index looked up by point id
+    let mut fused_scores: Vec<(uuid::Uuid, f32)> = vec![];
+    let mut fused_score_indices: HashMap<uuid::Uuid, usize> = HashMap::new();
+    let mut add_score = |point_id: uuid::Uuid, score: f32| match fused_score_indices.get(&point_id)
+    {
+        Some(&index) => fused_scores[index].1 += score,
+        None => {
+            fused_score_indices.insert(point_id, fused_scores.len());
+            fused_scores.push((point_id, score));
+        }
+    };
+
+    match options.fusion_strategy.clone().unwrap_or_default() {
+        HybridFusionStrategy::Linear => {
+            for (results, weight) in [
+                (semantic_results, semantic_weight),
+                (fulltext_results, fulltext_weight),
+            ] {
+                let (min_score, max_score) = results
+                    .iter()
+                    .fold((f32::MAX, f32::MIN), |(min, max), result| {
+                        (min.min(result.score), max.max(result.score))
+                    });
+
+                for result in results.iter().unique_by(|result| result.point_id) {
+                    let normalized_score = if max_score > min_score {
+                        (result.score - min_score) / (max_score - min_score)
+                    } else {
+                        1.0
+                    };
+                    add_score(result.point_id, weight * normalized_score / total_weight);
+                }
+            }
+        }
+        _ => {
+            let rrf_k = options.rrf_k.unwrap_or(60.0);
+            // Dividing by the score of a point ranked first by both legs maps the fused score to 0 to 1.
+            let max_rrf_score = total_weight / (rrf_k + 1.0);
+
+            for (results, weight) in [
+                (semantic_results, semantic_weight),
+                (fulltext_results, fulltext_weight),
+            ] {
+                for (rank, result) in results
+                    .iter()
+                    .unique_by(|result| result.point_id)
+                    .enumerate()
+                {
+                    add_score(
+                        result.point_id,
+                        weight / (rrf_k + rank as f32 + 1.0) / max_rrf_score,
+                    );
+                }
+            }
+        }
+    }
+
+    fused_scores
+        .into_iter()
+        .map(|(point_id, score)| SearchResult { score, point_id })
+        .sorted_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .collect()
+}
+
+/// Number of leading results each leg of a fused hybrid search has to return such that the requested page can be cut out of the fused ranking.
+pub fn get_hybrid_fusion_window(prefetch: u64, page: u64, page_size: u64) -> u64 {
+    prefetch.max(page.max(1) * page_size)
+}
+
+/// Cuts the requested page out of a fused ranking.
+pub fn page_fused_results<T>(fused_results: Vec<T>, page: u64, page_size: u64) -> Vec<T> {
+    fused_results
+        .into_iter()
+        .skip((page.max(1) - 1) as usize * page_size as usize)
+        .take(page_size as usize)
+        .collect()
+}
+
+/// Runs the semantic and fulltext legs of a hybrid search as separate batches so the ranking of each leg is preserved, then merges them according to the requested fusion strategy. For "rrf" and "linear" both legs are retrieved from their first result and the page is cut out after fusing, since fusing the n-th page of each leg does not give the n-th page of the fused ranking. For the cross encoder strategy the union of both legs is returned unscored so it can be reranked afterwards.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument]
+pub async fn retrieve_hybrid_qdrant_points_query(
+    mut semantic_query: QdrantSearchQuery,
+    mut fulltext_query: QdrantSearchQuery,
+    hybrid_options: &HybridSearchOptions,
+    page: u64,
+    page_size: u64,
+    get_total_pages: bool,
+    config: &DatasetConfiguration,
+) -> Result<(SearchChunkQueryResult, HashMap<uuid::Uuid, Vec<HybridLeg>>), ServiceError> {
+    if hybrid_options.fusion_strategy.clone().unwrap_or_default()
+        == HybridFusionStrategy::CrossEncoder
+    {
+        let semantic_future =
+            retrieve_qdrant_points_query(vec![semantic_query], page, get_total_pages, config);
+        let fulltext_future =
+            retrieve_qdrant_points_query(vec![fulltext_query], page, false, config);
+
+        let (semantic_results, fulltext_results) =
+            futures::try_join!(semantic_future, fulltext_future)?;
+
+        let hybrid_legs = get_hybrid_legs(
+            &semantic_results.search_results,
+            &fulltext_results.search_results,
+        );
+
+        return Ok((
+            SearchChunkQueryResult {
+                search_results: semantic_results
+                    .search_results
+                    .iter()
+                    .chain(fulltext_results.search_results.iter())
+                    .unique_by(|result| result.point_id)
+                    .cloned()
+                    .collect(),
+                total_chunk_pages: semantic_results.total_chunk_pages,
+                batch_lengths: vec![
+                    semantic_results.search_results.len(),
+                    fulltext_results.search_results.len(),
+                ],
+            },
+            hybrid_legs,
+        ));
+    }
+
+    semantic_query.limit = get_hybrid_fusion_window(semantic_query.limit, page, page_size);
+    fulltext_query.limit = get_hybrid_fusion_window(fulltext_query.limit, page, page_size);
+
+    let semantic_future =
+        search_qdrant_query(1, vec![semantic_query], config.clone(), get_total_pages);
+    let fulltext_future = search_qdrant_query(1, vec![fulltext_query], config.clone(), false);
+
+    let ((semantic_results, count, _), (fulltext_results, _, _)) =
+        futures::try_join!(semantic_future, fulltext_future)?;
+
+    let hybrid_legs = get_hybrid_legs(&semantic_results, &fulltext_results);
+
+    let search_results = page_fused_results(
+        fuse_hybrid_results(&semantic_results, &fulltext_results, hybrid_options),
+        page,
+        page_size,
+    );
+
+    Ok((
+        SearchChunkQueryResult {
+            search_results,
+            total_chunk_pages: (count as f64 / page_size.max(1) as f64).ceil() as i64,
+            batch_lengths: vec![semantic_results.len(), fulltext_results.len()],
+        },
+        hybrid_legs,
+    ))
+}
+
+/// One query per requested vector field, each comparing the query's dense vector against the field's vector, paired with the weight of the field.
+fn get_vector_field_queries(
+    qdrant_query: &QdrantSearchQuery,
+    vector_fields: &HashMap<String, f32>,
+    config: &DatasetConfiguration,
+) -> Result<Vec<(QdrantSearchQuery, f32)>, ServiceError> {
+    let embedding_vector = match &qdrant_query.vector {
+        VectorType::Dense(embedding_vector) => embedding_vector.clone(),
+        _ => {
+            return Err(ServiceError::BadRequest(
+                "vector_fields can only be used with semantic search".to_string(),
+            ))
+        }
+    };
+
+    vector_fields
+        .iter()
+        .map(|(field, weight)| {
+            if !weight.is_finite() {
+                return Err(ServiceError::BadRequest(format!(
+                    "Weight of vector field {} must be a finite number",
+                    field
+                )));
+            }
+
+            let vector = if field == "chunk_html" {
+                VectorType::Dense(embedding_vector.clone())
+            } else {
+                let slot = config
+                    .VECTOR_FIELDS
+                    .iter()
+                    .position(|vector_field| vector_field == field)
+                    .ok_or(ServiceError::BadRequest(format!(
+                        "Vector field {} is not one of the VECTOR_FIELDS of the dataset",
+                        field
+                    )))?;
+                if get_vector_fields_migration_stage() == VectorFieldsMigrationStage::DualWrite {
+                    return Err(ServiceError::BadRequest(
+                        "Vector fields can be searched once the vector fields migration has finished"
+                            .to_string(),
+                    ));
+                }
+                VectorType::FieldDense(slot, embedding_vector.clone())
+            };
+
+            Ok((
+                QdrantSearchQuery {
+                    vector,
+                    ..qdrant_query.clone()
+                },
+                *weight,
+            ))
+        })
+        .collect()
+}
+
+/// Scores every point by the weighted sum of its scores for each field and keeps the `limit` best.
+fn combine_vector_field_results(
+    weighted_field_results: &[(&[SearchResult], f32)],
+    limit: u64,
+) -> Vec<SearchResult> {
+    let mut fused_scores: Vec<(uuid::Uuid, f32)> = vec![];
+    let mut fused_score_indices: HashMap<uuid::Uuid, usize> = HashMap::new();
+    for (results, weight) in weighted_field_results {
+        for result in results.iter().unique_by(|result| result.point_id) {
+            match fused_score_indices.get(&result.point_id) {
+                Some(&index) => fused_scores[index].1 += weight * result.score,
+                None => {
+                    fused_score_indices.insert(result.point_id, fused_scores.len());
+                    fused_scores.push((result.point_id, weight * result.score));
+                }
+            }
+        }
+    }
+
+    fused_scores.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    fused_scores.truncate(limit as usize);
+
+    fused_scores
+        .into_iter()
+        .map(|(point_id, score)| SearchResult { score, point_id })
+        .collect()
+}
+
+/// Searches each requested vector field with the same query vector and scores every point by the weighted sum of its scores for each field.
+pub async fn retrieve_vector_fields_points_query(
+    qdrant_query: QdrantSearchQuery,
+    vector_fields: &HashMap<String, f32>,
+    page: u64,
+    get_total_pages: bool,
+    config: &DatasetConfiguration,
+) -> Result<SearchChunkQueryResult, ServiceError> {
+    let weighted_queries = get_vector_field_queries(&qdrant_query, vector_fields, config)?;
+
+    if let [(field_query, _)] = weighted_queries.as_slice() {
+        return retrieve_qdrant_points_query(
+            vec![field_query.clone()],
+            page,
+            get_total_pages,
+            config,
+        )
+        .await;
+    }
+
+    let field_results =
+        futures::future::try_join_all(weighted_queries.iter().map(|(field_query, _)| {
+            retrieve_qdrant_points_query(vec![field_query.clone()], page, get_total_pages, config)
+        }))
+        .await?;
+
+    let weighted_field_results = field_results
+        .iter()
+        .zip(weighted_queries.iter())
+        .map(|(results, (_, weight))| (results.search_results.as_slice(), *weight))
+        .collect_vec();
+
+    Ok(SearchChunkQueryResult {
+        search_results: combine_vector_field_results(&weighted_field_results, qdrant_query.limit),
+        total_chunk_pages: field_results
+            .iter()
+            .map(|results| results.total_chunk_pages)
+            .max()
+            .unwrap_or(0),
+        batch_lengths: field_results
+            .iter()
+            .map(|results| results.search_results.len())
+            .collect(),
+    })
+}
+
+fn set_hybrid_legs(
+    score_chunks: &mut [ScoreChunkDTO],
+    hybrid_legs: &HashMap<uuid::Uuid, Vec<HybridLeg>>,
+) {
+    score_chunks.iter_mut().for_each(|score_chunk| {
+        score_chunk.hybrid_legs = score_chunk
+            .metadata
+            .first()
+            .and_then(|chunk| hybrid_legs.get(&chunk.metadata().qdrant_point_id))
+            .cloned();
+    });
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn get_metadata_filter_condition(
     filter: &FieldCondition,
@@ -1015,13 +1412,53 @@ pub async fn retrieve_group_qdrant_points_query(
     pool: web::Data<Pool>,
     config: &DatasetConfiguration,
 ) -> Result<SearchOverGroupsQueryResult, ServiceError> {
+    let (point_ids, count) = retrieve_group_qdrant_points_with_count_query(
+        vector,
+        page,
+        get_total_pages,
+        filters,
+        limit,
+        score_threshold,
+        group_size,
+        parsed_query,
+        dataset_id,
+        pool,
+        config,
+    )
+    .await?;
+
+    let pages = (count as f64 / limit as f64).ceil() as i64;
+
+    Ok(SearchOverGroupsQueryResult {
+        search_results: point_ids,
+        corrected_query: None,
+        total_chunk_pages: pages,
+    })
+}
+
+/// Same as retrieve_group_qdrant_points_query but returns the number of matching groups instead of the number of pages, for callers which page the results themselves.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(pool))]
+pub async fn retrieve_group_qdrant_points_with_count_query(
+    vector: VectorType,
+    page: u64,
+    get_total_pages: bool,
+    filters: Option<ChunkFilter>,
+    limit: u64,
+    score_threshold: Option<f32>,
+    group_size: u32,
+    parsed_query: ParsedQueryTypes,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+    config: &DatasetConfiguration,
+) -> Result<(Vec<GroupSearchResults>, u64), ServiceError> {
     let page = if page == 0 { 1 } else { page };
     let parsed_query = match parsed_query {
         ParsedQueryTypes::Single(parsed_query) => Some(parsed_query),
         ParsedQueryTypes::Multi(_) => None,
     };
 
-    let filter = assemble_qdrant_filter(
+    let mut filter = assemble_qdrant_filter(
         filters.clone(),
         parsed_query
             .as_ref()
@@ -1038,7 +1475,9 @@ pub async fn retrieve_group_qdrant_points_query(
     )
     .await?;
 
-    let (point_ids, count) = search_over_groups_query(
+    exclude_query_point(&mut filter, parsed_query.as_ref());
+
+    search_over_groups_query(
         page,
         filter.clone(),
         limit,
@@ -1048,15 +1487,7 @@ pub async fn retrieve_group_qdrant_points_query(
         config.clone(),
         get_total_pages,
     )
-    .await?;
-
-    let pages = (count as f64 / limit as f64).ceil() as i64;
-
-    Ok(SearchOverGroupsQueryResult {
-        search_results: point_ids,
-        corrected_query: None,
-        total_chunk_pages: pages,
-    })
+    .await
 }
 
 #[derive(Debug, Serialize, Deserialize, Queryable)]
@@ -1119,6 +1550,11 @@ pub struct DeprecatedSearchOverGroupsResponseBody {
     pub group_chunks: Vec<GroupScoreChunk>,
     pub corrected_query: Option<String>,
     pub total_chunk_pages: i64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub facets: Option<FacetResults>,
+    /// Ids of the merchandising rules which were applied to the results, including rules which hid chunks. Only present if a rule was applied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 impl DeprecatedSearchOverGroupsResponseBody {
@@ -1132,6 +1568,8 @@ impl DeprecatedSearchOverGroupsResponseBody {
                 .collect(),
             corrected_query: self.corrected_query,
             total_pages: self.total_chunk_pages,
+            facets: self.facets,
+            merchandising_rule_ids: self.merchandising_rule_ids,
         }
     }
 }
@@ -1143,6 +1581,11 @@ pub struct SearchOverGroupsResponseBody {
     pub results: Vec<SearchOverGroupsResults>,
     pub corrected_query: Option<String>,
     pub total_pages: i64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub facets: Option<FacetResults>,
+    /// Ids of the merchandising rules which were applied to the results, including rules which hid chunks. Only present if a rule was applied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merchandising_rule_ids: Option<Vec<uuid::Uuid>>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -1159,6 +1602,7 @@ pub async fn retrieve_chunks_for_groups(
     search_over_groups_query_result: SearchOverGroupsQueryResult,
     data: &SearchOverGroupsReqPayload,
     pool: web::Data<Pool>,
+    language: DatasetLanguage,
 ) -> Result<DeprecatedSearchOverGroupsResponseBody, ServiceError> {
     let point_ids = search_over_groups_query_result
         .search_results
@@ -1216,7 +1660,7 @@ pub async fn retrieve_chunks_for_groups(
 
                     let mut highlights: Option<Vec<String>> = None;
                     if let Some(highlight_options)  = &data.highlight_options {
-                        if highlight_options.highlight_results.unwrap_or(true) && !data.slim_chunks.unwrap_or(false) && !matches!(data.query, QueryTypes::Multi(_)) {
+                        if highlight_options.highlight_results.unwrap_or(true) && !data.slim_chunks.unwrap_or(false) && matches!(data.query, QueryTypes::Single(_)) {
                             let (highlighted_chunk, highlighted_snippets) = match highlight_options.highlight_strategy {
                                 Some(HighlightStrategy::V1) => {
                                     get_highlights(
@@ -1235,7 +1679,8 @@ pub async fn retrieve_chunks_for_groups(
                                             highlight_options.highlight_max_num,
                                             highlight_options.highlight_window,
                                             highlight_options.pre_tag.clone(),
-                                            highlight_options.post_tag.clone()
+                                            highlight_options.post_tag.clone(),
+                                            language
                                         )
                                         .unwrap_or((chunk.clone().into(), vec![]))
                                 },
@@ -1256,7 +1701,8 @@ pub async fn retrieve_chunks_for_groups(
                                             highlight_options.highlight_max_num,
                                             highlight_options.highlight_window,
                                             highlight_options.pre_tag.clone(),
-                                            highlight_options.post_tag.clone()
+                                            highlight_options.post_tag.clone(),
+                                            language
                                         )
                                         .unwrap_or((chunk.clone().into(), vec![]))
                                 },
@@ -1283,6 +1729,8 @@ pub async fn retrieve_chunks_for_groups(
                         metadata: vec![chunk],
                         highlights,
                         score: search_result.score.into(),
+                        hybrid_legs: None,
+                        merchandising_rule_ids: None,
                     })
                 })
                 .sorted_by(|a, b| b.score.partial_cmp(&a.score).unwrap())
@@ -1310,6 +1758,8 @@ pub async fn retrieve_chunks_for_groups(
         group_chunks,
         corrected_query: None,
         total_chunk_pages: search_over_groups_query_result.total_chunk_pages,
+        facets: None,
+        merchandising_rule_ids: None,
     })
 }
 
@@ -1374,6 +1824,8 @@ pub async fn get_metadata_from_groups(
                         metadata: vec![chunk],
                         highlights: None,
                         score: search_result.score.into(),
+                        hybrid_legs: None,
+                        merchandising_rule_ids: None,
                     })
                 })
                 .collect_vec();
@@ -1407,6 +1859,7 @@ pub async fn retrieve_chunks_from_point_ids(
     timer: Option<&mut Timer>,
     data: &SearchChunksReqPayload,
     pool: web::Data<Pool>,
+    language: DatasetLanguage,
 ) -> Result<SearchChunkQueryResponseBody, actix_web::Error> {
     let parent_span = sentry::configure_scope(|scope| scope.get_span());
     let transaction: sentry::TransactionOrSpan = match &parent_span {
@@ -1478,7 +1931,7 @@ pub async fn retrieve_chunks_from_point_ids(
 
             let mut highlights: Option<Vec<String>> = None;
                 if let Some(highlight_options)  = &data.highlight_options {
-                        if highlight_options.highlight_results.unwrap_or(true) && !data.slim_chunks.unwrap_or(false) && !matches!(data.query, QueryTypes::Multi(_)) {
+                        if highlight_options.highlight_results.unwrap_or(true) && !data.slim_chunks.unwrap_or(false) && matches!(data.query, QueryTypes::Single(_)) {
                             let (highlighted_chunk, highlighted_snippets) = match highlight_options.highlight_strategy {
                                 Some(HighlightStrategy::V1) => {
                                     get_highlights(
@@ -1497,7 +1950,8 @@ pub async fn retrieve_chunks_from_point_ids(
                                             highlight_options.highlight_max_num,
                                             highlight_options.highlight_window,
                                             highlight_options.pre_tag.clone(),
-                                            highlight_options.post_tag.clone()
+                                            highlight_options.post_tag.clone(),
+                                            language
                                         )
                                         .unwrap_or((chunk.clone().into(), vec![]))
                                 },
@@ -1518,7 +1972,8 @@ pub async fn retrieve_chunks_from_point_ids(
                                             highlight_options.highlight_max_num,
                                             highlight_options.highlight_window,
                                             highlight_options.pre_tag.clone(),
-                                            highlight_options.post_tag.clone()
+                                            highlight_options.post_tag.clone(),
+                                            language
                                         )
                                         .unwrap_or((chunk.clone().into(), vec![]))
                                 },
@@ -1544,6 +1999,8 @@ pub async fn retrieve_chunks_from_point_ids(
                 metadata: vec![chunk],
                 highlights,
                 score: search_result.score.into(),
+                hybrid_legs: None,
+                merchandising_rule_ids: None,
             })
         })
         .collect();
@@ -1556,9 +2013,57 @@ pub async fn retrieve_chunks_from_point_ids(
         score_chunks,
         corrected_query: None,
         total_chunk_pages: search_chunk_query_results.total_chunk_pages,
+        facets: None,
+        merchandising_rule_ids: None,
     })
 }
 
+/// Multiplies the score of each chunk with a time_stamp by its recency decay factor. Chunks are not re-sorted.
+pub fn apply_recency_bias(chunks: &mut [ScoreChunkDTO], recency_bias: &RecencyBias) {
+    let origin = recency_bias.origin();
+
+    for chunk in chunks.iter_mut() {
+        if let Some(time_stamp) = chunk
+            .metadata
+            .first()
+            .and_then(|metadata| metadata.metadata().time_stamp)
+        {
+            chunk.score *= recency_bias.decay_factor(time_stamp, origin);
+        }
+    }
+}
+
+/// Applies recency bias to the chunks of each group, then orders the chunks within each group and the groups by their top chunk.
+pub fn rerank_groups_by_recency(
+    groups: Vec<GroupScoreChunk>,
+    recency_bias: &RecencyBias,
+) -> Vec<GroupScoreChunk> {
+    groups
+        .into_iter()
+        .map(|mut group| {
+            apply_recency_bias(&mut group.metadata, recency_bias);
+            group.metadata.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            group
+        })
+        .sorted_by(|a, b| {
+            let top_score = |group: &GroupScoreChunk| {
+                group
+                    .metadata
+                    .first()
+                    .map(|chunk| chunk.score)
+                    .unwrap_or(0.0)
+            };
+            top_score(b)
+                .partial_cmp(&top_score(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .collect()
+}
+
 #[tracing::instrument]
 pub fn rerank_chunks(
     chunks: Vec<ScoreChunkDTO>,
@@ -1566,6 +2071,7 @@ pub fn rerank_chunks(
     tag_weights: Option<HashMap<String, f32>>,
     use_weights: Option<bool>,
     query_location: Option<GeoInfoWithBias>,
+    recency_bias: Option<RecencyBias>,
 ) -> Vec<ScoreChunkDTO> {
     let mut reranked_chunks = Vec::new();
     if use_weights.unwrap_or(true) {
@@ -1619,6 +2125,10 @@ pub fn rerank_chunks(
             .collect::<Vec<ScoreChunkDTO>>();
     }
 
+    if let Some(recency_bias) = recency_bias {
+        apply_recency_bias(&mut reranked_chunks, &recency_bias);
+    }
+
     if let Some(tag_weights) = tag_weights {
         reranked_chunks = reranked_chunks
             .iter_mut()
@@ -1697,12 +2207,189 @@ pub fn rerank_chunks(
     reranked_chunks
 }
 
+/// Raw dense vector queries must match the dataset's embedding size and only hold finite values
+fn check_dense_query_vector(
+    dense_vector: &[f32],
+    embedding_size: usize,
+) -> Result<(), ServiceError> {
+    if dense_vector.len() != embedding_size {
+        return Err(ServiceError::BadRequest(format!(
+            "dense_vector has {} dimensions but this dataset's embedding size is {}",
+            dense_vector.len(),
+            embedding_size
+        )));
+    }
+
+    if !dense_vector.iter().all(|value| value.is_finite()) {
+        return Err(ServiceError::BadRequest(
+            "dense_vector must only hold finite values".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Pairs the indices of a raw sparse vector query with its values, which must be as many and finite
+fn get_sparse_query_vector(sparse_vector: SparseVector) -> Result<Vec<(u32, f32)>, ServiceError> {
+    if sparse_vector.indices.len() != sparse_vector.values.len() {
+        return Err(ServiceError::BadRequest(
+            "sparse_vector must have as many indices as values".to_string(),
+        ));
+    }
+
+    if !sparse_vector.values.iter().all(|value| value.is_finite()) {
+        return Err(ServiceError::BadRequest(
+            "sparse_vector must only hold finite values".to_string(),
+        ));
+    }
+
+    Ok(sparse_vector
+        .indices
+        .into_iter()
+        .zip(sparse_vector.values)
+        .collect())
+}
+
+/// A chunk query naming a chunk which is not in the dataset is a bad request rather than a missing resource
+fn query_chunk_not_found(err: ServiceError, query_chunk: String) -> ServiceError {
+    match err {
+        ServiceError::NotFound(_) => ServiceError::BadRequest(format!(
+            "The query chunk {} was not found in this dataset",
+            query_chunk
+        )),
+        err => err,
+    }
+}
+
+/// Resolves a raw vector or chunk query into a parsed query carrying the vectors to search with. Chunk queries reuse the vectors stored for that chunk and compute its BM25 vector from its content.
+#[tracing::instrument(skip(pool))]
+pub async fn parse_vector_query(
+    query: QueryTypes,
+    dataset_id: uuid::Uuid,
+    config: &DatasetConfiguration,
+    pool: web::Data<Pool>,
+) -> Result<ParsedQuery, ServiceError> {
+    let vectors = match query {
+        QueryTypes::DenseVector { dense_vector } => {
+            check_dense_query_vector(&dense_vector, config.EMBEDDING_SIZE)?;
+
+            QueryVectors {
+                dense_vector: Some(dense_vector),
+                ..Default::default()
+            }
+        }
+        QueryTypes::SparseVector { sparse_vector } => {
+            let sparse_vector = get_sparse_query_vector(sparse_vector)?;
+
+            QueryVectors {
+                sparse_vector: Some(sparse_vector.clone()),
+                bm25_vector: Some(sparse_vector),
+                ..Default::default()
+            }
+        }
+        QueryTypes::ChunkId { chunk_id } => {
+            let chunk = get_metadata_from_id_query(chunk_id, dataset_id, pool)
+                .await
+                .map_err(|err| query_chunk_not_found(err, chunk_id.to_string()))?;
+            get_chunk_query_vectors(chunk, config).await?
+        }
+        QueryTypes::TrackingId { tracking_id } => {
+            let chunk = get_metadata_from_tracking_id_query(tracking_id.clone(), dataset_id, pool)
+                .await
+                .map_err(|err| query_chunk_not_found(err, tracking_id))?;
+            get_chunk_query_vectors(chunk, config).await?
+        }
+        QueryTypes::Single(_) | QueryTypes::Multi(_) => {
+            return Err(ServiceError::BadRequest(
+                "Text queries do not carry vectors".to_string(),
+            ))
+        }
+    };
+
+    Ok(ParsedQuery {
+        query: "".to_string(),
+        quote_words: None,
+        negated_words: None,
+        vectors: Some(vectors),
+    })
+}
+
+async fn get_chunk_query_vectors(
+    chunk: ChunkMetadata,
+    config: &DatasetConfiguration,
+) -> Result<QueryVectors, ServiceError> {
+    let (dense_vector, sparse_vector) =
+        get_point_vectors_query(vec![chunk.qdrant_point_id], config.clone())
+            .await?
+            .remove(&chunk.qdrant_point_id)
+            .ok_or(ServiceError::BadRequest(
+                "The query chunk has not been embedded yet".to_string(),
+            ))?;
+
+    let content = convert_html_to_text(&chunk.chunk_html.unwrap_or_default());
+
+    let bm25_vector = get_bm25_embeddings(
+        vec![(content.clone(), None)],
+        config.BM25_AVG_LEN,
+        config.BM25_B,
+        config.BM25_K,
+        config.LANGUAGE,
+    )
+    .pop();
+
+    Ok(QueryVectors {
+        dense_vector,
+        sparse_vector,
+        bm25_vector,
+        content: Some(content),
+        qdrant_point_id: Some(chunk.qdrant_point_id),
+    })
+}
+
+/// Vector of the given kind from a vector or chunk query, errors when the query does not have it
+fn get_query_vector(
+    vectors: &QueryVectors,
+    search_type: SearchMethod,
+) -> Result<VectorType, ServiceError> {
+    let vector = match search_type {
+        SearchMethod::Semantic => vectors.dense_vector.clone().map(VectorType::Dense),
+        SearchMethod::FullText => vectors.sparse_vector.clone().map(VectorType::SpladeSparse),
+        SearchMethod::BM25 => vectors.bm25_vector.clone().map(VectorType::BM25Sparse),
+        SearchMethod::Hybrid => None,
+    };
+
+    vector.ok_or(ServiceError::BadRequest(format!(
+        "The query does not have a vector for {} search",
+        search_type
+    )))
+}
+
+/// Dense and sparse vectors of a vector or chunk query for the two legs of a hybrid search
+fn get_hybrid_query_vectors(
+    vectors: &QueryVectors,
+) -> Result<(Vec<f32>, Vec<(u32, f32)>), ServiceError> {
+    match (vectors.dense_vector.clone(), vectors.sparse_vector.clone()) {
+        (Some(dense_vector), Some(sparse_vector)) => Ok((dense_vector, sparse_vector)),
+        _ => Err(ServiceError::BadRequest(
+            "Hybrid search needs both a dense and a sparse vector, use a chunk query or a single search_type instead".to_string(),
+        )),
+    }
+}
+
 async fn get_qdrant_vector(
     search_type: SearchMethod,
     parsed_query: ParsedQueryTypes,
     scoring_options: Option<ScoringOptions>,
     config: &DatasetConfiguration,
 ) -> Result<VectorType, ServiceError> {
+    if let ParsedQueryTypes::Single(ParsedQuery {
+        vectors: Some(ref vectors),
+        ..
+    }) = parsed_query
+    {
+        return get_query_vector(vectors, search_type);
+    }
+
     match search_type {
         SearchMethod::Semantic => {
             if !config.SEMANTIC_ENABLED {
@@ -1781,6 +2468,7 @@ async fn get_qdrant_vector(
                     config.BM25_AVG_LEN,
                     config.BM25_B,
                     config.BM25_K,
+                    config.LANGUAGE,
                 ),
                 ParsedQueryTypes::Multi(_) => {
                     return Err(ServiceError::BadRequest(
@@ -1806,7 +2494,8 @@ async fn get_qdrant_vector(
 
             let sparse_vector = match parsed_query {
                 ParsedQueryTypes::Single(query) => {
-                    get_sparse_vector(query.query.clone(), fulltext_boost, "query").await?
+                    get_sparse_vector(query.query.clone(), fulltext_boost, "query", config.clone())
+                        .await?
                 }
                 ParsedQueryTypes::Multi(_) => {
                     return Err(ServiceError::BadRequest(
@@ -1853,7 +2542,7 @@ pub async fn search_chunks_query(
         match parsed_query {
             ParsedQueryTypes::Single(ref mut query) => {
                 let typo_corrected_query =
-                    correct_query(query.clone(), dataset.id, redis_pool, options).await?;
+                    correct_query(query.clone(), dataset.id, redis_pool.clone(), options).await?;
                 if typo_corrected_query.corrected {
                     corrected_query.clone_from(&typo_corrected_query.query);
                 }
@@ -1878,9 +2567,18 @@ pub async fn search_chunks_query(
 
     timer.add("start to create query vector");
 
-    let vector = get_qdrant_vector(
-        data.clone().search_type,
-        parsed_query.clone(),
+    let vector_query = match data.search_type {
+        SearchMethod::BM25 | SearchMethod::FullText => {
+            get_synonym_map(dataset.id, redis_pool.clone())
+                .await
+                .expand_parsed_query_types(parsed_query.clone())
+        }
+        _ => parsed_query.clone(),
+    };
+
+    let vector = get_qdrant_vector(
+        data.clone().search_type,
+        vector_query,
         data.clone().scoring_options,
         config,
     )
@@ -1910,16 +2608,34 @@ pub async fn search_chunks_query(
         rerank_by: rerank_by.clone(),
         filter: data.filters.clone(),
     }
-    .into_qdrant_query(parsed_query, dataset.id, None, config, pool.clone())
+    .into_qdrant_query(parsed_query.clone(), dataset.id, None, config, pool.clone())
     .await?;
 
-    let search_chunk_query_results = retrieve_qdrant_points_query(
-        vec![qdrant_query],
-        data.page.unwrap_or(1),
-        data.get_total_pages.unwrap_or(false),
-        config,
-    )
-    .await?;
+    let search_chunk_query_results = match data
+        .vector_fields
+        .as_ref()
+        .filter(|vector_fields| !vector_fields.is_empty())
+    {
+        Some(vector_fields) => {
+            retrieve_vector_fields_points_query(
+                qdrant_query,
+                vector_fields,
+                data.page.unwrap_or(1),
+                data.get_total_pages.unwrap_or(false),
+                config,
+            )
+            .await?
+        }
+        None => {
+            retrieve_qdrant_points_query(
+                vec![qdrant_query],
+                data.page.unwrap_or(1),
+                data.get_total_pages.unwrap_or(false),
+                config,
+            )
+            .await?
+        }
+    };
 
     timer.add("fetched from qdrant");
 
@@ -1928,6 +2644,7 @@ pub async fn search_chunks_query(
         Some(timer),
         &data,
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
@@ -1935,7 +2652,7 @@ pub async fn search_chunks_query(
         match rerank_by.rerank_type {
             ReRankOptions::CrossEncoder => {
                 let mut cross_encoder_results = cross_encoder(
-                    data.query.clone().to_single_query()?,
+                    parsed_query.to_parsed_query()?.to_rerank_query()?,
                     data.page_size.unwrap_or(10),
                     result_chunks.score_chunks,
                     config,
@@ -1969,9 +2686,21 @@ pub async fn search_chunks_query(
             .as_ref()
             .map(|d| d.location_bias)
             .unwrap_or_default(),
+        data.recency_bias,
     );
 
     timer.add("reranking");
+
+    result_chunks = apply_merchandising_rules(
+        result_chunks,
+        &data,
+        dataset.id,
+        pool.clone(),
+        config.LANGUAGE,
+    )
+    .await?;
+
+    timer.add("applied merchandising rules");
     transaction.finish();
 
     result_chunks.corrected_query = corrected_query.map(|c| c.query);
@@ -1990,6 +2719,10 @@ pub async fn search_hybrid_chunks(
     config: &DatasetConfiguration,
     timer: &mut Timer,
 ) -> Result<SearchChunkQueryResponseBody, actix_web::Error> {
+    if let Some(hybrid_options) = &data.hybrid_options {
+        check_hybrid_search_options(hybrid_options)?;
+    }
+
     let parent_span = sentry::configure_scope(|scope| scope.get_span());
     let transaction: sentry::TransactionOrSpan = match &parent_span {
         Some(parent) => parent
@@ -2007,8 +2740,13 @@ pub async fn search_hybrid_chunks(
 
     if let Some(options) = &data.typo_options {
         timer.add("start correcting query");
-        let typo_corrected_query =
-            correct_query(parsed_query.clone(), dataset.id, redis_pool, options).await?;
+        let typo_corrected_query = correct_query(
+            parsed_query.clone(),
+            dataset.id,
+            redis_pool.clone(),
+            options,
+        )
+        .await?;
         if typo_corrected_query.corrected {
             corrected_query.clone_from(&typo_corrected_query.query);
         }
@@ -2033,18 +2771,26 @@ pub async fn search_hybrid_chunks(
         .map(|options| options.fulltext_boost)
         .unwrap_or(None);
 
-    let dense_query_vector_future = get_dense_vector(
-        data.query.clone().to_single_query()?,
-        semantic_boost,
-        "query",
-        dataset_config.clone(),
-    );
+    let (dense_vector, sparse_vector) = match &parsed_query.vectors {
+        Some(vectors) => get_hybrid_query_vectors(vectors)?,
+        None => {
+            let dense_query_vector_future = get_dense_vector(
+                data.query.clone().to_single_query()?,
+                semantic_boost,
+                "query",
+                dataset_config.clone(),
+            );
 
-    let sparse_query_vector_future =
-        get_sparse_vector(parsed_query.query.clone(), fulltext_boost, "query");
+            let sparse_query = get_synonym_map(dataset.id, redis_pool.clone())
+                .await
+                .expand_query(&parsed_query.query);
 
-    let (dense_vector, sparse_vector) =
-        futures::try_join!(dense_query_vector_future, sparse_query_vector_future)?;
+            let sparse_query_vector_future =
+                get_sparse_vector(sparse_query, fulltext_boost, "query", config.clone());
+
+            futures::try_join!(dense_query_vector_future, sparse_query_vector_future)?
+        }
+    };
 
     timer.add("computed sparse and dense embeddings");
 
@@ -2056,75 +2802,92 @@ pub async fn search_hybrid_chunks(
         _ => (None, None),
     };
 
-    let qdrant_queries = vec![
-        RetrievePointQuery {
-            vector: VectorType::Dense(dense_vector),
-            score_threshold: None,
-            sort_by: sort_by.clone(),
-            rerank_by: rerank_by.clone(),
-            limit: data.page_size.unwrap_or(10),
-            filter: data.filters.clone(),
-        }
-        .into_qdrant_query(
-            ParsedQueryTypes::Single(parsed_query.clone()),
-            dataset.id,
-            None,
-            config,
-            pool.clone(),
-        )
-        .await?,
-        RetrievePointQuery {
-            vector: VectorType::SpladeSparse(sparse_vector),
-            score_threshold: None,
-            sort_by: sort_by.clone(),
-            rerank_by: rerank_by.clone(),
-            limit: data.page_size.unwrap_or(10),
-            filter: data.filters.clone(),
-        }
-        .into_qdrant_query(
-            ParsedQueryTypes::Single(parsed_query.clone()),
-            dataset.id,
-            None,
-            config,
-            pool.clone(),
-        )
-        .await?,
-    ];
+    let hybrid_options = data.hybrid_options.clone().unwrap_or_default();
+    let fusion_strategy = hybrid_options.fusion_strategy.clone().unwrap_or_default();
 
-    let search_chunk_query_results = retrieve_qdrant_points_query(
-        qdrant_queries,
+    let semantic_query = RetrievePointQuery {
+        vector: VectorType::Dense(dense_vector),
+        score_threshold: None,
+        sort_by: sort_by.clone(),
+        rerank_by: rerank_by.clone(),
+        limit: hybrid_options
+            .semantic_prefetch
+            .unwrap_or(data.page_size.unwrap_or(10)),
+        filter: data.filters.clone(),
+    }
+    .into_qdrant_query(
+        ParsedQueryTypes::Single(parsed_query.clone()),
+        dataset.id,
+        None,
+        config,
+        pool.clone(),
+    )
+    .await?;
+
+    let fulltext_query = RetrievePointQuery {
+        vector: VectorType::SpladeSparse(sparse_vector),
+        score_threshold: None,
+        sort_by: sort_by.clone(),
+        rerank_by: rerank_by.clone(),
+        limit: hybrid_options
+            .fulltext_prefetch
+            .unwrap_or(data.page_size.unwrap_or(10)),
+        filter: data.filters.clone(),
+    }
+    .into_qdrant_query(
+        ParsedQueryTypes::Single(parsed_query.clone()),
+        dataset.id,
+        None,
+        config,
+        pool.clone(),
+    )
+    .await?;
+
+    let (search_chunk_query_results, hybrid_legs) = retrieve_hybrid_qdrant_points_query(
+        semantic_query,
+        fulltext_query,
+        &hybrid_options,
         data.page.unwrap_or(1),
+        data.page_size.unwrap_or(10),
         data.get_total_pages.unwrap_or(false),
         config,
     )
     .await?;
 
-    let result_chunks = retrieve_chunks_from_point_ids(
+    let mut result_chunks = retrieve_chunks_from_point_ids(
         search_chunk_query_results,
         Some(timer),
         &data,
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
+    set_hybrid_legs(&mut result_chunks.score_chunks, &hybrid_legs);
+
     timer.add("fetched metadata from postgres");
 
     let mut reranked_chunks = {
         let mut reranked_chunks = {
-            let mut cross_encoder_results = cross_encoder(
-                data.query.clone().to_single_query()?,
-                data.page_size.unwrap_or(10),
-                result_chunks.score_chunks,
-                config,
-            )
-            .await?;
+            let mut fused_results = match fusion_strategy {
+                HybridFusionStrategy::CrossEncoder => {
+                    cross_encoder(
+                        parsed_query.to_rerank_query()?,
+                        data.page_size.unwrap_or(10),
+                        result_chunks.score_chunks,
+                        config,
+                    )
+                    .await?
+                }
+                _ => result_chunks.score_chunks,
+            };
 
             if let Some(score_threshold) = data.score_threshold {
-                cross_encoder_results.retain(|chunk| chunk.score >= score_threshold.into());
+                fused_results.retain(|chunk| chunk.score >= score_threshold.into());
             }
 
             rerank_chunks(
-                cross_encoder_results,
+                fused_results,
                 sort_by,
                 data.sort_options
                     .as_ref()
@@ -2138,6 +2901,7 @@ pub async fn search_hybrid_chunks(
                     .as_ref()
                     .map(|d| d.location_bias)
                     .unwrap_or_default(),
+                data.recency_bias,
             )
         };
 
@@ -2149,9 +2913,22 @@ pub async fn search_hybrid_chunks(
             score_chunks: reranked_chunks,
             corrected_query: corrected_query.map(|c| c.query),
             total_chunk_pages: result_chunks.total_chunk_pages,
+            facets: None,
+            merchandising_rule_ids: None,
         }
     };
 
+    reranked_chunks = apply_merchandising_rules(
+        reranked_chunks,
+        &data,
+        dataset.id,
+        pool.clone(),
+        config.LANGUAGE,
+    )
+    .await?;
+
+    timer.add("applied merchandising rules");
+
     if data.slim_chunks.unwrap_or(false) {
         reranked_chunks.score_chunks = reranked_chunks
             .score_chunks
@@ -2172,6 +2949,8 @@ pub async fn search_hybrid_chunks(
                     .collect(),
                 highlights: score_chunk.highlights,
                 score: score_chunk.score,
+                hybrid_legs: score_chunk.hybrid_legs,
+                merchandising_rule_ids: score_chunk.merchandising_rule_ids,
             })
             .collect();
     }
@@ -2192,8 +2971,16 @@ pub async fn search_groups_query(
     config: &DatasetConfiguration,
     timer: &mut Timer,
 ) -> Result<SearchWithinGroupResults, actix_web::Error> {
-    let vector =
-        get_qdrant_vector(data.clone().search_type, parsed_query.clone(), None, config).await?;
+    let vector_query = match data.search_type {
+        SearchMethod::BM25 | SearchMethod::FullText => {
+            get_synonym_map(dataset.id, redis_pool.clone())
+                .await
+                .expand_parsed_query_types(parsed_query.clone())
+        }
+        _ => parsed_query.clone(),
+    };
+
+    let vector = get_qdrant_vector(data.clone().search_type, vector_query, None, config).await?;
 
     let mut parsed_query = parsed_query.clone();
     let mut corrected_query = None;
@@ -2247,7 +3034,7 @@ pub async fn search_groups_query(
         rerank_by: rerank_by.clone(),
         filter: data.filters.clone(),
     }
-    .into_qdrant_query(parsed_query, dataset.id, None, config, pool.clone())
+    .into_qdrant_query(parsed_query.clone(), dataset.id, None, config, pool.clone())
     .await?;
 
     let search_semantic_chunk_query_results = retrieve_qdrant_points_query(
@@ -2263,6 +3050,7 @@ pub async fn search_groups_query(
         None,
         &web::Json(data.clone().into()),
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
@@ -2270,7 +3058,7 @@ pub async fn search_groups_query(
         match rerank_by.rerank_type {
             ReRankOptions::CrossEncoder => {
                 let mut cross_encoder_results = cross_encoder(
-                    data.query.clone().to_single_query()?,
+                    parsed_query.to_parsed_query()?.to_rerank_query()?,
                     data.page_size.unwrap_or(10),
                     result_chunks.score_chunks,
                     config,
@@ -2304,14 +3092,30 @@ pub async fn search_groups_query(
             .as_ref()
             .map(|d| d.location_bias)
             .unwrap_or_default(),
+        data.recency_bias,
     );
 
-    Ok(SearchWithinGroupResults {
+    let search_within_group_results = SearchWithinGroupResults {
         bookmarks: result_chunks.score_chunks,
         group,
         corrected_query: corrected_query.map(|c| c.query),
         total_pages: result_chunks.total_chunk_pages,
-    })
+        merchandising_rule_ids: None,
+    };
+
+    timer.add("reranking");
+
+    let search_within_group_results = apply_merchandising_rules_within_group(
+        search_within_group_results,
+        &data,
+        dataset.id,
+        pool,
+    )
+    .await?;
+
+    timer.add("applied merchandising rules");
+
+    Ok(search_within_group_results)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -2326,6 +3130,10 @@ pub async fn search_hybrid_groups(
     config: &DatasetConfiguration,
     timer: &mut Timer,
 ) -> Result<SearchWithinGroupResults, actix_web::Error> {
+    if let Some(hybrid_options) = &data.hybrid_options {
+        check_hybrid_search_options(hybrid_options)?;
+    }
+
     let dataset_config = DatasetConfiguration::from_json(dataset.server_configuration.clone());
 
     let mut parsed_query = parsed_query.clone();
@@ -2333,8 +3141,13 @@ pub async fn search_hybrid_groups(
 
     if let Some(options) = &data.typo_options {
         timer.add("start correcting query");
-        let typo_corrected_query =
-            correct_query(parsed_query.clone(), dataset.id, redis_pool, options).await?;
+        let typo_corrected_query = correct_query(
+            parsed_query.clone(),
+            dataset.id,
+            redis_pool.clone(),
+            options,
+        )
+        .await?;
         if typo_corrected_query.corrected {
             corrected_query.clone_from(&typo_corrected_query.query);
         }
@@ -2346,17 +3159,26 @@ pub async fn search_hybrid_groups(
         timer.add("corrected query");
     }
 
-    let dense_vector_future = get_dense_vector(
-        parsed_query.query.clone(),
-        None,
-        "query",
-        dataset_config.clone(),
-    );
+    let (dense_vector, sparse_vector) = match &parsed_query.vectors {
+        Some(vectors) => get_hybrid_query_vectors(vectors)?,
+        None => {
+            let dense_vector_future = get_dense_vector(
+                parsed_query.query.clone(),
+                None,
+                "query",
+                dataset_config.clone(),
+            );
 
-    let sparse_vector_future = get_sparse_vector(parsed_query.query.clone(), None, "query");
+            let sparse_query = get_synonym_map(dataset.id, redis_pool.clone())
+                .await
+                .expand_query(&parsed_query.query);
 
-    let (dense_vector, sparse_vector) =
-        futures::try_join!(dense_vector_future, sparse_vector_future)?;
+            let sparse_vector_future =
+                get_sparse_vector(sparse_query, None, "query", config.clone());
+
+            futures::try_join!(dense_vector_future, sparse_vector_future)?
+        }
+    };
 
     let (sort_by, rerank_by) = match data.sort_options.as_ref().map(|d| d.sort_by.clone()) {
         Some(Some(sort_by)) => match sort_by {
@@ -2366,66 +3188,92 @@ pub async fn search_hybrid_groups(
         _ => (None, None),
     };
 
-    let qdrant_queries = vec![
-        RetrievePointQuery {
-            vector: VectorType::Dense(dense_vector),
-            score_threshold: None,
-            sort_by: sort_by.clone(),
-            rerank_by: rerank_by.clone(),
-            limit: data.page_size.unwrap_or(10),
-            filter: data.filters.clone(),
-        }
-        .into_qdrant_query(
-            ParsedQueryTypes::Single(parsed_query.clone()),
-            dataset.id,
-            Some(group.id),
-            config,
-            pool.clone(),
-        )
-        .await?,
-        RetrievePointQuery {
-            vector: VectorType::SpladeSparse(sparse_vector),
-            score_threshold: None,
-            sort_by: sort_by.clone(),
-            rerank_by: rerank_by.clone(),
-            limit: data.page_size.unwrap_or(10),
-            filter: data.filters.clone(),
-        }
-        .into_qdrant_query(
-            ParsedQueryTypes::Single(parsed_query.clone()),
-            dataset.id,
-            Some(group.id),
-            config,
-            pool.clone(),
-        )
-        .await?,
-    ];
+    let hybrid_options = data.hybrid_options.clone().unwrap_or_default();
+    let fusion_strategy = hybrid_options.fusion_strategy.clone().unwrap_or_default();
 
-    let mut qdrant_results = retrieve_qdrant_points_query(
-        qdrant_queries,
+    let semantic_query = RetrievePointQuery {
+        vector: VectorType::Dense(dense_vector),
+        score_threshold: None,
+        sort_by: sort_by.clone(),
+        rerank_by: rerank_by.clone(),
+        limit: hybrid_options
+            .semantic_prefetch
+            .unwrap_or(data.page_size.unwrap_or(10)),
+        filter: data.filters.clone(),
+    }
+    .into_qdrant_query(
+        ParsedQueryTypes::Single(parsed_query.clone()),
+        dataset.id,
+        Some(group.id),
+        config,
+        pool.clone(),
+    )
+    .await?;
+
+    let fulltext_query = RetrievePointQuery {
+        vector: VectorType::SpladeSparse(sparse_vector),
+        score_threshold: None,
+        sort_by: sort_by.clone(),
+        rerank_by: rerank_by.clone(),
+        limit: hybrid_options
+            .fulltext_prefetch
+            .unwrap_or(data.page_size.unwrap_or(10)),
+        filter: data.filters.clone(),
+    }
+    .into_qdrant_query(
+        ParsedQueryTypes::Single(parsed_query.clone()),
+        dataset.id,
+        Some(group.id),
+        config,
+        pool.clone(),
+    )
+    .await?;
+
+    let (qdrant_results, hybrid_legs) = retrieve_hybrid_qdrant_points_query(
+        semantic_query,
+        fulltext_query,
+        &hybrid_options,
         data.page.unwrap_or(1),
+        data.page_size.unwrap_or(10),
         data.get_total_pages.unwrap_or(false),
         config,
     )
     .await?;
 
-    qdrant_results.search_results = qdrant_results
-        .search_results
-        .iter()
-        .unique_by(|chunk| chunk.point_id)
-        .cloned()
-        .collect();
-
-    let result_chunks = retrieve_chunks_from_point_ids(
+    let mut result_chunks = retrieve_chunks_from_point_ids(
         qdrant_results,
         None,
         &web::Json(data.clone().into()),
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
+    set_hybrid_legs(&mut result_chunks.score_chunks, &hybrid_legs);
+
     let reranked_chunks = {
-        let mut reranked_chunks = if result_chunks.score_chunks.len() > 20 {
+        let mut reranked_chunks = if fusion_strategy != HybridFusionStrategy::CrossEncoder {
+            let mut fused_results = rerank_chunks(
+                result_chunks.score_chunks.clone(),
+                sort_by,
+                data.sort_options
+                    .as_ref()
+                    .map(|d| d.tag_weights.clone())
+                    .unwrap_or_default(),
+                data.sort_options
+                    .as_ref()
+                    .map(|d| d.use_weights)
+                    .unwrap_or_default(),
+                data.sort_options
+                    .as_ref()
+                    .map(|d| d.location_bias)
+                    .unwrap_or_default(),
+                data.recency_bias,
+            );
+            fused_results.truncate(data.page_size.unwrap_or(10) as usize);
+
+            fused_results
+        } else if result_chunks.score_chunks.len() > 20 {
             let split_results = result_chunks
                 .score_chunks
                 .chunks(20)
@@ -2433,7 +3281,7 @@ pub async fn search_hybrid_groups(
                 .collect::<Vec<Vec<ScoreChunkDTO>>>();
 
             let cross_encoder_results = cross_encoder(
-                data.query.clone().to_single_query()?,
+                parsed_query.to_rerank_query()?,
                 data.page_size.unwrap_or(10),
                 split_results
                     .get(0)
@@ -2457,6 +3305,7 @@ pub async fn search_hybrid_groups(
                     .as_ref()
                     .map(|d| d.location_bias)
                     .unwrap_or_default(),
+                data.recency_bias,
             );
 
             score_chunks
@@ -2466,7 +3315,7 @@ pub async fn search_hybrid_groups(
                 .collect::<Vec<ScoreChunkDTO>>()
         } else {
             let cross_encoder_results = cross_encoder(
-                data.query.clone().to_single_query()?,
+                parsed_query.to_rerank_query()?,
                 data.page_size.unwrap_or(10),
                 result_chunks.score_chunks.clone(),
                 config,
@@ -2488,6 +3337,7 @@ pub async fn search_hybrid_groups(
                     .as_ref()
                     .map(|d| d.location_bias)
                     .unwrap_or_default(),
+                data.recency_bias,
             )
         };
 
@@ -2499,15 +3349,32 @@ pub async fn search_hybrid_groups(
             score_chunks: reranked_chunks,
             corrected_query: None,
             total_chunk_pages: result_chunks.total_chunk_pages,
+            facets: None,
+            merchandising_rule_ids: None,
         }
     };
 
-    Ok(SearchWithinGroupResults {
+    let search_within_group_results = SearchWithinGroupResults {
         bookmarks: reranked_chunks.score_chunks,
         group,
         corrected_query: corrected_query.map(|c| c.query),
         total_pages: result_chunks.total_chunk_pages,
-    })
+        merchandising_rule_ids: None,
+    };
+
+    timer.add("reranking");
+
+    let search_within_group_results = apply_merchandising_rules_within_group(
+        search_within_group_results,
+        &data,
+        dataset.id,
+        pool,
+    )
+    .await?;
+
+    timer.add("applied merchandising rules");
+
+    Ok(search_within_group_results)
 }
 
 #[tracing::instrument(skip(timer, pool, redis_pool))]
@@ -2585,6 +3452,7 @@ pub async fn semantic_search_over_groups(
         search_over_groups_qdrant_result.clone(),
         &data,
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
@@ -2602,9 +3470,19 @@ pub async fn semantic_search_over_groups(
 
     timer.add("fetched from postgres");
 
+    if let Some(recency_bias) = &data.recency_bias {
+        result_chunks.group_chunks =
+            rerank_groups_by_recency(result_chunks.group_chunks, recency_bias);
+    }
+
     //TODO: rerank for groups
     result_chunks.corrected_query = corrected_query.map(|c| c.query);
 
+    let result_chunks =
+        apply_merchandising_rules_over_groups(result_chunks, &data, dataset.id, pool).await?;
+
+    timer.add("applied merchandising rules");
+
     Ok(result_chunks)
 }
 
@@ -2620,9 +3498,13 @@ pub async fn full_text_search_over_groups(
 ) -> Result<DeprecatedSearchOverGroupsResponseBody, actix_web::Error> {
     timer.add("start to get sparse vector");
 
+    let vector_query = get_synonym_map(dataset.id, redis_pool.clone())
+        .await
+        .expand_parsed_query_types(parsed_query.clone());
+
     let embedding_vector = get_qdrant_vector(
         data.clone().search_type,
-        parsed_query.clone(),
+        vector_query,
         None,
         &config.clone(),
     )
@@ -2681,6 +3563,7 @@ pub async fn full_text_search_over_groups(
         search_over_groups_qdrant_result.clone(),
         &data,
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
@@ -2698,12 +3581,77 @@ pub async fn full_text_search_over_groups(
 
     timer.add("fetched from postgres");
 
+    if let Some(recency_bias) = &data.recency_bias {
+        result_groups_with_chunk_hits.group_chunks =
+            rerank_groups_by_recency(result_groups_with_chunk_hits.group_chunks, recency_bias);
+    }
+
     //TODO: rerank for groups
     result_groups_with_chunk_hits.corrected_query = corrected_query.map(|c| c.query);
 
+    let result_groups_with_chunk_hits = apply_merchandising_rules_over_groups(
+        result_groups_with_chunk_hits,
+        &data,
+        dataset.id,
+        pool,
+    )
+    .await?;
+
+    timer.add("applied merchandising rules");
+
     Ok(result_groups_with_chunk_hits)
 }
 
+/// Fuses the group results of the semantic and fulltext legs. Groups are ranked by fusing the score of their top hit in each leg and the hits of groups found by both legs are fused the same way.
+pub fn fuse_hybrid_group_results(
+    semantic_results: &[GroupSearchResults],
+    fulltext_results: &[GroupSearchResults],
+    options: &HybridSearchOptions,
+) -> Vec<GroupSearchResults> {
+    let group_top_hits = |groups: &[GroupSearchResults]| {
+        groups
+            .iter()
+            .map(|group| SearchResult {
+                score: group.hits.first().map(|hit| hit.score).unwrap_or(0.0),
+                point_id: group.group_id,
+            })
+            .collect_vec()
+    };
+
+    let semantic_hits_by_group = semantic_results
+        .iter()
+        .map(|group| (group.group_id, &group.hits))
+        .collect::<HashMap<uuid::Uuid, &Vec<SearchResult>>>();
+    let fulltext_hits_by_group = fulltext_results
+        .iter()
+        .map(|group| (group.group_id, &group.hits))
+        .collect::<HashMap<uuid::Uuid, &Vec<SearchResult>>>();
+    let empty_hits = vec![];
+
+    fuse_hybrid_results(
+        &group_top_hits(semantic_results),
+        &group_top_hits(fulltext_results),
+        options,
+    )
+    .into_iter()
+    .map(|fused_group| {
+        let semantic_hits = semantic_hits_by_group
+            .get(&fused_group.point_id)
+            .copied()
+            .unwrap_or(&empty_hits);
+        let fulltext_hits = fulltext_hits_by_group
+            .get(&fused_group.point_id)
+            .copied()
+            .unwrap_or(&empty_hits);
+
+        GroupSearchResults {
+            group_id: fused_group.point_id,
+            hits: fuse_hybrid_results(semantic_hits, fulltext_hits, options),
+        }
+    })
+    .collect()
+}
+
 async fn cross_encoder_for_groups(
     query: String,
     page_size: u64,
@@ -2774,6 +3722,10 @@ pub async fn hybrid_search_over_groups(
     config: &DatasetConfiguration,
     timer: &mut Timer,
 ) -> Result<DeprecatedSearchOverGroupsResponseBody, actix_web::Error> {
+    if let Some(hybrid_options) = &data.hybrid_options {
+        check_hybrid_search_options(hybrid_options)?;
+    }
+
     let dataset_config = DatasetConfiguration::from_json(dataset.server_configuration.clone());
 
     let mut parsed_query = parsed_query.clone();
@@ -2781,8 +3733,13 @@ pub async fn hybrid_search_over_groups(
 
     if let Some(options) = &data.typo_options {
         timer.add("start correcting query");
-        let typo_corrected_query =
-            correct_query(parsed_query.clone(), dataset.id, redis_pool, options).await?;
+        let typo_corrected_query = correct_query(
+            parsed_query.clone(),
+            dataset.id,
+            redis_pool.clone(),
+            options,
+        )
+        .await?;
         if typo_corrected_query.corrected {
             corrected_query.clone_from(&typo_corrected_query.query);
         }
@@ -2796,29 +3753,65 @@ pub async fn hybrid_search_over_groups(
 
     timer.add("start to create dense embedding vector and sparse vector");
 
-    let dense_embedding_vectors_future = get_dense_vector(
-        data.query.clone().to_single_query()?,
-        None,
-        "query",
-        dataset_config.clone(),
-    );
+    let (dense_vector, sparse_vector) = match &parsed_query.vectors {
+        Some(vectors) => get_hybrid_query_vectors(vectors)?,
+        None => {
+            let dense_embedding_vectors_future = get_dense_vector(
+                data.query.clone().to_single_query()?,
+                None,
+                "query",
+                dataset_config.clone(),
+            );
 
-    let sparse_embedding_vector_future =
-        get_sparse_vector(data.query.clone().to_single_query()?, None, "query");
+            let sparse_query = get_synonym_map(dataset.id, redis_pool.clone())
+                .await
+                .expand_query(&data.query.clone().to_single_query()?);
 
-    let (dense_vector, sparse_vector) = futures::try_join!(
-        dense_embedding_vectors_future,
-        sparse_embedding_vector_future
-    )?;
+            let sparse_embedding_vector_future =
+                get_sparse_vector(sparse_query, None, "query", config.clone());
+
+            futures::try_join!(
+                dense_embedding_vectors_future,
+                sparse_embedding_vector_future
+            )?
+        }
+    };
 
     timer.add("computed dense embedding");
 
-    let semantic_future = retrieve_group_qdrant_points_query(
+    let hybrid_options = data.hybrid_options.clone().unwrap_or_default();
+    let fusion_strategy = hybrid_options.fusion_strategy.clone().unwrap_or_default();
+
+    let page = data.page.unwrap_or(1);
+    let page_size = data.page_size.unwrap_or(10);
+    // Fused rankings are paged after fusing, so each leg is retrieved from its first group.
+    let (leg_page, semantic_limit, fulltext_limit) = match fusion_strategy {
+        HybridFusionStrategy::CrossEncoder => (
+            page,
+            hybrid_options.semantic_prefetch.unwrap_or(page_size),
+            hybrid_options.fulltext_prefetch.unwrap_or(page_size),
+        ),
+        _ => (
+            1,
+            get_hybrid_fusion_window(
+                hybrid_options.semantic_prefetch.unwrap_or(page_size),
+                page,
+                page_size,
+            ),
+            get_hybrid_fusion_window(
+                hybrid_options.fulltext_prefetch.unwrap_or(page_size),
+                page,
+                page_size,
+            ),
+        ),
+    };
+
+    let semantic_future = retrieve_group_qdrant_points_with_count_query(
         VectorType::Dense(dense_vector),
-        data.page.unwrap_or(1),
+        leg_page,
         data.get_total_pages.unwrap_or(false),
         data.filters.clone(),
-        data.page_size.unwrap_or(10),
+        semantic_limit,
         None,
         data.group_size.unwrap_or(3),
         ParsedQueryTypes::Single(parsed_query.clone()),
@@ -2827,12 +3820,12 @@ pub async fn hybrid_search_over_groups(
         config,
     );
 
-    let full_text_future = retrieve_group_qdrant_points_query(
+    let full_text_future = retrieve_group_qdrant_points_with_count_query(
         VectorType::SpladeSparse(sparse_vector),
-        data.page.unwrap_or(1),
-        data.get_total_pages.unwrap_or(false),
+        leg_page,
+        false,
         data.filters.clone(),
-        data.page_size.unwrap_or(10),
+        fulltext_limit,
         None,
         data.group_size.unwrap_or(3),
         ParsedQueryTypes::Single(parsed_query.clone()),
@@ -2843,23 +3836,45 @@ pub async fn hybrid_search_over_groups(
 
     let (semantic_results, full_text_results) = futures::join!(semantic_future, full_text_future);
 
-    let semantic_results = semantic_results?;
+    let (semantic_results, semantic_count) = semantic_results?;
 
-    let full_text_results = full_text_results?;
+    let (full_text_results, _) = full_text_results?;
 
-    let combined_results = semantic_results
-        .clone()
-        .search_results
-        .iter()
-        .zip(full_text_results.search_results.iter())
-        .flat_map(|(x, y)| vec![x.clone(), y.clone()])
-        .unique_by(|chunk| chunk.group_id)
-        .collect::<Vec<GroupSearchResults>>();
+    let hybrid_legs = get_hybrid_legs(
+        &semantic_results
+            .iter()
+            .flat_map(|group| group.hits.clone())
+            .collect_vec(),
+        &full_text_results
+            .iter()
+            .flat_map(|group| group.hits.clone())
+            .collect_vec(),
+    );
+
+    let (combined_results, total_chunk_pages) = match fusion_strategy {
+        HybridFusionStrategy::CrossEncoder => (
+            semantic_results
+                .iter()
+                .zip(full_text_results.iter())
+                .flat_map(|(x, y)| vec![x.clone(), y.clone()])
+                .unique_by(|chunk| chunk.group_id)
+                .collect::<Vec<GroupSearchResults>>(),
+            (semantic_count as f64 / semantic_limit as f64).ceil() as i64,
+        ),
+        _ => (
+            page_fused_results(
+                fuse_hybrid_group_results(&semantic_results, &full_text_results, &hybrid_options),
+                page,
+                page_size,
+            ),
+            (semantic_count as f64 / page_size.max(1) as f64).ceil() as i64,
+        ),
+    };
 
     let combined_search_chunk_query_results = SearchOverGroupsQueryResult {
         search_results: combined_results,
         corrected_query: None,
-        total_chunk_pages: semantic_results.total_chunk_pages,
+        total_chunk_pages,
     };
 
     timer.add("fetched from qdrant");
@@ -2868,12 +3883,15 @@ pub async fn hybrid_search_over_groups(
         combined_search_chunk_query_results.clone(),
         &data,
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
     timer.add("fetched from postgres");
 
-    let mut reranked_chunks = if combined_result_chunks.group_chunks.len() > 20 {
+    let mut reranked_chunks = if fusion_strategy != HybridFusionStrategy::CrossEncoder {
+        combined_result_chunks.group_chunks.clone()
+    } else if combined_result_chunks.group_chunks.len() > 20 {
         let split_results = combined_result_chunks
             .group_chunks
             .chunks(20)
@@ -2881,7 +3899,7 @@ pub async fn hybrid_search_over_groups(
             .collect::<Vec<Vec<GroupScoreChunk>>>();
 
         let cross_encoder_results = cross_encoder_for_groups(
-            data.query.clone().to_single_query()?,
+            parsed_query.to_rerank_query()?,
             data.page_size.unwrap_or(10),
             split_results
                 .get(0)
@@ -2898,7 +3916,7 @@ pub async fn hybrid_search_over_groups(
             .collect::<Vec<GroupScoreChunk>>()
     } else {
         cross_encoder_for_groups(
-            data.query.clone().to_single_query()?,
+            parsed_query.to_rerank_query()?,
             data.page_size.unwrap_or(10),
             combined_result_chunks.group_chunks.clone(),
             config,
@@ -2906,6 +3924,10 @@ pub async fn hybrid_search_over_groups(
         .await?
     };
 
+    reranked_chunks.iter_mut().for_each(|group_score_chunk| {
+        set_hybrid_legs(&mut group_score_chunk.metadata, &hybrid_legs)
+    });
+
     timer.add("reranking");
 
     if let Some(score_threshold) = data.score_threshold {
@@ -2924,14 +3946,27 @@ pub async fn hybrid_search_over_groups(
         });
     }
 
+    if let Some(recency_bias) = &data.recency_bias {
+        reranked_chunks = rerank_groups_by_recency(reranked_chunks, recency_bias);
+    }
+
     reranked_chunks.truncate(data.page_size.unwrap_or(10) as usize);
 
     let result_chunks = DeprecatedSearchOverGroupsResponseBody {
         group_chunks: reranked_chunks,
         corrected_query: corrected_query.map(|c| c.query),
         total_chunk_pages: combined_search_chunk_query_results.total_chunk_pages,
+        facets: None,
+        merchandising_rule_ids: None,
     };
 
+    timer.add("reranking");
+
+    let result_chunks =
+        apply_merchandising_rules_over_groups(result_chunks, &data, dataset.id, pool).await?;
+
+    timer.add("applied merchandising rules");
+
     Ok(result_chunks)
 }
 
@@ -2952,8 +3987,13 @@ pub async fn autocomplete_chunks_query(
 
     if let Some(options) = &data.typo_options {
         timer.add("start correcting query");
-        let typo_corrected_query =
-            correct_query(parsed_query.clone(), dataset.id, redis_pool, options).await?;
+        let typo_corrected_query = correct_query(
+            parsed_query.clone(),
+            dataset.id,
+            redis_pool.clone(),
+            options,
+        )
+        .await?;
         if typo_corrected_query.corrected {
             corrected_query.clone_from(&typo_corrected_query.query);
         }
@@ -3052,6 +4092,7 @@ pub async fn autocomplete_chunks_query(
         None,
         &data.clone().into(),
         pool.clone(),
+        config.LANGUAGE,
     )
     .await?;
 
@@ -3085,6 +4126,7 @@ pub async fn autocomplete_chunks_query(
             .as_ref()
             .map(|d| d.location_bias)
             .unwrap_or_default(),
+        data.recency_bias,
     );
     reranked_chunks.extend(rerank_chunks(
         after_increase.to_vec(),
@@ -3101,6 +4143,7 @@ pub async fn autocomplete_chunks_query(
             .as_ref()
             .map(|d| d.location_bias)
             .unwrap_or_default(),
+        data.recency_bias,
     ));
 
     result_chunks.score_chunks = reranked_chunks;
@@ -3149,3 +4192,457 @@ pub async fn count_chunks_query(
 
     Ok(CountChunkQueryResponseBody { count })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::models::DecayDuration;
+    use actix_web::ResponseError;
+
+    fn get_scored_chunk(score: f64, time_stamp: Option<chrono::NaiveDateTime>) -> ScoreChunkDTO {
+        ScoreChunkDTO {
+            metadata: vec![ChunkMetadataTypes::ID(SlimChunkMetadata {
+                id: uuid::Uuid::new_v4(),
+                link: None,
+                qdrant_point_id: uuid::Uuid::new_v4(),
+                created_at: chrono::Utc::now().naive_utc(),
+                updated_at: chrono::Utc::now().naive_utc(),
+                tag_set: None,
+                metadata: None,
+                tracking_id: None,
+                time_stamp,
+                location: None,
+                dataset_id: uuid::Uuid::new_v4(),
+                weight: 0.0,
+                image_urls: None,
+                num_value: None,
+            })],
+            highlights: None,
+            score,
+            hybrid_legs: None,
+            merchandising_rule_ids: None,
+        }
+    }
+
+    fn get_recency_bias(origin: chrono::NaiveDateTime) -> RecencyBias {
+        RecencyBias {
+            decay_function: None,
+            scale: DecayDuration(chrono::Duration::days(30)),
+            offset: None,
+            origin: Some(origin),
+            decay: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_recency_bias_decays_older_chunks() {
+        let origin = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let mut chunks = vec![
+            get_scored_chunk(0.8, Some(origin)),
+            get_scored_chunk(0.8, Some(origin - chrono::Duration::days(30))),
+            get_scored_chunk(0.8, None),
+        ];
+
+        apply_recency_bias(&mut chunks, &get_recency_bias(origin));
+
+        let scores = chunks.iter().map(|chunk| chunk.score).collect_vec();
+        assert!((scores[0] - 0.8).abs() < 1e-9);
+        assert!((scores[1] - 0.4).abs() < 1e-9);
+        // Chunks without a time_stamp are not affected
+        assert!((scores[2] - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rerank_groups_by_recency_orders_chunks_and_groups() {
+        let origin = chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let get_group = |chunks: Vec<ScoreChunkDTO>| GroupScoreChunk {
+            group_id: uuid::Uuid::new_v4(),
+            group_name: None,
+            group_description: None,
+            group_created_at: chrono::Utc::now().naive_utc(),
+            group_updated_at: chrono::Utc::now().naive_utc(),
+            group_tracking_id: None,
+            group_metadata: None,
+            group_tag_set: None,
+            group_dataset_id: uuid::Uuid::new_v4(),
+            metadata: chunks,
+            file_id: None,
+        };
+        let old_group = get_group(vec![get_scored_chunk(
+            0.9,
+            Some(origin - chrono::Duration::days(60)),
+        )]);
+        let recent_group = get_group(vec![
+            get_scored_chunk(0.9, Some(origin - chrono::Duration::days(90))),
+            get_scored_chunk(0.5, Some(origin)),
+        ]);
+        let recent_group_id = recent_group.group_id;
+
+        let reranked =
+            rerank_groups_by_recency(vec![old_group, recent_group], &get_recency_bias(origin));
+
+        assert_eq!(reranked[0].group_id, recent_group_id);
+        assert!((reranked[0].metadata[0].score - 0.5).abs() < 1e-9);
+        assert!((reranked[0].metadata[1].score - 0.1125).abs() < 1e-9);
+        assert!((reranked[1].metadata[0].score - 0.225).abs() < 1e-9);
+    }
+
+    fn get_results(ids: &[uuid::Uuid], scores: &[f32]) -> Vec<SearchResult> {
+        ids.iter()
+            .zip(scores)
+            .map(|(point_id, score)| SearchResult {
+                score: *score,
+                point_id: *point_id,
+            })
+            .collect()
+    }
+
+    fn get_options(fusion_strategy: HybridFusionStrategy) -> HybridSearchOptions {
+        HybridSearchOptions {
+            fusion_strategy: Some(fusion_strategy),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rrf_ranks_points_found_by_both_legs_first() {
+        let ids = (0..3).map(|_| uuid::Uuid::new_v4()).collect_vec();
+        let semantic_results = get_results(&[ids[0], ids[1]], &[0.9, 0.8]);
+        let fulltext_results = get_results(&[ids[2], ids[1]], &[12.0, 3.0]);
+
+        let fused = fuse_hybrid_results(
+            &semantic_results,
+            &fulltext_results,
+            &get_options(HybridFusionStrategy::Rrf),
+        );
+
+        assert_eq!(fused.len(), 3);
+        assert_eq!(fused[0].point_id, ids[1]);
+        assert!(fused
+            .iter()
+            .all(|result| result.score > 0.0 && result.score <= 1.0));
+    }
+
+    #[test]
+    fn test_rrf_scores_are_scaled_to_one() {
+        let id = uuid::Uuid::new_v4();
+        let results = get_results(&[id], &[0.5]);
+
+        let fused = fuse_hybrid_results(
+            &results,
+            &results,
+            &HybridSearchOptions {
+                fusion_strategy: Some(HybridFusionStrategy::Rrf),
+                semantic_weight: Some(2.0),
+                fulltext_weight: Some(0.5),
+                rrf_k: Some(10.0),
+                ..Default::default()
+            },
+        );
+
+        assert!((fused[0].score - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rrf_weights_favor_the_heavier_leg() {
+        let ids = (0..2).map(|_| uuid::Uuid::new_v4()).collect_vec();
+        let semantic_results = get_results(&[ids[0]], &[0.9]);
+        let fulltext_results = get_results(&[ids[1]], &[10.0]);
+
+        let fused = fuse_hybrid_results(
+            &semantic_results,
+            &fulltext_results,
+            &HybridSearchOptions {
+                fusion_strategy: Some(HybridFusionStrategy::Rrf),
+                semantic_weight: Some(0.2),
+                fulltext_weight: Some(1.0),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(fused[0].point_id, ids[1]);
+    }
+
+    #[test]
+    fn test_linear_normalizes_each_leg_before_weighting() {
+        let ids = (0..3).map(|_| uuid::Uuid::new_v4()).collect_vec();
+        let semantic_results = get_results(&[ids[0], ids[1], ids[2]], &[0.9, 0.7, 0.8]);
+        let fulltext_results = get_results(&[ids[2], ids[1]], &[40.0, 10.0]);
+
+        let fused = fuse_hybrid_results(
+            &semantic_results,
+            &fulltext_results,
+            &get_options(HybridFusionStrategy::Linear),
+        );
+
+        let score_of = |id: uuid::Uuid| {
+            fused
+                .iter()
+                .find(|result| result.point_id == id)
+                .map(|result| result.score)
+                .unwrap_or_default()
+        };
+
+        assert_eq!(fused[0].point_id, ids[2]);
+        assert!((score_of(ids[2]) - 0.75).abs() < 1e-6);
+        assert!((score_of(ids[0]) - 0.5).abs() < 1e-6);
+        assert!(score_of(ids[1]).abs() < 1e-6);
+        assert!(fused
+            .iter()
+            .all(|result| result.score >= 0.0 && result.score <= 1.0));
+    }
+
+    #[test]
+    fn test_linear_with_zero_weights_does_not_divide_by_zero() {
+        let id = uuid::Uuid::new_v4();
+        let results = get_results(&[id], &[0.5]);
+
+        let fused = fuse_hybrid_results(
+            &results,
+            &[],
+            &HybridSearchOptions {
+                fusion_strategy: Some(HybridFusionStrategy::Linear),
+                semantic_weight: Some(0.0),
+                fulltext_weight: Some(0.0),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(fused[0].score, 0.0);
+    }
+
+    #[test]
+    fn test_fused_ties_keep_the_order_points_were_first_seen() {
+        let ids = (0..3).map(|_| uuid::Uuid::new_v4()).collect_vec();
+        let semantic_results = get_results(&[ids[0], ids[1]], &[0.9, 0.8]);
+        let fulltext_results = get_results(&[ids[2], ids[1]], &[9.0, 8.0]);
+
+        let fused = fuse_hybrid_results(
+            &semantic_results,
+            &fulltext_results,
+            &get_options(HybridFusionStrategy::Rrf),
+        );
+
+        assert_eq!(
+            fused.iter().map(|result| result.point_id).collect_vec(),
+            vec![ids[1], ids[0], ids[2]]
+        );
+        assert!((fused[0].score - 61.0 / 62.0).abs() < 1e-6);
+        assert!((fused[1].score - 0.5).abs() < 1e-6);
+    }
+
+    fn get_vector_fields_query() -> QdrantSearchQuery {
+        QdrantSearchQuery {
+            filter: Filter::default(),
+            limit: 10,
+            score_threshold: None,
+            rerank_by: Box::new(None),
+            sort_by: None,
+            vector: VectorType::Dense(vec![0.1, 0.2]),
+        }
+    }
+
+    #[test]
+    fn test_vector_fields_query_their_slot() {
+        let config = DatasetConfiguration {
+            VECTOR_FIELDS: vec!["title".to_string(), "summary".to_string()],
+            ..Default::default()
+        };
+        let vector_fields = HashMap::from([
+            ("chunk_html".to_string(), 1.0),
+            ("summary".to_string(), 0.5),
+        ]);
+
+        let queries =
+            get_vector_field_queries(&get_vector_fields_query(), &vector_fields, &config).unwrap();
+
+        assert_eq!(queries.len(), 2);
+        for (query, weight) in queries {
+            match query.vector {
+                VectorType::Dense(vector) => {
+                    assert_eq!(weight, 1.0);
+                    assert_eq!(vector, vec![0.1, 0.2]);
+                }
+                VectorType::FieldDense(slot, vector) => {
+                    assert_eq!(weight, 0.5);
+                    assert_eq!(slot, 1);
+                    assert_eq!(vector, vec![0.1, 0.2]);
+                }
+                _ => panic!("vector fields must be queried with dense vectors"),
+            }
+            assert_eq!(query.limit, 10);
+        }
+    }
+
+    #[test]
+    fn test_invalid_vector_fields_are_rejected() {
+        let config = DatasetConfiguration {
+            VECTOR_FIELDS: vec!["title".to_string()],
+            ..Default::default()
+        };
+        let sparse_query = QdrantSearchQuery {
+            vector: VectorType::SpladeSparse(vec![(1, 0.5)]),
+            ..get_vector_fields_query()
+        };
+
+        for (query, vector_fields) in [
+            (
+                get_vector_fields_query(),
+                HashMap::from([("body".to_string(), 1.0)]),
+            ),
+            (
+                get_vector_fields_query(),
+                HashMap::from([("title".to_string(), f32::INFINITY)]),
+            ),
+            (sparse_query, HashMap::from([("title".to_string(), 1.0)])),
+        ] {
+            let err = get_vector_field_queries(&query, &vector_fields, &config).unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_vector_field_scores_are_weighted_and_summed() {
+        let ids = (0..3).map(|_| uuid::Uuid::new_v4()).collect_vec();
+        let title_results = get_results(&[ids[0], ids[1]], &[0.9, 0.6]);
+        let summary_results = get_results(&[ids[1], ids[2], ids[1]], &[0.8, 0.7, 0.1]);
+
+        let combined = combine_vector_field_results(
+            &[
+                (title_results.as_slice(), 1.0),
+                (summary_results.as_slice(), 0.5),
+            ],
+            2,
+        );
+
+        assert_eq!(
+            combined.iter().map(|result| result.point_id).collect_vec(),
+            vec![ids[1], ids[0]]
+        );
+        assert!((combined[0].score - 1.0).abs() < 1e-6);
+        assert!((combined[1].score - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_hybrid_weights_are_checked() {
+        let with_weights =
+            |fusion_strategy, semantic_weight, fulltext_weight| HybridSearchOptions {
+                fusion_strategy: Some(fusion_strategy),
+                semantic_weight,
+                fulltext_weight,
+                ..Default::default()
+            };
+
+        assert!(check_hybrid_search_options(&HybridSearchOptions::default()).is_ok());
+        assert!(check_hybrid_search_options(&with_weights(
+            HybridFusionStrategy::Linear,
+            Some(0.0),
+            Some(1.0)
+        ))
+        .is_ok());
+        assert!(check_hybrid_search_options(&with_weights(
+            HybridFusionStrategy::CrossEncoder,
+            Some(-1.0),
+            Some(0.0)
+        ))
+        .is_ok());
+
+        for options in [
+            with_weights(HybridFusionStrategy::Linear, Some(-0.5), Some(1.0)),
+            with_weights(HybridFusionStrategy::Linear, Some(0.0), Some(0.0)),
+            with_weights(HybridFusionStrategy::Linear, Some(f32::NAN), None),
+            with_weights(HybridFusionStrategy::Rrf, None, Some(-1.0)),
+            with_weights(HybridFusionStrategy::Rrf, Some(0.0), Some(0.0)),
+        ] {
+            let err = check_hybrid_search_options(&options).unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_fused_results_are_paged_after_fusion() {
+        let ids = (0..6).map(|_| uuid::Uuid::new_v4()).collect_vec();
+        let semantic_results = get_results(&ids, &[0.9, 0.8, 0.7, 0.6, 0.5, 0.4]);
+        let fulltext_results = get_results(&[ids[5], ids[4]], &[9.0, 8.0]);
+
+        let fused = fuse_hybrid_results(
+            &semantic_results,
+            &fulltext_results,
+            &get_options(HybridFusionStrategy::Rrf),
+        );
+
+        let first_page = page_fused_results(fused.clone(), 1, 2);
+        let second_page = page_fused_results(fused.clone(), 2, 2);
+
+        assert_eq!(first_page.len(), 2);
+        assert_eq!(first_page[0].point_id, fused[0].point_id);
+        assert_eq!(second_page[0].point_id, fused[2].point_id);
+        assert!(page_fused_results(fused, 4, 2).is_empty());
+        assert_eq!(get_hybrid_fusion_window(10, 3, 10), 30);
+        assert_eq!(get_hybrid_fusion_window(50, 2, 10), 50);
+    }
+
+    #[test]
+    fn test_dense_query_vector_must_match_the_embedding_size() {
+        assert!(check_dense_query_vector(&[0.1, 0.2, 0.3], 3).is_ok());
+
+        for dense_vector in [vec![0.1, 0.2], vec![0.1, 0.2, 0.3, 0.4], vec![]] {
+            let err = check_dense_query_vector(&dense_vector, 3).unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_query_vectors_must_be_finite() {
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let err = check_dense_query_vector(&[0.1, value, 0.3], 3).unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+
+            let err = get_sparse_query_vector(SparseVector {
+                indices: vec![1, 2],
+                values: vec![0.5, value],
+            })
+            .unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_sparse_query_vector_pairs_indices_with_values() {
+        assert_eq!(
+            get_sparse_query_vector(SparseVector {
+                indices: vec![3, 7],
+                values: vec![0.5, 1.5],
+            })
+            .unwrap(),
+            vec![(3, 0.5), (7, 1.5)]
+        );
+
+        for (indices, values) in [(vec![3, 7], vec![0.5]), (vec![3], vec![0.5, 1.5])] {
+            let err = get_sparse_query_vector(SparseVector { indices, values }).unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_missing_query_chunk_is_a_bad_request() {
+        let err = query_chunk_not_found(
+            ServiceError::NotFound("Chunk with id not found in the specified dataset".to_string()),
+            "missing-tracking-id".to_string(),
+        );
+        assert_eq!(err.error_response().status(), 400);
+        assert!(err.to_string().contains("missing-tracking-id"));
+
+        let err = query_chunk_not_found(
+            ServiceError::InternalServerError("Failed to get postgres connection".to_string()),
+            uuid::Uuid::nil().to_string(),
+        );
+        assert_eq!(err.error_response().status(), 500);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{errors::ServiceError, operators::user_operator::SECRET_KEY};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose, Engine as _};
+
+const NONCE_LENGTH: usize = 12;
+
+fn get_secret_cipher() -> Aes256Gcm {
+    let key = blake3::derive_key("trieve stored secrets v1", SECRET_KEY.as_bytes());
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+/// Encrypts a secret such as connector credentials or an OIDC client secret before it is stored. The key is derived from SECRET_KEY.
+pub fn encrypt_secret(secret: &str) -> Result<String, ServiceError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = get_secret_cipher()
+        .encrypt(&nonce, secret.as_bytes())
+        .map_err(|_| ServiceError::InternalServerError("Failed to encrypt secret".to_string()))?;
+
+    Ok(general_purpose::STANDARD.encode([nonce.as_slice(), ciphertext.as_slice()].concat()))
+}
+
+pub fn decrypt_secret(encrypted_secret: &str) -> Result<String, ServiceError> {
+    let invalid_secret =
+        || ServiceError::InternalServerError("Failed to decrypt secret".to_string());
+
+    let data = general_purpose::STANDARD
+        .decode(encrypted_secret)
+        .map_err(|_| invalid_secret())?;
+    if data.len() <= NONCE_LENGTH {
+        return Err(invalid_secret());
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+    let secret = get_secret_cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| invalid_secret())?;
+
+    String::from_utf8(secret).map_err(|_| invalid_secret())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_secret_round_trip() {
+        let encrypted = encrypt_secret("super secret").unwrap();
+
+        assert_ne!(encrypted, "super secret");
+        assert_ne!(encrypted, encrypt_secret("super secret").unwrap());
+        assert_eq!(decrypt_secret(&encrypted).unwrap(), "super secret");
+    }
+
+    #[test]
+    fn test_tampered_secret_is_rejected() {
+        let mut data = general_purpose::STANDARD
+            .decode(encrypt_secret("super secret").unwrap())
+            .unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+
+        assert!(decrypt_secret(&general_purpose::STANDARD.encode(data)).is_err());
+        assert!(decrypt_secret("not base64!").is_err());
+    }
+}
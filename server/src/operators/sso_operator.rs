@@ -0,0 +1,908 @@
+use crate::{
+    data::models::{
+        Organization, OrganizationDomain, OrganizationSsoConfig, OrganizationSsoIdentity, Pool,
+        RedisPool, SsoProvider, StripePlan, User, UserOrganization,
+    },
+    errors::ServiceError,
+    get_env,
+    operators::{
+        organization_operator::{get_org_from_id_query, get_user_org_count},
+        secret_operator::decrypt_secret,
+        user_operator::{
+            add_user_to_organization, create_user_query, get_user_by_email_query,
+            get_user_by_id_query,
+        },
+    },
+};
+use actix_web::web;
+use diesel::prelude::*;
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    TokioAsyncResolver,
+};
+use oauth2::{reqwest::async_http_client, ClientSecret, RedirectUrl};
+use openidconnect::{
+    core::{CoreClient, CoreProviderMetadata},
+    ClientId, IssuerUrl,
+};
+#[cfg(feature = "saml")]
+use samael::{
+    metadata::EntityDescriptor,
+    schema::Assertion,
+    service_provider::{ServiceProvider, ServiceProviderBuilder},
+};
+
+/// SAML attributes identity providers commonly put the user's email in when the NameID is not an email
+#[cfg(feature = "saml")]
+const SAML_EMAIL_ATTRIBUTES: [&str; 3] = [
+    "email",
+    "mail",
+    "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/emailaddress",
+];
+
+/// SAML attributes identity providers commonly put the user's display name in
+#[cfg(feature = "saml")]
+const SAML_NAME_ATTRIBUTES: [&str; 3] = [
+    "name",
+    "displayName",
+    "http://schemas.xmlsoap.org/ws/2005/05/identity/claims/name",
+];
+
+/// Lowercased domain of an email address, None if the email has no domain
+pub fn get_email_domain(email: &str) -> Option<String> {
+    email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim().to_lowercase())
+        .filter(|domain| !domain.is_empty())
+}
+
+/// Lowercased domain without a trailing dot, None if it is not a valid domain name
+pub fn normalize_org_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+
+    let is_valid = domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+
+    is_valid.then_some(domain)
+}
+
+fn is_org_member(user_orgs: &[UserOrganization], organization_id: uuid::Uuid) -> bool {
+    user_orgs
+        .iter()
+        .any(|user_org| user_org.organization_id == organization_id)
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_sso_config_query(
+    organization_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Option<OrganizationSsoConfig>, ServiceError> {
+    use crate::data::schema::organization_sso_configs::dsl as sso_configs_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let config = sso_configs_columns::organization_sso_configs
+        .filter(sso_configs_columns::organization_id.eq(organization_id))
+        .select(OrganizationSsoConfig::as_select())
+        .first::<OrganizationSsoConfig>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| {
+            log::error!("Error loading sso config: {:?}", e);
+            ServiceError::BadRequest("Error loading sso config".to_string())
+        })?;
+
+    Ok(config)
+}
+
+/// Creates or replaces the SSO configuration of the organization, optionally changing whether it is enforced
+#[tracing::instrument(skip(pool, config))]
+pub async fn set_sso_config_query(
+    config: OrganizationSsoConfig,
+    sso_only: Option<bool>,
+    pool: web::Data<Pool>,
+) -> Result<OrganizationSsoConfig, ServiceError> {
+    use crate::data::schema::organization_sso_configs::dsl as sso_configs_columns;
+    use crate::data::schema::organizations::dsl as organizations_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            let config = diesel::insert_into(sso_configs_columns::organization_sso_configs)
+                .values(&config)
+                .on_conflict(sso_configs_columns::organization_id)
+                .do_update()
+                .set((
+                    sso_configs_columns::provider.eq(&config.provider),
+                    sso_configs_columns::oidc_issuer_url.eq(&config.oidc_issuer_url),
+                    sso_configs_columns::oidc_client_id.eq(&config.oidc_client_id),
+                    sso_configs_columns::encrypted_oidc_client_secret
+                        .eq(&config.encrypted_oidc_client_secret),
+                    sso_configs_columns::saml_idp_metadata.eq(&config.saml_idp_metadata),
+                    sso_configs_columns::updated_at.eq(diesel::dsl::now),
+                ))
+                .get_result::<OrganizationSsoConfig>(conn)
+                .await?;
+
+            if let Some(sso_only) = sso_only {
+                diesel::update(organizations_columns::organizations)
+                    .filter(organizations_columns::id.eq(config.organization_id))
+                    .set(organizations_columns::sso_only.eq(sso_only))
+                    .execute(conn)
+                    .await?;
+            }
+
+            Ok(config)
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Error setting sso config: {:?}", e);
+        ServiceError::BadRequest("Error setting sso config".to_string())
+    })
+}
+
+/// Deletes the SSO configuration of the organization and stops enforcing SSO so members can sign in again
+#[tracing::instrument(skip(pool))]
+pub async fn delete_sso_config_query(
+    organization_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::organization_sso_configs::dsl as sso_configs_columns;
+    use crate::data::schema::organizations::dsl as organizations_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            diesel::delete(
+                sso_configs_columns::organization_sso_configs
+                    .filter(sso_configs_columns::organization_id.eq(organization_id)),
+            )
+            .execute(conn)
+            .await?;
+
+            diesel::update(organizations_columns::organizations)
+                .filter(organizations_columns::id.eq(organization_id))
+                .set(organizations_columns::sso_only.eq(false))
+                .execute(conn)
+                .await?;
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Error deleting sso config: {:?}", e);
+        ServiceError::BadRequest("Error deleting sso config".to_string())
+    })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn create_org_domain_query(
+    organization_id: uuid::Uuid,
+    domain: String,
+    default_role: i32,
+    pool: web::Data<Pool>,
+) -> Result<OrganizationDomain, ServiceError> {
+    use crate::data::schema::organization_domains::dsl as organization_domains_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let domain = OrganizationDomain::from_details(organization_id, domain, default_role);
+
+    diesel::insert_into(organization_domains_columns::organization_domains)
+        .values(&domain)
+        .get_result::<OrganizationDomain>(&mut conn)
+        .await
+        .map_err(|err| match err {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => ServiceError::BadRequest(
+                "Domain has already been added to the organization".to_string(),
+            ),
+            _ => {
+                log::error!("Error creating organization domain: {:?}", err);
+                ServiceError::BadRequest("Error creating organization domain".to_string())
+            }
+        })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_org_domains_query(
+    organization_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Vec<OrganizationDomain>, ServiceError> {
+    use crate::data::schema::organization_domains::dsl as organization_domains_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    organization_domains_columns::organization_domains
+        .filter(organization_domains_columns::organization_id.eq(organization_id))
+        .order_by(organization_domains_columns::created_at.asc())
+        .select(OrganizationDomain::as_select())
+        .load::<OrganizationDomain>(&mut conn)
+        .await
+        .map_err(|e| {
+            log::error!("Error loading organization domains: {:?}", e);
+            ServiceError::BadRequest("Error loading organization domains".to_string())
+        })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn delete_org_domain_query(
+    organization_id: uuid::Uuid,
+    domain_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::organization_domains::dsl as organization_domains_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let deleted = diesel::delete(
+        organization_domains_columns::organization_domains
+            .filter(organization_domains_columns::id.eq(domain_id))
+            .filter(organization_domains_columns::organization_id.eq(organization_id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|e| {
+        log::error!("Error deleting organization domain: {:?}", e);
+        ServiceError::BadRequest("Error deleting organization domain".to_string())
+    })?;
+
+    if deleted == 0 {
+        return Err(ServiceError::NotFound(
+            "Domain not found in the organization".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn is_verification_record_present(
+    records: impl IntoIterator<Item = String>,
+    domain: &OrganizationDomain,
+) -> bool {
+    records
+        .into_iter()
+        .any(|record| record.trim() == domain.verification_token)
+}
+
+/// Whether the TXT records of `_trieve-verification.<domain>` contain the verification token
+async fn domain_has_verification_record(domain: &OrganizationDomain) -> bool {
+    let resolver = TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default());
+
+    match resolver
+        .txt_lookup(format!("_trieve-verification.{}.", domain.domain))
+        .await
+    {
+        Ok(records) => {
+            is_verification_record_present(records.iter().map(|record| record.to_string()), domain)
+        }
+        Err(err) => {
+            log::info!(
+                "Failed to look up verification record for {}: {:?}",
+                domain.domain,
+                err
+            );
+            false
+        }
+    }
+}
+
+/// Marks the domain as verified once its verification TXT record is found
+#[tracing::instrument(skip(pool))]
+pub async fn verify_org_domain_query(
+    organization_id: uuid::Uuid,
+    domain_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<OrganizationDomain, ServiceError> {
+    use crate::data::schema::organization_domains::dsl as organization_domains_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let domain = organization_domains_columns::organization_domains
+        .filter(organization_domains_columns::id.eq(domain_id))
+        .filter(organization_domains_columns::organization_id.eq(organization_id))
+        .select(OrganizationDomain::as_select())
+        .first::<OrganizationDomain>(&mut conn)
+        .await
+        .map_err(|_| ServiceError::NotFound("Domain not found in the organization".to_string()))?;
+
+    if domain.verified_at.is_some() {
+        return Ok(domain);
+    }
+
+    if !domain_has_verification_record(&domain).await {
+        return Err(ServiceError::BadRequest(format!(
+            "No TXT record with the value {} was found on _trieve-verification.{}, DNS changes can take a while to propagate",
+            domain.verification_token, domain.domain
+        )));
+    }
+
+    diesel::update(organization_domains_columns::organization_domains)
+        .filter(organization_domains_columns::id.eq(domain.id))
+        .set(organization_domains_columns::verified_at.eq(diesel::dsl::now))
+        .get_result::<OrganizationDomain>(&mut conn)
+        .await
+        .map_err(|err| match err {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => ServiceError::BadRequest(
+                "Domain has already been verified by another organization".to_string(),
+            ),
+            _ => {
+                log::error!("Error verifying organization domain: {:?}", err);
+                ServiceError::BadRequest("Error verifying organization domain".to_string())
+            }
+        })
+}
+
+/// The verified domain matching the domain of the email along with the organization which verified it
+#[tracing::instrument(skip(pool))]
+pub async fn get_verified_domain_for_email_query(
+    email: &str,
+    pool: web::Data<Pool>,
+) -> Result<Option<(OrganizationDomain, Organization)>, ServiceError> {
+    use crate::data::schema::organization_domains::dsl as organization_domains_columns;
+    use crate::data::schema::organizations::dsl as organizations_columns;
+
+    let Some(email_domain) = get_email_domain(email) else {
+        return Ok(None);
+    };
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    organization_domains_columns::organization_domains
+        .inner_join(organizations_columns::organizations)
+        .filter(organization_domains_columns::domain.eq(email_domain))
+        .filter(organization_domains_columns::verified_at.is_not_null())
+        .filter(organizations_columns::deleted.eq(0))
+        .select((OrganizationDomain::as_select(), Organization::as_select()))
+        .first::<(OrganizationDomain, Organization)>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| {
+            log::error!("Error loading verified domain: {:?}", e);
+            ServiceError::BadRequest("Error loading verified domain".to_string())
+        })
+}
+
+pub async fn ensure_org_has_user_capacity(
+    organization_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    let org_plan_sub = get_org_from_id_query(organization_id, pool.clone()).await?;
+    let user_org_count = get_user_org_count(organization_id, pool).await?;
+
+    if user_org_count
+        >= org_plan_sub
+            .plan
+            .unwrap_or(StripePlan::default())
+            .user_count
+    {
+        return Err(ServiceError::BadRequest(
+            "User limit reached for organization, must upgrade plan to add more users".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Adds the user to the organization which verified the domain of their email with the domain's default role, if they are not a member of it yet. Returns whether the user was added.
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn join_verified_domain_org_query(
+    user_id: uuid::Uuid,
+    email: &str,
+    user_orgs: &[UserOrganization],
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<bool, ServiceError> {
+    let Some((domain, _)) = get_verified_domain_for_email_query(email, pool.clone()).await? else {
+        return Ok(false);
+    };
+
+    if is_org_member(user_orgs, domain.organization_id) {
+        return Ok(false);
+    }
+
+    ensure_org_has_user_capacity(domain.organization_id, pool.clone()).await?;
+
+    add_user_to_organization(
+        None,
+        None,
+        UserOrganization::from_details(user_id, domain.organization_id, domain.default_role.into()),
+        pool,
+        redis_pool,
+    )
+    .await?;
+
+    Ok(true)
+}
+
+/// The SSO identity the identity provider of the organization gave the subject, if they signed in through it before
+#[tracing::instrument(skip(pool))]
+pub async fn get_sso_identity_query(
+    organization_id: uuid::Uuid,
+    subject: &str,
+    pool: web::Data<Pool>,
+) -> Result<Option<OrganizationSsoIdentity>, ServiceError> {
+    use crate::data::schema::organization_sso_identities::dsl as sso_identities_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    sso_identities_columns::organization_sso_identities
+        .filter(sso_identities_columns::organization_id.eq(organization_id))
+        .filter(sso_identities_columns::subject.eq(subject))
+        .select(OrganizationSsoIdentity::as_select())
+        .first::<OrganizationSsoIdentity>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| {
+            log::error!("Error loading sso identity: {:?}", e);
+            ServiceError::BadRequest("Error loading sso identity".to_string())
+        })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn create_sso_identity_query(
+    identity: OrganizationSsoIdentity,
+    pool: web::Data<Pool>,
+) -> Result<OrganizationSsoIdentity, ServiceError> {
+    use crate::data::schema::organization_sso_identities::dsl as sso_identities_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::insert_into(sso_identities_columns::organization_sso_identities)
+        .values(&identity)
+        .get_result::<OrganizationSsoIdentity>(&mut conn)
+        .await
+        .map_err(|err| match err {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => ServiceError::BadRequest(
+                "The user is already linked to another identity of the organization's identity provider"
+                    .to_string(),
+            ),
+            _ => {
+                log::error!("Error creating sso identity: {:?}", err);
+                ServiceError::BadRequest("Error creating sso identity".to_string())
+            }
+        })
+}
+
+/// The verified domain of the email, which has to belong to the organization whose identity provider signed the user in
+fn check_sso_email_domain(
+    verified_domain: Option<OrganizationDomain>,
+    organization_id: uuid::Uuid,
+    email: &str,
+) -> Result<OrganizationDomain, ServiceError> {
+    verified_domain
+        .filter(|domain| domain.organization_id == organization_id)
+        .ok_or_else(|| {
+            ServiceError::BadRequest(format!(
+                "The domain of {} is not verified by the organization",
+                email
+            ))
+        })
+}
+
+/// An existing account is only linked to the organization's identity provider if it is already a member of the organization
+fn check_existing_user_can_link_sso(
+    user_orgs: &[UserOrganization],
+    organization_id: uuid::Uuid,
+    email: &str,
+) -> Result<(), ServiceError> {
+    if !is_org_member(user_orgs, organization_id) {
+        return Err(ServiceError::BadRequest(format!(
+            "An account with the email {} already exists and is not a member of the organization, an owner has to add it before it can sign in through SSO",
+            email
+        )));
+    }
+
+    Ok(())
+}
+
+/// Gets or creates the user an identity provider of the organization signed in. Users are found by the subject the provider gave them. The email is only used to link an existing member of the organization the first time they sign in through SSO, an account which is not a member is never taken over. Only emails at a domain verified by the organization are accepted.
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn provision_sso_user_query(
+    organization_id: uuid::Uuid,
+    subject: String,
+    email: String,
+    name: String,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(User, Vec<UserOrganization>, Vec<Organization>), ServiceError> {
+    let domain = check_sso_email_domain(
+        get_verified_domain_for_email_query(&email, pool.clone())
+            .await?
+            .map(|(domain, _)| domain),
+        organization_id,
+        &email,
+    )?;
+
+    if let Some(identity) = get_sso_identity_query(organization_id, &subject, pool.clone()).await? {
+        let (user, user_orgs, _) = get_user_by_id_query(&identity.user_id, pool.clone()).await?;
+        join_verified_domain_org_query(user.id, &email, &user_orgs, pool.clone(), redis_pool)
+            .await?;
+
+        return get_user_by_id_query(&user.id, pool).await;
+    }
+
+    match get_user_by_email_query(&email, pool.clone()).await? {
+        Some(user) => {
+            let (_, user_orgs, _) = get_user_by_id_query(&user.id, pool.clone()).await?;
+            check_existing_user_can_link_sso(&user_orgs, organization_id, &email)?;
+
+            create_sso_identity_query(
+                OrganizationSsoIdentity::from_details(organization_id, user.id, subject),
+                pool.clone(),
+            )
+            .await?;
+
+            get_user_by_id_query(&user.id, pool).await
+        }
+        None => {
+            ensure_org_has_user_capacity(organization_id, pool.clone()).await?;
+
+            let (user, user_orgs, orgs) = create_user_query(
+                format!("sso|{}|{}", organization_id, subject),
+                email,
+                Some(name),
+                domain.default_role.into(),
+                organization_id,
+                pool.clone(),
+            )
+            .await?;
+
+            create_sso_identity_query(
+                OrganizationSsoIdentity::from_details(organization_id, user.id, subject),
+                pool,
+            )
+            .await?;
+
+            Ok((user, user_orgs, orgs))
+        }
+    }
+}
+
+/// URL the organization's OIDC provider redirects back to, it has to be allowed in the provider's client settings
+pub fn get_sso_oidc_redirect_url() -> String {
+    let base_server_url = get_env!(
+        "BASE_SERVER_URL",
+        "Server hostname for OpenID provider must be set"
+    );
+
+    format!("{}/api/auth/sso/callback", base_server_url)
+}
+
+#[tracing::instrument(skip(config))]
+pub async fn build_sso_oidc_client(
+    config: &OrganizationSsoConfig,
+) -> Result<CoreClient, ServiceError> {
+    let (Some(issuer_url), Some(client_id), Some(encrypted_client_secret)) = (
+        config.oidc_issuer_url.clone(),
+        config.oidc_client_id.clone(),
+        config.encrypted_oidc_client_secret.as_deref(),
+    ) else {
+        return Err(ServiceError::BadRequest(
+            "OIDC SSO configuration is missing the issuer url, client id or client secret"
+                .to_string(),
+        ));
+    };
+
+    let client_secret = decrypt_secret(encrypted_client_secret)?;
+
+    let issuer_url = IssuerUrl::new(issuer_url)
+        .map_err(|_| ServiceError::BadRequest("OIDC issuer url is not a valid URL".to_string()))?;
+
+    let meta_data = CoreProviderMetadata::discover_async(issuer_url, async_http_client)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to discover OpenID provider for SSO: {:?}", err);
+            ServiceError::BadRequest("Failed to discover the OIDC provider".to_string())
+        })?;
+
+    Ok(CoreClient::from_provider_metadata(
+        meta_data,
+        ClientId::new(client_id),
+        Some(ClientSecret::new(client_secret)),
+    )
+    .set_redirect_uri(
+        RedirectUrl::new(get_sso_oidc_redirect_url())
+            .map_err(|_| ServiceError::BadRequest("SSO redirect url is invalid".to_string()))?,
+    ))
+}
+
+#[cfg(feature = "saml")]
+pub fn parse_saml_idp_metadata(metadata: &str) -> Result<EntityDescriptor, ServiceError> {
+    samael::metadata::de::from_str::<EntityDescriptor>(metadata).map_err(|err| {
+        ServiceError::BadRequest(format!(
+            "SAML identity provider metadata is invalid: {}",
+            err
+        ))
+    })
+}
+
+/// Trieve's side of the SAML exchange for the organization. Its entity id is the URL its metadata is served from.
+#[cfg(feature = "saml")]
+pub fn build_saml_service_provider(
+    config: &OrganizationSsoConfig,
+) -> Result<ServiceProvider, ServiceError> {
+    let base_server_url = get_env!(
+        "BASE_SERVER_URL",
+        "Server hostname for OpenID provider must be set"
+    );
+
+    let idp_metadata =
+        parse_saml_idp_metadata(config.saml_idp_metadata.as_deref().ok_or_else(|| {
+            ServiceError::BadRequest(
+                "SAML SSO configuration is missing the identity provider metadata".to_string(),
+            )
+        })?)?;
+
+    let metadata_url = format!(
+        "{}/api/auth/sso/saml/{}/metadata",
+        base_server_url, config.organization_id
+    );
+
+    ServiceProviderBuilder::default()
+        .entity_id(metadata_url.clone())
+        .metadata_url(metadata_url)
+        .acs_url(format!(
+            "{}/api/auth/sso/saml/{}/acs",
+            base_server_url, config.organization_id
+        ))
+        .idp_metadata(idp_metadata)
+        .allow_idp_initiated(false)
+        .build()
+        .map_err(|err| {
+            log::error!("Failed to build SAML service provider: {:?}", err);
+            ServiceError::BadRequest("Failed to build SAML service provider".to_string())
+        })
+}
+
+#[cfg(feature = "saml")]
+fn get_saml_attribute(assertion: &Assertion, names: &[&str]) -> Option<String> {
+    assertion
+        .attribute_statements
+        .iter()
+        .flatten()
+        .flat_map(|statement| statement.attributes.iter())
+        .find(|attribute| {
+            attribute
+                .name
+                .as_deref()
+                .is_some_and(|name| names.contains(&name))
+                || attribute
+                    .friendly_name
+                    .as_deref()
+                    .is_some_and(|name| names.contains(&name))
+        })
+        .and_then(|attribute| {
+            attribute
+                .values
+                .iter()
+                .find_map(|value| value.value.clone())
+        })
+}
+
+/// Subject, email and name of the user a verified SAML assertion is about
+#[cfg(feature = "saml")]
+pub fn get_saml_user_details(
+    assertion: &Assertion,
+) -> Result<(String, String, String), ServiceError> {
+    let subject = assertion
+        .subject
+        .as_ref()
+        .and_then(|subject| subject.name_id.as_ref())
+        .map(|name_id| name_id.value.clone())
+        .ok_or_else(|| ServiceError::BadRequest("SAML assertion has no subject".to_string()))?;
+
+    let email = get_saml_attribute(assertion, &SAML_EMAIL_ATTRIBUTES)
+        .or_else(|| subject.contains('@').then(|| subject.clone()))
+        .ok_or_else(|| {
+            ServiceError::BadRequest("SAML assertion does not contain an email".to_string())
+        })?;
+
+    let name = get_saml_attribute(assertion, &SAML_NAME_ATTRIBUTES).unwrap_or(email.clone());
+
+    Ok((subject, email, name))
+}
+
+/// SAML pulls in libxmlsec1, so servers built without the saml feature only support OIDC
+#[cfg(not(feature = "saml"))]
+pub fn saml_not_enabled_error() -> ServiceError {
+    ServiceError::BadRequest(
+        "SAML SSO is not enabled on this server, it has to be built with the saml feature"
+            .to_string(),
+    )
+}
+
+/// Checks that the configuration has everything its provider needs to sign users in
+pub fn validate_sso_config(config: &OrganizationSsoConfig) -> Result<(), ServiceError> {
+    match SsoProvider::from(config.provider.clone()) {
+        SsoProvider::Oidc => {
+            if config.oidc_issuer_url.is_none()
+                || config.oidc_client_id.is_none()
+                || config.encrypted_oidc_client_secret.is_none()
+            {
+                return Err(ServiceError::BadRequest(
+                    "oidc_issuer_url, oidc_client_id and oidc_client_secret are required for OIDC SSO"
+                        .to_string(),
+                ));
+            }
+        }
+        #[cfg(feature = "saml")]
+        SsoProvider::Saml => {
+            build_saml_service_provider(config)?;
+        }
+        #[cfg(not(feature = "saml"))]
+        SsoProvider::Saml => return Err(saml_not_enabled_error()),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::models::UserRole;
+    use actix_web::ResponseError;
+
+    fn verified_domain(organization_id: uuid::Uuid, domain: &str) -> OrganizationDomain {
+        OrganizationDomain {
+            verified_at: Some(chrono::Utc::now().naive_local()),
+            ..OrganizationDomain::from_details(organization_id, domain.to_string(), 0)
+        }
+    }
+
+    #[test]
+    fn test_email_domains_are_lowercased() {
+        assert_eq!(
+            get_email_domain("Jane.Doe@Acme.COM"),
+            Some("acme.com".to_string())
+        );
+        assert_eq!(
+            get_email_domain("jane@team@acme.com"),
+            Some("acme.com".to_string())
+        );
+        assert_eq!(get_email_domain("jane@"), None);
+        assert_eq!(get_email_domain("jane"), None);
+    }
+
+    #[test]
+    fn test_org_domains_are_normalized() {
+        assert_eq!(
+            normalize_org_domain(" Acme.com. "),
+            Some("acme.com".to_string())
+        );
+        assert_eq!(
+            normalize_org_domain("eu-west.acme.com"),
+            Some("eu-west.acme.com".to_string())
+        );
+        assert_eq!(normalize_org_domain("localhost"), None);
+        assert_eq!(normalize_org_domain("acme.com/login"), None);
+        assert_eq!(normalize_org_domain("jane@acme.com"), None);
+        assert_eq!(normalize_org_domain(""), None);
+    }
+
+    #[test]
+    fn test_verification_record_must_match_the_token() {
+        let domain = verified_domain(uuid::Uuid::new_v4(), "acme.com");
+
+        assert!(is_verification_record_present(
+            vec![
+                "v=spf1 -all".to_string(),
+                format!(" {} ", domain.verification_token)
+            ],
+            &domain
+        ));
+        assert!(!is_verification_record_present(
+            vec!["trieve-domain-verification=other".to_string()],
+            &domain
+        ));
+    }
+
+    #[test]
+    fn test_sso_emails_must_be_at_a_domain_of_the_organization() {
+        let organization_id = uuid::Uuid::new_v4();
+        let domain = verified_domain(organization_id, "acme.com");
+
+        assert_eq!(
+            check_sso_email_domain(Some(domain.clone()), organization_id, "jane@acme.com")
+                .unwrap()
+                .id,
+            domain.id
+        );
+
+        let other_org_domain = verified_domain(uuid::Uuid::new_v4(), "acme.com");
+        for verified_domain in [None, Some(other_org_domain)] {
+            let err = check_sso_email_domain(verified_domain, organization_id, "jane@acme.com")
+                .unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_only_members_are_linked_to_sso() {
+        let organization_id = uuid::Uuid::new_v4();
+        let user_id = uuid::Uuid::new_v4();
+
+        let member_orgs = vec![UserOrganization::from_details(
+            user_id,
+            organization_id,
+            UserRole::User,
+        )];
+        assert!(
+            check_existing_user_can_link_sso(&member_orgs, organization_id, "jane@acme.com")
+                .is_ok()
+        );
+
+        let outside_orgs = vec![UserOrganization::from_details(
+            user_id,
+            uuid::Uuid::new_v4(),
+            UserRole::Owner,
+        )];
+        for user_orgs in [outside_orgs, vec![]] {
+            let err =
+                check_existing_user_can_link_sso(&user_orgs, organization_id, "jane@acme.com")
+                    .unwrap_err();
+            assert_eq!(err.error_response().status(), 400);
+        }
+    }
+
+    #[test]
+    fn test_oidc_config_requires_issuer_client_and_secret() {
+        let organization_id = uuid::Uuid::new_v4();
+        let config = |client_secret: Option<&str>| {
+            OrganizationSsoConfig::from_details(
+                organization_id,
+                SsoProvider::Oidc,
+                Some("https://idp.acme.com".to_string()),
+                Some("trieve".to_string()),
+                client_secret.map(|client_secret| client_secret.to_string()),
+                None,
+            )
+        };
+
+        assert!(validate_sso_config(&config(Some("encrypted-secret"))).is_ok());
+        assert_eq!(
+            validate_sso_config(&config(None))
+                .unwrap_err()
+                .error_response()
+                .status(),
+            400
+        );
+    }
+}
@@ -0,0 +1,472 @@
+use crate::{
+    data::models::{DatasetSynonymSet, Pool, RedisPool},
+    errors::ServiceError,
+    handlers::chunk_handler::{ParsedQuery, ParsedQueryTypes},
+};
+use actix_web::web;
+use dashmap::DashMap;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Lowercases a term and collapses its whitespace such that multi-word terms compare equal regardless of spacing.
+pub fn normalize_synonym_term(term: &str) -> String {
+    term.split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn normalize_query_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Mapping from a normalized term to the terms a query containing it should be expanded with. Synonyms are only applied to queries, chunks are indexed as they are such that changing a synonym set does not require reindexing the dataset.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SynonymMap {
+    expansions: HashMap<String, Vec<String>>,
+}
+
+impl SynonymMap {
+    pub fn from_synonym_sets(synonym_sets: &[DatasetSynonymSet]) -> Self {
+        let mut expansions: HashMap<String, Vec<String>> = HashMap::new();
+
+        for synonym_set in synonym_sets {
+            let synonyms = synonym_set
+                .synonyms
+                .iter()
+                .flatten()
+                .map(|term| normalize_synonym_term(term))
+                .filter(|term| !term.is_empty())
+                .collect::<Vec<String>>();
+
+            let inputs = match &synonym_set.input {
+                Some(input) => vec![normalize_synonym_term(input)],
+                None => synonyms.clone(),
+            };
+
+            for input in inputs.into_iter().filter(|input| !input.is_empty()) {
+                let entry = expansions.entry(input.clone()).or_default();
+                for synonym in synonyms.iter() {
+                    if *synonym != input && !entry.contains(synonym) {
+                        entry.push(synonym.clone());
+                    }
+                }
+            }
+        }
+
+        SynonymMap { expansions }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expansions.is_empty()
+    }
+
+    /// Every word which appears in a term of the map.
+    pub fn words(&self) -> HashSet<String> {
+        self.expansions
+            .iter()
+            .flat_map(|(input, synonyms)| std::iter::once(input).chain(synonyms.iter()))
+            .flat_map(|term| term.split(' ').map(|word| word.to_string()))
+            .collect()
+    }
+
+    /// Appends the synonyms of every term found in the query. Multi-word terms are matched against consecutive words of the query and synonyms already present in the query are not repeated.
+    pub fn expand_query(&self, query: &str) -> String {
+        if self.is_empty() {
+            return query.to_string();
+        }
+
+        let max_term_words = self
+            .expansions
+            .keys()
+            .map(|term| term.split(' ').count())
+            .max()
+            .unwrap_or(1);
+
+        let query_words = query
+            .split_whitespace()
+            .map(normalize_query_word)
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<String>>();
+
+        let mut present_terms = HashSet::new();
+        for start in 0..query_words.len() {
+            for end in (start + 1)..=(start + max_term_words).min(query_words.len()) {
+                present_terms.insert(query_words[start..end].join(" "));
+            }
+        }
+
+        let mut added_terms = vec![];
+        for start in 0..query_words.len() {
+            for end in (start + 1)..=(start + max_term_words).min(query_words.len()) {
+                let Some(synonyms) = self.expansions.get(&query_words[start..end].join(" ")) else {
+                    continue;
+                };
+
+                for synonym in synonyms {
+                    if !present_terms.contains(synonym) && !added_terms.contains(synonym) {
+                        added_terms.push(synonym.clone());
+                    }
+                }
+            }
+        }
+
+        if added_terms.is_empty() {
+            query.to_string()
+        } else {
+            format!("{} {}", query, added_terms.join(" "))
+        }
+    }
+
+    pub fn expand_parsed_query(&self, parsed_query: ParsedQuery) -> ParsedQuery {
+        ParsedQuery {
+            query: self.expand_query(&parsed_query.query),
+            ..parsed_query
+        }
+    }
+
+    pub fn expand_parsed_query_types(&self, parsed_query: ParsedQueryTypes) -> ParsedQueryTypes {
+        match parsed_query {
+            ParsedQueryTypes::Single(query) => {
+                ParsedQueryTypes::Single(self.expand_parsed_query(query))
+            }
+            ParsedQueryTypes::Multi(queries) => ParsedQueryTypes::Multi(
+                queries
+                    .into_iter()
+                    .map(|(query, weight)| (self.expand_parsed_query(query), weight))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn get_synonym_map_key(dataset_id: uuid::Uuid) -> String {
+    format!("synonyms_{}", dataset_id)
+}
+
+const SYNONYM_MAP_CACHE_TTL: Duration = Duration::from_secs(30);
+
+static SYNONYM_MAP_CACHE: Lazy<DashMap<uuid::Uuid, (Instant, Arc<SynonymMap>)>> =
+    Lazy::new(DashMap::new);
+
+/// Reads the synonym map of a dataset from Redis. The map is written through on every change to the dataset's synonym sets, so a missing key means the dataset has no synonyms. Searches read it from an in-process cache such that they do not cost a Redis round trip each. Errors are logged and treated as no synonyms such that search keeps working.
+#[tracing::instrument(skip(redis_pool))]
+pub async fn get_synonym_map(
+    dataset_id: uuid::Uuid,
+    redis_pool: web::Data<RedisPool>,
+) -> Arc<SynonymMap> {
+    if let Some(entry) = SYNONYM_MAP_CACHE.get(&dataset_id) {
+        let (cached_at, synonym_map) = entry.value();
+        if cached_at.elapsed() < SYNONYM_MAP_CACHE_TTL {
+            return Arc::clone(synonym_map);
+        }
+    }
+
+    let mut redis_conn = match redis_pool.get().await {
+        Ok(redis_conn) => redis_conn,
+        Err(err) => {
+            log::error!("Failed to get redis connection for synonyms {:?}", err);
+            return Arc::new(SynonymMap::default());
+        }
+    };
+
+    let serialized_synonym_map: Option<String> = match redis::cmd("GET")
+        .arg(get_synonym_map_key(dataset_id))
+        .query_async(&mut *redis_conn)
+        .await
+    {
+        Ok(serialized_synonym_map) => serialized_synonym_map,
+        Err(err) => {
+            log::error!("Failed to get synonyms from redis {:?}", err);
+            return Arc::new(SynonymMap::default());
+        }
+    };
+
+    let synonym_map = Arc::new(
+        serialized_synonym_map
+            .and_then(|serialized_synonym_map| {
+                serde_json::from_str(&serialized_synonym_map)
+                    .map_err(|err| log::error!("Failed to deserialize synonyms {:?}", err))
+                    .ok()
+            })
+            .unwrap_or_default(),
+    );
+
+    SYNONYM_MAP_CACHE.insert(dataset_id, (Instant::now(), Arc::clone(&synonym_map)));
+
+    synonym_map
+}
+
+/// Rebuilds the synonym map of a dataset from Postgres and stores it in Redis. Other server processes pick up the change once their cached map expires.
+#[tracing::instrument(skip(pool, redis_pool))]
+pub async fn refresh_synonym_map(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+    redis_pool: web::Data<RedisPool>,
+) -> Result<(), ServiceError> {
+    SYNONYM_MAP_CACHE.remove(&dataset_id);
+
+    let synonym_sets = get_all_synonym_sets_query(dataset_id, pool).await?;
+    let synonym_map = SynonymMap::from_synonym_sets(&synonym_sets);
+
+    let mut redis_conn = redis_pool
+        .get()
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    if synonym_map.is_empty() {
+        redis::cmd("DEL")
+            .arg(get_synonym_map_key(dataset_id))
+            .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *redis_conn)
+            .await
+            .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+        return Ok(());
+    }
+
+    let serialized_synonym_map = serde_json::to_string(&synonym_map).map_err(|_| {
+        ServiceError::InternalServerError("Failed to serialize synonyms".to_string())
+    })?;
+
+    redis::cmd("SET")
+        .arg(get_synonym_map_key(dataset_id))
+        .arg(serialized_synonym_map)
+        .query_async::<redis::aio::MultiplexedConnection, ()>(&mut *redis_conn)
+        .await
+        .map_err(|err| ServiceError::BadRequest(err.to_string()))?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn create_synonym_set_query(
+    synonym_set: DatasetSynonymSet,
+    pool: web::Data<Pool>,
+) -> Result<DatasetSynonymSet, ServiceError> {
+    use crate::data::schema::dataset_synonyms::dsl as dataset_synonyms_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::insert_into(dataset_synonyms_columns::dataset_synonyms)
+        .values(&synonym_set)
+        .get_result::<DatasetSynonymSet>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to create synonym set {:?}", err);
+            ServiceError::BadRequest("Failed to create synonym set".to_string())
+        })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_all_synonym_sets_query(
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<Vec<DatasetSynonymSet>, ServiceError> {
+    use crate::data::schema::dataset_synonyms::dsl as dataset_synonyms_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    dataset_synonyms_columns::dataset_synonyms
+        .filter(dataset_synonyms_columns::dataset_id.eq(dataset_id))
+        .order_by(dataset_synonyms_columns::created_at)
+        .select(DatasetSynonymSet::as_select())
+        .load::<DatasetSynonymSet>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get synonym sets {:?}", err);
+            ServiceError::BadRequest("Failed to get synonym sets".to_string())
+        })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn get_synonym_sets_query(
+    dataset_id: uuid::Uuid,
+    page: u64,
+    page_size: u64,
+    pool: web::Data<Pool>,
+) -> Result<(Vec<DatasetSynonymSet>, i64), ServiceError> {
+    use crate::data::schema::dataset_synonyms::dsl as dataset_synonyms_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let synonym_sets = dataset_synonyms_columns::dataset_synonyms
+        .filter(dataset_synonyms_columns::dataset_id.eq(dataset_id))
+        .order_by(dataset_synonyms_columns::created_at.desc())
+        .offset(((page.max(1) - 1) * page_size) as i64)
+        .limit(page_size as i64)
+        .select(DatasetSynonymSet::as_select())
+        .load::<DatasetSynonymSet>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to get synonym sets {:?}", err);
+            ServiceError::BadRequest("Failed to get synonym sets".to_string())
+        })?;
+
+    let total_count = dataset_synonyms_columns::dataset_synonyms
+        .filter(dataset_synonyms_columns::dataset_id.eq(dataset_id))
+        .count()
+        .get_result::<i64>(&mut conn)
+        .await
+        .map_err(|err| {
+            log::error!("Failed to count synonym sets {:?}", err);
+            ServiceError::BadRequest("Failed to count synonym sets".to_string())
+        })?;
+
+    Ok((synonym_sets, total_count))
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn update_synonym_set_query(
+    synonym_set_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    synonyms: Vec<String>,
+    input: Option<String>,
+    pool: web::Data<Pool>,
+) -> Result<DatasetSynonymSet, ServiceError> {
+    use crate::data::schema::dataset_synonyms::dsl as dataset_synonyms_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::update(
+        dataset_synonyms_columns::dataset_synonyms
+            .filter(dataset_synonyms_columns::id.eq(synonym_set_id))
+            .filter(dataset_synonyms_columns::dataset_id.eq(dataset_id)),
+    )
+    .set((
+        dataset_synonyms_columns::synonyms.eq(synonyms
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<Option<String>>>()),
+        dataset_synonyms_columns::input.eq(input),
+        dataset_synonyms_columns::updated_at.eq(chrono::Utc::now().naive_local()),
+    ))
+    .get_result::<DatasetSynonymSet>(&mut conn)
+    .await
+    .map_err(|err| match err {
+        diesel::result::Error::NotFound => {
+            ServiceError::NotFound("Synonym set not found".to_string())
+        }
+        err => {
+            log::error!("Failed to update synonym set {:?}", err);
+            ServiceError::BadRequest("Failed to update synonym set".to_string())
+        }
+    })
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn delete_synonym_set_query(
+    synonym_set_id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::dataset_synonyms::dsl as dataset_synonyms_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let deleted_count = diesel::delete(
+        dataset_synonyms_columns::dataset_synonyms
+            .filter(dataset_synonyms_columns::id.eq(synonym_set_id))
+            .filter(dataset_synonyms_columns::dataset_id.eq(dataset_id)),
+    )
+    .execute(&mut conn)
+    .await
+    .map_err(|err| {
+        log::error!("Failed to delete synonym set {:?}", err);
+        ServiceError::BadRequest("Failed to delete synonym set".to_string())
+    })?;
+
+    if deleted_count == 0 {
+        return Err(ServiceError::NotFound("Synonym set not found".to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn synonym_map(synonym_sets: Vec<(Vec<&str>, Option<&str>)>) -> SynonymMap {
+        SynonymMap::from_synonym_sets(
+            &synonym_sets
+                .into_iter()
+                .map(|(synonyms, input)| {
+                    DatasetSynonymSet::from_details(
+                        uuid::Uuid::nil(),
+                        synonyms.into_iter().map(|term| term.to_string()).collect(),
+                        input.map(|input| input.to_string()),
+                    )
+                })
+                .collect::<Vec<DatasetSynonymSet>>(),
+        )
+    }
+
+    #[test]
+    fn test_equivalent_synonyms_expand_each_other() {
+        let synonym_map = synonym_map(vec![(vec!["couch", "Sofa", "settee"], None)]);
+
+        assert_eq!(
+            synonym_map.expand_query("red couch"),
+            "red couch sofa settee"
+        );
+        assert_eq!(
+            synonym_map.expand_query("Sofa, on sale"),
+            "Sofa, on sale couch settee"
+        );
+        assert_eq!(synonym_map.expand_query("red chair"), "red chair");
+    }
+
+    #[test]
+    fn test_one_way_synonyms_only_expand_the_input() {
+        let synonym_map = synonym_map(vec![(vec!["iphone", "android"], Some("Phone"))]);
+
+        assert_eq!(
+            synonym_map.expand_query("cheap phone"),
+            "cheap phone iphone android"
+        );
+        assert_eq!(synonym_map.expand_query("cheap iphone"), "cheap iphone");
+    }
+
+    #[test]
+    fn test_multi_word_terms_are_matched_and_not_repeated() {
+        let synonym_map = synonym_map(vec![(vec!["new  york city", "nyc", "big apple"], None)]);
+
+        assert_eq!(
+            synonym_map.expand_query("hotels in New York City"),
+            "hotels in New York City nyc big apple"
+        );
+        assert_eq!(
+            synonym_map.expand_query("nyc big apple"),
+            "nyc big apple new york city"
+        );
+    }
+
+    #[test]
+    fn test_words_lists_every_word_of_every_term() {
+        let synonym_map = synonym_map(vec![(vec!["nyc", "New York"], None)]);
+
+        assert_eq!(
+            synonym_map.words(),
+            HashSet::from(["nyc".to_string(), "new".to_string(), "york".to_string()])
+        );
+        assert!(SynonymMap::default().words().is_empty());
+    }
+}
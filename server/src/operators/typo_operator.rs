@@ -10,6 +10,7 @@ use crate::{
     data::models::{RedisPool, TypoOptions, TypoRange},
     errors::ServiceError,
     handlers::chunk_handler::ParsedQuery,
+    operators::synonym_operator::get_synonym_map,
 };
 use actix_web::web;
 use dashmap::DashMap;
@@ -584,6 +585,7 @@ fn correct_query_helper(
     tree: &BkTree,
     mut query: ParsedQuery,
     options: &TypoOptions,
+    synonym_words: &HashSet<String>,
 ) -> CorrectedQuery {
     let query_words: Vec<&str> = query.query.split_whitespace().collect();
 
@@ -617,6 +619,11 @@ fn correct_query_helper(
             continue;
         }
 
+        // Known aliases are intentional, correcting them would undo the synonym expansion
+        if synonym_words.contains(&word.to_lowercase()) {
+            continue;
+        }
+
         if word.contains(|c: char| !c.is_alphabetic()) {
             continue;
         }
@@ -778,9 +785,13 @@ pub async fn correct_query(
         return Ok(CorrectedQuery::default());
     }
 
+    let synonym_words = get_synonym_map(dataset_id, redis_pool.clone())
+        .await
+        .words();
+
     match BKTREE_CACHE.get_if_valid(&dataset_id) {
         Some(tree) => {
-            let result = correct_query_helper(&tree, query, options);
+            let result = correct_query_helper(&tree, query, options, &synonym_words);
             Ok(result)
         }
         None => {
@@ -817,7 +828,7 @@ pub async fn correct_query(
 
             match tree {
                 Some(tree) => {
-                    let result = correct_query_helper(&tree, query, options);
+                    let result = correct_query_helper(&tree, query, options, &synonym_words);
                     Ok(result)
                 }
                 None => Ok(CorrectedQuery::default()),
@@ -825,3 +836,44 @@ pub async fn correct_query(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn correct(query: &str, synonym_words: &HashSet<String>) -> String {
+        let mut tree = BkTree::new();
+        tree.insert_all(vec![("qdrant".to_string(), 10)]);
+
+        correct_query_helper(
+            &tree,
+            ParsedQuery {
+                query: query.to_string(),
+                quote_words: None,
+                negated_words: None,
+                vectors: None,
+            },
+            &TypoOptions {
+                correct_typos: Some(true),
+                prioritize_domain_specifc_words: Some(false),
+                ..Default::default()
+            },
+            synonym_words,
+        )
+        .query
+        .map(|query| query.query)
+        .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_typos_are_corrected_to_dataset_words() {
+        assert_eq!(correct("qdrnt", &HashSet::new()), "qdrant");
+    }
+
+    #[test]
+    fn test_synonym_words_are_not_corrected() {
+        let synonym_words = HashSet::from(["qdrnt".to_string()]);
+
+        assert_eq!(correct("Qdrnt", &synonym_words), "Qdrnt");
+    }
+}
@@ -5,6 +5,7 @@ use crate::data::models::{
 use crate::{
     data::models::{Pool, User},
     errors::ServiceError,
+    handlers::user_handler::SetUserApiKeyRequest,
 };
 use actix_web::{web, HttpRequest};
 use argon2::Config;
@@ -14,6 +15,7 @@ use once_cell::sync::Lazy;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use redis::AsyncCommands;
+use std::net::IpAddr;
 
 #[tracing::instrument(skip(pool))]
 pub async fn get_user_by_id_query(
@@ -122,6 +124,31 @@ pub async fn get_user_by_oidc_subject_query(
     Ok((user, user_orgs, orgs))
 }
 
+#[tracing::instrument(skip(pool))]
+pub async fn get_user_by_email_query(
+    email: &str,
+    pool: web::Data<Pool>,
+) -> Result<Option<User>, ServiceError> {
+    use crate::data::schema::users::dsl as users_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let user = users_columns::users
+        .filter(users_columns::email.eq(email))
+        .select(User::as_select())
+        .first::<User>(&mut conn)
+        .await
+        .optional()
+        .map_err(|e| {
+            log::error!("Error loading user by email: {:?}", e);
+            ServiceError::BadRequest("Error loading user by email".to_string())
+        })?;
+
+    Ok(user)
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn add_existing_user_to_org(
     email: String,
@@ -234,16 +261,86 @@ pub fn hash_function(password: &str) -> String {
     blake3::hash(password.as_bytes()).to_string()
 }
 
+/// Start of an api key which is stored such that keys can be told apart without storing them
+pub fn get_api_key_prefix(api_key: &str) -> String {
+    api_key.chars().take(11).collect()
+}
+
+/// Network address and prefix length of a CIDR block like `10.0.0.0/8`. A plain address is treated as a block of one address.
+pub fn parse_cidr(cidr: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix_length) = match cidr.trim().split_once('/') {
+        Some((address, prefix_length)) => (address, Some(prefix_length)),
+        None => (cidr.trim(), None),
+    };
+    let address = address.parse::<IpAddr>().ok()?;
+    let max_prefix_length = if address.is_ipv4() { 32 } else { 128 };
+    let prefix_length = match prefix_length {
+        Some(prefix_length) => prefix_length.parse::<u8>().ok()?,
+        None => max_prefix_length,
+    };
+
+    if prefix_length > max_prefix_length {
+        return None;
+    }
+
+    Some((address, prefix_length))
+}
+
+pub fn ip_in_cidr(ip: IpAddr, cidr: &str) -> bool {
+    let Some((network, prefix_length)) = parse_cidr(cidr) else {
+        return false;
+    };
+
+    let ip = match (ip, network) {
+        (IpAddr::V6(ip), IpAddr::V4(_)) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => return false,
+        },
+        _ => ip,
+    };
+
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_length as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - prefix_length as u32)
+                .unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn set_user_api_key_query(
     user_id: uuid::Uuid,
-    name: String,
-    role: ApiKeyRole,
-    dataset_ids: Option<Vec<uuid::Uuid>>,
-    organization_ids: Option<Vec<uuid::Uuid>>,
-    scopes: Option<Vec<String>>,
+    data: SetUserApiKeyRequest,
     pool: web::Data<Pool>,
-) -> Result<String, ServiceError> {
+) -> Result<(String, ApiKeyRespBody), ServiceError> {
+    if let Some(invalid_cidr) = data
+        .allowed_cidrs
+        .iter()
+        .flatten()
+        .find(|cidr| parse_cidr(cidr).is_none())
+    {
+        return Err(ServiceError::BadRequest(format!(
+            "Invalid CIDR block: {}",
+            invalid_cidr
+        )));
+    }
+
+    if data
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= chrono::Utc::now().naive_local())
+    {
+        return Err(ServiceError::BadRequest(
+            "expires_at must be in the future".to_string(),
+        ));
+    }
+
     let raw_api_key = generate_api_key();
     let hashed_api_key = hash_function(&raw_api_key);
 
@@ -251,15 +348,31 @@ pub async fn set_user_api_key_query(
         ServiceError::InternalServerError("Failed to get postgres connection".to_string())
     })?;
 
-    let api_key_struct = UserApiKey::from_details(
-        user_id,
-        hashed_api_key.clone(),
-        name,
-        role,
-        dataset_ids,
-        organization_ids,
-        scopes,
-    );
+    let api_key_struct = UserApiKey {
+        permissions: data.permissions.map(|permissions| {
+            permissions
+                .into_iter()
+                .map(|permission| Some(permission.to_string()))
+                .collect()
+        }),
+        expires_at: data.expires_at,
+        allowed_cidrs: data
+            .allowed_cidrs
+            .map(|cidrs| cidrs.into_iter().map(Some).collect()),
+        key_prefix: Some(get_api_key_prefix(&raw_api_key)),
+        rate_limits: data
+            .rate_limits
+            .map(|rate_limits| serde_json::json!(rate_limits)),
+        ..UserApiKey::from_details(
+            user_id,
+            hashed_api_key.clone(),
+            data.name,
+            data.role.into(),
+            data.dataset_ids,
+            data.organization_ids,
+            data.scopes,
+        )
+    };
 
     diesel::insert_into(crate::data::schema::user_api_key::dsl::user_api_key)
         .values(&api_key_struct)
@@ -267,7 +380,7 @@ pub async fn set_user_api_key_query(
         .await
         .map_err(|_| ServiceError::BadRequest("Error setting api key".to_string()))?;
 
-    Ok(raw_api_key)
+    Ok((raw_api_key, api_key_struct.into()))
 }
 
 #[tracing::instrument(skip(pool))]
@@ -281,6 +394,7 @@ pub async fn get_user_from_api_key_query(
     use crate::data::schema::users::dsl as users_columns;
 
     let api_key_hash = hash_function(api_key);
+    let now = chrono::Utc::now().naive_local();
 
     let mut conn = pool.get().await.map_err(|_e| {
         ServiceError::InternalServerError("Failed to get postgres connection".to_string())
@@ -294,7 +408,18 @@ pub async fn get_user_from_api_key_query(
                     .on(organization_columns::id.eq(user_organizations_columns::organization_id)),
             )
             .inner_join(user_api_key_columns::user_api_key)
-            .filter(user_api_key_columns::blake3_hash.eq(api_key_hash.clone()))
+            .filter(
+                user_api_key_columns::blake3_hash
+                    .eq(api_key_hash.clone())
+                    .or(user_api_key_columns::previous_blake3_hash
+                        .eq(api_key_hash.clone())
+                        .and(user_api_key_columns::previous_hash_expires_at.gt(now))),
+            )
+            .filter(
+                user_api_key_columns::expires_at
+                    .is_null()
+                    .or(user_api_key_columns::expires_at.gt(now)),
+            )
             .filter(organization_columns::deleted.eq(0))
             .select((
                 User::as_select(),
@@ -348,6 +473,11 @@ pub async fn get_user_from_api_key_query(
                     ))
                     .inner_join(user_api_key_columns::user_api_key)
                     .filter(user_api_key_columns::api_key_hash.eq(argon2_hash.clone()))
+                    .filter(
+                        user_api_key_columns::expires_at
+                            .is_null()
+                            .or(user_api_key_columns::expires_at.gt(now)),
+                    )
                     .filter(organization_columns::deleted.eq(0))
                     .select((
                         User::as_select(),
@@ -430,6 +560,92 @@ pub async fn get_user_api_keys_query(
     Ok(api_keys)
 }
 
+/// Records that the api key was used, at most once a minute such that every request does not write to postgres
+#[tracing::instrument(skip(pool))]
+pub async fn update_api_key_last_used_query(
+    api_key_id: uuid::Uuid,
+    pool: web::Data<Pool>,
+) -> Result<(), ServiceError> {
+    use crate::data::schema::user_api_key::dsl as user_api_key_columns;
+
+    let now = chrono::Utc::now().naive_local();
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    diesel::update(
+        user_api_key_columns::user_api_key
+            .filter(user_api_key_columns::id.eq(api_key_id))
+            .filter(
+                user_api_key_columns::last_used_at
+                    .is_null()
+                    .or(user_api_key_columns::last_used_at.lt(now - chrono::Duration::minutes(1))),
+            ),
+    )
+    .set(user_api_key_columns::last_used_at.eq(now))
+    .execute(&mut conn)
+    .await
+    .map_err(|_| ServiceError::BadRequest("Error updating api key last used".to_string()))?;
+
+    Ok(())
+}
+
+/// Issues a new secret for the api key. The current secret keeps working until the end of the overlap window such that it can be replaced without downtime.
+#[tracing::instrument(skip(pool))]
+pub async fn rotate_user_api_key_query(
+    user_id: uuid::Uuid,
+    api_key_id: uuid::Uuid,
+    overlap: chrono::Duration,
+    pool: web::Data<Pool>,
+) -> Result<(String, chrono::NaiveDateTime), ServiceError> {
+    use crate::data::schema::user_api_key::dsl as user_api_key_columns;
+
+    let mut conn = pool.get().await.map_err(|_e| {
+        ServiceError::InternalServerError("Failed to get postgres connection".to_string())
+    })?;
+
+    let api_key = user_api_key_columns::user_api_key
+        .filter(user_api_key_columns::user_id.eq(user_id))
+        .filter(user_api_key_columns::id.eq(api_key_id))
+        .select(UserApiKey::as_select())
+        .first::<UserApiKey>(&mut conn)
+        .await
+        .optional()
+        .map_err(|_| ServiceError::BadRequest("Error loading user api key".to_string()))?
+        .ok_or_else(|| ServiceError::NotFound(format!("Api key {} not found", api_key_id)))?;
+
+    let now = chrono::Utc::now().naive_local();
+    if api_key
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Err(ServiceError::BadRequest(
+            "Expired api keys cannot be rotated".to_string(),
+        ));
+    }
+
+    let raw_api_key = generate_api_key();
+    let previous_key_expires_at = now + overlap;
+
+    diesel::update(
+        user_api_key_columns::user_api_key.filter(user_api_key_columns::id.eq(api_key.id)),
+    )
+    .set((
+        user_api_key_columns::blake3_hash.eq(hash_function(&raw_api_key)),
+        user_api_key_columns::api_key_hash.eq(None::<String>),
+        user_api_key_columns::key_prefix.eq(get_api_key_prefix(&raw_api_key)),
+        user_api_key_columns::previous_blake3_hash.eq(api_key.blake3_hash),
+        user_api_key_columns::previous_hash_expires_at.eq(previous_key_expires_at),
+        user_api_key_columns::updated_at.eq(now),
+    ))
+    .execute(&mut conn)
+    .await
+    .map_err(|_| ServiceError::BadRequest("Error rotating api key".to_string()))?;
+
+    Ok((raw_api_key, previous_key_expires_at))
+}
+
 #[tracing::instrument(skip(pool))]
 pub async fn delete_user_api_keys_query(
     user_id: uuid::Uuid,
@@ -35,6 +35,7 @@ pub async fn delete_content<T: Into<ChunkReqPayload>>(
         full_dataset,
         pool,
         dataset_config,
+        None,
     )
     .await?;
 